@@ -1,3 +1,57 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// The frontend the app bundles; see `frontendDist` in tauri.conf.json.
+const FRONTEND: &str = "../src";
+
+/// Every file under the frontend, as a path relative to it with `/`
+/// separators, sorted so the manifest is reproducible.
+fn frontend_files(dir: &Path, root: &Path, out: &mut Vec<(String, PathBuf)>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("reading {}: {}", dir.display(), e));
+    for entry in entries {
+        let path = entry.expect("frontend entry").path();
+        if path.is_dir() {
+            frontend_files(&path, root, out);
+        } else {
+            let relative = path.strip_prefix(root).expect("under the frontend");
+            let key = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            out.push((key, path));
+        }
+    }
+}
+
+/// Writes the SHA-256 of every bundled frontend asset for
+/// `app_protocol` to check the embedded copies against at startup.
+fn asset_manifest() {
+    let root = Path::new(FRONTEND);
+    println!("cargo:rerun-if-changed={}", FRONTEND);
+    let mut files = Vec::new();
+    frontend_files(root, root, &mut files);
+    files.sort();
+    let mut manifest = String::from("pub const ASSETS: &[(&str, &str)] = &[\n");
+    for (key, path) in files {
+        let bytes = fs::read(&path).unwrap_or_else(|e| panic!("reading {}: {}", path.display(), e));
+        let digest = Sha256::digest(&bytes)
+            .iter()
+            .fold(String::new(), |mut hex, b| {
+                let _ = write!(hex, "{:02x}", b);
+                hex
+            });
+        let _ = writeln!(manifest, "    ({:?}, {:?}),", key, digest);
+    }
+    manifest.push_str("];\n");
+    let out = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR is set"));
+    fs::write(out.join("asset_manifest.rs"), manifest).expect("writing the asset manifest");
+}
+
 fn main() {
+    asset_manifest();
     tauri_build::build()
 }
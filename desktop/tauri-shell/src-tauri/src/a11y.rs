@@ -0,0 +1,296 @@
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::formatting::Formatter;
+use crate::plan_pdf::{as_text, first_text};
+use crate::settings::SettingsStore;
+
+/// Steps read out one by one; longer plans are summarized past this.
+const MAX_SPOKEN_STEPS: usize = 100;
+const MAX_SPOKEN_TARGETS: usize = 20;
+
+/// A value as words: lists joined with commas, objects as "key value"
+/// pairs, with underscores spoken as spaces. Never JSON.
+fn spoken(value: &Value) -> String {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(spoken)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| format!("{} {}", k.replace('_', " "), spoken(v)))
+            .collect::<Vec<_>>()
+            .join("; "),
+        Value::Bool(true) => "yes".to_string(),
+        Value::Bool(false) => "no".to_string(),
+        other => as_text(other),
+    }
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn end_sentence(text: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() || text.ends_with(['.', '!', '?']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+/// The plan's steps, from `steps` or the proposed `actions`.
+pub fn steps(plan: &Value) -> Vec<&Value> {
+    ["steps", "actions"]
+        .iter()
+        .find_map(|k| plan[*k].as_array().filter(|a| !a.is_empty()))
+        .map(|a| a.iter().collect())
+        .unwrap_or_default()
+}
+
+pub fn step_target(step: &Value) -> String {
+    first_text(step, &["target", "path", "selector", "command", "url"])
+}
+
+fn step_sentence(index: usize, total: usize, step: &Value) -> String {
+    if let Value::String(text) = step {
+        return format!("Step {} of {}: {}", index + 1, total, end_sentence(text));
+    }
+    let kind = first_text(step, &["type", "action", "kind", "name"]).replace('_', " ");
+    let target = step_target(step);
+    let value = spoken(
+        ["value", "text", "args"]
+            .iter()
+            .map(|k| &step[*k])
+            .find(|v| !v.is_null())
+            .unwrap_or(&Value::Null),
+    );
+    let description = first_text(step, &["description", "summary", "title"]);
+    let mut sentence = format!("Step {} of {}: ", index + 1, total);
+    sentence.push_str(if kind.is_empty() { "action" } else { &kind });
+    if !target.is_empty() {
+        sentence.push_str(&format!(" on {}", target));
+    }
+    if !value.is_empty() {
+        sentence.push_str(&format!(", with {}", value));
+    }
+    let mut sentence = end_sentence(&sentence);
+    if !description.is_empty() {
+        sentence.push(' ');
+        sentence.push_str(&end_sentence(&description));
+    }
+    let status = as_text(&step["status"]);
+    if !status.is_empty() {
+        sentence.push_str(&format!(" Status: {}.", status.replace('_', " ")));
+    }
+    sentence
+}
+
+/// Files, added lines and removed lines in a unified diff.
+fn diff_stats(diff: &str) -> (usize, usize, usize) {
+    let (mut files, mut headers, mut added, mut removed) = (0, 0, 0, 0);
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            files += 1;
+        } else if line.starts_with("+++ ") {
+            headers += 1;
+        } else if line.starts_with('+') {
+            added += 1;
+        } else if line.starts_with('-') && !line.starts_with("--- ") {
+            removed += 1;
+        }
+    }
+    (if files > 0 { files } else { headers }, added, removed)
+}
+
+fn risk_lines(plan: &Value) -> Vec<String> {
+    let report = ["risk_report", "risk"]
+        .iter()
+        .map(|k| &plan[*k])
+        .find(|v| !v.is_null());
+    let level = first_text(plan, &["risk_level"]);
+    let mut lines = Vec::new();
+    match report {
+        Some(Value::Object(report)) => {
+            for (key, value) in report {
+                let said = spoken(value);
+                if !said.is_empty() {
+                    lines.push(end_sentence(&format!(
+                        "{}: {}",
+                        key.replace('_', " "),
+                        said
+                    )));
+                }
+            }
+        }
+        Some(other) => lines.push(end_sentence(&format!("Risk: {}", spoken(other)))),
+        None => {}
+    }
+    if !level.is_empty() {
+        lines.insert(0, end_sentence(&format!("Risk level: {}", level)));
+    }
+    if lines.is_empty() {
+        lines.push("The core reported no risk assessment.".to_string());
+    }
+    lines
+}
+
+/// The plan as plain sentences under short headings, in reading order.
+fn narrate(
+    plan_id: &str,
+    plan: &Value,
+    formatter: &Formatter,
+) -> (Vec<(&'static str, Vec<String>)>, Value) {
+    let mut sections = Vec::new();
+    let objective = first_text(plan, &["objective", "title"]);
+    let mut overview = vec![end_sentence(&format!(
+        "Plan {}{}",
+        plan_id,
+        if objective.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", objective)
+        }
+    ))];
+    for (label, keys) in [
+        ("Status", &["status"][..]),
+        ("Strategy", &["strategy"][..]),
+        ("Requested by", &["requested_by", "agent", "agent_id"][..]),
+        ("Created", &["created_at", "created"][..]),
+    ] {
+        let mut value = first_text(plan, keys);
+        if label == "Created" {
+            value = formatter.when(&value);
+        }
+        if !value.is_empty() {
+            overview.push(end_sentence(&format!(
+                "{}: {}",
+                label,
+                value.replace('_', " ")
+            )));
+        }
+    }
+    let summary = first_text(plan, &["summary", "description", "rationale"]);
+    if !summary.is_empty() {
+        overview.push(end_sentence(&summary));
+    }
+    sections.push(("Overview", overview));
+
+    let steps = steps(plan);
+    let mut step_lines = vec![if steps.is_empty() {
+        "The plan lists no steps.".to_string()
+    } else {
+        format!("The plan has {}.", count(steps.len(), "step", "steps"))
+    }];
+    for (index, step) in steps.iter().take(MAX_SPOKEN_STEPS).enumerate() {
+        step_lines.push(step_sentence(index, steps.len(), step));
+    }
+    if steps.len() > MAX_SPOKEN_STEPS {
+        step_lines.push(format!(
+            "{} not read out.",
+            count(
+                steps.len() - MAX_SPOKEN_STEPS,
+                "more step is",
+                "more steps are"
+            )
+        ));
+    }
+    sections.push(("Steps", step_lines));
+
+    let mut targets: Vec<String> = Vec::new();
+    for target in steps.iter().map(|s| step_target(s)) {
+        if !target.is_empty() && !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    let target_lines = match targets.len() {
+        0 => vec!["No targets are named.".to_string()],
+        n => {
+            let mut line = format!(
+                "The plan touches {}: {}",
+                count(n, "target", "targets"),
+                targets
+                    .iter()
+                    .take(MAX_SPOKEN_TARGETS)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            if n > MAX_SPOKEN_TARGETS {
+                line.push_str(&format!(", and {} more", n - MAX_SPOKEN_TARGETS));
+            }
+            vec![end_sentence(&line)]
+        }
+    };
+    sections.push(("Targets", target_lines));
+
+    sections.push(("Risk", risk_lines(plan)));
+
+    let diff = plan["diff"].as_str().filter(|d| !d.trim().is_empty());
+    let (files, added, removed) = diff.map(diff_stats).unwrap_or_default();
+    let change_lines = match diff {
+        Some(_) => vec![format!(
+            "The changes add {} and remove {} across {}.",
+            count(added, "line", "lines"),
+            count(removed, "line", "lines"),
+            count(files.max(1), "file", "files")
+        )],
+        None => vec!["The core sent no diff for this plan.".to_string()],
+    };
+    sections.push(("Changes", change_lines));
+
+    let stats = json!({
+        "steps": steps.len(),
+        "targets": targets.len(),
+        "diff": diff.map(|_| json!({ "files": files.max(1), "added": added, "removed": removed })),
+    });
+    (sections, stats)
+}
+
+/// A plan as a linear narrative for screen readers: an overview, each step
+/// in order, what it touches, its risk and the size of its changes, in
+/// plain sentences instead of nested JSON. `text` is the whole narrative
+/// with each section introduced by its heading; `sections` has the same
+/// content split for navigation.
+#[tauri::command]
+pub async fn summarize_plan_for_a11y(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token,
+        None,
+    )
+    .await?;
+    let formatter = Formatter::for_profile(&store.snapshot(), Some(&conn.profile_id));
+    let (sections, stats) = narrate(&plan_id, &plan, &formatter);
+    let text = sections
+        .iter()
+        .map(|(heading, lines)| format!("{}.\n{}", heading, lines.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(json!({
+        "plan_id": plan_id,
+        "text": text,
+        "sections": sections
+            .iter()
+            .map(|(heading, lines)| json!({ "heading": heading, "lines": lines }))
+            .collect::<Vec<_>>(),
+        "stats": stats,
+    }))
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::stall::{self, StallTracker};
+use crate::{crypto, environment, sync};
+
+const CONFIRM_WINDOW: Duration = Duration::from_secs(60);
+const ABORTED_EVENT: &str = "plan:aborted";
+const MAX_REASON: usize = 2_000;
+
+/// Confirmation codes issued by the first `abort_execution` call, by
+/// profile and plan id.
+#[derive(Default)]
+pub struct PendingAborts {
+    codes: Mutex<HashMap<(String, String), (String, Instant)>>,
+}
+
+impl PendingAborts {
+    fn issue(&self, profile_id: &str, plan_id: &str) -> Result<String, String> {
+        let code: String = crypto::random_bytes::<3>()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        self.codes
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Abort state poisoned"))?
+            .insert(
+                (profile_id.to_string(), plan_id.to_string()),
+                (code.clone(), Instant::now()),
+            );
+        Ok(code)
+    }
+
+    fn redeem(&self, profile_id: &str, plan_id: &str, code: &str) -> bool {
+        let Ok(mut codes) = self.codes.lock() else {
+            return false;
+        };
+        codes
+            .remove(&(profile_id.to_string(), plan_id.to_string()))
+            .is_some_and(|(expected, at)| {
+                expected.eq_ignore_ascii_case(code.trim()) && at.elapsed() <= CONFIRM_WINDOW
+            })
+    }
+}
+
+/// Stops a running execution in two calls. The first, without
+/// `confirmation`, sends nothing: it returns the plan as the core reports
+/// it now with a one-time code valid for 60s. The second, with that code,
+/// asks the core to abort, then marks the plan `aborting` in the local
+/// cache and emits `plan:aborted` without waiting for the next sync.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn abort_execution(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    pending: State<'_, PendingAborts>,
+    tracker: State<'_, StallTracker>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    reason: String,
+    confirmation: Option<String>,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("A reason is required to abort an execution"));
+    }
+    if reason.len() > MAX_REASON {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("Reason must be at most {} bytes", MAX_REASON))
+        );
+    }
+    let conn = store.connection(profile.as_deref())?;
+
+    let Some(code) = confirmation.filter(|c| !c.trim().is_empty()) else {
+        let plan = crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/plans/{}", plan_id),
+            conn.token.clone(),
+            None,
+        )
+        .await?;
+        return Ok(json!({
+            "plan_id": plan_id,
+            "confirm_required": true,
+            "status": plan["status"],
+            "objective": plan["objective"],
+            "progress_completed": plan["progress_completed"],
+            "progress_total": plan["progress_total"],
+            "confirmation": pending.issue(&conn.profile_id, &plan_id)?,
+            "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+        }));
+    };
+    if !pending.redeem(&conn.profile_id, &plan_id, &code) {
+        return Err(ErrorCode::InvalidInput.with("Abort confirmation is invalid or has expired"));
+    }
+
+    let result = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/abort", plan_id),
+        conn.token.clone(),
+        Some(json!({ "reason": reason })),
+    )
+    .await?;
+    stall::forget(&tracker, &conn.profile_id, &plan_id);
+    let status = result["status"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .unwrap_or("aborting")
+        .to_string();
+    let cached = {
+        let guard = db.lock()?;
+        let _ = db::record_audit(
+            &guard,
+            &conn.profile_id,
+            "execution",
+            "aborted",
+            &plan_id,
+            &json!({ "reason": reason, "status": status }),
+        );
+        sync::set_plan_status(&guard, &conn.profile_id, &plan_id, &status).unwrap_or(false)
+    };
+    let event = json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "status": status,
+        "reason": reason,
+    });
+    let _ = environment::emit(&app, ABORTED_EVENT, &event);
+    if cached {
+        let _ = environment::emit(
+            &app,
+            sync::CHANGED_EVENT,
+            json!({ "profile_id": conn.profile_id, "updated": 1, "removed": 0 }),
+        );
+    }
+    Ok(json!({ "plan_id": plan_id, "aborted": true, "status": status, "result": result }))
+}
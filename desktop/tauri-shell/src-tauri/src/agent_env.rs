@@ -0,0 +1,163 @@
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+
+const MASK: &str = "********";
+const MAX_NAME_LEN: usize = 128;
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+/// Entry fields that could carry a secret's plaintext.
+const VALUE_FIELDS: &[&str] = &["value", "secret", "plaintext"];
+
+fn validate_name(raw: &str) -> Result<String, String> {
+    let name = raw.trim().to_string();
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Variable names are letters, digits and `_`, not starting with a digit: {:?}",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+/// Keeps names and metadata; secret values are replaced by the mask even if
+/// the core sent them.
+fn redact_entry(entry: &Value) -> Value {
+    let Some(fields) = entry.as_object() else {
+        return Value::Null;
+    };
+    let secret = fields
+        .get("secret")
+        .and_then(Value::as_bool)
+        .or_else(|| fields.get("is_secret").and_then(Value::as_bool))
+        .unwrap_or(true);
+    let mut out = Map::new();
+    for (key, value) in fields {
+        if secret && VALUE_FIELDS.contains(&key.as_str()) && !value.is_boolean() {
+            out.insert(key.clone(), json!(MASK));
+        } else {
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    out.insert("secret".to_string(), json!(secret));
+    Value::Object(out)
+}
+
+/// A core error that quotes the submitted value is masked before it leaves
+/// the backend.
+fn scrub(err: String, value: &str) -> String {
+    if value.is_empty() {
+        err
+    } else {
+        err.replace(value, MASK)
+    }
+}
+
+/// Lists the agent environment: plain variables with their values, secrets
+/// by name only.
+#[tauri::command]
+pub async fn list_env(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let body = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/agents/env",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let entries: Vec<Value> = body["variables"]
+        .as_array()
+        .or_else(|| body.as_array())
+        .into_iter()
+        .flatten()
+        .map(redact_entry)
+        .filter(|e| !e.is_null())
+        .collect();
+    Ok(json!({ "profile_id": conn.profile_id, "variables": entries }))
+}
+
+/// Creates or replaces a secret. The value goes to the core and nowhere
+/// else: it is not returned, and the local audit entry only records that it
+/// was set.
+#[tauri::command]
+pub async fn set_secret(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    name: String,
+    value: String,
+) -> Result<Value, String> {
+    let name = validate_name(&name)?;
+    if value.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Secret value is required"));
+    }
+    if value.len() > MAX_VALUE_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Secret value is longer than {} bytes",
+            MAX_VALUE_BYTES
+        )));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let result = crate::request_json(
+        Method::PUT,
+        &conn.base_url,
+        &format!("/agents/secrets/{}", name),
+        conn.token.clone(),
+        Some(json!({ "value": value })),
+    )
+    .await
+    .map_err(|e| scrub(e, &value))?;
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "secrets",
+        "set",
+        &name,
+        &json!({ "name": name, "value": MASK }),
+    );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "name": name,
+        "set": true,
+        "updated_at": result["updated_at"],
+    }))
+}
+
+#[tauri::command]
+pub async fn delete_secret(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    name: String,
+) -> Result<Value, String> {
+    let name = validate_name(&name)?;
+    let conn = store.connection(profile.as_deref())?;
+    crate::request_json(
+        Method::DELETE,
+        &conn.base_url,
+        &format!("/agents/secrets/{}", name),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "secrets",
+        "delete",
+        &name,
+        &json!({ "name": name }),
+    );
+    Ok(json!({ "profile_id": conn.profile_id, "name": name, "deleted": true }))
+}
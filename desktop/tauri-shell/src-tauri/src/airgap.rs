@@ -0,0 +1,1060 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use chrono::Utc;
+use ed25519_dalek::{Signature, Signer, SigningKey};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::core_signing::{self, Checked};
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{self, AirgapSettings, ReplayMode, ReplaySettings, SettingsStore};
+use crate::{audit_export, crypto, decisions, net, pagination, plugin_registry, residency};
+
+const FORMAT: &str = "novaadapt-airgap";
+const VERSION: u64 = 1;
+pub const SIGNING_KEY_SECRET: &str = "airgap:signing_key";
+const MANIFEST_FILE: &str = "manifest.json";
+const SIGNATURE_FILE: &str = "manifest.sig";
+const PAYLOAD_FILE: &str = "payload.json";
+const PLAN_BUNDLE: &str = "plan_bundle";
+const DECISION_FILE: &str = "decision_file";
+const MAX_FILE_BYTES: usize = 32 * 1024 * 1024;
+const MAX_TRUSTED_KEYS: usize = 16;
+const MAX_BUNDLE_PLANS: usize = 500;
+/// Plans read per page while exporting.
+const PAGE_SIZE: usize = 200;
+const MAX_REASON_LEN: usize = 2000;
+/// Decisions an applied file may send to the core at once.
+const MAX_PARALLEL: usize = 16;
+/// Fields of a bundled plan naming the plan it replaces; its decision waits
+/// for the replaced plan's.
+const REPLACES_FIELDS: &[&str] = &["replaces", "supersedes"];
+
+/// A file that passed `decode`: its manifest and payload, which trusted key
+/// signed it, and the manifest's digest, which identifies the file.
+struct Decoded {
+    manifest: Value,
+    payload: Value,
+    signer: String,
+    manifest_sha256: String,
+}
+
+pub fn validate(settings: &AirgapSettings) -> Result<(), String> {
+    if settings.trusted_keys.len() > MAX_TRUSTED_KEYS {
+        return Err(format!(
+            "At most {} trusted air-gap keys are allowed",
+            MAX_TRUSTED_KEYS
+        ));
+    }
+    for key in &settings.trusted_keys {
+        plugin_registry::decode_key(key)?;
+    }
+    if !(1..=MAX_PARALLEL).contains(&settings.replay.max_parallel) {
+        return Err(format!(
+            "airgap.replay.max_parallel must be between 1 and {}",
+            MAX_PARALLEL
+        ));
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn new_id(prefix: &str) -> String {
+    format!("{}-{}", prefix, hex(&crypto::random_bytes::<8>()))
+}
+
+/// Writes `payload` as a zip of a manifest carrying its SHA-256 digest, the
+/// manifest's Ed25519 signature and the payload itself.
+fn encode(
+    kind: &str,
+    id: &str,
+    payload: &Value,
+    key: &SigningKey,
+) -> Result<(Vec<u8>, String), String> {
+    let payload_bytes = serde_json::to_vec_pretty(payload).map_err(|e| e.to_string())?;
+    let manifest = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "kind": kind,
+        "id": id,
+        "created_at": Utc::now().to_rfc3339(),
+        "file": {
+            "name": PAYLOAD_FILE,
+            "sha256": hex(&Sha256::digest(&payload_bytes)),
+            "bytes": payload_bytes.len(),
+        },
+        "signature": {
+            "algorithm": "ed25519",
+            "public_key": B64.encode(key.verifying_key().to_bytes()),
+            "file": SIGNATURE_FILE,
+        },
+    });
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    let signature = B64.encode(key.sign(&manifest_bytes).to_bytes());
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let files: [(&str, &[u8]); 3] = [
+        (MANIFEST_FILE, &manifest_bytes),
+        (SIGNATURE_FILE, signature.as_bytes()),
+        (PAYLOAD_FILE, &payload_bytes),
+    ];
+    for (name, bytes) in files {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(bytes).map_err(|e| e.to_string())?;
+    }
+    let bytes = zip.finish().map_err(|e| e.to_string())?.into_inner();
+    Ok((bytes, hex(&Sha256::digest(&manifest_bytes))))
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|_| ErrorCode::InvalidInput.with(format!("File has no {}", name)))?;
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_FILE_BYTES as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Read {} failed: {}", name, e)))?;
+    if bytes.len() > MAX_FILE_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!("{} is too large", name)));
+    }
+    Ok(bytes)
+}
+
+/// Opens a file made by `encode`, refusing it unless it is of `kind`, its
+/// manifest is signed by one of `trusted_keys` and the payload matches the
+/// manifest's digest.
+fn decode(bytes: &[u8], kind: &str, trusted_keys: &[String]) -> Result<Decoded, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Not an air-gap file: {}", e)))?;
+    let manifest_bytes = read_entry(&mut archive, MANIFEST_FILE)?;
+    let signature = read_entry(&mut archive, SIGNATURE_FILE)?;
+    let payload_bytes = read_entry(&mut archive, PAYLOAD_FILE)?;
+    let signature = B64
+        .decode(String::from_utf8_lossy(&signature).trim())
+        .ok()
+        .and_then(|raw| Signature::from_slice(&raw).ok())
+        .ok_or_else(|| ErrorCode::InvalidInput.with("File signature is malformed"))?;
+    let signer = trusted_keys
+        .iter()
+        .find(|raw| {
+            plugin_registry::decode_key(raw)
+                .is_ok_and(|key| key.verify_strict(&manifest_bytes, &signature).is_ok())
+        })
+        .cloned()
+        .ok_or_else(|| ErrorCode::Forbidden.with("File is not signed by a trusted air-gap key"))?;
+    let manifest: Value = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Manifest is invalid: {}", e)))?;
+    if manifest["format"] != FORMAT || manifest["version"].as_u64() != Some(VERSION) {
+        return Err(ErrorCode::InvalidInput.with("Unsupported air-gap file format"));
+    }
+    if manifest["kind"] != kind {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Expected a {}, got a {}",
+            kind.replace('_', " "),
+            manifest["kind"]
+                .as_str()
+                .unwrap_or("unknown file")
+                .replace('_', " ")
+        )));
+    }
+    if manifest["file"]["sha256"].as_str() != Some(hex(&Sha256::digest(&payload_bytes)).as_str()) {
+        return Err(ErrorCode::InvalidInput.with("Payload does not match the signed digest"));
+    }
+    let payload = serde_json::from_slice(&payload_bytes)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Payload is invalid: {}", e)))?;
+    Ok(Decoded {
+        manifest,
+        payload,
+        signer,
+        manifest_sha256: hex(&Sha256::digest(&manifest_bytes)),
+    })
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(path.trim())
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read {} failed: {}", path.trim(), e)))?;
+    if bytes.len() > MAX_FILE_BYTES {
+        return Err(ErrorCode::InvalidInput.with("File is too large"));
+    }
+    Ok(bytes)
+}
+
+fn write_file(what: &str, path: &str, bytes: &[u8]) -> Result<(), String> {
+    let target = residency::check(what, path)?;
+    fs::write(&target, bytes)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write {} failed: {}", path.trim(), e)))
+}
+
+fn storage(e: rusqlite::Error) -> String {
+    ErrorCode::LocalStorage.with(e.to_string())
+}
+
+struct BundleRow {
+    profile_id: String,
+    origin: String,
+    signer: String,
+    manifest_sha256: String,
+    plans: Vec<Value>,
+    at: String,
+}
+
+fn bundle_row(
+    conn: &Connection,
+    bundle_id: &str,
+    direction: &str,
+) -> Result<Option<BundleRow>, String> {
+    conn.query_row(
+        "SELECT profile_id, origin, signer, manifest_sha256, plans, at FROM airgap_bundles
+         WHERE bundle_id = ?1 AND direction = ?2",
+        params![bundle_id, direction],
+        |row| {
+            Ok(BundleRow {
+                profile_id: row.get(0)?,
+                origin: row.get(1)?,
+                signer: row.get(2)?,
+                manifest_sha256: row.get(3)?,
+                plans: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+                at: row.get(5)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(storage)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_bundle(
+    conn: &Connection,
+    bundle_id: &str,
+    direction: &str,
+    profile_id: &str,
+    origin: &str,
+    signer: &str,
+    manifest_sha256: &str,
+    plans: &Value,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO airgap_bundles
+         (bundle_id, direction, profile_id, origin, signer, manifest_sha256, plans, at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            bundle_id,
+            direction,
+            profile_id,
+            origin,
+            signer,
+            manifest_sha256,
+            plans.to_string(),
+            Utc::now().to_rfc3339()
+        ],
+    )
+    .map_err(storage)?;
+    Ok(())
+}
+
+fn decisions_for(conn: &Connection, bundle_id: &str) -> Result<Vec<Value>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT plan_id, decision, reason, decided_at, after_plans FROM airgap_decisions
+             WHERE bundle_id = ?1 ORDER BY decided_at",
+        )
+        .map_err(storage)?;
+    let rows = stmt
+        .query_map(params![bundle_id], |row| {
+            Ok(json!({
+                "plan_id": row.get::<_, String>(0)?,
+                "decision": row.get::<_, String>(1)?,
+                "reason": row.get::<_, String>(2)?,
+                "decided_at": row.get::<_, String>(3)?,
+                "after": serde_json::from_str::<Value>(&row.get::<_, String>(4)?)
+                    .unwrap_or_else(|_| json!([])),
+            }))
+        })
+        .map_err(storage)?;
+    rows.collect::<rusqlite::Result<_>>().map_err(storage)
+}
+
+/// This machine's air-gap public key, for the other side's `trusted_keys`.
+#[tauri::command]
+pub fn get_airgap_key() -> Result<Value, String> {
+    let key = audit_export::signing_key(SIGNING_KEY_SECRET)?;
+    Ok(json!({ "public_key": B64.encode(key.verifying_key().to_bytes()) }))
+}
+
+/// Writes the profile's pending plans (or `plan_ids`) to a signed bundle
+/// at `path`, on removable media, for review on a machine with no network.
+/// Each plan's version is kept so a plan changed since export is refused
+/// when its decision comes back.
+#[tauri::command]
+pub async fn export_plan_bundle(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    path: String,
+    plan_ids: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    export_bundle(Some(&app), &db, &conn, &path, plan_ids).await
+}
+
+/// [`export_plan_bundle`] for a resolved connection. Every page of plans
+/// is read, as walk `airgap:<profile>`, and a walk that fails part way
+/// exports nothing rather than a bundle missing plans.
+pub async fn export_bundle(
+    app: Option<&AppHandle>,
+    db: &LocalDb,
+    conn: &settings::Connection,
+    path: &str,
+    plan_ids: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let listing = if plan_ids.is_some() {
+        "/plans"
+    } else {
+        "/plans?status=pending"
+    };
+    let plans = pagination::collect_all(
+        app,
+        &format!("airgap:{}", conn.profile_id),
+        conn,
+        listing,
+        PAGE_SIZE,
+        pagination::DEFAULT_MAX_ITEMS,
+        None,
+    )
+    .await
+    .into_result()?;
+    // Plans the core signed keep their envelope, so the reviewing side can
+    // verify them against the core rather than only against this shell.
+    let plans: Vec<Value> = plans
+        .iter()
+        .map(|p| (core_signing::unwrap(p), p))
+        .filter(|(p, _)| match &plan_ids {
+            Some(ids) => p["id"]
+                .as_str()
+                .is_some_and(|id| ids.iter().any(|i| i == id)),
+            None => p["status"].as_str() == Some("pending"),
+        })
+        .take(MAX_BUNDLE_PLANS)
+        .map(|(p, raw)| {
+            let mut entry = json!({
+                "id": p["id"],
+                "version": decisions::plan_version(&p),
+                "plan": p,
+            });
+            if raw["signed"].is_string() {
+                entry["core_signed"] = raw.clone();
+            }
+            entry
+        })
+        .collect();
+    if plans.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("No plans to export"));
+    }
+    let bundle_id = new_id("bundle");
+    let origin = net::base_origin(&conn.base_url).unwrap_or_default();
+    let plans = Value::Array(plans);
+    let key = audit_export::signing_key(SIGNING_KEY_SECRET)?;
+    let public_key = B64.encode(key.verifying_key().to_bytes());
+    let payload = json!({
+        "bundle_id": bundle_id,
+        "profile_id": conn.profile_id,
+        "core": origin,
+        "plans": plans,
+    });
+    let (bytes, manifest_sha256) = encode(PLAN_BUNDLE, &bundle_id, &payload, &key)?;
+    write_file("The plan bundle", path, &bytes)?;
+    let count = plans.as_array().map_or(0, Vec::len);
+    let summary = json!({
+        "path": path.trim(),
+        "bundle_id": bundle_id,
+        "profile_id": conn.profile_id,
+        "plans": count,
+        "public_key": public_key,
+        "manifest_sha256": manifest_sha256,
+    });
+    let local = db.lock()?;
+    insert_bundle(
+        &local,
+        &bundle_id,
+        "exported",
+        &conn.profile_id,
+        &origin,
+        &public_key,
+        &manifest_sha256,
+        &plans,
+    )?;
+    let _ = db::record_audit(
+        &local,
+        &conn.profile_id,
+        "airgap",
+        "export_bundle",
+        &bundle_id,
+        &summary,
+    );
+    Ok(summary)
+}
+
+fn review(conn: &Connection, bundle_id: &str) -> Result<Value, String> {
+    let bundle = bundle_row(conn, bundle_id, "imported")?
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("No imported bundle {}", bundle_id)))?;
+    let decided = decisions_for(conn, bundle_id)?;
+    let plans: Vec<Value> = bundle
+        .plans
+        .iter()
+        .map(|entry| {
+            let decision = decided
+                .iter()
+                .find(|d| d["plan_id"] == entry["id"])
+                .cloned()
+                .unwrap_or(Value::Null);
+            json!({
+                "id": entry["id"],
+                "version": entry["version"],
+                "plan": entry["plan"],
+                "decision": decision,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "bundle_id": bundle_id,
+        "profile_id": bundle.profile_id,
+        "core": bundle.origin,
+        "signed_by": bundle.signer,
+        "imported_at": bundle.at,
+        "plans": plans,
+        "decided": decided.len(),
+    }))
+}
+
+/// Verifies a plan bundle carried over from the connected network and keeps
+/// it for offline review. Importing the same bundle twice keeps the
+/// decisions already made on it.
+#[tauri::command]
+pub fn import_plan_bundle(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    path: String,
+) -> Result<Value, String> {
+    let trusted = store.snapshot().airgap.trusted_keys;
+    import_bundle(&store, &db, &trusted, &path)
+}
+
+/// [`import_plan_bundle`] trusting `trusted` keys.
+pub fn import_bundle(
+    store: &SettingsStore,
+    db: &LocalDb,
+    trusted: &[String],
+    path: &str,
+) -> Result<Value, String> {
+    let decoded = decode(&read_file(path)?, PLAN_BUNDLE, trusted)?;
+    let payload = &decoded.payload;
+    let bundle_id = payload["bundle_id"]
+        .as_str()
+        .filter(|id| *id == decoded.manifest["id"])
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Bundle id does not match its manifest"))?;
+    let plans = payload["plans"]
+        .as_array()
+        .filter(|plans| {
+            plans.len() <= MAX_BUNDLE_PLANS && plans.iter().all(|p| p["id"].is_string())
+        })
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Bundle plans are invalid"))?;
+    let plans = core_verified(
+        store,
+        payload["profile_id"].as_str().unwrap_or_default(),
+        plans,
+    )?;
+    let conn = db.lock()?;
+    insert_bundle(
+        &conn,
+        bundle_id,
+        "imported",
+        payload["profile_id"].as_str().unwrap_or_default(),
+        payload["core"].as_str().unwrap_or_default(),
+        &decoded.signer,
+        &decoded.manifest_sha256,
+        &Value::Array(plans.clone()),
+    )?;
+    let _ = db::record_audit(
+        &conn,
+        payload["profile_id"].as_str().unwrap_or_default(),
+        "airgap",
+        "import_bundle",
+        bundle_id,
+        &json!({ "signed_by": decoded.signer, "plans": plans.len() }),
+    );
+    review(&conn, bundle_id)
+}
+
+/// Checks the core's signature on each bundled plan that carries one,
+/// against the signing settings of the bundle's profile when it is set up
+/// here, and marks each entry `core_verified`. A plan whose signature fails,
+/// or whose signed copy differs from the plan shown for review, refuses the
+/// whole bundle.
+fn core_verified(
+    store: &SettingsStore,
+    profile_id: &str,
+    plans: &[Value],
+) -> Result<Vec<Value>, String> {
+    let settings = store
+        .snapshot()
+        .profile(profile_id)
+        .map(|p| p.core_signing.clone())
+        .unwrap_or_default();
+    plans
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            let signed = entry
+                .as_object_mut()
+                .and_then(|e| e.remove("core_signed"))
+                .unwrap_or_else(|| entry["plan"].clone());
+            let verified = match core_signing::check(profile_id, &settings, signed)
+                .map_err(|e| format!("Plan {}: {}", entry["id"], e))?
+            {
+                Checked::Verified(plan) if plan == entry["plan"] => true,
+                Checked::Verified(_) => {
+                    return Err(ErrorCode::Forbidden.with(format!(
+                        "Plan {} differs from the copy the core signed",
+                        entry["id"]
+                    )))
+                }
+                Checked::Unsigned(_) => false,
+            };
+            entry["core_verified"] = json!(verified);
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// An imported bundle's plans with the decisions made on them so far; the
+/// most recently imported bundle when `bundle_id` is omitted.
+#[tauri::command]
+pub fn get_offline_review(
+    db: State<'_, LocalDb>,
+    bundle_id: Option<String>,
+) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let bundle_id = match bundle_id {
+        Some(id) => id,
+        None => {
+            let latest: Option<String> = conn
+                .query_row(
+                    "SELECT bundle_id FROM airgap_bundles WHERE direction = 'imported'
+                     ORDER BY at DESC LIMIT 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(storage)?;
+            match latest {
+                Some(id) => id,
+                None => return Ok(Value::Null),
+            }
+        }
+    };
+    review(&conn, &bundle_id)
+}
+
+/// The plans of `replaces` and `supersedes` on a bundled plan.
+fn replaced(plan: &Value) -> Vec<String> {
+    REPLACES_FIELDS
+        .iter()
+        .flat_map(|field| match &plan[*field] {
+            Value::String(id) => vec![id.as_str()],
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        })
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/// The decided plans each decision waits on: the ones it was recorded
+/// `after`, and the plan it replaces. Plans without a decision of their
+/// own are left out, since there is nothing of theirs to wait for.
+fn dependencies(plans: &[Value], decided: &[Value]) -> HashMap<String, BTreeSet<String>> {
+    let ids: BTreeSet<&str> = decided
+        .iter()
+        .filter_map(|d| d["plan_id"].as_str())
+        .collect();
+    decided
+        .iter()
+        .filter_map(|entry| {
+            let plan_id = entry["plan_id"].as_str()?;
+            let mut after: BTreeSet<String> = entry["after"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            if let Some(bundled) = plans.iter().find(|p| p["id"] == plan_id) {
+                after.extend(replaced(&bundled["plan"]));
+            }
+            after.retain(|id| id != plan_id && ids.contains(id.as_str()));
+            Some((plan_id.to_string(), after))
+        })
+        .collect()
+}
+
+/// Whether `from` waits on `to`, directly or through other decisions.
+fn waits_on(deps: &HashMap<String, BTreeSet<String>>, from: &str, to: &str) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut next = vec![from];
+    while let Some(id) = next.pop() {
+        for dep in deps.get(id).into_iter().flatten() {
+            if dep == to {
+                return true;
+            }
+            if seen.insert(dep.as_str()) {
+                next.push(dep);
+            }
+        }
+    }
+    false
+}
+
+/// Records an offline approve/reject for a plan of an imported bundle; a
+/// later call for the same plan replaces it until the decisions are exported.
+/// `after` names plans of the bundle whose decisions must reach the core
+/// before this one; a plan that `replaces` another waits for it anyway.
+#[tauri::command]
+pub fn decide_offline(
+    db: State<'_, LocalDb>,
+    bundle_id: String,
+    plan_id: String,
+    decision: String,
+    reason: Option<String>,
+    after: Option<Vec<String>>,
+) -> Result<Value, String> {
+    record_offline_decision(
+        &db,
+        bundle_id,
+        plan_id,
+        decision,
+        reason,
+        after.unwrap_or_default(),
+    )
+}
+
+pub fn record_offline_decision(
+    db: &LocalDb,
+    bundle_id: String,
+    plan_id: String,
+    decision: String,
+    reason: Option<String>,
+    after: Vec<String>,
+) -> Result<Value, String> {
+    let decision = decision.trim().to_ascii_lowercase();
+    if decision != "approve" && decision != "reject" {
+        return Err(ErrorCode::InvalidInput.with(format!("Unsupported decision: {}", decision)));
+    }
+    let reason = reason.unwrap_or_default().trim().to_string();
+    if reason.len() > MAX_REASON_LEN {
+        return Err(ErrorCode::InvalidInput
+            .with(format!("Reason must be at most {} bytes", MAX_REASON_LEN)));
+    }
+    let conn = db.lock()?;
+    let bundle = bundle_row(&conn, &bundle_id, "imported")?
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("No imported bundle {}", bundle_id)))?;
+    if !bundle.plans.iter().any(|p| p["id"] == plan_id.as_str()) {
+        return Err(ErrorCode::NotFound.with(format!("Plan {} is not in the bundle", plan_id)));
+    }
+    let after: BTreeSet<String> = after
+        .iter()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    for id in &after {
+        if *id == plan_id {
+            return Err(ErrorCode::InvalidInput.with("A decision cannot wait on its own plan"));
+        }
+        if !bundle.plans.iter().any(|p| p["id"] == id.as_str()) {
+            return Err(ErrorCode::NotFound.with(format!("Plan {} is not in the bundle", id)));
+        }
+    }
+    let mut decided = decisions_for(&conn, &bundle_id)?;
+    decided.retain(|d| d["plan_id"] != plan_id.as_str());
+    decided.push(json!({ "plan_id": plan_id, "after": after }));
+    let deps = dependencies(&bundle.plans, &decided);
+    if let Some(looped) = deps
+        .get(&plan_id)
+        .into_iter()
+        .flatten()
+        .find(|dep| waits_on(&deps, dep, &plan_id))
+    {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Plan {} already waits on {}; the decisions would wait on each other",
+            looped, plan_id
+        )));
+    }
+    let decided_at = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT OR REPLACE INTO airgap_decisions
+            (bundle_id, plan_id, decision, reason, decided_at, after_plans)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            bundle_id,
+            plan_id,
+            decision,
+            reason,
+            decided_at,
+            json!(after).to_string()
+        ],
+    )
+    .map_err(storage)?;
+    let _ = db::record_audit(
+        &conn,
+        &bundle.profile_id,
+        "airgap",
+        "offline_decision",
+        &plan_id,
+        &json!({ "bundle_id": bundle_id, "decision": decision, "reason": reason, "after": after }),
+    );
+    Ok(json!({
+        "bundle_id": bundle_id,
+        "plan_id": plan_id,
+        "decision": decision,
+        "reason": reason,
+        "decided_at": decided_at,
+        "after": after,
+    }))
+}
+
+/// Writes the decisions made on an imported bundle to a signed decision
+/// file at `path`, to be carried back and applied on the connected network.
+#[tauri::command]
+pub fn export_decision_file(
+    db: State<'_, LocalDb>,
+    bundle_id: String,
+    path: String,
+) -> Result<Value, String> {
+    write_decision_file(&db, &bundle_id, &path)
+}
+
+pub fn write_decision_file(db: &LocalDb, bundle_id: &str, path: &str) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let bundle = bundle_row(&conn, bundle_id, "imported")?
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("No imported bundle {}", bundle_id)))?;
+    let decided = decisions_for(&conn, bundle_id)?;
+    if decided.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("No decisions have been made on this bundle"));
+    }
+    let file_id = new_id("decisions");
+    let key = audit_export::signing_key(SIGNING_KEY_SECRET)?;
+    let payload = json!({
+        "decision_file_id": file_id,
+        "bundle_id": bundle_id,
+        "bundle_sha256": bundle.manifest_sha256,
+        "decisions": decided,
+    });
+    let (bytes, manifest_sha256) = encode(DECISION_FILE, &file_id, &payload, &key)?;
+    write_file("The decision file", path, &bytes)?;
+    let summary = json!({
+        "path": path.trim(),
+        "decision_file_id": file_id,
+        "bundle_id": bundle_id,
+        "decisions": decided.len(),
+        "public_key": B64.encode(key.verifying_key().to_bytes()),
+        "manifest_sha256": manifest_sha256,
+    });
+    let _ = db::record_audit(
+        &conn,
+        &bundle.profile_id,
+        "airgap",
+        "export_decisions",
+        bundle_id,
+        &summary,
+    );
+    Ok(summary)
+}
+
+/// Verifies a decision file brought back from the offline reviewer and
+/// sends its decisions to the core. It must answer a bundle this machine
+/// exported, unchanged, and is applied once; each plan's decision carries
+/// the version it was reviewed at, so a plan modified since export fails
+/// with `PLAN_CONFLICT` instead of being decided blind. Decisions go out in
+/// the order `airgap.replay` sets, each after the ones it waits on; one
+/// waiting on a decision that failed is skipped rather than sent.
+#[tauri::command]
+pub async fn import_decision_file(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    path: String,
+) -> Result<Value, String> {
+    let airgap = store.snapshot().airgap;
+    apply_decision_file(
+        &db,
+        &airgap.trusted_keys,
+        &airgap.replay,
+        |profile| store.connection(Some(profile)),
+        &path,
+    )
+    .await
+}
+
+/// [`import_decision_file`] trusting `trusted` keys, sending the decisions
+/// as `replay` says and reaching the bundle's profile through `connection`.
+pub async fn apply_decision_file(
+    db: &LocalDb,
+    trusted: &[String],
+    replay: &ReplaySettings,
+    connection: impl Fn(&str) -> Result<settings::Connection, String>,
+    path: &str,
+) -> Result<Value, String> {
+    let decoded = decode(&read_file(path)?, DECISION_FILE, trusted)?;
+    let payload = &decoded.payload;
+    let file_id = payload["decision_file_id"]
+        .as_str()
+        .filter(|id| *id == decoded.manifest["id"])
+        .ok_or_else(|| {
+            ErrorCode::InvalidInput.with("Decision file id does not match its manifest")
+        })?
+        .to_string();
+    let bundle_id = payload["bundle_id"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let bundle = {
+        let conn = db.lock()?;
+        if bundle_row(&conn, &file_id, "applied")?.is_some() {
+            return Err(ErrorCode::InvalidInput.with("This decision file was already applied"));
+        }
+        bundle_row(&conn, &bundle_id, "exported")?.ok_or_else(|| {
+            ErrorCode::NotFound.with(format!("Bundle {} was not exported from here", bundle_id))
+        })?
+    };
+    if payload["bundle_sha256"].as_str() != Some(bundle.manifest_sha256.as_str()) {
+        return Err(ErrorCode::InvalidInput.with("The decisions answer a different bundle"));
+    }
+    let conn = connection(&bundle.profile_id)?;
+    if net::base_origin(&conn.base_url).unwrap_or_default() != bundle.origin {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Profile {} no longer points at {}",
+            bundle.profile_id, bundle.origin
+        )));
+    }
+    // Recorded before sending, so a file cannot be applied twice even if
+    // this is interrupted; the results below say which decisions went out.
+    insert_bundle(
+        &*db.lock()?,
+        &file_id,
+        "applied",
+        &bundle.profile_id,
+        &bundle.origin,
+        &decoded.signer,
+        &decoded.manifest_sha256,
+        &payload["decisions"],
+    )?;
+    let entries: Vec<Value> = payload["decisions"].as_array().cloned().unwrap_or_default();
+    let deps = dependencies(&bundle.plans, &entries);
+    let position: HashMap<&str, usize> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| Some((entry["plan_id"].as_str()?, i)))
+        .collect();
+    let waits: Vec<Vec<usize>> = entries
+        .iter()
+        .map(|entry| {
+            let plan_id = entry["plan_id"].as_str().unwrap_or_default();
+            deps.get(plan_id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| position.get(id.as_str()).copied())
+                .collect()
+        })
+        .collect();
+    let (rounds, cyclic) = schedule(&waits, replay.mode);
+    let width = match replay.mode {
+        ReplayMode::Ordered => 1,
+        ReplayMode::Parallel => replay.max_parallel.clamp(1, MAX_PARALLEL),
+    };
+    let plan_of = |i: usize| {
+        entries[i]["plan_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let result = |i: usize, round: Option<usize>, outcome: Result<Value, String>| {
+        let entry = &entries[i];
+        let after: Vec<String> = waits[i].iter().map(|d| plan_of(*d)).collect();
+        let mut result = json!({
+            "plan_id": entry["plan_id"],
+            "decision": entry["decision"],
+            "ok": outcome.is_ok(),
+            "round": round,
+            "after": after,
+        });
+        if let Err(err) = outcome {
+            result["error"] = json!(err);
+        }
+        result
+    };
+    let record = |result: &Value| -> Result<(), String> {
+        let _ = db::record_audit(
+            &*db.lock()?,
+            &bundle.profile_id,
+            "airgap",
+            "apply_decision",
+            result["plan_id"].as_str().unwrap_or_default(),
+            &json!({ "decision_file_id": file_id, "reviewer_key": decoded.signer, "result": result }),
+        );
+        Ok(())
+    };
+
+    let mut results = vec![Value::Null; entries.len()];
+    for i in cyclic {
+        results[i] = result(
+            i,
+            None,
+            Err(ErrorCode::InvalidInput
+                .with("The decision waits on itself through other decisions")),
+        );
+        record(&results[i])?;
+    }
+    for (round, members) in rounds.iter().enumerate() {
+        let mut ready = Vec::new();
+        for &i in members {
+            let failed: Vec<String> = waits[i]
+                .iter()
+                .filter(|d| results[**d]["ok"] != true)
+                .map(|d| plan_of(*d))
+                .collect();
+            if failed.is_empty() {
+                ready.push(i);
+                continue;
+            }
+            results[i] = result(
+                i,
+                Some(round),
+                Err(ErrorCode::InvalidInput.with(format!(
+                    "Not sent: it waits on {}, whose decision did not go through",
+                    failed.join(", ")
+                ))),
+            );
+            results[i]["skipped"] = json!(true);
+            record(&results[i])?;
+        }
+        for batch in ready.chunks(width) {
+            let mut sends = tokio::task::JoinSet::new();
+            for &i in batch {
+                let entry = entries[i].clone();
+                let reviewed = bundle
+                    .plans
+                    .iter()
+                    .find(|p| p["id"] == entry["plan_id"])
+                    .map(|p| p["version"].as_str().map(str::to_string));
+                let context = json!({
+                    "bundle_id": bundle_id,
+                    "decision_file_id": file_id,
+                    "reviewer_key": decoded.signer,
+                    "decided_at": entry["decided_at"],
+                });
+                let base_url = conn.base_url.clone();
+                let token = conn.token.clone();
+                sends.spawn(async move {
+                    let plan_id = entry["plan_id"].as_str().unwrap_or_default();
+                    let decision = entry["decision"].as_str().unwrap_or_default();
+                    let outcome = match reviewed {
+                        _ if decision != "approve" && decision != "reject" => {
+                            Err(ErrorCode::InvalidInput
+                                .with(format!("Unsupported decision: {}", decision)))
+                        }
+                        None => {
+                            Err(ErrorCode::InvalidInput.with("Plan is not in the exported bundle"))
+                        }
+                        Some(version) => {
+                            let body = json!({
+                                "reason": entry["reason"],
+                                "offline_review": context,
+                            });
+                            decisions::send_decision(
+                                &base_url,
+                                token,
+                                plan_id,
+                                decision,
+                                Some(body),
+                                version.as_deref(),
+                                None,
+                            )
+                            .await
+                        }
+                    };
+                    (i, outcome)
+                });
+            }
+            while let Some(joined) = sends.join_next().await {
+                let (i, outcome) = joined.map_err(|e| {
+                    ErrorCode::Internal.with(format!("Sending a decision failed: {}", e))
+                })?;
+                results[i] = result(i, Some(round), outcome);
+                record(&results[i])?;
+            }
+        }
+    }
+    let applied = results.iter().filter(|r| r["ok"] == true).count();
+    let skipped = results.iter().filter(|r| r["skipped"] == true).count();
+    Ok(json!({
+        "decision_file_id": file_id,
+        "bundle_id": bundle_id,
+        "profile_id": bundle.profile_id,
+        "reviewer_key": decoded.signer,
+        "mode": replay.mode,
+        "rounds": rounds.len(),
+        "applied": applied,
+        "failed": results.len() - applied,
+        "skipped": skipped,
+        "results": results,
+    }))
+}
+
+/// Rounds of indexes into a decision file's decisions, given the ones each
+/// waits on: every decision comes in a later round than those it waits on.
+/// `Ordered` puts one decision in each round, the earliest made of those
+/// ready; `Parallel` puts every decision that is ready into the round. The
+/// second list holds decisions that wait on each other and never become
+/// ready.
+fn schedule(waits: &[Vec<usize>], mode: ReplayMode) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut pending: Vec<usize> = waits.iter().map(Vec::len).collect();
+    let mut dependents = vec![Vec::new(); waits.len()];
+    for (i, deps) in waits.iter().enumerate() {
+        for dep in deps {
+            dependents[*dep].push(i);
+        }
+    }
+    let mut ready: BTreeSet<usize> = (0..waits.len()).filter(|i| pending[*i] == 0).collect();
+    let mut rounds = Vec::new();
+    while let Some(first) = ready.pop_first() {
+        let round = match mode {
+            ReplayMode::Ordered => vec![first],
+            ReplayMode::Parallel => std::iter::once(first)
+                .chain(std::mem::take(&mut ready))
+                .collect(),
+        };
+        for i in &round {
+            for dependent in &dependents[*i] {
+                pending[*dependent] -= 1;
+                if pending[*dependent] == 0 {
+                    ready.insert(*dependent);
+                }
+            }
+        }
+        rounds.push(round);
+    }
+    let cyclic = (0..waits.len()).filter(|i| pending[*i] > 0).collect();
+    (rounds, cyclic)
+}
@@ -0,0 +1,152 @@
+use std::sync::Mutex;
+
+use reqwest::Url;
+
+use crate::errors::ErrorCode;
+use crate::managed;
+use crate::settings::Profile;
+
+/// The user's approved cores, mirrored from settings for `request_json`.
+static USER: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// An allowlist entry as a URL, and whether it covers subdomains.
+pub fn parse_entry(entry: &str) -> Result<(Url, bool), String> {
+    let entry = entry.trim();
+    let (raw, wildcard) = match entry.split_once("://*.") {
+        Some((scheme, rest)) => (format!("{}://{}", scheme, rest), true),
+        None => (entry.to_string(), false),
+    };
+    let url = Url::parse(&raw).map_err(|e| format!("allowed_base_urls {:?}: {}", entry, e))?;
+    Ok((url, wildcard))
+}
+
+fn entry_matches(entry: &str, url: &Url) -> bool {
+    let Ok((allowed, wildcard)) = parse_entry(entry) else {
+        return false;
+    };
+    let (Some(host), Some(wanted)) = (url.host_str(), allowed.host_str()) else {
+        return false;
+    };
+    let host_ok = host.eq_ignore_ascii_case(wanted)
+        || (wildcard
+            && host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", wanted.to_ascii_lowercase())));
+    let prefix = allowed.path().trim_end_matches('/');
+    let path = url.path();
+    let path_ok = path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+        || prefix.is_empty();
+    url.scheme() == allowed.scheme()
+        && host_ok
+        && url.port_or_known_default() == allowed.port_or_known_default()
+        && path_ok
+}
+
+/// Whether `base_url` is on `list`; an empty list allows any core.
+pub fn allows(list: &[String], base_url: &str) -> bool {
+    list.is_empty()
+        || Url::parse(base_url.trim()).is_ok_and(|url| list.iter().any(|e| entry_matches(e, &url)))
+}
+
+/// The user's list must parse and cover every profile's endpoints, so
+/// turning it on cannot cut off a configured core.
+pub fn validate(entries: &[String], profiles: &[Profile]) -> Result<(), String> {
+    for entry in entries {
+        let (url, _) = parse_entry(entry)?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err(format!(
+                "allowed_base_urls {:?} must be an http(s) URL",
+                entry
+            ));
+        }
+    }
+    for profile in profiles {
+        let urls = profile
+            .endpoints()
+            .chain(profile.read_routing.read_url.as_deref());
+        for url in urls {
+            if !allows(entries, url) {
+                return Err(format!(
+                    "Profile {}: {} is not in allowed_base_urls",
+                    profile.id, url
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn configure(entries: &[String]) {
+    *USER.lock().unwrap_or_else(|e| e.into_inner()) = entries.to_vec();
+}
+
+/// Refuses to send a request, and the credential on it, to a core that is
+/// not on the administrator's allowlist or the user's. A page asking the
+/// operator to "just change the base URL" gets an error instead of a token.
+pub fn check(base_url: &str) -> Result<(), String> {
+    if !allows(&managed::policy().allowed_base_urls, base_url) {
+        return Err(ErrorCode::ForbiddenLocal.with(format!(
+            "{} is not one of the cores your administrator allows",
+            base_url.trim()
+        )));
+    }
+    let user = USER.lock().unwrap_or_else(|e| e.into_inner());
+    if !allows(&user, base_url) {
+        return Err(ErrorCode::ForbiddenLocal.with(format!(
+            "{} is not in allowed_base_urls; add it in settings if it is one of your cores",
+            base_url.trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn entries_match_scheme_host_port_and_path_prefix() {
+        let list = list(&["https://core.example/api", "https://*.corp.example"]);
+        assert!(allows(&list, "https://core.example/api"));
+        assert!(allows(&list, "https://CORE.example/api/t/acme"));
+        assert!(allows(&list, "https://core.example:443/api"));
+        assert!(!allows(&list, "https://core.example/apix"));
+        assert!(!allows(&list, "https://core.example/"));
+        assert!(!allows(&list, "http://core.example/api"));
+        assert!(!allows(&list, "https://core.example:8443/api"));
+        assert!(!allows(&list, "https://core.example.evil/api"));
+        assert!(allows(&list, "https://eu.corp.example/any"));
+        assert!(allows(&list, "https://a.b.corp.example"));
+        assert!(!allows(&list, "https://corp.example"));
+        assert!(!allows(&list, "https://evilcorp.example"));
+        assert!(!allows(&list, "not a url"));
+        assert!(allows(&[], "https://anything.example"));
+    }
+
+    #[test]
+    fn wildcard_entries_parse_without_the_star() {
+        let (url, wildcard) = parse_entry(" https://*.corp.example/api ").unwrap();
+        assert!(wildcard);
+        assert_eq!(url.host_str(), Some("corp.example"));
+        assert!(!parse_entry("https://core.example").unwrap().1);
+        assert!(parse_entry("core.example").is_err());
+        assert!(validate(&list(&["ftp://core.example"]), &[]).is_err());
+    }
+
+    #[test]
+    fn check_refuses_cores_off_the_users_list() {
+        configure(&list(&["https://core.example"]));
+        assert!(check("https://core.example/api").is_ok());
+        let err = check("https://elsewhere.example").unwrap_err();
+        configure(&[]);
+        assert!(err.contains("allowed_base_urls"), "{}", err);
+        assert!(check("https://elsewhere.example").is_ok());
+    }
+}
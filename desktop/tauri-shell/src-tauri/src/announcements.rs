@@ -0,0 +1,474 @@
+use std::cmp::Ordering;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::Signature;
+use reqwest::{Method, Url};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::core_signing::{self, Checked};
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::{AnnouncementSettings, CoreSigningSettings, SettingsStore};
+use crate::{bandwidth, environment, net, notify, plugin_registry, tasks};
+
+const TASK: &str = "announcements";
+const UPDATED_EVENT: &str = "announcement:updated";
+const CRITICAL_EVENT: &str = "announcement:critical";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// The core path read when no feed URL is configured.
+const CORE_PATH: &str = "/announcements";
+const MAX_FEED_BYTES: usize = 2 * 1024 * 1024;
+const MAX_ENTRIES: usize = 200;
+const MAX_TRUSTED_KEYS: usize = 16;
+const MIN_REFRESH_MINUTES: u32 = 5;
+const MAX_REFRESH_MINUTES: u32 = 24 * 60;
+const KINDS: &[&str] = &["announcement", "changelog", "advisory"];
+const SEVERITIES: &[&str] = &["info", "warning", "critical"];
+
+/// How the last fetch went, for `get_announcements`.
+#[derive(Default)]
+struct Fetched {
+    checked: Option<Instant>,
+    checked_at: Option<String>,
+    source: String,
+    signer: Option<String>,
+    error: Option<String>,
+}
+
+static FETCHED: OnceLock<Mutex<Fetched>> = OnceLock::new();
+
+fn fetched() -> &'static Mutex<Fetched> {
+    FETCHED.get_or_init(|| Mutex::new(Fetched::default()))
+}
+
+fn storage(e: rusqlite::Error) -> String {
+    ErrorCode::LocalStorage.with(e.to_string())
+}
+
+pub fn validate(settings: &AnnouncementSettings) -> Result<(), String> {
+    let url = settings.url.trim();
+    if !url.is_empty() {
+        let parsed =
+            Url::parse(url).map_err(|e| format!("Invalid announcement feed URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("The announcement feed must be an http/https URL".to_string());
+        }
+    }
+    if !(MIN_REFRESH_MINUTES..=MAX_REFRESH_MINUTES).contains(&settings.refresh_minutes) {
+        return Err(format!(
+            "announcements.refresh_minutes must be between {} and {}",
+            MIN_REFRESH_MINUTES, MAX_REFRESH_MINUTES
+        ));
+    }
+    if settings.trusted_keys.len() > MAX_TRUSTED_KEYS {
+        return Err(format!(
+            "At most {} trusted announcement keys are allowed",
+            MAX_TRUSTED_KEYS
+        ));
+    }
+    for key in &settings.trusted_keys {
+        plugin_registry::decode_key(key)?;
+    }
+    Ok(())
+}
+
+async fn download(url: &Url) -> Result<Value, String> {
+    let response = net::client_for(url)?
+        .get(url.clone())
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| {
+            ErrorCode::CoreUnreachable.with(format!("Announcement feed unreachable: {}", e))
+        })?;
+    let status = response.status().as_u16();
+    if !response.status().is_success() {
+        return Err(
+            ErrorCode::from_status(status).with(format!("Announcement feed answered {}", status))
+        );
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_FEED_BYTES)
+    {
+        return Err(ErrorCode::InvalidInput.with("Announcement feed is too large"));
+    }
+    let headers = response.headers().clone();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("Feed download failed: {}", e)))?;
+    bandwidth::opened(
+        url.as_str(),
+        bandwidth::current(),
+        bandwidth::request_bytes(&Method::GET, url, &[], None),
+        bandwidth::response_bytes(&headers, bytes.len() as u64),
+    );
+    if bytes.len() > MAX_FEED_BYTES {
+        return Err(ErrorCode::InvalidInput.with("Announcement feed is too large"));
+    }
+    serde_json::from_slice(&bytes)
+        .map_err(|e| ErrorCode::CoreError.with(format!("Announcement feed is invalid: {}", e)))
+}
+
+/// The feed inside a signed `{ "signed": "<JSON text>", "signature":
+/// "<base64>" }` envelope and who signed it: one of `trusted_keys`, or for
+/// a feed from the core, the profile's own core signature.
+fn verify(
+    raw: Value,
+    trusted_keys: &[String],
+    core: Option<(&str, &CoreSigningSettings)>,
+) -> Result<(Value, String), String> {
+    let (Some(signed), Some(signature)) = (raw["signed"].as_str(), raw["signature"].as_str())
+    else {
+        return Err(ErrorCode::Forbidden.with("The announcement feed is not signed"));
+    };
+    let parse = |text: &str| {
+        serde_json::from_str(text).map_err(|e| {
+            ErrorCode::InvalidInput.with(format!("Signed announcement feed is invalid: {}", e))
+        })
+    };
+    let signature = B64
+        .decode(signature.trim())
+        .ok()
+        .and_then(|raw| Signature::from_slice(&raw).ok());
+    if let Some(signature) = signature {
+        let signer = trusted_keys.iter().find(|raw| {
+            plugin_registry::decode_key(raw)
+                .is_ok_and(|key| key.verify_strict(signed.as_bytes(), &signature).is_ok())
+        });
+        if let Some(signer) = signer {
+            return Ok((parse(signed)?, signer.clone()));
+        }
+    }
+    if let Some((profile_id, settings)) = core {
+        if let Ok(Checked::Verified(feed)) = core_signing::check(profile_id, settings, raw.clone())
+        {
+            return Ok((feed, "core_signing".to_string()));
+        }
+    }
+    Err(ErrorCode::Forbidden.with("The announcement feed is not signed by a trusted key"))
+}
+
+/// One feed entry as cached: `id` and `title` are required; `kind`
+/// (`announcement`, `changelog`, `advisory`) and `severity` (`info`,
+/// `warning`, `critical`) default to the mildest. An entry whose
+/// `min_version` is newer than this shell is an upgrade requirement and
+/// always critical.
+fn entry(raw: &Value) -> Option<Value> {
+    let text = |key: &str| raw[key].as_str().map(str::trim).filter(|s| !s.is_empty());
+    let id = text("id")?;
+    let title = text("title")?;
+    let pick = |key: &str, allowed: &[&str]| {
+        text(key)
+            .map(str::to_ascii_lowercase)
+            .filter(|v| allowed.contains(&v.as_str()))
+            .unwrap_or_else(|| allowed[0].to_string())
+    };
+    let upgrade_required = text("min_version").is_some_and(|min| {
+        plugin_registry::compare_versions(min, env!("CARGO_PKG_VERSION")) == Ordering::Greater
+    });
+    let severity = if upgrade_required {
+        "critical".to_string()
+    } else {
+        pick("severity", SEVERITIES)
+    };
+    Some(json!({
+        "id": id,
+        "title": title,
+        "body": text("body").unwrap_or_default(),
+        "kind": pick("kind", KINDS),
+        "severity": severity,
+        "version": text("version"),
+        "min_version": text("min_version"),
+        "upgrade_required": upgrade_required,
+        "url": text("url"),
+        "published_at": text("published_at").unwrap_or_default(),
+        "expires_at": text("expires_at"),
+    }))
+}
+
+fn expired(entry: &Value) -> bool {
+    entry["expires_at"]
+        .as_str()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .is_some_and(|at| at < Utc::now())
+}
+
+/// Replaces the cached feed of `source` with `entries`, keeping the read
+/// state of the ones already seen. Returns the entries not seen before.
+fn store_feed(conn: &Connection, source: &str, entries: &[Value]) -> Result<Vec<Value>, String> {
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut fresh = Vec::new();
+    let ids: Vec<&str> = entries.iter().filter_map(|e| e["id"].as_str()).collect();
+    let known: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM announcements")
+            .map_err(storage)?;
+        let rows = stmt.query_map([], |row| row.get(0)).map_err(storage)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(storage)?
+    };
+    for entry in entries {
+        let id = entry["id"].as_str().unwrap_or_default();
+        if !known.iter().any(|k| k == id) {
+            fresh.push(entry.clone());
+        }
+        conn.execute(
+            "INSERT INTO announcements
+                (id, source, kind, severity, payload, published_at, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET source = ?2, kind = ?3, severity = ?4,
+                payload = ?5, published_at = ?6, fetched_at = ?7",
+            params![
+                id,
+                source,
+                entry["kind"].as_str().unwrap_or_default(),
+                entry["severity"].as_str().unwrap_or_default(),
+                entry.to_string(),
+                entry["published_at"].as_str().unwrap_or_default(),
+                now,
+            ],
+        )
+        .map_err(storage)?;
+    }
+    // Withdrawn from the feed, or left over from a feed no longer read.
+    let mut stmt = conn
+        .prepare("SELECT id, source FROM announcements")
+        .map_err(storage)?;
+    let cached: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(storage)?
+        .collect::<rusqlite::Result<_>>()
+        .map_err(storage)?;
+    for (id, from) in cached {
+        if from != source || !ids.contains(&id.as_str()) {
+            conn.execute("DELETE FROM announcements WHERE id = ?1", [&id])
+                .map_err(storage)?;
+        }
+    }
+    Ok(fresh)
+}
+
+/// Fetches the feed from `announcements.url`, or the active profile's core
+/// when it is empty, verifies it, caches it and announces what is new:
+/// `announcement:updated` for any new entry and `announcement:critical`,
+/// with a notification, for each new critical one.
+async fn refresh(app: &AppHandle) -> Result<Value, String> {
+    let store = app.state::<SettingsStore>();
+    let settings = store.snapshot();
+    let wanted = &settings.announcements;
+    let url = wanted.url.trim();
+    let outcome = async {
+        if url.is_empty() {
+            let conn = store.connection(None)?;
+            let raw = crate::request_json(
+                Method::GET,
+                &conn.base_url,
+                CORE_PATH,
+                conn.token.clone(),
+                None,
+            )
+            .await?;
+            let signing = settings
+                .profile(&conn.profile_id)
+                .map(|p| p.core_signing.clone())
+                .unwrap_or_default();
+            let source = format!("core:{}", conn.profile_id);
+            verify(
+                raw,
+                &wanted.trusted_keys,
+                Some((&conn.profile_id, &signing)),
+            )
+            .map(|(feed, signer)| (source, feed, signer))
+        } else {
+            let parsed =
+                Url::parse(url).map_err(|e| ErrorCode::InvalidInput.with(e.to_string()))?;
+            let raw = download(&parsed).await?;
+            verify(raw, &wanted.trusted_keys, None)
+                .map(|(feed, signer)| (url.to_string(), feed, signer))
+        }
+        .and_then(|(source, feed, signer)| {
+            let listed = feed
+                .as_array()
+                .or_else(|| feed["announcements"].as_array())
+                .or_else(|| feed["entries"].as_array())
+                .ok_or_else(|| ErrorCode::InvalidInput.with("The feed lists no announcements"))?;
+            let entries: Vec<Value> = listed.iter().filter_map(entry).take(MAX_ENTRIES).collect();
+            Ok((source, entries, signer))
+        })
+    }
+    .await;
+    let checked_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let (source, entries, signer) = match outcome {
+        Ok(found) => found,
+        Err(err) => {
+            if let Ok(mut last) = fetched().lock() {
+                last.checked = Some(Instant::now());
+                last.checked_at = Some(checked_at);
+                last.error = Some(err.clone());
+            }
+            return Err(err);
+        }
+    };
+    let fresh = store_feed(&*app.state::<LocalDb>().lock()?, &source, &entries)?;
+    if let Ok(mut last) = fetched().lock() {
+        *last = Fetched {
+            checked: Some(Instant::now()),
+            checked_at: Some(checked_at),
+            source: source.clone(),
+            signer: Some(signer.clone()),
+            error: None,
+        };
+    }
+    let fresh: Vec<Value> = fresh.into_iter().filter(|e| !expired(e)).collect();
+    for advisory in fresh.iter().filter(|e| e["severity"] == "critical") {
+        let title = advisory["title"].as_str().unwrap_or_default();
+        notify::notify(
+            app,
+            "announcement",
+            if advisory["upgrade_required"] == true {
+                "Upgrade required"
+            } else {
+                "Critical advisory"
+            },
+            title,
+            json!({ "announcement_id": advisory["id"] }),
+        );
+        let _ = environment::emit(app, CRITICAL_EVENT, advisory);
+    }
+    if !fresh.is_empty() {
+        let _ = environment::emit(
+            app,
+            UPDATED_EVENT,
+            json!({ "source": source, "new": fresh.len() }),
+        );
+    }
+    Ok(json!({
+        "source": source,
+        "signed_by": signer,
+        "entries": entries.len(),
+        "new": fresh.len(),
+    }))
+}
+
+/// Refetches the feed every `refresh_minutes`.
+pub fn spawn_fetcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let settings = app.state::<SettingsStore>().snapshot().announcements;
+            let every = Duration::from_secs(u64::from(settings.refresh_minutes) * 60);
+            let due = fetched()
+                .lock()
+                .map(|last| last.checked.is_none_or(|at| at.elapsed() >= every))
+                .unwrap_or(false);
+            if settings.enabled && due {
+                if let Err(err) = refresh(&app).await {
+                    tasks::failed(TASK, &err);
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// The cached announcements and changelog entries, newest first, with
+/// whether each was read; read ones only with `include_read`. Expired
+/// entries are left out.
+#[tauri::command]
+pub fn get_announcements(
+    db: State<'_, LocalDb>,
+    include_read: Option<bool>,
+) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT payload, read_at FROM announcements
+             ORDER BY published_at DESC, fetched_at DESC",
+        )
+        .map_err(storage)?;
+    let rows: Vec<(String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(storage)?
+        .collect::<rusqlite::Result<_>>()
+        .map_err(storage)?;
+    let mut unread = 0;
+    let mut critical = 0;
+    let mut announcements = Vec::new();
+    for (payload, read_at) in rows {
+        let Ok(mut entry) = serde_json::from_str::<Value>(&payload) else {
+            continue;
+        };
+        if expired(&entry) {
+            continue;
+        }
+        if read_at.is_none() {
+            unread += 1;
+            if entry["severity"] == "critical" {
+                critical += 1;
+            }
+        } else if !include_read.unwrap_or(false) {
+            continue;
+        }
+        entry["read"] = json!(read_at.is_some());
+        entry["read_at"] = json!(read_at);
+        announcements.push(entry);
+    }
+    let last = fetched()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Announcement state poisoned"))?;
+    Ok(json!({
+        "announcements": announcements,
+        "unread": unread,
+        "unread_critical": critical,
+        "source": last.source,
+        "signed_by": last.signer,
+        "checked_at": last.checked_at,
+        "error": last.error,
+    }))
+}
+
+/// Fetches the feed now instead of waiting for the next refresh.
+#[tauri::command]
+pub async fn refresh_announcements(app: AppHandle) -> Result<Value, String> {
+    refresh(&app).await
+}
+
+/// Marks `ids` read, or every cached entry without them.
+#[tauri::command]
+pub fn mark_announcements_read(
+    db: State<'_, LocalDb>,
+    ids: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let marked = match ids {
+        None => conn
+            .execute(
+                "UPDATE announcements SET read_at = ?1 WHERE read_at IS NULL",
+                [&now],
+            )
+            .map_err(storage)?,
+        Some(ids) => {
+            let mut marked = 0;
+            for id in ids {
+                marked += conn
+                    .execute(
+                        "UPDATE announcements SET read_at = ?1
+                         WHERE id = ?2 AND read_at IS NULL",
+                        params![now, id.trim()],
+                    )
+                    .map_err(storage)?;
+            }
+            marked
+        }
+    };
+    Ok(json!({ "marked": marked }))
+}
@@ -0,0 +1,315 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// An escape sequence cut off at the end of a chunk is held for the next
+/// one up to this length; anything longer is not a sequence worth keeping.
+const MAX_PENDING: usize = 64;
+
+const NAMED: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Attributes set by SGR sequences. Colors are a name (`red`,
+/// `bright_red`, ...) for the 16 the theme defines, `#rrggbb` otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Style {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub dim: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub inverse: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub strike: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A run of text in one style.
+#[derive(Clone, Debug, Serialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: Style,
+    /// The text overwrites the current line from its start: a carriage
+    /// return cleared what came before it in earlier output.
+    #[serde(skip_serializing_if = "is_false")]
+    pub line_reset: bool,
+}
+
+fn palette(index: u16, bright: bool) -> String {
+    let name = NAMED[usize::from(index % 8)];
+    if bright {
+        format!("bright_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// xterm's 256-color table: the 16 named colors, a 6×6×6 cube and a
+/// 24-step gray ramp.
+fn indexed(index: u16) -> String {
+    match index {
+        0..=7 => palette(index, false),
+        8..=15 => palette(index - 8, true),
+        16..=231 => {
+            let level = |v: u16| if v == 0 { 0 } else { v * 40 + 55 };
+            let i = index - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(i / 36),
+                level(i / 6 % 6),
+                level(i % 6)
+            )
+        }
+        _ => {
+            let gray = (index.min(255) - 232) * 10 + 8;
+            format!("#{:02x}{:02x}{:02x}", gray, gray, gray)
+        }
+    }
+}
+
+impl Style {
+    /// Applies one SGR sequence's parameters.
+    fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                9 => self.strike = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                29 => self.strike = false,
+                code @ 30..=37 => self.fg = Some(palette(code - 30, false)),
+                code @ 40..=47 => self.bg = Some(palette(code - 40, false)),
+                code @ 90..=97 => self.fg = Some(palette(code - 90, true)),
+                code @ 100..=107 => self.bg = Some(palette(code - 100, true)),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                code @ (38 | 48) => {
+                    let color = match params.get(i + 1) {
+                        Some(5) => {
+                            let color = params.get(i + 2).map(|n| indexed(*n));
+                            i += 2;
+                            color
+                        }
+                        Some(2) => {
+                            let rgb = params.get(i + 2..i + 5).map(|c| {
+                                format!(
+                                    "#{:02x}{:02x}{:02x}",
+                                    c[0].min(255),
+                                    c[1].min(255),
+                                    c[2].min(255)
+                                )
+                            });
+                            i += 4;
+                            rgb
+                        }
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.fg = color.or(self.fg.take());
+                    } else {
+                        self.bg = color.or(self.bg.take());
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+enum Escape {
+    Sgr(Vec<u16>),
+    /// Cursor movement, erasing, titles and the like: dropped.
+    Ignored,
+}
+
+/// The escape sequence at the start of `bytes` (which begins with ESC) and
+/// its length, or `None` when it is cut off.
+fn escape(bytes: &[u8]) -> Option<(usize, Escape)> {
+    match bytes.get(1)? {
+        b'[' => {
+            let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+            let escape = if bytes[end] == b'm' {
+                let params = std::str::from_utf8(&bytes[2..end]).unwrap_or_default();
+                Escape::Sgr(
+                    params
+                        .split([';', ':'])
+                        .filter(|p| !p.is_empty() || params.is_empty())
+                        .map(|p| p.parse::<u16>().unwrap_or(0))
+                        .collect(),
+                )
+            } else {
+                Escape::Ignored
+            };
+            Some((end + 1, escape))
+        }
+        // OSC: ends at BEL or ST (ESC \).
+        b']' => {
+            let mut i = 2;
+            loop {
+                match bytes.get(i)? {
+                    0x07 => return Some((i + 1, Escape::Ignored)),
+                    0x1b if bytes.get(i + 1)? == &b'\\' => return Some((i + 2, Escape::Ignored)),
+                    _ => i += 1,
+                }
+            }
+        }
+        // Character set selection takes one more byte.
+        b'(' | b')' | b'*' | b'+' => bytes.get(2).map(|_| (3, Escape::Ignored)),
+        _ => Some((2, Escape::Ignored)),
+    }
+}
+
+/// Spans being collected for one chunk.
+#[derive(Default)]
+struct Builder {
+    spans: Vec<Span>,
+    text: String,
+    line_reset: bool,
+}
+
+impl Builder {
+    fn flush(&mut self, style: &Style) {
+        if self.text.is_empty() {
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        match self.spans.last_mut() {
+            Some(last) if last.style == *style => last.text.push_str(&text),
+            _ => self.spans.push(Span {
+                text,
+                style: style.clone(),
+                line_reset: std::mem::take(&mut self.line_reset),
+            }),
+        }
+    }
+
+    /// Drops the current line back to its last newline, here or (by
+    /// flagging the next span) in output already sent.
+    fn carriage_return(&mut self, style: &Style) {
+        self.flush(style);
+        while let Some(last) = self.spans.last_mut() {
+            if let Some(newline) = last.text.rfind('\n') {
+                last.text.truncate(newline + 1);
+                return;
+            }
+            let dropped = self.spans.pop();
+            self.line_reset |= dropped.is_some_and(|s| s.line_reset);
+        }
+        self.line_reset = true;
+    }
+
+    fn backspace(&mut self, style: &Style) {
+        self.flush(style);
+        if let Some(last) = self.spans.last_mut() {
+            if !last.text.ends_with('\n') {
+                last.text.pop();
+            }
+            if last.text.is_empty() && !last.line_reset {
+                self.spans.pop();
+            }
+        }
+    }
+}
+
+/// One output stream's parser state, carried from chunk to chunk.
+#[derive(Default)]
+pub struct Parser {
+    style: Style,
+    pending: String,
+}
+
+impl Parser {
+    /// `text` as styled spans, with escape sequences removed. Carriage
+    /// returns rewrite the current line and backspaces remove a character;
+    /// other control characters are dropped.
+    pub fn feed(&mut self, text: &str) -> Vec<Span> {
+        let input = std::mem::take(&mut self.pending) + text;
+        let bytes = input.as_bytes();
+        let mut out = Builder::default();
+        let mut i = 0;
+        while i < bytes.len() {
+            let run = bytes[i..]
+                .iter()
+                .position(|b| *b < 0x20 || *b == 0x7f)
+                .unwrap_or(bytes.len() - i);
+            out.text.push_str(&input[i..i + run]);
+            i += run;
+            let Some(&byte) = bytes.get(i) else {
+                break;
+            };
+            match byte {
+                0x1b => match escape(&bytes[i..]) {
+                    Some((len, escape)) => {
+                        if let Escape::Sgr(params) = escape {
+                            out.flush(&self.style);
+                            self.style.apply(&params);
+                        }
+                        i += len;
+                        continue;
+                    }
+                    None => {
+                        if bytes.len() - i <= MAX_PENDING {
+                            self.pending = input[i..].to_string();
+                        }
+                        break;
+                    }
+                },
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {}
+                b'\r' if i + 1 == bytes.len() => {
+                    // Whether a newline follows is only known with the next chunk.
+                    self.pending = "\r".to_string();
+                }
+                b'\r' => out.carriage_return(&self.style),
+                0x08 => out.backspace(&self.style),
+                b'\n' | b'\t' => out.text.push(byte as char),
+                _ => {}
+            }
+            i += 1;
+        }
+        out.flush(&self.style);
+        if out.line_reset {
+            out.spans.push(Span {
+                text: String::new(),
+                style: self.style.clone(),
+                line_reset: true,
+            });
+        }
+        out.spans
+    }
+}
+
+/// Parses `text` on its own, from the default style.
+pub fn spans(text: &str) -> Vec<Span> {
+    Parser::default().feed(text)
+}
+
+/// Styled spans for `text`, for output the backend does not relay itself.
+#[tauri::command]
+pub fn parse_ansi(text: String) -> Result<Value, String> {
+    Ok(json!({ "spans": spans(&text) }))
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{Profile, SettingsStore};
+use crate::{environment, net, setup, tasks};
+
+const TASK: &str = "api_prefix";
+const DETECTED_EVENT: &str = "core:api_prefix";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a core that could not be found is left before it is probed
+/// again in the background.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+const MAX_PREFIX_LEN: usize = 128;
+
+/// Every endpoint's origin, to its profile's prefix.
+static PREFIXES: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn prefixes() -> &'static Mutex<HashMap<String, String>> {
+    PREFIXES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Background probes that found nothing: the base URL probed, and when.
+static MISSED: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+
+fn missed() -> &'static Mutex<HashMap<String, (String, Instant)>> {
+    MISSED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Tracks the profiles with a prefix, by the origin of each endpoint they
+/// send requests to.
+pub fn configure(profiles: &[Profile]) {
+    let mut next = HashMap::new();
+    for profile in profiles {
+        let Some(prefix) = profile.api_prefix.as_deref().filter(|p| !p.is_empty()) else {
+            continue;
+        };
+        let endpoints = profile
+            .endpoints()
+            .chain(profile.read_routing.read_url.as_deref());
+        for origin in endpoints.filter_map(net::base_origin) {
+            next.insert(origin, prefix.to_string());
+        }
+    }
+    *prefixes().lock().unwrap_or_else(|e| e.into_inner()) = next;
+}
+
+pub fn validate(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() {
+        return Ok(());
+    }
+    let valid = prefix.len() <= MAX_PREFIX_LEN
+        && prefix.starts_with('/')
+        && !prefix.ends_with('/')
+        && prefix.split('/').skip(1).all(|segment| {
+            !segment.is_empty()
+                && segment != ".."
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '~'))
+        });
+    if !valid {
+        return Err(format!(
+            "api_prefix `{}` must be empty or a path like `/api`",
+            prefix
+        ));
+    }
+    Ok(())
+}
+
+/// Clears the detected prefix of every profile whose `base_url` changed
+/// in an update that left the prefix alone, to be found again there.
+pub fn forget_moved(before: &[Profile], after: &mut [Profile]) {
+    for profile in after.iter_mut() {
+        let Some(old) = before.iter().find(|p| p.id == profile.id) else {
+            continue;
+        };
+        if old.base_url != profile.base_url && old.api_prefix == profile.api_prefix {
+            profile.api_prefix = None;
+        }
+    }
+}
+
+/// What goes between `base_url` and `path` on a request: the profile's
+/// prefix, or nothing when it has none or the path already carries it.
+pub fn for_request(base_url: &str, path: &str) -> String {
+    let Some(origin) = net::base_origin(base_url) else {
+        return String::new();
+    };
+    let Some(prefix) = prefixes().lock().ok().and_then(|p| p.get(&origin).cloned()) else {
+        return String::new();
+    };
+    let bare = path.split(['?', '#']).next().unwrap_or(path);
+    if bare == prefix || bare.starts_with(&format!("{}/", prefix)) {
+        return String::new();
+    }
+    prefix
+}
+
+/// Looks for the core under the profile's `base_url` and stores the prefix
+/// it answered at. A core that answered somewhere else altogether is an
+/// error: the base URL is the operator's to change.
+async fn detect(app: &AppHandle, store: &SettingsStore, profile_id: &str) -> Result<Value, String> {
+    let profile = store
+        .snapshot()
+        .profile(profile_id)
+        .cloned()
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    let base = profile.base_url.trim().trim_end_matches('/').to_string();
+    let url = Url::parse(&base)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    let found = setup::find_core(&url).await?;
+    if found.root != base {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "The core answered at {}; change the profile's base URL to it",
+            found.base_url()
+        )));
+    }
+    let previous = profile.api_prefix.clone();
+    store.update(app, |settings| {
+        let profile = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+        // Moved while the probe ran; the new address gets its own.
+        if profile.base_url.trim().trim_end_matches('/') == base {
+            profile.api_prefix = Some(found.prefix.clone());
+        }
+        Ok(())
+    })?;
+    let result = json!({
+        "profile_id": profile_id,
+        "api_prefix": found.prefix,
+        "source": found.source,
+        "previous": previous,
+    });
+    if previous.as_deref() != Some(found.prefix.as_str()) {
+        if let Some(db) = app.try_state::<LocalDb>() {
+            if let Ok(conn) = db.lock() {
+                let _ =
+                    db::record_audit(&conn, profile_id, "api_prefix", "detected", &base, &result);
+            }
+        }
+        let _ = environment::emit(app, DETECTED_EVENT, &result);
+    }
+    Ok(result)
+}
+
+/// Finds the prefix of every profile that has none yet: new ones, those
+/// from before prefixes were detected, and those whose base URL changed.
+/// Cores that cannot be found are tried again after `RETRY_AFTER`, or as
+/// soon as their base URL changes.
+pub fn spawn_detector(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let store = app.state::<SettingsStore>();
+            let pending: Vec<Profile> = store
+                .snapshot()
+                .profiles
+                .into_iter()
+                .filter(|p| p.api_prefix.is_none())
+                .filter(|p| {
+                    Url::parse(p.base_url.trim())
+                        .is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+                })
+                .collect();
+            for profile in pending {
+                let recent = missed().lock().ok().is_some_and(|missed| {
+                    missed.get(&profile.id).is_some_and(|(base, at)| {
+                        *base == profile.base_url && at.elapsed() < RETRY_AFTER
+                    })
+                });
+                if recent {
+                    continue;
+                }
+                if let Err(err) = detect(&app, &store, &profile.id).await {
+                    if let Ok(mut missed) = missed().lock() {
+                        missed.insert(profile.id.clone(), (profile.base_url, Instant::now()));
+                    }
+                    tasks::failed(TASK, &format!("{}: {}", profile.id, err));
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Probes `profile` (or the active one) for the path its core's API is
+/// mounted under, following gateway hints and redirects, and stores it on
+/// the profile so requests go there without a hand-tuned base URL.
+#[tauri::command]
+pub async fn detect_api_prefix(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let profile_id = profile.unwrap_or_else(|| store.snapshot().active_profile.clone());
+    let result = detect(&app, &store, &profile_id).await?;
+    if let Ok(mut missed) = missed().lock() {
+        missed.remove(&profile_id);
+    }
+    Ok(result)
+}
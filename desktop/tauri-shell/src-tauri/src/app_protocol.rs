@@ -0,0 +1,252 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use reqwest::Url;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::webview::NewWindowResponse;
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, WebviewUrl, WebviewWindowBuilder};
+
+use crate::cache_protocol;
+use crate::db::{self, LocalDb};
+use crate::settings::SettingsStore;
+use crate::thumbnails;
+
+mod manifest {
+    include!(concat!(env!("OUT_DIR"), "/asset_manifest.rs"));
+}
+
+pub const SCHEME: &str = "novaadapt";
+const MAIN_WINDOW: &str = "main";
+const ENTRY: &str = "index.html";
+
+/// Assets whose embedded bytes do not match the hash recorded when the
+/// app was built, filled in once at startup.
+static TAMPERED: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Where the webview loads the frontend from. Windows serves custom
+/// schemes over `http://<scheme>.localhost`.
+fn origin() -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    }
+}
+
+fn custom_origin(scheme: &str) -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost", scheme)
+    } else {
+        format!("{}:", scheme)
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn expected(path: &str) -> Option<&'static str> {
+    manifest::ASSETS
+        .iter()
+        .find(|(key, _)| *key == path)
+        .map(|(_, hash)| *hash)
+}
+
+/// Hashes every embedded frontend asset against the build's manifest.
+/// A mismatch means the install was modified after it was built; the
+/// approval UI is then not served at all.
+pub fn verify<R: Runtime>(app: &AppHandle<R>) -> &'static [String] {
+    TAMPERED.get_or_init(|| {
+        let resolver = app.asset_resolver();
+        let tampered: Vec<String> = manifest::ASSETS
+            .iter()
+            .filter(|(path, hash)| {
+                resolver
+                    .get(path.to_string())
+                    .is_none_or(|asset| sha256_hex(&asset.bytes) != *hash)
+            })
+            .map(|(path, _)| path.to_string())
+            .collect();
+        if !tampered.is_empty() {
+            eprintln!(
+                "frontend assets do not match this build, refusing to load them: {}",
+                tampered.join(", ")
+            );
+        }
+        tampered
+    })
+}
+
+/// The policy the frontend runs under: nothing but its own bundled
+/// scripts and styles, the IPC channel, thumbnails and the local cache
+/// (images, media, fetches, and rendered diffs in frames), and images from
+/// the configured cores (artifact previews). Inline style attributes stay
+/// allowed for the positions the views compute; inline scripts do not.
+fn content_security_policy<R: Runtime>(app: &AppHandle<R>) -> String {
+    let cache = custom_origin(cache_protocol::SCHEME);
+    let mut images = vec![
+        "'self'".to_string(),
+        custom_origin(thumbnails::SCHEME),
+        cache.clone(),
+    ];
+    if let Some(store) = app.try_state::<SettingsStore>() {
+        for profile in store.snapshot().profiles {
+            for origin in profile.endpoints().filter_map(crate::net::base_origin) {
+                if !images.contains(&origin) {
+                    images.push(origin);
+                }
+            }
+        }
+    }
+    [
+        "default-src 'none'".to_string(),
+        "script-src 'self'".to_string(),
+        "style-src 'self'".to_string(),
+        "style-src-attr 'unsafe-inline'".to_string(),
+        format!("img-src {}", images.join(" ")),
+        format!("media-src {}", cache),
+        format!("connect-src 'self' {} {}", custom_origin("ipc"), cache),
+        "font-src 'self'".to_string(),
+        "object-src 'none'".to_string(),
+        "base-uri 'none'".to_string(),
+        "form-action 'none'".to_string(),
+        format!("frame-src {}", cache),
+        "frame-ancestors 'none'".to_string(),
+    ]
+    .join("; ")
+}
+
+const BLOCKED_PAGE: &str =
+    "<!doctype html><html><head><meta charset=\"utf-8\"><title>NovaAdapt Desktop</title></head>\
+<body><h1>This installation has been modified</h1>\
+<p>The app's interface files do not match the ones it was built with, so it will not load them. \
+Reinstall NovaAdapt Desktop from a trusted source.</p></body></html>";
+
+fn respond(
+    status: StatusCode,
+    mime: &str,
+    csp: &str,
+    body: Vec<u8>,
+) -> Response<Cow<'static, [u8]>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CONTENT_SECURITY_POLICY, csp)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::REFERRER_POLICY, "no-referrer")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Cow::Owned(body))
+        .unwrap_or_default()
+}
+
+/// Serves `novaadapt://localhost/<path>` from the bundled frontend: only
+/// paths in the build's manifest, only when the startup check passed, and
+/// only bytes that still hash to the manifest, each under a strict CSP.
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+) -> Response<Cow<'static, [u8]>> {
+    let app = ctx.app_handle();
+    let path = request.uri().path().trim_start_matches('/');
+    let path = if path.is_empty() { ENTRY } else { path };
+    let blocked_csp = "default-src 'none'";
+    if !verify(app).is_empty() {
+        return if path == ENTRY {
+            respond(
+                StatusCode::OK,
+                "text/html",
+                blocked_csp,
+                BLOCKED_PAGE.as_bytes().to_vec(),
+            )
+        } else {
+            respond(StatusCode::FORBIDDEN, "text/plain", blocked_csp, Vec::new())
+        };
+    }
+    let Some(hash) = expected(path) else {
+        return respond(StatusCode::NOT_FOUND, "text/plain", blocked_csp, Vec::new());
+    };
+    match app.asset_resolver().get(path.to_string()) {
+        Some(asset) if sha256_hex(&asset.bytes) == hash => respond(
+            StatusCode::OK,
+            &asset.mime_type,
+            &content_security_policy(app),
+            asset.bytes,
+        ),
+        _ => respond(StatusCode::FORBIDDEN, "text/plain", blocked_csp, Vec::new()),
+    }
+}
+
+fn allowed(url: &Url) -> bool {
+    let origin = origin();
+    url.as_str() == origin || url.as_str().starts_with(&format!("{}/", origin))
+}
+
+fn blocked(app: &AppHandle, kind: &str, url: &Url) {
+    eprintln!("blocked webview {} to {}", kind, url);
+    let settings = app.state::<SettingsStore>().snapshot();
+    if let Some(db) = app.try_state::<LocalDb>() {
+        if let Ok(guard) = db.lock() {
+            let _ = db::record_audit(
+                &guard,
+                &settings.active_profile,
+                "webview",
+                kind,
+                url.as_str(),
+                &json!({ "blocked": true }),
+            );
+        }
+    }
+}
+
+/// Opens the main window on the app's own scheme. Navigation anywhere else
+/// and `window.open` are refused and audited, so a link cannot replace the
+/// approval UI with a remote page.
+pub fn open_main_window(app: &AppHandle) -> tauri::Result<()> {
+    let Some(mut config) = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == MAIN_WINDOW)
+        .cloned()
+    else {
+        return Ok(());
+    };
+    let entry = Url::parse(&format!("{}/{}", origin(), ENTRY)).expect("app origin parses");
+    config.url = WebviewUrl::CustomProtocol(entry);
+    let navigating = app.clone();
+    let opening = app.clone();
+    WebviewWindowBuilder::from_config(app, &config)?
+        .on_navigation(move |url| {
+            let ok = allowed(url);
+            if !ok {
+                blocked(&navigating, "navigation_blocked", url);
+            }
+            ok
+        })
+        .on_new_window(move |url, _| {
+            blocked(&opening, "new_window_blocked", &url);
+            NewWindowResponse::Deny
+        })
+        .build()?;
+    Ok(())
+}
+
+/// Whether the bundled frontend matched its build at startup, and the
+/// policy it is served under.
+#[tauri::command]
+pub fn get_asset_integrity(app: AppHandle) -> Value {
+    let tampered = verify(&app);
+    json!({
+        "ok": tampered.is_empty(),
+        "assets": manifest::ASSETS.len(),
+        "tampered": tampered,
+        "origin": origin(),
+        "content_security_policy": content_security_policy(&app),
+    })
+}
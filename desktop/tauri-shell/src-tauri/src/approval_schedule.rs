@@ -0,0 +1,549 @@
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::decisions;
+use crate::errors::{self, ErrorCode};
+use crate::reconcile::{self, Reconciled};
+use crate::settings::{EnvironmentClass, SettingsStore, WindowAction};
+use crate::{environment, exec_windows, notify, tasks};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(15);
+const FIRED_EVENT: &str = "schedule:fired";
+/// A schedule the shell missed by more than this (it was not running) is
+/// skipped rather than approved outside the window it was meant for.
+pub const DEFAULT_GRACE_MINUTES: u32 = 30;
+const MAX_GRACE_MINUTES: u32 = 24 * 60;
+
+struct Due {
+    id: i64,
+    profile_id: String,
+    plan_id: String,
+    execute: bool,
+    fire_at: DateTime<Utc>,
+    version: Option<String>,
+    grace_minutes: u32,
+    /// The plan when the approval was queued, for reconciling.
+    base: Option<Value>,
+}
+
+fn stamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Fires due approvals every `TICK_INTERVAL`. Schedules live in the local
+/// database, so those set before a restart still fire; one left `firing` by
+/// a crash mid-send is marked failed instead of being sent twice.
+const TASK: &str = "approval_schedule";
+
+pub fn spawn_scheduler(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        if let Ok(conn) = app.state::<LocalDb>().lock() {
+            let _ = conn.execute(
+                "UPDATE scheduled_approvals SET status = 'failed', fired_at = ?1,
+                     outcome = '{\"error\":\"Interrupted while firing\"}'
+                 WHERE status = 'firing'",
+                [stamp(Utc::now())],
+            );
+        }
+        loop {
+            let due = match app.state::<LocalDb>().lock() {
+                Ok(conn) => due_now(&conn, Utc::now()).unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            for entry in due {
+                fire(&app, entry).await;
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+/// Claims every schedule whose time has come by moving it to `firing`.
+fn due_now(conn: &Connection, now: DateTime<Utc>) -> rusqlite::Result<Vec<Due>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, plan_id, execute, fire_at, version, grace_minutes, base
+         FROM scheduled_approvals WHERE status = 'scheduled' ORDER BY fire_at",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, Option<String>>(7)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut due = Vec::new();
+    for (id, profile_id, plan_id, execute, fire_at, version, grace_minutes, base) in rows {
+        let Some(fire_at) = DateTime::parse_from_rfc3339(&fire_at)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+        else {
+            continue;
+        };
+        if fire_at > now {
+            continue;
+        }
+        conn.execute(
+            "UPDATE scheduled_approvals SET status = 'firing' WHERE id = ?1",
+            [id],
+        )?;
+        due.push(Due {
+            id,
+            profile_id,
+            plan_id,
+            execute,
+            fire_at,
+            version,
+            grace_minutes,
+            base: base.and_then(|b| serde_json::from_str(&b).ok()),
+        });
+    }
+    Ok(due)
+}
+
+/// Re-reads the plan just before approving and reconciles it with the plan
+/// as it was when the approval was queued: a clean approval is sent, one
+/// the plan has already reached is skipped, and a conflicting one is held
+/// as `conflict` until the operator resolves it.
+async fn fire(app: &AppHandle, entry: Due) {
+    let now = Utc::now();
+    let late = now - entry.fire_at > chrono::Duration::minutes(i64::from(entry.grace_minutes));
+    let (status, outcome) = if late {
+        let reason = format!(
+            "Missed by more than {} minutes while the shell was not running",
+            entry.grace_minutes
+        );
+        ("skipped", json!({ "reason": reason }))
+    } else {
+        match attempt(app, &entry).await {
+            Ok(result) => ("fired", json!({ "result": result })),
+            Err(Skip::Stale(reason)) => ("skipped", json!({ "reason": reason })),
+            Err(Skip::Conflict(conflict)) => ("conflict", json!({ "conflict": conflict })),
+            Err(Skip::Failed(error)) => ("failed", json!({ "error": error })),
+        }
+    };
+
+    let db = app.state::<LocalDb>();
+    if let Ok(conn) = db.lock() {
+        let _ = conn.execute(
+            "UPDATE scheduled_approvals SET status = ?1, fired_at = ?2, outcome = ?3
+             WHERE id = ?4",
+            params![status, stamp(now), outcome.to_string(), entry.id],
+        );
+        let _ = db::record_audit(
+            &conn,
+            &entry.profile_id,
+            "approval_schedule",
+            status,
+            &entry.plan_id,
+            &json!({ "schedule_id": entry.id, "execute": entry.execute, "outcome": outcome }),
+        );
+    }
+    let payload = json!({
+        "id": entry.id,
+        "profile_id": entry.profile_id,
+        "plan_id": entry.plan_id,
+        "execute": entry.execute,
+        "status": status,
+        "outcome": outcome,
+    });
+    let _ = environment::emit(app, FIRED_EVENT, &payload);
+    let body = match status {
+        "fired" => format!("Plan {} was approved as scheduled", entry.plan_id),
+        "skipped" => format!(
+            "Scheduled approval of plan {} was skipped: {}",
+            entry.plan_id,
+            outcome["reason"].as_str().unwrap_or_default()
+        ),
+        "conflict" => format!(
+            "Plan {} changed after its approval was queued; review it to apply or discard the approval",
+            entry.plan_id
+        ),
+        _ => format!(
+            "Scheduled approval of plan {} failed: {}",
+            entry.plan_id,
+            outcome["error"].as_str().unwrap_or_default()
+        ),
+    };
+    notify::notify(
+        app,
+        "approval_schedule",
+        "Scheduled approval",
+        &body,
+        payload,
+    );
+}
+
+enum Skip {
+    Stale(String),
+    Conflict(Value),
+    Failed(String),
+}
+
+async fn attempt(app: &AppHandle, entry: &Due) -> Result<Value, Skip> {
+    let conn = app
+        .state::<SettingsStore>()
+        .connection(Some(&entry.profile_id))
+        .map_err(Skip::Failed)?;
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", entry.plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    .map_err(Skip::Failed)?;
+    let current = match reconcile::reconcile(
+        "approve",
+        entry.base.as_ref(),
+        entry.version.as_deref(),
+        &plan,
+    ) {
+        Reconciled::Clean => decisions::plan_version(&plan),
+        Reconciled::AlreadyApplied(status) => {
+            return Err(Skip::Stale(format!("Plan is already {}", status)))
+        }
+        Reconciled::Conflict(conflict) => return Err(Skip::Conflict(conflict)),
+    };
+    // Sent against the version just reconciled, so a change racing this
+    // send is caught as a conflict too.
+    decisions::send_decision(
+        &conn.base_url,
+        conn.token,
+        &entry.plan_id,
+        "approve",
+        Some(json!({ "execute": entry.execute })),
+        current.as_deref(),
+        None,
+    )
+    .await
+    .map_err(|err| match errors::code_of(&err) {
+        Some(ErrorCode::PlanConflict) => Skip::Conflict(json!({
+            "reason": "Plan changed while the approval was being sent",
+            "decision": "approve",
+            "base_version": current,
+            "current_version": Value::Null,
+            "fields": [],
+        })),
+        _ => Skip::Failed(err),
+    })
+}
+
+/// Adds a schedule and returns its id. Also used at shutdown to keep
+/// approvals still in their undo window for the next launch.
+#[allow(clippy::too_many_arguments)]
+pub fn insert(
+    conn: &Connection,
+    profile_id: &str,
+    plan_id: &str,
+    execute: bool,
+    fire_at: DateTime<Utc>,
+    version: Option<&str>,
+    grace_minutes: u32,
+    base: Option<&Value>,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO scheduled_approvals
+             (profile_id, plan_id, execute, fire_at, version, grace_minutes, created_at, base)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            profile_id,
+            plan_id,
+            execute,
+            stamp(fire_at),
+            version,
+            grace_minutes,
+            stamp(Utc::now()),
+            base.map(Value::to_string),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Schedules approval of a pending plan at `at` (RFC 3339). The plan's
+/// current version is pinned unless `pin_version` is false, so a plan edited
+/// in the meantime is skipped rather than approved unseen. A schedule missed
+/// by more than `grace_minutes` (default 30) is skipped too.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn schedule_approval(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    at: String,
+    execute: Option<bool>,
+    pin_version: Option<bool>,
+    grace_minutes: Option<u32>,
+) -> Result<Value, String> {
+    crate::safe_mode::check("Scheduling approvals")?;
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let fire_at = DateTime::parse_from_rfc3339(at.trim())
+        .map_err(|_| ErrorCode::InvalidInput.with("`at` must be RFC 3339"))?
+        .with_timezone(&Utc);
+    if fire_at <= Utc::now() {
+        return Err(ErrorCode::InvalidInput.with("Scheduled time must be in the future"));
+    }
+    let grace_minutes = grace_minutes
+        .unwrap_or(DEFAULT_GRACE_MINUTES)
+        .clamp(1, MAX_GRACE_MINUTES);
+    let execute = execute.unwrap_or(false);
+    let conn = store.connection(profile.as_deref())?;
+    let prod = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .is_some_and(|p| p.environment == EnvironmentClass::Prod);
+    if execute && prod {
+        return Err(ErrorCode::InvalidInput.with(
+            "Executing on a prod profile needs a typed confirmation when sent; schedule without executing",
+        ));
+    }
+    // A window or calendar freeze known to cover the time is reported now
+    // rather than when the schedule fires unattended.
+    let window = execute
+        .then(|| exec_windows::window_at(&conn.base_url, fire_at))
+        .flatten();
+    if let Some(window) = window.as_ref().filter(|w| w.action == WindowAction::Block) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Execution is blocked at that time by {}; schedule without executing or pick another time",
+            if window.name.is_empty() { "an execution window" } else { window.name.as_str() }
+        )));
+    }
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    if plan["status"].as_str() != Some("pending") {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Plan {} is {}, only pending plans can be scheduled",
+            plan_id,
+            plan["status"].as_str().unwrap_or("unknown")
+        )));
+    }
+    let version = if pin_version.unwrap_or(true) {
+        decisions::plan_version(&plan)
+    } else {
+        None
+    };
+
+    let guard = db.lock()?;
+    let id = insert(
+        &guard,
+        &conn.profile_id,
+        &plan_id,
+        execute,
+        fire_at,
+        version.as_deref(),
+        grace_minutes,
+        Some(&reconcile::snapshot(&plan)),
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let _ = db::record_audit(
+        &guard,
+        &conn.profile_id,
+        "approval_schedule",
+        "scheduled",
+        &plan_id,
+        &json!({ "schedule_id": id, "fire_at": stamp(fire_at), "execute": execute }),
+    );
+    Ok(json!({
+        "id": id,
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "execute": execute,
+        "fire_at": stamp(fire_at),
+        "version": version,
+        "grace_minutes": grace_minutes,
+        "status": "scheduled",
+        "execution_window": window.map(|w| json!({ "name": w.name, "action": w.action })),
+    }))
+}
+
+/// Lists a profile's scheduled approvals, including those held in conflict;
+/// finished ones (fired, skipped, failed, cancelled) only with
+/// `include_done`.
+#[tauri::command]
+pub fn list_scheduled_approvals(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    include_done: Option<bool>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let guard = db.lock()?;
+    let mut stmt = guard
+        .prepare(
+            "SELECT id, plan_id, execute, fire_at, version, status, created_at, fired_at, outcome
+             FROM scheduled_approvals
+             WHERE profile_id = ?1 AND (?2 OR status IN ('scheduled', 'firing', 'conflict'))
+             ORDER BY fire_at DESC LIMIT 500",
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let items = stmt
+        .query_map(
+            params![conn.profile_id, include_done.unwrap_or(false)],
+            |row| {
+                Ok(json!({
+                    "id": row.get::<_, i64>(0)?,
+                    "plan_id": row.get::<_, String>(1)?,
+                    "execute": row.get::<_, bool>(2)?,
+                    "fire_at": row.get::<_, String>(3)?,
+                    "version": row.get::<_, Option<String>>(4)?,
+                    "status": row.get::<_, String>(5)?,
+                    "created_at": row.get::<_, String>(6)?,
+                    "fired_at": row.get::<_, Option<String>>(7)?,
+                    "outcome": row
+                        .get::<_, Option<String>>(8)?
+                        .and_then(|o| serde_json::from_str::<Value>(&o).ok()),
+                }))
+            },
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    Ok(json!({ "profile_id": conn.profile_id, "schedules": items }))
+}
+
+/// Cancels a schedule that has not fired yet.
+#[tauri::command]
+pub fn cancel_scheduled_approval(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    id: i64,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let guard = db.lock()?;
+    let plan_id: Option<String> = guard
+        .query_row(
+            "UPDATE scheduled_approvals SET status = 'cancelled', fired_at = ?1
+             WHERE id = ?2 AND profile_id = ?3 AND status = 'scheduled'
+             RETURNING plan_id",
+            params![stamp(Utc::now()), id, conn.profile_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let plan_id = plan_id.ok_or_else(|| {
+        ErrorCode::NotFound.with(format!("Schedule {} already fired or was cancelled", id))
+    })?;
+    let _ = db::record_audit(
+        &guard,
+        &conn.profile_id,
+        "approval_schedule",
+        "cancelled",
+        &plan_id,
+        &json!({ "schedule_id": id }),
+    );
+    Ok(json!({ "id": id, "plan_id": plan_id, "status": "cancelled" }))
+}
+
+/// Settles an approval held in conflict: `apply` approves the plan as it is
+/// now (the operator has reviewed the change), `discard` drops the approval.
+#[tauri::command]
+pub async fn resolve_schedule_conflict(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    id: i64,
+    resolution: String,
+) -> Result<Value, String> {
+    let apply = match resolution.trim() {
+        "apply" => true,
+        "discard" => false,
+        other => {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Unknown resolution: {} (use apply or discard)",
+                other
+            )))
+        }
+    };
+    let conn = store.connection(profile.as_deref())?;
+    let claimed: Option<(String, bool)> = db
+        .lock()?
+        .query_row(
+            "UPDATE scheduled_approvals SET status = 'firing'
+             WHERE id = ?1 AND profile_id = ?2 AND status = 'conflict'
+             RETURNING plan_id, execute",
+            params![id, conn.profile_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let (plan_id, execute) = claimed.ok_or_else(|| {
+        ErrorCode::NotFound.with(format!("Schedule {} is not waiting on a conflict", id))
+    })?;
+
+    let (status, outcome) = if apply {
+        let result = async {
+            let plan = crate::request_json(
+                Method::GET,
+                &conn.base_url,
+                &format!("/plans/{}", plan_id),
+                conn.token.clone(),
+                None,
+            )
+            .await?;
+            decisions::send_decision(
+                &conn.base_url,
+                conn.token.clone(),
+                &plan_id,
+                "approve",
+                Some(json!({ "execute": execute })),
+                decisions::plan_version(&plan).as_deref(),
+                None,
+            )
+            .await
+        }
+        .await;
+        match result {
+            Ok(result) => ("fired", json!({ "resolution": "apply", "result": result })),
+            Err(error) => ("failed", json!({ "resolution": "apply", "error": error })),
+        }
+    } else {
+        ("cancelled", json!({ "resolution": "discard" }))
+    };
+
+    let guard = db.lock()?;
+    guard
+        .execute(
+            "UPDATE scheduled_approvals SET status = ?1, fired_at = ?2, outcome = ?3
+             WHERE id = ?4",
+            params![status, stamp(Utc::now()), outcome.to_string(), id],
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let _ = db::record_audit(
+        &guard,
+        &conn.profile_id,
+        "approval_schedule",
+        if apply {
+            "conflict_applied"
+        } else {
+            "conflict_discarded"
+        },
+        &plan_id,
+        &json!({ "schedule_id": id, "status": status, "outcome": outcome }),
+    );
+    Ok(json!({ "id": id, "plan_id": plan_id, "status": status, "outcome": outcome }))
+}
@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use std::io::{Read, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_RANGE, COOKIE, RANGE};
+use reqwest::{Method, StatusCode, Url};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection as CoreConnection, SettingsStore};
+use crate::{
+    allowlist, api_prefix, bandwidth, cache_protocol, cookies, environment, maintenance, net,
+    permissions, residency, ssrf, tenants, tls,
+};
+
+const PROGRESS_EVENT: &str = "artifact:progress";
+/// Content-defined chunking: a cut falls where the low 16 bits of the
+/// rolling gear hash are zero, about every 64 KiB, but never before
+/// `MIN_CHUNK` or after `MAX_CHUNK`. Cores chunking their manifests the
+/// same way let files fetched whole be reused too.
+const MIN_CHUNK: usize = 16 * 1024;
+const MAX_CHUNK: usize = 256 * 1024;
+const CUT_MASK: u64 = (1 << 16) - 1;
+const MAX_MANIFEST_CHUNK: u64 = 16 * 1024 * 1024;
+/// Missing chunks next to each other are fetched in one range request of
+/// up to this much.
+const MAX_SPAN: u64 = 8 * 1024 * 1024;
+const SPAN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const WHOLE_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+const READ_BLOCK: usize = 1024 * 1024;
+/// The artifact is assembled beside its destination under this suffix and
+/// only renamed over it once its digest checks out.
+const PARTIAL_SUFFIX: &str = ".novaadapt-partial";
+
+/// What a core publishes, at `<path>.chunks` by default, for an artifact
+/// it serves with range requests.
+#[derive(Deserialize)]
+struct Manifest {
+    size: u64,
+    sha256: String,
+    chunks: Vec<ManifestChunk>,
+}
+
+#[derive(Deserialize)]
+struct ManifestChunk {
+    length: u64,
+    sha256: String,
+}
+
+#[derive(Clone)]
+struct Chunk {
+    offset: u64,
+    length: u64,
+    sha256: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn is_digest(raw: &str) -> bool {
+    raw.len() == 64 && raw.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The gear hash's table: splitmix64 from a zero seed, so every build and
+/// every core cuts in the same places.
+fn gear() -> &'static [u64; 256] {
+    static GEAR: OnceLock<[u64; 256]> = OnceLock::new();
+    GEAR.get_or_init(|| {
+        let mut state = 0u64;
+        let mut table = [0u64; 256];
+        for entry in &mut table {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *entry = z ^ (z >> 31);
+        }
+        table
+    })
+}
+
+/// Splits bytes into content-defined chunks as they are fed, so an edit
+/// only changes the chunks around it rather than every one after it.
+#[derive(Default)]
+struct Chunker {
+    hash: u64,
+    length: usize,
+    offset: u64,
+    digest: Sha256,
+    chunks: Vec<Chunk>,
+}
+
+impl Chunker {
+    fn feed(&mut self, data: &[u8]) {
+        let gear = gear();
+        let mut start = 0;
+        for (i, byte) in data.iter().enumerate() {
+            self.hash = (self.hash << 1).wrapping_add(gear[*byte as usize]);
+            self.length += 1;
+            if (self.length >= MIN_CHUNK && self.hash & CUT_MASK == 0) || self.length >= MAX_CHUNK {
+                self.digest.update(&data[start..=i]);
+                self.cut();
+                start = i + 1;
+            }
+        }
+        self.digest.update(&data[start..]);
+    }
+
+    fn cut(&mut self) {
+        if self.length == 0 {
+            return;
+        }
+        let digest = std::mem::take(&mut self.digest).finalize();
+        self.chunks.push(Chunk {
+            offset: self.offset,
+            length: self.length as u64,
+            sha256: hex(&digest),
+        });
+        self.offset += self.length as u64;
+        self.length = 0;
+        self.hash = 0;
+    }
+
+    fn finish(mut self) -> Vec<Chunk> {
+        self.cut();
+        self.chunks
+    }
+}
+
+/// The size, digest and content-defined chunks of the file at `path`.
+fn index_file(path: &Path) -> std::io::Result<(u64, String, Vec<Chunk>)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut chunker = Chunker::default();
+    let mut whole = Sha256::new();
+    let mut size = 0u64;
+    let mut block = vec![0u8; READ_BLOCK];
+    loop {
+        let read = file.read(&mut block)?;
+        if read == 0 {
+            break;
+        }
+        chunker.feed(&block[..read]);
+        whole.update(&block[..read]);
+        size += read as u64;
+    }
+    Ok((size, hex(&whole.finalize()), chunker.finish()))
+}
+
+async fn index_blocking(path: PathBuf) -> Result<(u64, String, Vec<Chunk>), String> {
+    tauri::async_runtime::spawn_blocking(move || index_file(&path))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(e.to_string()))?
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read artifact failed: {}", e)))
+}
+
+fn plan_chunks(manifest: &Manifest) -> Result<Vec<Chunk>, String> {
+    if !is_digest(&manifest.sha256) {
+        return Err(ErrorCode::CoreError.with("Artifact manifest has no valid sha256"));
+    }
+    let mut offset = 0u64;
+    let mut chunks = Vec::with_capacity(manifest.chunks.len());
+    for chunk in &manifest.chunks {
+        if chunk.length == 0 || chunk.length > MAX_MANIFEST_CHUNK || !is_digest(&chunk.sha256) {
+            return Err(ErrorCode::CoreError.with(format!(
+                "Artifact manifest chunk at {} is invalid (1 to {} bytes with a sha256)",
+                offset, MAX_MANIFEST_CHUNK
+            )));
+        }
+        chunks.push(Chunk {
+            offset,
+            length: chunk.length,
+            sha256: chunk.sha256.to_ascii_lowercase(),
+        });
+        offset += chunk.length;
+    }
+    if offset != manifest.size {
+        return Err(ErrorCode::CoreError.with(format!(
+            "Artifact manifest chunks cover {} bytes of {}",
+            offset, manifest.size
+        )));
+    }
+    Ok(chunks)
+}
+
+fn storage(e: rusqlite::Error) -> String {
+    ErrorCode::LocalStorage.with(e.to_string())
+}
+
+fn local_io(what: &str) -> impl Fn(std::io::Error) -> String + '_ {
+    move |e| ErrorCode::LocalIo.with(format!("{} failed: {}", what, e))
+}
+
+/// Where each chunk in `chunks` can already be read from: the files synced
+/// before that hold it.
+fn sources(
+    conn: &Connection,
+    chunks: &[Chunk],
+) -> rusqlite::Result<HashMap<String, Vec<(String, u64)>>> {
+    let mut stmt = conn.prepare(
+        "SELECT destination, offset FROM artifact_chunks WHERE sha256 = ?1 AND length = ?2",
+    )?;
+    let mut found: HashMap<String, Vec<(String, u64)>> = HashMap::new();
+    for chunk in chunks {
+        if found.contains_key(&chunk.sha256) {
+            continue;
+        }
+        let held = stmt
+            .query_map(params![chunk.sha256, chunk.length as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if !held.is_empty() {
+            found.insert(chunk.sha256.clone(), held);
+        }
+    }
+    Ok(found)
+}
+
+/// Replaces what the index holds for `destination` with `chunks`.
+fn record_chunks(
+    conn: &mut Connection,
+    destination: &str,
+    chunks: &[Chunk],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM artifact_chunks WHERE destination = ?1",
+        [destination],
+    )?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO artifact_chunks (sha256, length, destination, offset) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for chunk in chunks {
+            insert.execute(params![
+                chunk.sha256,
+                chunk.length as i64,
+                destination,
+                chunk.offset as i64
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// A GET for `path` on the core, for a byte `range` of it if given, held
+/// to the same checks as the shell's other requests.
+async fn download(
+    conn: &CoreConnection,
+    path: &str,
+    range: Option<(u64, u64)>,
+    timeout: Duration,
+) -> Result<(Url, reqwest::Response), String> {
+    let base = conn.base_url.trim().trim_end_matches('/');
+    let scoped = tenants::scope(base, path);
+    let prefix = match &scoped {
+        Some(tenants::Scope::Prefix(prefix)) => prefix.as_str(),
+        _ => "",
+    };
+    let mounted = api_prefix::for_request(base, path);
+    let url = Url::parse(&format!("{}{}{}{}", base, mounted, prefix, path))
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ErrorCode::InvalidInput.with("Artifact sync needs an http or https core"));
+    }
+    allowlist::check(base)?;
+    permissions::check(&Method::GET, base, path, conn.token.as_deref())?;
+    maintenance::check(&Method::GET, base)?;
+    ssrf::check_url(base, &url)?;
+
+    let client = net::client_for(&url)?;
+    let mut request =
+        net::authorize(client.get(url.clone()), &url, conn.token.clone())?.timeout(timeout);
+    if let Some(cookie) = cookies::header_for(&url) {
+        request = request.header(COOKIE, cookie);
+    }
+    if let Some(tenants::Scope::Header(name, value)) = scoped {
+        request = request.header(name, value);
+    }
+    if let Some((start, end)) = range {
+        request = request.header(RANGE, format!("bytes={}-{}", start, end));
+    }
+    let mut request = request
+        .build()
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid request: {}", e)))?;
+    net::sign(&mut request).await?;
+    let response = client.execute(request).await.map_err(|e| {
+        if let Some(reason) = tls::violation(&e) {
+            ErrorCode::TlsPolicy.with(format!("TLS handshake failed: {}", reason))
+        } else if e.is_timeout() {
+            ErrorCode::CoreTimeout.with(format!("Artifact download timed out: {}", e))
+        } else {
+            ErrorCode::CoreUnreachable.with(format!("Artifact download failed: {}", e))
+        }
+    })?;
+    cookies::store_from(&url, response.headers());
+    let status = response.status();
+    if !status.is_success() {
+        bandwidth::opened(
+            base,
+            bandwidth::DOWNLOADS,
+            bandwidth::request_bytes(&Method::GET, &url, &[], None),
+            bandwidth::response_bytes(response.headers(), 0),
+        );
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "Core API {}: artifact download refused",
+            status.as_u16()
+        )));
+    }
+    Ok((url, response))
+}
+
+/// Counts a finished download's `received` body bytes.
+fn counted(base_url: &str, url: &Url, response: &reqwest::Response, received: u64) {
+    bandwidth::opened(
+        base_url,
+        bandwidth::DOWNLOADS,
+        bandwidth::request_bytes(&Method::GET, url, &[], None),
+        bandwidth::response_bytes(response.headers(), received),
+    );
+}
+
+/// Fetches the whole artifact into `partial`.
+async fn fetch_whole(conn: &CoreConnection, path: &str, partial: &Path) -> Result<u64, String> {
+    let (url, mut response) = download(conn, path, None, WHOLE_TIMEOUT).await?;
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(local_io("Write artifact"))?;
+    let mut received = 0u64;
+    while let Some(block) = response
+        .chunk()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("Artifact download cut: {}", e)))?
+    {
+        file.write_all(&block)
+            .await
+            .map_err(local_io("Write artifact"))?;
+        received += block.len() as u64;
+    }
+    file.flush().await.map_err(local_io("Write artifact"))?;
+    counted(&conn.base_url, &url, &response, received);
+    Ok(received)
+}
+
+/// What a range request came back with.
+enum Span {
+    Fetched(Vec<u8>),
+    /// The core answered with the whole artifact instead.
+    Ignored,
+}
+
+async fn fetch_span(
+    conn: &CoreConnection,
+    path: &str,
+    start: u64,
+    length: u64,
+) -> Result<Span, String> {
+    let end = start + length - 1;
+    let (url, mut response) = download(conn, path, Some((start, end)), SPAN_TIMEOUT).await?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        counted(&conn.base_url, &url, &response, 0);
+        return Ok(Span::Ignored);
+    }
+    let answered = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes "))
+        .and_then(|v| v.split('/').next())
+        .map(str::to_string);
+    if answered.as_deref() != Some(format!("{}-{}", start, end).as_str()) {
+        return Err(ErrorCode::CoreError.with(format!(
+            "Core answered range {} for bytes {}-{}",
+            answered.unwrap_or_default(),
+            start,
+            end
+        )));
+    }
+    let mut body = Vec::with_capacity(length as usize);
+    while let Some(block) = response
+        .chunk()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("Artifact download cut: {}", e)))?
+    {
+        body.extend_from_slice(&block);
+        if body.len() as u64 > length {
+            break;
+        }
+    }
+    counted(&conn.base_url, &url, &response, body.len() as u64);
+    if body.len() as u64 != length {
+        return Err(ErrorCode::CoreError.with(format!(
+            "Core sent {} bytes for a {}-byte range",
+            body.len(),
+            length
+        )));
+    }
+    Ok(Span::Fetched(body))
+}
+
+/// Reads `chunk` from one of the files that held it when they were synced,
+/// checking it still does.
+async fn reuse(sources: &[(String, u64)], chunk: &Chunk) -> Option<Vec<u8>> {
+    for (file, offset) in sources {
+        let Ok(mut source) = tokio::fs::File::open(file).await else {
+            continue;
+        };
+        let mut bytes = vec![0u8; chunk.length as usize];
+        if source.seek(SeekFrom::Start(*offset)).await.is_err()
+            || source.read_exact(&mut bytes).await.is_err()
+        {
+            continue;
+        }
+        if hex(&Sha256::digest(&bytes)) == chunk.sha256 {
+            return Some(bytes);
+        }
+    }
+    None
+}
+
+struct Progress<'a> {
+    app: &'a AppHandle,
+    profile_id: &'a str,
+    path: &'a str,
+    total: u64,
+    done: u64,
+    fetched: u64,
+    reused: u64,
+}
+
+impl Progress<'_> {
+    fn emit(&self) {
+        let _ = environment::emit(
+            self.app,
+            PROGRESS_EVENT,
+            json!({
+                "profile_id": self.profile_id,
+                "path": self.path,
+                "total_bytes": self.total,
+                "done_bytes": self.done,
+                "fetched_bytes": self.fetched,
+                "reused_bytes": self.reused,
+            }),
+        );
+    }
+}
+
+/// Builds `partial` from the chunks already on disk and range requests for
+/// the rest; `false` when the core does not honour ranges.
+async fn fetch_delta(
+    progress: &mut Progress<'_>,
+    db: &LocalDb,
+    conn: &CoreConnection,
+    path: &str,
+    chunks: &[Chunk],
+    partial: &Path,
+) -> Result<bool, String> {
+    let held = sources(&*db.lock()?, chunks).map_err(storage)?;
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(local_io("Write artifact"))?;
+    file.set_len(progress.total)
+        .await
+        .map_err(local_io("Write artifact"))?;
+    let mut missing: Vec<&Chunk> = Vec::new();
+    for chunk in chunks {
+        let found = match held.get(&chunk.sha256) {
+            Some(sources) => reuse(sources, chunk).await,
+            None => None,
+        };
+        match found {
+            Some(bytes) => {
+                file.seek(SeekFrom::Start(chunk.offset))
+                    .await
+                    .map_err(local_io("Write artifact"))?;
+                file.write_all(&bytes)
+                    .await
+                    .map_err(local_io("Write artifact"))?;
+                progress.reused += chunk.length;
+                progress.done += chunk.length;
+            }
+            None => missing.push(chunk),
+        }
+    }
+    progress.emit();
+
+    let mut spans: Vec<Vec<&Chunk>> = Vec::new();
+    for chunk in missing {
+        match spans.last_mut() {
+            Some(span)
+                if span
+                    .last()
+                    .is_some_and(|c| c.offset + c.length == chunk.offset)
+                    && span.iter().map(|c| c.length).sum::<u64>() + chunk.length <= MAX_SPAN =>
+            {
+                span.push(chunk)
+            }
+            _ => spans.push(vec![chunk]),
+        }
+    }
+    for span in spans {
+        let start = span[0].offset;
+        let length: u64 = span.iter().map(|c| c.length).sum();
+        let body = match fetch_span(conn, path, start, length).await? {
+            Span::Fetched(body) => body,
+            Span::Ignored => return Ok(false),
+        };
+        for chunk in &span {
+            let at = (chunk.offset - start) as usize;
+            let bytes = &body[at..at + chunk.length as usize];
+            if hex(&Sha256::digest(bytes)) != chunk.sha256 {
+                return Err(ErrorCode::CoreError.with(format!(
+                    "Artifact chunk at {} does not match the manifest",
+                    chunk.offset
+                )));
+            }
+        }
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(local_io("Write artifact"))?;
+        file.write_all(&body)
+            .await
+            .map_err(local_io("Write artifact"))?;
+        progress.fetched += length;
+        progress.done += length;
+        progress.emit();
+    }
+    file.flush().await.map_err(local_io("Write artifact"))?;
+    Ok(true)
+}
+
+/// The core's chunk manifest for the artifact, or `None` if it has none.
+async fn fetch_manifest(conn: &CoreConnection, path: &str) -> Result<Option<Manifest>, String> {
+    let fetched = bandwidth::within(
+        bandwidth::DOWNLOADS,
+        crate::request_json(Method::GET, &conn.base_url, path, conn.token.clone(), None),
+    )
+    .await;
+    match fetched {
+        Ok(raw) => serde_json::from_value(raw)
+            .map(Some)
+            .map_err(|e| ErrorCode::CoreError.with(format!("Artifact manifest is invalid: {}", e))),
+        Err(e) if errors::code_of(&e) == Some(ErrorCode::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Brings the local copy of a large core artifact at `path` up to date at
+/// `destination`. When the core publishes a chunk manifest (at
+/// `manifest_path`, `<path>.chunks` by default), chunks any synced file
+/// already holds are copied locally and only the rest are fetched, with
+/// range requests; otherwise the artifact is fetched whole and chunked
+/// locally for next time. It is assembled beside the destination and only
+/// replaces it once its digest matches. Emits `artifact:progress`.
+#[tauri::command]
+pub async fn sync_artifact(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    path: String,
+    destination: String,
+    manifest_path: Option<String>,
+) -> Result<Value, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Artifact path is required"));
+    }
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    let conn = store.connection(profile.as_deref())?;
+    let target = residency::check("The artifact", &destination)?;
+    let destination = target.display().to_string();
+    let partial = PathBuf::from(format!("{}{}", destination, PARTIAL_SUFFIX));
+    let manifest_path = manifest_path
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| format!("{}.chunks", path));
+
+    // A copy fetched some other way is chunked so its unchanged parts count.
+    let indexed = db
+        .lock()?
+        .query_row(
+            "SELECT COUNT(*) FROM artifact_chunks WHERE destination = ?1",
+            [&destination],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(storage)?
+        > 0;
+    if !indexed && target.is_file() {
+        if let Ok((_, _, chunks)) = index_blocking(target.clone()).await {
+            record_chunks(&mut *db.lock()?, &destination, &chunks).map_err(storage)?;
+        }
+    }
+
+    let manifest = fetch_manifest(&conn, &manifest_path).await?;
+    let mut progress = Progress {
+        app: &app,
+        profile_id: &conn.profile_id,
+        path: &path,
+        total: manifest.as_ref().map_or(0, |m| m.size),
+        done: 0,
+        fetched: 0,
+        reused: 0,
+    };
+    let result = async {
+        let planned = manifest.as_ref().map(plan_chunks).transpose()?;
+        let ranged = match &planned {
+            Some(chunks) => fetch_delta(&mut progress, &db, &conn, &path, chunks, &partial).await?,
+            None => false,
+        };
+        if !ranged {
+            let whole = fetch_whole(&conn, &path, &partial).await?;
+            progress.reused = 0;
+            progress.fetched += whole;
+            progress.done = whole;
+            progress.total = whole;
+            progress.emit();
+        }
+        let (size, sha256, local_chunks) = index_blocking(partial.clone()).await?;
+        if let Some(manifest) = &manifest {
+            if size != manifest.size || !sha256.eq_ignore_ascii_case(&manifest.sha256) {
+                return Err(ErrorCode::CoreError.with(format!(
+                    "Assembled artifact is {} bytes with sha256 {}, the manifest says {} and {}",
+                    size, sha256, manifest.size, manifest.sha256
+                )));
+            }
+        }
+        tokio::fs::rename(&partial, &target)
+            .await
+            .map_err(local_io("Replace artifact"))?;
+        // The core's boundaries are what its next manifest will match.
+        Ok::<_, String>((size, sha256, planned.unwrap_or(local_chunks), ranged))
+    }
+    .await;
+    let (size, sha256, chunks, ranged) = match result {
+        Ok(done) => done,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(err);
+        }
+    };
+
+    let summary = json!({
+        "profile_id": conn.profile_id,
+        "path": path,
+        "destination": destination,
+        "size_bytes": size,
+        "sha256": sha256,
+        "url": cache_protocol::url(&format!("artifacts/{}", sha256)),
+        "chunks": chunks.len(),
+        "delta": ranged,
+        "fetched_bytes": progress.fetched,
+        "reused_bytes": progress.reused,
+        "synced_at": chrono::Utc::now().to_rfc3339(),
+    });
+    let mut guard = db.lock()?;
+    record_chunks(&mut guard, &destination, &chunks).map_err(storage)?;
+    guard
+        .execute(
+            "INSERT INTO artifacts (profile_id, path, destination, size, sha256, chunks,
+                 fetched_bytes, reused_bytes, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(profile_id, path) DO UPDATE SET destination = ?3, size = ?4,
+                 sha256 = ?5, chunks = ?6, fetched_bytes = ?7, reused_bytes = ?8, synced_at = ?9",
+            params![
+                conn.profile_id,
+                path,
+                destination,
+                size as i64,
+                sha256,
+                chunks.len() as i64,
+                progress.fetched as i64,
+                progress.reused as i64,
+                summary["synced_at"].as_str(),
+            ],
+        )
+        .map_err(storage)?;
+    let _ = db::record_audit(
+        &guard,
+        &conn.profile_id,
+        "artifact",
+        "sync",
+        &path,
+        &summary,
+    );
+    Ok(summary)
+}
+
+/// The artifacts synced for a profile (all profiles without one), with
+/// what the last sync fetched and reused.
+#[tauri::command]
+pub fn list_synced_artifacts(
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT profile_id, path, destination, size, sha256, chunks, fetched_bytes,
+                 reused_bytes, synced_at
+             FROM artifacts WHERE ?1 IS NULL OR profile_id = ?1 ORDER BY synced_at DESC",
+        )
+        .map_err(storage)?;
+    let artifacts = stmt
+        .query_map([profile.as_deref()], |row| {
+            Ok(json!({
+                "profile_id": row.get::<_, String>(0)?,
+                "path": row.get::<_, String>(1)?,
+                "destination": row.get::<_, String>(2)?,
+                "size_bytes": row.get::<_, i64>(3)?,
+                "sha256": row.get::<_, String>(4)?,
+                "url": cache_protocol::url(&format!("artifacts/{}", row.get::<_, String>(4)?)),
+                "chunks": row.get::<_, i64>(5)?,
+                "fetched_bytes": row.get::<_, i64>(6)?,
+                "reused_bytes": row.get::<_, i64>(7)?,
+                "synced_at": row.get::<_, String>(8)?,
+            }))
+        })
+        .map_err(storage)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(storage)?;
+    Ok(json!({ "artifacts": artifacts }))
+}
@@ -0,0 +1,212 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::quiet;
+use crate::settings::SettingsStore;
+
+/// Built-in sounds, mapped to sounds every supported OS ships with.
+pub const BUILTIN: &[&str] = &["chime", "alert", "alarm", "ping"];
+const CUSTOM_EXTENSIONS: &[&str] = &["wav", "aiff", "aif", "mp3", "ogg", "oga", "m4a"];
+
+/// Plays the sound configured for `class`, unless notifications are quiet
+/// (critical events excepted when the override is on).
+pub fn play_for(app: &AppHandle, class: &str, critical: bool) -> Result<Option<String>, String> {
+    let Some(store) = app.try_state::<SettingsStore>() else {
+        return Ok(None);
+    };
+    let quiet_hours = store.snapshot().notifications.quiet_hours;
+    if quiet::quiet_reason(&quiet_hours).is_some() && !(critical && quiet_hours.critical_override) {
+        return Ok(None);
+    }
+    play_configured(app, class)
+}
+
+/// Plays the sound configured for `class` unless sounds are off or the class
+/// is set to `none`. Callers have already applied quiet hours.
+pub fn play_configured(app: &AppHandle, class: &str) -> Result<Option<String>, String> {
+    let Some(store) = app.try_state::<SettingsStore>() else {
+        return Ok(None);
+    };
+    let sounds = store.snapshot().notifications.sounds;
+    let Some(sound) = sounds.sound_for(class) else {
+        return Ok(None);
+    };
+    play(&sound)?;
+    Ok(Some(sound))
+}
+
+/// Accepts a built-in name, `none`, or the absolute path of an audio file.
+pub fn validate(sound: &str) -> Result<(), String> {
+    let sound = sound.trim();
+    if sound == "none" || BUILTIN.contains(&sound) {
+        return Ok(());
+    }
+    let path = Path::new(sound);
+    let audio = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| CUSTOM_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+    if !path.is_absolute() || !audio {
+        return Err(format!(
+            "Sound must be one of {}, `none`, or an absolute path to a {} file",
+            BUILTIN.join(", "),
+            CUSTOM_EXTENSIONS.join("/")
+        ));
+    }
+    if !path.is_file() {
+        return Err(format!("Sound file not found: {}", sound));
+    }
+    Ok(())
+}
+
+/// Starts playback in the background with the platform's own player, so no
+/// audio stack is linked into the shell.
+fn play(sound: &str) -> Result<(), String> {
+    let file = match builtin_file(sound) {
+        Some(file) => file.to_string(),
+        None => sound.to_string(),
+    };
+    let mut cmd = player(&file);
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    let child = cmd
+        .spawn()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Could not start audio player: {}", e)))?;
+    std::thread::spawn(move || {
+        let mut child = child;
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn builtin_file(sound: &str) -> Option<&'static str> {
+    Some(match sound {
+        "chime" => "/System/Library/Sounds/Glass.aiff",
+        "alert" => "/System/Library/Sounds/Basso.aiff",
+        "alarm" => "/System/Library/Sounds/Sosumi.aiff",
+        "ping" => "/System/Library/Sounds/Ping.aiff",
+        _ => return None,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn builtin_file(sound: &str) -> Option<&'static str> {
+    Some(match sound {
+        "chime" => r"C:\Windows\Media\Windows Notify.wav",
+        "alert" => r"C:\Windows\Media\Windows Critical Stop.wav",
+        "alarm" => r"C:\Windows\Media\Alarm01.wav",
+        "ping" => r"C:\Windows\Media\Windows Ding.wav",
+        _ => return None,
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn builtin_file(sound: &str) -> Option<&'static str> {
+    Some(match sound {
+        "chime" => "/usr/share/sounds/freedesktop/stereo/message-new-instant.oga",
+        "alert" => "/usr/share/sounds/freedesktop/stereo/dialog-warning.oga",
+        "alarm" => "/usr/share/sounds/freedesktop/stereo/alarm-clock-elapsed.oga",
+        "ping" => "/usr/share/sounds/freedesktop/stereo/bell.oga",
+        _ => return None,
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn player(file: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new("afplay");
+    cmd.arg(file);
+    cmd
+}
+
+/// `Media.SoundPlayer` only plays WAV, which covers the built-ins.
+#[cfg(target_os = "windows")]
+fn player(file: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new("powershell");
+    cmd.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        &format!(
+            "(New-Object Media.SoundPlayer '{}').PlaySync()",
+            file.replace('\'', "''")
+        ),
+    ]);
+    cmd
+}
+
+/// PulseAudio/PipeWire's `paplay` decodes Ogg and WAV; fall back to ALSA's
+/// `aplay` where it is missing.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn player(file: &str) -> std::process::Command {
+    let mut cmd = std::process::Command::new("sh");
+    cmd.args([
+        "-c",
+        "command -v paplay >/dev/null && exec paplay \"$0\" || exec aplay -q \"$0\"",
+        file,
+    ]);
+    cmd
+}
+
+#[tauri::command]
+pub fn list_alert_sounds(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    Ok(json!({
+        "builtin": BUILTIN,
+        "sounds": store.snapshot().notifications.sounds,
+    }))
+}
+
+/// Sets the sound for an event class (`new_plan`, `plan_failed`,
+/// `core_down`, or any notification kind); `None` restores the default.
+#[tauri::command]
+pub fn set_alert_sound(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    class: String,
+    sound: Option<String>,
+) -> Result<Value, String> {
+    let class = class.trim().to_string();
+    if class.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Sound class is required"));
+    }
+    if let Some(sound) = &sound {
+        validate(sound).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    }
+    let updated = store.update(&app, |settings| {
+        let classes = &mut settings.notifications.sounds.classes;
+        match sound.map(|s| s.trim().to_string()) {
+            Some(sound) => classes.insert(class.clone(), sound),
+            None => classes.remove(&class),
+        };
+        Ok(())
+    })?;
+    Ok(json!({ "sounds": updated.notifications.sounds }))
+}
+
+/// Plays the sound for `class` as an event of that class would, honouring
+/// quiet hours; the frontend calls this for plan changes it detects.
+#[tauri::command]
+pub fn play_alert_sound(
+    app: AppHandle,
+    class: String,
+    critical: Option<bool>,
+) -> Result<Value, String> {
+    let played = play_for(&app, class.trim(), critical.unwrap_or(false))?;
+    Ok(json!({ "class": class, "played": played.is_some(), "sound": played }))
+}
+
+/// Plays `sound` (a built-in, or a custom file path) immediately, ignoring
+/// quiet hours, so a choice can be auditioned before it is saved.
+#[tauri::command]
+pub fn test_alert_sound(sound: String) -> Result<Value, String> {
+    let sound = sound.trim();
+    validate(sound).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    if sound != "none" {
+        play(sound)?;
+    }
+    Ok(json!({ "sound": sound, "played": sound != "none" }))
+}
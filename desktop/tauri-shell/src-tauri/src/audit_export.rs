@@ -0,0 +1,232 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{crypto, residency, secrets};
+
+const PACKAGE_FORMAT: &str = "novaadapt-audit-export";
+const PACKAGE_VERSION: u64 = 1;
+pub const SIGNING_KEY_SECRET: &str = "audit:signing_key";
+const CORE_EVENT_LIMIT: usize = 10_000;
+const LOCAL_FILE: &str = "local_audit.json";
+const CORE_FILE: &str = "core_events.json";
+const MANIFEST_FILE: &str = "manifest.json";
+const SIGNATURE_FILE: &str = "manifest.sig";
+
+/// Writes a zip for compliance review: the shell's own audit log and the
+/// core's audit events for the range, plus a manifest of their SHA-256
+/// digests signed with this machine's Ed25519 audit key.
+///
+/// `since`/`until` are RFC 3339 timestamps; either may be left open.
+#[tauri::command]
+pub async fn export_audit(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    path: String,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let target = residency::check("The audit export", &path)?;
+    let since = parse_bound(since.as_deref(), "since")?;
+    let until = parse_bound(until.as_deref(), "until")?;
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(ErrorCode::InvalidInput.with("`since` must not be after `until`"));
+        }
+    }
+
+    let local = local_records(&*db.lock()?, &conn.profile_id, since, until)
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let events = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/events?limit={}", CORE_EVENT_LIMIT),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let events = events.as_array().cloned().unwrap_or_default();
+    // The core lists newest first; a full page whose oldest event is still
+    // inside the range means older matching events were cut off.
+    let truncated = events.len() >= CORE_EVENT_LIMIT
+        && events
+            .last()
+            .and_then(|e| timestamp(&e["created_at"]))
+            .is_some_and(|oldest| in_range(oldest, since, None));
+    let mut core: Vec<Value> = events
+        .into_iter()
+        .filter(|e| timestamp(&e["created_at"]).is_some_and(|at| in_range(at, since, until)))
+        .collect();
+    core.reverse();
+
+    let local_bytes = serde_json::to_vec_pretty(&local).map_err(|e| e.to_string())?;
+    let core_bytes = serde_json::to_vec_pretty(&core).map_err(|e| e.to_string())?;
+    let key = signing_key(SIGNING_KEY_SECRET)?;
+    let public_key = B64.encode(key.verifying_key().to_bytes());
+    let manifest = json!({
+        "format": PACKAGE_FORMAT,
+        "version": PACKAGE_VERSION,
+        "generated_at": Utc::now().to_rfc3339(),
+        "profile_id": conn.profile_id,
+        "core": crate::net::base_origin(&conn.base_url),
+        "range": {
+            "since": since.map(|t| t.to_rfc3339()),
+            "until": until.map(|t| t.to_rfc3339()),
+        },
+        "core_truncated": truncated,
+        "files": [
+            file_entry(LOCAL_FILE, &local_bytes, local.len()),
+            file_entry(CORE_FILE, &core_bytes, core.len()),
+        ],
+        "signature": {
+            "algorithm": "ed25519",
+            "public_key": public_key,
+            "file": SIGNATURE_FILE,
+        },
+    });
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    let signature = B64.encode(key.sign(&manifest_bytes).to_bytes());
+
+    let path = path.trim();
+    write_zip(
+        &target,
+        &[
+            (MANIFEST_FILE, &manifest_bytes),
+            (SIGNATURE_FILE, signature.as_bytes()),
+            (LOCAL_FILE, &local_bytes),
+            (CORE_FILE, &core_bytes),
+        ],
+    )
+    .map_err(|e| ErrorCode::LocalIo.with(format!("Write audit export failed: {}", e)))?;
+
+    let summary = json!({
+        "path": path,
+        "profile_id": conn.profile_id,
+        "local_records": local.len(),
+        "core_records": core.len(),
+        "core_truncated": truncated,
+        "public_key": public_key,
+        "manifest_sha256": hex(&Sha256::digest(&manifest_bytes)),
+    });
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "audit",
+        "export",
+        "",
+        &summary,
+    );
+    Ok(summary)
+}
+
+fn parse_bound(raw: Option<&str>, name: &str) -> Result<Option<DateTime<Utc>>, String> {
+    match raw.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(raw) => DateTime::parse_from_rfc3339(raw)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| ErrorCode::InvalidInput.with(format!("`{}` must be RFC 3339", name))),
+        None => Ok(None),
+    }
+}
+
+fn timestamp(raw: &Value) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw.as_str()?)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn in_range(at: DateTime<Utc>, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> bool {
+    since.is_none_or(|s| at >= s) && until.is_none_or(|u| at <= u)
+}
+
+fn local_records(
+    conn: &Connection,
+    profile_id: &str,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> rusqlite::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, at, category, action, entity_id, detail FROM audit_log
+         WHERE profile_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![profile_id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+        ))
+    })?;
+    let mut out = Vec::new();
+    for row in rows {
+        let (id, at, category, action, entity_id, detail) = row?;
+        let at_value = Value::String(at);
+        if !timestamp(&at_value).is_some_and(|t| in_range(t, since, until)) {
+            continue;
+        }
+        out.push(json!({
+            "id": id,
+            "at": at_value,
+            "category": category,
+            "action": action,
+            "entity_id": entity_id,
+            "detail": serde_json::from_str::<Value>(&detail).unwrap_or(Value::String(detail)),
+        }));
+    }
+    Ok(out)
+}
+
+fn file_entry(name: &str, bytes: &[u8], records: usize) -> Value {
+    json!({
+        "name": name,
+        "sha256": hex(&Sha256::digest(bytes)),
+        "bytes": bytes.len(),
+        "records": records,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A signing key kept in the OS keyring under `secret` and created on first
+/// use, so every package this machine signs with it verifies against one key.
+pub fn signing_key(secret: &str) -> Result<SigningKey, String> {
+    if let Some(encoded) = secrets::get(secret)? {
+        let seed = crypto::decode_key(&encoded)
+            .map_err(|e| ErrorCode::LocalStorage.with(format!("Signing key {}: {}", secret, e)))?;
+        return Ok(SigningKey::from_bytes(&seed));
+    }
+    let seed: [u8; 32] = crypto::random_bytes();
+    secrets::set(secret, &crypto::encode_key(&seed))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn write_zip(path: &Path, files: &[(&str, &[u8])]) -> zip::result::ZipResult<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, bytes) in files {
+        zip.start_file(*name, options)?;
+        zip.write_all(bytes)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{self, LocalDb};
+use crate::settings::{
+    AutoApprovalSettings, EnvironmentClass, Profile, SettingsStore, WindowAction,
+};
+use crate::{decisions, exec_windows, notify, safe_mode, tasks};
+
+const TASK: &str = "auto_approval";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// A plan whose approval failed is tried again after this long, not on
+/// every pass.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Risk levels in rising order; `critical` can never be auto-approved.
+const RISKS: &[&str] = &["low", "medium", "high", "critical"];
+/// Sent as `approval_source`, so the core and decision metrics can tell
+/// these approvals from an operator's.
+const SOURCE: &str = "auto_approval";
+
+/// Plans whose approval failed, keyed by `profile_id plan_id`.
+static FAILED: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+fn failed() -> &'static Mutex<HashMap<String, Instant>> {
+    FAILED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn validate(profile: &Profile) -> Result<(), String> {
+    let settings = &profile.auto_approval;
+    if rank(&settings.max_risk).is_none_or(|r| RISKS[r] == "critical") {
+        return Err(format!(
+            "Auto-approval risk must be low, medium or high, got {:?}",
+            settings.max_risk
+        ));
+    }
+    if settings.agents.iter().any(|a| a.trim().is_empty()) {
+        return Err("Auto-approval agents must not be blank".to_string());
+    }
+    if settings.enabled && settings.execute && profile.environment == EnvironmentClass::Prod {
+        return Err(
+            "Auto-approval cannot execute on a prod profile; executing needs a typed confirmation"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+fn rank(risk: &str) -> Option<usize> {
+    let risk = risk.trim().to_ascii_lowercase();
+    RISKS.iter().position(|r| *r == risk)
+}
+
+fn text<'a>(plan: &'a Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|k| plan[*k].as_str())
+}
+
+/// Whether the profile's policy covers `plan`: pending, at or under the
+/// policy's risk, and from one of its agents if it names any.
+fn eligible(settings: &AutoApprovalSettings, plan: &Value) -> bool {
+    if plan["status"].as_str() != Some("pending") {
+        return false;
+    }
+    let (Some(risk), Some(max)) = (
+        text(plan, &["risk", "risk_level"]).and_then(rank),
+        rank(&settings.max_risk),
+    ) else {
+        return false;
+    };
+    if risk > max {
+        return false;
+    }
+    settings.agents.is_empty()
+        || text(plan, &["agent", "agent_id"]).is_some_and(|agent| {
+            settings
+                .agents
+                .iter()
+                .any(|a| a.eq_ignore_ascii_case(agent))
+        })
+}
+
+/// Approves pending plans under each profile's auto-approval policy.
+pub fn spawn_worker(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            run(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+async fn run(app: &AppHandle) {
+    if safe_mode::active() {
+        return;
+    }
+    let store = app.state::<SettingsStore>();
+    let settings = store.snapshot();
+    if let Ok(mut recent) = failed().lock() {
+        recent.retain(|_, at| at.elapsed() < RETRY_AFTER);
+    }
+    for profile in &settings.profiles {
+        let policy = &profile.auto_approval;
+        if !policy.enabled || profile.auto_approval_paused || profile.read_only {
+            continue;
+        }
+        let Ok(conn) = store.connection(Some(&profile.id)) else {
+            continue;
+        };
+        // Executing approvals wait out a freeze rather than fail on it.
+        let blocked = policy.execute
+            && exec_windows::window_at(&conn.base_url, Utc::now())
+                .is_some_and(|w| w.action == WindowAction::Block);
+        if blocked {
+            continue;
+        }
+        let listed = match crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            "/plans?limit=200",
+            conn.token.clone(),
+            None,
+        )
+        .await
+        {
+            Ok(listed) => listed,
+            Err(err) => {
+                tasks::failed(TASK, &err);
+                continue;
+            }
+        };
+        for plan in listed.as_array().into_iter().flatten() {
+            let Some(plan_id) = plan["id"].as_str() else {
+                continue;
+            };
+            let key = format!("{} {}", profile.id, plan_id);
+            if !eligible(policy, plan) || failed().lock().is_ok_and(|f| f.contains_key(&key)) {
+                continue;
+            }
+            let result = decisions::send_decision(
+                &conn.base_url,
+                conn.token.clone(),
+                plan_id,
+                "approve",
+                Some(json!({ "execute": policy.execute, "approval_source": SOURCE })),
+                decisions::plan_version(plan).as_deref(),
+                None,
+            )
+            .await;
+            let risk = text(plan, &["risk", "risk_level"]);
+            let agent = text(plan, &["agent", "agent_id"]);
+            let detail = json!({
+                "risk": risk,
+                "agent": agent,
+                "execute": policy.execute,
+                "result": result.as_ref().ok(),
+                "error": result.as_ref().err(),
+            });
+            if let Ok(guard) = app.state::<LocalDb>().lock() {
+                let action = if result.is_ok() {
+                    "auto_approved"
+                } else {
+                    "auto_approval_failed"
+                };
+                let _ = db::record_audit(&guard, &profile.id, "approval", action, plan_id, &detail);
+            }
+            let event = json!({
+                "profile_id": profile.id,
+                "plan_id": plan_id,
+                "risk": risk,
+                "agent": agent,
+            });
+            match result {
+                Ok(_) => notify::notify_in_app(
+                    app,
+                    "auto_approval",
+                    "Plan auto-approved",
+                    &format!(
+                        "{}: plan {} at {} risk",
+                        profile.name,
+                        plan_id,
+                        risk.unwrap_or("unknown")
+                    ),
+                    event,
+                ),
+                Err(err) => {
+                    tasks::failed(TASK, &err);
+                    if let Ok(mut recent) = failed().lock() {
+                        recent.insert(key, Instant::now());
+                    }
+                    notify::notify(
+                        app,
+                        "auto_approval",
+                        "Auto-approval failed",
+                        &format!(
+                            "{}: plan {} needs an operator: {}",
+                            profile.name, plan_id, err
+                        ),
+                        event,
+                    );
+                }
+            }
+        }
+    }
+}
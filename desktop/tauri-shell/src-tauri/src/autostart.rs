@@ -0,0 +1,65 @@
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::tray;
+
+/// Passed by the login item so a launch at login can be told apart from one
+/// the operator started.
+pub const AUTOSTART_ARG: &str = "--autostart";
+
+pub fn launched_at_login() -> bool {
+    std::env::args().any(|arg| arg == AUTOSTART_ARG)
+}
+
+/// Shows the main window on startup, unless this is a login launch with
+/// `desktop.start_minimized` set, in which case the shell stays in the tray
+/// with its pollers running.
+pub fn show_on_startup(app: &AppHandle, store: &SettingsStore) {
+    if launched_at_login() && store.snapshot().desktop.start_minimized {
+        return;
+    }
+    tray::show_main_window(app);
+}
+
+#[tauri::command]
+pub fn get_autostart(app: AppHandle, store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let enabled = app.autolaunch().is_enabled().map_err(autostart_err)?;
+    let desktop = store.snapshot().desktop;
+    Ok(json!({
+        "enabled": enabled,
+        "start_minimized": desktop.start_minimized,
+        "close_to_tray": desktop.close_to_tray,
+        "launched_at_login": launched_at_login(),
+    }))
+}
+
+/// Registers or removes the login item (a Launch Agent on macOS, the `Run`
+/// registry key on Windows, an XDG autostart entry on Linux).
+#[tauri::command]
+pub fn set_autostart(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    enabled: bool,
+    start_minimized: Option<bool>,
+) -> Result<Value, String> {
+    let launcher = app.autolaunch();
+    if enabled {
+        launcher.enable().map_err(autostart_err)?;
+    } else {
+        launcher.disable().map_err(autostart_err)?;
+    }
+    if let Some(minimized) = start_minimized {
+        store.update(&app, |settings| {
+            settings.desktop.start_minimized = minimized;
+            Ok(())
+        })?;
+    }
+    get_autostart(app, store)
+}
+
+fn autostart_err(err: tauri_plugin_autostart::Error) -> String {
+    ErrorCode::LocalIo.with(format!("Login item update failed: {}", err))
+}
@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::process::Command;
+
+use crate::errors::{self, ErrorCode};
+use crate::features::{self, FeatureCache};
+use crate::settings::{AvailabilitySettings, SettingsStore};
+use crate::{environment, notification_actions, tasks};
+
+const TASK: &str = "availability";
+const EVENT: &str = "operator:status";
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// An unchanged status is sent again this often, so a core can tell a
+/// shell that stopped answering from one whose operator is still there.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+/// How long a core that failed, or does not take the status, is left
+/// alone before it is tried again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const UNSUPPORTED_RETRY: Duration = Duration::from_secs(30 * 60);
+const MAX_IDLE_MINUTES: u32 = 8 * 60;
+const MAX_OVERRIDE_MINUTES: u32 = 24 * 60;
+/// The core feature flag of cores that route approvals by availability.
+const FEATURE: &str = "operator_status";
+const PATH: &str = "/operators/me/status";
+const AVAILABLE: &str = "available";
+const AWAY: &str = "away";
+
+/// A status the operator set by hand, until `until` if it runs out.
+struct Manual {
+    status: &'static str,
+    until: Option<(Instant, DateTime<Utc>)>,
+}
+
+/// What the status last sent to one profile's core was, and how it went.
+struct Published {
+    status: &'static str,
+    reason: &'static str,
+    at: Instant,
+    at_utc: String,
+    error: Option<String>,
+    supported: bool,
+}
+
+struct Availability {
+    manual: Option<Manual>,
+    /// The last input the window reported, for when the OS idle time
+    /// cannot be read.
+    activity: Instant,
+    status: &'static str,
+    reason: &'static str,
+    since: DateTime<Utc>,
+    idle: Duration,
+    locked: bool,
+    cores: HashMap<String, Published>,
+}
+
+impl Availability {
+    fn describe(&self) -> Value {
+        let manual = self.manual.as_ref().map(|m| {
+            json!({
+                "status": m.status,
+                "until": m.until.map(|(_, at)| at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            })
+        });
+        let mut cores: Vec<Value> = self
+            .cores
+            .iter()
+            .map(|(profile_id, sent)| {
+                json!({
+                    "profile_id": profile_id,
+                    "status": sent.status,
+                    "reason": sent.reason,
+                    "sent_at": sent.at_utc,
+                    "supported": sent.supported,
+                    "error": sent.error,
+                })
+            })
+            .collect();
+        cores.sort_by(|a, b| a["profile_id"].as_str().cmp(&b["profile_id"].as_str()));
+        json!({
+            "status": self.status,
+            "reason": self.reason,
+            "since": self.since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "idle_seconds": self.idle.as_secs(),
+            "locked": self.locked,
+            "manual": manual,
+            "cores": cores,
+        })
+    }
+}
+
+static AVAILABILITY: OnceLock<Mutex<Availability>> = OnceLock::new();
+
+fn availability() -> &'static Mutex<Availability> {
+    AVAILABILITY.get_or_init(|| {
+        Mutex::new(Availability {
+            manual: None,
+            activity: Instant::now(),
+            status: AVAILABLE,
+            reason: "active",
+            since: Utc::now(),
+            idle: Duration::ZERO,
+            locked: false,
+            cores: HashMap::new(),
+        })
+    })
+}
+
+fn poisoned() -> String {
+    ErrorCode::Internal.with("Availability state poisoned")
+}
+
+pub fn validate(settings: &AvailabilitySettings) -> Result<(), String> {
+    if settings.idle_minutes > MAX_IDLE_MINUTES {
+        return Err(format!(
+            "availability.idle_minutes must be at most {}",
+            MAX_IDLE_MINUTES
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn idle_probe() -> (Command, Duration) {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "ioreg -c IOHIDSystem -d 4 | awk '/HIDIdleTime/ { print $NF; exit }'",
+    ]);
+    (cmd, Duration::from_nanos(1))
+}
+
+#[cfg(target_os = "windows")]
+fn idle_probe() -> (Command, Duration) {
+    let mut cmd = Command::new("powershell");
+    cmd.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Add-Type -TypeDefinition 'using System; using System.Runtime.InteropServices; \
+         public static class NovaIdle { \
+         [StructLayout(LayoutKind.Sequential)] struct Info { public uint Size; public uint Time; } \
+         [DllImport(\"user32.dll\")] static extern bool GetLastInputInfo(ref Info info); \
+         public static uint Millis() { var i = new Info(); i.Size = 8; GetLastInputInfo(ref i); \
+         return (uint)Environment.TickCount - i.Time; } }'; [NovaIdle]::Millis()",
+    ]);
+    (cmd, Duration::from_millis(1))
+}
+
+/// `xprintidle` on X11, else GNOME's idle monitor, which also answers on
+/// Wayland.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn idle_probe() -> (Command, Duration) {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "xprintidle 2>/dev/null || gdbus call --session --dest org.gnome.Mutter.IdleMonitor \
+         --object-path /org/gnome/Mutter/IdleMonitor/Core \
+         --method org.gnome.Mutter.IdleMonitor.GetIdletime",
+    ]);
+    (cmd, Duration::from_millis(1))
+}
+
+/// How long since the last keyboard or mouse input anywhere in the OS
+/// session, or `None` where it cannot be read.
+async fn os_idle() -> Option<Duration> {
+    let (mut cmd, unit) = idle_probe();
+    let out = cmd
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()
+        .filter(|out| out.status.success())?;
+    // `12345`, or `(uint64 12345,)` from D-Bus.
+    let ticks: u64 = String::from_utf8_lossy(&out.stdout)
+        .split_whitespace()
+        .last()?
+        .trim_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()?;
+    Some(Duration::from_nanos(
+        ticks.saturating_mul(unit.as_nanos() as u64),
+    ))
+}
+
+/// The status the operator is in: the one set by hand while it lasts,
+/// else away while the session is locked or input has been idle past
+/// `idle_minutes`, else available.
+fn resolve(
+    state: &mut Availability,
+    settings: &AvailabilitySettings,
+) -> (&'static str, &'static str) {
+    if state
+        .manual
+        .as_ref()
+        .and_then(|m| m.until)
+        .is_some_and(|(at, _)| at <= Instant::now())
+    {
+        state.manual = None;
+    }
+    if let Some(manual) = &state.manual {
+        return (manual.status, "manual");
+    }
+    if settings.away_on_lock && state.locked {
+        return (AWAY, "locked");
+    }
+    let idle_after = Duration::from_secs(u64::from(settings.idle_minutes) * 60);
+    if settings.idle_minutes > 0 && state.idle >= idle_after {
+        return (AWAY, "idle");
+    }
+    (AVAILABLE, "active")
+}
+
+/// Works out the operator's status, emits `operator:status` when it
+/// changed and sends it to each profile's core that is due: on a change,
+/// every `REFRESH_INTERVAL` otherwise, and on `force`.
+async fn refresh(app: &AppHandle, force: bool) -> Result<(), String> {
+    let store = app.state::<SettingsStore>();
+    let settings = store.snapshot();
+    let wanted = &settings.availability;
+    let locked = wanted.away_on_lock && notification_actions::session_locked().await;
+    let os_idle = if wanted.idle_minutes > 0 {
+        os_idle().await
+    } else {
+        None
+    };
+    let (status, reason, changed) = {
+        let mut state = availability().lock().map_err(|_| poisoned())?;
+        let window_idle = state.activity.elapsed();
+        state.idle = os_idle.map_or(window_idle, |idle| idle.min(window_idle));
+        state.locked = locked;
+        let (status, reason) = resolve(&mut state, wanted);
+        let changed = (status, reason) != (state.status, state.reason);
+        if changed {
+            if status != state.status {
+                state.since = Utc::now();
+            }
+            state.status = status;
+            state.reason = reason;
+        }
+        (status, reason, changed)
+    };
+    if changed {
+        let described = availability().lock().map_err(|_| poisoned())?.describe();
+        let _ = environment::emit(app, EVENT, described);
+    }
+    if !wanted.enabled {
+        return Ok(());
+    }
+
+    let features = app.state::<FeatureCache>();
+    for profile in &settings.profiles {
+        let due = {
+            let state = availability().lock().map_err(|_| poisoned())?;
+            match state.cores.get(&profile.id) {
+                None => true,
+                Some(sent) if !sent.supported => sent.at.elapsed() >= UNSUPPORTED_RETRY,
+                Some(sent) if sent.error.is_some() => force || sent.at.elapsed() >= RETRY_INTERVAL,
+                Some(sent) => {
+                    force
+                        || (sent.status, sent.reason) != (status, reason)
+                        || sent.at.elapsed() >= REFRESH_INTERVAL
+                }
+            }
+        };
+        if !due {
+            continue;
+        }
+        let Ok(conn) = store.connection(Some(&profile.id)) else {
+            continue;
+        };
+        let outcome = if features::core_flag(&store, &features, &conn, FEATURE).await == Some(false)
+        {
+            Err(None)
+        } else {
+            let since = availability()
+                .lock()
+                .map_err(|_| poisoned())?
+                .since
+                .to_rfc3339_opts(SecondsFormat::Secs, true);
+            crate::request_json(
+                Method::PUT,
+                &conn.base_url,
+                PATH,
+                conn.token.clone(),
+                Some(json!({
+                    "status": status,
+                    "reason": reason,
+                    "since": since,
+                    "client": "desktop",
+                })),
+            )
+            .await
+            .map_err(|err| match errors::code_of(&err) {
+                Some(ErrorCode::NotFound) => None,
+                _ => Some(err),
+            })
+        };
+        if let Err(Some(err)) = &outcome {
+            tasks::failed(TASK, err);
+        }
+        availability().lock().map_err(|_| poisoned())?.cores.insert(
+            profile.id.clone(),
+            Published {
+                status,
+                reason,
+                at: Instant::now(),
+                at_utc: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                supported: !matches!(outcome, Err(None)),
+                error: outcome.err().flatten(),
+            },
+        );
+    }
+    // Profiles removed since they were last sent to.
+    availability()
+        .lock()
+        .map_err(|_| poisoned())?
+        .cores
+        .retain(|id, _| settings.profiles.iter().any(|p| p.id == *id));
+    Ok(())
+}
+
+/// Keeps the operator's status current and the cores told of it.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            if let Err(err) = refresh(&app, false).await {
+                tasks::failed(TASK, &err);
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// The operator's status, why it is what it is, and what each core was
+/// last told.
+#[tauri::command]
+pub fn get_operator_status() -> Result<Value, String> {
+    Ok(availability().lock().map_err(|_| poisoned())?.describe())
+}
+
+/// Sets the operator `available` or `away` by hand, for `minutes` or until
+/// changed, or hands the status back to idle and lock detection with
+/// `auto`. The cores are told straight away.
+#[tauri::command]
+pub async fn set_operator_status(
+    app: AppHandle,
+    status: String,
+    minutes: Option<u32>,
+) -> Result<Value, String> {
+    let manual = match status.trim().to_ascii_lowercase().as_str() {
+        "auto" => None,
+        wanted => {
+            let status = [AVAILABLE, AWAY]
+                .into_iter()
+                .find(|s| *s == wanted)
+                .ok_or_else(|| {
+                    ErrorCode::InvalidInput.with("Status must be `available`, `away` or `auto`")
+                })?;
+            let until = match minutes {
+                Some(minutes) if !(1..=MAX_OVERRIDE_MINUTES).contains(&minutes) => {
+                    return Err(ErrorCode::InvalidInput.with(format!(
+                        "minutes must be between 1 and {}",
+                        MAX_OVERRIDE_MINUTES
+                    )));
+                }
+                Some(minutes) => {
+                    let length = Duration::from_secs(u64::from(minutes) * 60);
+                    Some((
+                        Instant::now() + length,
+                        Utc::now() + chrono::Duration::from_std(length).unwrap_or_default(),
+                    ))
+                }
+                None => None,
+            };
+            Some(Manual { status, until })
+        }
+    };
+    availability().lock().map_err(|_| poisoned())?.manual = manual;
+    refresh(&app, true).await?;
+    get_operator_status()
+}
+
+/// Called by the window on keyboard and pointer input, for idle detection
+/// where the OS idle time cannot be read. Coming back from idle is sent to
+/// the cores at once rather than on the next check.
+#[tauri::command]
+pub async fn report_operator_activity(app: AppHandle) -> Result<Value, String> {
+    let returning = {
+        let mut state = availability().lock().map_err(|_| poisoned())?;
+        state.activity = Instant::now();
+        state.reason == "idle"
+    };
+    if returning {
+        refresh(&app, false).await?;
+    }
+    let state = availability().lock().map_err(|_| poisoned())?;
+    Ok(json!({ "status": state.status, "reason": state.reason }))
+}
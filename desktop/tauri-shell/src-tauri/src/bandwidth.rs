@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{Days, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Url};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::{BandwidthSettings, Profile, SettingsStore};
+use crate::{net, spill, tasks};
+
+/// Requests the operator's actions send.
+pub const INTERACTIVE: &str = "interactive";
+/// Requests from background tasks: pollers, watchers and the cache sync.
+pub const POLLING: &str = "polling";
+/// Live event streams and plan output streams.
+pub const STREAMING: &str = "streaming";
+/// Attachments, thumbnails and plugin packages.
+pub const DOWNLOADS: &str = "downloads";
+const SUBSYSTEMS: &[&str] = &[INTERACTIVE, POLLING, STREAMING, DOWNLOADS];
+
+const TASK: &str = "bandwidth";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_DAYS: u32 = 30;
+const MAX_DAYS: u32 = 400;
+
+tokio::task_local! {
+    static SUBSYSTEM: &'static str;
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    sent: u64,
+    received: u64,
+    requests: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.sent += other.sent;
+        self.received += other.received;
+        self.requests += other.requests;
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "sent_bytes": self.sent,
+            "received_bytes": self.received,
+            "total_bytes": self.sent + self.received,
+            "requests": self.requests,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Meter {
+    /// Every endpoint's origin, to its profile.
+    profiles: HashMap<String, String>,
+    /// Counted since the last flush, by day, profile and subsystem.
+    pending: HashMap<(String, String, &'static str), Counts>,
+}
+
+static METER: OnceLock<Mutex<Meter>> = OnceLock::new();
+static LOW_DATA: AtomicBool = AtomicBool::new(false);
+
+fn meter() -> &'static Mutex<Meter> {
+    METER.get_or_init(|| Mutex::new(Meter::default()))
+}
+
+/// Maps each profile's endpoints to it, and applies low-data mode.
+pub fn configure(profiles: &[Profile], settings: &BandwidthSettings) {
+    let mut map = HashMap::new();
+    for profile in profiles {
+        let endpoints = profile
+            .endpoints()
+            .chain(profile.read_routing.read_url.as_deref());
+        for origin in endpoints.filter_map(net::base_origin) {
+            map.insert(origin, profile.id.clone());
+        }
+    }
+    meter().lock().unwrap_or_else(|e| e.into_inner()).profiles = map;
+    LOW_DATA.store(settings.low_data, Ordering::Relaxed);
+}
+
+/// Whether low-data mode is on: background prefetching and thumbnails
+/// are off.
+pub fn low_data() -> bool {
+    LOW_DATA.load(Ordering::Relaxed)
+}
+
+/// Runs `work` with the core requests it makes counted under `subsystem`.
+/// Nested scopes count under the innermost.
+pub async fn within<F: Future>(subsystem: &'static str, work: F) -> F::Output {
+    SUBSYSTEM.scope(subsystem, work).await
+}
+
+/// The subsystem the running task's requests count under.
+pub fn current() -> &'static str {
+    SUBSYSTEM.try_with(|s| *s).unwrap_or(INTERACTIVE)
+}
+
+fn header_bytes(headers: impl Iterator<Item = (usize, usize)>) -> u64 {
+    // `Name: value\r\n`
+    headers.map(|(n, v)| (n + v + 4) as u64).sum()
+}
+
+/// About what a request puts on the wire before TLS: its request line,
+/// headers and body.
+pub fn request_bytes(
+    method: &Method,
+    url: &Url,
+    headers: &[(String, String)],
+    body: Option<&Value>,
+) -> u64 {
+    let line = method.as_str().len() + url.path().len() + url.query().map_or(0, str::len) + 12;
+    line as u64
+        + header_bytes(headers.iter().map(|(n, v)| (n.len(), v.len())))
+        + body.map_or(0, |b| b.to_string().len() as u64)
+}
+
+/// About what a response took to receive: its status line, headers and
+/// `body` bytes.
+pub fn response_bytes(headers: &HeaderMap, body: u64) -> u64 {
+    header_bytes(headers.iter().map(|(n, v)| (n.as_str().len(), v.len()))) + 15 + body
+}
+
+/// The size of a response body, whether buffered or spilled to disk.
+pub fn body_bytes(body: &spill::Body) -> u64 {
+    match body {
+        spill::Body::Buffered(text) => text.len() as u64,
+        spill::Body::Spilled(handle) => handle["size_bytes"].as_u64().unwrap_or(0),
+    }
+}
+
+/// Counts one request to `base_url` that sent `sent` bytes and received
+/// `received`, under the current subsystem.
+pub fn request(base_url: &str, sent: u64, received: u64) {
+    count(base_url, current(), sent, received, 1);
+}
+
+/// Counts bytes moved for `base_url` under `subsystem` outside a request
+/// of their own, such as a stream's chunks.
+pub fn transferred(base_url: &str, subsystem: &'static str, sent: u64, received: u64) {
+    count(base_url, subsystem, sent, received, 0);
+}
+
+/// Counts a request to `base_url` under `subsystem` whatever scope it runs
+/// in, such as a stream opening.
+pub fn opened(base_url: &str, subsystem: &'static str, sent: u64, received: u64) {
+    count(base_url, subsystem, sent, received, 1);
+}
+
+fn count(base_url: &str, subsystem: &'static str, sent: u64, received: u64, requests: u64) {
+    let Ok(mut meter) = meter().lock() else {
+        return;
+    };
+    // Traffic to a host no profile uses, such as the plugin registry,
+    // counts under no profile.
+    let profile_id = net::base_origin(base_url)
+        .and_then(|origin| meter.profiles.get(&origin).cloned())
+        .unwrap_or_default();
+    let day = Utc::now().format("%Y-%m-%d").to_string();
+    meter
+        .pending
+        .entry((day, profile_id, subsystem))
+        .or_default()
+        .add(Counts {
+            sent,
+            received,
+            requests,
+        });
+}
+
+fn flush(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let pending = match meter().lock() {
+        Ok(mut meter) => std::mem::take(&mut meter.pending),
+        Err(_) => return Ok(0),
+    };
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO bandwidth_daily
+                (day, profile_id, subsystem, sent_bytes, received_bytes, requests)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(day, profile_id, subsystem) DO UPDATE SET
+                sent_bytes = sent_bytes + excluded.sent_bytes,
+                received_bytes = received_bytes + excluded.received_bytes,
+                requests = requests + excluded.requests",
+        )?;
+        for ((day, profile_id, subsystem), c) in &pending {
+            stmt.execute(params![
+                day,
+                profile_id,
+                subsystem,
+                c.sent as i64,
+                c.received as i64,
+                c.requests as i64
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+/// Folds the counts into the daily rollups every `FLUSH_INTERVAL`.
+pub fn spawn_flusher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let db = app.state::<LocalDb>();
+            let result = match db.lock() {
+                Ok(mut conn) => flush(&mut conn).map_err(|e| e.to_string()),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(_) => tasks::ran(TASK),
+                Err(err) => tasks::failed(TASK, &err),
+            }
+        }
+    });
+}
+
+/// Bytes sent and received per profile and subsystem (`interactive`,
+/// `polling`, `streaming`, `downloads`) over the last `days` days (30 by
+/// default), day by day and in total, for operators on metered
+/// connections. Counts are of request and response lines, headers and
+/// bodies, so TLS and TCP overhead come on top. `profile` narrows the
+/// report to one profile.
+#[tauri::command]
+pub fn bandwidth_report(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    days: Option<u32>,
+) -> Result<Value, String> {
+    let days = days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS);
+    let profile_id = match profile.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(id) => Some(store.connection(Some(id))?.profile_id),
+        None => None,
+    };
+    let since = Utc::now()
+        .date_naive()
+        .checked_sub_days(Days::new(u64::from(days - 1)))
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string();
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let mut conn = db.lock()?;
+    flush(&mut conn).map_err(storage)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT day, profile_id, subsystem, sent_bytes, received_bytes, requests
+             FROM bandwidth_daily
+             WHERE day >= ?1 AND (?2 IS NULL OR profile_id = ?2)
+             ORDER BY day, profile_id, subsystem",
+        )
+        .map_err(storage)?;
+    let rows = stmt
+        .query_map(params![since, profile_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                Counts {
+                    sent: row.get::<_, i64>(3)?.max(0) as u64,
+                    received: row.get::<_, i64>(4)?.max(0) as u64,
+                    requests: row.get::<_, i64>(5)?.max(0) as u64,
+                },
+            ))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(storage)?;
+
+    // profile -> (subsystem -> total, day -> (subsystem -> counts))
+    type Daily = BTreeMap<String, BTreeMap<String, Counts>>;
+    let mut profiles: BTreeMap<String, (BTreeMap<String, Counts>, Daily)> = BTreeMap::new();
+    let mut total = Counts::default();
+    for (day, profile_id, subsystem, counts) in rows {
+        let (totals, by_day) = profiles.entry(profile_id).or_default();
+        totals.entry(subsystem.clone()).or_default().add(counts);
+        by_day
+            .entry(day)
+            .or_default()
+            .entry(subsystem)
+            .or_default()
+            .add(counts);
+        total.add(counts);
+    }
+    let by_subsystem = |map: &BTreeMap<String, Counts>| {
+        let mut out = serde_json::Map::new();
+        let mut all = Counts::default();
+        for subsystem in SUBSYSTEMS {
+            let counts = map.get(*subsystem).copied().unwrap_or_default();
+            all.add(counts);
+            out.insert(subsystem.to_string(), counts.describe());
+        }
+        (Value::Object(out), all)
+    };
+    let profiles: Vec<Value> = profiles
+        .iter()
+        .map(|(id, (totals, by_day))| {
+            let (subsystems, all) = by_subsystem(totals);
+            let daily: Vec<Value> = by_day
+                .iter()
+                .map(|(day, counts)| {
+                    let (subsystems, all) = by_subsystem(counts);
+                    let mut entry = all.describe();
+                    entry["day"] = json!(day);
+                    entry["subsystems"] = subsystems;
+                    entry
+                })
+                .collect();
+            let mut entry = all.describe();
+            entry["profile_id"] = json!((!id.is_empty()).then_some(id));
+            entry["subsystems"] = subsystems;
+            entry["daily"] = json!(daily);
+            entry
+        })
+        .collect();
+    let mut report = total.describe();
+    report["since"] = json!(since);
+    report["days"] = json!(days);
+    report["low_data"] = json!(low_data());
+    report["profiles"] = json!(profiles);
+    Ok(report)
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::{Profile, SettingsStore};
+use crate::{notify, permissions, tasks, usage};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(600);
+const WARN_RATIO: f64 = 0.8;
+const LEVELS: &[&str] = &["ok", "warning", "critical", "exceeded"];
+
+/// Highest alert level already raised per `profile:period:bucket`, so each
+/// escalation notifies once.
+#[derive(Default)]
+pub struct BudgetAlerts {
+    raised: Mutex<HashMap<String, &'static str>>,
+}
+
+const TASK: &str = "budget";
+
+pub fn spawn_checker(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            check_all(&app).await;
+            tasks::ran(TASK);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn set_budget(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: String,
+    daily_usd: Option<f64>,
+    monthly_usd: Option<f64>,
+    pause_auto_approval: Option<bool>,
+) -> Result<Value, String> {
+    for limit in [daily_usd, monthly_usd].into_iter().flatten() {
+        if !limit.is_finite() || limit < 0.0 {
+            return Err(
+                ErrorCode::InvalidInput.with("Budget thresholds must be non-negative numbers")
+            );
+        }
+    }
+    let updated = store.update(&app, |settings| {
+        let target = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile)
+            .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile)))?;
+        permissions::ensure_profile_writable(target)?;
+        target.budget.daily_usd = daily_usd;
+        target.budget.monthly_usd = monthly_usd;
+        if let Some(pause) = pause_auto_approval {
+            target.budget.pause_auto_approval = pause;
+        }
+        Ok(())
+    })?;
+    let budget = updated.profile(&profile).map(|p| p.budget.clone());
+    serde_json::to_value(budget).map_err(|e| e.to_string())
+}
+
+/// Clears an auto-approval pause raised by a budget alert.
+#[tauri::command]
+pub fn resume_auto_approval(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: String,
+) -> Result<Value, String> {
+    if let Some(target) = store.snapshot().profile(&profile) {
+        permissions::ensure_profile_writable(target)?;
+    }
+    set_auto_approval_paused(&app, &store, &profile, false)?;
+    Ok(json!({ "profile_id": profile, "auto_approval_paused": false }))
+}
+
+#[tauri::command]
+pub fn get_budget_status(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let settings = store.snapshot();
+    let target = settings
+        .profile(&profile_id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    evaluate(&db, target, Utc::now())
+}
+
+/// Syncs usage and evaluates every profile's thresholds immediately.
+#[tauri::command]
+pub async fn check_budgets_now(app: AppHandle) -> Result<Value, String> {
+    Ok(json!({ "profiles": check_all(&app).await }))
+}
+
+async fn check_all(app: &AppHandle) -> Vec<Value> {
+    let store = app.state::<SettingsStore>();
+    let db = app.state::<LocalDb>();
+    let mut out = Vec::new();
+    for profile in store.snapshot().profiles {
+        if profile.budget.daily_usd.is_none() && profile.budget.monthly_usd.is_none() {
+            continue;
+        }
+        if let Ok(conn) = store.connection(Some(&profile.id)) {
+            if let Err(err) = usage::sync_profile(&conn, &db).await {
+                eprintln!("usage sync for {} failed: {}", profile.id, err);
+            }
+        }
+        match evaluate(&db, &profile, Utc::now()) {
+            Ok(status) => {
+                raise_alerts(app, &profile, &status);
+                out.push(status);
+            }
+            Err(err) => out.push(json!({ "profile_id": profile.id, "error": err })),
+        }
+    }
+    out
+}
+
+fn evaluate(db: &LocalDb, profile: &Profile, now: DateTime<Utc>) -> Result<Value, String> {
+    let today = now.date_naive();
+    let month_start = today.with_day(1).unwrap_or(today);
+    let days_in_month = days_in_month(today);
+    let guard = db.lock()?;
+    let day_key = today.format("%Y-%m-%d").to_string();
+    let spent_today = usage::spend_between(&guard, &profile.id, &day_key, &day_key);
+    let spent_month = usage::spend_between(
+        &guard,
+        &profile.id,
+        &month_start.format("%Y-%m-%d").to_string(),
+        &day_key,
+    );
+    drop(guard);
+
+    // Projections are linear; clamp the elapsed fraction so one early call
+    // right after midnight doesn't project an absurd total.
+    let day_fraction = (now.num_seconds_from_midnight() as f64 / 86_400.0).max(1.0 / 24.0);
+    let month_elapsed = (today.day0() as f64 + day_fraction).max(1.0);
+    let projected_today = spent_today / day_fraction;
+    let projected_month = spent_month / month_elapsed * days_in_month as f64;
+
+    Ok(json!({
+        "profile_id": profile.id,
+        "auto_approval_paused": profile.auto_approval_paused,
+        "daily": period_status(profile.budget.daily_usd, spent_today, projected_today, &day_key),
+        "monthly": period_status(
+            profile.budget.monthly_usd,
+            spent_month,
+            projected_month,
+            &today.format("%Y-%m").to_string(),
+        ),
+    }))
+}
+
+fn period_status(limit: Option<f64>, spent: f64, projected: f64, bucket: &str) -> Value {
+    let level = match limit {
+        None => "ok",
+        Some(limit) if spent >= limit => "exceeded",
+        Some(limit) if projected >= limit => "critical",
+        Some(limit) if projected >= limit * WARN_RATIO => "warning",
+        Some(_) => "ok",
+    };
+    json!({
+        "bucket": bucket,
+        "limit_usd": limit,
+        "spent_usd": spent,
+        "projected_usd": projected,
+        "level": level,
+    })
+}
+
+fn raise_alerts(app: &AppHandle, profile: &Profile, status: &Value) {
+    let alerts = app.state::<BudgetAlerts>();
+    for period in ["daily", "monthly"] {
+        let entry = &status[period];
+        let level = entry["level"].as_str().unwrap_or("ok");
+        let rank = LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+        if rank == 0 {
+            continue;
+        }
+        let key = format!(
+            "{}:{}:{}",
+            profile.id,
+            period,
+            entry["bucket"].as_str().unwrap_or_default()
+        );
+        let Ok(mut raised) = alerts.raised.lock() else {
+            return;
+        };
+        let previous = raised
+            .get(&key)
+            .and_then(|l| LEVELS.iter().position(|x| x == l))
+            .unwrap_or(0);
+        if rank <= previous {
+            continue;
+        }
+        raised.insert(key, LEVELS[rank]);
+        drop(raised);
+
+        let title = format!("{} {} budget {}", profile.name, period, level);
+        let body = format!(
+            "Spent ${:.2}, projected ${:.2} of ${:.2}",
+            entry["spent_usd"].as_f64().unwrap_or(0.0),
+            entry["projected_usd"].as_f64().unwrap_or(0.0),
+            entry["limit_usd"].as_f64().unwrap_or(0.0),
+        );
+        notify::notify(app, "budget", &title, &body, status.clone());
+
+        if rank >= 2 && profile.budget.pause_auto_approval && !profile.auto_approval_paused {
+            let store = app.state::<SettingsStore>();
+            if let Err(err) = set_auto_approval_paused(app, &store, &profile.id, true) {
+                eprintln!("pausing auto-approval for {} failed: {}", profile.id, err);
+            }
+        }
+    }
+}
+
+pub fn set_auto_approval_paused(
+    app: &AppHandle,
+    store: &SettingsStore,
+    profile_id: &str,
+    paused: bool,
+) -> Result<(), String> {
+    store.update(app, |settings| {
+        let target = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+        target.auto_approval_paused = paused;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn days_in_month(day: NaiveDate) -> u32 {
+    let (year, month) = if day.month() == 12 {
+        (day.year() + 1, 1)
+    } else {
+        (day.year(), day.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|next| next.pred_opt())
+        .map(|last| last.day())
+        .unwrap_or(30)
+}
@@ -0,0 +1,363 @@
+use std::borrow::Cow;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use reqwest::Url;
+use rusqlite::OptionalExtension;
+use serde_json::Value;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::db::LocalDb;
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+use crate::{attachments, markdown, tenants, thumbnails};
+
+pub const SCHEME: &str = "nova-cache";
+/// The most one response carries: a larger range, or a whole file past
+/// `MAX_WHOLE`, is answered with its first `MAX_RANGE` bytes as a 206 for
+/// the webview to ask for the rest.
+const MAX_RANGE: u64 = 8 * 1024 * 1024;
+const MAX_WHOLE: u64 = 64 * 1024 * 1024;
+/// Bytes read from the start of a file to tell its type.
+const SNIFF_BYTES: usize = 8 * 1024;
+/// Cached content is served as data, never as a page that can run script.
+const CONTENT_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'; sandbox";
+
+/// The URL the webview loads `path` (such as `artifacts/<sha256>`) from.
+/// Windows serves custom schemes over `http://<scheme>.localhost`.
+pub fn url(path: &str) -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost/{}", SCHEME, path)
+    } else {
+        format!("{}://localhost/{}", SCHEME, path)
+    }
+}
+
+struct Served {
+    status: StatusCode,
+    mime: String,
+    body: Vec<u8>,
+    /// The `Content-Range` of a partial or unsatisfiable response.
+    content_range: Option<String>,
+    immutable: bool,
+}
+
+/// Serves content the shell already holds, so the webview loads it by URL
+/// instead of as base64 over IPC:
+///
+/// - `artifacts/<sha256>`: the file `sync_artifact` last wrote with that
+///   digest, with `Range` support;
+/// - `thumbnails/<sha256>?size=`: a thumbnail already in the cache;
+/// - `diffs?plan=&profile=&step=`: the cached plan's diff (or a
+///   step's) rendered as HTML.
+///
+/// Nothing is fetched from the core.
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    let uri = request.uri().to_string();
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    tauri::async_runtime::spawn(async move {
+        let response = match serve(&app, &uri, range.as_deref()).await {
+            Ok(served) => {
+                let mut builder = Response::builder()
+                    .status(served.status)
+                    .header(header::CONTENT_TYPE, served.mime)
+                    .header(header::CONTENT_SECURITY_POLICY, CONTENT_POLICY)
+                    .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+                    .header(header::ACCEPT_RANGES, "bytes")
+                    .header(
+                        header::CACHE_CONTROL,
+                        if served.immutable {
+                            "max-age=31536000, immutable"
+                        } else {
+                            "no-cache"
+                        },
+                    );
+                if let Some(content_range) = served.content_range {
+                    builder = builder.header(header::CONTENT_RANGE, content_range);
+                }
+                builder.body(Cow::Owned(served.body))
+            }
+            Err(err) => {
+                let status = match errors::code_of(&err) {
+                    Some(ErrorCode::InvalidInput) => StatusCode::BAD_REQUEST,
+                    Some(ErrorCode::NotFound) => StatusCode::NOT_FOUND,
+                    Some(ErrorCode::Forbidden) => StatusCode::FORBIDDEN,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                Response::builder()
+                    .status(status)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(Cow::Owned(err.into_bytes()))
+            }
+        };
+        match response {
+            Ok(response) => responder.respond(response),
+            Err(e) => responder.respond(
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Cow::Owned(e.to_string().into_bytes()))
+                    .unwrap_or_default(),
+            ),
+        }
+    });
+}
+
+fn digest_of(raw: &str, what: &str) -> Result<String, String> {
+    let raw = raw.to_ascii_lowercase();
+    if raw.len() != 64 || !raw.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ErrorCode::InvalidInput.with(format!("{} URL must name a SHA-256 hash", what)));
+    }
+    Ok(raw)
+}
+
+async fn serve<R: Runtime>(
+    app: &AppHandle<R>,
+    uri: &str,
+    range: Option<&str>,
+) -> Result<Served, String> {
+    let parsed = Url::parse(uri)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid cache URL: {}", e)))?;
+    let query = |key: &str| {
+        parsed
+            .query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+            .filter(|v| !v.is_empty())
+    };
+    let path = parsed.path().trim_matches('/');
+    let (kind, rest) = path.split_once('/').unwrap_or((path, ""));
+    match kind {
+        "artifacts" => {
+            let sha256 = digest_of(rest, "Artifact")?;
+            let destination: Option<String> = app
+                .state::<LocalDb>()
+                .lock()?
+                .query_row(
+                    "SELECT destination FROM artifacts WHERE sha256 = ?1
+                     ORDER BY synced_at DESC LIMIT 1",
+                    [&sha256],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            let Some(destination) = destination else {
+                return Err(ErrorCode::NotFound.with("No synced artifact has that digest"));
+            };
+            file(Path::new(&destination), range, false).await
+        }
+        "thumbnails" => {
+            let sha256 = digest_of(rest, "Thumbnail")?;
+            let size = query("size").unwrap_or_default();
+            let cached = thumbnails::cached_path(app, &sha256, &size)?;
+            file(&cached, range, true).await
+        }
+        "diffs" => {
+            let plan_id = query("plan")
+                .ok_or_else(|| ErrorCode::InvalidInput.with("Diff URL must name a plan"))?;
+            let store = app.state::<SettingsStore>();
+            let conn = store.connection(query("profile").as_deref())?;
+            let payload: Option<String> = app
+                .state::<LocalDb>()
+                .lock()?
+                .query_row(
+                    "SELECT payload FROM sync_entities
+                     WHERE profile_id = ?1 AND kind = 'plan' AND entity_id = ?2",
+                    [tenants::cache_scope(&conn.profile_id), plan_id.clone()],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            let plan: Value = payload
+                .and_then(|raw| serde_json::from_str(&raw).ok())
+                .ok_or_else(|| ErrorCode::NotFound.with("Plan is not in the local cache"))?;
+            let diff = match query("step") {
+                Some(step) => {
+                    let index: usize = step
+                        .parse()
+                        .map_err(|_| ErrorCode::InvalidInput.with("step must be an index"))?;
+                    plan["steps"][index]["diff"].as_str()
+                }
+                None => plan["diff"].as_str(),
+            }
+            .filter(|d| !d.trim().is_empty())
+            .ok_or_else(|| ErrorCode::NotFound.with("Plan has no diff"))?;
+            Ok(Served {
+                status: StatusCode::OK,
+                mime: "text/html; charset=utf-8".to_string(),
+                body: render_diff(&plan_id, diff).into_bytes(),
+                content_range: None,
+                immutable: false,
+            })
+        }
+        _ => Err(ErrorCode::NotFound.with(format!("Nothing is served under /{}", kind))),
+    }
+}
+
+/// The byte span a `Range` header asks for in a file of `total` bytes,
+/// capped at `MAX_RANGE`, or `Err` when none of it is in the file. Only
+/// single ranges are honoured; anything else gets the whole file.
+fn span(range: Option<&str>, total: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = range.and_then(|r| r.trim().strip_prefix("bytes=")) else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let (start, end) = match spec.split_once('-') {
+        Some(("", suffix)) => {
+            let len: u64 = suffix.trim().parse().map_err(|_| ())?;
+            if len == 0 {
+                return Err(());
+            }
+            (total.saturating_sub(len), total.saturating_sub(1))
+        }
+        Some((start, end)) => {
+            let start: u64 = start.trim().parse().map_err(|_| ())?;
+            let end = match end.trim() {
+                "" => total.saturating_sub(1),
+                end => end
+                    .parse::<u64>()
+                    .map_err(|_| ())?
+                    .min(total.saturating_sub(1)),
+            };
+            (start, end)
+        }
+        None => return Ok(None),
+    };
+    if total == 0 || start >= total || start > end {
+        return Err(());
+    }
+    Ok(Some((start, end.min(start + MAX_RANGE - 1))))
+}
+
+async fn file(path: &Path, range: Option<&str>, immutable: bool) -> Result<Served, String> {
+    let io = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::NotFound => ErrorCode::NotFound.with("Cached file is gone"),
+        _ => ErrorCode::LocalIo.with(format!("Read cached file failed: {}", e)),
+    };
+    let mut source = tokio::fs::File::open(path).await.map_err(io)?;
+    let total = source.metadata().await.map_err(io)?.len();
+    let mut head = vec![0u8; SNIFF_BYTES.min(total as usize)];
+    source.read_exact(&mut head).await.map_err(io)?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (mime, _) = attachments::detect_mime(&name, &head);
+
+    let Ok(asked) = span(range, total) else {
+        return Ok(Served {
+            status: StatusCode::RANGE_NOT_SATISFIABLE,
+            mime,
+            body: Vec::new(),
+            content_range: Some(format!("bytes */{}", total)),
+            immutable,
+        });
+    };
+    let (start, end) = match asked {
+        Some(span) => span,
+        None if total > MAX_WHOLE => (0, MAX_RANGE - 1),
+        None => (0, total.saturating_sub(1)),
+    };
+    let length = if total == 0 { 0 } else { end - start + 1 };
+    let mut body = vec![0u8; length as usize];
+    source.seek(SeekFrom::Start(start)).await.map_err(io)?;
+    source.read_exact(&mut body).await.map_err(io)?;
+    let partial = asked.is_some() || length < total;
+    Ok(Served {
+        status: if partial {
+            StatusCode::PARTIAL_CONTENT
+        } else {
+            StatusCode::OK
+        },
+        mime,
+        body,
+        content_range: partial.then(|| format!("bytes {}-{}/{}", start, end, total)),
+        immutable,
+    })
+}
+
+/// A unified diff as a standalone page: each line escaped and marked as
+/// added, removed, a hunk header or file metadata for the stylesheet.
+fn render_diff(title: &str, diff: &str) -> String {
+    let mut out = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>{}</title><style>\
+         body{{margin:0;font:12px/1.5 ui-monospace,monospace}}\
+         pre{{margin:0;padding:8px}}span{{display:block;white-space:pre-wrap}}\
+         .add{{background:#e6ffec}}.del{{background:#ffebe9}}\
+         .hunk{{color:#0550ae;background:#ddf4ff}}.meta{{font-weight:bold}}\
+         </style></head><body><pre>",
+        markdown::escape(title)
+    );
+    for line in diff.lines() {
+        let class = if line.starts_with("+++ ")
+            || line.starts_with("--- ")
+            || line.starts_with("diff ")
+            || line.starts_with("index ")
+        {
+            "meta"
+        } else if line.starts_with("@@") {
+            "hunk"
+        } else if line.starts_with('+') {
+            "add"
+        } else if line.starts_with('-') {
+            "del"
+        } else {
+            "ctx"
+        };
+        out.push_str(&format!(
+            "<span class=\"{}\">{}</span>",
+            class,
+            markdown::escape(line)
+        ));
+    }
+    out.push_str("</pre></body></html>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_reads_single_byte_ranges() {
+        assert_eq!(span(None, 100), Ok(None));
+        assert_eq!(span(Some("bytes=0-9"), 100), Ok(Some((0, 9))));
+        assert_eq!(span(Some("bytes=90-"), 100), Ok(Some((90, 99))));
+        assert_eq!(span(Some("bytes=-10"), 100), Ok(Some((90, 99))));
+        assert_eq!(span(Some("bytes=50-500"), 100), Ok(Some((50, 99))));
+        assert_eq!(span(Some("bytes=-500"), 100), Ok(Some((0, 99))));
+    }
+
+    #[test]
+    fn span_serves_the_whole_file_for_what_it_does_not_honour() {
+        assert_eq!(span(Some("bytes=0-1,5-6"), 100), Ok(None));
+        assert_eq!(span(Some("items=0-1"), 100), Ok(None));
+    }
+
+    #[test]
+    fn span_refuses_ranges_outside_the_file() {
+        assert_eq!(span(Some("bytes=100-"), 100), Err(()));
+        assert_eq!(span(Some("bytes=9-3"), 100), Err(()));
+        assert_eq!(span(Some("bytes=-0"), 100), Err(()));
+        assert_eq!(span(Some("bytes=0-"), 0), Err(()));
+        assert_eq!(span(Some("bytes=a-b"), 100), Err(()));
+    }
+
+    #[test]
+    fn span_caps_long_ranges() {
+        let total = 3 * MAX_RANGE;
+        assert_eq!(span(Some("bytes=0-"), total), Ok(Some((0, MAX_RANGE - 1))));
+    }
+}
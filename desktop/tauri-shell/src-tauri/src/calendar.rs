@@ -0,0 +1,1098 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc,
+    Weekday,
+};
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{
+    CalendarKind, CalendarSource, ExecutionWindow, Profile, SettingsStore, WindowAction,
+};
+use crate::{net, secrets, tasks};
+
+const TASK: &str = "calendar";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_SOURCES: usize = 10;
+const MAX_REFRESH_MINUTES: u32 = 24 * 60;
+const MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+/// Freezes are expanded this far ahead, which is as far as a scheduled
+/// approval can be checked against them.
+const HORIZON_DAYS: i64 = 90;
+/// Recurrence periods walked per event, so a daily series begun years ago
+/// still ends.
+const MAX_PERIODS: u32 = 20_000;
+const MAX_FREEZES: usize = 5_000;
+const WEEKDAYS: &[(&str, Weekday)] = &[
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+#[derive(Clone)]
+struct Freeze {
+    summary: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct SourceState {
+    settings: Option<CalendarSource>,
+    fetched: Option<Instant>,
+    fetched_at: Option<DateTime<Utc>>,
+    freezes: Vec<Freeze>,
+    last_error: Option<String>,
+}
+
+/// Calendar sources by profile and source id, with the freezes last read
+/// from each.
+static STATE: OnceLock<Mutex<HashMap<(String, String), SourceState>>> = OnceLock::new();
+/// The profile each core origin belongs to, for profiles with calendars.
+static ORIGINS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn state() -> &'static Mutex<HashMap<(String, String), SourceState>> {
+    STATE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn origins() -> &'static Mutex<HashMap<String, String>> {
+    ORIGINS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn calendar_secret_key(profile_id: &str, source_id: &str) -> String {
+    format!("profile:{}:calendar:{}", profile_id, source_id)
+}
+
+/// Tracks the profiles' calendar sources; a source whose settings changed
+/// drops what was read from it and is fetched again.
+pub fn configure(profiles: &[Profile]) {
+    let mut state = state().lock().unwrap_or_else(|e| e.into_inner());
+    state.retain(|(profile_id, source_id), entry| {
+        profiles.iter().any(|p| {
+            p.id == *profile_id
+                && p.calendars
+                    .iter()
+                    .any(|c| c.id == *source_id && entry.settings.as_ref() == Some(c))
+        })
+    });
+    for profile in profiles {
+        for source in &profile.calendars {
+            state
+                .entry((profile.id.clone(), source.id.clone()))
+                .or_insert_with(|| SourceState {
+                    settings: Some(source.clone()),
+                    ..SourceState::default()
+                });
+        }
+    }
+    let map = profiles
+        .iter()
+        .filter(|p| !p.calendars.is_empty())
+        .flat_map(|p| {
+            p.endpoints()
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, p.id.clone()))
+        })
+        .collect();
+    *origins().lock().unwrap_or_else(|e| e.into_inner()) = map;
+}
+
+pub fn validate(sources: &[CalendarSource]) -> Result<(), String> {
+    if sources.len() > MAX_SOURCES {
+        return Err(format!("At most {} calendars per profile", MAX_SOURCES));
+    }
+    let mut ids = HashSet::new();
+    for source in sources {
+        let id = source.id.trim();
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            || !ids.insert(id)
+        {
+            return Err("Calendar ids must be unique letters, digits, - or _".to_string());
+        }
+        let url = fetch_url(source).map_err(|e| format!("Calendar {}: {}", id, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Calendar {}: URL must be http(s) or webcal", id));
+        }
+        if source.refresh_minutes == 0 || source.refresh_minutes > MAX_REFRESH_MINUTES {
+            return Err(format!(
+                "Calendar {}: refresh must be 1 to {} minutes",
+                id, MAX_REFRESH_MINUTES
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn fetch_url(source: &CalendarSource) -> Result<Url, String> {
+    let raw = source.url.trim();
+    let raw = match raw.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => raw.to_string(),
+    };
+    Url::parse(&raw).map_err(|e| format!("invalid URL: {}", e))
+}
+
+// ---- ICS parsing ----
+
+struct Property {
+    name: String,
+    params: HashMap<String, String>,
+    value: String,
+}
+
+/// Content lines with folding undone (RFC 5545 §3.1).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+fn property(line: &str) -> Option<Property> {
+    // The value starts at the first colon outside a quoted parameter.
+    let mut quoted = false;
+    let split = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..split], &line[split + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            Some((
+                key.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn local(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// A DATE or DATE-TIME value: UTC with a trailing `Z`, otherwise (floating
+/// or with a `TZID`) local time. The flag says whether it was a DATE.
+fn date_time(value: &str, params: &HashMap<String, String>) -> Option<(DateTime<Utc>, bool)> {
+    let value = value.trim();
+    if params
+        .get("VALUE")
+        .is_some_and(|v| v.eq_ignore_ascii_case("DATE"))
+        || value.len() == 8
+    {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((local(date.and_time(NaiveTime::MIN))?, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&naive), false));
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((local(naive)?, false))
+}
+
+/// An ICS DURATION such as `PT1H30M`, `P1D` or `P2W`.
+fn duration(value: &str) -> Option<chrono::Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match (unit, in_time) {
+                    ('W', false) => 7 * 86_400,
+                    ('D', false) => 86_400,
+                    ('H', true) => 3_600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(chrono::Duration::seconds(if negative {
+        -seconds
+    } else {
+        seconds
+    }))
+}
+
+#[derive(Default)]
+struct Event {
+    uid: String,
+    summary: String,
+    start: Option<DateTime<Utc>>,
+    all_day: bool,
+    end: Option<DateTime<Utc>>,
+    duration: Option<chrono::Duration>,
+    rrule: Option<String>,
+    exdates: Vec<DateTime<Utc>>,
+    recurrence_id: Option<DateTime<Utc>>,
+    cancelled: bool,
+}
+
+fn events(text: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut current: Option<Event> = None;
+    // Components nested in the event (alarms) carry no event properties.
+    let mut nested = 0usize;
+    for line in unfold(text) {
+        let Some(prop) = property(&line) else {
+            continue;
+        };
+        match (
+            prop.name.as_str(),
+            prop.value.trim().to_ascii_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") => {
+                current = Some(Event::default());
+                nested = 0;
+                continue;
+            }
+            ("END", "VEVENT") => {
+                events.extend(current.take());
+                continue;
+            }
+            ("BEGIN", _) if current.is_some() => {
+                nested += 1;
+                continue;
+            }
+            ("END", _) if current.is_some() => {
+                nested = nested.saturating_sub(1);
+                continue;
+            }
+            _ => {}
+        }
+        let Some(event) = current.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match prop.name.as_str() {
+            "UID" => event.uid = prop.value.trim().to_string(),
+            "SUMMARY" => {
+                event.summary = prop
+                    .value
+                    .replace("\\n", " ")
+                    .replace("\\N", " ")
+                    .replace("\\,", ",")
+                    .replace("\\;", ";")
+                    .replace("\\\\", "\\")
+                    .trim()
+                    .to_string()
+            }
+            "DTSTART" => {
+                if let Some((at, all_day)) = date_time(&prop.value, &prop.params) {
+                    event.start = Some(at);
+                    event.all_day = all_day;
+                }
+            }
+            "DTEND" => event.end = date_time(&prop.value, &prop.params).map(|(at, _)| at),
+            "DURATION" => event.duration = duration(&prop.value),
+            "RRULE" => event.rrule = Some(prop.value.trim().to_string()),
+            "EXDATE" => event.exdates.extend(
+                prop.value
+                    .split(',')
+                    .filter_map(|v| date_time(v, &prop.params).map(|(at, _)| at)),
+            ),
+            "RECURRENCE-ID" => {
+                event.recurrence_id = date_time(&prop.value, &prop.params).map(|(at, _)| at)
+            }
+            "STATUS" => event.cancelled = prop.value.trim().eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    events
+}
+
+// ---- Recurrence ----
+
+#[derive(Clone, Copy, PartialEq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+struct Rule {
+    freq: Freq,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateTime<Utc>>,
+    /// Weekdays, with an ordinal within the month for monthly rules
+    /// (`-1FR` is the last Friday).
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+}
+
+fn rule(raw: &str) -> Option<Rule> {
+    let mut parsed = Rule {
+        freq: Freq::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+    };
+    let mut freq = None;
+    for part in raw.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(match value.trim().to_ascii_uppercase().as_str() {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    "YEARLY" => Freq::Yearly,
+                    // Sub-daily freezes are not a thing worth expanding.
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => parsed.interval = value.trim().parse().ok().filter(|n| *n > 0)?,
+            "COUNT" => parsed.count = value.trim().parse().ok(),
+            "UNTIL" => {
+                let (at, date) = date_time(value, &HashMap::new())?;
+                // A date-only UNTIL includes that whole day.
+                parsed.until = Some(if date {
+                    at + chrono::Duration::days(1) - chrono::Duration::seconds(1)
+                } else {
+                    at
+                });
+            }
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let day = day.trim().to_ascii_uppercase();
+                    let split = day.len().checked_sub(2)?;
+                    let weekday = WEEKDAYS
+                        .iter()
+                        .find(|(name, _)| *name == &day[split..])
+                        .map(|(_, d)| *d)?;
+                    let ordinal = match &day[..split] {
+                        "" => None,
+                        n => Some(n.trim_start_matches('+').parse().ok()?),
+                    };
+                    parsed.by_day.push((ordinal, weekday));
+                }
+            }
+            "BYMONTHDAY" => {
+                for day in value.split(',') {
+                    parsed.by_month_day.push(day.trim().parse().ok()?);
+                }
+            }
+            _ => {}
+        }
+    }
+    parsed.freq = freq?;
+    Some(parsed)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map_or(28, |d| d.day())
+}
+
+/// The days of one month a monthly rule picks, in order.
+fn month_days(rule: &Rule, year: i32, month: u32, start: NaiveDate) -> Vec<NaiveDate> {
+    let last = days_in_month(year, month);
+    let date = |day: u32| NaiveDate::from_ymd_opt(year, month, day);
+    let mut days: Vec<NaiveDate> = if !rule.by_month_day.is_empty() {
+        rule.by_month_day
+            .iter()
+            .filter_map(|&d| match d {
+                1.. => date(d as u32),
+                ..=-1 => date((last as i32 + d + 1).max(0) as u32),
+                0 => None,
+            })
+            .collect()
+    } else if !rule.by_day.is_empty() {
+        let mut picked = Vec::new();
+        for &(ordinal, weekday) in &rule.by_day {
+            let matching: Vec<NaiveDate> = (1..=last)
+                .filter_map(date)
+                .filter(|d| d.weekday() == weekday)
+                .collect();
+            match ordinal {
+                None => picked.extend(matching),
+                Some(n) if n > 0 => picked.extend(matching.get(n as usize - 1)),
+                Some(n) => picked.extend(
+                    matching
+                        .len()
+                        .checked_sub(n.unsigned_abs() as usize)
+                        .and_then(|i| matching.get(i)),
+                ),
+            }
+        }
+        picked
+    } else {
+        date(start.day()).into_iter().collect()
+    };
+    days.sort();
+    days.dedup();
+    days
+}
+
+fn add_months(year: i32, month: u32, months: u32) -> (i32, u32) {
+    let total = year * 12 + month as i32 - 1 + months as i32;
+    (total.div_euclid(12), total.rem_euclid(12) as u32 + 1)
+}
+
+/// Start times of the event's occurrences that overlap `from..to`.
+/// Recurrence is stepped in local time, so a 09:00 freeze stays at 09:00
+/// across daylight saving changes.
+fn occurrences(
+    start: DateTime<Utc>,
+    length: chrono::Duration,
+    rule: &Rule,
+    exdates: &HashSet<DateTime<Utc>>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    let first = start.with_timezone(&Local).naive_local();
+    let (date, time) = (first.date(), first.time());
+    let mut found = Vec::new();
+    let mut counted = 0u32;
+    for period in 0..MAX_PERIODS {
+        let step = period * rule.interval;
+        let candidates: Vec<NaiveDate> = match rule.freq {
+            Freq::Daily => {
+                let day = date + chrono::Duration::days(i64::from(step));
+                let wanted =
+                    rule.by_day.is_empty() || rule.by_day.iter().any(|(_, w)| *w == day.weekday());
+                if wanted {
+                    vec![day]
+                } else {
+                    Vec::new()
+                }
+            }
+            Freq::Weekly => {
+                let monday = date
+                    - chrono::Duration::days(date.weekday().num_days_from_monday().into())
+                    + chrono::Duration::weeks(i64::from(step));
+                let mut days: Vec<NaiveDate> = if rule.by_day.is_empty() {
+                    vec![
+                        monday
+                            + chrono::Duration::days(date.weekday().num_days_from_monday().into()),
+                    ]
+                } else {
+                    rule.by_day
+                        .iter()
+                        .map(|(_, w)| {
+                            monday + chrono::Duration::days(w.num_days_from_monday().into())
+                        })
+                        .collect()
+                };
+                days.sort();
+                days.dedup();
+                days
+            }
+            Freq::Monthly => {
+                let (year, month) = add_months(date.year(), date.month(), step);
+                month_days(rule, year, month, date)
+            }
+            Freq::Yearly => {
+                NaiveDate::from_ymd_opt(date.year() + step as i32, date.month(), date.day())
+                    .into_iter()
+                    .collect()
+            }
+        };
+        for day in candidates {
+            if day < date {
+                continue;
+            }
+            let Some(at) = local(day.and_time(time)) else {
+                continue;
+            };
+            if rule.until.is_some_and(|u| at > u) || at >= to {
+                return found;
+            }
+            if rule.count.is_some_and(|c| counted >= c) {
+                return found;
+            }
+            counted += 1;
+            if at + length > from && !exdates.contains(&at) {
+                found.push(at);
+            }
+        }
+    }
+    found
+}
+
+/// The freezes in `text` overlapping `from..to`, matching `filter`.
+fn freezes(text: &str, filter: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Freeze> {
+    let parsed = events(text);
+    // An occurrence moved or cancelled by its own VEVENT is taken from that
+    // one, not from the series.
+    let mut overridden: HashMap<&str, HashSet<DateTime<Utc>>> = HashMap::new();
+    for event in &parsed {
+        if let Some(at) = event.recurrence_id {
+            overridden.entry(event.uid.as_str()).or_default().insert(at);
+        }
+    }
+    let filter = filter.trim().to_lowercase();
+    let mut out = Vec::new();
+    for event in &parsed {
+        if event.cancelled
+            || (!filter.is_empty() && !event.summary.to_lowercase().contains(&filter))
+        {
+            continue;
+        }
+        let Some(start) = event.start else {
+            continue;
+        };
+        let end = event
+            .end
+            .or_else(|| event.duration.map(|d| start + d))
+            .unwrap_or_else(|| {
+                if event.all_day {
+                    start + chrono::Duration::days(1)
+                } else {
+                    start
+                }
+            });
+        let length = end - start;
+        if length <= chrono::Duration::zero() {
+            continue;
+        }
+        let starts = match event.rrule.as_deref().and_then(rule) {
+            Some(rule) if event.recurrence_id.is_none() => {
+                let mut exdates: HashSet<DateTime<Utc>> = event.exdates.iter().copied().collect();
+                if let Some(moved) = overridden.get(event.uid.as_str()) {
+                    exdates.extend(moved);
+                }
+                occurrences(start, length, &rule, &exdates, from, to)
+            }
+            _ if start < to && end > from => vec![start],
+            _ => Vec::new(),
+        };
+        out.extend(starts.into_iter().map(|at| Freeze {
+            summary: event.summary.clone(),
+            start: at,
+            end: at + length,
+        }));
+        if out.len() >= MAX_FREEZES {
+            break;
+        }
+    }
+    out.sort_by_key(|f| f.start);
+    out.truncate(MAX_FREEZES);
+    out
+}
+
+/// The `calendar-data` of each object in a CalDAV multistatus response.
+fn calendar_data(xml: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find("calendar-data") {
+        let after = &rest[open..];
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        // A self-closing or closing tag holds no data.
+        if after[..tag_end].ends_with('/') || rest[..open].ends_with("</") {
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let body = &after[tag_end + 1..];
+        let Some(close) = body.find("</") else {
+            break;
+        };
+        let data = body[..close]
+            .trim()
+            .trim_start_matches("<![CDATA[")
+            .trim_end_matches("]]>");
+        found.push(
+            data.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&#13;", "\r")
+                .replace("&#xD;", "\r")
+                .replace("&amp;", "&"),
+        );
+        rest = &body[close..];
+    }
+    found
+}
+
+async fn fetch(
+    profile_id: &str,
+    source: &CalendarSource,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<String, String> {
+    let url = fetch_url(source).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let client = net::client_for(&url)?;
+    let mut request = match source.kind {
+        CalendarKind::Ics => client.get(url),
+        CalendarKind::Caldav => {
+            let range = |at: DateTime<Utc>| at.format("%Y%m%dT%H%M%SZ").to_string();
+            let body = format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data/></D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{}" end="{}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#,
+                range(from),
+                range(to)
+            );
+            let report = Method::from_bytes(b"REPORT")
+                .map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+            client
+                .request(report, url)
+                .header("Depth", "1")
+                .header("Content-Type", "application/xml; charset=utf-8")
+                .body(body)
+        }
+    };
+    if !source.username.trim().is_empty() {
+        let password = secrets::get(&calendar_secret_key(profile_id, &source.id))?;
+        request = request.basic_auth(source.username.trim(), password);
+    }
+    let response = request.timeout(REQUEST_TIMEOUT).send().await.map_err(|e| {
+        let code = if e.is_timeout() {
+            ErrorCode::CoreTimeout
+        } else {
+            ErrorCode::CoreUnreachable
+        };
+        code.with(format!("Calendar request failed: {}", e.without_url()))
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ErrorCode::from_status(status.as_u16())
+            .with(format!("Calendar answered {}", status.as_u16())));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("Calendar read failed: {}", e)))?;
+    if bytes.len() > MAX_BODY_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Calendar is larger than {} MB",
+            MAX_BODY_BYTES / (1024 * 1024)
+        )));
+    }
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    Ok(match source.kind {
+        CalendarKind::Ics => text,
+        CalendarKind::Caldav => calendar_data(&text).join("\n"),
+    })
+}
+
+async fn refresh(profile_id: &str, source: &CalendarSource) -> Result<usize, String> {
+    let now = Utc::now();
+    let from = now - chrono::Duration::days(1);
+    let to = now + chrono::Duration::days(HORIZON_DAYS);
+    let outcome = fetch(profile_id, source, from, to)
+        .await
+        .map(|text| freezes(&text, &source.filter, from, to));
+    let mut state = state()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Calendar state poisoned"))?;
+    let key = (profile_id.to_string(), source.id.clone());
+    // Removed or changed while the fetch was in flight.
+    let Some(entry) = state
+        .get_mut(&key)
+        .filter(|e| e.settings.as_ref() == Some(source))
+    else {
+        return Ok(0);
+    };
+    entry.fetched = Some(Instant::now());
+    match outcome {
+        Ok(freezes) => {
+            let count = freezes.len();
+            entry.freezes = freezes;
+            entry.fetched_at = Some(now);
+            entry.last_error = None;
+            Ok(count)
+        }
+        Err(err) => {
+            // The freezes last read stay in force; a calendar that cannot
+            // be reached lifts no freeze.
+            entry.last_error = Some(err.clone());
+            Err(err)
+        }
+    }
+}
+
+/// Re-reads each calendar once its refresh interval has passed.
+pub fn spawn_refresher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |_| async move {
+        loop {
+            let due: Vec<(String, CalendarSource)> = match state().lock() {
+                Ok(state) => state
+                    .iter()
+                    .filter_map(|((profile_id, _), entry)| {
+                        let source = entry.settings.clone()?;
+                        let interval = Duration::from_secs(u64::from(source.refresh_minutes) * 60);
+                        entry
+                            .fetched
+                            .is_none_or(|at| at.elapsed() >= interval)
+                            .then(|| (profile_id.clone(), source))
+                    })
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            for (profile_id, source) in due {
+                if let Err(err) = refresh(&profile_id, &source).await {
+                    tasks::failed(TASK, &err);
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+fn local_label(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// The calendar freeze covering `at` for the core at `origin`, as an
+/// execution window; a `block` freeze wins over a `downgrade` one.
+pub fn window_at(origin: &str, at: DateTime<Utc>) -> Option<ExecutionWindow> {
+    let profile_id = origins().lock().ok()?.get(origin)?.clone();
+    let state = state().lock().ok()?;
+    let mut covering: Vec<(&CalendarSource, &Freeze)> = state
+        .iter()
+        .filter(|((p, _), _)| *p == profile_id)
+        .filter_map(|(_, entry)| {
+            let source = entry.settings.as_ref()?;
+            let freeze = entry.freezes.iter().find(|f| f.start <= at && at < f.end)?;
+            Some((source, freeze))
+        })
+        .collect();
+    covering.sort_by_key(|(source, _)| source.action != WindowAction::Block);
+    let (source, freeze) = covering.first()?;
+    let start = freeze.start.with_timezone(&Local);
+    let end = freeze.end.with_timezone(&Local);
+    let summary = if freeze.summary.is_empty() {
+        "Calendar freeze"
+    } else {
+        freeze.summary.as_str()
+    };
+    let calendar = if source.name.is_empty() {
+        &source.id
+    } else {
+        &source.name
+    };
+    Some(ExecutionWindow {
+        name: format!(
+            "{} ({}, until {})",
+            summary,
+            calendar,
+            local_label(freeze.end)
+        ),
+        days: Vec::new(),
+        start: start.format("%H:%M").to_string(),
+        end: end.format("%H:%M").to_string(),
+        action: source.action,
+    })
+}
+
+/// The profile's calendars with their state and the freezes ahead.
+#[tauri::command]
+pub fn get_calendar_freezes(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    days: Option<u32>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let now = Utc::now();
+    let until = now + chrono::Duration::days(i64::from(days.unwrap_or(14).clamp(1, 90)));
+    let state = state()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Calendar state poisoned"))?;
+    let mut calendars: Vec<Value> = state
+        .iter()
+        .filter(|((p, _), _)| *p == conn.profile_id)
+        .filter_map(|((_, id), entry)| {
+            let source = entry.settings.as_ref()?;
+            let ahead: Vec<Value> = entry
+                .freezes
+                .iter()
+                .filter(|f| f.end > now && f.start < until)
+                .map(|f| {
+                    json!({
+                        "summary": f.summary,
+                        "start": f.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        "end": f.end.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        "active": f.start <= now,
+                    })
+                })
+                .collect();
+            Some(json!({
+                "id": id,
+                "name": source.name,
+                "kind": source.kind,
+                "action": source.action,
+                "fetched_at": entry
+                    .fetched_at
+                    .map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                "last_error": entry.last_error,
+                "freezes": ahead,
+            }))
+        })
+        .collect();
+    calendars.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    Ok(json!({ "profile_id": conn.profile_id, "calendars": calendars }))
+}
+
+/// Reads one calendar now instead of at its next refresh.
+#[tauri::command]
+pub async fn refresh_calendar(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    calendar_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let source = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .and_then(|p| p.calendars.iter().find(|c| c.id == calendar_id).cloned())
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown calendar: {}", calendar_id)))?;
+    let freezes = refresh(&conn.profile_id, &source).await?;
+    Ok(json!({ "profile_id": conn.profile_id, "calendar_id": calendar_id, "freezes": freezes }))
+}
+
+/// Stores (or with `None`, clears) the password a calendar is read with.
+#[tauri::command]
+pub fn set_calendar_secret(
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+    calendar_id: String,
+    secret: Option<String>,
+) -> Result<Value, String> {
+    let known = store
+        .snapshot()
+        .profile(&profile_id)
+        .is_some_and(|p| p.calendars.iter().any(|c| c.id == calendar_id));
+    if !known {
+        return Err(ErrorCode::NotFound.with(format!("Unknown calendar: {}", calendar_id)));
+    }
+    let key = calendar_secret_key(&profile_id, &calendar_id);
+    let secret = secret.filter(|v| !v.trim().is_empty());
+    match secret.as_deref() {
+        Some(value) => secrets::set(&key, value)?,
+        None => secrets::delete(&key)?,
+    }
+    if let Ok(mut state) = state().lock() {
+        if let Some(entry) = state.get_mut(&(profile_id.clone(), calendar_id.clone())) {
+            entry.fetched = None;
+        }
+    }
+    Ok(json!({ "profile_id": profile_id, "calendar_id": calendar_id, "stored": secret.is_some() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A floating time, which is local like the expansion itself.
+    fn at(value: &str) -> DateTime<Utc> {
+        date_time(value, &HashMap::new()).unwrap().0
+    }
+
+    fn calendar(events: &[&[&str]]) -> String {
+        let mut text = String::from("BEGIN:VCALENDAR\r\n");
+        for lines in events {
+            text.push_str("BEGIN:VEVENT\r\n");
+            for line in *lines {
+                text.push_str(line);
+                text.push_str("\r\n");
+            }
+            text.push_str("END:VEVENT\r\n");
+        }
+        text.push_str("END:VCALENDAR\r\n");
+        text
+    }
+
+    fn starts(text: &str, from: &str, to: &str) -> Vec<DateTime<Utc>> {
+        freezes(text, "", at(from), at(to))
+            .into_iter()
+            .map(|f| f.start)
+            .collect()
+    }
+
+    fn series(rrule: &str, start: &str) -> Vec<DateTime<Utc>> {
+        let text = calendar(&[&[
+            "UID:freeze",
+            "SUMMARY:Change freeze",
+            &format!("DTSTART:{}", start),
+            "DURATION:PT1H",
+            &format!("RRULE:{}", rrule),
+        ]]);
+        starts(&text, "20260101T000000", "20260401T000000")
+    }
+
+    #[test]
+    fn daily_and_weekly_rules_expand() {
+        assert_eq!(
+            series("FREQ=DAILY;COUNT=3", "20260105T090000"),
+            [
+                at("20260105T090000"),
+                at("20260106T090000"),
+                at("20260107T090000")
+            ]
+        );
+        // A date-only UNTIL includes its whole day.
+        assert_eq!(
+            series(
+                "FREQ=WEEKLY;BYDAY=MO,WE,FR;UNTIL=20260116",
+                "20260105T090000"
+            ),
+            [
+                at("20260105T090000"),
+                at("20260107T090000"),
+                at("20260109T090000"),
+                at("20260112T090000"),
+                at("20260114T090000"),
+                at("20260116T090000")
+            ]
+        );
+        assert_eq!(
+            series("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU;COUNT=3", "20260106T220000"),
+            [
+                at("20260106T220000"),
+                at("20260120T220000"),
+                at("20260203T220000")
+            ]
+        );
+        assert_eq!(
+            series("FREQ=DAILY;BYDAY=SA,SU;COUNT=2", "20260105T090000"),
+            [at("20260110T090000"), at("20260111T090000")]
+        );
+    }
+
+    #[test]
+    fn monthly_rules_pick_ordinal_weekdays_and_month_days() {
+        assert_eq!(
+            series("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3", "20260130T180000"),
+            [
+                at("20260130T180000"),
+                at("20260227T180000"),
+                at("20260327T180000")
+            ]
+        );
+        assert_eq!(
+            series("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=3", "20260131T000000"),
+            [
+                at("20260131T000000"),
+                at("20260228T000000"),
+                at("20260331T000000")
+            ]
+        );
+    }
+
+    #[test]
+    fn excluded_and_overridden_occurrences_are_left_out() {
+        let text = calendar(&[
+            &[
+                "UID:freeze",
+                "SUMMARY:Change freeze",
+                "DTSTART:20260105T090000",
+                "DTEND:20260105T100000",
+                "RRULE:FREQ=DAILY;COUNT=5",
+                "EXDATE:20260106T090000",
+            ],
+            &[
+                "UID:freeze",
+                "SUMMARY:Change freeze",
+                "RECURRENCE-ID:20260107T090000",
+                "DTSTART:20260107T090000",
+                "DTEND:20260107T100000",
+                "STATUS:CANCELLED",
+            ],
+            &[
+                "UID:freeze",
+                "SUMMARY:Change freeze",
+                "RECURRENCE-ID:20260108T090000",
+                "DTSTART:20260108T140000",
+                "DTEND:20260108T150000",
+            ],
+        ]);
+        assert_eq!(
+            starts(&text, "20260101T000000", "20260201T000000"),
+            [
+                at("20260105T090000"),
+                at("20260108T140000"),
+                at("20260109T090000")
+            ]
+        );
+        // Only occurrences overlapping the window are returned.
+        assert_eq!(
+            starts(&text, "20260105T093000", "20260109T000000"),
+            [at("20260105T090000"), at("20260108T140000")]
+        );
+    }
+
+    #[test]
+    fn unsupported_rules_are_not_expanded() {
+        assert!(rule("FREQ=HOURLY").is_none());
+        assert!(rule("FREQ=DAILY;INTERVAL=0").is_none());
+        assert!(rule("COUNT=3").is_none());
+        assert!(rule("FREQ=WEEKLY;BYDAY=XX").is_none());
+        // The series' first occurrence still freezes.
+        assert_eq!(
+            series("FREQ=HOURLY;COUNT=5", "20260105T090000"),
+            [at("20260105T090000")]
+        );
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::{Method, Url};
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{ChatKind, ChatWebhook, Profile, SettingsStore};
+use crate::{net, quick_actions, secrets, tasks};
+
+const TASK: &str = "chat_webhooks";
+const POST_INTERVAL: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_WEBHOOKS: usize = 20;
+const MAX_NAME_LEN: usize = 80;
+const MAX_TEMPLATE_LEN: usize = 2_000;
+const MAX_PER_MINUTE: u32 = 120;
+/// Events held before posting beyond this are dropped, oldest first, so an
+/// unreachable chat service cannot grow the queue without bound.
+const MAX_QUEUED: usize = 500;
+const EVENTS: &[&str] = &[
+    "plan_approved",
+    "plan_rejected",
+    "execution_failed",
+    "execution_completed",
+];
+const PLACEHOLDERS: &[&str] = &[
+    "event",
+    "profile",
+    "plan_id",
+    "objective",
+    "status",
+    "reason",
+    "risk",
+    "error",
+];
+const FAILED: &[&str] = &["failed", "error", "errored"];
+const COMPLETED: &[&str] = &["completed", "succeeded", "executed", "done"];
+
+struct Queued {
+    /// Set for events the sync saw; decisions only know their core URL.
+    profile_id: Option<String>,
+    base_url: Option<String>,
+    event: &'static str,
+    plan_id: String,
+    /// `Null` for decisions, whose plan is fetched when posting.
+    plan: Value,
+    reason: Option<String>,
+}
+
+#[derive(Default)]
+struct Window {
+    started: Option<Instant>,
+    sent: u32,
+    suppressed: u32,
+    posted: u64,
+    last_error: Option<String>,
+}
+
+static QUEUE: OnceLock<Mutex<Vec<Queued>>> = OnceLock::new();
+/// Per-webhook rate windows and delivery state, by webhook id.
+static WINDOWS: OnceLock<Mutex<HashMap<String, Window>>> = OnceLock::new();
+
+fn queue() -> &'static Mutex<Vec<Queued>> {
+    QUEUE.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn windows() -> &'static Mutex<HashMap<String, Window>> {
+    WINDOWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn url_secret_key(webhook_id: &str) -> String {
+    format!("chat_webhook:{}:url", webhook_id)
+}
+
+pub fn validate(webhooks: &[ChatWebhook], profiles: &[Profile]) -> Result<(), String> {
+    if webhooks.len() > MAX_WEBHOOKS {
+        return Err(format!("At most {} chat webhooks", MAX_WEBHOOKS));
+    }
+    let mut ids = std::collections::HashSet::new();
+    for webhook in webhooks {
+        let id = webhook.id.trim();
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            || !ids.insert(id)
+        {
+            return Err("Chat webhook ids must be unique letters, digits, - or _".to_string());
+        }
+        if webhook.name.len() > MAX_NAME_LEN {
+            return Err(format!(
+                "Chat webhook {}: name must be at most {} bytes",
+                id, MAX_NAME_LEN
+            ));
+        }
+        if let Some(event) = webhook
+            .events
+            .iter()
+            .find(|e| !EVENTS.contains(&e.as_str()))
+        {
+            return Err(format!("Chat webhook {}: unknown event {}", id, event));
+        }
+        if let Some(profile) = webhook
+            .profiles
+            .iter()
+            .find(|p| !profiles.iter().any(|known| &known.id == *p))
+        {
+            return Err(format!("Chat webhook {}: unknown profile {}", id, profile));
+        }
+        if webhook.max_per_minute == 0 || webhook.max_per_minute > MAX_PER_MINUTE {
+            return Err(format!(
+                "Chat webhook {}: max_per_minute must be 1 to {}",
+                id, MAX_PER_MINUTE
+            ));
+        }
+        for (event, template) in &webhook.templates {
+            if !EVENTS.contains(&event.as_str()) {
+                return Err(format!("Chat webhook {}: unknown event {}", id, event));
+            }
+            if template.trim().is_empty() || template.len() > MAX_TEMPLATE_LEN {
+                return Err(format!(
+                    "Chat webhook {}: template for {} must be 1 to {} bytes",
+                    id, event, MAX_TEMPLATE_LEN
+                ));
+            }
+            let mut names = BTreeSet::new();
+            quick_actions::scan(template, &mut names)
+                .map_err(|e| format!("Chat webhook {}: {}", id, e))?;
+            if let Some(name) = names.iter().find(|n| !PLACEHOLDERS.contains(&n.as_str())) {
+                return Err(format!(
+                    "Chat webhook {}: unknown placeholder {{{}}}",
+                    id, name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn push(event: Queued) {
+    if let Ok(mut queue) = queue().lock() {
+        if queue.len() >= MAX_QUEUED {
+            queue.remove(0);
+        }
+        queue.push(event);
+    }
+}
+
+/// Queues a decision the core accepted, whichever path sent it.
+pub fn decided(base_url: &str, plan_id: &str, decision: &str, payload: Option<&Value>) {
+    push(Queued {
+        profile_id: None,
+        base_url: Some(base_url.to_string()),
+        event: if decision == "approve" {
+            "plan_approved"
+        } else {
+            "plan_rejected"
+        },
+        plan_id: plan_id.to_string(),
+        plan: Value::Null,
+        reason: payload
+            .and_then(|p| p["reason"].as_str())
+            .map(str::to_string),
+    });
+}
+
+/// Queues the event for a plan the sync saw change status, if it is one
+/// the webhooks report.
+pub fn status_changed(profile_id: &str, previous: &str, plan: &Value) {
+    let status = plan["status"]
+        .as_str()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let event = if FAILED.contains(&status.as_str()) {
+        "execution_failed"
+    } else if COMPLETED.contains(&status.as_str()) {
+        "execution_completed"
+    } else {
+        return;
+    };
+    if previous.eq_ignore_ascii_case(&status) {
+        return;
+    }
+    let Some(plan_id) = plan["id"].as_str() else {
+        return;
+    };
+    push(Queued {
+        profile_id: Some(profile_id.to_string()),
+        base_url: None,
+        event,
+        plan_id: plan_id.to_string(),
+        plan: plan.clone(),
+        reason: None,
+    });
+}
+
+fn default_template(event: &str) -> &'static str {
+    match event {
+        "plan_approved" => "Plan {plan_id} approved on {profile}: {objective}",
+        "plan_rejected" => "Plan {plan_id} rejected on {profile}: {objective} {reason}",
+        "execution_failed" => "Plan {plan_id} failed on {profile}: {objective} {error}",
+        _ => "Plan {plan_id} finished on {profile}: {objective}",
+    }
+}
+
+/// The error the plan failed with: its own, or the first failed step's.
+fn failure(plan: &Value) -> String {
+    plan["error"]
+        .as_str()
+        .or_else(|| {
+            plan["execution_results"]
+                .as_array()?
+                .iter()
+                .filter(|r| {
+                    r["status"]
+                        .as_str()
+                        .is_some_and(|s| FAILED.contains(&s.to_ascii_lowercase().as_str()))
+                })
+                .find_map(|r| r["error"].as_str().or_else(|| r["output"].as_str()))
+        })
+        .unwrap_or_default()
+        .chars()
+        .take(300)
+        .collect()
+}
+
+fn params_for(event: &Queued, profile: &Profile, plan: &Value) -> Map<String, Value> {
+    let mut params = Map::new();
+    params.insert("event".into(), json!(event.event));
+    params.insert(
+        "profile".into(),
+        json!(if profile.name.is_empty() {
+            &profile.id
+        } else {
+            &profile.name
+        }),
+    );
+    params.insert("plan_id".into(), json!(event.plan_id));
+    params.insert(
+        "objective".into(),
+        json!(plan["objective"].as_str().unwrap_or_default()),
+    );
+    params.insert(
+        "status".into(),
+        json!(plan["status"].as_str().unwrap_or_default()),
+    );
+    params.insert(
+        "reason".into(),
+        json!(event.reason.as_deref().unwrap_or_default()),
+    );
+    params.insert(
+        "risk".into(),
+        json!(["risk", "risk_level"]
+            .iter()
+            .find_map(|k| plan[*k].as_str())
+            .unwrap_or_default()),
+    );
+    params.insert("error".into(), json!(failure(plan)));
+    params
+}
+
+fn body_for(kind: ChatKind, text: &str) -> Value {
+    match kind {
+        ChatKind::Slack => json!({ "text": text }),
+        ChatKind::Teams => json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": text.lines().next().unwrap_or_default(),
+            "text": text,
+        }),
+    }
+}
+
+async fn post(webhook: &ChatWebhook, text: &str) -> Result<(), String> {
+    let raw = secrets::get(&url_secret_key(&webhook.id))?.ok_or_else(|| {
+        ErrorCode::InvalidInput.with(format!("No URL is stored for chat webhook {}", webhook.id))
+    })?;
+    let url = Url::parse(&raw)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid webhook URL: {}", e)))?;
+    let response = net::client_for(&url)?
+        .request(Method::POST, url)
+        .timeout(REQUEST_TIMEOUT)
+        .json(&body_for(webhook.kind, text))
+        .send()
+        .await
+        .map_err(|e| {
+            let code = if e.is_timeout() {
+                ErrorCode::CoreTimeout
+            } else {
+                ErrorCode::CoreUnreachable
+            };
+            // The error would otherwise repeat the URL, and with it the
+            // webhook's credential.
+            code.with(format!("Webhook request failed: {}", e.without_url()))
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "Webhook {}: {}",
+            status.as_u16(),
+            body.chars().take(200).collect::<String>()
+        )));
+    }
+    Ok(())
+}
+
+/// Counts a post against the webhook's minute, or the post it suppressed.
+/// Returns how many earlier posts were suppressed when this one may go.
+fn admit(webhook: &ChatWebhook) -> Option<u32> {
+    let mut windows = windows().lock().ok()?;
+    let window = windows.entry(webhook.id.clone()).or_default();
+    if window
+        .started
+        .is_none_or(|s| s.elapsed() >= Duration::from_secs(60))
+    {
+        window.started = Some(Instant::now());
+        window.sent = 0;
+    }
+    if window.sent >= webhook.max_per_minute {
+        window.suppressed += 1;
+        return None;
+    }
+    window.sent += 1;
+    Some(std::mem::take(&mut window.suppressed))
+}
+
+fn record(webhook_id: &str, outcome: Result<(), String>) {
+    if let Ok(mut windows) = windows().lock() {
+        let window = windows.entry(webhook_id.to_string()).or_default();
+        match outcome {
+            Ok(()) => {
+                window.posted += 1;
+                window.last_error = None;
+            }
+            Err(err) => window.last_error = Some(err),
+        }
+    }
+}
+
+/// Posts queued events every `POST_INTERVAL`.
+pub fn spawn_poster(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            post_queued(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(POST_INTERVAL).await;
+        }
+    });
+}
+
+async fn post_queued(app: &AppHandle) {
+    let events: Vec<Queued> = match queue().lock() {
+        Ok(mut queue) => queue.drain(..).collect(),
+        Err(_) => return,
+    };
+    if events.is_empty() {
+        return;
+    }
+    let store = app.state::<SettingsStore>();
+    let settings = store.snapshot();
+    for event in events {
+        let profile = match (&event.profile_id, &event.base_url) {
+            (Some(id), _) => settings.profile(id),
+            (None, Some(base_url)) => {
+                let origin = net::base_origin(base_url);
+                settings.profiles.iter().find(|p| {
+                    origin.is_some() && p.endpoints().any(|e| net::base_origin(e) == origin)
+                })
+            }
+            (None, None) => None,
+        };
+        let Some(profile) = profile else {
+            continue;
+        };
+        let targets: Vec<&ChatWebhook> = settings
+            .chat_webhooks
+            .iter()
+            .filter(|w| w.enabled)
+            .filter(|w| w.events.is_empty() || w.events.iter().any(|e| e == event.event))
+            .filter(|w| w.profiles.is_empty() || w.profiles.contains(&profile.id))
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+        let plan = if event.plan.is_null() {
+            match store.connection(Some(&profile.id)) {
+                Ok(conn) => crate::request_json(
+                    Method::GET,
+                    &conn.base_url,
+                    &format!("/plans/{}", event.plan_id),
+                    conn.token,
+                    None,
+                )
+                .await
+                .unwrap_or(Value::Null),
+                Err(_) => Value::Null,
+            }
+        } else {
+            event.plan.clone()
+        };
+        let params = params_for(&event, profile, &plan);
+        for webhook in targets {
+            let Some(suppressed) = admit(webhook) else {
+                continue;
+            };
+            let template = webhook
+                .templates
+                .get(event.event)
+                .map(String::as_str)
+                .unwrap_or_else(|| default_template(event.event));
+            let mut text = quick_actions::fill(template, &params, false)
+                .trim()
+                .to_string();
+            if suppressed > 0 {
+                text.push_str(&format!(
+                    "\n({} earlier event{} not posted: rate limit)",
+                    suppressed,
+                    if suppressed == 1 { "" } else { "s" }
+                ));
+            }
+            let outcome = post(webhook, &text).await;
+            if let Err(err) = &outcome {
+                tasks::failed(TASK, err);
+            }
+            record(&webhook.id, outcome);
+        }
+    }
+}
+
+/// Stores (or with `None`, clears) a chat webhook's URL in the keyring.
+#[tauri::command]
+pub fn set_chat_webhook_url(
+    store: State<'_, SettingsStore>,
+    webhook_id: String,
+    url: Option<String>,
+) -> Result<Value, String> {
+    if !store
+        .snapshot()
+        .chat_webhooks
+        .iter()
+        .any(|w| w.id == webhook_id)
+    {
+        return Err(ErrorCode::NotFound.with(format!("Unknown chat webhook: {}", webhook_id)));
+    }
+    let key = url_secret_key(&webhook_id);
+    let url = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+    match url.as_deref() {
+        Some(raw) => {
+            let parsed = Url::parse(raw)
+                .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid webhook URL: {}", e)))?;
+            if parsed.scheme() != "https" {
+                return Err(ErrorCode::InvalidInput.with("Webhook URL must be https"));
+            }
+            secrets::set(&key, raw)?
+        }
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "webhook_id": webhook_id, "stored": url.is_some() }))
+}
+
+/// Each webhook's delivery state this session: posts sent, posts held back
+/// by the rate limit and the last error. URLs are never returned.
+#[tauri::command]
+pub fn get_chat_webhook_status(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let windows = windows()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Chat webhook state poisoned"))?;
+    let listed: Vec<Value> = settings
+        .chat_webhooks
+        .iter()
+        .map(|webhook| {
+            let window = windows.get(&webhook.id);
+            json!({
+                "id": webhook.id,
+                "name": webhook.name,
+                "kind": webhook.kind,
+                "enabled": webhook.enabled,
+                "url_stored": secrets::get(&url_secret_key(&webhook.id)).ok().flatten().is_some(),
+                "posted": window.map_or(0, |w| w.posted),
+                "suppressed": window.map_or(0, |w| w.suppressed),
+                "last_error": window.and_then(|w| w.last_error.clone()),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "webhooks": listed,
+        "queued": queue().lock().map(|q| q.len()).unwrap_or(0),
+    }))
+}
+
+/// Posts a test message to one webhook now, outside the rate limit.
+#[tauri::command]
+pub async fn test_chat_webhook(
+    store: State<'_, SettingsStore>,
+    webhook_id: String,
+) -> Result<Value, String> {
+    let webhook = store
+        .snapshot()
+        .chat_webhooks
+        .into_iter()
+        .find(|w| w.id == webhook_id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown chat webhook: {}", webhook_id)))?;
+    let outcome = post(
+        &webhook,
+        "NovaAdapt test message: this channel receives plan events.",
+    )
+    .await;
+    record(&webhook.id, outcome.clone());
+    outcome?;
+    Ok(json!({ "webhook_id": webhook_id, "posted": true }))
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::features::{self, FeatureCache};
+use crate::settings::SettingsStore;
+use crate::{environment, net, stall, tasks};
+
+const TASK: &str = "claims";
+const EVENT: &str = "claim:changed";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_IDLE_MINUTES: u32 = 15;
+const MAX_IDLE_MINUTES: u32 = 4 * 60;
+/// The core feature flag of cores that hold review claims.
+const FEATURE: &str = "plan_claims";
+
+#[derive(Clone)]
+struct Claim {
+    profile_id: String,
+    /// `core` when the core holds the claim for every operator to see,
+    /// `local` when only this shell knows of it.
+    mode: &'static str,
+    claimed_at: String,
+    active: Instant,
+    idle: Duration,
+}
+
+impl Claim {
+    fn describe(&self) -> Value {
+        json!({
+            "mine": true,
+            "mode": self.mode,
+            "claimed_at": self.claimed_at,
+            "release_in_seconds": self.idle.saturating_sub(self.active.elapsed()).as_secs(),
+        })
+    }
+}
+
+/// This shell's claims, keyed by `origin plan_id` so dashboards fetched
+/// by base URL can show them too.
+static CLAIMS: OnceLock<Mutex<HashMap<String, Claim>>> = OnceLock::new();
+
+fn claims() -> &'static Mutex<HashMap<String, Claim>> {
+    CLAIMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn key(base_url: &str, plan_id: &str) -> String {
+    format!(
+        "{} {}",
+        net::base_origin(base_url).unwrap_or_default(),
+        plan_id
+    )
+}
+
+/// Marks the plans this shell has claimed in dashboard data, as `claim`
+/// on each plan. Claims the core reports for other operators are left as
+/// the core sent them.
+pub fn annotate(base_url: &str, data: &mut Value) {
+    let Ok(held) = claims().lock() else {
+        return;
+    };
+    if held.is_empty() {
+        return;
+    }
+    let Some(plans) = data["plans"].as_array_mut() else {
+        return;
+    };
+    for plan in plans {
+        let Some(id) = plan["id"].as_str() else {
+            continue;
+        };
+        if let Some(claim) = held.get(&key(base_url, id)) {
+            plan["claim"] = claim.describe();
+        }
+    }
+}
+
+/// Drops the claim on a plan once this shell has decided it; the review it
+/// covered is over.
+pub fn decided(base_url: &str, plan_id: &str) {
+    if let Ok(mut held) = claims().lock() {
+        held.remove(&key(base_url, plan_id));
+    }
+}
+
+/// Takes ownership of reviewing `plan_id`. The core holds the claim where
+/// it supports claims, so other operators see it and a claim by someone
+/// else is refused with `PLAN_CONFLICT`; otherwise it is kept here only.
+/// Claiming again renews it. A claim not renewed for `idle_minutes`
+/// (default 15) is released.
+#[tauri::command]
+pub async fn claim_plan(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    features: State<'_, FeatureCache>,
+    profile: Option<String>,
+    plan_id: String,
+    idle_minutes: Option<u32>,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let idle_minutes = idle_minutes
+        .unwrap_or(DEFAULT_IDLE_MINUTES)
+        .clamp(1, MAX_IDLE_MINUTES);
+    let idle = Duration::from_secs(u64::from(idle_minutes) * 60);
+    let conn = store.connection(profile.as_deref())?;
+    let mode = if features::core_flag(&store, &features, &conn, FEATURE).await == Some(false) {
+        "local"
+    } else {
+        match crate::request_json(
+            Method::POST,
+            &conn.base_url,
+            &format!("/plans/{}/claim", plan_id),
+            conn.token.clone(),
+            Some(json!({ "ttl_seconds": idle.as_secs(), "client": "desktop" })),
+        )
+        .await
+        {
+            Ok(_) => "core",
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => "local",
+            Err(err) => return Err(err),
+        }
+    };
+    let claim = {
+        let mut held = claims()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Claim state poisoned"))?;
+        let claimed_at = held
+            .get(&key(&conn.base_url, &plan_id))
+            .map(|c| c.claimed_at.clone())
+            .unwrap_or_else(|| Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+        let claim = Claim {
+            profile_id: conn.profile_id.clone(),
+            mode,
+            claimed_at,
+            active: Instant::now(),
+            idle,
+        };
+        held.insert(key(&conn.base_url, &plan_id), claim.clone());
+        claim
+    };
+    let mut described = claim.describe();
+    described["profile_id"] = json!(conn.profile_id);
+    described["plan_id"] = json!(plan_id);
+    let _ = environment::emit(
+        &app,
+        EVENT,
+        json!({ "action": "claimed", "claim": described }),
+    );
+    Ok(described)
+}
+
+/// Gives up this shell's claim on `plan_id`.
+#[tauri::command]
+pub async fn release_plan(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let removed = claims()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Claim state poisoned"))?
+        .remove(&key(&conn.base_url, &plan_id));
+    if let Some(claim) = &removed {
+        release_on_core(claim, &conn.base_url, conn.token.clone(), &plan_id).await?;
+        emit_released(&app, &conn.profile_id, &plan_id, "released");
+    }
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "released": removed.is_some(),
+    }))
+}
+
+async fn release_on_core(
+    claim: &Claim,
+    base_url: &str,
+    token: Option<String>,
+    plan_id: &str,
+) -> Result<(), String> {
+    if claim.mode != "core" {
+        return Ok(());
+    }
+    match crate::request_json(
+        Method::DELETE,
+        base_url,
+        &format!("/plans/{}/claim", plan_id),
+        token,
+        None,
+    )
+    .await
+    {
+        // Gone already: expired on the core or decided.
+        Err(err) if errors::code_of(&err) != Some(ErrorCode::NotFound) => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn emit_released(app: &AppHandle, profile_id: &str, plan_id: &str, reason: &str) {
+    let _ = environment::emit(
+        app,
+        EVENT,
+        json!({
+            "action": reason,
+            "claim": { "profile_id": profile_id, "plan_id": plan_id },
+        }),
+    );
+}
+
+/// Releases claims left idle past their timeout.
+pub fn spawn_expiry(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let expired: Vec<(String, Claim)> = match claims().lock() {
+                Ok(mut held) => {
+                    let keys: Vec<String> = held
+                        .iter()
+                        .filter(|(_, c)| c.active.elapsed() >= c.idle)
+                        .map(|(k, _)| k.clone())
+                        .collect();
+                    keys.into_iter()
+                        .filter_map(|k| held.remove(&k).map(|c| (k, c)))
+                        .collect()
+                }
+                Err(_) => Vec::new(),
+            };
+            for (key, claim) in expired {
+                let Some((_, plan_id)) = key.split_once(' ') else {
+                    continue;
+                };
+                if let Ok(conn) = app
+                    .state::<SettingsStore>()
+                    .connection(Some(&claim.profile_id))
+                {
+                    if let Err(err) =
+                        release_on_core(&claim, &conn.base_url, conn.token, plan_id).await
+                    {
+                        tasks::failed(TASK, &err);
+                    }
+                }
+                emit_released(&app, &claim.profile_id, plan_id, "idle_released");
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
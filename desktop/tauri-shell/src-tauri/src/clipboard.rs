@@ -0,0 +1,205 @@
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::attachments;
+use crate::environment;
+use crate::errors::ErrorCode;
+use crate::settings::{ClipboardGuardAction, ClipboardSettings, SettingsStore};
+
+const CLEARED_EVENT: &str = "clipboard:cleared";
+const MAX_CLEAR_AFTER_SECS: u64 = 3600;
+const MAX_COPY_BYTES: usize = 1024 * 1024;
+const REDACTED: &str = "[redacted]";
+
+/// Bumped on every copy, so a pending clear only fires for the latest one.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn validate(settings: &ClipboardSettings) -> Result<(), String> {
+    if settings.clear_after_seconds > MAX_CLEAR_AFTER_SECS {
+        return Err(format!(
+            "clipboard.clear_after_seconds must be at most {}",
+            MAX_CLEAR_AFTER_SECS
+        ));
+    }
+    Ok(())
+}
+
+/// `text` with every secret-looking match replaced, and the rules that hit.
+fn strip_secrets(text: &str) -> (String, Vec<&'static str>) {
+    let mut out = text.to_string();
+    let mut hits = Vec::new();
+    for (rule, regex) in attachments::secret_rules() {
+        if regex.is_match(&out) {
+            hits.push(*rule);
+            out = regex.replace_all(&out, REDACTED).into_owned();
+        }
+    }
+    (out, hits)
+}
+
+#[cfg(target_os = "macos")]
+fn writer() -> Command {
+    Command::new("pbcopy")
+}
+
+#[cfg(target_os = "windows")]
+fn writer() -> Command {
+    let mut cmd = Command::new("powershell");
+    cmd.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "[Console]::InputEncoding = [Text.Encoding]::UTF8; $text = [Console]::In.ReadToEnd(); \
+         if ($text) { Set-Clipboard -Value $text } \
+         else { Add-Type -AssemblyName System.Windows.Forms; [Windows.Forms.Clipboard]::Clear() }",
+    ]);
+    cmd
+}
+
+/// `wl-copy` on Wayland, else `xclip` or `xsel` on X11.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn writer() -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "if [ -n \"$WAYLAND_DISPLAY\" ] && command -v wl-copy >/dev/null; then exec wl-copy; \
+         elif command -v xclip >/dev/null; then exec xclip -selection clipboard; \
+         else exec xsel --clipboard --input; fi",
+    ]);
+    cmd
+}
+
+#[cfg(target_os = "macos")]
+fn reader() -> Command {
+    Command::new("pbpaste")
+}
+
+#[cfg(target_os = "windows")]
+fn reader() -> Command {
+    let mut cmd = Command::new("powershell");
+    cmd.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Clipboard -Raw",
+    ]);
+    cmd
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn reader() -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "if [ -n \"$WAYLAND_DISPLAY\" ] && command -v wl-paste >/dev/null; then exec wl-paste -n; \
+         elif command -v xclip >/dev/null; then exec xclip -selection clipboard -o; \
+         else exec xsel --clipboard --output; fi",
+    ]);
+    cmd
+}
+
+async fn write(text: &str) -> Result<(), String> {
+    let fail =
+        |e: std::io::Error| ErrorCode::LocalIo.with(format!("Clipboard write failed: {}", e));
+    let mut child = writer()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(fail)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await.map_err(fail)?;
+    }
+    let status = child.wait().await.map_err(fail)?;
+    if !status.success() {
+        return Err(ErrorCode::LocalIo.with(format!("Clipboard write failed: {}", status)));
+    }
+    Ok(())
+}
+
+async fn read() -> Option<String> {
+    let out = reader().stderr(Stdio::null()).output().await.ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Clears the clipboard after `delay`, unless it was copied to again since
+/// or no longer holds `text` (the user copied something elsewhere). A
+/// clipboard that cannot be read is cleared anyway.
+fn schedule_clear(app: AppHandle, text: String, delay: Duration, generation: u64) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let current = read().await;
+        if current.is_some_and(|c| {
+            c.trim_end_matches(['\r', '\n']) != text.trim_end_matches(['\r', '\n'])
+        }) {
+            return;
+        }
+        let cleared = write("").await;
+        let _ = environment::emit(
+            &app,
+            CLEARED_EVENT,
+            json!({ "cleared": cleared.is_ok(), "error": cleared.err() }),
+        );
+    });
+}
+
+/// Copies `text` to the system clipboard. With the clipboard guard on,
+/// text that looks like it holds a secret (a token, a private key, a
+/// password assignment) is copied with the secret replaced, or refused,
+/// per `clipboard.action`; the rules that matched come back under
+/// `redacted`. The guard also clears the clipboard
+/// `clipboard.clear_after_seconds` later.
+#[tauri::command]
+pub async fn copy_to_clipboard(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    text: String,
+) -> Result<Value, String> {
+    if text.len() > MAX_COPY_BYTES {
+        return Err(ErrorCode::InvalidInput.with("Text is too large to copy"));
+    }
+    let settings = store.snapshot().clipboard;
+    let (text, redacted) = if settings.guard {
+        strip_secrets(&text)
+    } else {
+        (text, Vec::new())
+    };
+    if !redacted.is_empty() && settings.action == ClipboardGuardAction::Refuse {
+        return Err(ErrorCode::ForbiddenLocal.with(format!(
+            "Not copied: the text looks like it contains a secret ({})",
+            redacted.join(", ")
+        )));
+    }
+    write(&text).await?;
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let clears_in = if settings.guard {
+        settings.clear_after_seconds
+    } else {
+        0
+    };
+    if clears_in > 0 {
+        schedule_clear(
+            app,
+            text.clone(),
+            Duration::from_secs(clears_in),
+            generation,
+        );
+    }
+    Ok(json!({
+        "copied": true,
+        "bytes": text.len(),
+        "redacted": redacted,
+        "clears_in_seconds": (clears_in > 0).then_some(clears_in),
+    }))
+}
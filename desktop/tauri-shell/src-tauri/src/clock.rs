@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, DATE};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+use crate::{environment, net, notify, tasks};
+
+const SKEW_EVENT: &str = "clock:skew";
+const CLEARED_EVENT: &str = "clock:skew_cleared";
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Past this, bearer-token `exp`/`nbf` checks and signed-request windows on
+/// the core start rejecting the shell's requests.
+const WARN_THRESHOLD_MS: i64 = 30_000;
+
+#[derive(Clone)]
+struct Sample {
+    base_url: String,
+    /// Core clock minus local clock.
+    offset_ms: i64,
+    /// Half the round trip, plus the `Date` header's one-second resolution.
+    uncertainty_ms: i64,
+    source: &'static str,
+    measured_at: DateTime<Utc>,
+}
+
+impl Sample {
+    /// Only skew the measurement cannot explain counts.
+    fn skewed(&self) -> bool {
+        self.offset_ms.abs() - self.uncertainty_ms > WARN_THRESHOLD_MS
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "base_url": self.base_url,
+            "offset_ms": self.offset_ms,
+            "uncertainty_ms": self.uncertainty_ms,
+            "source": self.source,
+            "measured_at": self.measured_at.to_rfc3339(),
+            "threshold_ms": WARN_THRESHOLD_MS,
+            "skewed": self.skewed(),
+        })
+    }
+}
+
+/// Latest measurement per core origin.
+static SAMPLES: OnceLock<Mutex<HashMap<String, Sample>>> = OnceLock::new();
+
+fn samples() -> &'static Mutex<HashMap<String, Sample>> {
+    SAMPLES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn store_sample(base_url: &str, sample: Sample) {
+    if let (Some(origin), Ok(mut samples)) = (net::base_origin(base_url), samples().lock()) {
+        // A precise reading from the time endpoint is not overwritten by the
+        // next coarse `Date` header.
+        let keep = samples.get(&origin).is_some_and(|old| {
+            old.uncertainty_ms < sample.uncertainty_ms
+                && (sample.measured_at - old.measured_at).num_seconds() < 300
+        });
+        if !keep {
+            samples.insert(origin, sample);
+        }
+    }
+}
+
+/// Takes a passive reading from the `Date` header of any core response;
+/// `sent` is when the request went out.
+pub fn observe(base_url: &str, headers: &HeaderMap, sent: DateTime<Utc>) {
+    let Some(date) = headers
+        .get(DATE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|raw| DateTime::parse_from_rfc2822(raw.trim()).ok())
+    else {
+        return;
+    };
+    let received = Utc::now();
+    let half_rtt = (received - sent).num_milliseconds().max(0) / 2;
+    // The header is truncated to the second, so its midpoint is the best guess.
+    let core_now = date.with_timezone(&Utc) + chrono::Duration::milliseconds(500);
+    store_sample(
+        base_url,
+        Sample {
+            base_url: base_url.trim().trim_end_matches('/').to_string(),
+            offset_ms: (core_now - (sent + chrono::Duration::milliseconds(half_rtt)))
+                .num_milliseconds(),
+            uncertainty_ms: half_rtt + 500,
+            source: "date_header",
+            measured_at: received,
+        },
+    );
+}
+
+/// Measures against the core's `/time` endpoint when it has one; otherwise
+/// the `/health` answer's `Date` header is recorded by `observe` in the
+/// request path.
+async fn probe(base_url: &str, token: Option<String>) -> Result<(), String> {
+    let sent = Utc::now();
+    match crate::request_json(Method::GET, base_url, "/time", token.clone(), None).await {
+        Ok(body) => {
+            let received = Utc::now();
+            if let Some(core_now) = body_time(&body) {
+                let half_rtt = (received - sent).num_milliseconds().max(0) / 2;
+                store_sample(
+                    base_url,
+                    Sample {
+                        base_url: base_url.trim().trim_end_matches('/').to_string(),
+                        offset_ms: (core_now - (sent + chrono::Duration::milliseconds(half_rtt)))
+                            .num_milliseconds(),
+                        uncertainty_ms: half_rtt,
+                        source: "time_endpoint",
+                        measured_at: received,
+                    },
+                );
+            }
+            Ok(())
+        }
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+            crate::request_json(Method::GET, base_url, "/health", token, None)
+                .await
+                .map(|_| ())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Accepts `{ "epoch_ms" }`, `{ "epoch" }` (seconds) or an RFC 3339
+/// `{ "now" | "time" }`.
+fn body_time(body: &Value) -> Option<DateTime<Utc>> {
+    if let Some(ms) = body["epoch_ms"].as_f64() {
+        return DateTime::from_timestamp_millis(ms as i64);
+    }
+    if let Some(secs) = body["epoch"].as_f64() {
+        return DateTime::from_timestamp_millis((secs * 1000.0) as i64);
+    }
+    ["now", "time"].iter().find_map(|k| {
+        DateTime::parse_from_rfc3339(body[*k].as_str()?)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    })
+}
+
+/// Probes each profile's core as it is first connected (and again when its
+/// base URL changes), then watches the readings every response refreshes:
+/// `clock:skew` is emitted when a core's offset passes the threshold and
+/// `clock:skew_cleared` once it is back within it.
+const TASK: &str = "clock";
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        let mut probed: HashMap<String, String> = HashMap::new();
+        let mut warned: HashMap<String, i64> = HashMap::new();
+        loop {
+            let profiles = app.state::<SettingsStore>().snapshot().profiles;
+            for profile in &profiles {
+                if probed.get(&profile.id) == Some(&profile.base_url) {
+                    continue;
+                }
+                let Ok(conn) = app.state::<SettingsStore>().connection(Some(&profile.id)) else {
+                    continue;
+                };
+                if probe(&conn.base_url, conn.token).await.is_ok() {
+                    probed.insert(profile.id.clone(), profile.base_url.clone());
+                }
+            }
+            tick(&app, &mut warned);
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+fn tick(app: &AppHandle, warned: &mut HashMap<String, i64>) {
+    let current: Vec<(String, Sample)> = match samples().lock() {
+        Ok(samples) => samples
+            .iter()
+            .map(|(origin, s)| (origin.clone(), s.clone()))
+            .collect(),
+        Err(_) => return,
+    };
+    for (origin, sample) in current {
+        let payload = sample.to_json();
+        if sample.skewed() {
+            // Re-announce only when the offset moved by another threshold's
+            // worth, not on every reading.
+            let moved = warned
+                .get(&origin)
+                .is_none_or(|last| (last - sample.offset_ms).abs() > WARN_THRESHOLD_MS);
+            if moved {
+                warned.insert(origin, sample.offset_ms);
+                let _ = environment::emit(app, SKEW_EVENT, &payload);
+                notify::notify_in_app(
+                    app,
+                    "clock_skew",
+                    "Clock skew detected",
+                    &format!(
+                        "{} is {:.1}s {} this machine; token checks and signed requests may fail",
+                        sample.base_url,
+                        sample.offset_ms.abs() as f64 / 1000.0,
+                        if sample.offset_ms > 0 {
+                            "ahead of"
+                        } else {
+                            "behind"
+                        }
+                    ),
+                    payload,
+                );
+            }
+        } else if warned.remove(&origin).is_some() {
+            let _ = environment::emit(app, CLEARED_EVENT, &payload);
+        }
+    }
+}
+
+/// Measures the profile's core clock now and returns the offset.
+#[tauri::command]
+pub async fn get_clock_skew(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    probe(&conn.base_url, conn.token.clone()).await?;
+    let sample = net::base_origin(&conn.base_url)
+        .and_then(|origin| samples().lock().ok()?.get(&origin).cloned());
+    Ok(match sample {
+        Some(sample) => {
+            let mut out = sample.to_json();
+            out["profile_id"] = json!(conn.profile_id);
+            out
+        }
+        None => json!({
+            "profile_id": conn.profile_id,
+            "base_url": conn.base_url,
+            "offset_ms": Value::Null,
+            "source": Value::Null,
+        }),
+    })
+}
@@ -0,0 +1,453 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::cloud_auth;
+use crate::core_signing;
+use crate::environment;
+use crate::errors::{self, ErrorCode};
+use crate::managed;
+use crate::notify;
+use crate::plugins::{self, Manifest};
+use crate::secrets;
+use crate::settings::{
+    CoreAuth, CoreSigningMode, Profile, ProxyAuth, Settings, SettingsStore, TunnelAuth, VaultAuth,
+};
+use crate::ticketing;
+use crate::vault;
+
+const PROBLEMS_EVENT: &str = "config:problems";
+/// How long each endpoint gets to answer `/health`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One thing wrong with the configuration, and what to do about it.
+struct Problem {
+    profile_id: Option<String>,
+    field: &'static str,
+    severity: &'static str,
+    message: String,
+    fix: String,
+}
+
+impl Problem {
+    fn to_json(&self) -> Value {
+        json!({
+            "profile_id": self.profile_id,
+            "field": self.field,
+            "severity": self.severity,
+            "message": self.message,
+            "fix": self.fix,
+        })
+    }
+}
+
+struct Report {
+    problems: Vec<Problem>,
+}
+
+impl Report {
+    fn add(
+        &mut self,
+        profile: Option<&Profile>,
+        field: &'static str,
+        severity: &'static str,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) {
+        self.problems.push(Problem {
+            profile_id: profile.map(|p| p.id.clone()),
+            field,
+            severity,
+            message: message.into(),
+            fix: fix.into(),
+        });
+    }
+
+    fn error(&mut self, profile: &Profile, field: &'static str, message: String, fix: &str) {
+        self.add(Some(profile), field, "error", message, fix);
+    }
+
+    fn warning(&mut self, profile: &Profile, field: &'static str, message: String, fix: &str) {
+        self.add(Some(profile), field, "warning", message, fix);
+    }
+
+    fn to_json(&self) -> Value {
+        let count = |severity: &str| {
+            self.problems
+                .iter()
+                .filter(|p| p.severity == severity)
+                .count()
+        };
+        json!({
+            "ok": self.problems.is_empty(),
+            "errors": count("error"),
+            "warnings": count("warning"),
+            "problems": self.problems.iter().map(Problem::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Whether the keyring holds `key`; a keyring that cannot be read is
+/// reported once per profile instead.
+fn stored(report: &mut Report, profile: &Profile, key: &str) -> Option<bool> {
+    match secrets::get(key) {
+        Ok(value) => Some(value.is_some_and(|v| !v.trim().is_empty())),
+        Err(err) => {
+            if !report
+                .problems
+                .iter()
+                .any(|p| p.field == "keyring" && p.profile_id.as_deref() == Some(&profile.id))
+            {
+                report.warning(
+                    profile,
+                    "keyring",
+                    err,
+                    "Unlock the OS keyring (or install a Secret Service provider) so saved credentials can be read",
+                );
+            }
+            None
+        }
+    }
+}
+
+async fn probe(report: &mut Report, profile: &Profile, field: &'static str, url: &str) {
+    let request = crate::request_json(Method::GET, url, "/health", None, None);
+    let result =
+        match tokio::time::timeout(PROBE_TIMEOUT, request).await {
+            Ok(result) => result,
+            Err(_) => Err(ErrorCode::CoreTimeout
+                .with(format!("No answer within {}s", PROBE_TIMEOUT.as_secs()))),
+        };
+    let Err(err) = result else {
+        return;
+    };
+    match errors::code_of(&err) {
+        Some(ErrorCode::CoreUnreachable | ErrorCode::CoreTimeout) => report.error(
+            profile,
+            field,
+            format!("{} cannot be reached: {}", url, err),
+            "Check the URL, and the proxy or SSH tunnel if the core is only reachable through one",
+        ),
+        Some(ErrorCode::CoreMaintenance) => report.warning(
+            profile,
+            field,
+            format!("{} is in maintenance", url),
+            "Nothing to change; polling resumes when maintenance ends",
+        ),
+        _ => report.warning(
+            profile,
+            field,
+            format!("{} answered /health with an error: {}", url, err),
+            "Check that the URL points at the core's API root, not a page in front of it",
+        ),
+    }
+}
+
+fn check_auth(report: &mut Report, profile: &Profile) {
+    let auth = &profile.core_auth;
+    if profile.vault.enabled && auth.mode != CoreAuth::Bearer {
+        report.warning(
+            profile,
+            "core_auth.mode",
+            "Vault supplies a bearer token, but the profile does not send one".to_string(),
+            "Set the auth mode to bearer, or turn Vault off for this profile",
+        );
+    }
+    let device_login = !profile.device_login.issuer.trim().is_empty()
+        || !profile.device_login.token_endpoint.trim().is_empty();
+    if device_login && auth.mode != CoreAuth::Bearer {
+        report.warning(
+            profile,
+            "core_auth.mode",
+            "Device login signs in for a bearer token, but the profile does not send one"
+                .to_string(),
+            "Set the auth mode to bearer, or clear the device login settings",
+        );
+    }
+    let secret_expected = auth.mode != CoreAuth::Bearer || !(profile.vault.enabled || device_login);
+    if let Some(key) = auth
+        .mode
+        .secret_key(&profile.id)
+        .filter(|_| secret_expected)
+    {
+        if stored(report, profile, &key) == Some(false) {
+            let (what, fix) = match auth.mode {
+                CoreAuth::ApiKey => ("API key", "Save the key with set_profile_credential"),
+                CoreAuth::Basic => (
+                    "Basic auth password",
+                    "Save the password with set_profile_credential",
+                ),
+                _ => ("token", "Sign in, or save a token with set_profile_token"),
+            };
+            report.error(
+                profile,
+                "core_auth",
+                format!("No {} is saved for this profile", what),
+                fix,
+            );
+        }
+    }
+    if auth.mode == CoreAuth::AwsSigv4 {
+        let shared = std::env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+            .map(|path| (PathBuf::from(path), true))
+            .or_else(|| {
+                cloud_auth::home_dir().map(|h| (h.join(".aws").join("credentials"), false))
+            });
+        let from_env = std::env::var_os("AWS_ACCESS_KEY_ID").is_some();
+        match shared {
+            Some((path, explicit)) if explicit && !path.is_file() => report.error(
+                profile,
+                "core_auth",
+                format!(
+                    "AWS_SHARED_CREDENTIALS_FILE names {}, which does not exist",
+                    path.display()
+                ),
+                "Point AWS_SHARED_CREDENTIALS_FILE at the credentials file, or unset it",
+            ),
+            Some((path, _)) if !from_env && !path.is_file() => report.warning(
+                profile,
+                "core_auth",
+                format!(
+                    "No AWS credentials in the environment and no {}",
+                    path.display()
+                ),
+                "Run `aws configure`, or rely on container or instance credentials if this machine has them",
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn check_tunnel(report: &mut Report, profile: &Profile) {
+    let tunnel = &profile.tunnel;
+    if !tunnel.enabled {
+        return;
+    }
+    match tunnel.auth {
+        TunnelAuth::Password => {
+            let key = secrets::profile_ssh_password_key(&profile.id);
+            if stored(report, profile, &key) == Some(false) {
+                report.error(
+                    profile,
+                    "tunnel.auth",
+                    "No SSH password is saved for the jump host".to_string(),
+                    "Save the password with set_tunnel_password",
+                );
+            }
+        }
+        TunnelAuth::Agent => {
+            if cfg!(unix) && std::env::var_os("SSH_AUTH_SOCK").is_none() {
+                report.error(
+                    profile,
+                    "tunnel.auth",
+                    "SSH agent auth is set but SSH_AUTH_SOCK is not".to_string(),
+                    "Start ssh-agent and add the key, or use password auth",
+                );
+            }
+        }
+    }
+    if tunnel.host_key_fingerprint.is_none() {
+        let known_hosts = cloud_auth::home_dir().map(|h| h.join(".ssh").join("known_hosts"));
+        if !known_hosts.as_ref().is_some_and(|p| p.is_file()) {
+            report.error(
+                profile,
+                "tunnel.host_key_fingerprint",
+                "No host key is pinned and there is no ~/.ssh/known_hosts to check it against"
+                    .to_string(),
+                "Pin the jump host's SHA256 fingerprint, or ssh to it once to record its key",
+            );
+        }
+    }
+}
+
+fn check_proxy(report: &mut Report, profile: &Profile) {
+    let proxy = &profile.proxy;
+    if proxy.url.trim().is_empty() || proxy.auth != ProxyAuth::Ntlm {
+        return;
+    }
+    let key = secrets::profile_proxy_password_key(&profile.id);
+    if stored(report, profile, &key) == Some(false) {
+        report.error(
+            profile,
+            "proxy.auth",
+            "No NTLM password is saved for the proxy".to_string(),
+            "Save the password with set_proxy_password",
+        );
+    }
+}
+
+fn check_vault(report: &mut Report, profile: &Profile) {
+    if !profile.vault.enabled || profile.vault.auth == VaultAuth::Oidc {
+        return;
+    }
+    if stored(report, profile, &vault::vault_secret_key(&profile.id)) == Some(false) {
+        let what = match profile.vault.auth {
+            VaultAuth::Approle => "AppRole secret id",
+            _ => "Vault token",
+        };
+        report.error(
+            profile,
+            "vault.auth",
+            format!("No {} is saved for this profile", what),
+            "Save it with set_vault_secret",
+        );
+    }
+}
+
+fn check_ticketing(report: &mut Report, profile: &Profile) {
+    if !profile.ticketing.enabled {
+        return;
+    }
+    if stored(
+        report,
+        profile,
+        &ticketing::ticketing_secret_key(&profile.id),
+    ) == Some(false)
+    {
+        report.error(
+            profile,
+            "ticketing",
+            "Ticketing is on but no credential is saved, so no decision can be filed".to_string(),
+            "Save the Jira API token or ServiceNow password with set_ticketing_secret",
+        );
+    }
+}
+
+fn check_core_signing(report: &mut Report, profile: &Profile) {
+    if profile.core_signing.mode != CoreSigningMode::Hmac {
+        return;
+    }
+    if stored(report, profile, &core_signing::secret_key(&profile.id)) == Some(false) {
+        report.error(
+            profile,
+            "core_signing",
+            "HMAC verification is on but no shared secret is saved, so every signed event is dropped"
+                .to_string(),
+            "Save the core's signing secret with set_core_signing_secret",
+        );
+    }
+}
+
+/// The installed plugin manifests, and the grants left for plugins that are
+/// not installed.
+fn check_plugins(report: &mut Report, app: &AppHandle, settings: &Settings) {
+    let Ok(dir) = plugins::plugins_dir(app) else {
+        return;
+    };
+    let mut installed = Vec::new();
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let parsed = fs::read(path.join(plugins::MANIFEST_FILE))
+            .map_err(|e| format!("Read failed: {}", e))
+            .and_then(|raw| serde_json::from_slice::<Manifest>(&raw).map_err(|e| e.to_string()))
+            .and_then(|manifest| plugins::check_manifest(&manifest, &name));
+        if let Err(err) = parsed {
+            report.add(
+                None,
+                "plugins",
+                "error",
+                format!(
+                    "Plugin {}: {} is invalid: {}",
+                    name,
+                    plugins::MANIFEST_FILE,
+                    err
+                ),
+                "Reinstall the plugin from the registry, or remove its directory",
+            );
+        }
+        installed.push(name);
+    }
+    for grant in &settings.plugins {
+        if grant.enabled && !installed.contains(&grant.id) {
+            report.add(
+                None,
+                "plugins",
+                "warning",
+                format!("Plugin {} is enabled but not installed", grant.id),
+                "Install it from the registry, or remove its grant",
+            );
+        }
+    }
+    let registry = &settings.plugin_registry;
+    if !registry.url.trim().is_empty() && registry.trusted_keys.is_empty() {
+        report.add(
+            None,
+            "plugin_registry.trusted_keys",
+            "warning",
+            "A plugin registry is set but no signing key is trusted, so nothing can be installed",
+            "Add the registry's public key to plugin_registry.trusted_keys",
+        );
+    }
+}
+
+async fn run(app: &AppHandle) -> Report {
+    let settings = app.state::<SettingsStore>().snapshot();
+    let mut report = Report {
+        problems: Vec::new(),
+    };
+    for profile in &settings.profiles {
+        check_auth(&mut report, profile);
+        check_tunnel(&mut report, profile);
+        check_proxy(&mut report, profile);
+        check_vault(&mut report, profile);
+        check_ticketing(&mut report, profile);
+        check_core_signing(&mut report, profile);
+        probe(&mut report, profile, "base_url", &profile.base_url).await;
+        for url in &profile.replicas.urls {
+            probe(&mut report, profile, "replicas.urls", url).await;
+        }
+        if let Some(url) = &profile.read_routing.read_url {
+            probe(&mut report, profile, "read_routing.read_url", url).await;
+        }
+    }
+    check_plugins(&mut report, app, &settings);
+    if let Some((source, err)) = managed::load_error() {
+        report.add(
+            None,
+            "managed",
+            "error",
+            format!("The managed policy in {} is ignored: {}", source, err),
+            "Ask your administrator to correct the policy",
+        );
+    }
+    report
+}
+
+/// Checks the configuration once at startup; problems are emitted as
+/// `config:problems`, with a badge when any is an error.
+pub fn spawn_check(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let report = run(&app).await;
+        if report.problems.is_empty() {
+            return;
+        }
+        let payload = report.to_json();
+        let _ = environment::emit(&app, PROBLEMS_EVENT, &payload);
+        let errors = payload["errors"].as_u64().unwrap_or(0);
+        if errors > 0 {
+            notify::notify_in_app(
+                &app,
+                "config",
+                "Configuration problems",
+                &format!("{} problem(s) need fixing before everything works", errors),
+                payload,
+            );
+        }
+    });
+}
+
+/// Checks every profile (endpoints answer, the auth mode has what it needs,
+/// SSH and credential files exist) and the installed plugin manifests.
+/// Each problem names its profile and setting, and suggests a fix.
+#[tauri::command]
+pub async fn validate_config(app: AppHandle) -> Result<Value, String> {
+    Ok(run(&app).await.to_json())
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use cookie::Cookie;
+use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::settings::{Profile, SettingsStore};
+use crate::{crypto, net, secrets};
+
+const JAR_FILE: &str = "cookies.enc";
+const JAR_FORMAT: &str = "novaadapt-cookie-jar-v1";
+pub const JAR_KEY_NAME: &str = "cookie_jar:key";
+const MAX_COOKIES_PER_PROFILE: usize = 200;
+
+/// Profile ids keyed by the origins of profiles with a cookie jar.
+static CONFIG: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+static JAR: OnceLock<Mutex<Jar>> = OnceLock::new();
+
+#[derive(Default)]
+struct Jar {
+    path: Option<PathBuf>,
+    key: Option<[u8; crypto::KEY_LEN]>,
+    cookies: HashMap<String, Vec<StoredCookie>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// Sent to `domain` only, not its subdomains (no `Domain` attribute).
+    host_only: bool,
+    path: String,
+    secure: bool,
+    /// Unix seconds; session cookies have none and are kept until the
+    /// server replaces them, which is what lets an SSO session outlive a
+    /// restart.
+    expires: Option<i64>,
+}
+
+impl StoredCookie {
+    fn expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|at| at <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain || host.ends_with(&format!(".{}", self.domain))
+        };
+        let path = url.path();
+        let path_ok = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+        domain_ok && path_ok && (!self.secure || url.scheme() == "https")
+    }
+}
+
+fn config() -> &'static Mutex<HashMap<String, String>> {
+    CONFIG.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn jar() -> &'static Mutex<Jar> {
+    JAR.get_or_init(|| Mutex::new(Jar::default()))
+}
+
+/// Opens the encrypted jar in the app data dir, creating its key on first
+/// use. Without one (no keyring, unreadable file) cookies are kept in
+/// memory only.
+pub fn load(app: &AppHandle) {
+    if let Err(err) = load_persistent(app) {
+        eprintln!("cookie jar unavailable, keeping cookies in memory: {}", err);
+    }
+}
+
+fn load_persistent(app: &AppHandle) -> Result<(), String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("App data dir unavailable: {}", e))?;
+    let key = match secrets::get(JAR_KEY_NAME)? {
+        Some(encoded) => crypto::decode_key(&encoded)?,
+        None => {
+            let key = crypto::generate_key();
+            secrets::set(JAR_KEY_NAME, &crypto::encode_key(&key))?;
+            key
+        }
+    };
+    let path = dir.join(JAR_FILE);
+    let cookies = if path.exists() {
+        let raw = fs::read(&path).map_err(|e| format!("Read cookie jar failed: {}", e))?;
+        let envelope: Value =
+            serde_json::from_slice(&raw).map_err(|e| format!("Cookie jar is corrupted: {}", e))?;
+        let plaintext = crypto::open(&key, JAR_FORMAT, &envelope)?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Cookie jar payload is corrupted: {}", e))?
+    } else {
+        HashMap::new()
+    };
+    let mut jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    jar.path = Some(path);
+    jar.key = Some(key);
+    jar.cookies = cookies;
+    Ok(())
+}
+
+fn persist(jar: &Jar) {
+    let (Some(path), Some(key)) = (&jar.path, &jar.key) else {
+        return;
+    };
+    let written = serde_json::to_vec(&jar.cookies)
+        .map_err(|e| e.to_string())
+        .and_then(|plaintext| crypto::seal(key, JAR_FORMAT, &plaintext))
+        .and_then(|envelope| {
+            let tmp = path.with_extension("enc.tmp");
+            fs::write(&tmp, envelope.to_string())
+                .and_then(|_| fs::rename(&tmp, path))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(err) = written {
+        eprintln!("cookie jar write failed: {}", err);
+    }
+}
+
+/// Refreshes which origins keep cookies. Cookies of profiles that turned
+/// the jar off, or were removed, are dropped.
+pub fn configure(profiles: &[Profile]) {
+    let map: HashMap<String, String> = profiles
+        .iter()
+        .filter(|p| p.cookie_jar)
+        .flat_map(|p| {
+            p.endpoints()
+                .chain(p.read_routing.read_url.as_deref())
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, p.id.clone()))
+        })
+        .collect();
+    let mut jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    let before = jar.cookies.len();
+    jar.cookies.retain(|id, _| map.values().any(|v| v == id));
+    if jar.cookies.len() != before {
+        persist(&jar);
+    }
+    *config().lock().unwrap_or_else(|e| e.into_inner()) = map;
+}
+
+fn profile_for(url: &Url) -> Option<String> {
+    let origin = net::base_origin(url.as_str())?;
+    config()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&origin)
+        .cloned()
+}
+
+/// The `Cookie` header for a request to `url`, when its profile keeps a
+/// jar and holds cookies for it.
+pub fn header_for(url: &Url) -> Option<HeaderValue> {
+    let profile_id = profile_for(url)?;
+    let now = chrono::Utc::now().timestamp();
+    let jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    let mut matching: Vec<&StoredCookie> = jar
+        .cookies
+        .get(&profile_id)?
+        .iter()
+        .filter(|c| !c.expired(now) && c.matches(url))
+        .collect();
+    if matching.is_empty() {
+        return None;
+    }
+    // Longer paths first, as RFC 6265 asks.
+    matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+    let header = matching
+        .iter()
+        .map(|c| format!("{}={}", c.name, c.value))
+        .collect::<Vec<_>>()
+        .join("; ");
+    HeaderValue::from_str(&header).ok()
+}
+
+/// The directory of the request path, the default cookie path.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => url.path()[..i].to_string(),
+    }
+}
+
+fn parse(raw: &str, url: &Url, now: i64) -> Option<StoredCookie> {
+    let cookie = Cookie::parse(raw.to_string()).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let (domain, host_only) = match cookie.domain().filter(|d| !d.is_empty()) {
+        Some(domain) => {
+            let domain = domain.to_ascii_lowercase();
+            // A server may only set cookies for itself or a parent domain.
+            if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                return None;
+            }
+            (domain, false)
+        }
+        None => (host, true),
+    };
+    let path = cookie
+        .path()
+        .filter(|p| p.starts_with('/'))
+        .map(str::to_string)
+        .unwrap_or_else(|| default_path(url));
+    let expires = match cookie.max_age() {
+        Some(max_age) => Some(now.saturating_add(max_age.whole_seconds())),
+        None => cookie.expires_datetime().map(|at| at.unix_timestamp()),
+    };
+    Some(StoredCookie {
+        name: cookie.name().to_string(),
+        value: cookie.value().to_string(),
+        domain,
+        host_only,
+        path,
+        secure: cookie.secure().unwrap_or(false),
+        expires,
+    })
+}
+
+/// Keeps the cookies a response to `url` sets, replacing ones with the
+/// same name, domain and path, and dropping those it expires.
+pub fn store_from(url: &Url, headers: &HeaderMap) {
+    let Some(profile_id) = profile_for(url) else {
+        return;
+    };
+    let now = chrono::Utc::now().timestamp();
+    let set: Vec<StoredCookie> = headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|raw| parse(raw, url, now))
+        .collect();
+    if set.is_empty() {
+        return;
+    }
+    let mut jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    let cookies = jar.cookies.entry(profile_id).or_default();
+    let before = cookies.clone();
+    for cookie in set {
+        cookies.retain(|c| {
+            (c.name.as_str(), c.domain.as_str(), c.path.as_str())
+                != (
+                    cookie.name.as_str(),
+                    cookie.domain.as_str(),
+                    cookie.path.as_str(),
+                )
+        });
+        if !cookie.expired(now) {
+            cookies.push(cookie);
+        }
+    }
+    cookies.retain(|c| !c.expired(now));
+    if cookies.len() > MAX_COOKIES_PER_PROFILE {
+        let excess = cookies.len() - MAX_COOKIES_PER_PROFILE;
+        cookies.drain(..excess);
+    }
+    if *cookies != before {
+        persist(&jar);
+    }
+}
+
+/// The cookies a profile's jar holds: names, domains, paths and expiry,
+/// never values.
+#[tauri::command]
+pub fn get_cookie_jar(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let enabled = store
+        .snapshot()
+        .profiles
+        .iter()
+        .any(|p| p.id == conn.profile_id && p.cookie_jar);
+    let now = chrono::Utc::now().timestamp();
+    let jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    let cookies: Vec<Value> = jar
+        .cookies
+        .get(&conn.profile_id)
+        .map(|cookies| {
+            cookies
+                .iter()
+                .filter(|c| !c.expired(now))
+                .map(|c| {
+                    json!({
+                        "name": c.name,
+                        "domain": c.domain,
+                        "path": c.path,
+                        "secure": c.secure,
+                        "session": c.expires.is_none(),
+                        "expires_at": c.expires
+                            .and_then(|at| chrono::DateTime::from_timestamp(at, 0))
+                            .map(|at| at.to_rfc3339()),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "enabled": enabled,
+        "persistent": jar.path.is_some(),
+        "cookies": cookies,
+    }))
+}
+
+/// Empties a profile's jar, signing it out of a cookie-based gateway.
+#[tauri::command]
+pub fn clear_cookie_jar(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let mut jar = jar().lock().unwrap_or_else(|e| e.into_inner());
+    let removed = jar
+        .cookies
+        .remove(&conn.profile_id)
+        .map(|c| c.len())
+        .unwrap_or(0);
+    if removed > 0 {
+        persist(&jar);
+    }
+    Ok(json!({ "profile_id": conn.profile_id, "removed": removed }))
+}
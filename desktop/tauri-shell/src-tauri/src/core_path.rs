@@ -0,0 +1,149 @@
+use reqwest::Url;
+
+use crate::errors::ErrorCode;
+
+/// Refuses a core path the URL parser would rewrite on its way to the core:
+/// `.` and `..` segments, plain or percent-encoded, encoded slashes,
+/// backslashes, control characters and fragments. Every policy matches on
+/// the path, so it must be the one the core routes.
+pub fn check(path: &str) -> Result<(), String> {
+    let route = path.split('?').next().unwrap_or_default();
+    let rewritten = path.contains('#')
+        || path.chars().any(char::is_control)
+        || route.contains('\\')
+        || route.split('/').any(|segment| {
+            let segment = segment.to_ascii_lowercase();
+            let decoded = segment.replace("%2e", ".");
+            decoded == "." || decoded == ".." || segment.contains("%2f") || segment.contains("%5c")
+        });
+    if rewritten {
+        return Err(ErrorCode::InvalidInput.with(format!("Invalid core path {:?}", path)));
+    }
+    Ok(())
+}
+
+/// The path and query of `url` below `root`, the base URL with its API and
+/// tenant prefixes: the route the core will see.
+pub fn canonical(url: &Url, root: &Url) -> Result<String, String> {
+    let path = url
+        .path()
+        .strip_prefix(root.path().trim_end_matches('/'))
+        .filter(|path| path.starts_with('/'))
+        .ok_or_else(|| {
+            ErrorCode::InvalidInput.with(format!("{} is outside the core's API", url.path()))
+        })?;
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    })
+}
+
+/// The decoded segments of a plain path: no query, fragment, empty or dot
+/// segment, or encoded separator.
+pub fn segments(path: &str) -> Result<Vec<String>, String> {
+    let invalid = || ErrorCode::InvalidInput.with(format!("Invalid core path {:?}", path));
+    let rest = path.strip_prefix('/').ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Ok(Vec::new());
+    }
+    rest.split('/')
+        .map(|segment| {
+            let decoded = decode(segment).ok_or_else(invalid)?;
+            let plain = !decoded.is_empty()
+                && decoded != "."
+                && decoded != ".."
+                && !decoded.contains(['/', '\\', '?', '#'])
+                && !decoded.chars().any(char::is_control);
+            plain.then_some(decoded).ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Whether `path` is `prefix` or below it, segment by segment.
+pub fn under(path: &[String], prefix: &[String]) -> bool {
+    path.starts_with(prefix)
+}
+
+fn decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(root: &str, path: &str) -> Result<String, String> {
+        check(path)?;
+        let url = Url::parse(&format!("{}{}", root, path)).unwrap();
+        canonical(&url, &Url::parse(&format!("{}/", root)).unwrap())
+    }
+
+    #[test]
+    fn dot_segments_and_fragments_are_refused() {
+        for path in [
+            "/plans/x/../p-1/approve",
+            "/plans/p-1/./approve",
+            "/foo/%2e%2e/plans/p-1/approve",
+            "/foo/%2E./plans/p-1/approve",
+            "/plans/p-1/approve#x",
+            "/plans/x%2f..%2fp-1/approve",
+            "/plans/x\\..\\p-1/approve",
+            "/plans/p-1/app\trove",
+        ] {
+            assert!(check(path).is_err(), "{}", path);
+        }
+        assert!(check("/plans/p-1/approve?next=../x").is_ok());
+    }
+
+    #[test]
+    fn the_route_is_relative_to_the_api_and_tenant_prefix() {
+        assert_eq!(
+            route("https://core.example/api/t/acme", "/plans/p-1/approve").unwrap(),
+            "/plans/p-1/approve"
+        );
+        assert_eq!(
+            route("https://core.example", "/plans?status=pending").unwrap(),
+            "/plans?status=pending"
+        );
+        assert_eq!(route("https://core.example/api", "/").unwrap(), "/");
+        let outside = Url::parse("https://core.example/apix/plans").unwrap();
+        let root = Url::parse("https://core.example/api/").unwrap();
+        assert!(canonical(&outside, &root).is_err());
+    }
+
+    #[test]
+    fn segments_are_decoded_and_plain() {
+        assert_eq!(segments("/plans/a%20b").unwrap(), ["plans", "a b"]);
+        assert!(segments("/").unwrap().is_empty());
+        for path in [
+            "plans",
+            "/plans//x",
+            "/plans/",
+            "/plans/%2e%2e/admin",
+            "/plans/.",
+            "/plans?x=1",
+            "/plans%3fx=1",
+            "/plans#x",
+            "/plans/a%2fb",
+            "/plans/%zz",
+        ] {
+            assert!(segments(path).is_err(), "{}", path);
+        }
+        let prefix = segments("/plans").unwrap();
+        assert!(under(&segments("/plans/p-1").unwrap(), &prefix));
+        assert!(!under(&segments("/plansx").unwrap(), &prefix));
+    }
+}
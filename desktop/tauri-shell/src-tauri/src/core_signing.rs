@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use ed25519_dalek::Signature;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::settings::{CoreSigningMode, CoreSigningSettings, SettingsStore};
+use crate::{plugin_registry, secrets};
+
+/// A payload from the core that is fit to act on.
+pub enum Checked {
+    /// Signed, and the signature is good.
+    Verified(Value),
+    /// Not signed, or signed while verification is off.
+    Unsigned(Value),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    verified: u64,
+    unsigned: u64,
+    rejected: u64,
+}
+
+/// Outcomes per profile since launch, for `get_core_signing_status`.
+static COUNTS: OnceLock<Mutex<HashMap<String, Counts>>> = OnceLock::new();
+
+fn counts() -> &'static Mutex<HashMap<String, Counts>> {
+    COUNTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn secret_key(profile_id: &str) -> String {
+    format!("profile:{}:core_signing", profile_id)
+}
+
+pub fn validate(settings: &CoreSigningSettings) -> Result<(), String> {
+    if settings.mode == CoreSigningMode::Ed25519 {
+        plugin_registry::decode_key(&settings.public_key)
+            .map_err(|e| format!("Core signing key: {}", e))?;
+    }
+    Ok(())
+}
+
+fn envelope(value: &Value) -> Option<(&str, &str)> {
+    Some((value["signed"].as_str()?, value["signature"].as_str()?))
+}
+
+fn verify(
+    profile_id: &str,
+    settings: &CoreSigningSettings,
+    signed: &str,
+    signature: &str,
+) -> Result<(), String> {
+    let signature = B64
+        .decode(signature.trim())
+        .map_err(|_| ErrorCode::Forbidden.with("Core signature is not base64"))?;
+    let good = match settings.mode {
+        CoreSigningMode::Off => true,
+        CoreSigningMode::Hmac => {
+            let secret = secrets::get(&secret_key(profile_id))?.ok_or_else(|| {
+                ErrorCode::Forbidden.with("No core signing secret is stored for this profile")
+            })?;
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC accepts any key length");
+            mac.update(signed.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        }
+        CoreSigningMode::Ed25519 => {
+            let key = plugin_registry::decode_key(&settings.public_key)
+                .map_err(|e| ErrorCode::Forbidden.with(e))?;
+            Signature::from_slice(&signature)
+                .is_ok_and(|sig| key.verify_strict(signed.as_bytes(), &sig).is_ok())
+        }
+    };
+    if good {
+        Ok(())
+    } else {
+        Err(ErrorCode::Forbidden.with("Core signature does not verify"))
+    }
+}
+
+/// Checks a payload from the core against the profile's signing settings.
+/// A signed envelope is verified over its exact `signed` text and replaced
+/// by the payload it carries; an unsigned payload passes unless `require`
+/// is set. An `Err` means the payload must not be acted on.
+pub fn check(
+    profile_id: &str,
+    settings: &CoreSigningSettings,
+    value: Value,
+) -> Result<Checked, String> {
+    let outcome = match envelope(&value) {
+        None if settings.mode != CoreSigningMode::Off && settings.require => {
+            Err(ErrorCode::Forbidden.with("Unsigned core payload refused"))
+        }
+        None => Ok(Checked::Unsigned(value)),
+        Some((signed, signature)) => verify(profile_id, settings, signed, signature)
+            .and_then(|()| {
+                serde_json::from_str(signed).map_err(|e| {
+                    ErrorCode::InvalidInput.with(format!("Signed core payload is invalid: {}", e))
+                })
+            })
+            .map(|inner| match settings.mode {
+                CoreSigningMode::Off => Checked::Unsigned(inner),
+                _ => Checked::Verified(inner),
+            }),
+    };
+    if let Ok(mut counts) = counts().lock() {
+        let entry = counts.entry(profile_id.to_string()).or_default();
+        match &outcome {
+            Ok(Checked::Verified(_)) => entry.verified += 1,
+            Ok(Checked::Unsigned(_)) => entry.unsigned += 1,
+            Err(_) => entry.rejected += 1,
+        }
+    }
+    outcome
+}
+
+/// The payload inside a signed envelope, unverified, for code that only
+/// carries it on (air-gap export); anything else is returned as is.
+pub fn unwrap(value: &Value) -> Value {
+    envelope(value)
+        .and_then(|(signed, _)| serde_json::from_str(signed).ok())
+        .unwrap_or_else(|| value.clone())
+}
+
+/// Stores (or with `None`, clears) the HMAC secret used to verify the
+/// core's signatures for `profile_id`.
+#[tauri::command]
+pub fn set_core_signing_secret(
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+    secret: Option<String>,
+) -> Result<Value, String> {
+    if store.snapshot().profile(&profile_id).is_none() {
+        return Err(ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)));
+    }
+    let secret = secret.filter(|v| !v.trim().is_empty());
+    match secret.as_deref() {
+        Some(value) => secrets::set(&secret_key(&profile_id), value)?,
+        None => secrets::delete(&secret_key(&profile_id))?,
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": secret.is_some() }))
+}
+
+/// The profile's signing settings and how core payloads have fared since
+/// launch.
+#[tauri::command]
+pub fn get_core_signing_status(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .map(|p| p.core_signing.clone())
+        .unwrap_or_default();
+    let secret_stored = match settings.mode {
+        CoreSigningMode::Hmac => Some(secrets::get(&secret_key(&conn.profile_id))?.is_some()),
+        _ => None,
+    };
+    let seen = counts()
+        .lock()
+        .ok()
+        .and_then(|c| c.get(&conn.profile_id).copied())
+        .unwrap_or_default();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "mode": settings.mode,
+        "require": settings.require,
+        "secret_stored": secret_stored,
+        "verified": seen.verified,
+        "unsigned": seen.unsigned,
+        "rejected": seen.rejected,
+    }))
+}
@@ -0,0 +1,169 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+pub const KEY_LEN: usize = 32;
+pub const PBKDF2_ROUNDS: u32 = 600_000;
+/// The most rounds an envelope may ask for: the count comes from the file
+/// being opened, and an absurd one would pin the CPU for hours.
+pub const MAX_PBKDF2_ROUNDS: u32 = 4 * PBKDF2_ROUNDS;
+
+pub fn generate_key() -> [u8; KEY_LEN] {
+    Aes256Gcm::generate_key(OsRng).into()
+}
+
+/// Derives a key from an operator passphrase for portable files that cannot
+/// rely on this machine's keyring.
+pub fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+    key
+}
+
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut out = [0u8; N];
+    OsRng.fill_bytes(&mut out);
+    out
+}
+
+pub fn encode_key(key: &[u8; KEY_LEN]) -> String {
+    B64.encode(key)
+}
+
+pub fn decode_key(encoded: &str) -> Result<[u8; KEY_LEN], String> {
+    let raw = B64
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid key encoding: {}", e))?;
+    raw.try_into().map_err(|_| "Invalid key length".to_string())
+}
+
+/// Encrypts `plaintext` with AES-256-GCM and returns a JSON envelope tagged
+/// with `format` so callers can reject files meant for another store.
+pub fn seal(key: &[u8; KEY_LEN], format: &str, plaintext: &[u8]) -> Result<Value, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Encryption failed".to_string())?;
+    Ok(json!({
+        "format": format,
+        "cipher": "aes-256-gcm",
+        "nonce": B64.encode(nonce),
+        "ciphertext": B64.encode(ciphertext),
+    }))
+}
+
+pub fn open(key: &[u8; KEY_LEN], format: &str, envelope: &Value) -> Result<Vec<u8>, String> {
+    if envelope["format"].as_str() != Some(format) {
+        return Err(format!("Not a {} envelope", format));
+    }
+    let nonce = B64
+        .decode(envelope["nonce"].as_str().unwrap_or_default())
+        .map_err(|e| format!("Invalid nonce: {}", e))?;
+    if nonce.len() != 12 {
+        return Err("Invalid nonce length".to_string());
+    }
+    let ciphertext = B64
+        .decode(envelope["ciphertext"].as_str().unwrap_or_default())
+        .map_err(|e| format!("Invalid ciphertext: {}", e))?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Decryption failed (wrong key or corrupted data)".to_string())
+}
+
+/// `seal` under a key derived from `passphrase`, for files that leave this
+/// machine. The derivation parameters travel in the envelope's `kdf`.
+pub fn seal_with_passphrase(
+    passphrase: &str,
+    format: &str,
+    plaintext: &[u8],
+) -> Result<Value, String> {
+    let salt: [u8; 16] = random_bytes();
+    let key = derive_key(passphrase, &salt, PBKDF2_ROUNDS);
+    let mut envelope = seal(&key, format, plaintext)?;
+    envelope["kdf"] = json!({
+        "name": "pbkdf2-sha256",
+        "rounds": PBKDF2_ROUNDS,
+        "salt": B64.encode(salt),
+    });
+    Ok(envelope)
+}
+
+/// Opens an envelope written by `seal_with_passphrase`.
+pub fn open_with_passphrase(
+    passphrase: &str,
+    format: &str,
+    envelope: &Value,
+) -> Result<Vec<u8>, String> {
+    if envelope["kdf"]["name"].as_str() != Some("pbkdf2-sha256") {
+        return Err("Unsupported key derivation".to_string());
+    }
+    let salt = B64
+        .decode(envelope["kdf"]["salt"].as_str().unwrap_or_default())
+        .map_err(|e| format!("Invalid salt: {}", e))?;
+    let rounds = envelope["kdf"]["rounds"]
+        .as_u64()
+        .and_then(|r| u32::try_from(r).ok())
+        .filter(|r| *r > 0)
+        .ok_or_else(|| "Invalid key derivation rounds".to_string())?;
+    if rounds > MAX_PBKDF2_ROUNDS {
+        return Err(format!(
+            "Key derivation asks for {} rounds; at most {} are accepted",
+            rounds, MAX_PBKDF2_ROUNDS
+        ));
+    }
+    let key = derive_key(passphrase, &salt, rounds);
+    open(&key, format, envelope).map_err(|_| "Wrong passphrase or corrupted file".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_envelopes_open_only_with_their_key_and_format() {
+        let key = generate_key();
+        let envelope = seal(&key, "test-format", b"secret").unwrap();
+        assert_eq!(open(&key, "test-format", &envelope).unwrap(), b"secret");
+        assert!(open(&key, "other-format", &envelope).is_err());
+        assert!(open(&generate_key(), "test-format", &envelope).is_err());
+        let mut tampered = envelope.clone();
+        tampered["ciphertext"] = json!(B64.encode(b"not the ciphertext"));
+        assert!(open(&key, "test-format", &tampered).is_err());
+    }
+
+    #[test]
+    fn keys_round_trip_through_their_encoding() {
+        let key = generate_key();
+        assert_eq!(decode_key(&encode_key(&key)).unwrap(), key);
+        assert!(decode_key(&B64.encode([0u8; 16])).is_err());
+    }
+
+    #[test]
+    fn passphrase_envelopes_refuse_excessive_rounds() {
+        let mut envelope = seal(&generate_key(), "test-format", b"secret").unwrap();
+        envelope["kdf"] = json!({
+            "name": "pbkdf2-sha256",
+            "rounds": MAX_PBKDF2_ROUNDS + 1,
+            "salt": B64.encode([0u8; 16]),
+        });
+        let err = open_with_passphrase("passphrase", "test-format", &envelope).unwrap_err();
+        assert!(err.contains("rounds"), "{}", err);
+        envelope["kdf"]["rounds"] = json!(0);
+        assert!(open_with_passphrase("passphrase", "test-format", &envelope).is_err());
+    }
+
+    #[test]
+    fn passphrase_envelopes_open_with_the_passphrase() {
+        let envelope = seal_with_passphrase("correct horse", "test-format", b"secret").unwrap();
+        assert_eq!(
+            open_with_passphrase("correct horse", "test-format", &envelope).unwrap(),
+            b"secret"
+        );
+        assert!(open_with_passphrase("wrong horse", "test-format", &envelope).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::{crypto, migrations, secrets};
+
+pub const DB_FILE: &str = "novaadapt_desktop.db";
+const DB_KEY_NAME: &str = "db:key";
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// The shell's local SQLite cache, encrypted with SQLCipher under a key kept
+/// in the OS keyring. Falls back to an in-memory database when the app data
+/// directory or the keyring cannot be used.
+pub struct LocalDb {
+    conn: Mutex<Connection>,
+    encryption: Value,
+    schema: Value,
+}
+
+impl LocalDb {
+    pub fn open(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                Ok(dir.join(DB_FILE))
+            });
+        let opened = path.and_then(|path| {
+            let (mut conn, encryption) = open_encrypted(&path)?;
+            let schema = migrations::run(&mut conn, Some(&path))?;
+            Ok((conn, encryption, schema))
+        });
+        match opened {
+            Ok((conn, encryption, schema)) => Self {
+                conn: Mutex::new(conn),
+                encryption,
+                schema,
+            },
+            Err(err) => {
+                eprintln!("local database unavailable, using in-memory cache: {}", err);
+                Self::in_memory(json!({ "encrypted": false, "persistent": false, "error": err }))
+            }
+        }
+    }
+
+    /// An empty database that lives only as long as the process, used when
+    /// the file cannot be opened and in safe mode.
+    pub fn in_memory(encryption: Value) -> Self {
+        let mut conn = Connection::open_in_memory().expect("in-memory sqlite");
+        let schema = migrations::run(&mut conn, None).unwrap_or_else(|e| json!({ "error": e }));
+        Self {
+            conn: Mutex::new(conn),
+            encryption,
+            schema,
+        }
+    }
+
+    pub fn lock(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.conn
+            .lock()
+            .map_err(|_| ErrorCode::LocalStorage.with("Local database state poisoned"))
+    }
+}
+
+fn key_pragma(key: &[u8; crypto::KEY_LEN]) -> String {
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("\"x'{}'\"", hex)
+}
+
+fn quote_path(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', "''"))
+}
+
+fn is_plaintext(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok_and(|_| &header == PLAINTEXT_HEADER)
+}
+
+/// Opens `path` with `key`, failing unless the key actually decrypts it.
+fn open_with_key(path: &Path, key: &[u8; crypto::KEY_LEN]) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(&format!("PRAGMA key = {};", key_pragma(key)))?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    })?;
+    Ok(conn)
+}
+
+fn sidecars(path: &Path) -> [PathBuf; 2] {
+    ["-wal", "-shm"].map(|suffix| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    })
+}
+
+/// Rewrites an unencrypted database from before encryption in place,
+/// through a temporary encrypted copy, so an interrupted migration leaves
+/// the original intact.
+fn migrate_plaintext(path: &Path, key: &[u8; crypto::KEY_LEN]) -> Result<(), String> {
+    let fail = |e: rusqlite::Error| format!("Encrypt existing database failed: {}", e);
+    let encrypted = path.with_extension("db.encrypting");
+    let _ = fs::remove_file(&encrypted);
+    let plain = Connection::open(path).map_err(fail)?;
+    plain
+        .execute_batch(&format!(
+            "PRAGMA wal_checkpoint(TRUNCATE);
+             ATTACH DATABASE {} AS encrypted KEY {};",
+            quote_path(&encrypted),
+            key_pragma(key)
+        ))
+        .map_err(fail)?;
+    plain
+        .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+        .map_err(fail)?;
+    plain
+        .execute_batch("DETACH DATABASE encrypted;")
+        .map_err(fail)?;
+    drop(plain);
+    for sidecar in sidecars(path) {
+        let _ = fs::remove_file(sidecar);
+    }
+    fs::rename(&encrypted, path).map_err(|e| format!("Replace plaintext database failed: {}", e))
+}
+
+/// Opens the database file, creating its key on first run. An unencrypted
+/// file is migrated; one the key cannot open (the keyring entry was lost or
+/// replaced) is moved aside for `restore_database` and a fresh database is
+/// started, so the shell never fails to start over its cache.
+fn open_encrypted(path: &Path) -> Result<(Connection, Value), String> {
+    let key = match secrets::get(DB_KEY_NAME)? {
+        Some(encoded) => crypto::decode_key(&encoded)?,
+        None => {
+            let key = crypto::generate_key();
+            secrets::set(DB_KEY_NAME, &crypto::encode_key(&key))?;
+            key
+        }
+    };
+    let mut status = json!({ "encrypted": true, "persistent": true, "migrated": false });
+    if is_plaintext(path) {
+        migrate_plaintext(path, &key)?;
+        status["migrated"] = json!(true);
+    }
+    let conn = match open_with_key(path, &key) {
+        Ok(conn) => conn,
+        Err(_) if path.exists() => {
+            let aside = path.with_extension(format!(
+                "db.unreadable-{}",
+                Utc::now().format("%Y%m%dT%H%M%SZ")
+            ));
+            fs::rename(path, &aside)
+                .map_err(|e| format!("Move unreadable database aside failed: {}", e))?;
+            for sidecar in sidecars(path) {
+                let _ = fs::remove_file(sidecar);
+            }
+            eprintln!(
+                "local database key does not match; moved it to {} and started afresh",
+                aside.display()
+            );
+            status["set_aside"] = json!(aside.display().to_string());
+            open_with_key(path, &key).map_err(|e| e.to_string())?
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    Ok((conn, status))
+}
+
+/// Empties every table with `secure_delete` on, so freed pages are zeroed,
+/// then rebuilds the file and truncates the write-ahead log. Returns the
+/// rows removed.
+pub fn wipe(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.pragma_update(None, "secure_delete", "ON")?;
+    let tables: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut removed = 0;
+    for table in tables {
+        removed += conn.execute(
+            &format!("DELETE FROM \"{}\"", table.replace('"', "\"\"")),
+            [],
+        )?;
+    }
+    let _ = conn.execute("DELETE FROM sqlite_sequence", []);
+    conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+    Ok(removed)
+}
+
+pub fn get_cursor(conn: &Connection, profile_id: &str, stream: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT cursor FROM sync_cursors WHERE profile_id = ?1 AND stream = ?2",
+        (profile_id, stream),
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+pub fn set_cursor(
+    conn: &Connection,
+    profile_id: &str,
+    stream: &str,
+    cursor: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_cursors (profile_id, stream, cursor, updated_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(profile_id, stream) DO UPDATE SET cursor = excluded.cursor,
+             updated_at = excluded.updated_at",
+        (profile_id, stream, cursor, chrono::Utc::now().to_rfc3339()),
+    )?;
+    Ok(())
+}
+
+/// Appends an entry to the shell's own audit trail for changes made from the
+/// desktop, independent of whatever the core records.
+pub fn record_audit(
+    conn: &Connection,
+    profile_id: &str,
+    category: &str,
+    action: &str,
+    entity_id: &str,
+    detail: &serde_json::Value,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (at, profile_id, category, action, entity_id, detail)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            chrono::Utc::now().to_rfc3339(),
+            profile_id,
+            category,
+            action,
+            entity_id,
+            detail.to_string(),
+        ),
+    )?;
+    crate::session_recording::audited(profile_id, category, action, entity_id, detail);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn database_schema_status(db: State<'_, LocalDb>) -> Result<Value, String> {
+    let mut status = db.schema.clone();
+    if let Ok(conn) = db.lock() {
+        status["version"] = json!(migrations::version(&conn).ok());
+    }
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn database_encryption_status(db: State<'_, LocalDb>) -> Result<Value, String> {
+    Ok(db.encryption.clone())
+}
+
+/// The database key as hex, to keep somewhere safe: with it a database set
+/// aside after the keyring entry was lost can still be restored.
+#[tauri::command]
+pub fn database_recovery_key() -> Result<Value, String> {
+    let encoded = secrets::get(DB_KEY_NAME)?
+        .ok_or_else(|| ErrorCode::NotFound.with("No database key in the keyring"))?;
+    let key = crypto::decode_key(&encoded).map_err(|e| ErrorCode::LocalStorage.with(e))?;
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(json!({ "recovery_key": hex }))
+}
+
+/// Merges a database set aside at startup (or any copy of one) back into the
+/// live database, given the recovery key it was encrypted with. `path`
+/// defaults to the file set aside this session. Rows the live database
+/// already has are kept; ids assigned by the database are renumbered.
+#[tauri::command]
+pub fn restore_database(
+    db: State<'_, LocalDb>,
+    path: Option<String>,
+    recovery_key: String,
+) -> Result<Value, String> {
+    let path = path
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .or_else(|| db.encryption["set_aside"].as_str().map(str::to_string))
+        .ok_or_else(|| ErrorCode::InvalidInput.with("No set-aside database to restore"))?;
+    let raw = recovery_key.trim();
+    if raw.len() != crypto::KEY_LEN * 2 || !raw.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ErrorCode::InvalidInput.with("Recovery key must be 64 hex digits"));
+    }
+    let mut key = [0u8; crypto::KEY_LEN];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&raw[i * 2..i * 2 + 2], 16).unwrap_or_default();
+    }
+    let path = PathBuf::from(path);
+    open_with_key(&path, &key)
+        .map_err(|_| ErrorCode::InvalidInput.with("Recovery key does not open that database"))?;
+
+    let storage =
+        |e: rusqlite::Error| ErrorCode::LocalStorage.with(format!("Restore failed: {}", e));
+    let mut conn = db.lock()?;
+    conn.execute_batch(&format!(
+        "ATTACH DATABASE {} AS recovered KEY {};",
+        quote_path(&path),
+        key_pragma(&key)
+    ))
+    .map_err(storage)?;
+    let merged = merge_recovered(&mut conn);
+    let _ = conn.execute_batch("DETACH DATABASE recovered;");
+    let rows = merged.map_err(storage)?;
+    Ok(json!({ "path": path.display().to_string(), "restored_rows": rows }))
+}
+
+fn merge_recovered(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let tables: Vec<String> = tx
+        .prepare("SELECT name FROM recovered.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut restored = 0;
+    for table in tables {
+        // Only columns both copies have, minus an autoincrement `id`.
+        let columns: Vec<String> = tx
+            .prepare(
+                "SELECT m.name FROM pragma_table_info(?1) AS m
+                 JOIN pragma_table_info(?1, 'recovered') AS r ON r.name = m.name
+                 WHERE NOT (m.pk = 1 AND m.name = 'id' AND upper(m.type) = 'INTEGER')
+                 ORDER BY m.cid",
+            )?
+            .query_map([&table], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if columns.is_empty() {
+            continue;
+        }
+        let list = columns
+            .iter()
+            .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let table = table.replace('"', "\"\"");
+        restored += tx.execute(
+            &format!(
+                "INSERT OR IGNORE INTO main.\"{t}\" ({c}) SELECT {c} FROM recovered.\"{t}\"",
+                t = table,
+                c = list
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok(restored)
+}
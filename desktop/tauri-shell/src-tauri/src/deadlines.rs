@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::LocalDb;
+use crate::settings::{Connection as CoreConnection, SettingsStore};
+use crate::{environment, notify, tasks};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+const REFRESH_EVERY_TICKS: u32 = 6;
+const TICK_EVENT: &str = "deadline:tick";
+const EXPIRED_EVENT: &str = "deadline:expired";
+
+/// Escalation steps, most urgent first: (seconds remaining, level, raise an
+/// OS notification rather than only an in-app one).
+const ESCALATION: &[(i64, &str, bool)] = &[
+    (60, "critical", true),
+    (5 * 60, "urgent", true),
+    (15 * 60, "soon", false),
+];
+
+/// Most urgent escalation index already announced per `profile:plan`.
+#[derive(Default)]
+pub struct DeadlineAlerts {
+    raised: Mutex<HashMap<String, usize>>,
+}
+
+const TASK: &str = "deadlines";
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        let mut ticks = 0u32;
+        loop {
+            if ticks.is_multiple_of(REFRESH_EVERY_TICKS) {
+                let store = app.state::<SettingsStore>();
+                if let Ok(conn) = store.connection(None) {
+                    if let Err(err) = refresh(&conn, &app.state::<LocalDb>()).await {
+                        eprintln!("deadline refresh for {} failed: {}", conn.profile_id, err);
+                        tasks::failed(TASK, &err);
+                    }
+                }
+            }
+            ticks = ticks.wrapping_add(1);
+            evaluate(&app, Utc::now());
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+/// Returns tracked approval deadlines for a profile, including plans that
+/// expired locally while still pending on the core.
+#[tauri::command]
+pub fn get_plan_deadlines(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let now = Utc::now();
+    let guard = db.lock()?;
+    let mut stmt = guard
+        .prepare(
+            "SELECT plan_id, objective, expires_at, expired_at FROM plan_deadlines
+             WHERE profile_id = ?1 ORDER BY expires_at",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([&conn.profile_id], |row| {
+            let expires_at: String = row.get(2)?;
+            Ok(json!({
+                "plan_id": row.get::<_, String>(0)?,
+                "objective": row.get::<_, String>(1)?,
+                "expires_at": expires_at,
+                "expired_at": row.get::<_, Option<String>>(3)?,
+                "remaining_seconds": remaining_seconds(&expires_at, now),
+            }))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(json!({ "profile_id": conn.profile_id, "deadlines": rows }))
+}
+
+#[tauri::command]
+pub async fn refresh_plan_deadlines(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let tracked = refresh(&conn, &db).await?;
+    evaluate(&app, Utc::now());
+    Ok(json!({ "profile_id": conn.profile_id, "tracked": tracked }))
+}
+
+/// Syncs the deadline table with the core's pending plans. Plans that left
+/// `pending` on the core are dropped; locally expired ones are kept until then.
+async fn refresh(conn: &CoreConnection, db: &LocalDb) -> Result<usize, String> {
+    let plans = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/plans?limit=200",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let pending: Vec<(String, String, DateTime<Utc>)> = plans
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|p| p["status"].as_str() == Some("pending"))
+        .filter_map(|p| {
+            let id = p["id"].as_str()?.to_string();
+            let objective = p["objective"].as_str().unwrap_or_default().to_string();
+            Some((id, objective, plan_deadline(p)?))
+        })
+        .collect();
+
+    let guard = db.lock()?;
+    let tracked: Vec<String> = {
+        let mut stmt = guard
+            .prepare("SELECT plan_id FROM plan_deadlines WHERE profile_id = ?1")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([&conn.profile_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        ids
+    };
+    for plan_id in tracked {
+        if !pending.iter().any(|(id, _, _)| *id == plan_id) {
+            guard
+                .execute(
+                    "DELETE FROM plan_deadlines WHERE profile_id = ?1 AND plan_id = ?2",
+                    (&conn.profile_id, &plan_id),
+                )
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    for (plan_id, objective, expires_at) in &pending {
+        upsert(&guard, &conn.profile_id, plan_id, objective, expires_at)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(pending.len())
+}
+
+fn upsert(
+    conn: &Connection,
+    profile_id: &str,
+    plan_id: &str,
+    objective: &str,
+    expires_at: &DateTime<Utc>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO plan_deadlines (profile_id, plan_id, objective, expires_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(profile_id, plan_id) DO UPDATE SET objective = excluded.objective,
+             expires_at = excluded.expires_at,
+             expired_at = CASE WHEN excluded.expires_at = expires_at THEN expired_at END",
+        params![profile_id, plan_id, objective, expires_at.to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Reads the approval deadline from plan metadata: an absolute timestamp, or
+/// a timeout relative to `created_at`.
+fn plan_deadline(plan: &Value) -> Option<DateTime<Utc>> {
+    let meta = &plan["metadata"];
+    let absolute = [
+        &plan["expires_at"],
+        &plan["approval_deadline"],
+        &plan["approval_expires_at"],
+        &meta["expires_at"],
+        &meta["approval_deadline"],
+    ]
+    .into_iter()
+    .find_map(|v| v.as_str())
+    .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+    .map(|ts| ts.with_timezone(&Utc));
+    if absolute.is_some() {
+        return absolute;
+    }
+    let timeout = [
+        &plan["approval_timeout_seconds"],
+        &meta["approval_timeout_seconds"],
+        &meta["ttl_seconds"],
+    ]
+    .into_iter()
+    .find_map(|v| v.as_f64())
+    .filter(|secs| *secs > 0.0)?;
+    let created = DateTime::parse_from_rfc3339(plan["created_at"].as_str()?).ok()?;
+    Some(created.with_timezone(&Utc) + chrono::Duration::milliseconds((timeout * 1000.0) as i64))
+}
+
+fn remaining_seconds(expires_at: &str, now: DateTime<Utc>) -> Option<i64> {
+    let expires = DateTime::parse_from_rfc3339(expires_at).ok()?;
+    Some((expires.with_timezone(&Utc) - now).num_seconds())
+}
+
+/// Emits countdowns for every live deadline, escalates notifications as they
+/// cross the `ESCALATION` thresholds, and marks lapsed plans expired.
+fn evaluate(app: &AppHandle, now: DateTime<Utc>) {
+    let db = app.state::<LocalDb>();
+    let Ok(guard) = db.lock() else {
+        return;
+    };
+    let live: Vec<(String, String, String, String)> = guard
+        .prepare(
+            "SELECT profile_id, plan_id, objective, expires_at FROM plan_deadlines
+             WHERE expired_at IS NULL",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect()
+        })
+        .unwrap_or_default();
+
+    let alerts = app.state::<DeadlineAlerts>();
+    for (profile_id, plan_id, objective, expires_at) in live {
+        let Some(remaining) = remaining_seconds(&expires_at, now) else {
+            continue;
+        };
+        let key = format!("{}:{}", profile_id, plan_id);
+        let payload = json!({
+            "profile_id": profile_id,
+            "plan_id": plan_id,
+            "objective": objective,
+            "expires_at": expires_at,
+            "remaining_seconds": remaining.max(0),
+        });
+
+        if remaining <= 0 {
+            let _ = guard.execute(
+                "UPDATE plan_deadlines SET expired_at = ?3 WHERE profile_id = ?1 AND plan_id = ?2",
+                (&profile_id, &plan_id, now.to_rfc3339()),
+            );
+            if let Ok(mut raised) = alerts.raised.lock() {
+                raised.remove(&key);
+            }
+            let _ = environment::emit(app, EXPIRED_EVENT, &payload);
+            notify::notify(
+                app,
+                "deadline",
+                "Plan approval expired",
+                &format!(
+                    "{} was not approved in time",
+                    display_name(&objective, &plan_id)
+                ),
+                payload,
+            );
+            continue;
+        }
+
+        let level = ESCALATION
+            .iter()
+            .position(|(threshold, _, _)| remaining <= *threshold);
+        let mut tick = payload.clone();
+        tick["level"] = json!(level.map(|i| ESCALATION[i].1).unwrap_or("normal"));
+        let _ = environment::emit(app, TICK_EVENT, &tick);
+
+        let Some(index) = level else {
+            continue;
+        };
+        let escalate = alerts
+            .raised
+            .lock()
+            .map(|mut raised| {
+                let previous = raised.get(&key).copied().unwrap_or(usize::MAX);
+                if index < previous {
+                    raised.insert(key.clone(), index);
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false);
+        if !escalate {
+            continue;
+        }
+        let (_, level, os) = ESCALATION[index];
+        let title = format!("Approval needed ({})", level);
+        let body = format!(
+            "{} expires in {}",
+            display_name(&objective, &plan_id),
+            format_remaining(remaining)
+        );
+        if os {
+            notify::notify(app, "deadline", &title, &body, tick);
+        } else {
+            notify::notify_in_app(app, "deadline", &title, &body, tick);
+        }
+    }
+}
+
+fn display_name<'a>(objective: &'a str, plan_id: &'a str) -> &'a str {
+    if objective.trim().is_empty() {
+        plan_id
+    } else {
+        objective
+    }
+}
+
+fn format_remaining(seconds: i64) -> String {
+    if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::handover::{self, DECISION_CATEGORIES};
+use crate::residency;
+use crate::settings::SettingsStore;
+
+const DEFAULT_RANGE: &str = "30d";
+const MAX_RANGE_DAYS: i64 = 366;
+/// The row holding the whole team's figures in CSV output.
+const ALL_OPERATORS: &str = "(all)";
+const UNKNOWN_OPERATOR: &str = "(unknown)";
+/// Approvers and approval sources meaning no operator decided the plan.
+const AUTOMATIC: &[&str] = &["auto", "auto_approval", "auto-approval", "policy", "system"];
+const CSV_COLUMNS: &[&str] = &[
+    "operator",
+    "decisions",
+    "approved",
+    "rejected",
+    "auto_approved",
+    "rejection_rate",
+    "auto_approval_share",
+    "latency_mean_seconds",
+    "latency_median_seconds",
+    "latency_p90_seconds",
+];
+
+#[derive(Default)]
+struct Tally {
+    approved: usize,
+    rejected: usize,
+    auto_approved: usize,
+    /// Seconds from creation to decision, for plans that carry both.
+    latencies: Vec<i64>,
+}
+
+impl Tally {
+    fn decisions(&self) -> usize {
+        self.approved + self.rejected
+    }
+
+    fn add(&mut self, rejected: bool, automatic: bool, latency: Option<i64>) {
+        if rejected {
+            self.rejected += 1;
+        } else {
+            self.approved += 1;
+            if automatic {
+                self.auto_approved += 1;
+            }
+        }
+        self.latencies.extend(latency);
+    }
+
+    fn describe(&mut self) -> Value {
+        self.latencies.sort_unstable();
+        let ratio = |part: usize, whole: usize| {
+            (whole > 0).then(|| (part as f64 / whole as f64 * 10_000.0).round() / 10_000.0)
+        };
+        let percentile = |p: f64| {
+            let n = self.latencies.len();
+            (n > 0).then(|| self.latencies[((n - 1) as f64 * p).round() as usize])
+        };
+        let mean = (!self.latencies.is_empty())
+            .then(|| self.latencies.iter().sum::<i64>() / self.latencies.len() as i64);
+        json!({
+            "decisions": self.decisions(),
+            "approved": self.approved,
+            "rejected": self.rejected,
+            "auto_approved": self.auto_approved,
+            "rejection_rate": ratio(self.rejected, self.decisions()),
+            "auto_approval_share": ratio(self.auto_approved, self.approved),
+            "latency_mean_seconds": mean,
+            "latency_median_seconds": percentile(0.5),
+            "latency_p90_seconds": percentile(0.9),
+        })
+    }
+}
+
+pub fn decided_by(plan: &Value) -> Option<String> {
+    ["decided_by", "approved_by", "rejected_by", "reviewer"]
+        .iter()
+        .find_map(|k| match &plan[*k] {
+            Value::String(by) if !by.trim().is_empty() => Some(by.trim().to_string()),
+            Value::Object(by) => ["name", "email", "id"]
+                .iter()
+                .find_map(|f| by.get(*f)?.as_str().map(str::to_string)),
+            _ => None,
+        })
+}
+
+pub fn automatic(plan: &Value, by: Option<&str>) -> bool {
+    plan["auto_approved"].as_bool() == Some(true)
+        || ["approval_source", "decision_source"]
+            .iter()
+            .any(|k| plan[*k].as_str().is_some_and(|s| AUTOMATIC.contains(&s)))
+        || by.is_some_and(|b| AUTOMATIC.contains(&b.to_ascii_lowercase().as_str()))
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn csv(rows: &[(String, Value)]) -> String {
+    let mut out = CSV_COLUMNS.join(",");
+    out.push('\n');
+    for (operator, figures) in rows {
+        let fields: Vec<String> = CSV_COLUMNS
+            .iter()
+            .map(|c| match *c {
+                "operator" => csv_field(&json!(operator)),
+                column => csv_field(&figures[column]),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Computes decision metrics for the team over `range` (a span ending now,
+/// `30d` by default, or `start/end`) from the local plan cache and audit
+/// trail, and writes them to `path` as CSV (one row per operator plus an
+/// `(all)` row) or JSON. `format` defaults to the file's extension.
+///
+/// The figures are approval latency (creation to decision), rejection rate,
+/// the share of approvals made automatically, and per-operator volumes.
+/// Plans decided with no recorded operator count as `(unknown)`.
+#[tauri::command]
+pub fn export_decision_metrics(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    path: String,
+    format: Option<String>,
+    range: Option<String>,
+) -> Result<Value, String> {
+    let path = path.trim().to_string();
+    if path.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Path is required"));
+    }
+    let target = residency::check("The decision metrics export", &path)?;
+    let format = format
+        .map(|f| f.trim().to_ascii_lowercase())
+        .or_else(|| {
+            Path::new(&path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase)
+        })
+        .unwrap_or_else(|| "csv".to_string());
+    if !matches!(format.as_str(), "csv" | "json") {
+        return Err(ErrorCode::InvalidInput.with("Format must be `csv` or `json`"));
+    }
+    let (since, until) =
+        handover::parse_range(range.as_deref().unwrap_or(DEFAULT_RANGE), MAX_RANGE_DAYS)?;
+    let conn = store.connection(profile.as_deref())?;
+    let (plans, audit) = {
+        let guard = db.lock()?;
+        let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+        (
+            handover::cached(&guard, &conn.profile_id, "plan").map_err(storage)?,
+            handover::audit_rows(&guard, &conn.profile_id, since, until).map_err(storage)?,
+        )
+    };
+
+    let mut overall = Tally::default();
+    let mut operators: BTreeMap<String, Tally> = BTreeMap::new();
+    for plan in &plans {
+        let status = handover::status_of(plan);
+        let Some(at) = handover::decided_at(plan, &status, since, until) else {
+            continue;
+        };
+        let rejected = status == "rejected";
+        let by = decided_by(plan);
+        let auto = !rejected && automatic(plan, by.as_deref());
+        let latency = handover::timestamp(&plan["created_at"])
+            .map(|created| (at - created).num_seconds())
+            .filter(|s| *s >= 0);
+        overall.add(rejected, auto, latency);
+        operators
+            .entry(by.unwrap_or_else(|| UNKNOWN_OPERATOR.to_string()))
+            .or_default()
+            .add(rejected, auto, latency);
+    }
+    // Decisions taken through the shell itself (scheduled, step-gated,
+    // second approvals), which the core may not attribute.
+    let mut shell_actions: BTreeMap<String, usize> = BTreeMap::new();
+    for row in &audit {
+        if let (Some(category), Some(action)) = (row["category"].as_str(), row["action"].as_str()) {
+            if DECISION_CATEGORIES.contains(&category) {
+                *shell_actions
+                    .entry(format!("{}.{}", category, action))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    let mut rows = vec![(ALL_OPERATORS.to_string(), overall.describe())];
+    rows.extend(
+        operators
+            .into_iter()
+            .map(|(operator, mut tally)| (operator, tally.describe())),
+    );
+    let report = json!({
+        "profile_id": conn.profile_id,
+        "range": {
+            "since": since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "until": until.to_rfc3339_opts(SecondsFormat::Secs, true),
+        },
+        "generated_at": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "overall": rows[0].1,
+        "operators": rows[1..]
+            .iter()
+            .map(|(operator, figures)| {
+                let mut row = figures.clone();
+                row["operator"] = json!(operator);
+                row
+            })
+            .collect::<Vec<_>>(),
+        "shell_actions": shell_actions,
+    });
+    let bytes = match format.as_str() {
+        "json" => serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?,
+        _ => csv(&rows).into_bytes(),
+    };
+    std::fs::write(&target, bytes)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write decision metrics failed: {}", e)))?;
+
+    let summary = json!({
+        "path": path,
+        "format": format,
+        "profile_id": report["profile_id"],
+        "range": report["range"],
+        "overall": report["overall"],
+        "operators": rows.len() - 1,
+    });
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "audit",
+        "decision_metrics_export",
+        "",
+        &summary,
+    );
+    Ok(summary)
+}
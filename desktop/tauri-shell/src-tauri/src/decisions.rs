@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::{
+    chat_webhooks, claims, environment, exec_windows, justifications, my_decisions, presence,
+    ticketing, trace_context, two_person,
+};
+
+const TICK_EVENT: &str = "decision:tick";
+const SENT_EVENT: &str = "decision:sent";
+const CANCELLED_EVENT: &str = "decision:cancelled";
+const MAX_DELAY_SECS: u64 = 60;
+/// Sent when the operator rejects without saying why.
+pub const DEFAULT_REJECT_REASON: &str = "Operator rejected";
+
+pub struct Scheduled {
+    pub base_url: String,
+    pub token: Option<String>,
+    pub plan_id: String,
+    pub decision: String,
+    pub payload: Option<Value>,
+    pub version: Option<String>,
+    pub fires_at: String,
+}
+
+/// The version a plan is compared by: an explicit `version`/`etag` when the
+/// core provides one, otherwise its `updated_at` timestamp.
+pub fn plan_version(plan: &Value) -> Option<String> {
+    [&plan["version"], &plan["etag"], &plan["updated_at"]]
+        .into_iter()
+        .find_map(|v| match v {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+}
+
+/// The payload field an execution-window override code travels in. The
+/// shared request path takes it out before the call reaches the core.
+pub const OVERRIDE_FIELD: &str = "override_confirmation";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Route {
+    /// `approve` and `approve_async`.
+    Approve,
+    /// Re-running failed steps, which approves them again.
+    Retry,
+    Reject,
+}
+
+/// The plan a core route decides on, and how.
+fn decision_route<'a>(method: &Method, path: &'a str) -> Option<(&'a str, Route)> {
+    if *method != Method::POST {
+        return None;
+    }
+    let path = path.split('?').next().unwrap_or_default();
+    let (plan_id, action) = path.strip_prefix("/plans/")?.split_once('/')?;
+    let route = match action {
+        "approve" | "approve_async" => Route::Approve,
+        "retry_failed" | "retry_failed_async" => Route::Retry,
+        "reject" => Route::Reject,
+        _ if action.starts_with("steps/") && action.ends_with("/retry") => Route::Retry,
+        _ => return None,
+    };
+    (!plan_id.is_empty()).then_some((plan_id, route))
+}
+
+/// The plan a decision is about, read from the core at most once however
+/// many of the policies in `gate` look at it.
+pub struct PlanRead<'a> {
+    base_url: &'a str,
+    token: Option<&'a str>,
+    plan_id: &'a str,
+    plan: Option<Value>,
+}
+
+impl<'a> PlanRead<'a> {
+    pub fn new(base_url: &'a str, token: Option<&'a str>, plan_id: &'a str) -> Self {
+        PlanRead {
+            base_url,
+            token,
+            plan_id,
+            plan: None,
+        }
+    }
+
+    pub fn base_url(&self) -> &'a str {
+        self.base_url
+    }
+
+    pub fn plan_id(&self) -> &'a str {
+        self.plan_id
+    }
+
+    pub async fn get(&mut self) -> Result<&Value, String> {
+        let plan = match self.plan.take() {
+            Some(plan) => plan,
+            None => {
+                crate::request_json(
+                    Method::GET,
+                    self.base_url,
+                    &format!("/plans/{}", self.plan_id),
+                    self.token.map(str::to_string),
+                    None,
+                )
+                .await?
+            }
+        };
+        Ok(self.plan.insert(plan))
+    }
+}
+
+/// What `gate` let a decision through with, to be spent once the core
+/// has taken it.
+pub struct Gate {
+    base_url: String,
+    plan_id: String,
+    second: Option<Value>,
+    window: Option<Value>,
+}
+
+impl Gate {
+    /// Spends the second approval and reports what was applied (under
+    /// `execution_window` and `second_approval`) in the core's answer.
+    pub fn finish(self, response: &mut Value) {
+        if self.second.is_some() {
+            two_person::redeem(&self.base_url, &self.plan_id);
+        }
+        if let Value::Object(body) = response {
+            if let Some(window) = self.window {
+                body.insert("execution_window".to_string(), window);
+            }
+            if let Some(second) = self.second {
+                body.insert("second_approval".to_string(), second);
+            }
+        }
+    }
+}
+
+/// Holds a core request that decides a plan to the profile's policies,
+/// whichever command sends it. Approvals and rejections meet the
+/// justification policy, with a `reason_template` in the payload filled in
+/// from the template library. Approvals, including retries of failed
+/// steps, of critical plans need a second operator's confirmation where
+/// the profile asks for one, and go out naming both operators. Approvals
+/// that execute are held to the execution windows, overridden by a code in
+/// the payload's `override_confirmation`. A decision sent `If-Match` a
+/// version the plan no longer has fails with `PLAN_CONFLICT` holding the
+/// plan, before any of that. Anything else passes untouched.
+pub async fn gate(
+    method: &Method,
+    base_url: &str,
+    path: &str,
+    token: Option<&str>,
+    payload: &mut Option<Value>,
+    if_match: Option<&str>,
+) -> Result<Option<Gate>, String> {
+    let Some((plan_id, route)) = decision_route(method, path) else {
+        return Ok(None);
+    };
+    let mut plan = PlanRead::new(base_url, token, plan_id);
+    if let Some(expected) = if_match.map(|v| v.trim().trim_matches('"')) {
+        let current = plan.get().await?;
+        if plan_version(current).as_deref() != Some(expected) {
+            return Err(conflict(current.clone()));
+        }
+    }
+    let override_code = match payload {
+        Some(Value::Object(body)) => body
+            .remove(OVERRIDE_FIELD)
+            .and_then(|code| code.as_str().map(str::to_string)),
+        _ => None,
+    };
+    let decision = if route == Route::Reject {
+        "reject"
+    } else {
+        "approve"
+    };
+    if route != Route::Retry {
+        justifications::guard(&mut plan, decision, payload).await?;
+    }
+    let mut gate = Gate {
+        base_url: base_url.to_string(),
+        plan_id: plan_id.to_string(),
+        second: None,
+        window: None,
+    };
+    if route == Route::Reject {
+        return Ok(Some(gate));
+    }
+    gate.second = two_person::guard(&mut plan).await?;
+    if route == Route::Approve {
+        gate.window = exec_windows::guard(base_url, plan_id, payload, override_code.as_deref())?;
+    }
+    if let Some(second) = &gate.second {
+        if let Value::Object(body) = payload.get_or_insert_with(|| json!({})) {
+            body.insert("second_approval".to_string(), second.clone());
+        }
+    }
+    Ok(Some(gate))
+}
+
+/// Sends an approve/reject decision. With `expected_version` set, the call
+/// carries `If-Match`, so a plan modified since the operator viewed it
+/// fails with `PLAN_CONFLICT` whose detail is JSON holding the refreshed
+/// plan. The request path holds the decision to the
+/// profile's policies (see `gate`); `override_code` goes along for an
+/// execution window in force.
+pub async fn send_decision(
+    base_url: &str,
+    token: Option<String>,
+    plan_id: &str,
+    decision: &str,
+    payload: Option<Value>,
+    expected_version: Option<&str>,
+    override_code: Option<&str>,
+) -> Result<Value, String> {
+    let mut payload = payload;
+    justifications::fill_template(decision, &mut payload)?;
+    let payload_sent = payload.clone();
+    if let Some(code) = override_code.filter(|c| !c.trim().is_empty()) {
+        if let Value::Object(body) = payload.get_or_insert_with(|| json!({})) {
+            body.insert(OVERRIDE_FIELD.to_string(), json!(code));
+        }
+    }
+    let operator = two_person::first_identity(token.as_deref());
+    // The version check, the decision and any conflict re-read are one trace.
+    let result = trace_context::within(
+        None,
+        "plan_decision",
+        send_checked(
+            base_url,
+            token,
+            plan_id,
+            decision,
+            payload,
+            expected_version,
+        ),
+    )
+    .await?;
+    ticketing::decided(base_url, plan_id, decision, payload_sent.as_ref());
+    chat_webhooks::decided(base_url, plan_id, decision, payload_sent.as_ref());
+    presence::decided(base_url, plan_id);
+    my_decisions::decided(base_url, operator, plan_id, decision, payload_sent.as_ref());
+    claims::decided(base_url, plan_id);
+    Ok(result)
+}
+
+async fn send_checked(
+    base_url: &str,
+    token: Option<String>,
+    plan_id: &str,
+    decision: &str,
+    payload: Option<Value>,
+    expected_version: Option<&str>,
+) -> Result<Value, String> {
+    let mut headers = Vec::new();
+    if let Some(expected) = expected_version.filter(|v| !v.is_empty()) {
+        headers.push(("If-Match", format!("\"{}\"", expected)));
+    }
+    let path = format!("/plans/{}/{}", plan_id, decision);
+    match crate::request_json_with_headers(
+        Method::POST,
+        base_url,
+        &path,
+        token.clone(),
+        payload,
+        &headers,
+    )
+    .await
+    {
+        // `gate` refused it and already carries the plan.
+        Err(err) if is_conflict(&err) => Err(err),
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::PlanConflict) => {
+            let plan_path = format!("/plans/{}", plan_id);
+            let current = crate::request_json(Method::GET, base_url, &plan_path, token, None)
+                .await
+                .unwrap_or(Value::Null);
+            Err(conflict(current))
+        }
+        other => other,
+    }
+}
+
+fn conflict(plan: Value) -> String {
+    ErrorCode::PlanConflict.with(json!({
+        "message": "Plan changed since it was last viewed",
+        "version": plan_version(&plan),
+        "plan": plan,
+    }))
+}
+
+/// Whether `err` is one `conflict` made.
+fn is_conflict(err: &str) -> bool {
+    errors::code_of(err) == Some(ErrorCode::PlanConflict)
+        && serde_json::from_str::<Value>(errors::detail_of(err))
+            .is_ok_and(|detail| detail.get("plan").is_some())
+}
+
+/// Approve/reject calls held back for an undo window. A ticket that is still
+/// in the map when its countdown ends is sent; cancelling removes it, so
+/// whichever side removes the ticket first wins.
+#[derive(Default)]
+pub struct ScheduledDecisions {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<String, Scheduled>>,
+}
+
+impl ScheduledDecisions {
+    fn take(&self, ticket_id: &str) -> Option<Scheduled> {
+        self.pending.lock().ok()?.remove(ticket_id)
+    }
+
+    /// Drops every pending decision, announcing each as cancelled.
+    pub fn cancel_all(&self, app: &AppHandle) -> usize {
+        let drained: Vec<(String, Scheduled)> = match self.pending.lock() {
+            Ok(mut pending) => pending.drain().collect(),
+            Err(_) => return 0,
+        };
+        for (ticket_id, entry) in &drained {
+            let _ = environment::emit(
+                app,
+                CANCELLED_EVENT,
+                json!({
+                    "ticket_id": ticket_id,
+                    "plan_id": entry.plan_id,
+                    "decision": entry.decision,
+                }),
+            );
+        }
+        drained.len()
+    }
+
+    /// Takes every decision still in its undo window, for shutdown to send
+    /// or persist. Their countdown tasks find them gone and stop.
+    pub fn take_all(&self) -> Vec<(String, Scheduled)> {
+        match self.pending.lock() {
+            Ok(mut pending) => pending.drain().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().map(|p| p.len()).unwrap_or(0)
+    }
+
+    fn is_pending(&self, ticket_id: &str) -> bool {
+        self.pending
+            .lock()
+            .map(|p| p.contains_key(ticket_id))
+            .unwrap_or(false)
+    }
+}
+
+/// Sends a decision immediately with an arbitrary payload (e.g. repair
+/// options), optional version check and execution-window override. A
+/// `trace_id` from the caller ties the requests to its trace.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_plan_decision(
+    base_url: String,
+    token: Option<String>,
+    plan_id: String,
+    decision: String,
+    payload: Option<Value>,
+    version: Option<String>,
+    override_confirmation: Option<String>,
+    trace_id: Option<String>,
+) -> Result<Value, String> {
+    let decision = validate_decision(&decision, &plan_id)?;
+    trace_context::within(
+        trace_id.as_deref(),
+        "send_plan_decision",
+        send_decision(
+            &base_url,
+            token,
+            &plan_id,
+            &decision,
+            payload,
+            version.as_deref(),
+            override_confirmation.as_deref(),
+        ),
+    )
+    .await
+}
+
+pub fn validate_decision(decision: &str, plan_id: &str) -> Result<String, String> {
+    let decision = decision.trim().to_ascii_lowercase();
+    if decision != "approve" && decision != "reject" {
+        return Err(ErrorCode::InvalidInput.with(format!("Unsupported decision: {}", decision)));
+    }
+    if plan_id.trim().is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    Ok(decision)
+}
+
+/// Schedules `decision` (`approve` or `reject`) for `plan_id` to be sent
+/// after `delay_seconds`, emitting `decision:tick` once per second until then.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn schedule_plan_decision(
+    app: AppHandle,
+    scheduled: State<'_, ScheduledDecisions>,
+    base_url: String,
+    token: Option<String>,
+    plan_id: String,
+    decision: String,
+    payload: Option<Value>,
+    delay_seconds: u64,
+    version: Option<String>,
+) -> Result<Value, String> {
+    let decision = validate_decision(&decision, &plan_id)?;
+    if decision == "approve" {
+        // Refuse up front rather than when the undo window runs out.
+        exec_windows::guard(&base_url, &plan_id, &mut payload.clone(), None)?;
+        environment::guard(
+            &Method::POST,
+            &base_url,
+            &format!("/plans/{}/approve", plan_id),
+            payload.as_ref(),
+            false,
+        )?;
+    }
+    let delay = delay_seconds.clamp(1, MAX_DELAY_SECS);
+    let ticket_id = format!(
+        "decision-{}",
+        scheduled.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    );
+    let fires_at = (chrono::Utc::now() + chrono::Duration::seconds(delay as i64)).to_rfc3339();
+    scheduled
+        .pending
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Decision state poisoned"))?
+        .insert(
+            ticket_id.clone(),
+            Scheduled {
+                base_url,
+                token,
+                plan_id: plan_id.clone(),
+                decision: decision.clone(),
+                payload,
+                version,
+                fires_at: fires_at.clone(),
+            },
+        );
+
+    let ticket = ticket_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let scheduled = app.state::<ScheduledDecisions>();
+        for remaining in (1..=delay).rev() {
+            if !scheduled.is_pending(&ticket) {
+                return;
+            }
+            let _ = environment::emit(
+                &app,
+                TICK_EVENT,
+                json!({
+                    "ticket_id": ticket,
+                    "plan_id": plan_id,
+                    "decision": decision,
+                    "remaining_seconds": remaining,
+                }),
+            );
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let Some(entry) = scheduled.take(&ticket) else {
+            return;
+        };
+        let result = send_decision(
+            &entry.base_url,
+            entry.token,
+            &entry.plan_id,
+            &entry.decision,
+            entry.payload,
+            entry.version.as_deref(),
+            None,
+        )
+        .await;
+        let _ = environment::emit(
+            &app,
+            SENT_EVENT,
+            json!({
+                "ticket_id": ticket,
+                "plan_id": entry.plan_id,
+                "decision": entry.decision,
+                "ok": result.is_ok(),
+                "result": result.as_ref().ok(),
+                "error": result.as_ref().err(),
+            }),
+        );
+    });
+
+    Ok(json!({
+        "ticket_id": ticket_id,
+        "delay_seconds": delay,
+        "fires_at": fires_at,
+    }))
+}
+
+/// Cancels a scheduled decision before it is sent.
+#[tauri::command]
+pub fn cancel_plan_decision(
+    app: AppHandle,
+    scheduled: State<'_, ScheduledDecisions>,
+    ticket_id: String,
+) -> Result<Value, String> {
+    let entry = scheduled.take(&ticket_id).ok_or_else(|| {
+        ErrorCode::NotFound.with(format!(
+            "Decision {} was already sent or cancelled",
+            ticket_id
+        ))
+    })?;
+    let out = json!({
+        "ticket_id": ticket_id,
+        "plan_id": entry.plan_id,
+        "decision": entry.decision,
+    });
+    let _ = environment::emit(&app, CANCELLED_EVENT, &out);
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn list_scheduled_decisions(scheduled: State<'_, ScheduledDecisions>) -> Result<Value, String> {
+    let pending = scheduled
+        .pending
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Decision state poisoned"))?;
+    let items: Vec<Value> = pending
+        .iter()
+        .map(|(ticket_id, entry)| {
+            json!({
+                "ticket_id": ticket_id,
+                "plan_id": entry.plan_id,
+                "decision": entry.decision,
+                "fires_at": entry.fires_at,
+            })
+        })
+        .collect();
+    Ok(json!({ "scheduled": items }))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::*;
+    use crate::core_path;
+
+    #[test]
+    fn decision_routes_name_the_plan_and_the_decision() {
+        let route = |method: &Method, path: &'static str| decision_route(method, path);
+        assert!(matches!(
+            route(&Method::POST, "/plans/p-1/approve"),
+            Some(("p-1", Route::Approve))
+        ));
+        assert!(matches!(
+            route(&Method::POST, "/plans/p-1/approve_async?execute=1"),
+            Some(("p-1", Route::Approve))
+        ));
+        assert!(matches!(
+            route(&Method::POST, "/plans/p-1/reject"),
+            Some(("p-1", Route::Reject))
+        ));
+        assert!(matches!(
+            route(&Method::POST, "/plans/p-1/retry_failed"),
+            Some(("p-1", Route::Retry))
+        ));
+        assert!(matches!(
+            route(&Method::POST, "/plans/p-1/steps/3/retry"),
+            Some(("p-1", Route::Retry))
+        ));
+    }
+
+    #[test]
+    fn other_routes_are_not_decisions() {
+        assert!(decision_route(&Method::GET, "/plans/p-1/approve").is_none());
+        assert!(decision_route(&Method::POST, "/plans/p-1").is_none());
+        assert!(decision_route(&Method::POST, "/plans//approve").is_none());
+        assert!(decision_route(&Method::POST, "/plans/p-1/revise").is_none());
+        assert!(decision_route(&Method::POST, "/jobs/j-1/cancel").is_none());
+    }
+
+    #[test]
+    fn the_gates_own_conflicts_are_told_apart_from_the_cores() {
+        assert!(is_conflict(&conflict(json!({ "id": "p-1", "version": 3 }))));
+        assert!(!is_conflict(
+            &ErrorCode::PlanConflict.with("Core API 409: version mismatch")
+        ));
+        assert!(!is_conflict(
+            &ErrorCode::CoreError.with(json!({ "plan": {} }))
+        ));
+    }
+
+    #[test]
+    fn decisions_are_routed_on_the_path_the_core_sees() {
+        for path in [
+            "/plans/x/../p-1/approve",
+            "/plans/p-1/./approve",
+            "/foo/%2e%2e/plans/p-1/approve",
+            "/plans/p-1/approve#x",
+        ] {
+            assert!(core_path::check(path).is_err(), "{}", path);
+        }
+        let url = Url::parse("https://core.example/api/t/acme/plans/p-1/approve").unwrap();
+        let root = Url::parse("https://core.example/api/t/acme/").unwrap();
+        let route = core_path::canonical(&url, &root).unwrap();
+        assert!(matches!(
+            decision_route(&Method::POST, &route),
+            Some(("p-1", Route::Approve))
+        ));
+    }
+}
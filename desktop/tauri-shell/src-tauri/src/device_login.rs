@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use reqwest::Url;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{DeviceLoginSettings, SettingsStore};
+use crate::{environment, net, secrets};
+
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// RFC 8628 §3.2: poll every 5s unless the server says otherwise.
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const SLOW_DOWN_STEP_SECS: u64 = 5;
+const DEFAULT_EXPIRES_SECS: u64 = 600;
+const COMPLETE_EVENT: &str = "device_login:complete";
+const FAILED_EVENT: &str = "device_login:failed";
+
+/// The flow in progress per profile, by number so a restarted or cancelled
+/// flow's poller stops on its next tick.
+static FLOWS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+static NEXT_FLOW: AtomicU64 = AtomicU64::new(1);
+
+fn flows() -> &'static Mutex<HashMap<String, u64>> {
+    FLOWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn current(profile_id: &str, flow: u64) -> bool {
+    flows()
+        .lock()
+        .map(|f| f.get(profile_id) == Some(&flow))
+        .unwrap_or(false)
+}
+
+pub fn validate(login: &DeviceLoginSettings) -> Result<(), String> {
+    let endpoints = [
+        ("issuer", &login.issuer),
+        (
+            "device authorization endpoint",
+            &login.device_authorization_endpoint,
+        ),
+        ("token endpoint", &login.token_endpoint),
+    ];
+    for (label, value) in endpoints {
+        if value.trim().is_empty() {
+            continue;
+        }
+        let parsed = Url::parse(value.trim()).map_err(|e| format!("Invalid {}: {}", label, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("Device login {} must be http(s)", label));
+        }
+    }
+    let configured = login != &DeviceLoginSettings::default();
+    let has_endpoints = !login.issuer.trim().is_empty()
+        || (!login.device_authorization_endpoint.trim().is_empty()
+            && !login.token_endpoint.trim().is_empty());
+    if configured && (login.client_id.trim().is_empty() || !has_endpoints) {
+        return Err("Device login needs a client id and an issuer or both endpoints".to_string());
+    }
+    Ok(())
+}
+
+fn parse_url(raw: &str) -> Result<Url, String> {
+    Url::parse(raw.trim())
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid login URL: {}", e)))
+}
+
+/// Sends a form POST to the identity provider. OAuth errors come back as
+/// the body (with `error`) rather than `Err`, since polling reads them.
+async fn post_form(url: &str, form: &[(&str, &str)]) -> Result<Value, String> {
+    let url = parse_url(url)?;
+    let response = net::client_for(&url)?
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header("Accept", "application/json")
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| {
+            ErrorCode::CoreUnreachable.with(format!("Identity provider request failed: {}", e))
+        })?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() && body["error"].as_str().is_none() {
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "Identity provider {}: {}",
+            status.as_u16(),
+            body
+        )));
+    }
+    Ok(body)
+}
+
+/// The device and token endpoints, from settings or OIDC discovery.
+async fn endpoints(login: &DeviceLoginSettings) -> Result<(String, String), String> {
+    let device = login.device_authorization_endpoint.trim();
+    let token = login.token_endpoint.trim();
+    if !device.is_empty() && !token.is_empty() {
+        return Ok((device.to_string(), token.to_string()));
+    }
+    let url = parse_url(&format!(
+        "{}/.well-known/openid-configuration",
+        login.issuer.trim().trim_end_matches('/')
+    ))?;
+    let config: Value = net::client_for(&url)?
+        .get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("OIDC discovery failed: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| ErrorCode::CoreError.with(format!("OIDC discovery failed: {}", e)))?;
+    let pick = |configured: &str, key: &str| match configured {
+        "" => config[key].as_str().map(str::to_string),
+        value => Some(value.to_string()),
+    };
+    match (
+        pick(device, "device_authorization_endpoint"),
+        pick(token, "token_endpoint"),
+    ) {
+        (Some(device), Some(token)) => Ok((device, token)),
+        _ => Err(ErrorCode::InvalidInput
+            .with("The issuer does not advertise a device authorization endpoint")),
+    }
+}
+
+/// Starts a device login: returns the code and URL to show the operator
+/// (they finish signing in on another device), then polls the token
+/// endpoint in the background. Success stores the access token as the
+/// profile's bearer token and emits `device_login:complete`; denial or
+/// expiry emits `device_login:failed`. Refresh tokens are not kept, so an
+/// expired session is renewed by signing in again.
+#[tauri::command]
+pub async fn start_device_login(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let profile = settings
+        .profile(&profile_id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    let login = profile.device_login.clone();
+    if login.client_id.trim().is_empty() {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Profile {} has no device login configured",
+            profile_id
+        )));
+    }
+    let (device_endpoint, token_endpoint) = endpoints(&login).await?;
+
+    let mut form = vec![("client_id", login.client_id.trim())];
+    if !login.scope.trim().is_empty() {
+        form.push(("scope", login.scope.trim()));
+    }
+    if !login.audience.trim().is_empty() {
+        form.push(("audience", login.audience.trim()));
+    }
+    let grant = post_form(&device_endpoint, &form).await?;
+    if let Some(error) = grant["error"].as_str() {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Device authorization refused: {} {}",
+            error,
+            grant["error_description"].as_str().unwrap_or_default()
+        )));
+    }
+    let (Some(device_code), Some(user_code)) =
+        (grant["device_code"].as_str(), grant["user_code"].as_str())
+    else {
+        return Err(ErrorCode::CoreError.with("Device authorization returned no code"));
+    };
+    // Google answers with `verification_url`.
+    let verification_uri = grant["verification_uri"]
+        .as_str()
+        .or_else(|| grant["verification_url"].as_str())
+        .unwrap_or_default()
+        .to_string();
+    let interval = grant["interval"]
+        .as_u64()
+        .unwrap_or(DEFAULT_INTERVAL_SECS)
+        .max(1);
+    let expires_in = grant["expires_in"].as_u64().unwrap_or(DEFAULT_EXPIRES_SECS);
+
+    let flow = NEXT_FLOW.fetch_add(1, Ordering::Relaxed);
+    flows()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Device login state poisoned"))?
+        .insert(profile_id.clone(), flow);
+    tauri::async_runtime::spawn(poll(
+        app,
+        profile_id.clone(),
+        flow,
+        token_endpoint,
+        login.client_id.trim().to_string(),
+        device_code.to_string(),
+        interval,
+        expires_in,
+    ));
+
+    Ok(json!({
+        "profile_id": profile_id,
+        "user_code": user_code,
+        "verification_uri": verification_uri,
+        "verification_uri_complete": grant["verification_uri_complete"],
+        "expires_in_seconds": expires_in,
+        "interval_seconds": interval,
+    }))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn poll(
+    app: AppHandle,
+    profile_id: String,
+    flow: u64,
+    token_endpoint: String,
+    client_id: String,
+    device_code: String,
+    mut interval: u64,
+    expires_in: u64,
+) {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(expires_in);
+    let outcome = loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        if !current(&profile_id, flow) {
+            return;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            break Err("expired_token".to_string());
+        }
+        let form = [
+            ("grant_type", GRANT_TYPE),
+            ("device_code", device_code.as_str()),
+            ("client_id", client_id.as_str()),
+        ];
+        // Transport errors are retried until the code expires.
+        let Ok(body) = post_form(&token_endpoint, &form).await else {
+            continue;
+        };
+        match body["error"].as_str() {
+            None => match body["access_token"].as_str() {
+                Some(token) => break Ok((token.to_string(), body)),
+                None => break Err("no access_token in token response".to_string()),
+            },
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += SLOW_DOWN_STEP_SECS,
+            Some(error) => {
+                break Err(match body["error_description"].as_str() {
+                    Some(description) => format!("{}: {}", error, description),
+                    None => error.to_string(),
+                })
+            }
+        }
+    };
+    if !current(&profile_id, flow) {
+        return;
+    }
+    if let Ok(mut flows) = flows().lock() {
+        flows.remove(&profile_id);
+    }
+
+    let result = outcome.and_then(|(token, body)| {
+        secrets::set(&secrets::profile_token_key(&profile_id), &token)?;
+        Ok(body)
+    });
+    match result {
+        Ok(body) => {
+            if let Ok(conn) = app.state::<LocalDb>().lock() {
+                let _ = db::record_audit(
+                    &conn,
+                    &profile_id,
+                    "auth",
+                    "device_login",
+                    &profile_id,
+                    &json!({ "scope": body["scope"], "expires_in": body["expires_in"] }),
+                );
+            }
+            let _ = environment::emit(
+                &app,
+                COMPLETE_EVENT,
+                json!({
+                    "profile_id": profile_id,
+                    "scope": body["scope"],
+                    "expires_in_seconds": body["expires_in"],
+                }),
+            );
+        }
+        Err(reason) => {
+            let _ = environment::emit(
+                &app,
+                FAILED_EVENT,
+                json!({ "profile_id": profile_id, "reason": reason }),
+            );
+        }
+    }
+}
+
+/// Stops polling for the profile's device login, if one is running.
+#[tauri::command]
+pub fn cancel_device_login(profile_id: String) -> Result<Value, String> {
+    let cancelled = flows()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Device login state poisoned"))?
+        .remove(&profile_id)
+        .is_some();
+    Ok(json!({ "profile_id": profile_id, "cancelled": cancelled }))
+}
@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{Delivery, DigestSettings, SettingsStore};
+use crate::{notify, tasks};
+
+const TASK: &str = "digest";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_INTERVAL_MINUTES: u32 = 24 * 60;
+const BATCH_LIMIT: usize = 500;
+/// The kind digests are delivered as; never batched itself.
+pub const DIGEST_KIND: &str = "digest";
+
+/// Non-urgent notifications waiting for their class's next summary, by
+/// profile and kind.
+#[derive(Default)]
+pub struct ActivityDigest {
+    batches: Mutex<HashMap<(String, String), Batch>>,
+}
+
+struct Batch {
+    started: Instant,
+    interval: Duration,
+    /// Count per notification title, which carries the event, not its
+    /// subject ("Scheduled approval", "Plan approval expired", ...).
+    titles: BTreeMap<String, usize>,
+    total: usize,
+    items: Vec<Value>,
+}
+
+pub fn validate(settings: &DigestSettings) -> Result<(), String> {
+    for (class, minutes) in &settings.classes {
+        if class.trim().is_empty() || class == DIGEST_KIND {
+            return Err(format!(
+                "Digest class {:?} is not a notification kind",
+                class
+            ));
+        }
+        if *minutes == 0 || *minutes > MAX_INTERVAL_MINUTES {
+            return Err(format!(
+                "Digest interval for {} must be 1 to {} minutes",
+                class, MAX_INTERVAL_MINUTES
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The digest interval for `kind`, if its class is batched.
+fn interval_for(settings: &DigestSettings, kind: &str) -> Option<Duration> {
+    if kind == DIGEST_KIND {
+        return None;
+    }
+    settings
+        .classes
+        .get(kind)
+        .or_else(|| settings.classes.get("*"))
+        .map(|minutes| Duration::from_secs(u64::from(*minutes) * 60))
+}
+
+/// Events the operator is asked to act on, or at critical risk, are never
+/// held for a digest.
+fn urgent(delivery: Delivery, data: &Value) -> bool {
+    delivery == Delivery::Actionable
+        || ["risk", "risk_level"].iter().any(|k| {
+            data[*k]
+                .as_str()
+                .is_some_and(|r| r.eq_ignore_ascii_case("critical"))
+        })
+}
+
+/// Adds the notification to its profile's batch for its class when that
+/// class is digested and the event is not urgent. Returns whether it was
+/// batched, in which case the caller only badges it.
+#[allow(clippy::too_many_arguments)]
+pub fn batch(
+    app: &AppHandle,
+    profile_id: &str,
+    settings: &DigestSettings,
+    delivery: Delivery,
+    kind: &str,
+    title: &str,
+    body: &str,
+    data: &Value,
+) -> bool {
+    let Some(interval) = interval_for(settings, kind) else {
+        return false;
+    };
+    if urgent(delivery, data) {
+        return false;
+    }
+    let Some(digest) = app.try_state::<ActivityDigest>() else {
+        return false;
+    };
+    let Ok(mut batches) = digest.batches.lock() else {
+        return false;
+    };
+    let key = (profile_id.to_string(), kind.to_string());
+    let batch = batches.entry(key).or_insert_with(|| Batch {
+        started: Instant::now(),
+        interval,
+        titles: BTreeMap::new(),
+        total: 0,
+        items: Vec::new(),
+    });
+    batch.interval = interval;
+    *batch.titles.entry(title.to_string()).or_default() += 1;
+    batch.total += 1;
+    if batch.items.len() < BATCH_LIMIT {
+        batch.items.push(json!({
+            "kind": kind,
+            "title": title,
+            "body": body,
+            "at": chrono::Utc::now().to_rfc3339(),
+        }));
+    }
+    true
+}
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            flush(&app, false);
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Sends one summary per profile for every batch that is due, or for all
+/// of them with `force`. Returns how many notifications were summarised.
+fn flush(app: &AppHandle, force: bool) -> usize {
+    let digest = app.state::<ActivityDigest>();
+    let mut due: BTreeMap<String, Vec<Batch>> = BTreeMap::new();
+    match digest.batches.lock() {
+        Ok(mut batches) => {
+            let keys: Vec<(String, String)> = batches
+                .iter()
+                .filter(|(_, b)| force || b.started.elapsed() >= b.interval)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in keys {
+                if let Some(batch) = batches.remove(&key) {
+                    due.entry(key.0).or_default().push(batch);
+                }
+            }
+        }
+        Err(_) => return 0,
+    }
+    due.into_iter()
+        .map(|(profile_id, batches)| summarise(app, &profile_id, batches))
+        .sum()
+}
+
+fn summarise(app: &AppHandle, profile_id: &str, due: Vec<Batch>) -> usize {
+    let mut titles: BTreeMap<String, usize> = BTreeMap::new();
+    let mut items = Vec::new();
+    let mut total = 0;
+    for batch in due {
+        for (title, count) in batch.titles {
+            *titles.entry(title).or_default() += count;
+        }
+        total += batch.total;
+        items.extend(batch.items);
+    }
+    let mut counted: Vec<(String, usize)> = titles.into_iter().collect();
+    counted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let body = counted
+        .iter()
+        .map(|(title, count)| format!("{} × {}", count, title))
+        .collect::<Vec<_>>()
+        .join(", ");
+    notify::notify(
+        app,
+        DIGEST_KIND,
+        &format!("Activity digest: {} notifications", total),
+        &body,
+        json!({ "profile_id": profile_id, "total": total, "counts": counted.iter().map(|(t, c)| json!({ "title": t, "count": c })).collect::<Vec<_>>(), "notifications": items }),
+    );
+    total
+}
+
+/// The batched classes and what each holds until its next summary.
+#[tauri::command]
+pub fn get_digest_status(
+    store: State<'_, SettingsStore>,
+    digest: State<'_, ActivityDigest>,
+) -> Result<Value, String> {
+    let batches = digest
+        .batches
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Digest state poisoned"))?;
+    let pending: Vec<Value> = batches
+        .iter()
+        .map(|((profile_id, kind), b)| {
+            json!({
+                "profile_id": profile_id,
+                "kind": kind,
+                "count": b.total,
+                "next_in_seconds": b.interval.saturating_sub(b.started.elapsed()).as_secs(),
+            })
+        })
+        .collect();
+    Ok(json!({
+        "digest": store.snapshot().notifications.digest,
+        "pending": pending,
+    }))
+}
+
+/// Batches the notification kind `class` (or `*` for every other kind)
+/// into a summary every `minutes`; `None` delivers it as it happens again,
+/// sending every pending summary first. With `profile` set this changes
+/// that profile's own classes, which start as a copy of the global ones.
+#[tauri::command]
+pub fn set_digest_interval(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    class: String,
+    minutes: Option<u32>,
+) -> Result<Value, String> {
+    let class = class.trim().to_string();
+    let updated = store.update(&app, |settings| {
+        let global = settings.notifications.digest.clone();
+        let digest = match &profile {
+            Some(id) => settings
+                .profiles
+                .iter_mut()
+                .find(|p| p.id == *id)
+                .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", id)))?
+                .notifications
+                .digest
+                .get_or_insert(global),
+            None => &mut settings.notifications.digest,
+        };
+        match minutes {
+            Some(minutes) => digest.classes.insert(class.clone(), minutes),
+            None => digest.classes.remove(&class),
+        };
+        Ok(())
+    })?;
+    if minutes.is_none() {
+        flush(&app, true);
+    }
+    let digest = match &profile {
+        Some(id) => updated
+            .profile(id)
+            .and_then(|p| p.notifications.digest.clone())
+            .unwrap_or_default(),
+        None => updated.notifications.digest,
+    };
+    Ok(json!({ "profile_id": profile, "digest": digest }))
+}
+
+/// Sends the pending summary now instead of at the next interval.
+#[tauri::command]
+pub fn flush_digest(app: AppHandle) -> Result<Value, String> {
+    Ok(json!({ "summarised": flush(&app, true) }))
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::net;
+use crate::session_recording;
+use crate::settings::{EnvironmentClass, Profile, SettingsStore};
+
+/// A confirmed action stays armed this long, enough to outlast an undo
+/// window, and is used up by the request it was confirmed for.
+const ARMED_FOR: Duration = Duration::from_secs(2 * 60);
+const ACTIONS: &[&str] = &["execute", "rollback"];
+
+#[derive(Default)]
+struct Classes {
+    by_profile: HashMap<String, EnvironmentClass>,
+    /// Origins of every endpoint, to the profile they belong to.
+    by_origin: HashMap<String, String>,
+    active: String,
+}
+
+static CLASSES: OnceLock<Mutex<Classes>> = OnceLock::new();
+/// Confirmed actions, keyed by `profile_id action target`.
+static ARMED: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+fn classes() -> &'static Mutex<Classes> {
+    CLASSES.get_or_init(|| Mutex::new(Classes::default()))
+}
+
+fn armed() -> &'static Mutex<HashMap<String, Instant>> {
+    ARMED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn configure(profiles: &[Profile], active: &str) {
+    let mut next = Classes {
+        active: active.to_string(),
+        ..Classes::default()
+    };
+    for profile in profiles {
+        next.by_profile
+            .insert(profile.id.clone(), profile.environment);
+        for origin in profile.endpoints().filter_map(net::base_origin) {
+            next.by_origin.insert(origin, profile.id.clone());
+        }
+    }
+    *classes().lock().unwrap_or_else(|e| e.into_inner()) = next;
+}
+
+/// The phrase an operator types to confirm `action` on `profile_id`.
+pub fn phrase(action: &str, profile_id: &str) -> String {
+    format!("{} {}", action, profile_id)
+}
+
+fn armed_key(profile_id: &str, action: &str, plan_id: Option<&str>) -> String {
+    format!("{} {} {}", profile_id, action, plan_id.unwrap_or("*"))
+}
+
+/// The interlocked action a core request would take, with the plan it
+/// acts on: approving with `execute`, or undo that executes the plan's
+/// undo actions. Marking a plan undone changes nothing, so it is left
+/// alone.
+fn action_of<'a>(
+    method: &Method,
+    path: &'a str,
+    payload: Option<&Value>,
+) -> Option<(&'static str, Option<&'a str>)> {
+    if *method != Method::POST {
+        return None;
+    }
+    let executes = payload.is_some_and(|p| p["execute"].as_bool() == Some(true));
+    if !executes {
+        return None;
+    }
+    let path = path.split('?').next().unwrap_or_default();
+    if path == "/undo" {
+        return Some(("rollback", None));
+    }
+    let (plan_id, action) = path.strip_prefix("/plans/")?.split_once('/')?;
+    match action {
+        "approve" | "approve_async" => Some(("execute", Some(plan_id))),
+        "undo" => Some(("rollback", Some(plan_id))),
+        _ => None,
+    }
+}
+
+/// The prod profile whose endpoint `base_url` is, if any.
+pub fn prod_profile(base_url: &str) -> Option<String> {
+    let origin = net::base_origin(base_url)?;
+    let classes = classes().lock().ok()?;
+    let profile_id = classes.by_origin.get(&origin)?;
+    (classes.by_profile.get(profile_id) == Some(&EnvironmentClass::Prod))
+        .then(|| profile_id.clone())
+}
+
+/// Refuses execute-on-approve and rollback against a prod profile's core
+/// unless the operator has confirmed that very action with its phrase.
+/// `consume` uses the confirmation up; without it the check only looks,
+/// for callers that send the request later.
+pub fn guard(
+    method: &Method,
+    base_url: &str,
+    path: &str,
+    payload: Option<&Value>,
+    consume: bool,
+) -> Result<(), String> {
+    let Some((action, plan_id)) = action_of(method, path, payload) else {
+        return Ok(());
+    };
+    let Some(profile_id) = prod_profile(base_url) else {
+        return Ok(());
+    };
+    let key = armed_key(&profile_id, action, plan_id);
+    let confirmed = armed().lock().is_ok_and(|mut armed| {
+        armed.retain(|_, at| at.elapsed() < ARMED_FOR);
+        if consume {
+            armed.remove(&key).is_some()
+        } else {
+            armed.contains_key(&key)
+        }
+    });
+    if confirmed {
+        return Ok(());
+    }
+    Err(ErrorCode::ConfirmationRequired.with(json!({
+        "message": format!(
+            "{} is a production profile; type the confirmation phrase to {}",
+            profile_id,
+            if action == "execute" { "execute this plan" } else { "roll back" }
+        ),
+        "profile_id": profile_id,
+        "action": action,
+        "plan_id": plan_id,
+        "phrase": phrase(action, &profile_id),
+        "expires_in_seconds": ARMED_FOR.as_secs(),
+    })))
+}
+
+fn class_for(classes: &Classes, profile_id: Option<&str>) -> (String, EnvironmentClass) {
+    let profile_id = profile_id
+        .filter(|id| classes.by_profile.contains_key(*id))
+        .unwrap_or(&classes.active)
+        .to_string();
+    let class = classes
+        .by_profile
+        .get(&profile_id)
+        .copied()
+        .unwrap_or_default();
+    (profile_id, class)
+}
+
+/// Emits `event` to the webview with the environment of the profile it
+/// concerns (its `profile_id`, or the active profile) added as
+/// `environment`, so every view can show which environment it is about.
+/// Payloads that are not objects are sent as they are.
+pub fn emit<S: Serialize>(app: &AppHandle, event: &str, payload: S) -> tauri::Result<()> {
+    let mut payload = serde_json::to_value(payload)?;
+    if let Value::Object(body) = &mut payload {
+        if !body.contains_key("environment") {
+            if let Ok(classes) = classes().lock() {
+                let (_, class) =
+                    class_for(&classes, body.get("profile_id").and_then(Value::as_str));
+                body.insert("environment".to_string(), json!(class));
+            }
+        }
+    }
+    session_recording::event(event, &payload);
+    app.emit(event, payload)
+}
+
+/// The profile's environment class and, for prod, the phrases that confirm
+/// its interlocked actions.
+#[tauri::command]
+pub fn get_environment(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let class = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .map(|p| p.environment)
+        .unwrap_or_default();
+    let phrases = (class == EnvironmentClass::Prod).then(|| {
+        ACTIONS
+            .iter()
+            .map(|a| (a.to_string(), json!(phrase(a, &conn.profile_id))))
+            .collect::<serde_json::Map<_, _>>()
+    });
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "environment": class,
+        "interlocked": class == EnvironmentClass::Prod,
+        "phrases": phrases,
+    }))
+}
+
+/// Confirms `action` (`execute` or `rollback`) on a prod profile with its
+/// typed `phrase`, for `plan_id` or, for rollback, every plan (`/undo`).
+/// The next matching request within two minutes goes through.
+#[tauri::command]
+pub fn confirm_production_action(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    action: String,
+    plan_id: Option<String>,
+    phrase: String,
+) -> Result<Value, String> {
+    let action = action.trim().to_ascii_lowercase();
+    let Some(action) = ACTIONS.iter().find(|a| **a == action) else {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Unknown action: {} (use execute or rollback)",
+            action
+        )));
+    };
+    let conn = store.connection(profile.as_deref())?;
+    let class = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .map(|p| p.environment)
+        .unwrap_or_default();
+    if class != EnvironmentClass::Prod {
+        return Ok(json!({ "profile_id": conn.profile_id, "action": action, "required": false }));
+    }
+    let plan_id = plan_id
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    if *action == "execute" && plan_id.is_none() {
+        return Err(ErrorCode::InvalidInput.with("Confirming execute needs the plan id"));
+    }
+    let expected = self::phrase(action, &conn.profile_id);
+    let matched = phrase.trim() == expected;
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "interlock",
+        if matched {
+            "confirmed"
+        } else {
+            "phrase_mismatch"
+        },
+        plan_id.as_deref().unwrap_or_default(),
+        &json!({ "action": action }),
+    );
+    if !matched {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Confirmation phrase does not match; type {:?}",
+            expected
+        )));
+    }
+    armed()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Interlock state poisoned"))?
+        .insert(
+            armed_key(&conn.profile_id, action, plan_id.as_deref()),
+            Instant::now(),
+        );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "action": action,
+        "plan_id": plan_id,
+        "required": true,
+        "expires_in_seconds": ARMED_FOR.as_secs(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::*;
+    use crate::core_path;
+
+    fn execute() -> Option<Value> {
+        Some(json!({ "execute": true }))
+    }
+
+    #[test]
+    fn executing_and_undoing_plans_are_interlocked() {
+        let action = |path| action_of(&Method::POST, path, execute().as_ref());
+        assert_eq!(action("/plans/p-1/approve"), Some(("execute", Some("p-1"))));
+        assert_eq!(
+            action("/plans/p-1/approve_async?x=1"),
+            Some(("execute", Some("p-1")))
+        );
+        assert_eq!(action("/plans/p-1/undo"), Some(("rollback", Some("p-1"))));
+        assert_eq!(action("/undo"), Some(("rollback", None)));
+        assert_eq!(action("/plans/p-1/reject"), None);
+        assert_eq!(action_of(&Method::POST, "/plans/p-1/approve", None), None);
+    }
+
+    #[test]
+    fn actions_are_found_on_the_path_the_core_sees() {
+        for path in [
+            "/plans/x/../p-1/approve",
+            "/plans/p-1/./approve",
+            "/foo/%2e%2e/plans/p-1/approve",
+            "/foo/%2e%2e/undo",
+            "/plans/p-1/approve#x",
+        ] {
+            assert!(core_path::check(path).is_err(), "{}", path);
+        }
+        let url = Url::parse("https://core.example/api/plans/p-1/approve").unwrap();
+        let root = Url::parse("https://core.example/api/").unwrap();
+        let route = core_path::canonical(&url, &root).unwrap();
+        assert_eq!(
+            action_of(&Method::POST, &route, execute().as_ref()),
+            Some(("execute", Some("p-1")))
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::Display;
+
+use serde_json::{json, Value};
+
+/// Machine-readable error codes. Command errors are strings of the form
+/// `CODE: detail` so the frontend can map the code to a localized message and
+/// recovery action while still showing the detail as-is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    AuthExpired,
+    Forbidden,
+    /// Refused by the shell itself before reaching the core.
+    ForbiddenLocal,
+    /// The request's target (user info in the URL, a link-local or metadata
+    /// address, too many redirects) was refused before anything was sent.
+    RequestTargetBlocked,
+    NotFound,
+    PlanConflict,
+    RateLimited,
+    CoreUnreachable,
+    /// The connection could not be made within the profile's TLS policy:
+    /// the core offers no allowed protocol version or cipher suite.
+    TlsPolicy,
+    CoreTimeout,
+    CoreError,
+    /// The core answered 503; polling is suspended until it is back.
+    CoreMaintenance,
+    /// Execute-on-approve fell in an execution window; retry with the
+    /// override confirmation from the detail, or approve without executing.
+    ExecutionWindow,
+    /// A critical plan needs a second operator's confirmation code first.
+    TwoPersonRequired,
+    /// The profile's policy wants a justification for this decision; repeat
+    /// it with one, or a reason template, from the detail.
+    JustificationRequired,
+    /// Execute-on-approve or rollback against a prod profile; repeat it after
+    /// `confirm_production_action` with the phrase from the detail.
+    ConfirmationRequired,
+    /// The shell is draining before exit and takes no new changes.
+    ShuttingDown,
+    InvalidInput,
+    LocalStorage,
+    KeyringUnavailable,
+    LocalIo,
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::AuthExpired,
+        ErrorCode::Forbidden,
+        ErrorCode::ForbiddenLocal,
+        ErrorCode::RequestTargetBlocked,
+        ErrorCode::NotFound,
+        ErrorCode::PlanConflict,
+        ErrorCode::RateLimited,
+        ErrorCode::CoreUnreachable,
+        ErrorCode::TlsPolicy,
+        ErrorCode::CoreTimeout,
+        ErrorCode::CoreError,
+        ErrorCode::CoreMaintenance,
+        ErrorCode::ExecutionWindow,
+        ErrorCode::TwoPersonRequired,
+        ErrorCode::JustificationRequired,
+        ErrorCode::ConfirmationRequired,
+        ErrorCode::ShuttingDown,
+        ErrorCode::InvalidInput,
+        ErrorCode::LocalStorage,
+        ErrorCode::KeyringUnavailable,
+        ErrorCode::LocalIo,
+        ErrorCode::Internal,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::AuthExpired => "AUTH_EXPIRED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::ForbiddenLocal => "FORBIDDEN_LOCAL",
+            ErrorCode::RequestTargetBlocked => "REQUEST_TARGET_BLOCKED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::PlanConflict => "PLAN_CONFLICT",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::CoreUnreachable => "CORE_UNREACHABLE",
+            ErrorCode::TlsPolicy => "TLS_POLICY",
+            ErrorCode::CoreTimeout => "CORE_TIMEOUT",
+            ErrorCode::CoreError => "CORE_ERROR",
+            ErrorCode::CoreMaintenance => "CORE_MAINTENANCE",
+            ErrorCode::ExecutionWindow => "EXECUTION_WINDOW",
+            ErrorCode::TwoPersonRequired => "TWO_PERSON_REQUIRED",
+            ErrorCode::JustificationRequired => "JUSTIFICATION_REQUIRED",
+            ErrorCode::ConfirmationRequired => "CONFIRMATION_REQUIRED",
+            ErrorCode::ShuttingDown => "SHUTTING_DOWN",
+            ErrorCode::InvalidInput => "INVALID_INPUT",
+            ErrorCode::LocalStorage => "LOCAL_STORAGE",
+            ErrorCode::KeyringUnavailable => "KEYRING_UNAVAILABLE",
+            ErrorCode::LocalIo => "LOCAL_IO",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+
+    /// The recovery action the UI should offer.
+    pub fn recovery(self) -> &'static str {
+        match self {
+            ErrorCode::AuthExpired => "reauthenticate",
+            ErrorCode::Forbidden | ErrorCode::ForbiddenLocal => "check_permissions",
+            ErrorCode::NotFound | ErrorCode::PlanConflict => "refresh",
+            ErrorCode::RateLimited | ErrorCode::CoreTimeout | ErrorCode::CoreError => "retry",
+            ErrorCode::CoreUnreachable => "check_connection",
+            ErrorCode::TlsPolicy => "check_tls_policy",
+            ErrorCode::RequestTargetBlocked => "check_base_url",
+            ErrorCode::CoreMaintenance | ErrorCode::ShuttingDown => "wait",
+            ErrorCode::ExecutionWindow => "confirm_override",
+            ErrorCode::TwoPersonRequired => "confirm_second_approver",
+            ErrorCode::JustificationRequired => "enter_justification",
+            ErrorCode::ConfirmationRequired => "confirm_phrase",
+            ErrorCode::InvalidInput => "fix_input",
+            ErrorCode::LocalStorage | ErrorCode::KeyringUnavailable | ErrorCode::LocalIo => {
+                "check_local_setup"
+            }
+            ErrorCode::Internal => "report",
+        }
+    }
+
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::RateLimited
+                | ErrorCode::CoreUnreachable
+                | ErrorCode::CoreTimeout
+                | ErrorCode::CoreError
+        )
+    }
+
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 => ErrorCode::AuthExpired,
+            403 => ErrorCode::Forbidden,
+            404 | 410 => ErrorCode::NotFound,
+            409 | 412 => ErrorCode::PlanConflict,
+            400 | 422 => ErrorCode::InvalidInput,
+            408 | 504 => ErrorCode::CoreTimeout,
+            429 => ErrorCode::RateLimited,
+            503 => ErrorCode::CoreMaintenance,
+            _ => ErrorCode::CoreError,
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_str() == code)
+    }
+
+    pub fn with(self, detail: impl Display) -> String {
+        format!("{}: {}", self.as_str(), detail)
+    }
+}
+
+/// Extracts the code from a `CODE: detail` error string.
+pub fn code_of(message: &str) -> Option<ErrorCode> {
+    ErrorCode::parse(message.split_once(": ")?.0)
+}
+
+/// The detail of a `CODE: detail` error string; all of it when it has no
+/// code.
+pub fn detail_of(message: &str) -> &str {
+    match message.split_once(": ") {
+        Some((code, detail)) if ErrorCode::parse(code).is_some() => detail,
+        _ => message,
+    }
+}
+
+#[tauri::command]
+pub fn get_error_catalog() -> Value {
+    let codes: Vec<Value> = ErrorCode::ALL
+        .iter()
+        .map(|code| {
+            json!({
+                "code": code.as_str(),
+                "recovery": code.recovery(),
+                "retryable": code.retryable(),
+            })
+        })
+        .collect();
+    json!({ "format": "CODE: detail", "codes": codes })
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc, Weekday};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::settings::{ExecutionWindow, Profile, SettingsStore, WindowAction};
+use crate::{calendar, crypto, net, quiet};
+
+const CONFIRM_WINDOW: Duration = Duration::from_secs(60);
+const DAYS: &[(&str, Weekday)] = &[
+    ("mon", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("sun", Weekday::Sun),
+];
+
+/// Execution windows per core origin, refreshed from the profiles.
+static WINDOWS: OnceLock<Mutex<HashMap<String, Vec<ExecutionWindow>>>> = OnceLock::new();
+
+/// Override codes handed out while a window is active, keyed by
+/// `origin plan_id`. A code is single-use.
+static OVERRIDES: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+
+fn windows() -> &'static Mutex<HashMap<String, Vec<ExecutionWindow>>> {
+    WINDOWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn overrides() -> &'static Mutex<HashMap<String, (String, Instant)>> {
+    OVERRIDES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn configure(profiles: &[Profile]) {
+    let map = profiles
+        .iter()
+        .filter(|p| !p.execution_windows.is_empty())
+        .flat_map(|p| {
+            p.endpoints()
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, p.execution_windows.clone()))
+        })
+        .collect();
+    *windows().lock().unwrap_or_else(|e| e.into_inner()) = map;
+}
+
+pub fn validate(windows: &[ExecutionWindow]) -> Result<(), String> {
+    for window in windows {
+        let label = if window.name.is_empty() {
+            "execution window"
+        } else {
+            window.name.as_str()
+        };
+        let start = quiet::parse_time(&window.start)
+            .map_err(|_| format!("{}: start must be HH:MM, got {:?}", label, window.start))?;
+        let end = quiet::parse_time(&window.end)
+            .map_err(|_| format!("{}: end must be HH:MM, got {:?}", label, window.end))?;
+        if start == end {
+            return Err(format!("{}: start and end must differ", label));
+        }
+        if let Some(day) = window.days.iter().find(|d| weekday(d).is_none()) {
+            return Err(format!("{}: unknown day {:?} (use mon..sun)", label, day));
+        }
+    }
+    Ok(())
+}
+
+fn weekday(raw: &str) -> Option<Weekday> {
+    let raw = raw.trim().to_ascii_lowercase();
+    DAYS.iter()
+        .find(|(name, _)| raw.starts_with(name))
+        .map(|(_, day)| *day)
+}
+
+fn covers(window: &ExecutionWindow, now: NaiveDateTime) -> bool {
+    let (Ok(start), Ok(end)) = (
+        quiet::parse_time(&window.start),
+        quiet::parse_time(&window.end),
+    ) else {
+        return false;
+    };
+    let on = |day: Weekday| {
+        window.days.is_empty() || window.days.iter().any(|d| weekday(d) == Some(day))
+    };
+    let time = now.time();
+    let today = now.weekday();
+    if start < end {
+        on(today) && time >= start && time < end
+    } else {
+        // Past midnight the window belongs to the day it started on.
+        (on(today) && time >= start) || (on(today.pred()) && time < end)
+    }
+}
+
+/// The window of the core's profile in force at `at`: a configured window
+/// or a freeze read from one of the profile's calendars, a `block` one
+/// winning over a `downgrade` one.
+pub fn window_at(base_url: &str, at: DateTime<Utc>) -> Option<ExecutionWindow> {
+    let origin = net::base_origin(base_url)?;
+    let local = at.with_timezone(&Local).naive_local();
+    let configured = windows().lock().ok().and_then(|windows| {
+        windows
+            .get(&origin)?
+            .iter()
+            .find(|w| covers(w, local))
+            .cloned()
+    });
+    let frozen = calendar::window_at(&origin, at);
+    match (configured, frozen) {
+        (Some(window), Some(freeze)) if window.action != WindowAction::Block => Some(freeze),
+        (configured, frozen) => configured.or(frozen),
+    }
+}
+
+/// The window in force now.
+pub fn active(base_url: &str) -> Option<ExecutionWindow> {
+    window_at(base_url, Utc::now())
+}
+
+fn describe(window: &ExecutionWindow) -> Value {
+    json!({
+        "name": window.name,
+        "days": window.days,
+        "start": window.start,
+        "end": window.end,
+        "action": window.action,
+    })
+}
+
+fn issue(base_url: &str, plan_id: &str) -> Result<String, String> {
+    let origin = net::base_origin(base_url).unwrap_or_default();
+    let code: String = crypto::random_bytes::<3>()
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    overrides()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Override state poisoned"))?
+        .insert(
+            format!("{} {}", origin, plan_id),
+            (code.clone(), Instant::now()),
+        );
+    Ok(code)
+}
+
+fn redeem(base_url: &str, plan_id: &str, code: &str) -> bool {
+    let origin = net::base_origin(base_url).unwrap_or_default();
+    let Ok(mut pending) = overrides().lock() else {
+        return false;
+    };
+    pending
+        .remove(&format!("{} {}", origin, plan_id))
+        .is_some_and(|(expected, at)| {
+            expected.eq_ignore_ascii_case(code.trim()) && at.elapsed() <= CONFIRM_WINDOW
+        })
+}
+
+/// Applies the active execution window to an approve payload asking for
+/// `execute=true`. A `block` window refuses with `EXECUTION_WINDOW` (whose
+/// detail carries a fresh override code); a `downgrade` window clears
+/// `execute`. A valid `override_code` lets the approval execute anyway.
+/// Returns what was applied, for the caller to pass back to the UI.
+pub fn guard(
+    base_url: &str,
+    plan_id: &str,
+    payload: &mut Option<Value>,
+    override_code: Option<&str>,
+) -> Result<Option<Value>, String> {
+    let wants_execute = payload
+        .as_ref()
+        .is_some_and(|p| p["execute"].as_bool() == Some(true));
+    if !wants_execute {
+        return Ok(None);
+    }
+    let Some(window) = active(base_url) else {
+        return Ok(None);
+    };
+    if let Some(code) = override_code.filter(|c| !c.trim().is_empty()) {
+        if !redeem(base_url, plan_id, code) {
+            return Err(
+                ErrorCode::InvalidInput.with("Override confirmation is invalid or has expired")
+            );
+        }
+        return Ok(Some(
+            json!({ "window": describe(&window), "overridden": true }),
+        ));
+    }
+    match window.action {
+        WindowAction::Block => Err(ErrorCode::ExecutionWindow.with(json!({
+            "message": format!(
+                "Execution is blocked during {}; approve without executing or confirm the override",
+                if window.name.is_empty() { "this window" } else { window.name.as_str() }
+            ),
+            "plan_id": plan_id,
+            "window": describe(&window),
+            "confirmation": issue(base_url, plan_id)?,
+            "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+        }))),
+        WindowAction::Downgrade => {
+            if let Some(Value::Object(body)) = payload {
+                body.insert("execute".to_string(), json!(false));
+            }
+            Ok(Some(
+                json!({ "window": describe(&window), "downgraded": true }),
+            ))
+        }
+    }
+}
+
+/// Reports the profile's execution windows and which one, if any, is in
+/// force now.
+#[tauri::command]
+pub fn get_execution_windows(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = store.snapshot();
+    let configured: Vec<Value> = settings
+        .profile(&conn.profile_id)
+        .map(|p| p.execution_windows.iter().map(describe).collect())
+        .unwrap_or_default();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "windows": configured,
+        "active": active(&conn.base_url).as_ref().map(describe),
+    }))
+}
+
+/// Issues an override code so the next approval of `plan_id` may execute
+/// despite the window in force. The code is valid once, for 60s.
+#[tauri::command]
+pub fn request_execution_override(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let window = active(&conn.base_url)
+        .ok_or_else(|| ErrorCode::InvalidInput.with("No execution window is in force"))?;
+    Ok(json!({
+        "plan_id": plan_id,
+        "window": describe(&window),
+        "confirmation": issue(&conn.base_url, &plan_id)?,
+        "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+    }))
+}
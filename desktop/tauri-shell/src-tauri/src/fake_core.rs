@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+use crate::spill;
+use crate::transport::{self, CoreRequest, CoreResponse, CoreTransport, TransportFuture};
+
+/// Serves larger pages than this only when a script asks for it.
+const DEFAULT_PAGE_SIZE: usize = 200;
+/// Plans one fake core holds.
+const MAX_PLANS: usize = 5_000;
+/// Longest scripted delay, so a typo cannot hang a run.
+const MAX_LATENCY_MS: u64 = 30_000;
+
+/// A failure the fake core answers with instead of serving the request.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    /// Only requests with this method; any method when unset.
+    pub method: Option<String>,
+    /// Matched as a prefix of the request path, without the query.
+    pub path: String,
+    /// `status` (answer with `status`), `unreachable` or `timeout`.
+    pub kind: String,
+    pub status: u16,
+    /// Requests it fails before it stops firing; every one when unset.
+    pub times: Option<u32>,
+}
+
+/// How a fake core behaves: its plans, how slowly it answers and which
+/// requests fail.
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    /// Delay before every answer.
+    pub latency_ms: u64,
+    /// Largest page `/plans` returns whatever `limit` asks for.
+    pub page_size: Option<usize>,
+    pub faults: Vec<Fault>,
+    /// Seed plans; `id` is required, `status` defaults to `pending` and
+    /// `version` to 1.
+    pub plans: Vec<Value>,
+    /// Who the core records as deciding, as `decided_by`.
+    pub operator: Option<String>,
+}
+
+struct State {
+    plans: Vec<Value>,
+    faults: Vec<Fault>,
+    latency: Duration,
+    page_size: usize,
+    operator: String,
+    requests: u64,
+}
+
+/// An in-process core with real plan state: lists page, decisions check
+/// `If-Match` and move plans on, and every change bumps the plan's version.
+/// Installed over a `mock://` base URL so requests pass through the whole
+/// `request_json` pipeline before reaching it.
+pub struct FakeCore {
+    state: Mutex<State>,
+}
+
+fn version_of(plan: &Value) -> u64 {
+    match &plan["version"] {
+        Value::Number(n) => n.as_u64().unwrap_or(1),
+        Value::String(s) => s.parse().unwrap_or(1),
+        _ => 1,
+    }
+}
+
+fn bump(plan: &mut Value) {
+    let next = version_of(plan) + 1;
+    plan["version"] = json!(next.to_string());
+    plan["updated_at"] = json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+}
+
+impl FakeCore {
+    pub fn new(script: Script) -> Result<Self, String> {
+        if script.plans.len() > MAX_PLANS {
+            return Err(ErrorCode::InvalidInput
+                .with(format!("A fake core holds at most {} plans", MAX_PLANS)));
+        }
+        if let Some(bad) = script.faults.iter().find(|f| {
+            !f.path.starts_with('/')
+                || !matches!(f.kind.as_str(), "status" | "unreachable" | "timeout")
+        }) {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Fault on {} needs a path starting with / and a kind of status, unreachable or timeout",
+                bad.path
+            )));
+        }
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut plans = Vec::with_capacity(script.plans.len());
+        for mut plan in script.plans {
+            if !plan["id"].is_string() {
+                return Err(ErrorCode::InvalidInput.with("Every fake plan needs a string id"));
+            }
+            if !plan["status"].is_string() {
+                plan["status"] = json!("pending");
+            }
+            plan["version"] = json!(version_of(&plan).to_string());
+            if !plan["created_at"].is_string() {
+                plan["created_at"] = json!(now);
+            }
+            plans.push(plan);
+        }
+        Ok(Self {
+            state: Mutex::new(State {
+                plans,
+                faults: script.faults,
+                latency: Duration::from_millis(script.latency_ms.min(MAX_LATENCY_MS)),
+                page_size: script.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1),
+                operator: script.operator.unwrap_or_else(|| "fake-core".to_string()),
+                requests: 0,
+            }),
+        })
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> Result<T, String> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Fake core state poisoned"))?;
+        Ok(f(&mut state))
+    }
+
+    pub fn plan(&self, id: &str) -> Option<Value> {
+        self.with_state(|s| s.plans.iter().find(|p| p["id"] == id).cloned())
+            .ok()
+            .flatten()
+    }
+
+    /// Changes a plan as another client would, bumping its version.
+    pub fn edit_plan(&self, id: &str, edit: impl FnOnce(&mut Value)) -> bool {
+        self.with_state(|s| match s.plans.iter_mut().find(|p| p["id"] == id) {
+            Some(plan) => {
+                edit(plan);
+                bump(plan);
+                true
+            }
+            None => false,
+        })
+        .unwrap_or(false)
+    }
+
+    pub fn inject(&self, fault: Fault) {
+        let _ = self.with_state(|s| s.faults.push(fault));
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        let _ = self.with_state(|s| s.latency = latency);
+    }
+
+    /// The first armed fault for the request, used up by one firing.
+    fn fault(state: &mut State, method: &Method, path: &str) -> Option<Fault> {
+        let index = state.faults.iter().position(|f| {
+            path.starts_with(&f.path)
+                && f.times != Some(0)
+                && f.method
+                    .as_ref()
+                    .is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+        })?;
+        let fault = state.faults[index].clone();
+        if let Some(times) = state.faults[index].times.as_mut() {
+            *times -= 1;
+        }
+        Some(fault)
+    }
+
+    fn answer(&self, request: &CoreRequest) -> Result<(u16, Value, HeaderMap), String> {
+        let path = request.url.path().trim_end_matches('/').to_string();
+        let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+        let if_match = request
+            .headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("if-match"))
+            .map(|(_, v)| v.trim_matches('"').to_string());
+        let mut headers = HeaderMap::new();
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Fake core state poisoned"))?;
+        state.requests += 1;
+        if let Some(fault) = Self::fault(&mut state, &request.method, &path) {
+            return match fault.kind.as_str() {
+                "unreachable" => Err(ErrorCode::CoreUnreachable
+                    .with(format!("Fake core refused {} {}", request.method, path))),
+                "timeout" => Err(ErrorCode::CoreTimeout.with(format!(
+                    "Fake core timed out on {} {}",
+                    request.method, path
+                ))),
+                _ => Ok((
+                    fault.status,
+                    json!({ "error": format!("Injected {} on {}", fault.status, path) }),
+                    headers,
+                )),
+            };
+        }
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let method = &request.method;
+        let answer = match (method.as_str(), segments.as_slice()) {
+            ("GET", ["health"]) => (200, json!({ "ok": true, "fake": true })),
+            ("GET", ["plans"]) => {
+                let offset = query
+                    .get("offset")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                let limit = query
+                    .get("limit")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(state.page_size)
+                    .min(state.page_size);
+                let matching: Vec<&Value> = state
+                    .plans
+                    .iter()
+                    .filter(|p| {
+                        query
+                            .get("status")
+                            .is_none_or(|s| p["status"] == s.as_str())
+                    })
+                    .collect();
+                if let Ok(total) = HeaderValue::from_str(&matching.len().to_string()) {
+                    headers.insert("x-total-count", total);
+                }
+                let page: Vec<&Value> = matching.into_iter().skip(offset).take(limit).collect();
+                (200, json!(page))
+            }
+            ("GET", ["plans", id]) => match state.plans.iter().find(|p| p["id"] == *id) {
+                Some(plan) => (200, plan.clone()),
+                None => (404, json!({ "error": format!("No plan {}", id) })),
+            },
+            ("POST", ["plans", id, decision @ ("approve" | "approve_async" | "reject")]) => {
+                let operator = state.operator.clone();
+                match state.plans.iter_mut().find(|p| p["id"] == *id) {
+                    None => (404, json!({ "error": format!("No plan {}", id) })),
+                    Some(plan) if plan["status"] != "pending" => (
+                        409,
+                        json!({ "error": format!("Plan {} is already {}", id, plan["status"]) }),
+                    ),
+                    Some(plan)
+                        if if_match
+                            .as_ref()
+                            .is_some_and(|v| *v != version_of(plan).to_string()) =>
+                    {
+                        (
+                            412,
+                            json!({ "error": "Plan version does not match If-Match" }),
+                        )
+                    }
+                    Some(plan) => {
+                        let body = request.body.clone().unwrap_or(Value::Null);
+                        let status = match *decision {
+                            "reject" => "rejected",
+                            _ if body["execute"] == true => "executed",
+                            _ => "approved",
+                        };
+                        plan["status"] = json!(status);
+                        plan["decided_by"] = json!(operator);
+                        plan["decided_at"] =
+                            json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+                        if *decision == "reject" {
+                            plan["rejection_reason"] = body["reason"].clone();
+                        }
+                        bump(plan);
+                        (200, plan.clone())
+                    }
+                }
+            }
+            ("GET", ["jobs"]) => (200, json!([])),
+            ("GET", ["dashboard", "data"]) => {
+                let pending: Vec<&Value> = state
+                    .plans
+                    .iter()
+                    .filter(|p| p["status"] == "pending")
+                    .collect();
+                (200, json!({ "plans": pending, "jobs": [] }))
+            }
+            _ => (
+                404,
+                json!({ "error": format!("Fake core has no route for {} {}", method, path) }),
+            ),
+        };
+        Ok((answer.0, answer.1, headers))
+    }
+}
+
+impl CoreTransport for FakeCore {
+    fn name(&self) -> &'static str {
+        transport::MOCK
+    }
+
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let latency = self.with_state(|s| s.latency)?;
+            if !latency.is_zero() {
+                tokio::time::sleep(latency).await;
+            }
+            let (status, body, mut headers) = self.answer(&request)?;
+            headers.insert(
+                reqwest::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            Ok(CoreResponse {
+                status: StatusCode::from_u16(status).unwrap_or(StatusCode::OK),
+                headers,
+                url: request.url,
+                body: spill::Body::Buffered(body.to_string()),
+            })
+        })
+    }
+}
+
+static FAKES: OnceLock<Mutex<HashMap<String, Arc<FakeCore>>>> = OnceLock::new();
+
+fn fakes() -> &'static Mutex<HashMap<String, Arc<FakeCore>>> {
+    FAKES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Starts a fake core at `mock://<name>`, replacing one of that name.
+pub fn start(name: &str, script: Script) -> Result<(String, Arc<FakeCore>), String> {
+    let name = transport::mock_name(name)?;
+    let base_url = format!("{}://{}", transport::MOCK, name);
+    let fake = Arc::new(FakeCore::new(script)?);
+    transport::install(&base_url, fake.clone())?;
+    fakes()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Fake core state poisoned"))?
+        .insert(name, fake.clone());
+    Ok((base_url, fake))
+}
+
+pub fn stop(name: &str) -> Result<bool, String> {
+    let name = transport::mock_name(name)?;
+    let removed = fakes()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Fake core state poisoned"))?
+        .remove(&name);
+    if removed.is_some() {
+        transport::remove(&format!("{}://{}", transport::MOCK, name));
+    }
+    Ok(removed.is_some())
+}
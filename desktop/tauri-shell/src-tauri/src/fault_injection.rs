@@ -0,0 +1,390 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::transport::CoreRequest;
+use crate::{environment, net};
+
+const EVENT: &str = "fault-injection:changed";
+const MAX_RULES: usize = 64;
+const MAX_LATENCY_MS: u64 = 120_000;
+const MAX_SCENARIO_BYTES: u64 = 1024 * 1024;
+
+/// One kind of trouble, for the requests it matches while it is active.
+/// Rules that match the same request all add their latency; the first one
+/// whose roll fails the request decides how.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Only requests with this method.
+    pub method: Option<String>,
+    /// Only paths starting with this, e.g. `/plans`.
+    pub path: Option<String>,
+    /// Only this core (any URL on its origin).
+    pub base_url: Option<String>,
+    /// Seconds after the scenario starts that the rule switches on...
+    pub from_secs: u64,
+    /// ...and off again; it stays on when unset.
+    pub until_secs: Option<u64>,
+    pub latency_ms: u64,
+    /// Up to this much more, at random.
+    pub jitter_ms: u64,
+    /// Chance (0 to 1) the connection drops before the request is sent.
+    pub drop_rate: f64,
+    /// Chance the core gets the request but the response is lost, as when a
+    /// connection resets mid-response.
+    pub drop_after_send_rate: f64,
+    /// Chance the request times out without reaching the core.
+    pub timeout_rate: f64,
+    /// Chance the request is answered with `status` instead.
+    pub error_rate: f64,
+    pub status: u16,
+    /// Sent as `Retry-After` with injected errors.
+    pub retry_after_secs: Option<u64>,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            method: None,
+            path: None,
+            base_url: None,
+            from_secs: 0,
+            until_secs: None,
+            latency_ms: 0,
+            jitter_ms: 0,
+            drop_rate: 0.0,
+            drop_after_send_rate: 0.0,
+            timeout_rate: 0.0,
+            error_rate: 0.0,
+            status: 500,
+            retry_after_secs: None,
+        }
+    }
+}
+
+/// A scenario file: what goes wrong, where and when.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name: String,
+    /// Makes the random rolls repeat from run to run.
+    pub seed: Option<u64>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Default, Serialize)]
+struct Counts {
+    matched: u64,
+    delayed_ms: u64,
+    dropped: u64,
+    dropped_after_send: u64,
+    timed_out: u64,
+    errors: u64,
+}
+
+struct Active {
+    scenario: Scenario,
+    source: Option<String>,
+    started: Instant,
+    started_at: String,
+    rng: u64,
+    /// Origin of each rule's `base_url`, resolved once.
+    origins: Vec<Option<String>>,
+    counts: Vec<Counts>,
+    /// Requests left alone because they went to a prod profile's core.
+    spared: u64,
+}
+
+impl Active {
+    /// xorshift64*: plenty for deciding which requests fail, and seedable.
+    fn roll(&mut self, chance: f64) -> bool {
+        if chance <= 0.0 {
+            return false;
+        }
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let draw = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        (draw as f64 / (1u64 << 53) as f64) < chance
+    }
+
+    fn jitter(&mut self, max: u64) -> u64 {
+        if max == 0 {
+            return 0;
+        }
+        self.roll(1.0);
+        self.rng % (max + 1)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "active": true,
+            "name": self.scenario.name,
+            "source": self.source,
+            "seed": self.scenario.seed,
+            "started_at": self.started_at,
+            "elapsed_secs": self.started.elapsed().as_secs(),
+            "spared_prod_requests": self.spared,
+            "rules": self.scenario.rules.iter().zip(&self.counts).map(|(rule, counts)| {
+                json!({ "rule": rule, "counts": counts })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+static ACTIVE: OnceLock<Mutex<Option<Active>>> = OnceLock::new();
+
+fn active() -> &'static Mutex<Option<Active>> {
+    ACTIVE.get_or_init(|| Mutex::new(None))
+}
+
+/// What the transport layer does with a request.
+pub enum Outcome {
+    Send,
+    /// Fail without sending.
+    Fail(String),
+    /// Send, then lose the response.
+    LoseResponse(String),
+    /// Answer in the core's place.
+    Respond(StatusCode, HeaderMap, String),
+}
+
+fn decide(request: &CoreRequest) -> (Duration, Outcome) {
+    let mut guard = match active().lock() {
+        Ok(guard) => guard,
+        Err(_) => return (Duration::ZERO, Outcome::Send),
+    };
+    let Some(active) = guard.as_mut() else {
+        return (Duration::ZERO, Outcome::Send);
+    };
+    // Never near production, whatever the scenario says.
+    if environment::prod_profile(&request.base_url).is_some() {
+        active.spared += 1;
+        return (Duration::ZERO, Outcome::Send);
+    }
+    let origin = net::base_origin(&request.base_url);
+    let path = request.url.path().to_string();
+    let elapsed = active.started.elapsed().as_secs();
+    let mut delay = 0;
+    let mut outcome = Outcome::Send;
+    for i in 0..active.scenario.rules.len() {
+        let rule = active.scenario.rules[i].clone();
+        let matches = rule
+            .method
+            .as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(request.method.as_str()))
+            && rule.path.as_ref().is_none_or(|p| path.starts_with(p))
+            && active.origins[i]
+                .as_ref()
+                .is_none_or(|o| Some(o) == origin.as_ref())
+            && elapsed >= rule.from_secs
+            && rule.until_secs.is_none_or(|until| elapsed < until);
+        if !matches {
+            continue;
+        }
+        let added = rule.latency_ms + active.jitter(rule.jitter_ms);
+        delay += added;
+        active.counts[i].matched += 1;
+        active.counts[i].delayed_ms += added;
+        if !matches!(outcome, Outcome::Send) {
+            continue;
+        }
+        let label = format!("{} {} (fault injection)", request.method, path);
+        if active.roll(rule.drop_rate) {
+            active.counts[i].dropped += 1;
+            outcome = Outcome::Fail(
+                ErrorCode::CoreUnreachable.with(format!("Connection dropped: {}", label)),
+            );
+        } else if active.roll(rule.timeout_rate) {
+            active.counts[i].timed_out += 1;
+            outcome = Outcome::Fail(ErrorCode::CoreTimeout.with(format!("Timed out: {}", label)));
+        } else if active.roll(rule.drop_after_send_rate) {
+            active.counts[i].dropped_after_send += 1;
+            outcome = Outcome::LoseResponse(
+                ErrorCode::CoreUnreachable
+                    .with(format!("Connection reset after sending: {}", label)),
+            );
+        } else if active.roll(rule.error_rate) {
+            active.counts[i].errors += 1;
+            let status =
+                StatusCode::from_u16(rule.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            if let Some(secs) = rule.retry_after_secs {
+                headers.insert(RETRY_AFTER, HeaderValue::from(secs));
+            }
+            let body = json!({ "error": format!("Injected {}: {}", status.as_u16(), label) });
+            outcome = Outcome::Respond(status, headers, body.to_string());
+        }
+    }
+    (Duration::from_millis(delay.min(MAX_LATENCY_MS)), outcome)
+}
+
+/// Applies the running scenario to a request about to go to a core:
+/// waits out any injected latency, then says whether to send it.
+pub async fn apply(request: &CoreRequest) -> Outcome {
+    let (delay, outcome) = decide(request);
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    outcome
+}
+
+fn validate(scenario: &Scenario) -> Result<(), String> {
+    if scenario.rules.is_empty() || scenario.rules.len() > MAX_RULES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "A scenario needs between 1 and {} rules",
+            MAX_RULES
+        )));
+    }
+    for (i, rule) in scenario.rules.iter().enumerate() {
+        let rates = [
+            rule.drop_rate,
+            rule.drop_after_send_rate,
+            rule.timeout_rate,
+            rule.error_rate,
+        ];
+        let problem = if rates.iter().any(|r| !(0.0..=1.0).contains(r)) {
+            Some("rates must be between 0 and 1".to_string())
+        } else if !(500..=599).contains(&rule.status) {
+            Some("status must be a 5xx".to_string())
+        } else if rule.latency_ms + rule.jitter_ms > MAX_LATENCY_MS {
+            Some(format!("latency is capped at {} ms", MAX_LATENCY_MS))
+        } else if rule.path.as_ref().is_some_and(|p| !p.starts_with('/')) {
+            Some("path must start with /".to_string())
+        } else if rule.until_secs.is_some_and(|until| until <= rule.from_secs) {
+            Some("until_secs must be after from_secs".to_string())
+        } else if let Some(base) = &rule.base_url {
+            match net::base_origin(base) {
+                None => Some(format!("{} is not a base URL", base)),
+                Some(_) => environment::prod_profile(base)
+                    .map(|p| format!("{} is production profile {}'s core", base, p)),
+            }
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(ErrorCode::InvalidInput.with(format!("Rule {}: {}", i + 1, problem)));
+        }
+    }
+    Ok(())
+}
+
+fn load(path: &str) -> Result<Scenario, String> {
+    let path = path.trim();
+    let size = std::fs::metadata(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read {} failed: {}", path, e)))?
+        .len();
+    if size > MAX_SCENARIO_BYTES {
+        return Err(ErrorCode::InvalidInput.with("Scenario file is too large"));
+    }
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read {} failed: {}", path, e)))?;
+    serde_json::from_str(&raw)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Scenario {} is invalid: {}", path, e)))
+}
+
+fn audit(app: &AppHandle, store: &SettingsStore, db: &LocalDb, action: &str, detail: &Value) {
+    let profile = store.snapshot().active_profile;
+    if let Ok(guard) = db.lock() {
+        let _ = db::record_audit(
+            &guard,
+            &profile,
+            "developer",
+            action,
+            "fault_injection",
+            detail,
+        );
+    }
+    let _ = environment::emit(app, EVENT, detail);
+}
+
+/// Developer mode for resilience testing: until stopped (or the shell
+/// restarts), requests to cores get the latency, dropped connections,
+/// timeouts and 5xx answers the scenario describes, in the transport layer
+/// under `request_json`, so retries, replica failover, maintenance back-off
+/// and offline queuing meet them as they would a failing core. Takes the
+/// scenario inline or from a JSON file at `path`; a running one is
+/// replaced. Cores of production profiles are never affected.
+#[tauri::command]
+pub fn start_fault_injection(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    scenario: Option<Scenario>,
+    path: Option<String>,
+) -> Result<Value, String> {
+    let scenario = match (scenario, path.as_deref()) {
+        (Some(scenario), None) => scenario,
+        (None, Some(path)) => load(path)?,
+        _ => return Err(ErrorCode::InvalidInput.with("Pass either a scenario or a scenario path")),
+    };
+    validate(&scenario)?;
+    let seed = scenario.seed.unwrap_or_else(|| {
+        Utc::now()
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .unsigned_abs()
+    });
+    let next = Active {
+        origins: scenario
+            .rules
+            .iter()
+            .map(|r| r.base_url.as_deref().and_then(net::base_origin))
+            .collect(),
+        counts: scenario.rules.iter().map(|_| Counts::default()).collect(),
+        scenario,
+        source: path.map(|p| p.trim().to_string()),
+        started: Instant::now(),
+        started_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        // xorshift never leaves zero.
+        rng: seed.max(1),
+        spared: 0,
+    };
+    let described = next.describe();
+    *active()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Fault injection state poisoned"))? = Some(next);
+    audit(&app, &store, &db, "fault_injection_started", &described);
+    Ok(described)
+}
+
+/// The running scenario and what each rule has done so far.
+#[tauri::command]
+pub fn get_fault_injection() -> Value {
+    active()
+        .lock()
+        .ok()
+        .and_then(|a| a.as_ref().map(Active::describe))
+        .unwrap_or_else(|| json!({ "active": false }))
+}
+
+/// Turns fault injection off and returns the scenario's final counts.
+#[tauri::command]
+pub fn stop_fault_injection(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+) -> Result<Value, String> {
+    let stopped = active()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Fault injection state poisoned"))?
+        .take();
+    let Some(stopped) = stopped else {
+        return Ok(json!({ "active": false }));
+    };
+    let mut described = stopped.describe();
+    described["active"] = json!(false);
+    audit(&app, &store, &db, "fault_injection_stopped", &described);
+    Ok(described)
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use tauri::State;
+
+use crate::settings::{Connection, SettingsStore};
+
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Per-profile capability maps, refreshed at most every `CACHE_TTL`.
+#[derive(Default)]
+pub struct FeatureCache {
+    entries: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl FeatureCache {
+    fn fresh(&self, profile_id: &str) -> Option<Value> {
+        let entries = self.entries.lock().ok()?;
+        let (fetched, value) = entries.get(profile_id)?;
+        (fetched.elapsed() < CACHE_TTL).then(|| value.clone())
+    }
+
+    fn store(&self, profile_id: &str, value: Value) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(profile_id.to_string(), (Instant::now(), value));
+        }
+    }
+}
+
+/// Returns a flat `name → bool` capability map combining the core's feature
+/// flags (`core.*`) with what this shell can do on this machine (`shell.*`).
+#[tauri::command]
+pub async fn get_features(
+    store: State<'_, SettingsStore>,
+    cache: State<'_, FeatureCache>,
+    profile: Option<String>,
+    refresh: Option<bool>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    Ok(load(&store, &cache, &conn, refresh.unwrap_or(false)).await)
+}
+
+/// Whether the core of `conn` reports the feature flag `name` (without the
+/// `core.` prefix); `None` when it says nothing either way.
+pub async fn core_flag(
+    store: &SettingsStore,
+    cache: &FeatureCache,
+    conn: &Connection,
+    name: &str,
+) -> Option<bool> {
+    load(store, cache, conn, false).await["capabilities"][format!("core.{}", name)].as_bool()
+}
+
+async fn load(
+    store: &SettingsStore,
+    cache: &FeatureCache,
+    conn: &Connection,
+    refresh: bool,
+) -> Value {
+    if !refresh {
+        if let Some(cached) = cache.fresh(&conn.profile_id) {
+            return cached;
+        }
+    }
+
+    let (core_flags, source, core_error) = match fetch_core_flags(conn).await {
+        Ok((flags, source)) => (flags, Some(source), None),
+        Err(err) => (Map::new(), None, Some(err)),
+    };
+    let mut capabilities = Map::new();
+    flatten_flags("core", &Value::Object(core_flags), &mut capabilities);
+    for (name, enabled) in shell_capabilities(store) {
+        capabilities.insert(format!("shell.{}", name), json!(enabled));
+    }
+
+    let out = json!({
+        "profile_id": conn.profile_id,
+        "capabilities": capabilities,
+        "core_source": source,
+        "core_error": core_error,
+        "fetched_at": chrono::Utc::now().to_rfc3339(),
+    });
+    // Don't pin an unreachable core's empty flag set for the whole TTL.
+    if core_error.is_none() {
+        cache.store(&conn.profile_id, out.clone());
+    }
+    out
+}
+
+/// Prefers a dedicated `/features` endpoint and falls back to the
+/// `capabilities` block of the deep health check on older cores.
+async fn fetch_core_flags(conn: &Connection) -> Result<(Map<String, Value>, &'static str), String> {
+    let token = conn.token.clone();
+    if let Ok(Value::Object(mut body)) = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/features",
+        token.clone(),
+        None,
+    )
+    .await
+    {
+        let flags = match body.remove("features") {
+            Some(Value::Object(flags)) => flags,
+            _ => body,
+        };
+        return Ok((flags, "features"));
+    }
+    let health =
+        crate::request_json(Method::GET, &conn.base_url, "/health?deep=1", token, None).await?;
+    match health.get("capabilities") {
+        Some(Value::Object(flags)) => Ok((flags.clone(), "health")),
+        _ => Ok((Map::new(), "health")),
+    }
+}
+
+fn flatten_flags(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    match value {
+        Value::Bool(enabled) => {
+            out.insert(prefix.to_string(), json!(enabled));
+        }
+        Value::Object(map) => {
+            for (key, child) in map {
+                flatten_flags(&format!("{}.{}", prefix, key), child, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn shell_capabilities(store: &SettingsStore) -> Vec<(&'static str, bool)> {
+    vec![
+        ("pty", true),
+        ("preview", !crate::preview::allowed_roots().is_empty()),
+        ("local_exec", crate::local_exec::is_enabled()),
+        ("encrypted_settings", store.is_persistent()),
+        ("keyring", crate::secrets::get("probe").is_ok()),
+        ("negotiate_auth", crate::negotiate::SUPPORTED),
+        ("http3", crate::http3::SUPPORTED),
+        ("grpc", crate::grpc::SUPPORTED),
+        ("plugins", crate::plugins::SUPPORTED),
+        ("screenshot", crate::screenshot::SUPPORTED),
+    ]
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+/// Sparse field selection for list payloads. Selections are dotted paths; a
+/// leading segment naming one of the payload's collections scopes the path to
+/// that collection (`jobs.status`), anything else applies to `default`.
+pub struct FieldSelection {
+    by_collection: BTreeMap<String, Vec<Vec<String>>>,
+}
+
+impl FieldSelection {
+    pub fn parse(fields: &[String], collections: &[&str], default: &str) -> Option<Self> {
+        let mut by_collection: BTreeMap<String, Vec<Vec<String>>> = BTreeMap::new();
+        for raw in fields.iter().flat_map(|f| f.split(',')) {
+            let segments: Vec<String> = raw
+                .trim()
+                .split('.')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+            let Some(first) = segments.first() else {
+                continue;
+            };
+            let (collection, path) = if segments.len() > 1 && collections.contains(&first.as_str())
+            {
+                (first.clone(), segments[1..].to_vec())
+            } else {
+                (default.to_string(), segments)
+            };
+            by_collection.entry(collection).or_default().push(path);
+        }
+        (!by_collection.is_empty()).then_some(Self { by_collection })
+    }
+
+    /// The selection as a `fields=` query value for cores that support it.
+    pub fn query_value(&self, default: &str) -> String {
+        self.by_collection
+            .iter()
+            .flat_map(|(collection, paths)| {
+                paths.iter().map(move |path| {
+                    if collection == default {
+                        path.join(".")
+                    } else {
+                        format!("{}.{}", collection, path.join("."))
+                    }
+                })
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Projects each selected collection in `payload` (a top-level array for
+    /// plain list endpoints, keyed by `default`). Items keep their `id` so the
+    /// UI can still address them. Idempotent, so it is safe to apply after a
+    /// core that already honoured the selection.
+    pub fn apply(&self, payload: &mut Value, default: &str) {
+        for (collection, paths) in &self.by_collection {
+            let items = if payload.is_array() && collection == default {
+                Some(&mut *payload)
+            } else {
+                payload.get_mut(collection)
+            };
+            if let Some(Value::Array(items)) = items {
+                for item in items.iter_mut() {
+                    if let Value::Object(map) = item {
+                        *map = project(map, paths);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn project(item: &Map<String, Value>, paths: &[Vec<String>]) -> Map<String, Value> {
+    let mut out = Map::new();
+    if let Some(id) = item.get("id") {
+        out.insert("id".to_string(), id.clone());
+    }
+    for path in paths {
+        copy_path(item, path, &mut out);
+    }
+    out
+}
+
+fn copy_path(src: &Map<String, Value>, path: &[String], dst: &mut Map<String, Value>) {
+    let Some((head, rest)) = path.split_first() else {
+        return;
+    };
+    let Some(value) = src.get(head) else {
+        return;
+    };
+    if rest.is_empty() {
+        dst.insert(head.clone(), value.clone());
+        return;
+    }
+    if let Value::Object(child) = value {
+        let entry = dst
+            .entry(head.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(child_dst) = entry {
+            copy_path(child, rest, child_dst);
+        }
+    }
+}
@@ -0,0 +1,926 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, Offset, SecondsFormat, TimeZone, Utc,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::settings::{FormattingSettings, Settings, SettingsStore};
+
+/// Where the IANA time zone database is installed on Linux and macOS.
+const ZONEINFO_DIRS: &[&str] = &[
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+/// Real zone files are a few KiB; anything much bigger is not one.
+const MAX_ZONE_BYTES: u64 = 256 * 1024;
+const MAX_ITEMS: usize = 1_000;
+
+/// How one locale writes dates, times and numbers. Only what reports and
+/// exports need, not a full CLDR table.
+struct Style {
+    tags: &'static [&'static str],
+    date: &'static str,
+    time: &'static str,
+    group: &'static str,
+    decimal: &'static str,
+    /// Lakh grouping: 12,34,567.
+    indian: bool,
+}
+
+const fn style(
+    tags: &'static [&'static str],
+    date: &'static str,
+    time: &'static str,
+    group: &'static str,
+    decimal: &'static str,
+) -> Style {
+    Style {
+        tags,
+        date,
+        time,
+        group,
+        decimal,
+        indian: false,
+    }
+}
+
+/// Grouping that CLDR writes with a narrow no-break space uses a plain
+/// no-break space, which the PDF export's fonts can draw.
+const STYLES: &[Style] = &[
+    style(
+        &["en-us", "en", "en-ph"],
+        "%m/%d/%Y",
+        "%-I:%M:%S %p",
+        ",",
+        ".",
+    ),
+    style(
+        &["en-gb", "en-au", "en-nz", "en-ie", "en-za"],
+        "%d/%m/%Y",
+        "%H:%M:%S",
+        ",",
+        ".",
+    ),
+    Style {
+        indian: true,
+        ..style(
+            &["en-in", "hi", "hi-in"],
+            "%d/%m/%Y",
+            "%-I:%M:%S %p",
+            ",",
+            ".",
+        )
+    },
+    style(&["en-ca"], "%Y-%m-%d", "%-I:%M:%S %p", ",", "."),
+    style(
+        &["de", "de-de", "de-at", "de-lu"],
+        "%d.%m.%Y",
+        "%H:%M:%S",
+        ".",
+        ",",
+    ),
+    style(&["de-ch"], "%d.%m.%Y", "%H:%M:%S", "\u{2019}", "."),
+    style(
+        &["fr", "fr-fr", "fr-be"],
+        "%d/%m/%Y",
+        "%H:%M:%S",
+        "\u{a0}",
+        ",",
+    ),
+    style(&["fr-ca"], "%Y-%m-%d", "%H:%M:%S", "\u{a0}", ","),
+    style(&["es", "es-es"], "%d/%m/%Y", "%H:%M:%S", ".", ","),
+    style(&["es-mx", "es-us"], "%d/%m/%Y", "%H:%M:%S", ",", "."),
+    style(&["it", "it-it"], "%d/%m/%Y", "%H:%M:%S", ".", ","),
+    style(&["pt-br"], "%d/%m/%Y", "%H:%M:%S", ".", ","),
+    style(&["pt", "pt-pt"], "%d/%m/%Y", "%H:%M:%S", "\u{a0}", ","),
+    style(&["nl", "nl-nl"], "%d-%m-%Y", "%H:%M:%S", ".", ","),
+    style(&["sv", "sv-se"], "%Y-%m-%d", "%H:%M:%S", "\u{a0}", ","),
+    style(&["nb", "no", "nn"], "%d.%m.%Y", "%H:%M:%S", "\u{a0}", ","),
+    style(&["da", "da-dk"], "%d.%m.%Y", "%H.%M.%S", ".", ","),
+    style(&["fi", "fi-fi"], "%-d.%-m.%Y", "%H.%M.%S", "\u{a0}", ","),
+    style(&["pl", "pl-pl"], "%d.%m.%Y", "%H:%M:%S", "\u{a0}", ","),
+    style(&["cs", "cs-cz"], "%-d. %-m. %Y", "%H:%M:%S", "\u{a0}", ","),
+    style(
+        &["ru", "ru-ru", "uk"],
+        "%d.%m.%Y",
+        "%H:%M:%S",
+        "\u{a0}",
+        ",",
+    ),
+    style(&["tr", "tr-tr"], "%d.%m.%Y", "%H:%M:%S", ".", ","),
+    style(&["ja", "ja-jp"], "%Y/%m/%d", "%H:%M:%S", ",", "."),
+    style(&["zh", "zh-cn", "zh-sg"], "%Y/%m/%d", "%H:%M:%S", ",", "."),
+    style(&["zh-tw", "zh-hk"], "%Y/%m/%d", "%H:%M:%S", ",", "."),
+    style(&["ko", "ko-kr"], "%Y. %-m. %-d.", "%H:%M:%S", ",", "."),
+];
+/// When neither settings nor the OS name a locale: ISO 8601 dates, which
+/// no reader misreads.
+const FALLBACK: Style = style(&["und"], "%Y-%m-%d", "%H:%M:%S", ",", ".");
+
+fn lookup(tag: &str) -> &'static Style {
+    let tag = tag.to_ascii_lowercase();
+    let language = tag.split('-').next().unwrap_or("");
+    STYLES
+        .iter()
+        .find(|s| s.tags.contains(&tag.as_str()))
+        .or_else(|| STYLES.iter().find(|s| s.tags.contains(&language)))
+        .unwrap_or(&FALLBACK)
+}
+
+/// `de_DE.UTF-8@euro` as a BCP 47 tag; `C` and `POSIX` name no locale.
+fn normalize(raw: &str) -> Option<String> {
+    let tag = raw.split(['.', '@']).next()?.trim().replace('_', "-");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    Some(tag)
+}
+
+fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= 35
+        && tag.split('-').all(|part| {
+            (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        && tag
+            .split('-')
+            .next()
+            .is_some_and(|l| l.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+#[cfg(target_os = "macos")]
+fn system_locale() -> Option<String> {
+    let path = crate::cloud_auth::home_dir()?
+        .join("Library")
+        .join("Preferences")
+        .join(".GlobalPreferences.plist");
+    let prefs = plist::from_file::<_, Value>(&path).ok()?;
+    prefs["AppleLocale"].as_str().and_then(normalize)
+}
+
+#[cfg(windows)]
+fn system_locale() -> Option<String> {
+    let key = windows_registry::CURRENT_USER
+        .open("Control Panel\\International")
+        .ok()?;
+    normalize(&key.get_string("LocaleName").ok()?)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|v| normalize(&v))
+}
+
+/// The operator's language, `de` for `de-AT`: from `formatting.locale`, or
+/// the OS locale while that is unset.
+pub fn operator_language(settings: &FormattingSettings) -> Option<String> {
+    let configured = settings.locale.trim();
+    let tag = if configured.is_empty() {
+        system_locale().filter(|t| valid_tag(t))?
+    } else {
+        configured.to_string()
+    };
+    tag.split('-')
+        .next()
+        .map(str::to_ascii_lowercase)
+        .filter(|language| language != "und")
+}
+
+/// One local time type from a zone file.
+#[derive(Clone)]
+struct LocalType {
+    offset: i32,
+    abbr: String,
+}
+
+/// A POSIX `TZ` rule, which zone files end with to cover times after their
+/// last transition: `CET-1CEST,M3.5.0,M10.5.0/3`.
+struct Rule {
+    std: LocalType,
+    dst: Option<(LocalType, Transition, Transition)>,
+}
+
+enum Day {
+    /// `Jn`: 1 to 365, never counting February 29.
+    Julian(i64),
+    /// `n`: 0 to 365, counting it.
+    Ordinal(i64),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` of month `m`; week
+    /// 5 is the last.
+    Month(u32, u32, u32),
+}
+
+struct Transition {
+    day: Day,
+    /// Seconds after local midnight; 02:00 unless given.
+    time: i64,
+}
+
+impl Transition {
+    /// The moment, as seconds since the epoch on the local wall clock.
+    fn local(&self, year: i32) -> Option<i64> {
+        let jan1 = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let date = match self.day {
+            Day::Julian(n) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let skip = i64::from(leap && n >= 60);
+                jan1 + chrono::Duration::days(n - 1 + skip)
+            }
+            Day::Ordinal(n) => jan1 + chrono::Duration::days(n),
+            Day::Month(month, week, weekday) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let next = if month == 12 {
+                    NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+                } else {
+                    NaiveDate::from_ymd_opt(year, month + 1, 1)?
+                };
+                let days = (next - first).num_days() as u32;
+                let lead = (weekday + 7 - first.weekday().num_days_from_sunday()) % 7;
+                let mut day = 1 + lead + (week - 1) * 7;
+                while day > days {
+                    day -= 7;
+                }
+                first.with_day(day)?
+            }
+        };
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() + self.time)
+    }
+}
+
+impl Rule {
+    fn at(&self, t: i64) -> &LocalType {
+        let Some((dst, start, end)) = &self.dst else {
+            return &self.std;
+        };
+        let Some(year) =
+            DateTime::from_timestamp(t + i64::from(self.std.offset), 0).map(|d| d.year())
+        else {
+            return &self.std;
+        };
+        let (Some(start), Some(end)) = (start.local(year), end.local(year)) else {
+            return &self.std;
+        };
+        // Each change happens on the clock in force until then.
+        let start = start - i64::from(self.std.offset);
+        let end = end - i64::from(dst.offset);
+        let in_dst = if start < end {
+            t >= start && t < end
+        } else {
+            // Southern hemisphere: summer spans the new year.
+            t >= start || t < end
+        };
+        if in_dst {
+            dst
+        } else {
+            &self.std
+        }
+    }
+}
+
+struct Posix<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Posix<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.i).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let hit = self.peek() == Some(c);
+        self.i += usize::from(hit);
+        hit
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start = self.i;
+        if self.eat(b'<') {
+            while self.peek().is_some_and(|c| c != b'>') {
+                self.i += 1;
+            }
+            let name = std::str::from_utf8(&self.s[start + 1..self.i])
+                .ok()?
+                .to_string();
+            self.eat(b'>').then_some(name)
+        } else {
+            while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.i += 1;
+            }
+            (self.i - start >= 3)
+                .then(|| String::from_utf8_lossy(&self.s[start..self.i]).into_owned())
+        }
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let start = self.i;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.i += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.i])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds.
+    fn clock(&mut self) -> Option<i64> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let mut secs = self.number()? * 3600;
+        for unit in [60, 1] {
+            if !self.eat(b':') {
+                break;
+            }
+            secs += self.number()? * unit;
+        }
+        Some(sign * secs)
+    }
+
+    fn transition(&mut self) -> Option<Transition> {
+        let day = if self.eat(b'J') {
+            Day::Julian(self.number().filter(|n| (1..=365).contains(n))?)
+        } else if self.eat(b'M') {
+            let month = self.number()? as u32;
+            self.eat(b'.').then_some(())?;
+            let week = self.number()? as u32;
+            self.eat(b'.').then_some(())?;
+            let weekday = self.number()? as u32;
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            Day::Month(month, week, weekday)
+        } else {
+            Day::Ordinal(self.number().filter(|n| (0..=365).contains(n))?)
+        };
+        let time = if self.eat(b'/') { self.clock()? } else { 7200 };
+        Some(Transition { day, time })
+    }
+}
+
+fn posix(raw: &str) -> Option<Rule> {
+    let mut p = Posix {
+        s: raw.trim().as_bytes(),
+        i: 0,
+    };
+    // POSIX offsets are hours west of Greenwich: `EST5` is UTC-5.
+    let std = LocalType {
+        abbr: p.name()?,
+        offset: -p.clock()? as i32,
+    };
+    if p.peek().is_none() {
+        return Some(Rule { std, dst: None });
+    }
+    let abbr = p.name()?;
+    let offset = match p.peek() {
+        Some(b',') | None => std.offset + 3600,
+        _ => -p.clock()? as i32,
+    };
+    let dst = LocalType { abbr, offset };
+    // A zone with summer time but no rule uses the US one.
+    let (start, end) = if p.eat(b',') {
+        let start = p.transition()?;
+        p.eat(b',').then_some(())?;
+        (start, p.transition()?)
+    } else {
+        (
+            Transition {
+                day: Day::Month(3, 2, 0),
+                time: 7200,
+            },
+            Transition {
+                day: Day::Month(11, 1, 0),
+                time: 7200,
+            },
+        )
+    };
+    p.peek().is_none().then_some(Rule {
+        std,
+        dst: Some((dst, start, end)),
+    })
+}
+
+/// A zone read from the IANA database (RFC 8536 TZif).
+pub struct Tz {
+    transitions: Vec<i64>,
+    kinds: Vec<usize>,
+    types: Vec<LocalType>,
+    rule: Option<Rule>,
+}
+
+impl Tz {
+    fn at(&self, t: i64) -> &LocalType {
+        let n = self.transitions.partition_point(|at| *at <= t);
+        if n == self.transitions.len() {
+            if let Some(rule) = &self.rule {
+                return rule.at(t);
+            }
+        }
+        match n {
+            0 => &self.types[0],
+            n => &self.types[self.kinds[n - 1]],
+        }
+    }
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn tzif_block(
+    b: &[u8],
+    at: usize,
+    width: usize,
+    [_, _, _, time, kinds, chars]: [usize; 6],
+) -> Option<Tz> {
+    let mut transitions = Vec::with_capacity(time);
+    for i in 0..time {
+        let p = at + i * width;
+        transitions.push(if width == 8 {
+            i64::from_be_bytes(b.get(p..p + 8)?.try_into().ok()?)
+        } else {
+            i64::from(be32(b, p)? as i32)
+        });
+    }
+    let index_at = at + time * width;
+    let indices: Vec<usize> = b
+        .get(index_at..index_at + time)?
+        .iter()
+        .map(|i| usize::from(*i))
+        .collect();
+    let types_at = index_at + time;
+    let abbrs = b.get(types_at + kinds * 6..types_at + kinds * 6 + chars)?;
+    let mut types = Vec::with_capacity(kinds);
+    for i in 0..kinds {
+        let p = types_at + i * 6;
+        let abbr = abbrs.get(usize::from(*b.get(p + 5)?)..)?;
+        let abbr = abbr.split(|c| *c == 0).next()?;
+        types.push(LocalType {
+            offset: be32(b, p)? as i32,
+            abbr: String::from_utf8_lossy(abbr).into_owned(),
+        });
+    }
+    if types.is_empty() || indices.iter().any(|i| *i >= types.len()) {
+        return None;
+    }
+    Some(Tz {
+        transitions,
+        kinds: indices,
+        types,
+        rule: None,
+    })
+}
+
+fn parse_tzif(b: &[u8]) -> Option<Tz> {
+    let counts = |at: usize| -> Option<[usize; 6]> {
+        if b.get(at..at + 4)? != b"TZif" {
+            return None;
+        }
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            *count = be32(b, at + 20 + i * 4)? as usize;
+        }
+        Some(counts)
+    };
+    // isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt.
+    let size = |[isut, isstd, leap, time, kinds, chars]: [usize; 6], width: usize| {
+        time * width + time + kinds * 6 + chars + leap * (width + 4) + isstd + isut
+    };
+    let v1 = counts(0)?;
+    if *b.get(4)? < b'2' {
+        return tzif_block(b, 44, 4, v1);
+    }
+    // Version 2 and later repeat the data with 64-bit times, then end with
+    // the rule for later times.
+    let start = 44 + size(v1, 4);
+    let v2 = counts(start)?;
+    let tz = tzif_block(b, start + 44, 8, v2)?;
+    let footer = b.get(start + 44 + size(v2, 8)..)?;
+    let rule = std::str::from_utf8(footer)
+        .ok()
+        .and_then(|f| f.trim_start_matches('\n').lines().next())
+        .filter(|r| !r.is_empty())
+        .and_then(posix);
+    Some(Tz { rule, ..tz })
+}
+
+fn zones() -> &'static Mutex<HashMap<String, Arc<Tz>>> {
+    static ZONES: OnceLock<Mutex<HashMap<String, Arc<Tz>>>> = OnceLock::new();
+    ZONES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn load(name: &str) -> Result<Arc<Tz>, String> {
+    let safe = !name.starts_with('/')
+        && !name.split('/').any(|part| part.is_empty() || part == "..")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '/'));
+    if !safe {
+        return Err(format!("`{}` is not a time zone name", name));
+    }
+    if let Some(tz) = zones().lock().ok().and_then(|z| z.get(name).cloned()) {
+        return Ok(tz);
+    }
+    let dir = ZONEINFO_DIRS
+        .iter()
+        .map(Path::new)
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| {
+            "Named time zones need the IANA time zone database, which this system does not have; use UTC or an offset like +02:00".to_string()
+        })?;
+    let path = dir.join(name);
+    let bytes = std::fs::metadata(&path)
+        .ok()
+        .filter(|m| m.is_file() && m.len() <= MAX_ZONE_BYTES)
+        .and_then(|_| std::fs::read(&path).ok())
+        .ok_or_else(|| format!("Unknown time zone `{}`", name))?;
+    let tz =
+        Arc::new(parse_tzif(&bytes).ok_or_else(|| format!("Time zone `{}` is unreadable", name))?);
+    if let Ok(mut zones) = zones().lock() {
+        zones.insert(name.to_string(), tz.clone());
+    }
+    Ok(tz)
+}
+
+/// A time zone to show times in.
+#[derive(Clone)]
+pub enum Zone {
+    /// The operating system's.
+    Local,
+    Fixed(FixedOffset),
+    Named(String, Arc<Tz>),
+}
+
+impl Zone {
+    pub fn name(&self) -> String {
+        match self {
+            Zone::Local => "local".to_string(),
+            Zone::Fixed(offset) => label(*offset, None),
+            Zone::Named(name, _) => name.clone(),
+        }
+    }
+
+    /// The offset in force at `at`, and its abbreviation when the zone
+    /// database has one.
+    fn offset(&self, at: DateTime<Utc>) -> (FixedOffset, Option<String>) {
+        match self {
+            Zone::Local => (Local.offset_from_utc_datetime(&at.naive_utc()).fix(), None),
+            Zone::Fixed(offset) => (*offset, None),
+            Zone::Named(_, tz) => {
+                let local = tz.at(at.timestamp());
+                let offset = FixedOffset::east_opt(local.offset).unwrap_or(Utc.fix());
+                (offset, Some(local.abbr.clone()))
+            }
+        }
+    }
+}
+
+/// `CEST` when the abbreviation is a real one, else `UTC+05:30`; the
+/// database writes zones without one as `+0530`.
+fn label(offset: FixedOffset, abbr: Option<&str>) -> String {
+    if let Some(abbr) = abbr.filter(|a| a.len() >= 2 && a.chars().all(|c| c.is_ascii_alphabetic()))
+    {
+        return abbr.to_string();
+    }
+    let secs = offset.local_minus_utc();
+    if secs == 0 {
+        return "UTC".to_string();
+    }
+    let sign = if secs < 0 { '-' } else { '+' };
+    let (hours, minutes) = (secs.abs() / 3600, secs.abs() % 3600 / 60);
+    if minutes == 0 {
+        format!("UTC{}{}", sign, hours)
+    } else {
+        format!("UTC{}{}:{:02}", sign, hours, minutes)
+    }
+}
+
+/// `local`, `UTC`, an offset (`+02:00`, `UTC-3`) or an IANA name.
+pub fn zone(raw: &str) -> Result<Zone, String> {
+    let raw = raw.trim();
+    if raw.is_empty() || raw.eq_ignore_ascii_case("local") {
+        return Ok(Zone::Local);
+    }
+    if ["utc", "gmt", "z", "etc/utc"]
+        .iter()
+        .any(|z| raw.eq_ignore_ascii_case(z))
+    {
+        return Ok(Zone::Fixed(Utc.fix()));
+    }
+    let bare = ["UTC", "GMT", "utc", "gmt"]
+        .iter()
+        .find_map(|p| raw.strip_prefix(p))
+        .unwrap_or(raw);
+    if let Some(sign) = bare.chars().next().filter(|c| matches!(c, '+' | '-')) {
+        let (hours, minutes) = bare[1..].split_once(':').unwrap_or(if bare.len() > 3 {
+            bare[1..].split_at(bare.len() - 3)
+        } else {
+            (&bare[1..], "0")
+        });
+        let offset = hours
+            .parse::<i32>()
+            .ok()
+            .zip(minutes.parse::<i32>().ok())
+            .filter(|(h, m)| *h <= 14 && *m < 60)
+            .and_then(|(h, m)| {
+                let secs = (h * 3600 + m * 60) * if sign == '-' { -1 } else { 1 };
+                FixedOffset::east_opt(secs)
+            })
+            .ok_or_else(|| format!("`{}` is not an offset between -14:00 and +14:00", raw))?;
+        return Ok(Zone::Fixed(offset));
+    }
+    Ok(Zone::Named(raw.to_string(), load(raw)?))
+}
+
+pub fn validate(settings: &FormattingSettings) -> Result<(), String> {
+    let locale = settings.locale.trim();
+    if !locale.is_empty() && !valid_tag(locale) {
+        return Err(format!(
+            "formatting.locale: `{}` is not a locale tag like de-DE",
+            locale
+        ));
+    }
+    zone(&settings.time_zone).map_err(|e| format!("formatting.time_zone: {}", e))?;
+    Ok(())
+}
+
+/// Formats times and numbers for one profile: in the operator's locale and
+/// time zone, with the core's own time alongside when it differs.
+pub struct Formatter {
+    locale: String,
+    source: &'static str,
+    style: &'static Style,
+    zone: Zone,
+    /// Unset when `core_time` is off.
+    core: Option<Zone>,
+    core_name: String,
+}
+
+impl Formatter {
+    pub fn for_profile(settings: &Settings, profile_id: Option<&str>) -> Self {
+        let configured = settings.formatting.locale.trim();
+        let (locale, source) = if !configured.is_empty() {
+            (configured.to_string(), "settings")
+        } else if let Some(tag) = system_locale().filter(|t| valid_tag(t)) {
+            (tag, "system")
+        } else {
+            ("und".to_string(), "default")
+        };
+        let core_name = settings
+            .profile(profile_id.unwrap_or(&settings.active_profile))
+            .map(|p| p.core_time_zone.trim().to_string())
+            .filter(|z| !z.is_empty())
+            .unwrap_or_else(|| "UTC".to_string());
+        Self {
+            style: lookup(&locale),
+            locale,
+            source,
+            zone: zone(&settings.formatting.time_zone).unwrap_or(Zone::Local),
+            core: settings
+                .formatting
+                .core_time
+                .then(|| zone(&core_name).ok())
+                .flatten(),
+            core_name,
+        }
+    }
+
+    fn wall(&self, zone: &Zone, at: DateTime<Utc>) -> (DateTime<FixedOffset>, String) {
+        let (offset, abbr) = zone.offset(at);
+        (at.with_timezone(&offset), label(offset, abbr.as_deref()))
+    }
+
+    /// `14/03/2026 09:30:00 CET`.
+    pub fn local_time(&self, at: DateTime<Utc>) -> String {
+        let (local, zone) = self.wall(&self.zone, at);
+        format!(
+            "{} {} {}",
+            local.format(self.style.date),
+            local.format(self.style.time),
+            zone
+        )
+    }
+
+    /// The core's clock, when it reads differently: just the time on the
+    /// same day, with the date otherwise.
+    pub fn core_time(&self, at: DateTime<Utc>) -> Option<String> {
+        let core = self.core.as_ref()?;
+        let (local, _) = self.wall(&self.zone, at);
+        let (remote, zone) = self.wall(core, at);
+        if local.offset() == remote.offset() {
+            return None;
+        }
+        Some(if local.date_naive() == remote.date_naive() {
+            format!("{} {}", remote.format(self.style.time), zone)
+        } else {
+            format!(
+                "{} {} {}",
+                remote.format(self.style.date),
+                remote.format(self.style.time),
+                zone
+            )
+        })
+    }
+
+    /// The local time, then the core's in brackets when it differs.
+    pub fn timestamp(&self, at: DateTime<Utc>) -> String {
+        match self.core_time(at) {
+            Some(core) => format!("{} (core {})", self.local_time(at), core),
+            None => self.local_time(at),
+        }
+    }
+
+    /// An RFC 3339 time from a core record or the audit log, formatted;
+    /// anything else is returned as it is.
+    pub fn when(&self, raw: &str) -> String {
+        DateTime::parse_from_rfc3339(raw.trim())
+            .map(|t| self.timestamp(t.with_timezone(&Utc)))
+            .unwrap_or_else(|_| raw.to_string())
+    }
+
+    pub fn number(&self, n: f64, decimals: usize) -> String {
+        if !n.is_finite() {
+            return n.to_string();
+        }
+        let fixed = format!("{:.*}", decimals, n.abs());
+        let (int, frac) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let digits: Vec<char> = int.chars().collect();
+        let mut grouped = String::new();
+        for (i, digit) in digits.iter().enumerate() {
+            let left = digits.len() - i;
+            let boundary = if self.style.indian && left > 3 {
+                (left - 3).is_multiple_of(2)
+            } else {
+                left.is_multiple_of(3)
+            };
+            if i > 0 && boundary {
+                grouped.push_str(self.style.group);
+            }
+            grouped.push(*digit);
+        }
+        let sign = if n < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            "-"
+        } else {
+            ""
+        };
+        if frac.is_empty() {
+            format!("{}{}", sign, grouped)
+        } else {
+            format!("{}{}{}{}", sign, grouped, self.style.decimal, frac)
+        }
+    }
+
+    /// `1h 05m`, `2m 30s`, `850ms`: the two largest units.
+    pub fn duration(&self, secs: f64) -> String {
+        if !secs.is_finite() {
+            return secs.to_string();
+        }
+        let sign = if secs < 0.0 { "-" } else { "" };
+        let secs = secs.abs();
+        if secs < 1.0 {
+            return format!("{}{}ms", sign, (secs * 1_000.0).round());
+        }
+        if secs < 60.0 {
+            let shown = if secs < 10.0 { 1 } else { 0 };
+            return format!("{}{}s", sign, self.number(secs, shown));
+        }
+        let total = secs.round() as u64;
+        let (days, hours, minutes, seconds) = (
+            total / 86_400,
+            total % 86_400 / 3600,
+            total % 3600 / 60,
+            total % 60,
+        );
+        if days > 0 {
+            format!("{}{}d {:02}h", sign, self.number(days as f64, 0), hours)
+        } else if hours > 0 {
+            format!("{}{}h {:02}m", sign, hours, minutes)
+        } else {
+            format!("{}{}m {:02}s", sign, minutes, seconds)
+        }
+    }
+
+    /// Binary units, as the resource report counts them.
+    pub fn bytes(&self, n: f64) -> String {
+        const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+        if n.abs() < 1024.0 {
+            return format!("{} B", self.number(n, 0));
+        }
+        let mut value = n / 1024.0;
+        let mut unit = 0;
+        while value.abs() >= 1024.0 && unit + 1 < UNITS.len() {
+            value /= 1024.0;
+            unit += 1;
+        }
+        format!("{} {}", self.number(value, 1), UNITS[unit])
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "locale": self.locale,
+            "locale_source": self.source,
+            "time_zone": self.zone.name(),
+            "core_time_zone": self.core_name,
+            "core_time": self.core.is_some(),
+        })
+    }
+}
+
+/// An RFC 3339 string, or seconds (or, past 1e11, milliseconds) since the
+/// epoch.
+fn instant(raw: &Value) -> Option<DateTime<Utc>> {
+    match raw {
+        Value::String(s) => DateTime::parse_from_rfc3339(s.trim())
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+        Value::Number(n) => {
+            let n = n.as_f64()?;
+            let millis = if n.abs() > 1e11 { n } else { n * 1_000.0 };
+            DateTime::from_timestamp_millis(millis as i64)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormatItem {
+    /// `timestamp`, `duration` (seconds), `number` or `bytes`.
+    pub kind: String,
+    pub value: Value,
+    #[serde(default)]
+    pub decimals: Option<usize>,
+}
+
+/// Formats values the way the shell's reports and exports do, so the
+/// frontend shows the same text: in the locale from settings (else the
+/// OS's) and the chosen time zone, with each time also in the profile's
+/// core time zone when `core_time` is on and it differs. Without `items`,
+/// reports the effective settings and the time now.
+#[tauri::command]
+pub fn format_values(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    items: Option<Vec<FormatItem>>,
+) -> Result<Value, String> {
+    let items = items.unwrap_or_default();
+    if items.len() > MAX_ITEMS {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "At most {} values can be formatted at once",
+            MAX_ITEMS
+        )));
+    }
+    let settings = store.snapshot();
+    let id = profile.as_deref().unwrap_or(&settings.active_profile);
+    if settings.profile(id).is_none() {
+        return Err(ErrorCode::NotFound.with(format!("Unknown profile: {}", id)));
+    }
+    let formatter = Formatter::for_profile(&settings, Some(id));
+    let stamp = |at: DateTime<Utc>| {
+        json!({
+            "text": formatter.local_time(at),
+            "core_text": formatter.core_time(at),
+            "iso": at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        })
+    };
+    let values: Vec<Value> = items
+        .iter()
+        .map(|item| {
+            let number = item.value.as_f64();
+            let text = match (item.kind.as_str(), number) {
+                ("timestamp", _) => {
+                    return instant(&item.value).map_or_else(
+                        || json!({ "error": "Not an RFC 3339 time or epoch seconds" }),
+                        stamp,
+                    )
+                }
+                ("duration", Some(n)) => formatter.duration(n),
+                ("number", Some(n)) => formatter.number(n, item.decimals.unwrap_or(0).min(12)),
+                ("bytes", Some(n)) => formatter.bytes(n),
+                ("duration" | "number" | "bytes", None) => {
+                    return json!({ "error": "Not a number" })
+                }
+                (other, _) => return json!({ "error": format!("Unknown kind '{}'", other) }),
+            };
+            json!({ "text": text })
+        })
+        .collect();
+    let mut report = formatter.describe();
+    report["profile_id"] = json!(id);
+    report["now"] = stamp(Utc::now());
+    report["values"] = json!(values);
+    Ok(report)
+}
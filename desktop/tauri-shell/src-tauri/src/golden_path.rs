@@ -0,0 +1,436 @@
+//! The shell's decision paths run end to end against fake cores: every
+//! request goes through `request_json` exactly as a profile's would.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::airgap;
+use crate::db::LocalDb;
+use crate::decisions;
+use crate::errors::{self, ErrorCode};
+use crate::fake_core::{self, FakeCore, Fault, Script};
+use crate::settings::{Connection, ReplayMode, ReplaySettings, SettingsStore};
+
+/// The profile id the offline scenario's bundles are made for; no
+/// settings profile has it, so nothing it records lands in a real
+/// profile's history.
+const PROFILE: &str = "golden-path";
+const LATENCY: Duration = Duration::from_millis(150);
+
+fn pending(id: &str) -> Value {
+    json!({
+        "id": id,
+        "objective": format!("Golden path plan {}", id),
+        "agent": "golden-path",
+        "risk": "low",
+        "steps": [{ "action": "noop", "description": "Does nothing" }],
+    })
+}
+
+fn script(ids: &[&str]) -> Script {
+    Script {
+        plans: ids.iter().map(|id| pending(id)).collect(),
+        operator: Some("golden-path".to_string()),
+        ..Script::default()
+    }
+}
+
+fn fault(method: &str, path: &str, kind: &str, status: u16) -> Fault {
+    Fault {
+        method: Some(method.to_string()),
+        path: path.to_string(),
+        kind: kind.to_string(),
+        status,
+        times: Some(1),
+    }
+}
+
+fn expect(ok: bool, what: impl Into<String>) -> Result<(), String> {
+    if ok {
+        Ok(())
+    } else {
+        Err(what.into())
+    }
+}
+
+fn expect_code(result: Result<Value, String>, code: ErrorCode) -> Result<String, String> {
+    match result {
+        Ok(value) => Err(format!(
+            "expected {}, got success: {}",
+            code.as_str(),
+            value
+        )),
+        Err(err) if errors::code_of(&err) == Some(code) => Ok(err),
+        Err(err) => Err(format!("expected {}, got {}", code.as_str(), err)),
+    }
+}
+
+fn status(fake: &FakeCore, id: &str) -> Value {
+    fake.plan(id).map_or(Value::Null, |p| p["status"].clone())
+}
+
+async fn get(base: &str, path: &str) -> Result<Value, String> {
+    crate::request_json(Method::GET, base, path, None, None).await
+}
+
+/// Approves at the version shown and requires the core to record it.
+async fn approve(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    let shown = get(base, "/plans/p-approve").await?;
+    let version = decisions::plan_version(&shown);
+    decisions::send_decision(
+        base,
+        None,
+        "p-approve",
+        "approve",
+        Some(json!({ "execute": false })),
+        version.as_deref(),
+        None,
+    )
+    .await?;
+    let after = fake.plan("p-approve").unwrap_or_default();
+    expect(
+        after["status"] == "approved",
+        format!("plan is {} after approving", after["status"]),
+    )?;
+    expect(
+        decisions::plan_version(&after) != version,
+        "approving did not change the plan's version",
+    )?;
+    Ok(json!({ "status": after["status"], "version": after["version"] }))
+}
+
+async fn reject(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    let reason = "Golden path rejection";
+    decisions::send_decision(
+        base,
+        None,
+        "p-reject",
+        "reject",
+        Some(json!({ "reason": reason })),
+        None,
+        None,
+    )
+    .await?;
+    let after = fake.plan("p-reject").unwrap_or_default();
+    expect(
+        after["status"] == "rejected",
+        format!("plan is {} after rejecting", after["status"]),
+    )?;
+    expect(
+        after["rejection_reason"] == reason,
+        "the core did not keep the rejection reason",
+    )?;
+    Ok(json!({ "status": after["status"], "reason": after["rejection_reason"] }))
+}
+
+/// A plan edited after it was shown must not be approved blind.
+async fn stale_version(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    let shown = get(base, "/plans/p-stale").await?;
+    let version = decisions::plan_version(&shown);
+    fake.edit_plan("p-stale", |p| {
+        p["objective"] = json!("Edited by another client")
+    });
+    let err = expect_code(
+        decisions::send_decision(
+            base,
+            None,
+            "p-stale",
+            "approve",
+            None,
+            version.as_deref(),
+            None,
+        )
+        .await,
+        ErrorCode::PlanConflict,
+    )?;
+    expect(
+        status(fake, "p-stale") == "pending",
+        "the stale approval still reached the plan",
+    )?;
+    Ok(json!({ "shown_version": version, "error": err }))
+}
+
+/// Connection failures surface as their own codes and the next request
+/// goes through once the core is back.
+async fn unreachable(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    fake.inject(fault("GET", "/plans", "unreachable", 0));
+    let refused = expect_code(get(base, "/plans").await, ErrorCode::CoreUnreachable)?;
+    fake.inject(fault("GET", "/plans", "timeout", 0));
+    let timed_out = expect_code(get(base, "/plans").await, ErrorCode::CoreTimeout)?;
+    let listed = get(base, "/plans").await?;
+    expect(
+        listed.as_array().is_some_and(|p| !p.is_empty()),
+        "the core did not answer after recovering",
+    )?;
+    Ok(json!({ "unreachable": refused, "timeout": timed_out }))
+}
+
+/// A failed decision leaves the plan pending and can be sent again.
+async fn core_error(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    fake.inject(fault("POST", "/plans/p-error", "status", 500));
+    let err = expect_code(
+        decisions::send_decision(base, None, "p-error", "approve", None, None, None).await,
+        ErrorCode::CoreError,
+    )?;
+    expect(
+        status(fake, "p-error") == "pending",
+        "the plan moved on although the core failed",
+    )?;
+    decisions::send_decision(base, None, "p-error", "approve", None, None, None).await?;
+    expect(
+        status(fake, "p-error") == "approved",
+        "the retried approval did not land",
+    )?;
+    Ok(json!({ "first_attempt": err }))
+}
+
+async fn latency(base: &str, fake: &FakeCore) -> Result<Value, String> {
+    fake.set_latency(LATENCY);
+    let started = Instant::now();
+    let answered = get(base, "/health").await;
+    let took = started.elapsed();
+    fake.set_latency(Duration::ZERO);
+    answered?;
+    expect(
+        took >= LATENCY,
+        format!(
+            "answered in {} ms, under the scripted {} ms",
+            took.as_millis(),
+            LATENCY.as_millis()
+        ),
+    )?;
+    Ok(json!({ "scripted_ms": LATENCY.as_millis() as u64, "took_ms": took.as_millis() as u64 }))
+}
+
+/// Walks `/plans` page by page against a core that caps every page.
+async fn pagination(base: &str, ids: &[&str]) -> Result<Value, String> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut pages = 0;
+    loop {
+        let page = get(base, &format!("/plans?limit=50&offset={}", seen.len())).await?;
+        let page = page.as_array().cloned().unwrap_or_default();
+        if page.is_empty() {
+            break;
+        }
+        pages += 1;
+        expect(page.len() <= 3, format!("a page held {} plans", page.len()))?;
+        seen.extend(
+            page.iter()
+                .filter_map(|p| p["id"].as_str().map(str::to_string)),
+        );
+        expect(pages <= ids.len(), "paging did not end")?;
+    }
+    expect(
+        seen == ids,
+        format!("paging returned {:?}, expected {:?}", seen, ids),
+    )?;
+    Ok(json!({ "pages": pages, "plans": seen.len() }))
+}
+
+fn path_in(dir: &Path, name: &str) -> String {
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+/// The air-gap round trip with two local databases standing for the
+/// connected and the offline machine: export, import, decide offline,
+/// carry the decisions back and apply them once. A plan edited while the
+/// bundle was away must come back as a conflict rather than be decided.
+async fn offline_queue(base: &str, fake: &FakeCore, dir: &Path) -> Result<Value, String> {
+    let store = SettingsStore::in_memory();
+    let connected = LocalDb::in_memory(json!({ "encrypted": false, "persistent": false }));
+    let offline = LocalDb::in_memory(json!({ "encrypted": false, "persistent": false }));
+    let conn = Connection {
+        profile_id: PROFILE.to_string(),
+        base_url: base.to_string(),
+        token: None,
+    };
+    let bundle_path = path_in(dir, "plans.bundle");
+    let decisions_path = path_in(dir, "decisions.bundle");
+    let exported = airgap::export_bundle(None, &connected, &conn, &bundle_path, None).await?;
+    let trusted = vec![exported["public_key"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()];
+    let imported = airgap::import_bundle(&store, &offline, &trusted, &bundle_path)?;
+    let bundle_id = imported["bundle_id"]
+        .as_str()
+        .or(exported["bundle_id"].as_str())
+        .unwrap_or_default()
+        .to_string();
+    for (plan_id, decision, after) in [
+        ("p-off-reject", "reject", vec![]),
+        ("p-off-approve", "approve", vec!["p-off-reject".to_string()]),
+        ("p-off-changed", "approve", vec![]),
+    ] {
+        airgap::record_offline_decision(
+            &offline,
+            bundle_id.clone(),
+            plan_id.to_string(),
+            decision.to_string(),
+            Some("Reviewed offline".to_string()),
+            after,
+        )?;
+    }
+    airgap::write_decision_file(&offline, &bundle_id, &decisions_path)?;
+    fake.edit_plan("p-off-changed", |p| {
+        p["objective"] = json!("Edited while offline")
+    });
+
+    let replay = ReplaySettings {
+        mode: ReplayMode::Parallel,
+        max_parallel: 2,
+    };
+    let connection = |_: &str| Ok(conn.clone());
+    let applied =
+        airgap::apply_decision_file(&connected, &trusted, &replay, connection, &decisions_path)
+            .await?;
+    expect(
+        applied["applied"] == 2 && applied["failed"] == 1,
+        format!(
+            "applied {} and failed {}",
+            applied["applied"], applied["failed"]
+        ),
+    )?;
+    expect(
+        status(fake, "p-off-approve") == "approved" && status(fake, "p-off-reject") == "rejected",
+        "the offline decisions did not reach the core",
+    )?;
+    let result_of = |plan_id: &str| {
+        applied["results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|r| r["plan_id"] == plan_id)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (rejected, approved) = (result_of("p-off-reject"), result_of("p-off-approve"));
+    expect(
+        matches!(
+            (rejected["round"].as_u64(), approved["round"].as_u64()),
+            (Some(first), Some(then)) if first < then
+        ),
+        format!(
+            "the approval waiting on the rejection went out in round {} against {}",
+            approved["round"], rejected["round"]
+        ),
+    )?;
+    let changed = result_of("p-off-changed");
+    expect(
+        errors::code_of(changed["error"].as_str().unwrap_or_default())
+            == Some(ErrorCode::PlanConflict),
+        format!("the edited plan came back as {}", changed),
+    )?;
+    expect(
+        status(fake, "p-off-changed") == "pending",
+        "the edited plan was decided anyway",
+    )?;
+    let again = airgap::apply_decision_file(
+        &connected,
+        &trusted,
+        &replay,
+        |_: &str| Ok(conn.clone()),
+        &decisions_path,
+    )
+    .await;
+    expect(again.is_err(), "the decision file was applied twice")?;
+    Ok(
+        json!({ "bundle_id": bundle_id, "applied": applied["applied"], "failed": applied["failed"] }),
+    )
+}
+
+/// A fake core at `mock://golden-<scenario>`, stopped when dropped.
+struct Core {
+    name: String,
+    base: String,
+    fake: Arc<FakeCore>,
+}
+
+impl Core {
+    fn start(scenario: &str, script: Script) -> Self {
+        let name = format!("golden-{}", scenario.replace('_', "-"));
+        let (base, fake) = fake_core::start(&name, script).expect("fake core starts");
+        Self { name, base, fake }
+    }
+}
+
+impl Drop for Core {
+    fn drop(&mut self) {
+        let _ = fake_core::stop(&self.name);
+    }
+}
+
+fn passed(scenario: &str, outcome: Result<Value, String>) {
+    if let Err(err) = outcome {
+        panic!("{}: {}", scenario, err);
+    }
+}
+
+#[tokio::test]
+async fn approves_and_bumps_the_version() {
+    let core = Core::start("approve", script(&["p-approve"]));
+    passed("approve", approve(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn rejects_with_the_reason() {
+    let core = Core::start("reject", script(&["p-reject"]));
+    passed("reject", reject(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn stale_version_is_a_conflict() {
+    let core = Core::start("stale_version", script(&["p-stale"]));
+    passed("stale_version", stale_version(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn recovers_from_an_unreachable_core() {
+    let core = Core::start("unreachable", script(&["p-listed"]));
+    passed("unreachable", unreachable(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn retries_after_a_core_error() {
+    let core = Core::start("core_error", script(&["p-error"]));
+    passed("core_error", core_error(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn waits_out_scripted_latency() {
+    let core = Core::start("latency", script(&[]));
+    passed("latency", latency(&core.base, &core.fake).await);
+}
+
+#[tokio::test]
+async fn walks_every_page() {
+    let ids = ["p-1", "p-2", "p-3", "p-4", "p-5", "p-6", "p-7", "p-8"];
+    let core = Core::start(
+        "pagination",
+        Script {
+            page_size: Some(3),
+            ..script(&ids)
+        },
+    );
+    passed("pagination", pagination(&core.base, &ids).await);
+}
+
+#[tokio::test]
+async fn applies_the_offline_queue_once() {
+    let core = Core::start(
+        "offline_queue",
+        script(&["p-off-approve", "p-off-reject", "p-off-changed"]),
+    );
+    let dir = std::env::temp_dir().join(format!(
+        "novaadapt-golden-path-{}",
+        chrono::Utc::now().timestamp_millis()
+    ));
+    std::fs::create_dir_all(&dir).expect("temporary directory");
+    let outcome = offline_queue(&core.base, &core.fake, &dir).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    passed("offline_queue", outcome);
+}
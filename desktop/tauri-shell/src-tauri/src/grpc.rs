@@ -0,0 +1,248 @@
+use crate::transport::{CoreRequest, CoreTransport, TransportFuture, GRPC};
+
+/// Whether this build has the gRPC transport (the `grpc` feature).
+pub const SUPPORTED: bool = cfg!(feature = "grpc");
+
+/// `grpc://host:port` (plaintext) or `grpcs://host:port` (TLS): a core
+/// behind a gRPC gateway. Requests travel as JSON-framed unary calls, the
+/// same convention as the core's `native-grpc` runtime, so no generated
+/// protobuf code is needed on either side:
+///
+/// - `novaadapt.core.CoreGateway/Call`:
+///   `{"method", "path", "headers", "body"}` ->
+///   `{"status", "headers", "body"}`, with `text` instead of `body` for a
+///   response that is not JSON.
+///
+/// The bearer token goes in the `authorization` metadata. Like a socket
+/// core, the profile's proxy, resolver, TLS policy and other auth modes do
+/// not apply; `grpcs` checks the server against the bundled web roots.
+pub struct GrpcTransport;
+
+#[cfg(feature = "grpc")]
+mod imp {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+
+    use bytes::{Buf, BufMut};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+    use reqwest::StatusCode;
+    use serde_json::{json, Map, Value};
+    use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::metadata::MetadataValue;
+    use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+    use tonic::{Code, Status};
+
+    use crate::errors::ErrorCode;
+    use crate::transport::{CoreRequest, CoreResponse, GRPCS};
+    use crate::{net, spill, ssrf};
+
+    const CALL_PATH: &str = "/novaadapt.core.CoreGateway/Call";
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Largest response message taken from a gRPC core.
+    const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+    static CHANNELS: OnceLock<Mutex<HashMap<String, Channel>>> = OnceLock::new();
+
+    fn channels() -> &'static Mutex<HashMap<String, Channel>> {
+        CHANNELS.get_or_init(|| Mutex::new(HashMap::new()))
+    }
+
+    /// Serializes messages as JSON rather than protobuf.
+    struct JsonCodec;
+    struct JsonEncoder;
+    struct JsonDecoder;
+
+    impl Codec for JsonCodec {
+        type Encode = Value;
+        type Decode = Value;
+        type Encoder = JsonEncoder;
+        type Decoder = JsonDecoder;
+
+        fn encoder(&mut self) -> JsonEncoder {
+            JsonEncoder
+        }
+
+        fn decoder(&mut self) -> JsonDecoder {
+            JsonDecoder
+        }
+    }
+
+    impl Encoder for JsonEncoder {
+        type Item = Value;
+        type Error = Status;
+
+        fn encode(&mut self, item: Value, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+            serde_json::to_writer(dst.writer(), &item)
+                .map_err(|e| Status::internal(format!("Encode request failed: {}", e)))
+        }
+    }
+
+    impl Decoder for JsonDecoder {
+        type Item = Value;
+        type Error = Status;
+
+        fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Value>, Status> {
+            if !src.has_remaining() {
+                return Ok(Some(Value::Null));
+            }
+            serde_json::from_reader(src.reader())
+                .map(Some)
+                .map_err(|e| Status::internal(format!("Core sent invalid JSON: {}", e)))
+        }
+    }
+
+    /// The origin's channel, connecting on first use and reconnecting by
+    /// itself after the core goes away.
+    fn channel(request: &CoreRequest) -> Result<Channel, String> {
+        let url = &request.url;
+        let key = net::base_origin(url.as_str())
+            .ok_or_else(|| ErrorCode::InvalidInput.with(format!("Invalid base URL: {}", url)))?;
+        let mut channels = channels()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("gRPC channel state poisoned"))?;
+        if let Some(channel) = channels.get(&key) {
+            return Ok(channel.clone());
+        }
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port().unwrap_or_default();
+        let tls = url.scheme() == GRPCS;
+        let target = format!("{}://{}:{}", if tls { "https" } else { "http" }, host, port);
+        let init_err = |e: tonic::transport::Error| {
+            ErrorCode::Internal.with(format!("gRPC channel init failed: {}", e))
+        };
+        let mut endpoint = Endpoint::from_shared(target)
+            .map_err(init_err)?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(net::REQUEST_TIMEOUT);
+        if tls {
+            endpoint = endpoint
+                .tls_config(
+                    ClientTlsConfig::new()
+                        .with_webpki_roots()
+                        .domain_name(host.trim_matches(['[', ']'])),
+                )
+                .map_err(init_err)?;
+        }
+        let channel = endpoint.connect_lazy();
+        channels.insert(key, channel.clone());
+        Ok(channel)
+    }
+
+    fn call_error(status: Status) -> String {
+        let detail = format!("gRPC {:?}: {}", status.code(), status.message());
+        match status.code() {
+            Code::DeadlineExceeded => ErrorCode::CoreTimeout.with(detail),
+            Code::Unavailable | Code::Cancelled | Code::Unknown => {
+                ErrorCode::CoreUnreachable.with(detail)
+            }
+            _ => ErrorCode::CoreError.with(detail),
+        }
+    }
+
+    /// Authentication failures come back as the HTTP statuses they stand
+    /// for, so token refresh and role handling treat both transports alike.
+    fn status_response(status: &Status) -> Option<StatusCode> {
+        match status.code() {
+            Code::Unauthenticated => Some(StatusCode::UNAUTHORIZED),
+            Code::PermissionDenied => Some(StatusCode::FORBIDDEN),
+            _ => None,
+        }
+    }
+
+    fn headers_of(value: &Value) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in value.as_object().into_iter().flatten() {
+            let Some(value) = value.as_str() else {
+                continue;
+            };
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        headers
+    }
+
+    pub async fn send(request: CoreRequest) -> Result<CoreResponse, String> {
+        ssrf::check_url(&request.base_url, &request.url)?;
+        let channel = channel(&request)?;
+        let headers: Map<String, Value> = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), json!(value)))
+            .collect();
+        let mut call = tonic::Request::new(json!({
+            "method": request.method.as_str(),
+            "path": request.path(),
+            "headers": headers,
+            "body": request.body,
+        }));
+        call.set_timeout(net::REQUEST_TIMEOUT);
+        if let Some(token) = request.token.as_deref().filter(|t| !t.is_empty()) {
+            let value = MetadataValue::try_from(format!("Bearer {}", token))
+                .map_err(|_| ErrorCode::InvalidInput.with("Token is not valid metadata"))?;
+            call.metadata_mut().insert("authorization", value);
+        }
+        let mut client = tonic::client::Grpc::new(channel).max_decoding_message_size(MAX_MESSAGE);
+        client.ready().await.map_err(|e| {
+            ErrorCode::CoreUnreachable.with(format!("gRPC core unreachable: {}", e))
+        })?;
+        let answer = match client
+            .unary(call, PathAndQuery::from_static(CALL_PATH), JsonCodec)
+            .await
+        {
+            Ok(answer) => answer.into_inner(),
+            Err(status) => match status_response(&status) {
+                Some(code) => json!({
+                    "status": code.as_u16(),
+                    "body": { "error": status.message() },
+                }),
+                None => return Err(call_error(status)),
+            },
+        };
+        let status = answer["status"]
+            .as_u64()
+            .and_then(|s| u16::try_from(s).ok())
+            .and_then(|s| StatusCode::from_u16(s).ok())
+            .ok_or_else(|| ErrorCode::CoreError.with("gRPC core answered without a status"))?;
+        let mut headers = headers_of(&answer["headers"]);
+        let body = match answer["text"].as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                if !headers.contains_key(CONTENT_TYPE) {
+                    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                }
+                answer["body"].to_string()
+            }
+        };
+        Ok(CoreResponse {
+            status,
+            headers,
+            url: request.url,
+            body: spill::Body::Buffered(body),
+        })
+    }
+}
+
+impl CoreTransport for GrpcTransport {
+    fn name(&self) -> &'static str {
+        GRPC
+    }
+
+    #[cfg(feature = "grpc")]
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(imp::send(request))
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    fn send(&self, _request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(async {
+            Err(crate::errors::ErrorCode::InvalidInput
+                .with("gRPC cores need a build with the `grpc` feature"))
+        })
+    }
+}
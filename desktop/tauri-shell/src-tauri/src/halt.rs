@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::decisions::ScheduledDecisions;
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{budget, crypto, environment, notify};
+
+const HALTED_EVENT: &str = "runtime:halted";
+const CONFIRM_WINDOW: Duration = Duration::from_secs(30);
+const HALT_REASON: &str = "Emergency halt from desktop";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaltScope {
+    /// Cancels every queued and running job and pauses the runtime.
+    StopAll,
+    /// Pauses the runtime so no new runs start; running jobs finish.
+    Pause,
+}
+
+impl HaltScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            HaltScope::StopAll => "stop_all",
+            HaltScope::Pause => "pause",
+        }
+    }
+}
+
+/// Confirmation codes handed out by the first `emergency_halt` call, keyed by
+/// profile. A code is single-use and only valid for its scope.
+#[derive(Default)]
+pub struct HaltConfirmations {
+    pending: Mutex<HashMap<String, (String, HaltScope, Instant)>>,
+}
+
+impl HaltConfirmations {
+    fn issue(&self, profile_id: &str, scope: HaltScope) -> Result<String, String> {
+        let code: String = crypto::random_bytes::<3>()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        self.pending
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Halt state poisoned"))?
+            .insert(
+                profile_id.to_string(),
+                (code.clone(), scope, Instant::now()),
+            );
+        Ok(code)
+    }
+
+    fn redeem(&self, profile_id: &str, scope: HaltScope, code: &str) -> bool {
+        let Ok(mut pending) = self.pending.lock() else {
+            return false;
+        };
+        pending
+            .remove(profile_id)
+            .is_some_and(|(expected, issued_for, at)| {
+                expected.eq_ignore_ascii_case(code.trim())
+                    && issued_for == scope
+                    && at.elapsed() <= CONFIRM_WINDOW
+            })
+    }
+}
+
+/// Kill switch for a profile's core. Called without `confirmation` it only
+/// returns a short-lived code; called again with that code it drops the
+/// shell's scheduled decisions, pauses auto-approval, and posts the halt
+/// straight to the core (no undo window, no idempotent replay). Every
+/// confirmed attempt is recorded in the local audit trail, failed or not.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn emergency_halt(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    confirmations: State<'_, HaltConfirmations>,
+    scheduled: State<'_, ScheduledDecisions>,
+    profile: Option<String>,
+    scope: Option<HaltScope>,
+    confirmation: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let scope = scope.unwrap_or(HaltScope::StopAll);
+    let Some(code) = confirmation else {
+        let code = confirmations.issue(&conn.profile_id, scope)?;
+        return Ok(json!({
+            "profile_id": conn.profile_id,
+            "scope": scope.as_str(),
+            "confirmation_required": true,
+            "confirmation": code,
+            "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+        }));
+    };
+    if !confirmations.redeem(&conn.profile_id, scope, &code) {
+        return Err(ErrorCode::InvalidInput.with("Halt confirmation is invalid or has expired"));
+    }
+
+    let dropped_decisions = scheduled.cancel_all(&app);
+    let paused_locally =
+        budget::set_auto_approval_paused(&app, &store, &conn.profile_id, true).is_ok();
+    let (path, payload) = match scope {
+        HaltScope::StopAll => (
+            "/runtime/jobs/cancel_all",
+            json!({ "pause": true, "pause_reason": HALT_REASON }),
+        ),
+        HaltScope::Pause => (
+            "/runtime/governance",
+            json!({ "paused": true, "pause_reason": HALT_REASON }),
+        ),
+    };
+    let result = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        path,
+        conn.token.clone(),
+        Some(payload),
+    )
+    .await;
+
+    let out = json!({
+        "profile_id": conn.profile_id,
+        "scope": scope.as_str(),
+        "halted": result.is_ok(),
+        "dropped_decisions": dropped_decisions,
+        "auto_approval_paused": paused_locally,
+        "core": result.as_ref().ok(),
+        "error": result.as_ref().err(),
+    });
+    db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "runtime",
+        "emergency_halt",
+        scope.as_str(),
+        &out,
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+
+    let _ = environment::emit(&app, HALTED_EVENT, &out);
+    match &result {
+        Ok(_) => notify::notify(
+            &app,
+            "runtime",
+            "Emergency halt sent",
+            "The core has been told to stop; auto-approval is paused",
+            out.clone(),
+        ),
+        Err(err) => notify::notify(
+            &app,
+            "runtime",
+            "Emergency halt failed",
+            &format!("The core did not confirm the halt: {}", err),
+            out.clone(),
+        ),
+    }
+    result.map(|_| out)
+}
@@ -0,0 +1,394 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::formatting::Formatter;
+use crate::metrics;
+use crate::settings::SettingsStore;
+use crate::tenants;
+
+const DEFAULT_RANGE: &str = "8h";
+const MAX_RANGE_DAYS: i64 = 31;
+/// Rows read per section; a shift rarely comes close.
+const SECTION_LIMIT: usize = 500;
+/// Plan statuses a decision leaves behind, and where its time is recorded.
+const DECIDED: &[(&str, &[&str])] = &[
+    ("approved", &["approved_at", "decided_at"]),
+    ("executing", &["approved_at", "decided_at"]),
+    ("running", &["approved_at", "decided_at"]),
+    ("completed", &["approved_at", "decided_at"]),
+    ("succeeded", &["approved_at", "decided_at"]),
+    ("failed", &["approved_at", "decided_at"]),
+    ("rejected", &["rejected_at", "decided_at"]),
+];
+const FAILED: &[&str] = &["failed", "error", "errored", "aborted"];
+/// Local audit categories that are decisions taken in the shell.
+pub const DECISION_CATEGORIES: &[&str] = &["approval", "approval_schedule", "step_gate"];
+/// Local audit categories that are incidents.
+const INCIDENT_CATEGORIES: &[&str] = &["runtime", "execution", "stall"];
+const HEALTH_CATEGORY: &str = "health";
+
+fn stamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn timestamp(raw: &Value) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw.as_str()?)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// `8h`-style ranges end now; `start/end` takes two RFC 3339 times.
+pub fn parse_range(raw: &str, max_days: i64) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let raw = raw.trim();
+    let (since, until) = match raw.split_once('/') {
+        Some((start, end)) => {
+            let parse = |v: &str| timestamp(&Value::String(v.trim().to_string()));
+            parse(start)
+                .zip(parse(end))
+                .ok_or_else(|| ErrorCode::InvalidInput.with("Range times must be RFC 3339"))?
+        }
+        None => {
+            let span = metrics::parse_range(raw).ok_or_else(|| {
+                ErrorCode::InvalidInput.with("Range must look like `8h`, `12h` or `start/end`")
+            })?;
+            let span = chrono::Duration::from_std(span)
+                .map_err(|_| ErrorCode::InvalidInput.with("Range is too long"))?;
+            let now = Utc::now();
+            (now - span, now)
+        }
+    };
+    if since >= until {
+        return Err(ErrorCode::InvalidInput.with("Range must end after it starts"));
+    }
+    if until - since > chrono::Duration::days(max_days) {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("Range must be at most {} days", max_days))
+        );
+    }
+    Ok((since, until))
+}
+
+pub fn cached(conn: &Connection, profile_id: &str, kind: &str) -> rusqlite::Result<Vec<Value>> {
+    let mut stmt =
+        conn.prepare("SELECT payload FROM sync_entities WHERE profile_id = ?1 AND kind = ?2")?;
+    let scope = tenants::cache_scope(profile_id);
+    let rows = stmt.query_map(params![scope, kind], |row| row.get::<_, String>(0))?;
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|raw| serde_json::from_str(&raw).ok())
+        .collect())
+}
+
+pub fn audit_rows(
+    conn: &Connection,
+    profile_id: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> rusqlite::Result<Vec<Value>> {
+    // `at` is RFC 3339 written by this shell, so it orders as text.
+    let mut stmt = conn.prepare(
+        "SELECT at, category, action, entity_id, detail FROM audit_log
+         WHERE profile_id = ?1 AND at >= ?2 AND at <= ?3 ORDER BY at LIMIT ?4",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            profile_id,
+            since.to_rfc3339(),
+            until.to_rfc3339(),
+            (SECTION_LIMIT * 4) as i64
+        ],
+        |row| {
+            let detail: String = row.get(4)?;
+            Ok(json!({
+                "at": row.get::<_, String>(0)?,
+                "category": row.get::<_, String>(1)?,
+                "action": row.get::<_, String>(2)?,
+                "entity_id": row.get::<_, String>(3)?,
+                "detail": serde_json::from_str::<Value>(&detail).unwrap_or(Value::Null),
+            }))
+        },
+    )?;
+    rows.collect()
+}
+
+/// Puts each time in `fields` beside itself as `<field>_text`, formatted
+/// for the operator.
+fn annotate(items: &mut [Value], fields: &[&str], formatter: &Formatter) {
+    for item in items {
+        for field in fields {
+            if let Some(raw) = item[*field].as_str() {
+                item[format!("{}_text", field)] = json!(formatter.when(raw));
+            }
+        }
+    }
+}
+
+pub fn id_of(item: &Value) -> String {
+    match &item["id"] {
+        Value::String(id) => id.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+pub fn status_of(item: &Value) -> String {
+    item["status"].as_str().unwrap_or("").to_ascii_lowercase()
+}
+
+pub fn objective(plan: &Value) -> Value {
+    ["objective", "title", "summary"]
+        .iter()
+        .find_map(|k| plan[*k].as_str().filter(|s| !s.is_empty()))
+        .map_or(Value::Null, |s| json!(s))
+}
+
+/// When the plan was decided, if the cached record says and it was within
+/// the range.
+pub fn decided_at(
+    plan: &Value,
+    status: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let (_, fields) = DECIDED.iter().find(|(s, _)| *s == status)?;
+    fields
+        .iter()
+        .find_map(|f| timestamp(&plan[*f]))
+        .filter(|at| *at >= since && *at <= until)
+}
+
+/// Everything a shift left behind for the next operator, from the local
+/// plan and job cache and the shell's audit trail, so it works offline:
+/// plans decided in the range, plans still pending (oldest first, with
+/// their deadlines), incidents (failed plans and jobs, halts, aborts,
+/// stalls), core health changes and the rest of the shell's activity.
+/// `range` is a span ending now (`8h`, the default) or `start/end`.
+#[tauri::command]
+pub fn generate_handover(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    range: Option<String>,
+) -> Result<Value, String> {
+    let (since, until) = parse_range(range.as_deref().unwrap_or(DEFAULT_RANGE), MAX_RANGE_DAYS)?;
+    let conn = store.connection(profile.as_deref())?;
+    let profile_id = conn.profile_id;
+    let guard = db.lock()?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let plans = cached(&guard, &profile_id, "plan").map_err(storage)?;
+    let jobs = cached(&guard, &profile_id, "job").map_err(storage)?;
+    let audit = audit_rows(&guard, &profile_id, since, until).map_err(storage)?;
+    let deadlines: Vec<(String, String)> = {
+        let mut stmt = guard
+            .prepare(
+                "SELECT plan_id, expires_at FROM plan_deadlines
+                 WHERE profile_id = ?1 AND expired_at IS NULL",
+            )
+            .map_err(storage)?;
+        let rows = stmt
+            .query_map([&profile_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(storage)?;
+        rows.filter_map(Result::ok).collect()
+    };
+    let mut scheduled: Vec<Value> = {
+        let mut stmt = guard
+            .prepare(
+                "SELECT id, plan_id, execute, fire_at FROM scheduled_approvals
+                 WHERE profile_id = ?1 AND status IN ('scheduled', 'conflict') ORDER BY fire_at",
+            )
+            .map_err(storage)?;
+        let rows = stmt
+            .query_map([&profile_id], |row| {
+                Ok(json!({
+                    "id": row.get::<_, i64>(0)?,
+                    "plan_id": row.get::<_, String>(1)?,
+                    "execute": row.get::<_, bool>(2)?,
+                    "fire_at": row.get::<_, String>(3)?,
+                }))
+            })
+            .map_err(storage)?;
+        rows.filter_map(Result::ok).collect()
+    };
+    drop(guard);
+
+    let mut decisions: Vec<Value> = plans
+        .iter()
+        .filter_map(|plan| {
+            let status = status_of(plan);
+            let at = decided_at(plan, &status, since, until)?;
+            Some(json!({
+                "at": stamp(at),
+                "plan_id": id_of(plan),
+                "objective": objective(plan),
+                "decision": if status == "rejected" { "rejected" } else { "approved" },
+                "status": status,
+                "source": "core",
+            }))
+        })
+        .collect();
+    decisions.extend(
+        audit
+            .iter()
+            .filter(|row| {
+                row["category"]
+                    .as_str()
+                    .is_some_and(|c| DECISION_CATEGORIES.contains(&c))
+            })
+            .map(|row| {
+                json!({
+                    "at": row["at"],
+                    "plan_id": row["entity_id"],
+                    "decision": row["action"],
+                    "category": row["category"],
+                    "detail": row["detail"],
+                    "source": "shell",
+                })
+            }),
+    );
+    decisions.sort_by(|a, b| a["at"].as_str().cmp(&b["at"].as_str()));
+    decisions.truncate(SECTION_LIMIT);
+
+    let now = Utc::now();
+    let mut pending: Vec<Value> = plans
+        .iter()
+        .filter(|plan| status_of(plan) == "pending")
+        .map(|plan| {
+            let id = id_of(plan);
+            let created = timestamp(&plan["created_at"]);
+            json!({
+                "plan_id": id,
+                "objective": objective(plan),
+                "risk": plan["risk"].as_str().or(plan["risk_level"].as_str()),
+                "created_at": created.map(stamp),
+                "age_minutes": created.map(|c| (now - c).num_minutes()),
+                "expires_at": deadlines
+                    .iter()
+                    .find(|(plan_id, _)| *plan_id == id)
+                    .map(|(_, at)| at.clone()),
+                "scheduled": scheduled
+                    .iter()
+                    .find(|s| s["plan_id"].as_str() == Some(id.as_str()))
+                    .cloned(),
+            })
+        })
+        .collect();
+    pending.sort_by(|a, b| a["created_at"].as_str().cmp(&b["created_at"].as_str()));
+    pending.truncate(SECTION_LIMIT);
+
+    let failed = |kind: &'static str| {
+        move |item: &Value| {
+            let status = status_of(item);
+            if !FAILED.contains(&status.as_str()) {
+                return None;
+            }
+            let at = ["finished_at", "executed_at", "updated_at"]
+                .iter()
+                .find_map(|f| timestamp(&item[*f]))
+                .filter(|at| *at >= since && *at <= until)?;
+            Some(json!({
+                "at": stamp(at),
+                "kind": kind,
+                "entity_id": id_of(item),
+                "status": status,
+                "error": item["error"].as_str().or(item["last_error"].as_str()),
+                "source": "core",
+            }))
+        }
+    };
+    let mut incidents: Vec<Value> = plans
+        .iter()
+        .filter_map(failed("plan"))
+        .chain(jobs.iter().filter_map(failed("job")))
+        .collect();
+    incidents.extend(
+        audit
+            .iter()
+            .filter(|row| {
+                row["category"]
+                    .as_str()
+                    .is_some_and(|c| INCIDENT_CATEGORIES.contains(&c))
+            })
+            .map(|row| {
+                json!({
+                    "at": row["at"],
+                    "kind": row["category"],
+                    "action": row["action"],
+                    "entity_id": row["entity_id"],
+                    "detail": row["detail"],
+                    "source": "shell",
+                })
+            }),
+    );
+    incidents.sort_by(|a, b| a["at"].as_str().cmp(&b["at"].as_str()));
+    incidents.truncate(SECTION_LIMIT);
+
+    let mut health: Vec<Value> = audit
+        .iter()
+        .filter(|row| row["category"].as_str() == Some(HEALTH_CATEGORY))
+        .map(|row| {
+            json!({
+                "at": row["at"],
+                "from": row["detail"]["from"],
+                "to": row["detail"]["to"],
+            })
+        })
+        .take(SECTION_LIMIT)
+        .collect();
+
+    let mut activity: Vec<Value> = audit
+        .into_iter()
+        .filter(|row| {
+            row["category"].as_str().is_some_and(|c| {
+                !DECISION_CATEGORIES.contains(&c)
+                    && !INCIDENT_CATEGORIES.contains(&c)
+                    && c != HEALTH_CATEGORY
+            })
+        })
+        .collect();
+    let activity_truncated = activity.len() > SECTION_LIMIT;
+    activity.truncate(SECTION_LIMIT);
+
+    let active_jobs = jobs
+        .iter()
+        .filter(|job| matches!(status_of(job).as_str(), "queued" | "running"))
+        .count();
+    let formatter = Formatter::for_profile(&store.snapshot(), Some(&profile_id));
+    annotate(&mut decisions, &["at"], &formatter);
+    annotate(&mut pending, &["created_at", "expires_at"], &formatter);
+    annotate(&mut scheduled, &["fire_at"], &formatter);
+    annotate(&mut incidents, &["at"], &formatter);
+    annotate(&mut health, &["at"], &formatter);
+    annotate(&mut activity, &["at"], &formatter);
+    Ok(json!({
+        "profile_id": profile_id,
+        "range": {
+            "since": stamp(since),
+            "until": stamp(until),
+            "since_text": formatter.timestamp(since),
+            "until_text": formatter.timestamp(until),
+        },
+        "generated_at": stamp(now),
+        "generated_at_text": formatter.timestamp(now),
+        "summary": {
+            "decisions": decisions.len(),
+            "approved": decisions.iter().filter(|d| d["decision"] == "approved").count(),
+            "rejected": decisions.iter().filter(|d| d["decision"] == "rejected").count(),
+            "pending": pending.len(),
+            "scheduled": scheduled.len(),
+            "incidents": incidents.len(),
+            "health_changes": health.len(),
+            "active_jobs": active_jobs,
+        },
+        "decisions": decisions,
+        "pending": pending,
+        "scheduled": scheduled,
+        "incidents": incidents,
+        "health_changes": health,
+        "activity": activity,
+        "activity_truncated": activity_truncated,
+    }))
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::{Method, Request, Response, Version};
+
+use crate::{net, watchdog};
+
+/// Whether this build can speak HTTP/3 (the `http3` feature, built with
+/// `RUSTFLAGS="--cfg reqwest_unstable"` as reqwest requires).
+pub const SUPPORTED: bool = cfg!(feature = "http3");
+
+/// How long an origin stays on TCP after a QUIC exchange with it failed.
+const COOLDOWN: Duration = Duration::from_secs(10 * 60);
+/// How recently QUIC must have worked for an origin before requests that
+/// must not be sent twice go over it.
+const TRUSTED_FOR: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy)]
+enum Path {
+    Working(Instant),
+    Failed(Instant),
+}
+
+static PATHS: OnceLock<Mutex<HashMap<String, Path>>> = OnceLock::new();
+
+fn paths() -> &'static Mutex<HashMap<String, Path>> {
+    PATHS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Requests that can be sent again over TCP when QUIC fails part way,
+/// because the core may already have acted on them otherwise.
+pub fn retry_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Whether a `method` request to `origin` should try QUIC first. An origin
+/// that failed recently stays on TCP until its cooldown ends; decisions and
+/// other requests that must not repeat only use a path seen working, so a
+/// blocked UDP port costs a read, never a duplicated approval.
+pub fn should_try(origin: &str, method: &Method) -> bool {
+    let path = paths().lock().ok().and_then(|p| p.get(origin).copied());
+    match path {
+        Some(Path::Failed(at)) => at.elapsed() >= COOLDOWN,
+        Some(Path::Working(at)) => retry_safe(method) || at.elapsed() < TRUSTED_FOR,
+        None => retry_safe(method),
+    }
+}
+
+pub fn succeeded(origin: &str) {
+    if let Ok(mut paths) = paths().lock() {
+        paths.insert(origin.to_string(), Path::Working(Instant::now()));
+    }
+}
+
+pub fn failed(origin: &str) {
+    if let Ok(mut paths) = paths().lock() {
+        paths.insert(origin.to_string(), Path::Failed(Instant::now()));
+    }
+}
+
+/// Sends `request` like `watchdog::execute`, over HTTP/3 first when its
+/// profile opted in and the origin's QUIC path is worth trying, then over
+/// `client` on TCP. A QUIC failure sends the origin back to TCP for a while;
+/// the request itself is only resent over TCP when that is safe.
+pub async fn execute(
+    client: &reqwest::Client,
+    request: Request,
+    base_url: &str,
+) -> reqwest::Result<Response> {
+    let quic = net::quic_client_for(request.url()).ok().flatten();
+    let origin = net::base_origin(request.url().as_str());
+    if let (Some(quic), Some(origin)) = (quic, origin) {
+        let attempt = should_try(&origin, request.method())
+            .then(|| request.try_clone())
+            .flatten();
+        if let Some(mut attempt) = attempt {
+            *attempt.version_mut() = Version::HTTP_3;
+            match watchdog::execute(&quic, attempt, base_url).await {
+                Ok(response) => {
+                    succeeded(&origin);
+                    return Ok(response);
+                }
+                Err(err) => {
+                    failed(&origin);
+                    if !retry_safe(request.method()) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+    watchdog::execute(client, request, base_url).await
+}
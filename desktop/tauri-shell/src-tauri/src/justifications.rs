@@ -0,0 +1,359 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::decisions::{PlanRead, DEFAULT_REJECT_REASON};
+use crate::errors::ErrorCode;
+use crate::net;
+use crate::settings::{
+    JustificationPolicy, JustificationRule, Profile, ReasonTemplate, SettingsStore,
+};
+
+const DECISIONS: &[&str] = &["approve", "reject"];
+const MAX_RULES: usize = 50;
+const MAX_TEMPLATES: usize = 100;
+const MAX_LABEL_LEN: usize = 80;
+const MAX_TEXT_LEN: usize = 1000;
+
+/// Profiles with a policy, by the origin of each endpoint, with the
+/// template library.
+static CONFIG: OnceLock<Mutex<Config>> = OnceLock::new();
+
+#[derive(Default)]
+struct Config {
+    policies: HashMap<String, (String, JustificationPolicy)>,
+    templates: Vec<ReasonTemplate>,
+}
+
+fn config() -> &'static Mutex<Config> {
+    CONFIG.get_or_init(|| Mutex::new(Config::default()))
+}
+
+pub fn configure(profiles: &[Profile], templates: &[ReasonTemplate]) {
+    let policies = profiles
+        .iter()
+        .filter(|p| !p.justification.rules.is_empty())
+        .flat_map(|p| {
+            p.endpoints()
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, (p.id.clone(), p.justification.clone())))
+        })
+        .collect();
+    *config().lock().unwrap_or_else(|e| e.into_inner()) = Config {
+        policies,
+        templates: templates.to_vec(),
+    };
+}
+
+fn valid_decision(decision: &Option<String>) -> bool {
+    decision
+        .as_deref()
+        .is_none_or(|d| DECISIONS.contains(&d.trim().to_ascii_lowercase().as_str()))
+}
+
+pub fn validate_policy(policy: &JustificationPolicy) -> Result<(), String> {
+    if policy.rules.len() > MAX_RULES {
+        return Err(format!("At most {} justification rules", MAX_RULES));
+    }
+    for rule in &policy.rules {
+        if !valid_decision(&rule.decision) {
+            return Err(format!(
+                "Justification rule decision must be approve or reject, got {:?}",
+                rule.decision
+            ));
+        }
+        if rule.min_length == 0 || rule.min_length > MAX_TEXT_LEN {
+            return Err(format!(
+                "Justification min_length must be 1 to {}",
+                MAX_TEXT_LEN
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_templates(templates: &[ReasonTemplate]) -> Result<(), String> {
+    if templates.len() > MAX_TEMPLATES {
+        return Err(format!("At most {} reason templates", MAX_TEMPLATES));
+    }
+    let mut ids = HashSet::new();
+    for template in templates {
+        if template.id.trim().is_empty() || !ids.insert(template.id.as_str()) {
+            return Err("Reason template ids must be unique and non-empty".to_string());
+        }
+        if template.label.trim().is_empty() || template.label.len() > MAX_LABEL_LEN {
+            return Err(format!(
+                "Reason template {}: label must be 1 to {} bytes",
+                template.id, MAX_LABEL_LEN
+            ));
+        }
+        if template.text.trim().is_empty() || template.text.len() > MAX_TEXT_LEN {
+            return Err(format!(
+                "Reason template {}: text must be 1 to {} bytes",
+                template.id, MAX_TEXT_LEN
+            ));
+        }
+        if !valid_decision(&template.decision) {
+            return Err(format!(
+                "Reason template {}: decision must be approve or reject",
+                template.id
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn for_decision(field: &Option<String>, decision: &str) -> bool {
+    field
+        .as_deref()
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .is_none_or(|f| f.eq_ignore_ascii_case(decision))
+}
+
+/// Where a decision's justification goes in its payload.
+fn field_of(decision: &str) -> &'static str {
+    if decision == "reject" {
+        "reason"
+    } else {
+        "justification"
+    }
+}
+
+fn matches(rule: &JustificationRule, decision: &str, plan: &Value) -> bool {
+    let wanted = |field: &Option<String>| {
+        field
+            .as_deref()
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+    };
+    let first_of = |keys: &[&str]| keys.iter().find_map(|k| plan[*k].as_str());
+    let same = |want: &str, have: Option<&str>| have.is_some_and(|h| h.eq_ignore_ascii_case(want));
+    if !for_decision(&rule.decision, decision) {
+        return false;
+    }
+    if wanted(&rule.risk).is_some_and(|risk| !same(&risk, first_of(&["risk", "risk_level"]))) {
+        return false;
+    }
+    if wanted(&rule.agent).is_some_and(|agent| !same(&agent, first_of(&["agent", "agent_id"]))) {
+        return false;
+    }
+    if let Some(tag) = wanted(&rule.tag) {
+        let tags: Vec<&str> = match &plan["tags"] {
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            _ => plan["tag"].as_str().into_iter().collect(),
+        };
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            return false;
+        }
+    }
+    true
+}
+
+fn templates_for(templates: &[ReasonTemplate], decision: Option<&str>) -> Vec<ReasonTemplate> {
+    templates
+        .iter()
+        .filter(|t| decision.is_none_or(|d| for_decision(&t.decision, d)))
+        .cloned()
+        .collect()
+}
+
+/// The profile and the length its policy wants for `decision` on `plan_id`,
+/// or `None` when no rule covers it. The plan is only read when a rule
+/// could apply.
+async fn requirement(
+    plan: &mut PlanRead<'_>,
+    decision: &str,
+) -> Result<Option<(String, usize)>, String> {
+    let origin = net::base_origin(plan.base_url()).unwrap_or_default();
+    let Some((profile_id, policy)) = config()
+        .lock()
+        .ok()
+        .and_then(|config| config.policies.get(&origin).cloned())
+    else {
+        return Ok(None);
+    };
+    let rules: Vec<&JustificationRule> = policy
+        .rules
+        .iter()
+        .filter(|rule| for_decision(&rule.decision, decision))
+        .collect();
+    if rules.is_empty() {
+        return Ok(None);
+    }
+    let plan = plan.get().await?;
+    let needed = rules
+        .iter()
+        .filter(|rule| matches(rule, decision, plan))
+        .map(|rule| rule.min_length)
+        .max();
+    Ok(needed.map(|min_length| (profile_id, min_length)))
+}
+
+/// Fills in the payload's justification (`reason` for a rejection) from
+/// its `reason_template` unless the operator wrote one, or refuses a
+/// template that is unknown or meant for the other decision.
+pub fn fill_template(decision: &str, payload: &mut Option<Value>) -> Result<(), String> {
+    let field = field_of(decision);
+    let chosen = payload
+        .as_ref()
+        .and_then(|body| body["reason_template"].as_str())
+        .filter(|id| !id.is_empty())
+        .map(str::to_string);
+    let Some(id) = chosen else {
+        return Ok(());
+    };
+    let templates = config()
+        .lock()
+        .map(|config| config.templates.clone())
+        .unwrap_or_default();
+    let template = templates
+        .iter()
+        .find(|t| t.id == id && for_decision(&t.decision, decision))
+        .ok_or_else(|| {
+            ErrorCode::InvalidInput.with(format!("Unknown {} reason template: {}", decision, id))
+        })?;
+    if let Some(Value::Object(body)) = payload.as_mut() {
+        let written = body[field]
+            .as_str()
+            .is_some_and(|text| !text.trim().is_empty() && text != DEFAULT_REJECT_REASON);
+        if !written {
+            body.insert(field.to_string(), json!(template.text));
+        }
+    }
+    Ok(())
+}
+
+/// Fills in the payload's `reason_template`, then holds `decision` to the
+/// profile's policy: a plan one of its rules covers needs a justification
+/// (`reason` for a rejection) of the rule's length, or the call fails with
+/// `JUSTIFICATION_REQUIRED` listing the templates that fit. The stock
+/// rejection reason does not count.
+pub async fn guard(
+    plan: &mut PlanRead<'_>,
+    decision: &str,
+    payload: &mut Option<Value>,
+) -> Result<(), String> {
+    fill_template(decision, payload)?;
+    let field = field_of(decision);
+    let Some((profile_id, min_length)) = requirement(plan, decision).await? else {
+        return Ok(());
+    };
+    let given = payload
+        .as_ref()
+        .and_then(|body| body[field].as_str())
+        .map(str::trim)
+        .filter(|text| *text != DEFAULT_REJECT_REASON)
+        .map_or(0, |text| text.chars().count());
+    if given >= min_length {
+        return Ok(());
+    }
+    let templates = config()
+        .lock()
+        .map(|config| config.templates.clone())
+        .unwrap_or_default();
+    Err(ErrorCode::JustificationRequired.with(json!({
+        "message": format!(
+            "This plan needs a justification of at least {} characters to {}",
+            min_length, decision
+        ),
+        "plan_id": plan.plan_id(),
+        "profile_id": profile_id,
+        "decision": decision,
+        "field": field,
+        "min_length": min_length,
+        "templates": templates_for(&templates, Some(decision)),
+    })))
+}
+
+/// The reason templates, or those usable for `decision`.
+#[tauri::command]
+pub fn list_reason_templates(
+    store: State<'_, SettingsStore>,
+    decision: Option<String>,
+) -> Result<Value, String> {
+    let decision = decision.map(|d| d.trim().to_ascii_lowercase());
+    let templates = templates_for(&store.snapshot().reason_templates, decision.as_deref());
+    Ok(json!({ "templates": templates }))
+}
+
+/// Adds `template`, or replaces the one with its id; a new template
+/// without an id gets one.
+#[tauri::command]
+pub fn upsert_reason_template(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    mut template: ReasonTemplate,
+) -> Result<Value, String> {
+    template.label = template.label.trim().to_string();
+    template.text = template.text.trim().to_string();
+    template.decision = template
+        .decision
+        .map(|d| d.trim().to_ascii_lowercase())
+        .filter(|d| !d.is_empty());
+    if template.id.trim().is_empty() {
+        let hex: String = crate::crypto::random_bytes::<8>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        template.id = format!("reason-{}", hex);
+    }
+    store.update(&app, |settings| {
+        match settings
+            .reason_templates
+            .iter_mut()
+            .find(|t| t.id == template.id)
+        {
+            Some(existing) => *existing = template.clone(),
+            None => settings.reason_templates.push(template.clone()),
+        }
+        Ok(())
+    })?;
+    Ok(json!(template))
+}
+
+#[tauri::command]
+pub fn delete_reason_template(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+) -> Result<Value, String> {
+    store.update(&app, |settings| {
+        let before = settings.reason_templates.len();
+        settings.reason_templates.retain(|t| t.id != id);
+        if settings.reason_templates.len() == before {
+            return Err(ErrorCode::NotFound.with(format!("Unknown reason template: {}", id)));
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "deleted": id }))
+}
+
+/// Whether approving or rejecting `plan_id` needs a justification under
+/// the profile's policy, so the window can ask for one before sending.
+#[tauri::command]
+pub async fn get_justification_requirement(
+    base_url: String,
+    token: Option<String>,
+    plan_id: String,
+    decision: String,
+) -> Result<Value, String> {
+    let decision = crate::decisions::validate_decision(&decision, &plan_id)?;
+    let mut plan = PlanRead::new(&base_url, token.as_deref(), &plan_id);
+    let needed = requirement(&mut plan, &decision).await?;
+    let templates = config()
+        .lock()
+        .map(|config| templates_for(&config.templates, Some(&decision)))
+        .unwrap_or_default();
+    Ok(json!({
+        "plan_id": plan_id,
+        "decision": decision,
+        "required": needed.is_some(),
+        "min_length": needed.map(|(_, min)| min),
+        "field": field_of(&decision),
+        "templates": templates,
+    }))
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64URL;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::settings::SettingsStore;
+use crate::{environment, notify, tasks};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const EXPIRING_EVENT: &str = "token:expiring";
+const EXPIRED_EVENT: &str = "token:expired";
+const DEFAULT_WARNING_MINUTES: u32 = 10;
+
+/// Last warning raised per profile, keyed by the token's `exp` so a
+/// refreshed token is watched afresh.
+#[derive(Default)]
+pub struct TokenAlerts {
+    raised: Mutex<HashMap<String, (i64, &'static str)>>,
+}
+
+const TASK: &str = "token_expiry";
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            check_all(&app);
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Decodes the profile's stored token without verifying its signature, for
+/// display only: expiry, scopes, tenant and subject. Opaque (non-JWT) tokens
+/// are reported as such.
+#[tauri::command]
+pub fn inspect_token(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let Some(token) = conn.token.as_deref().filter(|t| !t.trim().is_empty()) else {
+        return Ok(json!({ "profile_id": conn.profile_id, "present": false }));
+    };
+    let mut out = match decode_claims(token) {
+        Some(claims) => describe(&claims, Utc::now()),
+        None => json!({ "jwt": false }),
+    };
+    out["profile_id"] = json!(conn.profile_id);
+    out["present"] = json!(true);
+    Ok(out)
+}
+
+pub fn decode_claims(token: &str) -> Option<Value> {
+    let mut parts = token.trim().split('.');
+    let (_header, payload) = (parts.next()?, parts.next()?);
+    parts.next()?;
+    let bytes = B64URL.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .filter(Value::is_object)
+}
+
+fn describe(claims: &Value, now: DateTime<Utc>) -> Value {
+    let exp = claims["exp"].as_i64();
+    let timestamp = |v: Option<i64>| {
+        v.and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|ts| ts.to_rfc3339())
+    };
+    let tenant = ["tid", "tenant", "tenant_id", "org_id"]
+        .iter()
+        .map(|k| &claims[*k])
+        .find(|v| !v.is_null());
+    json!({
+        "jwt": true,
+        "subject": claims["sub"],
+        "issuer": claims["iss"],
+        "audience": claims["aud"],
+        "tenant": tenant,
+        "scopes": scopes(claims),
+        "issued_at": timestamp(claims["iat"].as_i64()),
+        "expires_at": timestamp(exp),
+        "expires_in_seconds": exp.map(|e| e - now.timestamp()),
+        "expired": exp.is_some_and(|e| e <= now.timestamp()),
+    })
+}
+
+/// Scopes as a list, from an OAuth `scope` string or a `scp`/`scopes`/
+/// `permissions` claim in either string or array form.
+pub fn scopes(claims: &Value) -> Vec<String> {
+    ["scope", "scp", "scopes", "permissions"]
+        .iter()
+        .find_map(|key| match &claims[*key] {
+            Value::String(s) => Some(s.split_whitespace().map(str::to_string).collect()),
+            Value::Array(items) => Some(
+                items
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Warns once per token as it enters each profile's warning window, and
+/// again once it has expired.
+fn check_all(app: &AppHandle) {
+    let store = app.state::<SettingsStore>();
+    let alerts = app.state::<TokenAlerts>();
+    let now = Utc::now();
+    for profile in store.snapshot().profiles {
+        let Ok(conn) = store.connection(Some(&profile.id)) else {
+            continue;
+        };
+        let Some(exp) = conn
+            .token
+            .as_deref()
+            .and_then(decode_claims)
+            .and_then(|claims| claims["exp"].as_i64())
+        else {
+            continue;
+        };
+        let remaining = exp - now.timestamp();
+        let window = i64::from(
+            profile
+                .token_warning_minutes
+                .unwrap_or(DEFAULT_WARNING_MINUTES),
+        ) * 60;
+        let level = if remaining <= 0 {
+            "expired"
+        } else if remaining <= window {
+            "expiring"
+        } else {
+            continue;
+        };
+        let fresh = alerts
+            .raised
+            .lock()
+            .map(|mut raised| {
+                let previous = raised.insert(profile.id.clone(), (exp, level));
+                previous != Some((exp, level))
+            })
+            .unwrap_or(false);
+        if !fresh {
+            continue;
+        }
+        let payload = json!({
+            "profile_id": profile.id,
+            "expires_at": DateTime::from_timestamp(exp, 0).map(|ts| ts.to_rfc3339()),
+            "expires_in_seconds": remaining.max(0),
+        });
+        if level == "expired" {
+            let _ = environment::emit(app, EXPIRED_EVENT, &payload);
+            notify::notify(
+                app,
+                "token",
+                "Session expired",
+                &format!("The token for {} has expired; sign in again", profile.name),
+                payload,
+            );
+        } else {
+            let _ = environment::emit(app, EXPIRING_EVENT, &payload);
+            notify::notify(
+                app,
+                "token",
+                "Session expiring soon",
+                &format!(
+                    "The token for {} expires in {} min",
+                    profile.name,
+                    (remaining + 59) / 60
+                ),
+                payload,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(claims: &str) -> String {
+        format!("e30.{}.sig", B64URL.encode(claims))
+    }
+
+    #[test]
+    fn claims_are_read_from_the_payload() {
+        let claims = decode_claims(&token(r#"{"sub":"ops","exp":1700000000}"#)).unwrap();
+        assert_eq!(claims["sub"], "ops");
+        assert_eq!(claims["exp"], 1700000000);
+        let padded = format!("e30.{}==.sig", B64URL.encode(r#"{"sub":"a"}"#));
+        assert_eq!(decode_claims(&padded).unwrap()["sub"], "a");
+        assert!(decode_claims(&format!("  {}\n", token("{}"))).is_some());
+    }
+
+    #[test]
+    fn opaque_and_malformed_tokens_have_no_claims() {
+        assert!(decode_claims("opaque-api-key").is_none());
+        assert!(decode_claims(&token("{}").replace(".sig", "")).is_none());
+        assert!(decode_claims("e30.!!!.sig").is_none());
+        assert!(decode_claims(&token("[1,2]")).is_none());
+        assert!(decode_claims(&token("not json")).is_none());
+    }
+
+    #[test]
+    fn scopes_are_read_in_any_common_form() {
+        assert_eq!(
+            scopes(&json!({ "scope": "plans:read  plans:write" })),
+            ["plans:read", "plans:write"]
+        );
+        assert_eq!(scopes(&json!({ "scp": ["a", 1, "b"] })), ["a", "b"]);
+        assert_eq!(scopes(&json!({ "permissions": "admin" })), ["admin"]);
+        assert!(scopes(&json!({ "sub": "x" })).is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, SERVER};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{net, tasks};
+
+const TASK: &str = "latency";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+/// Samples held between flushes; beyond this the newest are dropped.
+const MAX_BUFFERED: usize = 10_000;
+const VERSION_HEADER: &str = "x-novaadapt-version";
+const DEFAULT_WINDOW_HOURS: u32 = 24;
+const MAX_WINDOW_HOURS: u32 = 24 * 30;
+/// Without a core upgrade to compare against, the baseline is this many
+/// days before the window.
+const BASELINE_DAYS: i64 = 7;
+/// An endpoint is flagged when its p95 grew by both bounds, over at least
+/// `MIN_SAMPLES` calls on each side.
+const MIN_SAMPLES: usize = 20;
+const DEGRADED_RATIO: f64 = 1.5;
+const DEGRADED_MIN_MS: i64 = 100;
+
+struct Sample {
+    origin: String,
+    endpoint: String,
+    at_ms: i64,
+    ms: i64,
+    status: u16,
+    core_version: String,
+}
+
+static BUFFER: OnceLock<Mutex<Vec<Sample>>> = OnceLock::new();
+
+fn buffer() -> &'static Mutex<Vec<Sample>> {
+    BUFFER.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// `/plans/3f2a.../approve` → `/plans/{id}/approve`: segments carrying a
+/// digit (other than a `v2`-style version) or long enough to be an id are
+/// folded, so one endpoint gets one series.
+pub fn endpoint(method: &Method, path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let segments: Vec<&str> = path
+        .split('/')
+        .map(|segment| {
+            let version = segment.len() > 1
+                && segment.starts_with('v')
+                && segment[1..].bytes().all(|b| b.is_ascii_digit());
+            if !version && (segment.bytes().any(|b| b.is_ascii_digit()) || segment.len() >= 24) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    format!("{} {}", method.as_str(), segments.join("/"))
+}
+
+/// The core build that answered, from `X-NovaAdapt-Version` or else its
+/// `Server` header.
+fn core_version(headers: &HeaderMap) -> String {
+    headers
+        .get(VERSION_HEADER)
+        .or_else(|| headers.get(SERVER))
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Records how long the core took to answer a request sent at `sent_at`.
+/// Samples are buffered here and written to the local database by the
+/// flusher.
+pub fn observe(
+    base_url: &str,
+    method: &Method,
+    path: &str,
+    status: u16,
+    sent_at: DateTime<Utc>,
+    headers: &HeaderMap,
+) {
+    let Some(origin) = net::base_origin(base_url) else {
+        return;
+    };
+    let Ok(mut buffer) = buffer().lock() else {
+        return;
+    };
+    if buffer.len() >= MAX_BUFFERED {
+        return;
+    }
+    buffer.push(Sample {
+        origin,
+        endpoint: endpoint(method, path),
+        at_ms: sent_at.timestamp_millis(),
+        ms: (Utc::now() - sent_at).num_milliseconds().max(0),
+        status,
+        core_version: core_version(headers),
+    });
+}
+
+fn flush(conn: &mut Connection) -> rusqlite::Result<usize> {
+    let samples = match buffer().lock() {
+        Ok(mut buffer) => std::mem::take(&mut *buffer),
+        Err(_) => return Ok(0),
+    };
+    if samples.is_empty() {
+        return Ok(0);
+    }
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO latency_samples (origin, endpoint, at_ms, ms, status, core_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for s in &samples {
+            stmt.execute(params![
+                s.origin,
+                s.endpoint,
+                s.at_ms,
+                s.ms,
+                s.status,
+                s.core_version
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(samples.len())
+}
+
+/// Writes buffered latency samples to the local database every
+/// `FLUSH_INTERVAL`.
+pub fn spawn_flusher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let db = app.state::<LocalDb>();
+            let result = match db.lock() {
+                Ok(mut conn) => flush(&mut conn).map_err(|e| e.to_string()),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(_) => tasks::ran(TASK),
+                Err(err) => tasks::failed(TASK, &err),
+            }
+        }
+    });
+}
+
+/// Nearest-rank percentile of sorted durations.
+fn percentile(sorted: &[i64], p: f64) -> i64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Default)]
+struct Series {
+    ms: Vec<i64>,
+    errors: usize,
+}
+
+impl Series {
+    fn summary(&mut self) -> Value {
+        self.ms.sort_unstable();
+        let count = self.ms.len();
+        json!({
+            "count": count,
+            "p50_ms": percentile(&self.ms, 50.0),
+            "p90_ms": percentile(&self.ms, 90.0),
+            "p95_ms": percentile(&self.ms, 95.0),
+            "p99_ms": percentile(&self.ms, 99.0),
+            "max_ms": self.ms.last().copied().unwrap_or(0),
+            "error_rate": if count == 0 { 0.0 } else { self.errors as f64 / count as f64 },
+        })
+    }
+}
+
+fn load(
+    conn: &Connection,
+    origin: &str,
+    from_ms: i64,
+    until_ms: i64,
+    core_version: Option<&str>,
+) -> rusqlite::Result<BTreeMap<String, Series>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint, ms, status FROM latency_samples
+         WHERE origin = ?1 AND at_ms >= ?2 AND at_ms < ?3
+           AND (?4 IS NULL OR core_version = ?4)",
+    )?;
+    let mut series: BTreeMap<String, Series> = BTreeMap::new();
+    let rows = stmt.query_map(params![origin, from_ms, until_ms, core_version], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, u16>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (endpoint, ms, status) = row?;
+        let entry = series.entry(endpoint).or_default();
+        entry.ms.push(ms);
+        if status >= 500 {
+            entry.errors += 1;
+        }
+    }
+    Ok(series)
+}
+
+/// The core version most recently seen before `before_ms` that differs
+/// from `current`, and when it was last seen.
+fn previous_version(
+    conn: &Connection,
+    origin: &str,
+    current: &str,
+    before_ms: i64,
+) -> rusqlite::Result<Option<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT core_version, MAX(at_ms) FROM latency_samples
+         WHERE origin = ?1 AND core_version <> ?2 AND core_version <> '' AND at_ms < ?3
+         GROUP BY core_version ORDER BY MAX(at_ms) DESC LIMIT 1",
+    )?;
+    let mut rows = stmt.query_map(params![origin, current, before_ms], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.next().transpose()
+}
+
+/// Response-time percentiles per core endpoint over the last
+/// `window_hours` (default 24), each next to its baseline: the previous
+/// core version when the core was upgraded since, otherwise the week
+/// before the window. Endpoints whose p95 grew markedly are listed in
+/// `degraded`.
+#[tauri::command]
+pub fn performance_report(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    window_hours: Option<u32>,
+) -> Result<Value, String> {
+    let hours = window_hours.unwrap_or(DEFAULT_WINDOW_HOURS);
+    if hours == 0 || hours > MAX_WINDOW_HOURS {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Window must be between 1 and {} hours",
+            MAX_WINDOW_HOURS
+        )));
+    }
+    let conn_info = store.connection(profile.as_deref())?;
+    let origin = net::base_origin(&conn_info.base_url)
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Profile has no valid base URL"))?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let mut conn = db.lock()?;
+    flush(&mut conn).map_err(storage)?;
+
+    let now_ms = Utc::now().timestamp_millis();
+    let from_ms = now_ms - i64::from(hours) * 3_600_000;
+    let current_version: String = conn
+        .query_row(
+            "SELECT core_version FROM latency_samples WHERE origin = ?1
+             ORDER BY at_ms DESC LIMIT 1",
+            params![origin],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+    let upgrade = if current_version.is_empty() {
+        None
+    } else {
+        previous_version(&conn, &origin, &current_version, now_ms).map_err(storage)?
+    };
+
+    // After an upgrade, compare this version's calls with the last week of
+    // the one before it.
+    let (mut window, mut baseline, baseline_info) = match &upgrade {
+        Some((previous, last_seen)) => (
+            load(&conn, &origin, from_ms, now_ms + 1, Some(&current_version)).map_err(storage)?,
+            load(
+                &conn,
+                &origin,
+                last_seen - BASELINE_DAYS * 86_400_000,
+                last_seen + 1,
+                Some(previous),
+            )
+            .map_err(storage)?,
+            json!({ "kind": "previous_version", "core_version": previous }),
+        ),
+        None => {
+            let start = from_ms - BASELINE_DAYS * 86_400_000;
+            (
+                load(&conn, &origin, from_ms, now_ms + 1, None).map_err(storage)?,
+                load(&conn, &origin, start, from_ms, None).map_err(storage)?,
+                json!({ "kind": "previous_period", "days": BASELINE_DAYS }),
+            )
+        }
+    };
+    drop(conn);
+
+    let mut endpoints = Vec::new();
+    let mut degraded = Vec::new();
+    for (name, series) in window.iter_mut() {
+        let current = series.summary();
+        let base = baseline.get_mut(name).map(Series::summary);
+        let (now_p95, base_p95) = (
+            current["p95_ms"].as_i64().unwrap_or(0),
+            base.as_ref()
+                .and_then(|b| b["p95_ms"].as_i64())
+                .unwrap_or(0),
+        );
+        let enough = current["count"].as_u64().unwrap_or(0) as usize >= MIN_SAMPLES
+            && base
+                .as_ref()
+                .is_some_and(|b| b["count"].as_u64().unwrap_or(0) as usize >= MIN_SAMPLES);
+        let is_degraded = enough
+            && now_p95 as f64 > base_p95 as f64 * DEGRADED_RATIO
+            && now_p95 - base_p95 >= DEGRADED_MIN_MS;
+        if is_degraded {
+            degraded.push(json!(name));
+        }
+        endpoints.push(json!({
+            "endpoint": name,
+            "window": current,
+            "baseline": base,
+            "p95_change_pct": (enough && base_p95 > 0)
+                .then(|| ((now_p95 - base_p95) as f64 / base_p95 as f64 * 100.0).round()),
+            "degraded": is_degraded,
+        }));
+    }
+    let quiet: Vec<&String> = baseline
+        .keys()
+        .filter(|k| !window.contains_key(*k))
+        .collect();
+
+    Ok(json!({
+        "profile_id": conn_info.profile_id,
+        "base_url": conn_info.base_url,
+        "generated_at": Utc::now().to_rfc3339(),
+        "window_hours": hours,
+        "core_version": current_version,
+        "baseline": baseline_info,
+        "endpoints": endpoints,
+        "degraded": degraded,
+        "not_called_in_window": quiet,
+    }))
+}
@@ -0,0 +1,268 @@
+use std::sync::Mutex;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{decisions, environment, notification_actions, tray};
+
+/// `--approve <plan_id>`: approves the plan, without executing it unless
+/// `--execute` is given too.
+pub const APPROVE_ARG: &str = "--approve";
+/// `--reject <plan_id>`, with an optional `--reason <text>`.
+pub const REJECT_ARG: &str = "--reject";
+/// `--open-plan <plan_id>`: shows the plan in the window.
+pub const OPEN_PLAN_ARG: &str = "--open-plan";
+/// `--profile <id>`: the profile the action is for; the active one without.
+pub const PROFILE_ARG: &str = "--profile";
+pub const EXECUTE_ARG: &str = "--execute";
+pub const REASON_ARG: &str = "--reason";
+
+const DONE_EVENT: &str = "launch_action:done";
+const MAX_ID_LEN: usize = 128;
+const MAX_REASON_LEN: usize = 500;
+
+/// What the launch action did, for a window that loads after it finished.
+static OUTCOME: Mutex<Option<Value>> = Mutex::new(None);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Approve,
+    Reject,
+    OpenPlan,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Approve => "approve",
+            Kind::Reject => "reject",
+            Kind::OpenPlan => "open_plan",
+        }
+    }
+}
+
+struct LaunchAction {
+    kind: Kind,
+    plan_id: String,
+    profile: Option<String>,
+    execute: bool,
+    reason: Option<String>,
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':'))
+}
+
+/// The launch action in `args` (the process arguments without the binary),
+/// or `None` when there is none. Arguments it does not know, such as
+/// `--autostart`, `--safe-mode` or ones the OS adds, are left alone; a
+/// known flag without its value, two actions, or a malformed id is refused.
+fn parse(args: impl Iterator<Item = String>) -> Result<Option<LaunchAction>, String> {
+    let mut args = args.peekable();
+    let mut action: Option<(Kind, String)> = None;
+    let mut profile = None;
+    let mut execute = false;
+    let mut reason = None;
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.into())),
+            _ => (arg, None),
+        };
+        let takes_value = [
+            APPROVE_ARG,
+            REJECT_ARG,
+            OPEN_PLAN_ARG,
+            PROFILE_ARG,
+            REASON_ARG,
+        ]
+        .contains(&flag.as_str());
+        if !takes_value {
+            if flag == EXECUTE_ARG {
+                execute = true;
+            }
+            continue;
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => args
+                .next_if(|next| !next.starts_with("--"))
+                .ok_or_else(|| ErrorCode::InvalidInput.with(format!("{} needs a value", flag)))?,
+        };
+        let value = value.trim().to_string();
+        let kind = match flag.as_str() {
+            APPROVE_ARG => Kind::Approve,
+            REJECT_ARG => Kind::Reject,
+            OPEN_PLAN_ARG => Kind::OpenPlan,
+            PROFILE_ARG => {
+                profile = Some(value);
+                continue;
+            }
+            _ => {
+                reason = Some(value.chars().take(MAX_REASON_LEN).collect());
+                continue;
+            }
+        };
+        if action.is_some() {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Only one of {}, {} and {} can be given",
+                APPROVE_ARG, REJECT_ARG, OPEN_PLAN_ARG
+            )));
+        }
+        if !valid_id(&value) {
+            return Err(ErrorCode::InvalidInput.with(format!("{} needs a plan id", flag)));
+        }
+        action = Some((kind, value));
+    }
+    let Some((kind, plan_id)) = action else {
+        if profile.is_some() || execute || reason.is_some() {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "{}, {} and {} only go with an action",
+                PROFILE_ARG, EXECUTE_ARG, REASON_ARG
+            )));
+        }
+        return Ok(None);
+    };
+    if execute && kind != Kind::Approve {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("{} only goes with {}", EXECUTE_ARG, APPROVE_ARG))
+        );
+    }
+    if reason.is_some() && kind != Kind::Reject {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("{} only goes with {}", REASON_ARG, REJECT_ARG))
+        );
+    }
+    if profile.as_deref().is_some_and(|p| !valid_id(p)) {
+        return Err(ErrorCode::InvalidInput.with(format!("{} needs a profile id", PROFILE_ARG)));
+    }
+    Ok(Some(LaunchAction {
+        kind,
+        plan_id,
+        profile,
+        execute,
+        reason,
+    }))
+}
+
+/// Carries out the action as the window would: the plan is read, and a
+/// decision goes through the profile's permissions (fetched again first)
+/// and `send_decision`, with its execution windows, two-person rule and
+/// production confirmation.
+async fn dispatch(store: &SettingsStore, action: &LaunchAction) -> Result<(String, Value), String> {
+    let conn = store.connection(action.profile.as_deref())?;
+    let result = match action.kind {
+        Kind::OpenPlan => {
+            crate::request_json(
+                Method::GET,
+                &conn.base_url,
+                &format!("/plans/{}", action.plan_id),
+                conn.token.clone(),
+                None,
+            )
+            .await?
+        }
+        Kind::Approve | Kind::Reject => {
+            let decision = action.kind.name();
+            notification_actions::authorize(&conn, &action.plan_id, decision).await?;
+            let payload = if action.kind == Kind::Approve {
+                json!({ "execute": action.execute, "source": "launch" })
+            } else {
+                json!({
+                    "reason": action.reason.as_deref().unwrap_or("Rejected from the command line"),
+                    "source": "launch",
+                })
+            };
+            decisions::send_decision(
+                &conn.base_url,
+                conn.token.clone(),
+                &action.plan_id,
+                decision,
+                Some(payload),
+                None,
+                None,
+            )
+            .await?
+        }
+    };
+    Ok((conn.profile_id, result))
+}
+
+fn finish(app: &AppHandle, outcome: Value) {
+    if let Some(db) = app.try_state::<LocalDb>() {
+        if let Ok(conn) = db.lock() {
+            let _ = db::record_audit(
+                &conn,
+                outcome["profile_id"].as_str().unwrap_or_default(),
+                "launch_action",
+                outcome["action"].as_str().unwrap_or("invalid"),
+                outcome["plan_id"].as_str().unwrap_or_default(),
+                &json!({
+                    "ok": outcome["ok"],
+                    "execute": outcome["execute"],
+                    "error": outcome["error"],
+                }),
+            );
+        }
+    }
+    if let Ok(mut slot) = OUTCOME.lock() {
+        *slot = Some(outcome.clone());
+    }
+    tray::show_main_window(app);
+    let _ = environment::emit(app, DONE_EVENT, outcome);
+}
+
+/// Runs the action the app was launched with (`--approve`, `--reject` or
+/// `--open-plan`, with `--profile`), for external launchers and scripts.
+/// The outcome is audited, shown in the window as `launch_action:done` and
+/// kept for `get_launch_action`; a decision the profile needs confirmed
+/// first fails with that code, for the window to ask for it.
+pub fn run(app: &AppHandle) {
+    let action = match parse(std::env::args().skip(1)) {
+        Ok(Some(action)) => action,
+        Ok(None) => return,
+        Err(err) => {
+            finish(app, json!({ "action": null, "ok": false, "error": err }));
+            return;
+        }
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let outcome = dispatch(&app.state::<SettingsStore>(), &action).await;
+        let mut summary = json!({
+            "action": action.kind.name(),
+            "plan_id": action.plan_id,
+            "execute": action.execute,
+            "ok": outcome.is_ok(),
+        });
+        match outcome {
+            Ok((profile_id, result)) => {
+                summary["profile_id"] = json!(profile_id);
+                summary["result"] = result;
+            }
+            Err(err) => {
+                summary["profile_id"] = json!(action.profile);
+                summary["error"] = json!(err);
+            }
+        }
+        finish(&app, summary);
+    });
+}
+
+/// What the action the app was launched with did, or null when it was
+/// launched without one or the action is still running.
+#[tauri::command]
+pub fn get_launch_action() -> Result<Value, String> {
+    let outcome = OUTCOME
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Launch action state poisoned"))?
+        .clone();
+    Ok(json!({ "action": outcome }))
+}
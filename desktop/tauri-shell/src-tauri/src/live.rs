@@ -0,0 +1,375 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::header::{ACCEPT, COOKIE};
+use reqwest::{Method, Response, Url};
+use serde_json::{json, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+
+use crate::core_signing::{self, Checked};
+use crate::errors::ErrorCode;
+use crate::settings::{Connection, SettingsStore, StreamingSettings};
+use crate::{
+    api_prefix, bandwidth, cookies, crypto, environment, maintenance, net, permissions, sanitize,
+    tenants, tls,
+};
+
+const EVENT: &str = "live:event";
+const STATUS_EVENT: &str = "live:status";
+/// Events dropped because their core signature did not verify.
+const REJECTED_EVENT: &str = "live:rejected";
+const STREAM_PATH: &str = "/events/stream";
+/// The core's own cap on a stream's lifetime.
+const MAX_CONNECTION_SECS: u64 = 300;
+const MAX_KEEPALIVE_SECS: u64 = 3_600;
+
+/// The active profile's live event subscription, if one is running.
+#[derive(Default)]
+pub struct LiveStream {
+    running: Mutex<Option<(String, JoinHandle<()>)>>,
+}
+
+impl LiveStream {
+    fn stop(&self) -> Option<String> {
+        let (profile_id, handle) = self.running.lock().ok()?.take()?;
+        handle.abort();
+        Some(profile_id)
+    }
+}
+
+pub fn validate(settings: &StreamingSettings) -> Result<(), String> {
+    if settings.keepalive_secs == 0 || settings.keepalive_secs > MAX_KEEPALIVE_SECS {
+        return Err(format!(
+            "Stream keepalive must be between 1 and {} seconds",
+            MAX_KEEPALIVE_SECS
+        ));
+    }
+    if settings.max_connection_secs == 0 || settings.max_connection_secs > MAX_CONNECTION_SECS {
+        return Err(format!(
+            "Stream lifetime must be between 1 and {} seconds",
+            MAX_CONNECTION_SECS
+        ));
+    }
+    if settings.stale_after_secs == 0 {
+        return Err("Stale stream threshold must be above zero".to_string());
+    }
+    if settings.reconnect_initial_ms == 0
+        || settings.reconnect_max_ms < settings.reconnect_initial_ms
+    {
+        return Err("Reconnect delays must satisfy 0 < initial <= max".to_string());
+    }
+    if !(1.0..=10.0).contains(&settings.reconnect_multiplier) {
+        return Err("Reconnect multiplier must be between 1 and 10".to_string());
+    }
+    if !(0.0..=1.0).contains(&settings.reconnect_jitter) {
+        return Err("Reconnect jitter must be between 0 and 1".to_string());
+    }
+    Ok(())
+}
+
+/// The wait before reconnect attempt `failures` (1-based): exponential from
+/// `reconnect_initial_ms` up to `reconnect_max_ms`, spread by the jitter
+/// share so a fleet of shells does not reconnect in lockstep.
+pub fn backoff(settings: &StreamingSettings, failures: u32) -> Duration {
+    let base = settings.reconnect_initial_ms as f64
+        * settings
+            .reconnect_multiplier
+            .powi(failures.saturating_sub(1).min(64) as i32);
+    let base = base.min(settings.reconnect_max_ms as f64);
+    let unit = u16::from_le_bytes(crypto::random_bytes::<2>()) as f64 / u16::MAX as f64;
+    let spread = base * settings.reconnect_jitter * (unit * 2.0 - 1.0);
+    Duration::from_millis((base + spread).clamp(0.0, settings.reconnect_max_ms as f64) as u64)
+}
+
+/// Why a stream ended without an error.
+enum End {
+    /// The core closed it at the end of its lifetime.
+    Recycled,
+    /// Nothing arrived for `stale_after_secs`.
+    Stale,
+}
+
+fn emit_status(app: &AppHandle, profile_id: &str, state: &str, detail: Value) {
+    let mut payload = json!({ "profile_id": profile_id, "state": state });
+    if let (Some(payload), Value::Object(detail)) = (payload.as_object_mut(), detail) {
+        payload.extend(detail);
+    }
+    let _ = environment::emit(app, STATUS_EVENT, payload);
+}
+
+/// Subscribes to the core's audit event stream for `profile` (the active
+/// one by default), emitting each event as `live:event` and the connection's
+/// state as `live:status`. Streams are resumed from the last event id after
+/// every reconnect, so nothing is missed while one is re-established.
+/// Replaces any stream already running.
+#[tauri::command]
+pub fn start_live_events(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    live: State<'_, LiveStream>,
+    profile: Option<String>,
+    since_id: Option<i64>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    live.stop();
+    let profile_id = conn.profile_id.clone();
+    let task_app = app.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        run(task_app, profile_id, since_id.unwrap_or(0)).await;
+    });
+    *live
+        .running
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Live stream state poisoned"))? =
+        Some((conn.profile_id.clone(), handle));
+    Ok(json!({ "profile_id": conn.profile_id, "started": true }))
+}
+
+#[tauri::command]
+pub fn stop_live_events(app: AppHandle, live: State<'_, LiveStream>) -> Result<Value, String> {
+    let stopped = live.stop();
+    if let Some(profile_id) = &stopped {
+        emit_status(&app, profile_id, "stopped", json!({}));
+    }
+    Ok(json!({ "stopped": stopped.is_some() }))
+}
+
+async fn run(app: AppHandle, profile_id: String, mut since_id: i64) {
+    let mut failures = 0u32;
+    loop {
+        // Read afresh on every connect, so edited settings apply from the
+        // next reconnect on.
+        let store = app.state::<SettingsStore>();
+        let settings = store
+            .snapshot()
+            .profile(&profile_id)
+            .map(|p| p.streaming.clone())
+            .unwrap_or_default();
+        emit_status(
+            &app,
+            &profile_id,
+            "connecting",
+            json!({ "attempt": failures + 1 }),
+        );
+        let result = match store.connection(Some(&profile_id)) {
+            Ok(conn) => stream(&app, &conn, &settings, &mut since_id, &mut failures).await,
+            Err(err) => Err(err),
+        };
+        let delay = match result {
+            Ok(End::Recycled) => Duration::ZERO,
+            Ok(End::Stale) => {
+                emit_status(
+                    &app,
+                    &profile_id,
+                    "stale",
+                    json!({ "silent_for_secs": settings.stale_after_secs }),
+                );
+                backoff(&settings, 1)
+            }
+            Err(err) => {
+                failures += 1;
+                let delay = backoff(&settings, failures);
+                emit_status(
+                    &app,
+                    &profile_id,
+                    "reconnecting",
+                    json!({
+                        "error": err,
+                        "attempt": failures,
+                        "retry_in_ms": delay.as_millis() as u64,
+                    }),
+                );
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Opens a server-sent event stream on the core at `path`, kept alive and
+/// bounded by the profile's streaming settings; the core is asked to end it
+/// after `max_connection_secs`.
+pub async fn open(
+    conn: &Connection,
+    settings: &StreamingSettings,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Response, String> {
+    let base = conn.base_url.trim().trim_end_matches('/');
+    let scoped = tenants::scope(base, path);
+    let prefix = match &scoped {
+        Some(tenants::Scope::Prefix(prefix)) => prefix.as_str(),
+        _ => "",
+    };
+    let mounted = api_prefix::for_request(base, path);
+    let mut url = Url::parse(&format!("{}{}{}{}", base, mounted, prefix, path))
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    url.query_pairs_mut()
+        .extend_pairs(query)
+        .append_pair("timeout", &settings.max_connection_secs.to_string());
+    permissions::check(&Method::GET, base, path, conn.token.as_deref())?;
+    maintenance::check(&Method::GET, base)?;
+
+    let client = net::stream_client_for(&url, Duration::from_secs(settings.keepalive_secs))?;
+    let lifetime = Duration::from_secs(settings.max_connection_secs + settings.stale_after_secs);
+    let mut request = net::authorize(client.get(url.clone()), &url, conn.token.clone())?
+        .header(ACCEPT, "text/event-stream")
+        .timeout(lifetime);
+    if let Some(cookie) = cookies::header_for(&url) {
+        request = request.header(COOKIE, cookie);
+    }
+    if let Some(tenants::Scope::Header(name, value)) = scoped {
+        request = request.header(name, value);
+    }
+    let mut request = request
+        .build()
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid request: {}", e)))?;
+    net::sign(&mut request).await?;
+    let stale = Duration::from_secs(settings.stale_after_secs);
+    let response = tokio::time::timeout(stale, client.execute(request))
+        .await
+        .map_err(|_| ErrorCode::CoreTimeout.with("Stream did not open"))?
+        .map_err(|e| match tls::violation(&e) {
+            Some(reason) => ErrorCode::TlsPolicy.with(format!("TLS handshake failed: {}", reason)),
+            None => ErrorCode::CoreUnreachable.with(format!("Stream failed: {}", e)),
+        })?;
+    cookies::store_from(&url, response.headers());
+    bandwidth::opened(
+        base,
+        bandwidth::STREAMING,
+        bandwidth::request_bytes(&Method::GET, &url, &[], None),
+        bandwidth::response_bytes(response.headers(), 0),
+    );
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ErrorCode::from_status(status.as_u16())
+            .with(format!("Core API {}: stream refused", status.as_u16())));
+    }
+    Ok(response)
+}
+
+/// One stream from connect to close. `failures` is reset once the core
+/// accepts it; `since_id` follows the events delivered.
+async fn stream(
+    app: &AppHandle,
+    conn: &Connection,
+    settings: &StreamingSettings,
+    since_id: &mut i64,
+    failures: &mut u32,
+) -> Result<End, String> {
+    let query = [("since_id", since_id.to_string())];
+    let mut response = open(conn, settings, STREAM_PATH, &query).await?;
+    let stale = Duration::from_secs(settings.stale_after_secs);
+    *failures = 0;
+    emit_status(
+        app,
+        &conn.profile_id,
+        "connected",
+        json!({ "since_id": *since_id }),
+    );
+
+    // Read once per connection; a settings change applies from the next one.
+    let signing = app
+        .state::<SettingsStore>()
+        .snapshot()
+        .profile(&conn.profile_id)
+        .map(|p| p.core_signing.clone())
+        .unwrap_or_default();
+    let mut frames = Frames::default();
+    loop {
+        let chunk = match tokio::time::timeout(stale, response.chunk()).await {
+            Err(_) => return Ok(End::Stale),
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Ok(None)) => return Ok(End::Recycled),
+            Ok(Err(e)) => {
+                return Err(ErrorCode::CoreUnreachable.with(format!("Live stream cut: {}", e)))
+            }
+        };
+        bandwidth::transferred(&conn.base_url, bandwidth::STREAMING, 0, chunk.len() as u64);
+        for (event, data) in frames.push(&chunk) {
+            match event.as_str() {
+                "timeout" => return Ok(End::Recycled),
+                "error" => {
+                    return Err(ErrorCode::CoreError.with(format!("Live stream error: {}", data)))
+                }
+                _ if data.is_empty() => {}
+                _ => {
+                    let value =
+                        serde_json::from_str(&data).unwrap_or_else(|_| json!({ "raw": data }));
+                    let (mut value, verified) =
+                        match core_signing::check(&conn.profile_id, &signing, value) {
+                            Ok(Checked::Verified(value)) => (value, true),
+                            Ok(Checked::Unsigned(value)) => (value, false),
+                            Err(err) => {
+                                let _ = environment::emit(
+                                    app,
+                                    REJECTED_EVENT,
+                                    json!({
+                                        "profile_id": conn.profile_id,
+                                        "event": event,
+                                        "error": err,
+                                    }),
+                                );
+                                continue;
+                            }
+                        };
+                    sanitize::scrub(&mut value);
+                    if let Some(id) = value["id"].as_i64() {
+                        *since_id = (*since_id).max(id);
+                    }
+                    let _ = environment::emit(
+                        app,
+                        EVENT,
+                        json!({
+                            "profile_id": conn.profile_id,
+                            "event": event,
+                            "data": value,
+                            "verified": verified,
+                        }),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Splits a stream's chunks into whole SSE frames.
+#[derive(Default)]
+pub struct Frames {
+    pending: String,
+}
+
+impl Frames {
+    /// The frames completed by `chunk`, parsed by `parse_frame`.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.pending
+            .push_str(&String::from_utf8_lossy(chunk).replace("\r\n", "\n"));
+        let mut frames = Vec::new();
+        while let Some(end) = self.pending.find("\n\n") {
+            let frame: String = self.pending.drain(..end + 2).collect();
+            frames.push(parse_frame(&frame));
+        }
+        frames
+    }
+}
+
+/// An SSE frame's event name (`message` by default) and its data lines
+/// joined; comment lines are skipped.
+fn parse_frame(frame: &str) -> (String, String) {
+    let mut event = "message".to_string();
+    let mut data: Vec<&str> = Vec::new();
+    for line in frame.lines() {
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = value.to_string(),
+            "data" => data.push(value),
+            _ => {}
+        }
+    }
+    (event, data.join("\n"))
+}
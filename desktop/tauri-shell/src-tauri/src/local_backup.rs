@@ -0,0 +1,265 @@
+use std::fs;
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection};
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{self, SettingsStore};
+use crate::{crypto, migrations, residency, secrets};
+
+const ARCHIVE_FORMAT: &str = "novaadapt-local-backup";
+const ARCHIVE_VERSION: u64 = 1;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Tables whose rows exist only on this machine: the approval queue, the
+/// audit trail and usage history. Cached core data is left out; it is
+/// fetched again after a restore.
+const TABLES: &[&str] = &[
+    "scheduled_approvals",
+    "audit_log",
+    "plan_deadlines",
+    "usage_daily",
+    "usage_model_counters",
+    "metric_samples",
+];
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Backup passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn to_json(value: SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(n) => json!(n),
+        SqlValue::Real(f) => json!(f),
+        SqlValue::Text(s) => json!(s),
+        SqlValue::Blob(b) => json!({ "blob": B64.encode(b) }),
+    }
+}
+
+fn from_json(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Number(n) => n
+            .as_i64()
+            .map(SqlValue::Integer)
+            .unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or_default())),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        Value::Object(o) => match o.get("blob").and_then(Value::as_str) {
+            Some(encoded) => SqlValue::Blob(B64.decode(encoded).unwrap_or_default()),
+            None => SqlValue::Text(value.to_string()),
+        },
+        Value::Array(_) => SqlValue::Text(value.to_string()),
+    }
+}
+
+fn columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    conn.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?
+        .query_map([table], |row| row.get(0))?
+        .collect()
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn dump(conn: &Connection) -> rusqlite::Result<Map<String, Value>> {
+    let mut tables = Map::new();
+    for table in TABLES {
+        let columns = columns(conn, table)?;
+        let list = columns.iter().map(|c| quote(c)).collect::<Vec<_>>();
+        let mut stmt =
+            conn.prepare(&format!("SELECT {} FROM {}", list.join(", "), quote(table)))?;
+        let rows = stmt
+            .query_map([], |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, SqlValue>(i).map(to_json))
+                    .collect::<rusqlite::Result<Vec<Value>>>()
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables.insert(
+            table.to_string(),
+            json!({ "columns": columns, "rows": rows }),
+        );
+    }
+    Ok(tables)
+}
+
+/// Replaces each archived table's rows with the archive's, in one
+/// transaction. Columns this schema no longer has are dropped; ones it
+/// added since take their defaults.
+fn load(conn: &mut Connection, tables: &Map<String, Value>) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let mut restored = 0;
+    for table in TABLES {
+        let Some(archived) = tables.get(*table) else {
+            continue;
+        };
+        let current = columns(&tx, table)?;
+        let archived_columns: Vec<String> = archived["columns"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.as_str().map(str::to_string))
+            .collect();
+        let kept: Vec<usize> = (0..archived_columns.len())
+            .filter(|&i| current.contains(&archived_columns[i]))
+            .collect();
+        tx.execute(&format!("DELETE FROM {}", quote(table)), [])?;
+        if kept.is_empty() {
+            continue;
+        }
+        let sql = format!(
+            "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+            quote(table),
+            kept.iter()
+                .map(|&i| quote(&archived_columns[i]))
+                .collect::<Vec<_>>()
+                .join(", "),
+            vec!["?"; kept.len()].join(", ")
+        );
+        let mut stmt = tx.prepare(&sql)?;
+        for row in archived["rows"].as_array().into_iter().flatten() {
+            let values = kept.iter().map(|&i| from_json(&row[i]));
+            restored += stmt.execute(params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+    Ok(restored)
+}
+
+/// Writes a passphrase-encrypted archive of this station's settings, its
+/// queued approvals, audit trail and usage history to `path`, for moving to
+/// another machine. Like settings bundles, it carries no secrets: only which
+/// profiles had a stored credential, so they can be re-entered.
+#[tauri::command]
+pub fn backup_local_data(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    path: String,
+    passphrase: String,
+) -> Result<Value, String> {
+    check_passphrase(&passphrase)?;
+    let path = path.trim();
+    let target = residency::check("The backup", path)?;
+    let snapshot = store.snapshot();
+    let tables = dump(&*db.lock()?)
+        .map_err(|e| ErrorCode::LocalStorage.with(format!("Read local data failed: {}", e)))?;
+    let rows: usize = tables
+        .values()
+        .map(|t| t["rows"].as_array().map_or(0, Vec::len))
+        .sum();
+    let secret_refs: Vec<Value> = snapshot
+        .profiles
+        .iter()
+        .filter_map(|p| {
+            let key = p.core_auth.mode.secret_key(&p.id)?;
+            let present = secrets::get(&key).ok().flatten().is_some();
+            Some(json!({ "profile_id": p.id, "secret": key, "present": present }))
+        })
+        .collect();
+    let payload = json!({
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "schema_version": migrations::latest(),
+        "settings": snapshot,
+        "secret_refs": secret_refs,
+        "tables": tables,
+    });
+
+    let plaintext = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
+    let mut envelope = crypto::seal_with_passphrase(&passphrase, ARCHIVE_FORMAT, &plaintext)?;
+    envelope["version"] = json!(ARCHIVE_VERSION);
+    let archive = envelope.to_string();
+    fs::write(&target, &archive)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write backup failed: {}", e)))?;
+
+    let summary = json!({
+        "path": path,
+        "profiles": snapshot.profiles.len(),
+        "rows": rows,
+        "bytes": archive.len(),
+    });
+    let _ = db::record_audit(&*db.lock()?, "", "local_data", "backup", "", &summary);
+    Ok(summary)
+}
+
+/// Restores an archive from `backup_local_data`: its settings replace the
+/// current ones and its tables replace the local rows. Credentials the
+/// archive's profiles had but this machine's keyring lacks are listed in
+/// `missing_secrets`.
+#[tauri::command]
+pub fn restore_local_data(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    path: String,
+    passphrase: String,
+) -> Result<Value, String> {
+    let path = path.trim();
+    let raw = fs::read(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read backup failed: {}", e)))?;
+    let envelope: Value = serde_json::from_slice(&raw)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Backup is not valid JSON: {}", e)))?;
+    if envelope["version"].as_u64().unwrap_or(0) > ARCHIVE_VERSION {
+        return Err(ErrorCode::InvalidInput.with("Backup was created by a newer shell"));
+    }
+    let plaintext = crypto::open_with_passphrase(&passphrase, ARCHIVE_FORMAT, &envelope)
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let payload: Value = serde_json::from_slice(&plaintext)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Backup payload is corrupted: {}", e)))?;
+    let schema = payload["schema_version"].as_u64().unwrap_or(0);
+    if schema > u64::from(migrations::latest()) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Backup holds a newer local schema ({} > {})",
+            schema,
+            migrations::latest()
+        )));
+    }
+    let imported = settings::migrate(payload["settings"].clone())?;
+    let empty = Map::new();
+    let tables = payload["tables"].as_object().unwrap_or(&empty);
+
+    let rows = {
+        let mut conn = db.lock()?;
+        load(&mut conn, tables).map_err(|e| {
+            ErrorCode::LocalStorage.with(format!("Restore local data failed: {}", e))
+        })?
+    };
+    let updated = store.update(&app, |current| {
+        *current = imported;
+        Ok(())
+    })?;
+
+    let missing_secrets: Vec<Value> = payload["secret_refs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["present"].as_bool() == Some(true))
+        .filter(|r| {
+            let key = r["secret"].as_str().unwrap_or_default();
+            secrets::get(key).ok().flatten().is_none()
+        })
+        .cloned()
+        .collect();
+    let summary = json!({
+        "path": path,
+        "created_at": payload["created_at"],
+        "profiles": updated.profiles.len(),
+        "rows": rows,
+        "missing_secrets": missing_secrets,
+    });
+    let _ = db::record_audit(&*db.lock()?, "", "local_data", "restored", "", &summary);
+    Ok(summary)
+}
@@ -0,0 +1,410 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+use crate::environment;
+use crate::errors::ErrorCode;
+use crate::path_watch::PathWatch;
+use crate::permissions;
+
+const EXEC_ROOT_ENV: &str = "NOVAADAPT_DESKTOP_EXEC_ROOT";
+/// Set to `1` to let steps reach the network from inside the sandbox.
+const EXEC_NETWORK_ENV: &str = "NOVAADAPT_DESKTOP_EXEC_NETWORK";
+/// Set to `1` to run steps without a sandbox, as where none is available.
+const EXEC_UNSANDBOXED_ENV: &str = "NOVAADAPT_DESKTOP_EXEC_UNSANDBOXED";
+const STEP_EVENT: &str = "local_exec:step";
+const DEFAULT_STEP_TIMEOUT_SECS: u64 = 120;
+const MAX_STEP_TIMEOUT_SECS: u64 = 3600;
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+/// How long output is still read once a step (and its process group) has
+/// ended, for anything that escaped the group holding the pipes open.
+const OUTPUT_GRACE: Duration = Duration::from_secs(5);
+/// The only names a plan may set in a step's `env`, so it cannot reach the
+/// loader or shell (`LD_PRELOAD`, `PATH`, `BASH_ENV`).
+const PLAN_ENV_PREFIX: &str = "NOVAADAPT_PLAN_";
+const LOCAL_TARGETS: &[&str] = &["operator_machine", "operator", "local"];
+const RUNNABLE_TYPES: &[&str] = &["shell", "command", "run"];
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SystemRoot",
+    "COMSPEC",
+    "USERPROFILE",
+];
+
+/// Runs an approved plan's steps on this machine.
+///
+/// Only plans whose target is the operator's machine are accepted. Every step
+/// runs sandboxed in its own process group: bubblewrap on Linux and
+/// `sandbox-exec` on macOS let it write only inside the configured jail,
+/// hide the home directory and cut it off from the network. The environment
+/// is scrubbed, each step has a timeout that kills its whole group, and
+/// output capture is bounded; results are reported back to the core once
+/// the run finishes.
+///
+/// While it runs, the paths the plan references (`paths`, and `paths` and
+/// `writes` on its steps, which may point outside the jail at mounted
+/// shares) are watched; changes outside those its steps declare they
+/// write arrive as `local_exec:drift` events and in the results' `drift`.
+#[tauri::command]
+pub async fn execute_local_plan(
+    app: AppHandle,
+    base_url: String,
+    token: Option<String>,
+    plan_id: String,
+    timeout_seconds: Option<u64>,
+) -> Result<Value, String> {
+    permissions::ensure_writable(&base_url)?;
+    let jail = exec_root()?;
+    let plan_path = format!("/plans/{}", plan_id);
+    let plan = crate::request_json(Method::GET, &base_url, &plan_path, token.clone(), None).await?;
+
+    if !is_local_target(&plan) {
+        return Err(ErrorCode::InvalidInput.with("Plan is not targeted at the operator's machine"));
+    }
+    let status = plan["status"].as_str().unwrap_or_default();
+    if status != "approved" {
+        return Err(ErrorCode::PlanConflict.with(format!(
+            "Plan must be approved before local execution (status: {})",
+            status
+        )));
+    }
+
+    let timeout = Duration::from_secs(
+        timeout_seconds
+            .unwrap_or(DEFAULT_STEP_TIMEOUT_SECS)
+            .clamp(1, MAX_STEP_TIMEOUT_SECS),
+    );
+    let actions = plan["actions"].as_array().cloned().unwrap_or_default();
+    let watch = PathWatch::start(&app, &plan_id, &jail, &plan);
+    let mut results = Vec::with_capacity(actions.len());
+    let mut failed = false;
+    for (index, action) in actions.iter().enumerate() {
+        let result = if failed {
+            json!({ "index": index, "status": "skipped" })
+        } else {
+            if let Some(watch) = &watch {
+                watch.step(index);
+            }
+            run_step(&jail, index, action, timeout).await
+        };
+        failed = failed || result["status"] != "ok";
+        let _ = environment::emit(
+            &app,
+            STEP_EVENT,
+            json!({ "plan_id": plan_id, "result": result }),
+        );
+        results.push(result);
+    }
+
+    let drift = match watch {
+        Some(watch) => watch.finish().await,
+        None => Value::Null,
+    };
+    let summary = json!({
+        "plan_id": plan_id,
+        "status": if failed { "failed" } else { "executed" },
+        "workdir": jail.display().to_string(),
+        "results": results,
+        "drift": drift,
+    });
+    let report_path = format!("/plans/{}/local_results", plan_id);
+    let report = crate::request_json(
+        Method::POST,
+        &base_url,
+        &report_path,
+        token,
+        Some(summary.clone()),
+    )
+    .await;
+
+    let mut out = summary;
+    out["reported"] = json!(report.is_ok());
+    if let Err(err) = report {
+        out["report_error"] = json!(err);
+    }
+    Ok(out)
+}
+
+pub fn is_enabled() -> bool {
+    exec_root().is_ok()
+}
+
+fn exec_root() -> Result<PathBuf, String> {
+    crate::safe_mode::check("Local execution")?;
+    let raw = std::env::var_os(EXEC_ROOT_ENV)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| {
+            ErrorCode::Forbidden.with(format!(
+                "Local execution is disabled: set {} to a jail directory",
+                EXEC_ROOT_ENV
+            ))
+        })?;
+    std::fs::canonicalize(&raw)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Local execution root not accessible: {}", e)))
+}
+
+fn is_local_target(plan: &Value) -> bool {
+    [
+        &plan["target"],
+        &plan["execution_target"],
+        &plan["metadata"]["target"],
+    ]
+    .iter()
+    .filter_map(|v| v.as_str())
+    .any(|t| LOCAL_TARGETS.contains(&t.trim().to_ascii_lowercase().as_str()))
+}
+
+fn jailed_cwd(jail: &Path, requested: Option<&str>) -> Result<PathBuf, String> {
+    let Some(rel) = requested.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(jail.to_path_buf());
+    };
+    let candidate = jail.join(rel);
+    let resolved =
+        std::fs::canonicalize(&candidate).map_err(|e| format!("Step cwd not accessible: {}", e))?;
+    if !resolved.starts_with(jail) {
+        return Err(format!(
+            "Step cwd escapes the execution root: {}",
+            resolved.display()
+        ));
+    }
+    Ok(resolved)
+}
+
+async fn run_step(jail: &Path, index: usize, action: &Value, timeout: Duration) -> Value {
+    let kind = action["type"]
+        .as_str()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if !RUNNABLE_TYPES.contains(&kind.as_str()) {
+        return json!({
+            "index": index,
+            "status": "failed",
+            "error": format!("Step type '{}' cannot run locally", kind),
+        });
+    }
+    let command = action["command"]
+        .as_str()
+        .or_else(|| action["value"].as_str())
+        .unwrap_or_default();
+    if command.trim().is_empty() {
+        return json!({ "index": index, "status": "failed", "error": "Step has no command" });
+    }
+    let cwd = match jailed_cwd(jail, action["cwd"].as_str()) {
+        Ok(cwd) => cwd,
+        Err(err) => return json!({ "index": index, "status": "failed", "error": err }),
+    };
+
+    let (mut cmd, sandbox) = match sandboxed(jail, &cwd, command) {
+        Ok(sandboxed) => sandboxed,
+        Err(err) => return json!({ "index": index, "status": "failed", "error": err }),
+    };
+    #[cfg(unix)]
+    cmd.process_group(0);
+    cmd.current_dir(&cwd)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    for key in PASSTHROUGH_ENV {
+        if let Some(value) = std::env::var_os(key) {
+            cmd.env(key, value);
+        }
+    }
+    if let Some(extra) = action["env"].as_object() {
+        for (key, value) in extra {
+            let allowed = key.strip_prefix(PLAN_ENV_PREFIX).is_some_and(|rest| {
+                !rest.is_empty()
+                    && rest
+                        .bytes()
+                        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+            });
+            if !allowed {
+                return json!({
+                    "index": index,
+                    "status": "failed",
+                    "error": format!(
+                        "Step env may only set {}* variables, not {}",
+                        PLAN_ENV_PREFIX, key
+                    ),
+                });
+            }
+            if let Some(value) = value.as_str() {
+                cmd.env(key, value);
+            }
+        }
+    }
+
+    let started = Instant::now();
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && sandbox != "none" => {
+            return json!({
+                "index": index,
+                "status": "failed",
+                "error": format!("Local execution needs {} to sandbox steps", sandbox),
+            })
+        }
+        Err(err) => {
+            return json!({ "index": index, "status": "failed", "error": format!("Spawn failed: {}", err) })
+        }
+    };
+    // Taken now: `id` is gone once the step has been waited on, and its
+    // group still has to be ended then.
+    let pid = child.id();
+    let stdout = tauri::async_runtime::spawn(capture(child.stdout.take()));
+    let stderr = tauri::async_runtime::spawn(capture(child.stderr.take()));
+
+    let waited = tokio::time::timeout(timeout, child.wait()).await;
+    let timed_out = waited.is_err();
+    // Ends whatever the step left running in the background too, which
+    // would otherwise hold its output pipes open.
+    if let Some(pid) = pid {
+        kill_group(pid).await;
+    }
+    if timed_out {
+        let _ = child.kill().await;
+    }
+    let stdout = tokio::time::timeout(OUTPUT_GRACE, stdout).await;
+    let stderr = tokio::time::timeout(OUTPUT_GRACE, stderr).await;
+    let output_complete = matches!((&stdout, &stderr), (Ok(Ok(_)), Ok(Ok(_))));
+    let (stdout, stdout_truncated) = stdout.ok().and_then(Result::ok).unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.ok().and_then(Result::ok).unwrap_or_default();
+    let exit_code = match waited {
+        Ok(Ok(status)) => status.code(),
+        _ => None,
+    };
+
+    json!({
+        "index": index,
+        "command": command,
+        "cwd": cwd.display().to_string(),
+        "status": if !timed_out && exit_code == Some(0) { "ok" } else { "failed" },
+        "exit_code": exit_code,
+        "timed_out": timed_out,
+        "duration_ms": started.elapsed().as_millis() as u64,
+        "stdout": stdout,
+        "stderr": stderr,
+        "truncated": stdout_truncated || stderr_truncated,
+        "output_complete": output_complete,
+        "sandbox": sandbox,
+    })
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| v.trim() == "1")
+}
+
+/// The shell running `command` inside this platform's sandbox, and which
+/// sandbox that is. Where there is none, steps only run when
+/// `EXEC_UNSANDBOXED_ENV` opts out.
+fn sandboxed(jail: &Path, cwd: &Path, command: &str) -> Result<(Command, &'static str), String> {
+    if env_flag(EXEC_UNSANDBOXED_ENV) {
+        return Ok((shell(command), "none"));
+    }
+    let network = env_flag(EXEC_NETWORK_ENV);
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .map(PathBuf::from)
+        .filter(|home| home.is_absolute());
+    if cfg!(target_os = "linux") {
+        let mut cmd = Command::new("bwrap");
+        cmd.args(["--die-with-parent", "--unshare-all"]);
+        if network {
+            cmd.arg("--share-net");
+        }
+        cmd.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]);
+        cmd.args(["--tmpfs", "/tmp"]);
+        if let Some(home) = &home {
+            cmd.arg("--tmpfs").arg(home);
+        }
+        cmd.arg("--bind").arg(jail).arg(jail);
+        cmd.arg("--chdir").arg(cwd);
+        cmd.args(["--", "/bin/sh", "-c", command]);
+        return Ok((cmd, "bwrap"));
+    }
+    if cfg!(target_os = "macos") {
+        let quote = |path: &Path| format!("{:?}", path.display().to_string());
+        let mut profile = String::from("(version 1)(allow default)(deny file-write*)");
+        if let Some(home) = &home {
+            profile.push_str(&format!("(deny file-read* (subpath {}))", quote(home)));
+        }
+        profile.push_str(&format!(
+            "(allow file-read* file-write* (subpath {}))(allow file-write* (subpath \"/dev\") (subpath \"/private/tmp\") (subpath \"/private/var/folders\"))",
+            quote(jail)
+        ));
+        if !network {
+            profile.push_str("(deny network*)");
+        }
+        let mut cmd = Command::new("sandbox-exec");
+        cmd.arg("-p").arg(profile).args(["/bin/sh", "-c", command]);
+        return Ok((cmd, "sandbox-exec"));
+    }
+    Err(format!(
+        "No sandbox is available on this platform; set {}=1 to run steps without one",
+        EXEC_UNSANDBOXED_ENV
+    ))
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Kills the process group (the process tree on Windows) a step runs in.
+async fn kill_group(pid: u32) {
+    let mut kill = if cfg!(windows) {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/T", "/F", "/PID", &pid.to_string()]);
+        cmd
+    } else {
+        let mut cmd = Command::new("kill");
+        cmd.args(["-KILL", "--", &format!("-{}", pid)]);
+        cmd
+    };
+    let _ = kill
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+}
+
+async fn capture<R: AsyncRead + Unpin>(stream: Option<R>) -> (String, bool) {
+    let Some(mut stream) = stream else {
+        return (String::new(), false);
+    };
+    let mut kept = Vec::new();
+    let mut buf = [0u8; 8192];
+    let mut truncated = false;
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = MAX_OUTPUT_BYTES.saturating_sub(kept.len());
+                kept.extend_from_slice(&buf[..n.min(room)]);
+                truncated = truncated || n > room;
+            }
+        }
+    }
+    (String::from_utf8_lossy(&kept).into_owned(), truncated)
+}
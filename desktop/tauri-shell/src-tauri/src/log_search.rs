@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use regex::RegexBuilder;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::logs::{self, CORE_SOURCE};
+use crate::resources;
+use crate::settings::SettingsStore;
+
+const DEFAULT_CONTEXT: usize = 2;
+const MAX_CONTEXT: usize = 10;
+const DEFAULT_MATCHES: usize = 100;
+const MAX_MATCHES: usize = 1_000;
+const MAX_PATTERN_LEN: usize = 1_024;
+/// Caps compiled regex size so a pathological pattern fails fast.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const STREAM_LIMIT: usize = 5_000;
+
+/// Runs `pattern` as a regex over the cached lines of `source` (default
+/// `core`) within `since`/`until` (RFC 3339, both optional). With the core
+/// as source, the range the cache does not reach back to is streamed from
+/// the core's `/logs` first. Matches carry `context` lines either side
+/// (default 2) and the byte offsets of every hit in the line.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn search_logs(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    pattern: String,
+    since: Option<String>,
+    until: Option<String>,
+    source: Option<String>,
+    context: Option<usize>,
+    limit: Option<usize>,
+    case_insensitive: Option<bool>,
+) -> Result<Value, String> {
+    if pattern.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Search pattern is required"));
+    }
+    if pattern.len() > MAX_PATTERN_LEN {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Search pattern is longer than {} bytes",
+            MAX_PATTERN_LEN
+        )));
+    }
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive.unwrap_or(false))
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid pattern: {}", e)))?;
+    let since = parse_bound(since.as_deref(), "since")?;
+    let until = parse_bound(until.as_deref(), "until")?;
+    let source = source
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| CORE_SOURCE.to_string());
+    let context = context.unwrap_or(DEFAULT_CONTEXT).min(MAX_CONTEXT);
+    let limit = limit.unwrap_or(DEFAULT_MATCHES).clamp(1, MAX_MATCHES);
+
+    let (profile_id, origin, fetch_error) =
+        logs::refresh_cache(&store, &db, profile.as_deref(), &source).await?;
+    let mut entries = logs::cached_entries(&*db.lock()?, &profile_id, &source)
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+
+    let mut streamed = 0;
+    if source == CORE_SOURCE && fetch_error.is_none() {
+        let oldest = entries
+            .iter()
+            .find_map(|(_, at)| at.as_deref().and_then(logs::parse_time));
+        let uncovered = match (since, oldest) {
+            (Some(since), Some(oldest)) => since < oldest,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if uncovered {
+            let older = stream_range(&store, profile.as_deref(), since, oldest.or(until)).await?;
+            streamed = older.len();
+            let mut merged: Vec<(String, Option<String>)> = {
+                let cached: HashSet<&str> = entries.iter().map(|(l, _)| l.as_str()).collect();
+                older
+                    .into_iter()
+                    .filter(|line| !cached.contains(line.as_str()))
+                    .map(|line| {
+                        let at = logs::parse_line(&line).at.map(|t| t.to_rfc3339());
+                        (line, at)
+                    })
+                    .collect()
+            };
+            merged.append(&mut entries);
+            entries = merged;
+        }
+    }
+
+    let scan = resources::timer(resources::SEARCH);
+    // Continuation lines (tracebacks) inherit the last timestamp seen.
+    let mut current: Option<DateTime<Utc>> = None;
+    let times: Vec<Option<DateTime<Utc>>> = entries
+        .iter()
+        .map(|(_, at)| {
+            if let Some(t) = at.as_deref().and_then(logs::parse_time) {
+                current = Some(t);
+            }
+            current
+        })
+        .collect();
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    let mut scanned = 0;
+    for (i, (line, _)) in entries.iter().enumerate() {
+        let in_range = match times[i] {
+            Some(at) => since.is_none_or(|s| at >= s) && until.is_none_or(|u| at <= u),
+            None => since.is_none() && until.is_none(),
+        };
+        if !in_range {
+            continue;
+        }
+        scanned += 1;
+        let offsets: Vec<Value> = regex
+            .find_iter(line)
+            .map(|m| json!([m.start(), m.end()]))
+            .collect();
+        if offsets.is_empty() {
+            continue;
+        }
+        if matches.len() == limit {
+            truncated = true;
+            break;
+        }
+        let before: Vec<&str> = entries[i.saturating_sub(context)..i]
+            .iter()
+            .map(|(l, _)| l.as_str())
+            .collect();
+        let after: Vec<&str> = entries[i + 1..(i + 1 + context).min(entries.len())]
+            .iter()
+            .map(|(l, _)| l.as_str())
+            .collect();
+        matches.push(json!({
+            "index": i,
+            "at": times[i].map(|t| t.to_rfc3339()),
+            "line": line,
+            "offsets": offsets,
+            "before": before,
+            "after": after,
+        }));
+    }
+    drop(scan);
+
+    Ok(json!({
+        "profile_id": profile_id,
+        "source": source,
+        "origin": origin,
+        "fetch_error": fetch_error,
+        "streamed": streamed,
+        "scanned": scanned,
+        "truncated": truncated,
+        "matches": matches,
+    }))
+}
+
+fn parse_bound(raw: Option<&str>, name: &str) -> Result<Option<DateTime<Utc>>, String> {
+    match raw.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(raw) => logs::parse_time(raw)
+            .map(Some)
+            .ok_or_else(|| ErrorCode::InvalidInput.with(format!("`{}` must be RFC 3339", name))),
+        None => Ok(None),
+    }
+}
+
+/// Reads the core's log lines for a window the local cache does not cover;
+/// these are scanned but not cached, so a wide search does not evict the
+/// recent lines the cache is for.
+async fn stream_range(
+    store: &SettingsStore,
+    profile: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<Vec<String>, String> {
+    let conn = store.connection(profile)?;
+    let mut path = format!("/logs?limit={}", STREAM_LIMIT);
+    if let Some(since) = since {
+        path.push_str(&format!("&since={}", since.timestamp()));
+    }
+    if let Some(until) = until {
+        path.push_str(&format!("&until={}", until.timestamp()));
+    }
+    let body =
+        crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None).await?;
+    Ok(logs::lines_of(&body))
+}
@@ -0,0 +1,650 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::preview;
+use crate::settings::SettingsStore;
+
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 2_000;
+const FETCH_LINES: usize = 2_000;
+/// Only the tail of a local log file is read per fetch.
+const FILE_TAIL_BYTES: u64 = 1024 * 1024;
+/// Cached lines kept per profile and source.
+const CACHE_LINES: i64 = 50_000;
+pub const CORE_SOURCE: &str = "core";
+
+const TIME_KEYS: &[&str] = &[
+    "timestamp",
+    "time",
+    "ts",
+    "@timestamp",
+    "asctime",
+    "created_at",
+];
+const LEVEL_KEYS: &[&str] = &["level", "levelname", "severity", "lvl"];
+const MESSAGE_KEYS: &[&str] = &["message", "msg", "event"];
+const LOGGER_KEYS: &[&str] = &["logger", "name", "logger_name"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl Level {
+    pub fn parse(raw: &str) -> Option<Self> {
+        Some(match raw.trim().to_ascii_lowercase().as_str() {
+            "trace" => Level::Trace,
+            "debug" => Level::Debug,
+            "info" | "information" | "notice" => Level::Info,
+            "warn" | "warning" => Level::Warn,
+            "error" | "err" => Level::Error,
+            "critical" | "crit" | "fatal" | "panic" => Level::Critical,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Trace => "trace",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+            Level::Critical => "critical",
+        }
+    }
+}
+
+/// One log line with whatever structure could be recovered from it.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub at: Option<DateTime<Utc>>,
+    pub level: Option<Level>,
+    pub logger: Option<String>,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+    pub raw: String,
+}
+
+impl LogRecord {
+    /// Looks a filter field up: the fixed `level`/`logger`/`message`/`at`
+    /// names first, then any extracted `key=value` or JSON field.
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "level" => self.level.map(|l| l.as_str().to_string()),
+            "logger" => self.logger.clone(),
+            "message" | "msg" => Some(self.message.clone()),
+            "at" | "time" | "timestamp" => self.at.map(|t| t.to_rfc3339()),
+            _ => self.fields.get(name).cloned(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "at": self.at.map(|t| t.to_rfc3339()),
+            "level": self.level.map(Level::as_str),
+            "logger": self.logger,
+            "agent": self.fields.get("agent"),
+            "message": self.message,
+            "fields": self.fields,
+            "raw": self.raw,
+        })
+    }
+}
+
+/// Parses a JSON log object or a plain line such as the core's
+/// `2026-10-14 09:30:00,123 INFO novaadapt.core: core request id=… status=200`.
+pub fn parse_line(line: &str) -> LogRecord {
+    let raw = line.trim_end_matches(['\r', '\n']).to_string();
+    let trimmed = raw.trim();
+    if trimmed.starts_with('{') {
+        if let Ok(Value::Object(obj)) = serde_json::from_str::<Value>(trimmed) {
+            return from_json(obj, raw);
+        }
+    }
+    from_plain(raw)
+}
+
+fn from_json(obj: Map<String, Value>, raw: String) -> LogRecord {
+    let pick = |keys: &[&str]| keys.iter().find_map(|k| obj.get(*k).and_then(scalar));
+    let at = pick(TIME_KEYS).and_then(|t| parse_time(&t));
+    let level = pick(LEVEL_KEYS).and_then(|l| Level::parse(&l));
+    let logger = pick(LOGGER_KEYS);
+    let message = pick(MESSAGE_KEYS).unwrap_or_default();
+    let mut fields: BTreeMap<String, String> = obj
+        .iter()
+        .filter(|(k, _)| {
+            ![TIME_KEYS, LEVEL_KEYS, MESSAGE_KEYS, LOGGER_KEYS]
+                .iter()
+                .any(|keys| keys.contains(&k.as_str()))
+        })
+        .filter_map(|(k, v)| Some((k.clone(), scalar(v)?)))
+        .collect();
+    for (k, v) in key_values(&message) {
+        fields.entry(k).or_insert(v);
+    }
+    LogRecord {
+        at,
+        level,
+        logger,
+        message,
+        fields,
+        raw,
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn from_plain(raw: String) -> LogRecord {
+    let mut rest = raw.trim();
+    let mut at = None;
+    // Timestamps are either one RFC 3339 token or a `date time` pair.
+    let mut tokens = rest.splitn(3, ' ');
+    if let Some(first) = tokens.next() {
+        let first = first.trim_matches(['[', ']']);
+        if let Some(t) = parse_time(first) {
+            at = Some(t);
+            rest = rest[rest.find(' ').map_or(rest.len(), |i| i + 1)..].trim_start();
+        } else if let Some(second) = tokens.next() {
+            let pair = format!("{} {}", first, second.trim_matches(['[', ']']));
+            if let Some(t) = parse_time(&pair) {
+                at = Some(t);
+                rest = rest
+                    .splitn(3, ' ')
+                    .nth(2)
+                    .map(str::trim_start)
+                    .unwrap_or_default();
+            }
+        }
+    }
+
+    let mut level = None;
+    if let Some((token, tail)) = split_token(rest) {
+        if let Some(l) = Level::parse(token.trim_matches(['[', ']', ':'])) {
+            level = Some(l);
+            rest = tail;
+        }
+    }
+
+    let mut logger = None;
+    if let Some((token, tail)) = split_token(rest) {
+        if let Some(name) = token
+            .strip_suffix(':')
+            .filter(|n| !n.is_empty() && !n.contains('='))
+        {
+            logger = Some(name.to_string());
+            rest = tail;
+        }
+    }
+
+    let message = rest.to_string();
+    LogRecord {
+        at,
+        level,
+        logger,
+        fields: key_values(&message).into_iter().collect(),
+        message,
+        raw,
+    }
+}
+
+fn split_token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    if s.is_empty() {
+        return None;
+    }
+    Some(match s.find(' ') {
+        Some(i) => (&s[..i], s[i + 1..].trim_start()),
+        None => (s, ""),
+    })
+}
+
+/// `key=value` and `key="quoted value"` pairs anywhere in a message.
+fn key_values(message: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut rest = message;
+    while let Some(eq) = rest.find('=') {
+        let key_start = rest[..eq]
+            .rfind(|c: char| c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        let key = &rest[key_start..eq];
+        let after = &rest[eq + 1..];
+        let (value, consumed) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], end + 2),
+                None => (quoted, after.len()),
+            }
+        } else {
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        let valid_key = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if valid_key {
+            out.push((key.to_string(), value.to_string()));
+        }
+        rest = &after[consumed.min(after.len())..];
+    }
+    out
+}
+
+pub fn parse_time(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(raw) {
+        return Some(t.with_timezone(&Utc));
+    }
+    [
+        "%Y-%m-%d %H:%M:%S,%3f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S",
+    ]
+    .iter()
+    .find_map(|f| NaiveDateTime::parse_from_str(raw, f).ok())
+    .map(|t| t.and_utc())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Contains,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Cmp(String, Op, String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// A backend-side log filter such as `level>=warn AND agent=deploy-bot`.
+///
+/// Comparisons are `=`, `!=`, `>=`, `<=`, `>`, `<` and `~` (substring),
+/// combined with `AND`, `OR`, `NOT` and parentheses. `level` compares by
+/// severity, `at` by time, numeric values numerically, the rest as text.
+#[derive(Debug)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let tokens = tokenize(raw)?;
+        let mut pos = 0;
+        let expr = parse_or(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(format!("Unexpected `{}` in filter", tokens[pos]));
+        }
+        Ok(Filter(expr))
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        eval(&self.0, record)
+    }
+}
+
+fn tokenize(raw: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::from("\"");
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(ch) => value.push(ch),
+                    None => return Err("Unterminated quote in filter".to_string()),
+                }
+            }
+            tokens.push(value);
+        } else if matches!(c, '=' | '!' | '>' | '<' | '~') {
+            let mut op = String::new();
+            op.push(c);
+            chars.next();
+            if chars.peek() == Some(&'=') && c != '~' {
+                op.push('=');
+                chars.next();
+            }
+            tokens.push(op);
+        } else {
+            let mut word = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() || matches!(ch, '(' | ')' | '=' | '!' | '>' | '<' | '~' | '"')
+                {
+                    break;
+                }
+                word.push(ch);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+    if tokens.is_empty() {
+        return Err("Filter is empty".to_string());
+    }
+    Ok(tokens)
+}
+
+fn keyword(tokens: &[String], pos: usize, word: &str) -> bool {
+    tokens
+        .get(pos)
+        .is_some_and(|t| t.eq_ignore_ascii_case(word))
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    let mut left = parse_and(tokens, pos)?;
+    while keyword(tokens, *pos, "or") {
+        *pos += 1;
+        left = Expr::Or(Box::new(left), Box::new(parse_and(tokens, pos)?));
+    }
+    Ok(left)
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    let mut left = parse_unary(tokens, pos)?;
+    while keyword(tokens, *pos, "and") {
+        *pos += 1;
+        left = Expr::And(Box::new(left), Box::new(parse_unary(tokens, pos)?));
+    }
+    Ok(left)
+}
+
+fn parse_unary(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    if keyword(tokens, *pos, "not") {
+        *pos += 1;
+        return Ok(Expr::Not(Box::new(parse_unary(tokens, pos)?)));
+    }
+    if tokens.get(*pos).map(String::as_str) == Some("(") {
+        *pos += 1;
+        let inner = parse_or(tokens, pos)?;
+        if tokens.get(*pos).map(String::as_str) != Some(")") {
+            return Err("Missing `)` in filter".to_string());
+        }
+        *pos += 1;
+        return Ok(inner);
+    }
+    let field = tokens
+        .get(*pos)
+        .ok_or("Filter ends where a field was expected")?;
+    let op = match tokens.get(*pos + 1).map(String::as_str) {
+        Some("=") | Some("==") => Op::Eq,
+        Some("!=") => Op::Ne,
+        Some(">=") => Op::Ge,
+        Some("<=") => Op::Le,
+        Some(">") => Op::Gt,
+        Some("<") => Op::Lt,
+        Some("~") => Op::Contains,
+        other => {
+            return Err(format!(
+                "Expected a comparison after `{}`, found {}",
+                field,
+                other.map_or("the end".to_string(), |o| format!("`{}`", o))
+            ))
+        }
+    };
+    let value = tokens
+        .get(*pos + 2)
+        .ok_or_else(|| format!("Missing value after `{}`", field))?;
+    *pos += 3;
+    let value = value.strip_prefix('"').unwrap_or(value).to_string();
+    if field.eq_ignore_ascii_case("level") && Level::parse(&value).is_none() {
+        return Err(format!("Unknown log level `{}`", value));
+    }
+    Ok(Expr::Cmp(field.to_ascii_lowercase(), op, value))
+}
+
+fn eval(expr: &Expr, record: &LogRecord) -> bool {
+    match expr {
+        Expr::Not(inner) => !eval(inner, record),
+        Expr::And(a, b) => eval(a, record) && eval(b, record),
+        Expr::Or(a, b) => eval(a, record) || eval(b, record),
+        Expr::Cmp(field, op, value) => {
+            let Some(actual) = record.field(field) else {
+                // A missing field only satisfies `!=`.
+                return *op == Op::Ne;
+            };
+            if *op == Op::Contains {
+                return actual.to_lowercase().contains(&value.to_lowercase());
+            }
+            let ordering = match field.as_str() {
+                "level" => Level::parse(&actual)
+                    .zip(Level::parse(value))
+                    .map(|(a, b)| a.cmp(&b)),
+                "at" | "time" | "timestamp" => parse_time(&actual)
+                    .zip(parse_time(value))
+                    .map(|(a, b)| a.cmp(&b)),
+                _ => match (actual.parse::<f64>(), value.parse::<f64>()) {
+                    (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                    _ => Some(actual.as_str().cmp(value.as_str())),
+                },
+            };
+            let Some(ordering) = ordering else {
+                return false;
+            };
+            match op {
+                Op::Eq => ordering.is_eq(),
+                Op::Ne => ordering.is_ne(),
+                Op::Ge => ordering.is_ge(),
+                Op::Le => ordering.is_le(),
+                Op::Gt => ordering.is_gt(),
+                Op::Lt => ordering.is_lt(),
+                Op::Contains => unreachable!(),
+            }
+        }
+    }
+}
+
+/// Pulls recent lines from `source`: `core` (the core's `/logs`) or the
+/// absolute path of a local log file under the preview roots.
+async fn fetch_lines(
+    store: &SettingsStore,
+    profile: Option<&str>,
+    source: &str,
+) -> Result<(String, Vec<String>), String> {
+    if source == CORE_SOURCE {
+        let conn = store.connection(profile)?;
+        let body = crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/logs?limit={}", FETCH_LINES),
+            conn.token.clone(),
+            None,
+        )
+        .await?;
+        return Ok((conn.profile_id, lines_of(&body)));
+    }
+    let profile_id = store.connection(profile)?.profile_id;
+    let path = preview::resolve_allowed(source)?;
+    let lines = tauri::async_runtime::spawn_blocking(move || tail_file(&path))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(format!("Log read task failed: {}", e)))??;
+    Ok((profile_id, lines))
+}
+
+/// The core serves `{ "lines": [...] }` or a bare array; structured lines
+/// may arrive as objects and are kept as their JSON text.
+pub fn lines_of(body: &Value) -> Vec<String> {
+    body["lines"]
+        .as_array()
+        .or_else(|| body.as_array())
+        .into_iter()
+        .flatten()
+        .map(|line| match line {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+fn tail_file(path: &std::path::Path) -> Result<Vec<String>, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Open log failed: {}", e)))?;
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let start = len.saturating_sub(FILE_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read log failed: {}", e)))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read log failed: {}", e)))?;
+    let text = String::from_utf8_lossy(&buf);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    // The first line of a partial read is usually cut mid-way.
+    if start > 0 && !lines.is_empty() {
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(FETCH_LINES);
+    Ok(lines.split_off(skip))
+}
+
+/// Appends unseen lines to the local log cache and trims it.
+fn cache_lines(
+    conn: &mut Connection,
+    profile_id: &str,
+    source: &str,
+    lines: &[String],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for line in lines {
+        let record = parse_line(line);
+        let hash: String = Sha256::digest(line.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        tx.execute(
+            "INSERT OR IGNORE INTO log_cache (profile_id, source, line_hash, at, level, line)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                profile_id,
+                source,
+                hash,
+                record.at.map(|t| t.to_rfc3339()),
+                record.level.map(Level::as_str),
+                line
+            ],
+        )?;
+    }
+    tx.execute(
+        "DELETE FROM log_cache WHERE profile_id = ?1 AND source = ?2 AND id <= (
+             SELECT id FROM log_cache WHERE profile_id = ?1 AND source = ?2
+             ORDER BY id DESC LIMIT 1 OFFSET ?3)",
+        params![profile_id, source, CACHE_LINES],
+    )?;
+    tx.commit()
+}
+
+/// Cached lines for a profile and source with their parsed timestamps,
+/// oldest first.
+pub fn cached_entries(
+    conn: &Connection,
+    profile_id: &str,
+    source: &str,
+) -> rusqlite::Result<Vec<(String, Option<String>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT line, at FROM log_cache WHERE profile_id = ?1 AND source = ?2 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![profile_id, source], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Fetches `source` into the cache. A source that cannot be read is not an
+/// error: the caller falls back to what is cached and reports why.
+pub async fn refresh_cache(
+    store: &SettingsStore,
+    db: &LocalDb,
+    profile: Option<&str>,
+    source: &str,
+) -> Result<(String, &'static str, Option<String>), String> {
+    match fetch_lines(store, profile, source).await {
+        Ok((profile_id, lines)) => {
+            cache_lines(&mut *db.lock()?, &profile_id, source, &lines)
+                .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            Ok((profile_id, "live", None))
+        }
+        Err(err) => {
+            let profile_id = store.connection(profile)?.profile_id;
+            Ok((profile_id, "cache", Some(err)))
+        }
+    }
+}
+
+/// Returns parsed log records from `source` (default `core`) that match
+/// `filter`, newest last. Fetched lines are cached locally, and the cache
+/// answers when the source cannot be read.
+#[tauri::command]
+pub async fn get_logs(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    source: Option<String>,
+    filter: Option<String>,
+    limit: Option<usize>,
+) -> Result<Value, String> {
+    let filter = match filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+        Some(raw) => Some(Filter::parse(raw).map_err(|e| ErrorCode::InvalidInput.with(e))?),
+        None => None,
+    };
+    let source = source
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| CORE_SOURCE.to_string());
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (profile_id, origin, fetch_error) =
+        refresh_cache(&store, &db, profile.as_deref(), &source).await?;
+    let lines = cached_entries(&*db.lock()?, &profile_id, &source)
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+
+    let scanned = lines.len();
+    let mut matched: Vec<Value> = lines
+        .iter()
+        .rev()
+        .map(|(line, _)| parse_line(line))
+        .filter(|record| filter.as_ref().is_none_or(|f| f.matches(record)))
+        .take(limit)
+        .map(|record| record.to_json())
+        .collect();
+    matched.reverse();
+    Ok(json!({
+        "profile_id": profile_id,
+        "source": source,
+        "origin": origin,
+        "fetch_error": fetch_error,
+        "scanned": scanned,
+        "records": matched,
+    }))
+}
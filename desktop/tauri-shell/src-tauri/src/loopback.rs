@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::settings::{Settings, SettingsStore};
+use crate::tasks;
+
+/// How often a listener looks at the settings for a new port or for being
+/// turned off.
+const RECHECK: Duration = Duration::from_secs(2);
+const BIND_RETRY: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// Each listener's port while it listens, and why it could not.
+#[derive(Clone, Default)]
+pub struct Listening {
+    pub port: Option<u16>,
+    pub last_error: Option<String>,
+}
+
+static LISTENERS: OnceLock<Mutex<HashMap<&'static str, Listening>>> = OnceLock::new();
+
+fn listeners() -> &'static Mutex<HashMap<&'static str, Listening>> {
+    LISTENERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn listening(task: &'static str) -> Listening {
+    listeners()
+        .lock()
+        .ok()
+        .and_then(|l| l.get(task).cloned())
+        .unwrap_or_default()
+}
+
+/// Runs background task `task` listening on `127.0.0.1` at the port
+/// `port` picks from the settings (`None` while off), moving when it
+/// changes, and hands each connection to `handle` with the port it came
+/// in on.
+pub fn spawn<H, Fut>(
+    app: &AppHandle,
+    task: &'static str,
+    port: fn(&Settings) -> Option<u16>,
+    handle: H,
+) where
+    H: Fn(AppHandle, u16, TcpStream) -> Fut + Copy + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tasks::spawn(app, task, move |app| async move {
+        let mut bound: Option<(u16, TcpListener)> = None;
+        loop {
+            let wanted = port(&app.state::<SettingsStore>().snapshot());
+            if bound.as_ref().map(|(port, _)| *port) != wanted {
+                bound = None;
+                let mut error = None;
+                if let Some(port) = wanted {
+                    match TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await {
+                        Ok(listener) => bound = Some((port, listener)),
+                        Err(e) => error = Some(format!("Port {} unavailable: {}", port, e)),
+                    }
+                }
+                if let Ok(mut listeners) = listeners().lock() {
+                    listeners.insert(
+                        task,
+                        Listening {
+                            port: bound.as_ref().map(|(port, _)| *port),
+                            last_error: error.clone(),
+                        },
+                    );
+                }
+                if let Some(err) = error {
+                    tasks::failed(task, &err);
+                    tokio::time::sleep(BIND_RETRY).await;
+                    continue;
+                }
+            }
+            match &bound {
+                Some((port, listener)) => {
+                    if let Ok(Ok((stream, _))) =
+                        tokio::time::timeout(RECHECK, listener.accept()).await
+                    {
+                        tauri::async_runtime::spawn(handle(app.clone(), *port, stream));
+                    }
+                }
+                None => tokio::time::sleep(RECHECK).await,
+            }
+            tasks::ran(task);
+        }
+    });
+}
+
+/// One HTTP/1.1 request. Header names are lowercased.
+pub struct Request {
+    pub method: String,
+    /// The request target without its query.
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Whether the request names this machine in `Host`, by loopback
+    /// address or `localhost`, with `port`. Anything else may be a web page
+    /// reaching the listener through DNS rebinding.
+    pub fn addressed_locally(&self, port: u16) -> bool {
+        let host = self.headers.get("host").map(|h| h.to_ascii_lowercase());
+        [format!("127.0.0.1:{}", port), format!("localhost:{}", port)]
+            .iter()
+            .any(|allowed| host.as_deref() == Some(allowed.as_str()))
+    }
+}
+
+/// Reads one request with a body of at most `max_body` bytes within
+/// `READ_TIMEOUT`, or the status to refuse it with.
+pub async fn read_request(
+    stream: &mut TcpStream,
+    max_body: usize,
+) -> Result<Request, (u16, &'static str)> {
+    tokio::time::timeout(READ_TIMEOUT, read(stream, max_body))
+        .await
+        .unwrap_or(Err((408, "Request timed out")))
+}
+
+async fn read(stream: &mut TcpStream, max_body: usize) -> Result<Request, (u16, &'static str)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(at) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break at;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err((431, "Request headers too large"));
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err((400, "Incomplete request")),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let length = match headers.get("content-length") {
+        Some(raw) => raw
+            .parse::<usize>()
+            .map_err(|_| (400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > max_body {
+        return Err((413, "Request body too large"));
+    }
+    let mut body = buf.split_off(head_end + 4);
+    while body.len() < length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err((400, "Incomplete request")),
+            Ok(n) => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    body.truncate(length);
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+/// Writes a whole response and closes the connection.
+pub async fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &str) {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        _ => "Service Unavailable",
+    };
+    let _ = stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+                status,
+                reason,
+                content_type,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await;
+}
+
+/// `respond` with a JSON body.
+pub async fn respond_json(stream: &mut TcpStream, status: u16, body: &serde_json::Value) {
+    respond(stream, status, "application/json", &body.to_string()).await;
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+use tokio::net::TcpStream;
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::loopback;
+use crate::settings::{
+    MacroAction, MacroButton, MacroPadSettings, SettingsStore, StatusEndpointSettings,
+    WebhookReceiverSettings,
+};
+use crate::{crypto, decisions, environment, notification_actions, quiet, secrets};
+
+/// The keyring entry holding the bearer token pad software presses with.
+pub const TOKEN_KEY: &str = "macro_pad:token";
+
+const TASK: &str = "macro_pad";
+const PRESS_PREFIX: &str = "/press/";
+const PRESSED_EVENT: &str = "macro_pad:pressed";
+/// How long a confirming button stays armed after its first press.
+const CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+const MAX_MUTE_MINUTES: u32 = 24 * 60;
+const MAX_BUTTON_ID_LEN: usize = 64;
+
+/// The plan the window has open, as it last reported it.
+#[derive(Clone)]
+struct CurrentPlan {
+    profile: Option<String>,
+    plan_id: String,
+    version: Option<String>,
+}
+
+/// A confirming button pressed once, waiting for its second press.
+struct Armed {
+    button: String,
+    plan_id: String,
+    at: Instant,
+}
+
+struct Pad {
+    current: Option<CurrentPlan>,
+    armed: Option<Armed>,
+}
+
+static PAD: Mutex<Pad> = Mutex::new(Pad {
+    current: None,
+    armed: None,
+});
+
+fn pad() -> Result<std::sync::MutexGuard<'static, Pad>, String> {
+    PAD.lock()
+        .map_err(|_| ErrorCode::Internal.with("Macro pad state poisoned"))
+}
+
+fn action_name(action: MacroAction) -> &'static str {
+    match action {
+        MacroAction::ApproveCurrent => "approve_current",
+        MacroAction::RejectCurrent => "reject_current",
+        MacroAction::MuteNotifications => "mute_notifications",
+    }
+}
+
+pub fn validate(
+    settings: &MacroPadSettings,
+    receiver: &WebhookReceiverSettings,
+    status: &StatusEndpointSettings,
+) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for button in &settings.buttons {
+        let id = button.id.as_str();
+        let valid = !id.is_empty()
+            && id.len() <= MAX_BUTTON_ID_LEN
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !valid {
+            return Err(format!("Invalid macro button id: {:?}", id));
+        }
+        if !ids.insert(id) {
+            return Err(format!("Macro button {} is mapped twice", id));
+        }
+        if button.action == MacroAction::MuteNotifications
+            && !(1..=MAX_MUTE_MINUTES).contains(&button.mute_minutes)
+        {
+            return Err(format!(
+                "Macro button {} must mute for 1 to {} minutes",
+                id, MAX_MUTE_MINUTES
+            ));
+        }
+    }
+    if !settings.enabled {
+        return Ok(());
+    }
+    if settings.port < 1024 {
+        return Err("Macro pad port must be 1024 or above".to_string());
+    }
+    if (receiver.enabled && receiver.port == settings.port)
+        || (status.enabled && status.port == settings.port)
+    {
+        return Err(
+            "Macro pad needs a port apart from the webhook receiver and status endpoint"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Takes presses on `/press/<id>` while the listener is enabled.
+pub fn spawn_listener(app: AppHandle) {
+    loopback::spawn(
+        &app,
+        TASK,
+        |settings| {
+            let pad = &settings.macro_pad;
+            pad.enabled.then_some(pad.port)
+        },
+        serve,
+    );
+}
+
+/// Checks `Authorization: Bearer <token>` against the pad token.
+fn authenticate(request: &loopback::Request) -> Result<(), (u16, &'static str)> {
+    let token = secrets::get(TOKEN_KEY)
+        .ok()
+        .flatten()
+        .ok_or((503, "No macro pad token is set"))?;
+    let given = request
+        .headers
+        .get("authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or((401, "Missing bearer token"))?;
+    let same = given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !same {
+        return Err((401, "Token does not match"));
+    }
+    Ok(())
+}
+
+async fn serve(app: AppHandle, port: u16, mut stream: TcpStream) {
+    let request = match loopback::read_request(&mut stream, 0).await {
+        Ok(request) => request,
+        Err((status, reason)) => {
+            loopback::respond(&mut stream, status, "text/plain", reason).await;
+            return;
+        }
+    };
+    if !request.addressed_locally(port) {
+        loopback::respond(&mut stream, 403, "text/plain", "Host not allowed").await;
+        return;
+    }
+    if request.method != "POST" {
+        loopback::respond(&mut stream, 405, "text/plain", "Use POST").await;
+        return;
+    }
+    let Some(button) = request.path.strip_prefix(PRESS_PREFIX) else {
+        loopback::respond(&mut stream, 404, "text/plain", "Try /press/<button>").await;
+        return;
+    };
+    if let Err((status, reason)) = authenticate(&request) {
+        loopback::respond(&mut stream, status, "text/plain", reason).await;
+        return;
+    }
+    match press(&app, button).await {
+        Ok(outcome) => loopback::respond_json(&mut stream, 200, &outcome).await,
+        Err(err) => {
+            let status = match errors::code_of(&err) {
+                Some(ErrorCode::NotFound) => 404,
+                _ => 422,
+            };
+            loopback::respond_json(&mut stream, status, &json!({ "ok": false, "error": err }))
+                .await;
+        }
+    }
+}
+
+/// Whether this press of `button` on the current plan is the second one
+/// within `CONFIRM_WINDOW`; a first press arms the button instead.
+fn confirmed(button: &str, plan_id: &str) -> Result<bool, String> {
+    let mut pad = pad()?;
+    let second = pad.armed.as_ref().is_some_and(|armed| {
+        armed.button == button && armed.plan_id == plan_id && armed.at.elapsed() < CONFIRM_WINDOW
+    });
+    pad.armed = (!second).then(|| Armed {
+        button: button.to_string(),
+        plan_id: plan_id.to_string(),
+        at: Instant::now(),
+    });
+    Ok(second)
+}
+
+/// Approves or rejects the plan open in the window the way its own buttons
+/// do: through the profile's permissions (fetched again first) and
+/// `send_decision`, with the version the window showed, so a plan changed
+/// since fails as a conflict. Approving does not execute, and a decision
+/// the production guard wants typed out fails with that code.
+async fn decide(store: &SettingsStore, button: &MacroButton) -> Result<Value, String> {
+    let current = pad()?
+        .current
+        .clone()
+        .ok_or_else(|| ErrorCode::NotFound.with("No plan is open in the window"))?;
+    if button.confirm && !confirmed(&button.id, &current.plan_id)? {
+        return Ok(json!({
+            "outcome": "armed",
+            "plan_id": current.plan_id,
+            "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+        }));
+    }
+    let (decision, payload) = match button.action {
+        MacroAction::ApproveCurrent => (
+            "approve",
+            json!({ "execute": false, "source": "macro_pad" }),
+        ),
+        _ => (
+            "reject",
+            json!({ "reason": "Rejected from a macro pad", "source": "macro_pad" }),
+        ),
+    };
+    let conn = store.connection(current.profile.as_deref())?;
+    notification_actions::authorize(&conn, &current.plan_id, decision).await?;
+    let result = decisions::send_decision(
+        &conn.base_url,
+        conn.token.clone(),
+        &current.plan_id,
+        decision,
+        Some(payload),
+        current.version.as_deref(),
+        None,
+    )
+    .await?;
+    Ok(json!({
+        "outcome": "sent",
+        "decision": decision,
+        "profile_id": conn.profile_id,
+        "plan_id": current.plan_id,
+        "result": result,
+    }))
+}
+
+/// Mutes notifications for the button's minutes, or unmutes them when
+/// they already are. Critical ones still come through while muted, when
+/// quiet hours let them.
+fn toggle_mute(button: &MacroButton) -> Value {
+    if quiet::muted_until().is_some() {
+        quiet::set_muted(None);
+        return json!({ "outcome": "unmuted" });
+    }
+    let until = Utc::now() + chrono::Duration::minutes(i64::from(button.mute_minutes));
+    quiet::set_muted(Some(until));
+    json!({ "outcome": "muted", "until": until.to_rfc3339() })
+}
+
+/// Runs the button mapped to `id`, audits the press and emits
+/// `macro_pad:pressed` for the window to show.
+async fn press(app: &AppHandle, id: &str) -> Result<Value, String> {
+    let store = app.state::<SettingsStore>();
+    let button = store
+        .snapshot()
+        .macro_pad
+        .buttons
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown macro button: {}", id)))?;
+    let outcome = match button.action {
+        MacroAction::MuteNotifications => Ok(toggle_mute(&button)),
+        MacroAction::ApproveCurrent | MacroAction::RejectCurrent => decide(&store, &button).await,
+    };
+    let mut summary = match &outcome {
+        Ok(outcome) => outcome.clone(),
+        Err(err) => json!({ "outcome": "failed", "error": err }),
+    };
+    summary["button"] = json!(button.id);
+    summary["action"] = json!(action_name(button.action));
+    summary["ok"] = json!(outcome.is_ok());
+    if let Some(db) = app.try_state::<LocalDb>() {
+        if let Ok(conn) = db.lock() {
+            let _ = db::record_audit(
+                &conn,
+                summary["profile_id"].as_str().unwrap_or_default(),
+                "macro_pad",
+                action_name(button.action),
+                summary["plan_id"].as_str().unwrap_or_default(),
+                &json!({
+                    "button": button.id,
+                    "outcome": summary["outcome"],
+                    "error": summary["error"],
+                }),
+            );
+        }
+    }
+    let _ = environment::emit(app, PRESSED_EVENT, summary.clone());
+    outcome.map(|_| summary)
+}
+
+/// Presses `button` from the window, for a pad it reads over WebHID.
+#[tauri::command]
+pub async fn press_macro_button(app: AppHandle, button: String) -> Result<Value, String> {
+    press(&app, &button).await
+}
+
+/// Records the plan the window has open (at `version`) for the approve and
+/// reject buttons, or that none is with no `plan_id`. A change disarms a
+/// button waiting for its second press.
+#[tauri::command]
+pub fn set_current_plan(
+    profile: Option<String>,
+    plan_id: Option<String>,
+    version: Option<String>,
+) -> Result<Value, String> {
+    let mut pad = pad()?;
+    pad.current = plan_id.map(|plan_id| CurrentPlan {
+        profile,
+        plan_id,
+        version,
+    });
+    if pad
+        .armed
+        .as_ref()
+        .is_some_and(|armed| pad.current.as_ref().map(|c| &c.plan_id) != Some(&armed.plan_id))
+    {
+        pad.armed = None;
+    }
+    Ok(json!({ "plan_id": pad.current.as_ref().map(|c| c.plan_id.clone()) }))
+}
+
+/// The button mapping, whether the press listener is up and where, and
+/// whether a token is set.
+#[tauri::command]
+pub fn get_macro_pad(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let settings = store.snapshot().macro_pad;
+    let listening = loopback::listening(TASK);
+    Ok(json!({
+        "enabled": settings.enabled,
+        "port": settings.port,
+        "listening": listening.port.is_some(),
+        "url": listening
+            .port
+            .map(|port| format!("http://127.0.0.1:{}{}<button>", port, PRESS_PREFIX)),
+        "token_set": secrets::get(TOKEN_KEY).ok().flatten().is_some(),
+        "last_error": listening.last_error,
+        "buttons": settings.buttons,
+        "current_plan": pad()?.current.as_ref().map(|c| c.plan_id.clone()),
+    }))
+}
+
+/// Generates a new pad token and returns it this once for the pad
+/// software; presses with the old one are refused from now on.
+#[tauri::command]
+pub fn rotate_macro_pad_token(db: State<'_, LocalDb>) -> Result<Value, String> {
+    let token: String = crypto::random_bytes::<32>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    secrets::set(TOKEN_KEY, &token)?;
+    let _ = db::record_audit(
+        &*db.lock()?,
+        "",
+        "macro_pad",
+        "token_rotated",
+        "",
+        &json!({}),
+    );
+    Ok(json!({ "token": token }))
+}
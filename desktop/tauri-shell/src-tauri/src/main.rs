@@ -1,35 +1,237 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod a11y;
+mod abort;
+mod agent_env;
+mod airgap;
+mod allowlist;
+mod announcements;
+mod ansi;
+mod api_prefix;
+mod app_protocol;
+mod approval_schedule;
+mod artifact_sync;
+mod attachments;
+mod audio;
+mod audit_export;
+mod auto_approval;
+mod autostart;
+mod availability;
+mod bandwidth;
+mod budget;
+mod cache_protocol;
+mod calendar;
+mod chat_webhooks;
+mod claims;
+mod clipboard;
+mod clock;
+mod cloud_auth;
+mod config_check;
+mod cookies;
+mod core_path;
+mod core_signing;
+mod crypto;
+mod db;
+mod deadlines;
+mod decision_metrics;
+mod decisions;
+mod device_login;
+mod digest;
+mod environment;
+mod errors;
+mod exec_windows;
+#[cfg(test)]
+mod fake_core;
+mod fault_injection;
+mod features;
+mod fields;
+mod formatting;
+#[cfg(test)]
+mod golden_path;
+mod grpc;
+mod halt;
+mod handover;
+mod http3;
+mod justifications;
+mod jwt;
+mod latency;
+mod launch_actions;
+mod live;
+mod local_backup;
+mod local_exec;
+mod log_search;
+mod logs;
+mod loopback;
+mod macro_pad;
+mod maintenance;
+mod managed;
+mod markdown;
+mod memory;
+mod metrics;
+mod migrations;
+mod models;
+mod my_decisions;
+mod negotiate;
+mod net;
+mod notification_actions;
+mod notify;
+mod ntlm;
+mod pagination;
+mod path_watch;
+mod permissions;
+mod plan_compare;
+mod plan_graph;
+mod plan_output;
+mod plan_pdf;
+mod plan_revisions;
+mod plan_secrets;
+mod plan_watch;
+mod plan_window;
+mod plugin_permissions;
+mod plugin_registry;
+mod plugins;
+mod presence;
+mod preview;
+mod private_notes;
+mod pty;
+mod quick_actions;
+mod quiet;
+mod rate_limits;
+mod recent;
+mod reconcile;
+mod replicas;
+mod request_history;
+mod residency;
+mod resources;
+mod response_cache;
+mod retention;
+mod review_queue;
+mod routing;
+mod safe_mode;
+mod sanitize;
+mod screenshot;
+mod secrets;
+mod session_recording;
+mod settings;
+mod settings_bundle;
+mod setup;
+mod shutdown;
+mod spill;
+mod ssrf;
+mod stall;
+mod state;
+mod status_endpoint;
+mod step_gate;
+mod sync;
+mod tasks;
+mod telemetry;
+mod tenants;
+mod thumbnails;
+mod ticketing;
+mod time_travel;
+mod timeline;
+mod tls;
+mod trace;
+mod trace_context;
+mod translation;
+mod transport;
+mod tray;
+mod tunnel;
+mod two_person;
+mod usage;
+mod vault;
+mod views;
+mod watchdog;
+mod webhook_receiver;
+mod wipe;
+
 use reqwest::{Method, Url};
 use serde_json::{json, Value};
+use tauri::Manager;
+
+use crate::errors::ErrorCode;
 
 #[tauri::command]
 async fn core_request(
+    cache: tauri::State<'_, response_cache::ResponseCache>,
     method: String,
     base_url: String,
     token: Option<String>,
     path: String,
     payload: Option<Value>,
+    trace_id: Option<String>,
 ) -> Result<Value, String> {
     let parsed_method = Method::from_bytes(method.trim().to_uppercase().as_bytes())
-        .map_err(|err| format!("Unsupported HTTP method: {}", err))?;
-    request_json(parsed_method, &base_url, &path, token, payload).await
+        .map_err(|err| ErrorCode::InvalidInput.with(format!("Unsupported HTTP method: {}", err)))?;
+    let changes = parsed_method != Method::GET;
+    let result = trace_context::within(
+        trace_id.as_deref(),
+        "core_request",
+        request_json(parsed_method, &base_url, &path, token, payload),
+    )
+    .await;
+    if changes {
+        cache.invalidate(&base_url);
+    }
+    result
 }
 
+const DASHBOARD_COLLECTIONS: &[&str] = &["plans", "jobs", "events"];
+
+/// `fields` selects plan fields (or `jobs.*` / `events.*` paths) to keep;
+/// it is forwarded to the core and also applied locally for older cores.
 #[tauri::command]
-async fn fetch_dashboard_data(base_url: String, token: Option<String>) -> Result<Value, String> {
-    request_json(Method::GET, &base_url, "/dashboard/data?plans_limit=100", token, None).await
+async fn fetch_dashboard_data(
+    base_url: String,
+    token: Option<String>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let selection = fields
+        .as_deref()
+        .and_then(|f| fields::FieldSelection::parse(f, DASHBOARD_COLLECTIONS, "plans"));
+    let mut path = "/dashboard/data?plans_limit=100".to_string();
+    if let Some(selection) = &selection {
+        path.push_str(&format!("&fields={}", selection.query_value("plans")));
+    }
+    let mut data = request_json(Method::GET, &base_url, &path, token, None).await?;
+    if let Some(selection) = &selection {
+        selection.apply(&mut data, "plans");
+    }
+    claims::annotate(&base_url, &mut data);
+    Ok(data)
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn approve_plan(
     base_url: String,
     token: Option<String>,
     plan_id: String,
     execute: bool,
+    version: Option<String>,
+    override_confirmation: Option<String>,
+    trace_id: Option<String>,
+    justification: Option<String>,
+    reason_template: Option<String>,
 ) -> Result<Value, String> {
-    let path = format!("/plans/{}/approve", plan_id);
-    request_json(Method::POST, &base_url, &path, token, Some(json!({ "execute": execute }))).await
+    trace_context::within(
+        trace_id.as_deref(),
+        "approve_plan",
+        decisions::send_decision(
+            &base_url,
+            token,
+            &plan_id,
+            "approve",
+            Some(json!({
+                "execute": execute,
+                "justification": justification,
+                "reason_template": reason_template,
+            })),
+            version.as_deref(),
+            override_confirmation.as_deref(),
+        ),
+    )
+    .await
 }
 
 #[tauri::command]
@@ -38,14 +240,25 @@ async fn reject_plan(
     token: Option<String>,
     plan_id: String,
     reason: Option<String>,
+    version: Option<String>,
+    trace_id: Option<String>,
+    reason_template: Option<String>,
 ) -> Result<Value, String> {
-    let path = format!("/plans/{}/reject", plan_id);
-    request_json(
-        Method::POST,
-        &base_url,
-        &path,
-        token,
-        Some(json!({ "reason": reason.unwrap_or_else(|| "Operator rejected".to_string()) })),
+    let reason = reason
+        .filter(|r| !r.trim().is_empty())
+        .unwrap_or_else(|| decisions::DEFAULT_REJECT_REASON.to_string());
+    trace_context::within(
+        trace_id.as_deref(),
+        "reject_plan",
+        decisions::send_decision(
+            &base_url,
+            token,
+            &plan_id,
+            "reject",
+            Some(json!({ "reason": reason, "reason_template": reason_template })),
+            version.as_deref(),
+            None,
+        ),
     )
     .await
 }
@@ -56,11 +269,24 @@ async fn request_json(
     path: &str,
     token: Option<String>,
     payload: Option<Value>,
+) -> Result<Value, String> {
+    request_json_with_headers(method, base_url, path, token, payload, &[]).await
+}
+
+async fn request_json_with_headers(
+    method: Method,
+    base_url: &str,
+    path: &str,
+    token: Option<String>,
+    mut payload: Option<Value>,
+    headers: &[(&str, String)],
 ) -> Result<Value, String> {
     let base = base_url.trim().trim_end_matches('/');
     if base.is_empty() {
-        return Err("Base URL is required".to_string());
+        return Err(ErrorCode::InvalidInput.with("Base URL is required"));
     }
+    let read = routing::route(&method, base);
+    let base = read.as_deref().unwrap_or(base);
 
     let normalized_path = if path.trim().is_empty() {
         "/".to_string()
@@ -69,56 +295,601 @@ async fn request_json(
     } else {
         format!("/{}", path)
     };
-    let url = format!("{}{}", base, normalized_path);
-    let parsed_url = Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
-    let scheme = parsed_url.scheme().to_ascii_lowercase();
-    if scheme != "http" && scheme != "https" {
-        return Err("Only http/https base URLs are supported".to_string());
+    core_path::check(&normalized_path)?;
+    let mounted = api_prefix::for_request(base, &normalized_path);
+    let scoped = tenants::scope(base_url, &normalized_path);
+    let root = match &scoped {
+        Some(tenants::Scope::Prefix(prefix)) => format!("{}{}{}", base, mounted, prefix),
+        _ => format!("{}{}", base, mounted),
+    };
+    let parse = |url: String| {
+        Url::parse(&url).map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))
+    };
+    let parsed_url = parse(format!("{}{}", root, normalized_path))?;
+    // What the policies below match: the route the core will see.
+    let route = core_path::canonical(&parsed_url, &parse(format!("{}/", root))?)?;
+    if !transport::supports(parsed_url.scheme()) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Base URLs must be http, https, {} or {}",
+            transport::UNIX,
+            transport::MOCK
+        )));
+    }
+    allowlist::check(base)?;
+    permissions::check(&method, base, &route, token.as_deref())?;
+    // Every command that can decide a plan comes through here; the plan
+    // reads the policies need make this recursive.
+    let if_match = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("If-Match"))
+        .map(|(_, value)| value.as_str());
+    let gate = Box::pin(decisions::gate(
+        &method,
+        base,
+        &route,
+        token.as_deref(),
+        &mut payload,
+        if_match,
+    ))
+    .await?;
+    environment::guard(&method, base, &route, payload.as_ref(), true)?;
+    maintenance::check(&method, base)?;
+    rate_limits::pace(base).await?;
+    let _mutation = shutdown::begin(&method)?;
+    let core = transport::for_url(&parsed_url)?;
+    // Replayed on the primary or another replica if this endpoint fails;
+    // only safe methods are, as the core may already have applied anything
+    // else.
+    let replay = method.is_safe().then(|| (token.clone(), payload.clone()));
+
+    let span = trace_context::outgoing();
+    let mut request_headers = vec![("traceparent".to_string(), span.traceparent())];
+    request_headers.extend(headers.iter().map(|(n, v)| (n.to_string(), v.clone())));
+    if let Some(tenants::Scope::Header(name, value)) = scoped {
+        request_headers.push((name, value));
     }
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(20))
-        .build()
-        .map_err(|e| format!("HTTP client init failed: {}", e))?;
-
-    let mut req = client.request(method, parsed_url);
-    if let Some(tok) = token {
-        let trimmed = tok.trim().to_string();
-        if !trimmed.is_empty() {
-            req = req.bearer_auth(trimmed);
+    let sent_bytes =
+        bandwidth::request_bytes(&method, &parsed_url, &request_headers, payload.as_ref());
+    let request = transport::CoreRequest {
+        method: method.clone(),
+        base_url: base.to_string(),
+        url: parsed_url.clone(),
+        token,
+        headers: request_headers,
+        body: payload,
+    };
+
+    let sent_at = chrono::Utc::now();
+    let response = transport::send(core.as_ref(), request).await;
+    span.finish(
+        &method,
+        &parsed_url,
+        match &response {
+            Ok(response) => Ok(response.status.as_u16()),
+            Err(e) if errors::code_of(e) == Some(ErrorCode::CoreTimeout) => Err("timeout"),
+            Err(_) => Err("unreachable"),
+        },
+    );
+    request_history::record(
+        &method,
+        &parsed_url,
+        match &response {
+            Ok(response) => Some(&response.url),
+            Err(_) => None,
+        },
+        match &response {
+            Ok(response) => Ok(response.status.as_u16()),
+            Err(e) => Err(e
+                .split_once(": ")
+                .map_or(e.as_str(), |(_, m)| m)
+                .to_string()),
+        },
+        sent_at,
+    );
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            let failover = matches!(
+                errors::code_of(&err),
+                Some(ErrorCode::CoreTimeout | ErrorCode::CoreUnreachable)
+            );
+            if failover {
+                let next = routing::failed(read.is_some(), base_url, base, &err);
+                if let (Some(next), Some((token, payload))) = (next, replay) {
+                    return Box::pin(request_json_with_headers(
+                        method, &next, path, token, payload, headers,
+                    ))
+                    .await;
+                }
+            }
+            return Err(err);
         }
+    };
+    // Failed requests are not counted: most never left the machine.
+    bandwidth::request(
+        base_url,
+        sent_bytes,
+        bandwidth::response_bytes(&response.headers, bandwidth::body_bytes(&response.body)),
+    );
+    if !method.is_safe() {
+        routing::wrote(base);
     }
-    if let Some(body) = payload {
-        req = req.json(&body);
+    let status = response.status;
+    if status.is_redirection() && status != reqwest::StatusCode::NOT_MODIFIED {
+        let location = response
+            .headers
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        return Err(ErrorCode::CoreError.with(format!(
+            "Core API {}: redirected to {}, which this profile does not follow",
+            status.as_u16(),
+            location
+        )));
     }
+    let retry_after = maintenance::retry_after(&response.headers);
+    clock::observe(base, &response.headers, sent_at);
+    rate_limits::observe(base, status.as_u16(), &response.headers);
+    latency::observe(
+        base,
+        &method,
+        &normalized_path,
+        status.as_u16(),
+        sent_at,
+        &response.headers,
+    );
+    let body_text = match response.body {
+        spill::Body::Buffered(text) => text,
+        spill::Body::Spilled(mut handle) if status.is_success() => {
+            if let Err(err) = spill::scrub(&mut handle).await {
+                let id = handle["handle"].as_str().unwrap_or_default().to_string();
+                let _ = spill::release_chunked_body(id).await;
+                return Err(err);
+            }
+            if let Some(gate) = gate {
+                gate.finish(&mut handle);
+            }
+            return Ok(handle);
+        }
+        spill::Body::Spilled(handle) => {
+            if status == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+                maintenance::record(base, retry_after, "");
+            }
+            let id = handle["handle"].as_str().unwrap_or_default().to_string();
+            let _ = spill::release_chunked_body(id).await;
+            return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+                "Core API {}: error body of {} bytes discarded",
+                status.as_u16(),
+                handle["size_bytes"]
+            )));
+        }
+    };
 
-    let response = req.send().await.map_err(|e| format!("Request failed: {}", e))?;
-    let status = response.status();
-    let body_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Read response failed: {}", e))?;
-
+    if status == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        maintenance::record(base, retry_after, &body_text);
+    } else {
+        maintenance::clear(base);
+    }
     if !status.is_success() {
-        return Err(format!("Core API {}: {}", status.as_u16(), body_text));
+        let err = ErrorCode::from_status(status.as_u16()).with(format!(
+            "Core API {}: {}",
+            status.as_u16(),
+            body_text
+        ));
+        if matches!(status.as_u16(), 502..=504) {
+            let next = routing::failed(read.is_some(), base_url, base, &err);
+            if let (Some(next), Some((token, payload))) = (next, replay) {
+                return Box::pin(request_json_with_headers(
+                    method, &next, path, token, payload, headers,
+                ))
+                .await;
+            }
+        }
+        return Err(err);
     }
 
     if body_text.trim().is_empty() {
-        return Ok(json!({}));
+        let mut empty = json!({});
+        if let Some(gate) = gate {
+            gate.finish(&mut empty);
+        }
+        return Ok(empty);
     }
-    match serde_json::from_str(&body_text) {
-        Ok(value) => Ok(value),
-        Err(_) => Ok(json!({ "raw": body_text })),
+    let parsing = std::time::Instant::now();
+    let mut parsed = match serde_json::from_str(&body_text) {
+        Ok(mut value) => {
+            sanitize::scrub(&mut value);
+            value
+        }
+        Err(_) => json!({ "raw": body_text }),
+    };
+    resources::request(
+        &method,
+        &normalized_path,
+        body_text.len(),
+        parsing.elapsed(),
+    );
+    if let Some(gate) = gate {
+        gate.finish(&mut parsed);
     }
+    Ok(parsed)
 }
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![
-            core_request,
-            fetch_dashboard_data,
-            approve_plan,
-            reject_plan
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![autostart::AUTOSTART_ARG]),
+        ))
+        .plugin(tauri_plugin_notification::init())
+        .register_uri_scheme_protocol(app_protocol::SCHEME, app_protocol::handle)
+        .register_asynchronous_uri_scheme_protocol(thumbnails::SCHEME, thumbnails::handle)
+        .register_asynchronous_uri_scheme_protocol(cache_protocol::SCHEME, cache_protocol::handle)
+        .manage(pty::PtyState::default())
+        .manage(features::FeatureCache::default())
+        .manage(response_cache::ResponseCache::default())
+        .manage(budget::BudgetAlerts::default())
+        .manage(decisions::ScheduledDecisions::default())
+        .manage(notification_actions::NotificationIntents::default())
+        .manage(deadlines::DeadlineAlerts::default())
+        .manage(tunnel::Tunnels::default())
+        .manage(live::LiveStream::default())
+        .manage(plan_output::PlanOutputStreams::default())
+        .manage(plan_watch::PlanWatches::default())
+        .manage(jwt::TokenAlerts::default())
+        .manage(halt::HaltConfirmations::default())
+        .manage(wipe::WipeConfirmation::default())
+        .manage(quiet::QuietDigest::default())
+        .manage(digest::ActivityDigest::default())
+        .manage(stall::StallTracker::default())
+        .manage(step_gate::StepGates::default())
+        .manage(abort::PendingAborts::default())
+        .manage(tray::PendingApprovals::default())
+        .manage(state::StateStore::default())
+        .manage(plugins::PluginHost::default())
+        .manage(plugin_permissions::PermissionPrompts::default())
+        .manage(presence::Presence::default())
+        .manage(plugin_registry::PendingInstalls::default())
+        .setup(|app| {
+            spill::sweep_stale();
+            app.manage(settings::SettingsStore::open(app.handle()));
+            cookies::load(app.handle());
+            if safe_mode::active() {
+                app.manage(db::LocalDb::in_memory(
+                    json!({ "encrypted": false, "persistent": false, "safe_mode": true }),
+                ));
+            } else {
+                app.manage(db::LocalDb::open(app.handle()));
+            }
+            app_protocol::verify(app.handle());
+            app_protocol::open_main_window(app.handle())?;
+            budget::spawn_checker(app.handle().clone());
+            deadlines::spawn_watcher(app.handle().clone());
+            tunnel::spawn_supervisor(app.handle().clone());
+            jwt::spawn_watcher(app.handle().clone());
+            clock::spawn_watcher(app.handle().clone());
+            maintenance::spawn_watcher(app.handle().clone());
+            rate_limits::spawn_watcher(app.handle().clone());
+            quiet::spawn_watcher(app.handle().clone());
+            digest::spawn_watcher(app.handle().clone());
+            notification_actions::spawn_watcher(app.handle().clone());
+            plan_watch::spawn_watcher(app.handle().clone());
+            presence::spawn_heartbeat(app.handle().clone());
+            claims::spawn_expiry(app.handle().clone());
+            auto_approval::spawn_worker(app.handle().clone());
+            availability::spawn_watcher(app.handle().clone());
+            announcements::spawn_fetcher(app.handle().clone());
+            my_decisions::spawn_recorder(app.handle().clone());
+            plan_revisions::spawn_watcher(app.handle().clone());
+            tray::install(app.handle())?;
+            tray::spawn_poller(app.handle().clone());
+            state::spawn_pusher(app.handle().clone());
+            stall::spawn_watcher(app.handle().clone());
+            step_gate::spawn_watcher(app.handle().clone());
+            if !safe_mode::active() {
+                sync::spawn_syncer(app.handle().clone());
+                approval_schedule::spawn_scheduler(app.handle().clone());
+            }
+            vault::spawn_renewer(app.handle().clone());
+            telemetry::spawn_exporter(app.handle().clone());
+            ticketing::spawn_sync(app.handle().clone());
+            chat_webhooks::spawn_poster(app.handle().clone());
+            calendar::spawn_refresher(app.handle().clone());
+            retention::spawn_compactor(app.handle().clone());
+            latency::spawn_flusher(app.handle().clone());
+            bandwidth::spawn_flusher(app.handle().clone());
+            watchdog::spawn_watcher(app.handle().clone());
+            webhook_receiver::spawn_receiver(app.handle().clone());
+            status_endpoint::spawn_endpoint(app.handle().clone());
+            macro_pad::spawn_listener(app.handle().clone());
+            api_prefix::spawn_detector(app.handle().clone());
+            replicas::spawn_checker(app.handle().clone());
+            config_check::spawn_check(app.handle().clone());
+            if !safe_mode::active() {
+                plugins::spawn_loader(app.handle().clone());
+            }
+            autostart::show_on_startup(app.handle(), &app.state::<settings::SettingsStore>());
+            launch_actions::run(app.handle());
+            Ok(())
+        })
+        .invoke_handler(session_recording::recorded(permissions::observed(
+            tauri::generate_handler![
+                core_request,
+                pagination::core_request_all,
+                pagination::cancel_pagination,
+                fetch_dashboard_data,
+                approve_plan,
+                reject_plan,
+                agent_env::list_env,
+                a11y::summarize_plan_for_a11y,
+                abort::abort_execution,
+                agent_env::set_secret,
+                agent_env::delete_secret,
+                airgap::get_airgap_key,
+                airgap::export_plan_bundle,
+                airgap::import_plan_bundle,
+                airgap::get_offline_review,
+                airgap::decide_offline,
+                airgap::export_decision_file,
+                airgap::import_decision_file,
+                announcements::get_announcements,
+                announcements::refresh_announcements,
+                announcements::mark_announcements_read,
+                ansi::parse_ansi,
+                app_protocol::get_asset_integrity,
+                approval_schedule::schedule_approval,
+                approval_schedule::list_scheduled_approvals,
+                approval_schedule::cancel_scheduled_approval,
+                approval_schedule::resolve_schedule_conflict,
+                artifact_sync::sync_artifact,
+                artifact_sync::list_synced_artifacts,
+                attachments::validate_attachment,
+                attachments::upload_attachment,
+                attachments::list_plan_attachments,
+                attachments::preview_attachment,
+                audit_export::export_audit,
+                audio::list_alert_sounds,
+                audio::set_alert_sound,
+                audio::play_alert_sound,
+                audio::test_alert_sound,
+                autostart::get_autostart,
+                autostart::set_autostart,
+                availability::get_operator_status,
+                availability::report_operator_activity,
+                availability::set_operator_status,
+                bandwidth::bandwidth_report,
+                budget::set_budget,
+                budget::get_budget_status,
+                budget::check_budgets_now,
+                budget::resume_auto_approval,
+                calendar::get_calendar_freezes,
+                calendar::refresh_calendar,
+                calendar::set_calendar_secret,
+                chat_webhooks::get_chat_webhook_status,
+                chat_webhooks::set_chat_webhook_url,
+                chat_webhooks::test_chat_webhook,
+                claims::claim_plan,
+                claims::release_plan,
+                clipboard::copy_to_clipboard,
+                clock::get_clock_skew,
+                config_check::validate_config,
+                cookies::clear_cookie_jar,
+                cookies::get_cookie_jar,
+                core_signing::get_core_signing_status,
+                core_signing::set_core_signing_secret,
+                db::database_encryption_status,
+                db::database_recovery_key,
+                db::database_schema_status,
+                db::restore_database,
+                deadlines::get_plan_deadlines,
+                deadlines::refresh_plan_deadlines,
+                decision_metrics::export_decision_metrics,
+                decisions::send_plan_decision,
+                decisions::schedule_plan_decision,
+                decisions::cancel_plan_decision,
+                decisions::list_scheduled_decisions,
+                device_login::start_device_login,
+                device_login::cancel_device_login,
+                digest::get_digest_status,
+                digest::set_digest_interval,
+                digest::flush_digest,
+                environment::confirm_production_action,
+                environment::get_environment,
+                errors::get_error_catalog,
+                exec_windows::get_execution_windows,
+                exec_windows::request_execution_override,
+                features::get_features,
+                halt::emergency_halt,
+                handover::generate_handover,
+                justifications::delete_reason_template,
+                justifications::get_justification_requirement,
+                justifications::list_reason_templates,
+                justifications::upsert_reason_template,
+                jwt::inspect_token,
+                latency::performance_report,
+                launch_actions::get_launch_action,
+                live::start_live_events,
+                live::stop_live_events,
+                local_backup::backup_local_data,
+                local_backup::restore_local_data,
+                local_exec::execute_local_plan,
+                log_search::search_logs,
+                logs::get_logs,
+                macro_pad::get_macro_pad,
+                macro_pad::set_current_plan,
+                macro_pad::press_macro_button,
+                macro_pad::rotate_macro_pad_token,
+                managed::get_managed_policy,
+                markdown::render_markdown,
+                memory::list_memories,
+                memory::search_memories,
+                memory::delete_memory,
+                metrics::get_metric_series,
+                models::get_model_config,
+                models::update_model_config,
+                my_decisions::list_my_decisions,
+                net::network_diagnostics,
+                notification_actions::notification_action,
+                notification_actions::list_notification_intents,
+                notification_actions::cancel_notification_intent,
+                notify::get_notification_rules,
+                notify::set_notification_rules,
+                notify::evaluate_notification_rules,
+                permissions::get_permissions,
+                permissions::set_profile_read_only,
+                plan_compare::compare_plans,
+                plan_graph::get_plan_graph,
+                plan_output::plan_output_transcript,
+                plan_output::stop_plan_output,
+                plan_output::watch_plan_output,
+                plan_pdf::export_plan_pdf,
+                plan_revisions::request_revision,
+                plan_revisions::get_plan_revisions,
+                plan_secrets::scan_plan_secrets,
+                plan_watch::watch_plan,
+                plan_watch::unwatch_plan,
+                plan_watch::list_watched_plans,
+                plan_window::get_plans_window,
+                plugin_permissions::list_plugin_permission_requests,
+                plugin_permissions::answer_plugin_permission,
+                plugin_permissions::reset_plugin_permissions,
+                plugin_registry::browse_plugin_registry,
+                plugin_registry::install_plugin,
+                plugin_registry::confirm_plugin_install,
+                plugin_registry::remove_plugin,
+                plugin_registry::pin_plugin,
+                plugins::list_plugins,
+                plugins::set_plugin_grant,
+                plugins::reload_plugins,
+                plugins::invoke_plugin,
+                plugins::plugin_palette_entries,
+                presence::get_plan_presence,
+                presence::join_plan_presence,
+                presence::leave_plan_presence,
+                private_notes::list_note_keys,
+                private_notes::create_note_team_key,
+                private_notes::export_note_key,
+                private_notes::import_note_key,
+                private_notes::remove_note_key,
+                private_notes::save_private_note,
+                private_notes::get_private_note,
+                preview::preview_path,
+                pty::pty_open,
+                pty::pty_open_remote,
+                pty::pty_write,
+                pty::pty_resize,
+                pty::pty_close,
+                quick_actions::delete_quick_action,
+                quick_actions::list_quick_actions,
+                quick_actions::run_quick_action,
+                quick_actions::upsert_quick_action,
+                quiet::get_quiet_status,
+                rate_limits::get_rate_limits,
+                recent::clear_recent_items,
+                recent::get_recent_items,
+                recent::record_recent_item,
+                replicas::replica_status,
+                request_history::get_request_history,
+                response_cache::cached_core_request,
+                retention::cache_stats,
+                review_queue::next_pending,
+                review_queue::previous,
+                review_queue::decide_current,
+                safe_mode::get_safe_mode,
+                safe_mode::restart_safe_mode,
+                sanitize::sanitize_html,
+                screenshot::capture_screenshot,
+                session_recording::start_session_recording,
+                session_recording::get_session_recording,
+                session_recording::stop_session_recording,
+                session_recording::load_session_recording,
+                session_recording::replay_session_recording,
+                session_recording::stop_session_replay,
+                settings::get_settings,
+                settings::update_settings,
+                settings::upsert_profile,
+                settings::delete_profile,
+                settings::set_profile_token,
+                settings::set_profile_credential,
+                settings::set_tunnel_password,
+                settings::set_proxy_password,
+                settings::get_profile_token,
+                settings_bundle::export_settings_bundle,
+                settings_bundle::import_settings_bundle,
+                setup::probe_core_url,
+                setup::run_first_run_setup,
+                api_prefix::detect_api_prefix,
+                shutdown::confirm_close,
+                spill::read_chunk,
+                spill::release_chunked_body,
+                stall::list_stalled_plans,
+                stall::retry_step,
+                step_gate::approve_plan_step_gated,
+                step_gate::approve_step,
+                step_gate::list_step_gates,
+                state::subscribe_state,
+                state::unsubscribe_state,
+                state::refresh_state,
+                status_endpoint::get_status_endpoint,
+                sync::sync_changes,
+                sync::get_synced,
+                tasks::list_background_tasks,
+                tasks::restart_task,
+                telemetry::get_telemetry_status,
+                telemetry::flush_telemetry,
+                ticketing::get_plan_ticket,
+                ticketing::get_ticketing_status,
+                ticketing::retry_ticket_sync,
+                ticketing::set_ticketing_secret,
+                time_travel::get_dashboard_at,
+                timeline::get_plan_timeline,
+                tls::list_tls_cipher_suites,
+                trace::get_plan_trace,
+                translation::get_plan_translated,
+                translation::set_translation_key,
+                translation::translate_content,
+                transport::set_mock_core,
+                transport::replay_core_recording,
+                transport::get_mock_requests,
+                transport::remove_mock_core,
+                transport::start_core_recording,
+                transport::stop_core_recording,
+                fault_injection::start_fault_injection,
+                fault_injection::get_fault_injection,
+                fault_injection::stop_fault_injection,
+                resources::resource_report,
+                formatting::format_values,
+                residency::check_data_residency,
+                tenants::list_tenants,
+                tenants::switch_tenant,
+                tray::refresh_pending_approvals,
+                tunnel::restart_tunnel,
+                tunnel::tunnel_status,
+                two_person::confirm_second_approval,
+                two_person::enroll_second_approver,
+                two_person::remove_second_approver,
+                two_person::request_confirmation_code,
+                usage::sync_usage,
+                usage::get_usage_series,
+                vault::vault_status,
+                vault::vault_refresh,
+                vault::set_vault_secret,
+                vault::vault_oidc_login,
+                views::create_view,
+                views::delete_view,
+                views::list_views,
+                views::reorder_views,
+                views::update_view,
+                watchdog::list_slow_requests,
+                webhook_receiver::get_webhook_receiver,
+                webhook_receiver::upsert_webhook_automation,
+                webhook_receiver::delete_webhook_automation,
+                webhook_receiver::rotate_webhook_receiver_secret,
+                wipe::wipe_local_data
+            ],
+        )))
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(shutdown::on_run_event);
 }
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::errors::ErrorCode;
+use crate::{audio, environment, net, notify, tasks};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const MAINTENANCE_EVENT: &str = "core:maintenance";
+const ENDED_EVENT: &str = "core:maintenance_ended";
+const MIN_BACKOFF: Duration = Duration::from_secs(15);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+struct Window {
+    base_url: String,
+    until: Instant,
+    retry_at: DateTime<Utc>,
+    attempts: u32,
+    reason: String,
+}
+
+/// Cores currently answering 503, keyed by origin.
+static WINDOWS: OnceLock<Mutex<HashMap<String, Window>>> = OnceLock::new();
+
+fn windows() -> &'static Mutex<HashMap<String, Window>> {
+    WINDOWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Fails polling (GET) requests fast while their core is in a maintenance
+/// window, so background loops and the dashboard stop hammering it. Other
+/// methods still go through; an operator action is never held back here.
+pub fn check(method: &Method, base_url: &str) -> Result<(), String> {
+    if *method != Method::GET {
+        return Ok(());
+    }
+    let Some(origin) = net::base_origin(base_url) else {
+        return Ok(());
+    };
+    let remaining = windows().lock().ok().and_then(|w| {
+        w.get(&origin)
+            .map(|window| window.until.saturating_duration_since(Instant::now()))
+    });
+    match remaining {
+        Some(left) if !left.is_zero() => Err(ErrorCode::CoreMaintenance.with(format!(
+            "Core is under maintenance; polling resumes in {}s",
+            left.as_secs().max(1)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The delay a `Retry-After` header asks for, in either its seconds or its
+/// HTTP-date form.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let raw = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match raw.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let at = DateTime::parse_from_rfc2822(raw).ok()?.with_timezone(&Utc);
+            (at - Utc::now()).to_std().unwrap_or_default()
+        }
+    };
+    Some(delay.clamp(Duration::from_secs(1), MAX_RETRY_AFTER))
+}
+
+/// Records a 503 from the core. Without `Retry-After` the wait doubles with
+/// each consecutive 503, from `MIN_BACKOFF` up to `MAX_BACKOFF`.
+pub fn record(base_url: &str, retry_after: Option<Duration>, body: &str) {
+    let Some(origin) = net::base_origin(base_url) else {
+        return;
+    };
+    let reason = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            ["message", "reason", "error", "detail"]
+                .iter()
+                .find_map(|k| v[*k].as_str().map(str::to_string))
+        })
+        .unwrap_or_default();
+    reschedule(&origin, base_url, retry_after, Some(reason));
+}
+
+/// Any non-503 answer means the core is back.
+pub fn clear(base_url: &str) {
+    if let (Some(origin), Ok(mut windows)) = (net::base_origin(base_url), windows().lock()) {
+        windows.remove(&origin);
+    }
+}
+
+fn reschedule(origin: &str, base_url: &str, retry_after: Option<Duration>, reason: Option<String>) {
+    let Ok(mut windows) = windows().lock() else {
+        return;
+    };
+    let window = windows.entry(origin.to_string()).or_insert_with(|| Window {
+        base_url: base_url.trim().trim_end_matches('/').to_string(),
+        until: Instant::now(),
+        retry_at: Utc::now(),
+        attempts: 0,
+        reason: String::new(),
+    });
+    window.attempts += 1;
+    let delay = retry_after.unwrap_or_else(|| {
+        MIN_BACKOFF
+            .saturating_mul(1 << (window.attempts - 1).min(5))
+            .min(MAX_BACKOFF)
+    });
+    window.until = Instant::now() + delay;
+    window.retry_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+    if let Some(reason) = reason.filter(|r| !r.is_empty()) {
+        window.reason = reason;
+    }
+}
+
+/// Emits a `core:maintenance` countdown every second for each core in a
+/// window, probes `/health` once the window runs out, and emits
+/// `core:maintenance_ended` when the core answers normally again.
+const TASK: &str = "maintenance";
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        let mut announced: HashMap<String, String> = HashMap::new();
+        loop {
+            let due = tick(&app, &mut announced);
+            for (origin, base_url) in due {
+                probe(&origin, &base_url).await;
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+/// Emits the countdowns and returns the cores whose window has run out.
+fn tick(app: &AppHandle, announced: &mut HashMap<String, String>) -> Vec<(String, String)> {
+    let now = Instant::now();
+    let mut due = Vec::new();
+    let mut active = HashMap::new();
+    let mut started = Vec::new();
+    if let Ok(windows) = windows().lock() {
+        for (origin, window) in windows.iter() {
+            let remaining = window.until.saturating_duration_since(now);
+            let payload = json!({
+                "base_url": window.base_url,
+                "retry_at": window.retry_at.to_rfc3339(),
+                "retry_in_seconds": remaining.as_secs(),
+                "attempt": window.attempts,
+                "reason": window.reason,
+            });
+            let _ = environment::emit(app, MAINTENANCE_EVENT, &payload);
+            if !announced.contains_key(origin) {
+                started.push(payload);
+            }
+            if remaining.is_zero() {
+                due.push((origin.clone(), window.base_url.clone()));
+            }
+            active.insert(origin.clone(), window.base_url.clone());
+        }
+    }
+    for payload in started {
+        let _ = audio::play_for(app, "core_down", false);
+        notify::notify(
+            app,
+            "maintenance",
+            "Core under maintenance",
+            &format!(
+                "{} is unavailable; polling is suspended until it is back",
+                payload["base_url"].as_str().unwrap_or_default()
+            ),
+            payload,
+        );
+    }
+    for (origin, base_url) in announced.iter() {
+        if active.contains_key(origin) {
+            continue;
+        }
+        let payload = json!({ "base_url": base_url });
+        let _ = environment::emit(app, ENDED_EVENT, &payload);
+        notify::notify_in_app(
+            app,
+            "maintenance",
+            "Core is back",
+            "Maintenance has ended; polling has resumed",
+            payload,
+        );
+    }
+    *announced = active;
+    due
+}
+
+/// A 503 reply reschedules through `record`, and any other reply clears the
+/// window in the request path. Only a core that cannot be reached at all
+/// needs extending here.
+async fn probe(origin: &str, base_url: &str) {
+    let result = crate::request_json(Method::GET, base_url, "/health", None, None).await;
+    if let Err(err) = result {
+        if matches!(
+            crate::errors::code_of(&err),
+            Some(ErrorCode::CoreUnreachable | ErrorCode::CoreTimeout)
+        ) {
+            reschedule(origin, base_url, None, None);
+        }
+    }
+}
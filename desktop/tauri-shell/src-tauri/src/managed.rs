@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::allowlist;
+use crate::errors::ErrorCode;
+use crate::settings::{self, EnvironmentClass, Profile, Settings, TlsSettings, TlsVersion};
+use crate::tls;
+
+/// Plain JSON policy file read on Linux and the other Unixes.
+#[cfg(all(unix, not(target_os = "macos")))]
+const POLICY_FILE: &str = "/etc/novaadapt/managed.json";
+/// macOS managed preferences domain, as deployed by an MDM profile.
+#[cfg(target_os = "macos")]
+const PREFERENCES_DOMAIN: &str = "com.novaadapt.desktop";
+/// Group Policy key; its `Policy` string value holds the policy as JSON.
+#[cfg(windows)]
+const POLICY_KEY: &str = "SOFTWARE\\Policies\\NovaAdapt\\Desktop";
+#[cfg(windows)]
+const POLICY_VALUE: &str = "Policy";
+
+/// Settings an administrator provisions for every user of the machine.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// Cores profiles may point at: `https://core.example.com`, a path
+    /// prefix under one, or `https://*.example.com` for a whole domain.
+    /// Empty allows any core.
+    pub allowed_base_urls: Vec<String>,
+    /// Every profile is read-only, and so is any core reached directly.
+    pub read_only: bool,
+    /// A merge patch over the user's settings; each value it sets is locked.
+    pub settings: Map<String, Value>,
+    /// Further settings users may not change, as JSON pointers into the
+    /// settings document; `*` matches every array element or object key,
+    /// e.g. `/profiles/*/proxy`.
+    pub locked: Vec<String>,
+    pub tls: ManagedTls,
+}
+
+/// TLS floors every profile is held to, on top of its own policy.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManagedTls {
+    /// The oldest version any profile may negotiate.
+    pub min_version: Option<TlsVersion>,
+    /// A stricter floor for `prod` profiles, e.g. `1.3` only.
+    pub prod_min_version: Option<TlsVersion>,
+    /// The only cipher suites profiles may offer; empty leaves them to
+    /// each profile.
+    pub cipher_suites: Vec<String>,
+}
+
+impl ManagedTls {
+    fn floor(&self, profile: &Profile) -> Option<TlsVersion> {
+        let prod = self
+            .prod_min_version
+            .filter(|_| profile.environment == EnvironmentClass::Prod);
+        self.min_version.max(prod)
+    }
+
+    fn allows(&self, suite: &str) -> bool {
+        self.cipher_suites.is_empty()
+            || self
+                .cipher_suites
+                .iter()
+                .any(|allowed| allowed.trim().eq_ignore_ascii_case(suite.trim()))
+    }
+
+    /// Why `profile`'s TLS policy is weaker than this one, if it is.
+    fn shortfall(&self, profile: &Profile) -> Option<String> {
+        if let Some(floor) = self.floor(profile).filter(|f| profile.tls.min_version < *f) {
+            return Some(format!(
+                "TLS {} or later is required by your administrator",
+                version_name(floor)
+            ));
+        }
+        if !self.cipher_suites.is_empty() && profile.tls.cipher_suites.is_empty() {
+            return Some("cipher suites are set by your administrator".to_string());
+        }
+        profile
+            .tls
+            .cipher_suites
+            .iter()
+            .find(|suite| !self.allows(suite))
+            .map(|suite| {
+                format!(
+                    "cipher suite {} is not allowed by your administrator",
+                    suite
+                )
+            })
+    }
+
+    /// Raises `profile`'s TLS policy to this one: its floor, and only the
+    /// allowed suites it lists, or all of them when none of its own are left
+    /// for the version it now needs.
+    fn raise(&self, profile: &mut Profile) {
+        if let Some(floor) = self.floor(profile) {
+            profile.tls.min_version = profile.tls.min_version.max(floor);
+        }
+        if self.cipher_suites.is_empty() {
+            return;
+        }
+        let kept: Vec<String> = profile
+            .tls
+            .cipher_suites
+            .iter()
+            .filter(|suite| self.allows(suite))
+            .cloned()
+            .collect();
+        let raised = TlsSettings {
+            min_version: profile.tls.min_version,
+            cipher_suites: kept,
+        };
+        profile.tls.cipher_suites =
+            if raised.cipher_suites.is_empty() || tls::validate(&raised).is_err() {
+                self.cipher_suites.clone()
+            } else {
+                raised.cipher_suites
+            };
+    }
+}
+
+fn version_name(version: TlsVersion) -> &'static str {
+    match version {
+        TlsVersion::Tls12 => "1.2",
+        TlsVersion::Tls13 => "1.3",
+    }
+}
+
+struct Loaded {
+    source: Option<String>,
+    policy: Policy,
+    error: Option<String>,
+}
+
+fn loaded() -> &'static Loaded {
+    static LOADED: OnceLock<Loaded> = OnceLock::new();
+    LOADED.get_or_init(|| {
+        let Some((source, raw)) = read() else {
+            return Loaded {
+                source: None,
+                policy: Policy::default(),
+                error: None,
+            };
+        };
+        let parsed = raw.and_then(|value| {
+            let policy: Policy = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            check_policy(&policy)?;
+            Ok(policy)
+        });
+        match parsed {
+            Ok(policy) => Loaded {
+                source: Some(source),
+                policy,
+                error: None,
+            },
+            Err(err) => {
+                eprintln!("managed policy {} is invalid, ignoring it: {}", source, err);
+                Loaded {
+                    source: Some(source),
+                    policy: Policy::default(),
+                    error: Some(err),
+                }
+            }
+        }
+    })
+}
+
+pub fn policy() -> &'static Policy {
+    &loaded().policy
+}
+
+/// Where the policy came from and why it was ignored, if it was.
+pub fn load_error() -> Option<(&'static str, &'static str)> {
+    let loaded = loaded();
+    Some((loaded.source.as_deref()?, loaded.error.as_deref()?))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn read() -> Option<(String, Result<Value, String>)> {
+    let raw = match std::fs::read(POLICY_FILE) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => return Some((POLICY_FILE.to_string(), Err(e.to_string()))),
+    };
+    Some((
+        POLICY_FILE.to_string(),
+        serde_json::from_slice(&raw).map_err(|e| e.to_string()),
+    ))
+}
+
+/// The machine-wide managed preferences, then the current user's.
+#[cfg(target_os = "macos")]
+fn read() -> Option<(String, Result<Value, String>)> {
+    let base = std::path::Path::new("/Library/Managed Preferences");
+    let file = format!("{}.plist", PREFERENCES_DOMAIN);
+    let user = std::env::var("USER").unwrap_or_default();
+    let path = [base.join(&file), base.join(user).join(&file)]
+        .into_iter()
+        .find(|p| p.is_file())?;
+    Some((
+        path.display().to_string(),
+        plist::from_file::<_, Value>(&path).map_err(|e| e.to_string()),
+    ))
+}
+
+/// Machine policy (HKLM), then user policy (HKCU).
+#[cfg(windows)]
+fn read() -> Option<(String, Result<Value, String>)> {
+    let hives = [
+        (windows_registry::LOCAL_MACHINE, "HKLM"),
+        (windows_registry::CURRENT_USER, "HKCU"),
+    ];
+    hives.into_iter().find_map(|(hive, name)| {
+        let key = hive.open(POLICY_KEY).ok()?;
+        let raw = key.get_string(POLICY_VALUE).ok()?;
+        Some((
+            format!("{}\\{}\\{}", name, POLICY_KEY, POLICY_VALUE),
+            serde_json::from_str(&raw).map_err(|e| e.to_string()),
+        ))
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read() -> Option<(String, Result<Value, String>)> {
+    None
+}
+
+fn check_policy(policy: &Policy) -> Result<(), String> {
+    for entry in &policy.allowed_base_urls {
+        let (url, _) = allowlist::parse_entry(entry)?;
+        settings::validate_base_url(url.as_str())
+            .map_err(|e| format!("allowed_base_urls {:?}: {}", entry, e))?;
+    }
+    for pointer in &policy.locked {
+        if !pointer.starts_with('/') {
+            return Err(format!("Locked setting {:?} must start with /", pointer));
+        }
+    }
+    if !policy.tls.cipher_suites.is_empty() {
+        let strictest = policy.tls.min_version.max(policy.tls.prod_min_version);
+        tls::validate(&TlsSettings {
+            min_version: strictest.unwrap_or_default(),
+            cipher_suites: policy.tls.cipher_suites.clone(),
+        })
+        .map_err(|e| format!("tls: {}", e))?;
+    }
+    let mut doc = serde_json::to_value(Settings::default()).map_err(|e| e.to_string())?;
+    settings::merge_patch(&mut doc, &Value::Object(policy.settings.clone()));
+    serde_json::from_value::<Settings>(doc).map_err(|e| format!("settings: {}", e))?;
+    Ok(())
+}
+
+/// Applies the policy over `settings`: its values win, every profile is
+/// read-only when the policy says so, and none falls below its TLS floor.
+pub fn apply(settings: Settings) -> Settings {
+    let policy = policy();
+    let mut settings = if policy.settings.is_empty() {
+        settings
+    } else {
+        let mut doc = match serde_json::to_value(&settings) {
+            Ok(doc) => doc,
+            Err(_) => return settings,
+        };
+        settings::merge_patch(&mut doc, &Value::Object(policy.settings.clone()));
+        match serde_json::from_value(doc) {
+            Ok(managed) => managed,
+            Err(e) => {
+                eprintln!("managed settings do not apply: {}", e);
+                settings
+            }
+        }
+    };
+    if policy.read_only {
+        for profile in &mut settings.profiles {
+            profile.read_only = true;
+        }
+    }
+    for profile in &mut settings.profiles {
+        policy.tls.raise(profile);
+    }
+    settings
+}
+
+/// The pointers the policy locks: every leaf of its settings patch (arrays
+/// count as one value), the extra `locked` entries and, for display,
+/// profiles' read-only flags when it forces them.
+fn locked_pointers(policy: &Policy, with_read_only: bool) -> Vec<String> {
+    fn leaves(prefix: String, value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    let key = key.replace('~', "~0").replace('/', "~1");
+                    leaves(format!("{}/{}", prefix, key), value, out);
+                }
+            }
+            _ => out.push(prefix),
+        }
+    }
+    let mut out = Vec::new();
+    for (key, value) in &policy.settings {
+        leaves(format!("/{}", key), value, &mut out);
+    }
+    out.extend(policy.locked.iter().cloned());
+    if with_read_only && policy.read_only {
+        out.push("/profiles/*/read_only".to_string());
+    }
+    out
+}
+
+/// Every value at `pointer`, with `*` expanded, keyed by its concrete path.
+fn select(doc: &Value, pointer: &str) -> BTreeMap<String, Value> {
+    fn walk(doc: &Value, path: String, rest: &[&str], out: &mut BTreeMap<String, Value>) {
+        let Some((first, rest)) = rest.split_first() else {
+            out.insert(path, doc.clone());
+            return;
+        };
+        match (*first, doc) {
+            ("*", Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    walk(item, format!("{}/{}", path, i), rest, out);
+                }
+            }
+            ("*", Value::Object(map)) => {
+                for (key, item) in map {
+                    walk(item, format!("{}/{}", path, key), rest, out);
+                }
+            }
+            (segment, _) => {
+                if let Some(item) = doc.pointer(&format!("/{}", segment)) {
+                    walk(item, format!("{}/{}", path, segment), rest, out);
+                }
+            }
+        }
+    }
+    let segments: Vec<&str> = pointer.trim_start_matches('/').split('/').collect();
+    let mut out = BTreeMap::new();
+    walk(doc, String::new(), &segments, &mut out);
+    out
+}
+
+/// Checks a settings change against the policy before it is saved: no
+/// locked value may change, new profiles come out read-only and within the
+/// TLS policy when those are enforced, existing ones may not drop below
+/// it, and new core URLs must be on the allowlist. Profiles and URLs
+/// saved before the policy arrived are left alone; requests to them are
+/// refused instead.
+pub fn enforce(before: &Settings, next: &mut Settings) -> Result<(), String> {
+    let policy = policy();
+    if policy.read_only {
+        for profile in &mut next.profiles {
+            match before.profile(&profile.id) {
+                Some(_) if !profile.read_only => {
+                    return Err(ErrorCode::ForbiddenLocal.with(format!(
+                        "Profile {}: read-only mode is set by your administrator",
+                        profile.id
+                    )));
+                }
+                Some(_) => {}
+                None => profile.read_only = true,
+            }
+        }
+    }
+    for profile in &mut next.profiles {
+        if before.profile(&profile.id).is_none() {
+            policy.tls.raise(profile);
+        } else if let Some(shortfall) = policy.tls.shortfall(profile) {
+            return Err(
+                ErrorCode::ForbiddenLocal.with(format!("Profile {}: {}", profile.id, shortfall))
+            );
+        }
+    }
+    let old = serde_json::to_value(before).map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+    let new = serde_json::to_value(&*next).map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+    for pointer in locked_pointers(policy, false) {
+        let (was, now) = (select(&old, &pointer), select(&new, &pointer));
+        // Profiles come and go under a wildcard; only one present before and
+        // after has a locked value to compare.
+        let changed = now
+            .iter()
+            .filter(|(path, value)| was.get(*path) != Some(value))
+            .map(|(path, _)| path)
+            .chain(was.keys().filter(|path| !now.contains_key(*path)))
+            .find(|path| {
+                !(path.starts_with("/profiles/")
+                    && !(was.contains_key(*path) && now.contains_key(*path)))
+            });
+        if let Some(path) = changed {
+            return Err(
+                ErrorCode::ForbiddenLocal.with(format!("{} is set by your administrator", path))
+            );
+        }
+    }
+    for profile in &next.profiles {
+        let existing = before.profile(&profile.id);
+        let new_urls = profile
+            .endpoints()
+            .chain(profile.read_routing.read_url.as_deref())
+            .filter(|url| {
+                !existing.is_some_and(|p| {
+                    p.endpoints()
+                        .chain(p.read_routing.read_url.as_deref())
+                        .any(|old| old == *url)
+                })
+            });
+        for url in new_urls {
+            if !allowlist::allows(&policy.allowed_base_urls, url) {
+                return Err(ErrorCode::ForbiddenLocal.with(format!(
+                    "Profile {}: {} is not one of the cores your administrator allows",
+                    profile.id, url
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The administrator's policy, if any, for the settings screen to mark
+/// which settings it locks.
+#[tauri::command]
+pub fn get_managed_policy() -> Result<Value, String> {
+    let loaded = loaded();
+    Ok(json!({
+        "managed": loaded.source.is_some() && loaded.error.is_none(),
+        "source": loaded.source,
+        "error": loaded.error,
+        "allowed_base_urls": loaded.policy.allowed_base_urls,
+        "read_only": loaded.policy.read_only,
+        "tls": loaded.policy.tls,
+        "locked": locked_pointers(&loaded.policy, true),
+    }))
+}
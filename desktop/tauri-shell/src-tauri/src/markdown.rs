@@ -0,0 +1,127 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+
+/// Plans and comments are short; anything past this is not prose.
+const MAX_INPUT_BYTES: usize = 512 * 1024;
+pub const LINK_REL: &str = "noopener noreferrer nofollow";
+
+/// Renders core-provided Markdown (CommonMark plus GFM tables,
+/// strikethrough and task lists) to HTML that is safe to assign to
+/// `innerHTML`.
+///
+/// pulldown-cmark parses; raw HTML in the source is turned back into text
+/// before rendering, and images become links to the image so rendering
+/// never loads remote content. The result then goes through ammonia,
+/// which keeps `http(s)`, `mailto` and in-page links and drops every other
+/// URL, tag and attribute.
+#[tauri::command]
+pub fn render_markdown(markdown: String) -> Result<Value, String> {
+    if markdown.len() > MAX_INPUT_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Markdown is {} bytes; at most {} are rendered",
+            markdown.len(),
+            MAX_INPUT_BYTES
+        )));
+    }
+    Ok(json!({ "html": render(&markdown) }))
+}
+
+pub fn render(markdown: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }),
+        Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+        other => other,
+    });
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events);
+    sanitizer().clean(&html).to_string()
+}
+
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder
+            .rm_tags(["img"])
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "disabled"])
+            .add_tag_attributes("code", ["class"])
+            .add_tag_attributes("th", ["style"])
+            .add_tag_attributes("td", ["style"])
+            .filter_style_properties(HashSet::from(["text-align"]))
+            .url_schemes(HashSet::from(["http", "https", "mailto"]))
+            .url_relative(UrlRelative::Custom(Box::new(in_page)))
+            .link_rel(Some(LINK_REL))
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("input", "type") => (value == "checkbox").then_some(value.into()),
+                ("code", "class") => language_class(value).then_some(value.into()),
+                _ => Some(value.into()),
+            });
+        builder
+    })
+}
+
+/// The only relative targets kept are fragments within the rendered text.
+fn in_page(url: &str) -> Option<Cow<'_, str>> {
+    url.starts_with('#').then_some(Cow::Borrowed(url))
+}
+
+fn language_class(value: &str) -> bool {
+    value.strip_prefix("language-").is_some_and(|language| {
+        !language.is_empty()
+            && language
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || "+-_.#".contains(ch))
+    })
+}
+
+fn escape_into(text: &str, out: &mut String) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    escape_into(text, &mut out);
+    out
+}
+
+/// Link targets the webview may follow; anything else (`javascript:`,
+/// `data:`, `file:`, relative paths into the app) is dropped.
+pub fn safe_url(raw: &str) -> Option<String> {
+    let url = raw.trim().trim_start_matches('<').trim_end_matches('>');
+    let lower = url.to_ascii_lowercase();
+    let allowed = ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+        || url.starts_with('#');
+    (allowed && !url.chars().any(char::is_control)).then(|| escape(url))
+}
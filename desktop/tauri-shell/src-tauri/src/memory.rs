@@ -0,0 +1,282 @@
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::fields::FieldSelection;
+use crate::settings::SettingsStore;
+
+const DEFAULT_PAGE: usize = 50;
+const MAX_PAGE: usize = 200;
+const MAX_TOP_K: usize = 100;
+
+/// Lists the core's stored memories a page at a time. Cores without a
+/// `/memory/items` listing are served from the local cache built up by
+/// earlier searches, flagged with `source: "cache"`.
+#[tauri::command]
+pub async fn list_memories(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    refresh: Option<bool>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let offset = offset.unwrap_or(0);
+    let limit = page_limit(limit);
+    let selection = fields
+        .as_deref()
+        .and_then(|f| FieldSelection::parse(f, &[], "memories"));
+
+    let mut core_error = None;
+    if refresh.unwrap_or(true) {
+        let mut path = format!("/memory/items?offset={}&limit={}", offset, limit);
+        if let Some(selection) = &selection {
+            path.push_str(&format!("&fields={}", selection.query_value("memories")));
+        }
+        match crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None)
+            .await
+        {
+            Ok(page) => {
+                let items = normalize_all(&page["memories"]);
+                cache_items(&*db.lock()?, &conn.profile_id, &items).map_err(|e| e.to_string())?;
+                return Ok(project(
+                    selection.as_ref(),
+                    json!({
+                    "profile_id": conn.profile_id,
+                    "source": "core",
+                    "offset": offset,
+                    "limit": limit,
+                    "total": page["total"].as_u64().unwrap_or((offset + items.len()) as u64),
+                    "memories": items,
+                    }),
+                ));
+            }
+            Err(err) => core_error = Some(err),
+        }
+    }
+
+    let guard = db.lock()?;
+    let (items, total) =
+        cached_page(&guard, &conn.profile_id, None, offset, limit).map_err(|e| e.to_string())?;
+    let out = json!({
+        "profile_id": conn.profile_id,
+        "source": "cache",
+        "core_error": core_error,
+        "offset": offset,
+        "limit": limit,
+        "total": total,
+        "memories": items,
+    });
+    Ok(project(selection.as_ref(), out))
+}
+
+fn project(selection: Option<&FieldSelection>, mut out: Value) -> Value {
+    if let Some(selection) = selection {
+        selection.apply(&mut out, "memories");
+    }
+    out
+}
+
+/// Runs a recall query against the core and pages through the ranked hits.
+/// Results are cached so they stay browsable when the core is unreachable,
+/// in which case the cache is searched by substring instead.
+#[tauri::command]
+pub async fn search_memories(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    query: String,
+    top_k: Option<usize>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Value, String> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Search query is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let offset = offset.unwrap_or(0);
+    let limit = page_limit(limit);
+    let top_k = top_k.unwrap_or(MAX_TOP_K).clamp(1, MAX_TOP_K);
+
+    match crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        "/memory/recall",
+        conn.token.clone(),
+        Some(json!({ "query": query, "top_k": top_k })),
+    )
+    .await
+    {
+        Ok(body) => {
+            let items = normalize_all(&body["memories"]);
+            cache_items(&*db.lock()?, &conn.profile_id, &items).map_err(|e| e.to_string())?;
+            Ok(json!({
+                "profile_id": conn.profile_id,
+                "source": "core",
+                "query": query,
+                "offset": offset,
+                "limit": limit,
+                "total": items.len(),
+                "memories": items.into_iter().skip(offset).take(limit).collect::<Vec<_>>(),
+            }))
+        }
+        Err(err) => {
+            let guard = db.lock()?;
+            let (items, total) = cached_page(&guard, &conn.profile_id, Some(&query), offset, limit)
+                .map_err(|e| e.to_string())?;
+            Ok(json!({
+                "profile_id": conn.profile_id,
+                "source": "cache",
+                "core_error": err,
+                "query": query,
+                "offset": offset,
+                "limit": limit,
+                "total": total,
+                "memories": items,
+            }))
+        }
+    }
+}
+
+/// Deletes a memory on the core, then drops it from the local cache.
+#[tauri::command]
+pub async fn delete_memory(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    memory_id: String,
+) -> Result<Value, String> {
+    let memory_id = memory_id.trim().to_string();
+    if memory_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Memory id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    // Hash-derived ids mean nothing to the core, so send the content along
+    // for it to match on.
+    let cached: Option<Value> = db
+        .lock()?
+        .query_row(
+            "SELECT payload FROM memory_cache WHERE profile_id = ?1 AND memory_id = ?2",
+            (&conn.profile_id, &memory_id),
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|payload| serde_json::from_str(&payload).ok());
+    let body = cached
+        .filter(|item| item["derived_id"].as_bool() == Some(true))
+        .map(|item| json!({ "content": item["content"] }));
+    let path = format!("/memory/items/{}", memory_id);
+    let result = crate::request_json(
+        Method::DELETE,
+        &conn.base_url,
+        &path,
+        conn.token.clone(),
+        body,
+    )
+    .await?;
+    db.lock()?
+        .execute(
+            "DELETE FROM memory_cache WHERE profile_id = ?1 AND memory_id = ?2",
+            (&conn.profile_id, &memory_id),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "memory_id": memory_id,
+        "deleted": true,
+        "core": result,
+    }))
+}
+
+fn page_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE)
+}
+
+fn normalize_all(raw: &Value) -> Vec<Value> {
+    raw.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(normalize)
+        .collect()
+}
+
+/// Recall hits don't always carry an id; fall back to a content hash so the
+/// same memory maps to one cache row across searches.
+fn normalize(item: &Value) -> Option<Value> {
+    let mut item = item.as_object()?.clone();
+    let content = item.get("content").and_then(Value::as_str)?.to_string();
+    let explicit = item
+        .get("id")
+        .or_else(|| item.get("metadata").and_then(|m| m.get("id")))
+        .and_then(|id| match id {
+            Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        });
+    let derived = explicit.is_none();
+    let id = explicit.unwrap_or_else(|| {
+        Sha256::digest(content.as_bytes())
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    });
+    item.insert("id".to_string(), json!(id));
+    item.insert("derived_id".to_string(), json!(derived));
+    Some(Value::Object(item))
+}
+
+fn cache_items(conn: &Connection, profile_id: &str, items: &[Value]) -> rusqlite::Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    for item in items {
+        conn.execute(
+            "INSERT INTO memory_cache (profile_id, memory_id, content, payload, cached_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(profile_id, memory_id) DO UPDATE SET content = excluded.content,
+                 payload = excluded.payload, cached_at = excluded.cached_at",
+            params![
+                profile_id,
+                item["id"].as_str().unwrap_or_default(),
+                item["content"].as_str().unwrap_or_default(),
+                item.to_string(),
+                now,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn cached_page(
+    conn: &Connection,
+    profile_id: &str,
+    query: Option<&str>,
+    offset: usize,
+    limit: usize,
+) -> rusqlite::Result<(Vec<Value>, usize)> {
+    let pattern = format!("%{}%", query.unwrap_or_default());
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM memory_cache WHERE profile_id = ?1 AND content LIKE ?2",
+        (profile_id, &pattern),
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT payload FROM memory_cache WHERE profile_id = ?1 AND content LIKE ?2
+         ORDER BY cached_at DESC, memory_id LIMIT ?3 OFFSET ?4",
+    )?;
+    let items = stmt
+        .query_map(
+            params![profile_id, pattern, limit as i64, offset as i64],
+            |row| row.get::<_, String>(0),
+        )?
+        .filter_map(|payload| payload.ok())
+        .filter_map(|payload| serde_json::from_str(&payload).ok())
+        .collect();
+    Ok((items, total as usize))
+}
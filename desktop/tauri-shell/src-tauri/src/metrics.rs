@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+
+/// Local samples are kept at one per minute per profile, for this long.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+const RETENTION_DAYS: i64 = 400;
+const DEFAULT_RANGE: &str = "24h";
+const DEFAULT_RESOLUTION: usize = 200;
+const MAX_RESOLUTION: usize = 2_000;
+
+fn last_sampled() -> &'static Mutex<HashMap<String, Instant>> {
+    static LAST: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Stores the numeric values of a core `metrics` snapshot (as carried by
+/// `/dashboard/data`), at most once per `SAMPLE_INTERVAL` per profile, so
+/// cores without a series endpoint still get history.
+pub fn record_snapshot(db: &LocalDb, profile_id: &str, metrics: &Value) {
+    let Some(values) = metrics.as_object() else {
+        return;
+    };
+    if let Ok(mut last) = last_sampled().lock() {
+        if last
+            .get(profile_id)
+            .is_some_and(|at| at.elapsed() < SAMPLE_INTERVAL)
+        {
+            return;
+        }
+        last.insert(profile_id.to_string(), Instant::now());
+    }
+    let Ok(mut conn) = db.lock() else {
+        return;
+    };
+    let now = Utc::now().timestamp_millis();
+    let Ok(tx) = conn.transaction() else {
+        return;
+    };
+    for (name, value) in values {
+        if let Some(value) = value.as_f64() {
+            let _ = tx.execute(
+                "INSERT OR REPLACE INTO metric_samples (profile_id, name, at_ms, value)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![profile_id, name, now, value],
+            );
+        }
+    }
+    let cutoff = now - RETENTION_DAYS * 86_400_000;
+    let _ = tx.execute(
+        "DELETE FROM metric_samples WHERE profile_id = ?1 AND at_ms < ?2",
+        params![profile_id, cutoff],
+    );
+    let _ = tx.commit();
+}
+
+/// `30m`, `24h`, `7d`, `4w`; a bare number is seconds.
+pub fn parse_range(raw: &str) -> Option<Duration> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (count, unit) = raw.split_at(split);
+    let count: u64 = count.parse().ok().filter(|c| *c > 0)?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    Some(Duration::from_secs(count.checked_mul(seconds)?))
+}
+
+fn point_time(raw: &Value) -> Option<i64> {
+    match raw {
+        Value::Number(n) => {
+            let t = n.as_f64()?;
+            // Seconds and milliseconds since the epoch are both in use.
+            Some(if t < 1e11 {
+                (t * 1000.0) as i64
+            } else {
+                t as i64
+            })
+        }
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_millis()),
+        _ => None,
+    }
+}
+
+/// Accepts `[{ "t" | "at" | "timestamp", "v" | "value" }]` or `[[t, v]]`.
+fn parse_points(body: &Value) -> Vec<(i64, f64)> {
+    body["points"]
+        .as_array()
+        .or_else(|| body.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| {
+            if let Some([t, v]) = p.as_array().map(Vec::as_slice) {
+                return Some((point_time(t)?, v.as_f64()?));
+            }
+            let t = ["t", "at", "timestamp"]
+                .iter()
+                .find_map(|k| point_time(&p[*k]))?;
+            let v = ["v", "value"].iter().find_map(|k| p[*k].as_f64())?;
+            Some((t, v))
+        })
+        .collect()
+}
+
+fn local_points(
+    conn: &Connection,
+    profile_id: &str,
+    name: &str,
+    from: i64,
+    to: i64,
+) -> rusqlite::Result<Vec<(i64, f64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT at_ms, value FROM metric_samples
+         WHERE profile_id = ?1 AND name = ?2 AND at_ms >= ?3 AND at_ms <= ?4
+         ORDER BY at_ms",
+    )?;
+    let rows = stmt.query_map(params![profile_id, name, from, to], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    last: f64,
+}
+
+/// Folds points into `resolution` equal windows over `[from, to]`; empty
+/// windows are left out so gaps stay visible as gaps.
+fn downsample(points: &[(i64, f64)], from: i64, to: i64, resolution: usize) -> Vec<Value> {
+    let width = ((to - from) as f64 / resolution as f64).max(1.0);
+    let mut buckets: Vec<Option<Bucket>> = vec![None; resolution];
+    for &(t, v) in points {
+        if t < from || t > to || !v.is_finite() {
+            continue;
+        }
+        let i = (((t - from) as f64 / width) as usize).min(resolution - 1);
+        let bucket = buckets[i].get_or_insert(Bucket {
+            min: v,
+            max: v,
+            sum: 0.0,
+            count: 0,
+            last: v,
+        });
+        bucket.min = bucket.min.min(v);
+        bucket.max = bucket.max.max(v);
+        bucket.sum += v;
+        bucket.count += 1;
+        bucket.last = v;
+    }
+    buckets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| {
+            let b = b.as_ref()?;
+            let start = from + (i as f64 * width) as i64;
+            Some(json!({
+                "t": start,
+                "at": DateTime::<Utc>::from_timestamp_millis(start).map(|t| t.to_rfc3339()),
+                "min": b.min,
+                "max": b.max,
+                "avg": b.sum / b.count as f64,
+                "last": b.last,
+                "count": b.count,
+            }))
+        })
+        .collect()
+}
+
+/// Returns a metric over `range` (default `24h`) downsampled to at most
+/// `resolution` buckets (default 200) of min/max/avg/last. High-resolution
+/// series come from the core's `/metrics/series`; cores without it are
+/// served from the shell's own once-a-minute samples.
+#[tauri::command]
+pub async fn get_metric_series(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    name: String,
+    range: Option<String>,
+    resolution: Option<usize>,
+) -> Result<Value, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Metric name is required"));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '.'))
+    {
+        return Err(ErrorCode::InvalidInput.with(format!("Invalid metric name: {}", name)));
+    }
+    let range_raw = range.unwrap_or_else(|| DEFAULT_RANGE.to_string());
+    let span = parse_range(&range_raw).ok_or_else(|| {
+        ErrorCode::InvalidInput.with("Range must look like `30m`, `24h`, `7d` or `4w`")
+    })?;
+    let resolution = resolution
+        .unwrap_or(DEFAULT_RESOLUTION)
+        .clamp(1, MAX_RESOLUTION);
+    let conn = store.connection(profile.as_deref())?;
+    let to = Utc::now().timestamp_millis();
+    let from = to - i64::try_from(span.as_millis()).unwrap_or(i64::MAX / 2);
+
+    let path = format!(
+        "/metrics/series?name={}&since={}&until={}",
+        name,
+        from / 1000,
+        to / 1000
+    );
+    let (source, points) =
+        match crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None)
+            .await
+        {
+            Ok(body) => ("core", parse_points(&body)),
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+                let points = local_points(&*db.lock()?, &conn.profile_id, &name, from, to)
+                    .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+                ("local", points)
+            }
+            Err(err) => return Err(err),
+        };
+
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "name": name,
+        "range": range_raw.trim(),
+        "source": source,
+        "from": from,
+        "to": to,
+        "resolution": resolution,
+        "raw_points": points.len(),
+        "buckets": downsample(&points, from, to, resolution),
+    }))
+}
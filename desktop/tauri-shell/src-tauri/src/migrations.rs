@@ -0,0 +1,490 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+
+/// Backups taken before migrating that are kept beside the database.
+const KEEP_BACKUPS: usize = 3;
+
+/// One step of the local schema. Versions are recorded in `user_version`
+/// and only ever appended: a released migration is never edited.
+struct Migration {
+    version: u32,
+    name: &'static str,
+    statements: &'static [&'static str],
+}
+
+/// The schema as it stood before migrations were versioned. Every statement
+/// is idempotent, so databases created before then adopt it unchanged.
+const BASELINE: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS usage_daily (
+        profile_id TEXT NOT NULL,
+        day TEXT NOT NULL,
+        week TEXT NOT NULL,
+        model TEXT NOT NULL DEFAULT '',
+        agent TEXT NOT NULL DEFAULT '',
+        plan_id TEXT NOT NULL DEFAULT '',
+        calls INTEGER NOT NULL DEFAULT 0,
+        input_tokens INTEGER NOT NULL DEFAULT 0,
+        output_tokens INTEGER NOT NULL DEFAULT 0,
+        cost_usd REAL NOT NULL DEFAULT 0,
+        PRIMARY KEY (profile_id, day, model, agent, plan_id)
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_usage_daily_week ON usage_daily(profile_id, week)",
+    "CREATE TABLE IF NOT EXISTS usage_model_counters (
+        profile_id TEXT NOT NULL,
+        model TEXT NOT NULL,
+        calls INTEGER NOT NULL DEFAULT 0,
+        cost_usd REAL NOT NULL DEFAULT 0,
+        PRIMARY KEY (profile_id, model)
+    )",
+    "CREATE TABLE IF NOT EXISTS sync_cursors (
+        profile_id TEXT NOT NULL,
+        stream TEXT NOT NULL,
+        cursor TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (profile_id, stream)
+    )",
+    "CREATE TABLE IF NOT EXISTS memory_cache (
+        profile_id TEXT NOT NULL,
+        memory_id TEXT NOT NULL,
+        content TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        cached_at TEXT NOT NULL,
+        PRIMARY KEY (profile_id, memory_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at TEXT NOT NULL,
+        profile_id TEXT NOT NULL,
+        category TEXT NOT NULL,
+        action TEXT NOT NULL,
+        entity_id TEXT NOT NULL DEFAULT '',
+        detail TEXT NOT NULL DEFAULT '{}'
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_audit_log_profile ON audit_log(profile_id, at)",
+    "CREATE TABLE IF NOT EXISTS plan_deadlines (
+        profile_id TEXT NOT NULL,
+        plan_id TEXT NOT NULL,
+        objective TEXT NOT NULL DEFAULT '',
+        expires_at TEXT NOT NULL,
+        expired_at TEXT,
+        PRIMARY KEY (profile_id, plan_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS sync_entities (
+        profile_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        entity_id TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        synced_at TEXT NOT NULL,
+        PRIMARY KEY (profile_id, kind, entity_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS metric_samples (
+        profile_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (profile_id, name, at_ms)
+    )",
+    "CREATE TABLE IF NOT EXISTS log_cache (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        profile_id TEXT NOT NULL,
+        source TEXT NOT NULL,
+        line_hash TEXT NOT NULL,
+        at TEXT,
+        level TEXT,
+        line TEXT NOT NULL,
+        UNIQUE (profile_id, source, line_hash)
+    )",
+    "CREATE TABLE IF NOT EXISTS scheduled_approvals (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        profile_id TEXT NOT NULL,
+        plan_id TEXT NOT NULL,
+        execute INTEGER NOT NULL DEFAULT 0,
+        fire_at TEXT NOT NULL,
+        version TEXT,
+        grace_minutes INTEGER NOT NULL DEFAULT 30,
+        status TEXT NOT NULL DEFAULT 'scheduled',
+        created_at TEXT NOT NULL,
+        fired_at TEXT,
+        outcome TEXT
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_scheduled_approvals_status ON scheduled_approvals(status, fire_at)",
+];
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        statements: BASELINE,
+    },
+    Migration {
+        version: 2,
+        name: "audit_log_entity_index",
+        statements: &[
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(profile_id, entity_id)",
+        ],
+    },
+    Migration {
+        version: 3,
+        name: "scheduled_approvals_base",
+        statements: &["ALTER TABLE scheduled_approvals ADD COLUMN base TEXT"],
+    },
+    Migration {
+        version: 4,
+        name: "latency_samples",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS latency_samples (
+                origin TEXT NOT NULL,
+                endpoint TEXT NOT NULL,
+                at_ms INTEGER NOT NULL,
+                ms INTEGER NOT NULL,
+                status INTEGER NOT NULL,
+                core_version TEXT NOT NULL DEFAULT ''
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_latency_samples_origin ON latency_samples(origin, at_ms)",
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "plan_output",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS plan_output (
+                profile_id TEXT NOT NULL,
+                plan_id TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                step INTEGER NOT NULL,
+                stream TEXT NOT NULL,
+                text TEXT NOT NULL,
+                at TEXT NOT NULL,
+                PRIMARY KEY (profile_id, plan_id, seq)
+            )",
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "sync_entities_window",
+        statements: &[
+            "ALTER TABLE sync_entities ADD COLUMN status TEXT
+                GENERATED ALWAYS AS (lower(json_extract(payload, '$.status'))) VIRTUAL",
+            "ALTER TABLE sync_entities ADD COLUMN created_at TEXT
+                GENERATED ALWAYS AS (json_extract(payload, '$.created_at')) VIRTUAL",
+            "ALTER TABLE sync_entities ADD COLUMN updated_at TEXT
+                GENERATED ALWAYS AS (COALESCE(json_extract(payload, '$.updated_at'),
+                    json_extract(payload, '$.created_at'))) VIRTUAL",
+            "CREATE INDEX IF NOT EXISTS idx_sync_entities_created
+                ON sync_entities(profile_id, kind, created_at, entity_id)",
+            "CREATE INDEX IF NOT EXISTS idx_sync_entities_updated
+                ON sync_entities(profile_id, kind, updated_at, entity_id)",
+            "CREATE INDEX IF NOT EXISTS idx_sync_entities_status
+                ON sync_entities(profile_id, kind, status, created_at, entity_id)",
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "recent_items",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS recent_items (
+                profile_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                label TEXT NOT NULL DEFAULT '',
+                visits INTEGER NOT NULL DEFAULT 0,
+                first_ms INTEGER NOT NULL,
+                last_ms INTEGER NOT NULL,
+                rank REAL NOT NULL,
+                PRIMARY KEY (profile_id, kind, item_id)
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_recent_items_rank ON recent_items(profile_id, rank)",
+        ],
+    },
+    Migration {
+        version: 8,
+        name: "airgap",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS airgap_bundles (
+                bundle_id TEXT PRIMARY KEY,
+                direction TEXT NOT NULL,
+                profile_id TEXT NOT NULL DEFAULT '',
+                origin TEXT NOT NULL DEFAULT '',
+                signer TEXT NOT NULL,
+                manifest_sha256 TEXT NOT NULL,
+                plans TEXT NOT NULL,
+                at TEXT NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS airgap_decisions (
+                bundle_id TEXT NOT NULL,
+                plan_id TEXT NOT NULL,
+                decision TEXT NOT NULL,
+                reason TEXT NOT NULL DEFAULT '',
+                decided_at TEXT NOT NULL,
+                PRIMARY KEY (bundle_id, plan_id)
+            )",
+        ],
+    },
+    Migration {
+        version: 9,
+        name: "plan_revisions",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS plan_revisions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT NOT NULL,
+                plan_id TEXT NOT NULL,
+                feedback TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'waiting',
+                revision_plan_id TEXT,
+                requested_at TEXT NOT NULL,
+                linked_at TEXT
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_plan_revisions_plan ON plan_revisions(profile_id, plan_id)",
+            "CREATE INDEX IF NOT EXISTS idx_plan_revisions_revision
+                ON plan_revisions(profile_id, revision_plan_id)",
+        ],
+    },
+    Migration {
+        version: 10,
+        name: "tickets",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS ticket_links (
+                profile_id TEXT NOT NULL,
+                plan_id TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                ticket_id TEXT NOT NULL,
+                ticket_key TEXT NOT NULL,
+                ticket_url TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (profile_id, plan_id, provider)
+            )",
+            "CREATE TABLE IF NOT EXISTS ticket_outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT NOT NULL,
+                plan_id TEXT NOT NULL,
+                decision TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TEXT NOT NULL,
+                last_error TEXT,
+                created_at TEXT NOT NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_ticket_outbox_due ON ticket_outbox(status, next_attempt_at)",
+        ],
+    },
+    Migration {
+        version: 11,
+        name: "bandwidth",
+        statements: &["CREATE TABLE IF NOT EXISTS bandwidth_daily (
+                day TEXT NOT NULL,
+                profile_id TEXT NOT NULL,
+                subsystem TEXT NOT NULL,
+                sent_bytes INTEGER NOT NULL DEFAULT 0,
+                received_bytes INTEGER NOT NULL DEFAULT 0,
+                requests INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, profile_id, subsystem)
+            )"],
+    },
+    Migration {
+        version: 12,
+        name: "sync_history",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS sync_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                payload TEXT,
+                recorded_at TEXT NOT NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_sync_history_entity
+                ON sync_history(profile_id, kind, entity_id, id)",
+            "CREATE INDEX IF NOT EXISTS idx_sync_history_at ON sync_history(profile_id, recorded_at)",
+            "INSERT INTO sync_history (profile_id, kind, entity_id, payload, recorded_at)
+                SELECT profile_id, kind, entity_id, payload, synced_at FROM sync_entities",
+        ],
+    },
+    Migration {
+        version: 13,
+        name: "translations",
+        statements: &["CREATE TABLE IF NOT EXISTS translations (
+                key TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                source_language TEXT,
+                target_language TEXT NOT NULL,
+                text TEXT NOT NULL,
+                at TEXT NOT NULL
+            )"],
+    },
+    Migration {
+        version: 14,
+        name: "artifacts",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS artifacts (
+                profile_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                destination TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                chunks INTEGER NOT NULL,
+                fetched_bytes INTEGER NOT NULL,
+                reused_bytes INTEGER NOT NULL,
+                synced_at TEXT NOT NULL,
+                PRIMARY KEY (profile_id, path)
+            )",
+            "CREATE TABLE IF NOT EXISTS artifact_chunks (
+                sha256 TEXT NOT NULL,
+                length INTEGER NOT NULL,
+                destination TEXT NOT NULL,
+                offset INTEGER NOT NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS idx_artifact_chunks_sha ON artifact_chunks(sha256)",
+            "CREATE INDEX IF NOT EXISTS idx_artifact_chunks_destination
+                ON artifact_chunks(destination)",
+        ],
+    },
+    Migration {
+        version: 15,
+        name: "offline_decision_order",
+        statements: &[
+            "ALTER TABLE airgap_decisions ADD COLUMN after_plans TEXT NOT NULL DEFAULT '[]'",
+        ],
+    },
+    Migration {
+        version: 16,
+        name: "announcements",
+        statements: &["CREATE TABLE IF NOT EXISTS announcements (
+                id TEXT PRIMARY KEY,
+                source TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                severity TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                published_at TEXT NOT NULL DEFAULT '',
+                fetched_at TEXT NOT NULL,
+                read_at TEXT
+            )"],
+    },
+];
+
+pub fn latest() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+pub fn version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Brings the database at `path` (or an in-memory one, with `None`) up to
+/// the latest schema. A database holding data is copied aside first, and
+/// every pending migration runs in one transaction, so a failure leaves
+/// the database exactly as it was. A database from a newer shell is
+/// refused rather than touched.
+pub fn run(conn: &mut Connection, path: Option<&Path>) -> Result<Value, String> {
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .and_then(|_| conn.pragma_update(None, "synchronous", "NORMAL"))
+        .map_err(|e| format!("Configure local database failed: {}", e))?;
+    let current = version(conn).map_err(|e| format!("Read schema version failed: {}", e))?;
+    if current > latest() {
+        return Err(format!(
+            "Local database was written by a newer shell (schema {} > {})",
+            current,
+            latest()
+        ));
+    }
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    let mut status = json!({
+        "version": current,
+        "latest": latest(),
+        "migrated_from": Value::Null,
+        "applied": [],
+        "backup": Value::Null,
+    });
+    if pending.is_empty() {
+        return Ok(status);
+    }
+
+    let backup = match path {
+        Some(path) if has_tables(conn) => Some(backup(conn, path, current)?),
+        _ => None,
+    };
+    let failed = |m: &Migration, e: rusqlite::Error| {
+        format!(
+            "Migration {} ({}) failed and was rolled back: {}{}",
+            m.version,
+            m.name,
+            e,
+            backup
+                .as_ref()
+                .map(|b| format!("; a backup is at {}", b))
+                .unwrap_or_default()
+        )
+    };
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Start migration failed: {}", e))?;
+    for migration in &pending {
+        for statement in migration.statements {
+            tx.execute_batch(statement)
+                .map_err(|e| failed(migration, e))?;
+        }
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| failed(migration, e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Commit migrations failed: {}", e))?;
+
+    status["version"] = json!(latest());
+    status["migrated_from"] = json!(current);
+    status["applied"] = json!(pending
+        .iter()
+        .map(|m| json!({ "version": m.version, "name": m.name }))
+        .collect::<Vec<_>>());
+    status["backup"] = json!(backup);
+    Ok(status)
+}
+
+fn has_tables(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )
+    .is_ok_and(|n| n > 0)
+}
+
+/// Copies the database to `<file>.bak-v<version>-<time>` (encrypted like
+/// the original) and prunes all but the newest `KEEP_BACKUPS`.
+fn backup(conn: &Connection, path: &Path, version: u32) -> Result<String, String> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let prefix = format!("{}.bak-v", file_name);
+    let target = path.with_file_name(format!(
+        "{}{}-{}",
+        prefix,
+        version,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    let quoted = target.display().to_string().replace('\'', "''");
+    conn.execute_batch(&format!("VACUUM INTO '{}';", quoted))
+        .map_err(|e| format!("Back up local database before migrating failed: {}", e))?;
+
+    if let Some(dir) = path.parent() {
+        let mut backups: Vec<(SystemTime, PathBuf)> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect();
+        backups.sort();
+        let excess = backups.len().saturating_sub(KEEP_BACKUPS);
+        for (_, old) in &backups[..excess] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(target.display().to_string())
+}
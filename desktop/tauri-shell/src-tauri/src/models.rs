@@ -0,0 +1,185 @@
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+
+const PROVIDERS: &[&str] = &["openai-compatible", "litellm"];
+const MAX_TEMPERATURE: f64 = 2.0;
+
+/// A partial update to the core's model routing. `name` selects one
+/// configured endpoint; without it the routing-wide defaults are changed.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<RateLimits>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl ModelConfigUpdate {
+    fn validate(&self, known_models: &[String]) -> Result<(), String> {
+        if let Some(name) = &self.name {
+            if !known_models.is_empty() && !known_models.iter().any(|m| m == name) {
+                return Err(format!("Unknown model endpoint: {}", name));
+            }
+        }
+        if let Some(provider) = &self.provider {
+            if !PROVIDERS.contains(&provider.as_str()) {
+                return Err(format!(
+                    "Unsupported provider: {} (expected one of {})",
+                    provider,
+                    PROVIDERS.join(", ")
+                ));
+            }
+        }
+        if let Some(model) = &self.model {
+            if model.trim().is_empty() {
+                return Err("Model name must not be empty".to_string());
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            if !temperature.is_finite() || !(0.0..=MAX_TEMPERATURE).contains(&temperature) {
+                return Err(format!(
+                    "Temperature must be between 0 and {}",
+                    MAX_TEMPERATURE
+                ));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be positive".to_string());
+        }
+        if let Some(limits) = &self.rate_limits {
+            if limits.requests_per_minute == Some(0) || limits.tokens_per_minute == Some(0) {
+                return Err("Rate limits must be positive; omit them to disable".to_string());
+            }
+        }
+        if self.provider.is_none()
+            && self.model.is_none()
+            && self.temperature.is_none()
+            && self.max_tokens.is_none()
+            && self.rate_limits.is_none()
+        {
+            return Err("Model config update is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the configured model endpoints plus the routing settings, when
+/// the core exposes them on `/models/config`.
+#[tauri::command]
+pub async fn get_model_config(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let models = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/models",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let config = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/models/config",
+        conn.token.clone(),
+        None,
+    )
+    .await
+    .ok();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "models": models,
+        "config": config,
+    }))
+}
+
+/// Validates and submits a model routing change, then records it in the
+/// local audit log with the previous configuration for reference.
+#[tauri::command]
+pub async fn update_model_config(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    update: Value,
+) -> Result<Value, String> {
+    let update: ModelConfigUpdate = serde_json::from_value(update)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid model config update: {}", e)))?;
+    let conn = store.connection(profile.as_deref())?;
+    let models = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/models",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let known: Vec<String> = models
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m["name"].as_str().map(str::to_string))
+        .collect();
+    update
+        .validate(&known)
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+
+    let previous = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/models/config",
+        conn.token.clone(),
+        None,
+    )
+    .await
+    .ok();
+    let payload = serde_json::to_value(&update).map_err(|e| e.to_string())?;
+    let result = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        "/models/config",
+        conn.token.clone(),
+        Some(payload.clone()),
+    )
+    .await?;
+
+    db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "models",
+        "config_update",
+        update.name.as_deref().unwrap_or("routing"),
+        &json!({ "update": payload, "previous": previous }),
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "applied": payload,
+        "core": result,
+    }))
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use rusqlite::params;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::decision_metrics;
+use crate::errors::ErrorCode;
+use crate::handover;
+use crate::settings::{Settings, SettingsStore};
+use crate::{net, tasks, two_person};
+
+const TASK: &str = "my_decisions";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// The audit category decisions sent from this shell are kept under.
+const CATEGORY: &str = "decision";
+const DEFAULT_RANGE: &str = "30d";
+const MAX_RANGE_DAYS: i64 = 366;
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+/// Audit rows read per search, newest first.
+const SCAN_LIMIT: i64 = 5_000;
+/// Sources meaning the shell decided by policy rather than the operator.
+const AUTOMATIC: &[&str] = &["auto_approval"];
+
+struct Sent {
+    base_url: String,
+    plan_id: String,
+    decision: String,
+    operator: String,
+    payload: Value,
+    at: String,
+}
+
+/// Decisions sent since the last flush, which moves them to the audit log.
+static SENT: OnceLock<Mutex<Vec<Sent>>> = OnceLock::new();
+
+fn sent() -> &'static Mutex<Vec<Sent>> {
+    SENT.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Notes a decision the core accepted from `operator`. Called by
+/// `decisions` for every path that sends one.
+pub fn decided(
+    base_url: &str,
+    operator: String,
+    plan_id: &str,
+    decision: &str,
+    payload: Option<&Value>,
+) {
+    if let Ok(mut queue) = sent().lock() {
+        queue.push(Sent {
+            base_url: base_url.to_string(),
+            plan_id: plan_id.to_string(),
+            decision: decision.to_string(),
+            operator,
+            payload: payload.cloned().unwrap_or(Value::Null),
+            at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        });
+    }
+}
+
+fn profile_for(settings: &Settings, base_url: &str) -> Option<String> {
+    let origin = net::base_origin(base_url)?;
+    settings
+        .profiles
+        .iter()
+        .find(|p| {
+            p.endpoints()
+                .any(|e| net::base_origin(e).as_ref() == Some(&origin))
+        })
+        .map(|p| p.id.clone())
+}
+
+fn flush(app: &AppHandle) -> Result<(), String> {
+    let queued: Vec<Sent> = sent()
+        .lock()
+        .map(|mut q| q.drain(..).collect())
+        .unwrap_or_default();
+    if queued.is_empty() {
+        return Ok(());
+    }
+    let settings = app.state::<SettingsStore>().snapshot();
+    let db = app.state::<LocalDb>();
+    let guard = db.lock()?;
+    for entry in queued {
+        let Some(profile_id) = profile_for(&settings, &entry.base_url) else {
+            continue;
+        };
+        let source = ["approval_source", "decision_source"]
+            .iter()
+            .find_map(|k| entry.payload[*k].as_str())
+            .unwrap_or("operator");
+        let detail = json!({
+            "operator": entry.operator,
+            "decided_at": entry.at,
+            "execute": entry.payload["execute"],
+            "reason": entry.payload["reason"],
+            "source": source,
+        });
+        db::record_audit(
+            &guard,
+            &profile_id,
+            CATEGORY,
+            &entry.decision,
+            &entry.plan_id,
+            &detail,
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Moves sent decisions into the local audit log every few seconds.
+pub fn spawn_recorder(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            if let Err(err) = flush(&app) {
+                tasks::failed(TASK, &err);
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+        }
+    });
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DecisionFilter {
+    /// Words that must all appear in the plan's objective, steps, agent,
+    /// risk, id or the decision's reason, in any case.
+    pub query: Option<String>,
+    /// `approve` or `reject`.
+    pub decision: Option<String>,
+    /// `30d` by default, or `start/end` in RFC 3339.
+    pub range: Option<String>,
+    /// Also list approvals the shell made by policy.
+    pub include_automatic: bool,
+    pub limit: Option<usize>,
+}
+
+/// Builds the text a search runs over: everything an operator would
+/// remember a plan by.
+fn haystack(plan: &Value, reason: &Value) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut push = |v: &Value| {
+        if let Some(s) = v.as_str().filter(|s| !s.is_empty()) {
+            parts.push(s.to_lowercase());
+        }
+    };
+    for key in [
+        "id",
+        "objective",
+        "title",
+        "summary",
+        "agent",
+        "agent_id",
+        "risk",
+        "risk_level",
+    ] {
+        push(&plan[key]);
+    }
+    for step in plan["steps"].as_array().into_iter().flatten() {
+        push(step);
+        for key in ["action", "description", "command", "target"] {
+            push(&step[key]);
+        }
+    }
+    push(reason);
+    parts.join("\n")
+}
+
+fn decision_of(status: &str) -> &'static str {
+    if status == "rejected" {
+        "reject"
+    } else {
+        "approve"
+    }
+}
+
+/// The current operator's decisions for `profile`: those sent from this
+/// shell (from the local audit log) merged with plans the core records as
+/// decided by them, newest first. `filter.query` is matched word by word
+/// against each plan's objective, steps, agent, risk and id and the
+/// decision's reason, to answer "did I approve something like this?".
+#[tauri::command]
+pub async fn list_my_decisions(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    filter: Option<DecisionFilter>,
+) -> Result<Value, String> {
+    let filter = filter.unwrap_or_default();
+    let wanted = match filter.decision.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(d @ ("approve" | "reject")) => Some(d.to_string()),
+        Some(other) => {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Unknown decision: {} (use approve or reject)",
+                other
+            )))
+        }
+    };
+    let (since, until) = handover::parse_range(
+        filter.range.as_deref().unwrap_or(DEFAULT_RANGE),
+        MAX_RANGE_DAYS,
+    )?;
+    let terms: Vec<String> = filter
+        .query
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let conn = store.connection(profile.as_deref())?;
+    let me = two_person::first_identity(conn.token.as_deref());
+    flush(&app)?;
+
+    // The core's view of the plans, falling back to the local mirror.
+    let (plans, core_error) = match crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/plans?limit=200",
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(listed) => (listed.as_array().cloned().unwrap_or_default(), None),
+        Err(err) => {
+            let cached = handover::cached(&*db.lock()?, &conn.profile_id, "plan")
+                .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            (cached, Some(err))
+        }
+    };
+    let plans: HashMap<String, Value> = plans
+        .into_iter()
+        .map(|p| (handover::id_of(&p), p))
+        .collect();
+
+    let local: Vec<(String, String, String, Value)> = {
+        let guard = db.lock()?;
+        let mut stmt = guard
+            .prepare(
+                "SELECT at, action, entity_id, detail FROM audit_log
+                 WHERE profile_id = ?1 AND category = ?2 AND at >= ?3 AND at <= ?4
+                 ORDER BY at DESC LIMIT ?5",
+            )
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        let rows = stmt
+            .query_map(
+                params![
+                    conn.profile_id,
+                    CATEGORY,
+                    since.to_rfc3339(),
+                    until.to_rfc3339(),
+                    SCAN_LIMIT
+                ],
+                |row| {
+                    let detail: String = row.get(3)?;
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        serde_json::from_str(&detail).unwrap_or(Value::Null),
+                    ))
+                },
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        rows
+    };
+
+    let mut found: Vec<(DateTime<Utc>, Value)> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let matches = |plan: &Value, reason: &Value| {
+        let text = haystack(plan, reason);
+        terms.iter().all(|t| text.contains(t.as_str()))
+    };
+    for (at, decision, plan_id, detail) in &local {
+        let operator = detail["operator"].as_str().unwrap_or_default();
+        let automatic = detail["source"]
+            .as_str()
+            .is_some_and(|s| AUTOMATIC.contains(&s));
+        if !operator.eq_ignore_ascii_case(&me) || (automatic && !filter.include_automatic) {
+            continue;
+        }
+        if wanted.as_ref().is_some_and(|w| w != decision) {
+            continue;
+        }
+        let plan = plans
+            .get(plan_id)
+            .cloned()
+            .unwrap_or_else(|| json!({ "id": plan_id }));
+        if !matches(&plan, &detail["reason"]) {
+            continue;
+        }
+        // One entry per plan: the newest local decision on it.
+        if !seen.insert(plan_id.clone()) {
+            continue;
+        }
+        let at_time = handover::timestamp(&json!(at)).unwrap_or(since);
+        found.push((
+            at_time,
+            json!({
+                "plan_id": plan_id,
+                "decision": decision,
+                "decided_at": at,
+                "objective": handover::objective(&plan),
+                "risk": plan["risk"].as_str().or(plan["risk_level"].as_str()),
+                "agent": plan["agent"].as_str().or(plan["agent_id"].as_str()),
+                "status": plan["status"],
+                "execute": detail["execute"],
+                "reason": detail["reason"],
+                "source": "local",
+                "automatic": automatic,
+            }),
+        ));
+    }
+    for (plan_id, plan) in &plans {
+        if seen.contains(plan_id) {
+            continue;
+        }
+        let by = decision_metrics::decided_by(plan);
+        if !by.as_deref().is_some_and(|b| b.eq_ignore_ascii_case(&me)) {
+            continue;
+        }
+        let status = handover::status_of(plan);
+        let Some(at) = handover::decided_at(plan, &status, since, until) else {
+            continue;
+        };
+        let decision = decision_of(&status);
+        let automatic = decision_metrics::automatic(plan, by.as_deref());
+        if wanted.as_deref().is_some_and(|w| w != decision)
+            || (automatic && !filter.include_automatic)
+        {
+            continue;
+        }
+        let reason = &plan["rejection_reason"];
+        if !matches(plan, reason) {
+            continue;
+        }
+        found.push((
+            at,
+            json!({
+                "plan_id": plan_id,
+                "decision": decision,
+                "decided_at": at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "objective": handover::objective(plan),
+                "risk": plan["risk"].as_str().or(plan["risk_level"].as_str()),
+                "agent": plan["agent"].as_str().or(plan["agent_id"].as_str()),
+                "status": plan["status"],
+                "execute": Value::Null,
+                "reason": reason,
+                "source": "core",
+                "automatic": automatic,
+            }),
+        ));
+    }
+    found.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    let total = found.len();
+    let decisions: Vec<Value> = found.into_iter().take(limit).map(|(_, d)| d).collect();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "operator": me,
+        "range": {
+            "since": since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "until": until.to_rfc3339_opts(SecondsFormat::Secs, true),
+        },
+        "total": total,
+        "decisions": decisions,
+        "core_error": core_error,
+    }))
+}
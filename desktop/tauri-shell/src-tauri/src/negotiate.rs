@@ -0,0 +1,28 @@
+use crate::errors::ErrorCode;
+
+/// Whether this build can obtain Kerberos tickets (the `negotiate` feature
+/// links GSSAPI on Unix and SSPI on Windows).
+pub const SUPPORTED: bool = cfg!(feature = "negotiate");
+
+/// An `Authorization`/`Proxy-Authorization` value carrying a Kerberos ticket
+/// for the `HTTP/<host>` service, taken from the user's login session. Each
+/// call starts a fresh security context so a token is never replayed.
+#[cfg(feature = "negotiate")]
+pub fn authorization(host: &str) -> Result<String, String> {
+    use base64::engine::general_purpose::STANDARD as B64;
+    use base64::Engine;
+    use cross_krb5::{ClientCtx, InitiateFlags};
+
+    let spn = format!("HTTP/{}", host);
+    let (_pending, token) =
+        ClientCtx::new(InitiateFlags::empty(), None, &spn, None).map_err(|e| {
+            ErrorCode::AuthExpired.with(format!("No Kerberos ticket for {}: {}", spn, e))
+        })?;
+    Ok(format!("Negotiate {}", B64.encode(&*token)))
+}
+
+#[cfg(not(feature = "negotiate"))]
+pub fn authorization(_host: &str) -> Result<String, String> {
+    Err(ErrorCode::InvalidInput
+        .with("Negotiate authentication needs a build with the `negotiate` feature"))
+}
@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::cloud_auth;
+use crate::errors::ErrorCode;
+use crate::negotiate;
+use crate::ntlm;
+use crate::secrets;
+use crate::settings::{
+    CoreAuth, CoreAuthSettings, IpFamily, NetworkSettings, Profile, ProxyAuth, ProxySettings,
+    RedirectSettings, SettingsStore, TlsSettings,
+};
+use crate::ssrf;
+use crate::tls;
+use crate::transport;
+use crate::vault;
+
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const DNS_PORT: u16 = 53;
+const DOH_PORT: u16 = 443;
+const DEFAULT_API_KEY_HEADER: &str = "X-API-Key";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_PROBED: usize = 8;
+
+/// Per-origin routing for profiles with custom name resolution, a proxy,
+/// non-bearer core auth, or an SSH tunnel. Requests go through `request_json`
+/// by base URL, so the origin of a profile's base URL is what ties a request
+/// back to its settings.
+static ROUTES: OnceLock<Mutex<Routes>> = OnceLock::new();
+
+#[derive(Default)]
+struct Routes {
+    profiles: HashMap<String, RouteSettings>,
+    tunnels: HashMap<String, TunnelRoute>,
+    clients: HashMap<String, reqwest::Client>,
+    #[cfg(feature = "http3")]
+    quic_clients: HashMap<String, reqwest::Client>,
+}
+
+#[derive(Clone, PartialEq)]
+struct RouteSettings {
+    profile_id: String,
+    network: NetworkSettings,
+    proxy: ProxySettings,
+    core_auth: CoreAuthSettings,
+    redirects: RedirectSettings,
+    tls: TlsSettings,
+}
+
+impl RouteSettings {
+    fn of(profile: &Profile) -> Self {
+        Self {
+            profile_id: profile.id.clone(),
+            network: profile.network.clone(),
+            proxy: profile.proxy.clone(),
+            core_auth: profile.core_auth.clone(),
+            redirects: profile.redirects.clone(),
+            tls: profile.tls.clone(),
+        }
+    }
+
+    /// Profiles on direct connections with bearer auth and the default
+    /// redirect and TLS policies need no route.
+    fn is_plain(&self) -> bool {
+        self.network == NetworkSettings::default()
+            && self.proxy == ProxySettings::default()
+            && self.core_auth == CoreAuthSettings::default()
+            && self.redirects == RedirectSettings::default()
+            && self.tls == TlsSettings::default()
+    }
+
+    /// Whether the core credential travels in a header reqwest does not
+    /// drop on a cross-host redirect.
+    fn custom_credentials(&self) -> bool {
+        match self.core_auth.mode {
+            CoreAuth::ApiKey => true,
+            CoreAuth::GcpIdentity => !cloud_auth::gcp_uses_authorization(&self.core_auth),
+            _ => false,
+        }
+    }
+
+    /// Negotiate proxy tokens are bound to the client that sends them, so
+    /// those clients are rebuilt per request rather than cached.
+    fn cacheable(&self) -> bool {
+        self.proxy.url.trim().is_empty() || self.proxy.auth != ProxyAuth::Negotiate
+    }
+
+    fn ntlm_proxy(&self) -> bool {
+        !self.proxy.url.trim().is_empty() && self.proxy.auth == ProxyAuth::Ntlm
+    }
+}
+
+/// Where a tunnelled profile's traffic goes. While the tunnel is down its
+/// requests fail instead of silently bypassing the jump host.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TunnelRoute {
+    Down,
+    Up(u16),
+}
+
+/// Replaces the routing settings from the current profiles. Cached clients
+/// are dropped only for origins whose settings changed.
+pub fn configure(profiles: &[Profile]) {
+    let mut next = HashMap::new();
+    for profile in profiles {
+        let settings = RouteSettings::of(profile);
+        if settings.is_plain() {
+            continue;
+        }
+        let read = profile.read_routing.read_url.as_deref();
+        for key in profile.endpoints().chain(read).filter_map(base_origin) {
+            next.insert(key, settings.clone());
+        }
+    }
+    ntlm::retain(|key| next.get(key).is_some_and(RouteSettings::ntlm_proxy));
+    let mut guard = routes().lock().unwrap_or_else(|e| e.into_inner());
+    let routes = &mut *guard;
+    let profiles = &routes.profiles;
+    routes
+        .clients
+        .retain(|key, _| profiles.get(key) == next.get(key));
+    #[cfg(feature = "http3")]
+    routes
+        .quic_clients
+        .retain(|key, _| profiles.get(key) == next.get(key));
+    routes.profiles = next;
+}
+
+/// Routes `base_url` through a tunnel's local SOCKS port, marks it as
+/// waiting for one, or (`None`) back to direct connections.
+pub fn set_tunnel(base_url: &str, route: Option<TunnelRoute>) {
+    let Some(key) = base_origin(base_url) else {
+        return;
+    };
+    let mut routes = routes().lock().unwrap_or_else(|e| e.into_inner());
+    routes.clients.remove(&key);
+    #[cfg(feature = "http3")]
+    routes.quic_clients.remove(&key);
+    match route {
+        Some(route) => routes.tunnels.insert(key, route),
+        None => routes.tunnels.remove(&key),
+    };
+}
+
+/// The HTTP client to use for `url`: the owning profile's tunnelled, proxied
+/// or resolver-backed client when one is configured, a plain client otherwise.
+pub fn client_for(url: &Url) -> Result<reqwest::Client, String> {
+    let Some(key) = origin(url) else {
+        return build_client(None, None);
+    };
+    let mut routes = routes().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = routes.clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let (settings, socks_port) = route(&routes, &key)?;
+    let client = build_client(settings.as_ref(), socks_port)?;
+    if socks_port.is_some() || settings.as_ref().is_some_and(RouteSettings::cacheable) {
+        routes.clients.insert(key, client.clone());
+    }
+    Ok(client)
+}
+
+/// The HTTP/3 client for `url` when its profile turned on `network.http3`
+/// and nothing on its route needs TCP: an `https` core reached directly,
+/// without a tunnel or proxy, under the default TLS policy. Its own name
+/// resolution and redirect policy still apply.
+#[cfg(feature = "http3")]
+pub fn quic_client_for(url: &Url) -> Result<Option<reqwest::Client>, String> {
+    let Some(key) = origin(url).filter(|_| url.scheme() == "https") else {
+        return Ok(None);
+    };
+    let mut routes = routes().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = routes.quic_clients.get(&key) {
+        return Ok(Some(client.clone()));
+    }
+    let settings = routes.profiles.get(&key).filter(|s| {
+        s.network.http3 && s.proxy.url.trim().is_empty() && s.tls == TlsSettings::default()
+    });
+    let Some(settings) = settings
+        .cloned()
+        .filter(|_| !routes.tunnels.contains_key(&key))
+    else {
+        return Ok(None);
+    };
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .http3_prior_knowledge()
+        .redirect(ssrf::redirect_policy(
+            &settings.redirects,
+            settings.custom_credentials(),
+        ))
+        .dns_resolver(Arc::new(ProfileResolver::new(&settings.network)?))
+        .build()
+        .map_err(|e| ErrorCode::Internal.with(format!("HTTP/3 client init failed: {}", e)))?;
+    routes.quic_clients.insert(key, client.clone());
+    Ok(Some(client))
+}
+
+#[cfg(not(feature = "http3"))]
+pub fn quic_client_for(_url: &Url) -> Result<Option<reqwest::Client>, String> {
+    Ok(None)
+}
+
+/// A client for one long-lived stream to `url`, routed like `client_for`
+/// but never cached, sending TCP keepalive probes every `keepalive` and
+/// without the request timeout; each stream bounds itself.
+pub fn stream_client_for(url: &Url, keepalive: Duration) -> Result<reqwest::Client, String> {
+    let (settings, socks_port) = match origin(url) {
+        Some(key) => route(&routes().lock().unwrap_or_else(|e| e.into_inner()), &key)?,
+        None => (None, None),
+    };
+    let builder = reqwest::Client::builder()
+        .tcp_keepalive(keepalive)
+        .pool_max_idle_per_host(0);
+    configure_client(builder, settings.as_ref(), socks_port, None)
+}
+
+/// A client routed like `client_for`, but never cached and following
+/// redirects as `redirects` decides rather than by the profile's policy.
+pub fn client_with_redirects(url: &Url, redirects: Policy) -> Result<reqwest::Client, String> {
+    let (settings, socks_port) = match origin(url) {
+        Some(key) => route(&routes().lock().unwrap_or_else(|e| e.into_inner()), &key)?,
+        None => (None, None),
+    };
+    let builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+    configure_client(builder, settings.as_ref(), socks_port, Some(redirects))
+}
+
+/// The origin's settings and the local SOCKS port its traffic goes through:
+/// an SSH tunnel's, or the NTLM relay in front of the profile's proxy.
+fn route(routes: &Routes, key: &str) -> Result<(Option<RouteSettings>, Option<u16>), String> {
+    let settings = routes.profiles.get(key).cloned();
+    match routes.tunnels.get(key).copied() {
+        Some(TunnelRoute::Down) => {
+            Err(ErrorCode::CoreUnreachable.with("SSH tunnel for this core is not connected"))
+        }
+        Some(TunnelRoute::Up(port)) => Ok((settings, Some(port))),
+        None => match settings.as_ref().filter(|s| s.ntlm_proxy()) {
+            Some(s) => {
+                let port = ntlm::relay_port(key, &s.profile_id, &s.proxy)?;
+                Ok((settings, Some(port)))
+            }
+            None => Ok((settings, None)),
+        },
+    }
+}
+
+/// Applies the owning profile's core credential to `req`: the caller's bearer
+/// token by default, otherwise the profile's API key, Basic credentials or a
+/// Kerberos ticket. Non-bearer secrets are read from the keyring, so the
+/// caller's token is ignored for those modes.
+pub fn authorize(
+    req: reqwest::RequestBuilder,
+    url: &Url,
+    token: Option<String>,
+) -> Result<reqwest::RequestBuilder, String> {
+    let route = origin(url).and_then(|key| {
+        let routes = routes().lock().unwrap_or_else(|e| e.into_inner());
+        routes
+            .profiles
+            .get(&key)
+            .map(|s| (s.profile_id.clone(), s.core_auth.clone()))
+    });
+    let (profile_id, auth) = route.unwrap_or_default();
+    match auth.mode {
+        CoreAuth::Bearer => Ok(match token.as_deref().map(str::trim) {
+            Some(tok) if !tok.is_empty() => req.bearer_auth(tok),
+            _ => req,
+        }),
+        CoreAuth::ApiKey => {
+            let header = auth.header.as_deref().unwrap_or(DEFAULT_API_KEY_HEADER);
+            Ok(req.header(header.trim(), stored_secret(&profile_id, auth.mode)?))
+        }
+        CoreAuth::Basic => {
+            let password = stored_secret(&profile_id, auth.mode)?;
+            Ok(req.basic_auth(auth.username.trim(), Some(password)))
+        }
+        CoreAuth::Negotiate => {
+            let host = url.host_str().unwrap_or_default();
+            Ok(req.header(AUTHORIZATION, negotiate::authorization(host)?))
+        }
+        // Signed once the body is in place, by `sign`.
+        CoreAuth::AwsSigv4 => Ok(req),
+        CoreAuth::GcpIdentity => Ok(match token.as_deref().map(str::trim) {
+            Some(tok) if !tok.is_empty() && !cloud_auth::gcp_uses_authorization(&auth) => {
+                req.bearer_auth(tok)
+            }
+            _ => req,
+        }),
+    }
+}
+
+/// Adds the cloud-identity credential to a built request: a SigV4
+/// signature over the final method, URL, headers and body, or a GCP
+/// identity token. Other modes were applied by `authorize`.
+pub async fn sign(request: &mut reqwest::Request) -> Result<(), String> {
+    let auth = origin(request.url())
+        .and_then(|key| {
+            let routes = routes().lock().unwrap_or_else(|e| e.into_inner());
+            routes.profiles.get(&key).map(|s| s.core_auth.clone())
+        })
+        .unwrap_or_default();
+    match auth.mode {
+        CoreAuth::AwsSigv4 => cloud_auth::sign_aws(request, &auth).await,
+        CoreAuth::GcpIdentity => cloud_auth::attach_gcp(request, &auth).await,
+        _ => Ok(()),
+    }
+}
+
+fn stored_secret(profile_id: &str, mode: CoreAuth) -> Result<String, String> {
+    let missing = || {
+        ErrorCode::AuthExpired.with(format!(
+            "No {} credential stored for profile {}",
+            match mode {
+                CoreAuth::ApiKey => "API key",
+                _ => "password",
+            },
+            profile_id
+        ))
+    };
+    if vault::enabled(profile_id) {
+        return vault::credential(profile_id).ok_or_else(missing);
+    }
+    let key = mode.secret_key(profile_id).ok_or_else(missing)?;
+    secrets::get(&key)?.ok_or_else(missing)
+}
+
+/// Checks that overrides and nameservers parse, without building a client.
+pub fn validate(network: &NetworkSettings) -> Result<(), String> {
+    parse_overrides(network)?;
+    let servers = parse_servers(network)?;
+    if network.doh_server_name.is_some() && servers.is_empty() {
+        return Err("DNS-over-HTTPS needs at least one DNS server address".to_string());
+    }
+    Ok(())
+}
+
+/// Checks the proxy URL and core credential settings, and that Negotiate is
+/// available in this build wherever the profile asks for it.
+pub fn validate_auth(proxy: &ProxySettings, core_auth: &CoreAuthSettings) -> Result<(), String> {
+    let proxy_url = proxy.url.trim();
+    if !proxy_url.is_empty() {
+        let parsed = Url::parse(proxy_url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+            return Err("Proxy URL must be http(s)://host:port".to_string());
+        }
+        if proxy.auth == ProxyAuth::Ntlm {
+            if parsed.scheme() != "http" {
+                return Err("NTLM proxies must be http://host:port".to_string());
+            }
+            if proxy.username.trim().is_empty() {
+                return Err("NTLM proxy auth needs a user name".to_string());
+            }
+        }
+    }
+    match core_auth.mode {
+        CoreAuth::ApiKey => {
+            let header = core_auth
+                .header
+                .as_deref()
+                .unwrap_or(DEFAULT_API_KEY_HEADER);
+            HeaderName::from_bytes(header.trim().as_bytes())
+                .map_err(|_| format!("Invalid API key header name: {}", header))?;
+        }
+        CoreAuth::Basic => {
+            let user = core_auth.username.trim();
+            if user.is_empty() || user.contains(':') {
+                return Err("Basic auth needs a user name without ':'".to_string());
+            }
+        }
+        CoreAuth::AwsSigv4 => {
+            if core_auth.region.trim().is_empty() {
+                return Err("AWS SigV4 auth needs the core's region".to_string());
+            }
+        }
+        CoreAuth::GcpIdentity => {
+            if let Some(header) = core_auth.header.as_deref().filter(|h| !h.trim().is_empty()) {
+                HeaderName::from_bytes(header.trim().as_bytes())
+                    .map_err(|_| format!("Invalid identity token header name: {}", header))?;
+            }
+        }
+        CoreAuth::Bearer | CoreAuth::Negotiate => {}
+    }
+    let wants_negotiate = (!proxy_url.is_empty() && proxy.auth == ProxyAuth::Negotiate)
+        || core_auth.mode == CoreAuth::Negotiate;
+    if wants_negotiate && !negotiate::SUPPORTED {
+        return Err(
+            "Negotiate authentication needs a build with the `negotiate` feature".to_string(),
+        );
+    }
+    Ok(())
+}
+
+fn routes() -> &'static Mutex<Routes> {
+    ROUTES.get_or_init(|| Mutex::new(Routes::default()))
+}
+
+type Peers = HashMap<String, (SocketAddr, chrono::DateTime<chrono::Utc>)>;
+
+/// The address each core origin was last reached at.
+static PEERS: OnceLock<Mutex<Peers>> = OnceLock::new();
+
+fn peers() -> &'static Mutex<Peers> {
+    PEERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Notes the address a response from `base_url` came from; through a
+/// tunnel or proxy that is the tunnel's or proxy's address.
+pub fn observe_peer(base_url: &str, addr: Option<SocketAddr>) {
+    let (Some(origin), Some(addr)) = (base_origin(base_url), addr) else {
+        return;
+    };
+    if let Ok(mut peers) = peers().lock() {
+        peers.insert(origin, (addr, chrono::Utc::now()));
+    }
+}
+
+pub fn base_origin(base_url: &str) -> Option<String> {
+    Url::parse(base_url.trim()).ok().and_then(|u| origin(&u))
+}
+
+fn origin(url: &Url) -> Option<String> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    match url.port_or_known_default() {
+        Some(port) => Some(format!("{}://{}:{}", url.scheme(), host, port)),
+        // Socket and mock cores have no port; the host alone names them.
+        None if matches!(url.scheme(), transport::UNIX | transport::MOCK) => {
+            Some(format!("{}://{}", url.scheme(), host))
+        }
+        None => None,
+    }
+}
+
+fn build_client(
+    settings: Option<&RouteSettings>,
+    socks_port: Option<u16>,
+) -> Result<reqwest::Client, String> {
+    configure_client(
+        reqwest::Client::builder().timeout(REQUEST_TIMEOUT),
+        settings,
+        socks_port,
+        None,
+    )
+}
+
+/// With a tunnel, hostnames are resolved on the jump host (`socks5h`), so the
+/// profile's proxy and resolver settings do not apply to the core's address;
+/// behind the NTLM relay the proxy resolves them. The TLS policy applies
+/// either way. Redirects follow the profile's policy unless `redirects`
+/// overrides it.
+fn configure_client(
+    mut builder: reqwest::ClientBuilder,
+    settings: Option<&RouteSettings>,
+    socks_port: Option<u16>,
+    redirects: Option<Policy>,
+) -> Result<reqwest::Client, String> {
+    let init_err =
+        |e: reqwest::Error| ErrorCode::Internal.with(format!("HTTP client init failed: {}", e));
+    builder = builder.redirect(redirects.unwrap_or_else(|| match settings {
+        Some(settings) => ssrf::redirect_policy(&settings.redirects, settings.custom_credentials()),
+        None => ssrf::redirect_policy(&RedirectSettings::default(), false),
+    }));
+    if let Some(settings) = settings.filter(|s| s.tls != TlsSettings::default()) {
+        builder = builder.use_preconfigured_tls(tls::client_config(&settings.tls)?);
+    }
+    if let Some(port) = socks_port {
+        let proxy =
+            reqwest::Proxy::all(format!("socks5h://127.0.0.1:{}", port)).map_err(init_err)?;
+        return builder.proxy(proxy).build().map_err(init_err);
+    }
+    let Some(settings) = settings else {
+        let resolver = ProfileResolver::new(&NetworkSettings::default())?;
+        return builder
+            .dns_resolver(Arc::new(resolver))
+            .build()
+            .map_err(init_err);
+    };
+    let proxy_url = settings.proxy.url.trim();
+    if !proxy_url.is_empty() {
+        let mut proxy = reqwest::Proxy::all(proxy_url).map_err(init_err)?;
+        if settings.proxy.auth == ProxyAuth::Negotiate {
+            let host = Url::parse(proxy_url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+                .unwrap_or_default();
+            let header = HeaderValue::from_str(&negotiate::authorization(&host)?).map_err(|e| {
+                ErrorCode::Internal.with(format!("Invalid proxy credential: {}", e))
+            })?;
+            proxy = proxy.custom_http_auth(header);
+        }
+        builder = builder.proxy(proxy);
+    }
+    builder = builder.dns_resolver(Arc::new(ProfileResolver::new(&settings.network)?));
+    builder.build().map_err(init_err)
+}
+
+fn parse_overrides(network: &NetworkSettings) -> Result<HashMap<String, Vec<IpAddr>>, String> {
+    let mut out = HashMap::new();
+    for (host, addrs) in &network.host_overrides {
+        let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+        if host.is_empty() {
+            return Err("Host override needs a hostname".to_string());
+        }
+        let ips = addrs
+            .iter()
+            .map(|a| {
+                a.trim()
+                    .parse::<IpAddr>()
+                    .map_err(|_| format!("Invalid override address for {}: {}", host, a))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if ips.is_empty() {
+            return Err(format!("Host override for {} has no addresses", host));
+        }
+        out.insert(host, ips);
+    }
+    Ok(out)
+}
+
+/// Accepts `ip` or `ip:port` (`[v6]:port` for IPv6).
+fn parse_servers(network: &NetworkSettings) -> Result<Vec<SocketAddr>, String> {
+    let default_port = if network.doh_server_name.is_some() {
+        DOH_PORT
+    } else {
+        DNS_PORT
+    };
+    network
+        .dns_servers
+        .iter()
+        .map(|raw| {
+            let raw = raw.trim();
+            raw.parse::<SocketAddr>()
+                .or_else(|_| {
+                    raw.parse::<IpAddr>()
+                        .map(|ip| SocketAddr::new(ip, default_port))
+                })
+                .map_err(|_| format!("Invalid DNS server address: {}", raw))
+        })
+        .collect()
+}
+
+/// Resolves the profile's host overrides first, then asks its nameservers
+/// (plain DNS or DoH), falling back to the system resolver when none are set.
+/// Addresses `ssrf` refuses are dropped, so a name cannot be pointed at a
+/// metadata endpoint.
+#[derive(Clone)]
+struct ProfileResolver {
+    overrides: Arc<HashMap<String, Vec<IpAddr>>>,
+    upstream: Option<TokioAsyncResolver>,
+    family: IpFamily,
+}
+
+fn family_name(ip: &IpAddr) -> &'static str {
+    if ip.is_ipv4() {
+        "ipv4"
+    } else {
+        "ipv6"
+    }
+}
+
+/// Puts the preferred family first, keeping the resolver's order within
+/// each family, or drops the excluded one. The HTTP connector starts with
+/// the first address's family and races the other after a short delay, so
+/// the order is what sets the preference.
+fn order_addrs(addrs: Vec<SocketAddr>, family: IpFamily) -> Vec<SocketAddr> {
+    let mut addrs = addrs;
+    match family {
+        IpFamily::Auto => {}
+        IpFamily::PreferIpv4 => addrs.sort_by_key(|a| !a.is_ipv4()),
+        IpFamily::PreferIpv6 => addrs.sort_by_key(|a| !a.is_ipv6()),
+        IpFamily::Ipv4Only => addrs.retain(SocketAddr::is_ipv4),
+        IpFamily::Ipv6Only => addrs.retain(SocketAddr::is_ipv6),
+    }
+    addrs
+}
+
+impl ProfileResolver {
+    fn new(network: &NetworkSettings) -> Result<Self, String> {
+        let overrides = parse_overrides(network)?;
+        let servers = parse_servers(network)?;
+        let upstream = (!servers.is_empty()).then(|| {
+            let mut group = NameServerConfigGroup::new();
+            for server in &servers {
+                group.merge(match &network.doh_server_name {
+                    Some(tls_name) => NameServerConfigGroup::from_ips_https(
+                        &[server.ip()],
+                        server.port(),
+                        tls_name.clone(),
+                        true,
+                    ),
+                    None => {
+                        NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true)
+                    }
+                });
+            }
+            TokioAsyncResolver::tokio(
+                ResolverConfig::from_parts(None, Vec::new(), group),
+                ResolverOpts::default(),
+            )
+        });
+        Ok(Self {
+            overrides: Arc::new(overrides),
+            upstream,
+            family: network.ip_family,
+        })
+    }
+}
+
+impl ProfileResolver {
+    async fn lookup(
+        &self,
+        host: &str,
+    ) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(ips) = self.overrides.get(host) {
+            return Ok(ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect());
+        }
+        Ok(match &self.upstream {
+            Some(resolver) => resolver
+                .lookup_ip(host)
+                .await?
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .collect(),
+            None => tokio::net::lookup_host((host, 0)).await?.collect(),
+        })
+    }
+}
+
+impl Resolve for ProfileResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().trim_end_matches('.').to_ascii_lowercase();
+        let resolver = self.clone();
+        Box::pin(async move {
+            let found = ssrf::filter_resolved(&host, resolver.lookup(&host).await?)?;
+            let addrs = order_addrs(found, resolver.family);
+            if addrs.is_empty() {
+                return Err(
+                    format!("{} has no address of the family this profile allows", host).into(),
+                );
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+async fn probe(addr: SocketAddr) -> Value {
+    let started = Instant::now();
+    let result = tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect(addr)).await;
+    let (reachable, error) = match result {
+        Ok(Ok(_)) => (true, None),
+        Ok(Err(e)) => (false, Some(e.to_string())),
+        Err(_) => (
+            false,
+            Some(format!("no answer within {}s", PROBE_TIMEOUT.as_secs())),
+        ),
+    };
+    json!({
+        "address": addr.ip().to_string(),
+        "family": family_name(&addr.ip()),
+        "reachable": reachable,
+        "connect_ms": reachable.then(|| started.elapsed().as_millis() as u64),
+        "error": error,
+    })
+}
+
+/// Which address family reaches a profile's core: the address the last
+/// request was answered from, every address the core's name resolves to
+/// under the profile's resolver settings with the order the shell tries
+/// them in, and a direct TCP connect to each. A family whose addresses all
+/// fail while the other's answer is called out in `hint`.
+#[tauri::command]
+pub async fn network_diagnostics(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = store.snapshot();
+    let profile = settings
+        .profile(&conn.profile_id)
+        .ok_or_else(|| ErrorCode::NotFound.with("Profile disappeared"))?;
+    let url = Url::parse(conn.base_url.trim())
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid base URL: {}", e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Base URL has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase();
+    let port = url.port_or_known_default().unwrap_or(80);
+    let family = profile.network.ip_family;
+
+    let found = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => ProfileResolver::new(&profile.network)?
+            .lookup(&host)
+            .await
+            .map_err(|e| {
+                ErrorCode::CoreUnreachable.with(format!("Resolving {} failed: {}", host, e))
+            })?
+            .into_iter()
+            .map(|a| SocketAddr::new(a.ip(), port))
+            .collect(),
+    };
+    let ordered = order_addrs(found.clone(), family);
+
+    let mut probes = tokio::task::JoinSet::new();
+    for addr in found.iter().take(MAX_PROBED).copied() {
+        probes.spawn(probe(addr));
+    }
+    let mut results = HashMap::new();
+    while let Some(Ok(result)) = probes.join_next().await {
+        results.insert(
+            result["address"].as_str().unwrap_or_default().to_string(),
+            result,
+        );
+    }
+    let addresses: Vec<Value> = found
+        .iter()
+        .map(|addr| {
+            let mut entry = results.remove(&addr.ip().to_string()).unwrap_or_else(
+                || json!({ "address": addr.ip().to_string(), "family": family_name(&addr.ip()) }),
+            );
+            entry["try_order"] = json!(ordered.iter().position(|a| a == addr));
+            entry
+        })
+        .collect();
+
+    let summary = |name: &str| {
+        let of_family: Vec<&Value> = addresses.iter().filter(|a| a["family"] == name).collect();
+        json!({
+            "addresses": of_family.len(),
+            "reachable": of_family.iter().filter(|a| a["reachable"] == true).count(),
+        })
+    };
+    let (v4, v6) = (summary("ipv4"), summary("ipv6"));
+    let broken = |f: &Value| f["addresses"].as_u64() > Some(0) && f["reachable"] == 0;
+    let reachable = |f: &Value| f["reachable"].as_u64() > Some(0);
+    let hint = if broken(&v6) && reachable(&v4) && family != IpFamily::Ipv4Only {
+        Some("IPv6 does not reach the core from this network; set network.ip_family to prefer_ipv4 or ipv4_only")
+    } else if broken(&v4) && reachable(&v6) && family != IpFamily::Ipv6Only {
+        Some("IPv4 does not reach the core from this network; set network.ip_family to prefer_ipv6 or ipv6_only")
+    } else if !found.is_empty() && ordered.is_empty() {
+        Some("The core has no address of the family this profile allows")
+    } else {
+        None
+    };
+
+    let origin = origin(&url).unwrap_or_default();
+    let last = peers()
+        .lock()
+        .ok()
+        .and_then(|p| p.get(&origin).copied())
+        .map(|(addr, at)| {
+            json!({
+                "address": addr.ip().to_string(),
+                "family": family_name(&addr.ip()),
+                "at": at.to_rfc3339(),
+            })
+        });
+    let routed_via = if profile.tunnel.enabled {
+        Some("tunnel")
+    } else if !profile.proxy.url.trim().is_empty() {
+        Some("proxy")
+    } else {
+        None
+    };
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "host": host,
+        "port": port,
+        "ip_family": family,
+        "routed_via": routed_via,
+        "last_connection": last,
+        "addresses": addresses,
+        "families": { "ipv4": v4, "ipv6": v6 },
+        "hint": hint,
+    }))
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+use tokio::process::Command;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{Connection, SettingsStore};
+use crate::{crypto, decisions, environment, notify, permissions, tasks};
+
+const TASK: &str = "notification_actions";
+const QUEUED_EVENT: &str = "notification_action:queued";
+const DONE_EVENT: &str = "notification_action:done";
+const CHECK_INTERVAL: Duration = Duration::from_secs(3);
+/// A queued intent the operator does not unlock for in time is dropped
+/// rather than fired long after the plan was looked at.
+const INTENT_TTL: Duration = Duration::from_secs(600);
+const MAX_QUEUED: usize = 50;
+
+/// Decisions taken from a notification while the session was locked,
+/// waiting for the unlock.
+#[derive(Default)]
+pub struct NotificationIntents {
+    pending: Mutex<HashMap<String, Intent>>,
+}
+
+#[derive(Clone)]
+struct Intent {
+    profile_id: String,
+    plan_id: String,
+    decision: String,
+    version: Option<String>,
+    execute: bool,
+    queued_at: Instant,
+    queued_at_utc: String,
+}
+
+impl Intent {
+    fn describe(&self, id: &str) -> Value {
+        json!({
+            "intent_id": id,
+            "profile_id": self.profile_id,
+            "plan_id": self.plan_id,
+            "decision": self.decision,
+            "execute": self.execute,
+            "queued_at": self.queued_at_utc,
+            "expires_in_seconds": INTENT_TTL.saturating_sub(self.queued_at.elapsed()).as_secs(),
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lock_probe() -> (Command, &'static str) {
+    let mut cmd = Command::new("ioreg");
+    cmd.args(["-n", "Root", "-d1"]);
+    (cmd, "\"CGSSessionScreenIsLocked\"=Yes")
+}
+
+/// The lock screen runs as `LogonUI.exe` while the session is locked.
+#[cfg(target_os = "windows")]
+fn lock_probe() -> (Command, &'static str) {
+    let mut cmd = Command::new("tasklist");
+    cmd.args(["/FI", "IMAGENAME eq LogonUI.exe", "/NH"]);
+    (cmd, "LogonUI.exe")
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn lock_probe() -> (Command, &'static str) {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "loginctl show-session \"${XDG_SESSION_ID:-auto}\" -p LockedHint --value",
+    ]);
+    (cmd, "yes")
+}
+
+/// Whether the OS session is locked. A probe that cannot run reports the
+/// session as unlocked.
+pub async fn session_locked() -> bool {
+    let (mut cmd, marker) = lock_probe();
+    match cmd
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+    {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout).contains(marker),
+        _ => false,
+    }
+}
+
+/// Re-reads the operator's permissions from the core, so a scope revoked
+/// since the notification was raised applies, then checks the decision
+/// against them.
+pub async fn authorize(conn: &Connection, plan_id: &str, decision: &str) -> Result<(), String> {
+    permissions::refresh(conn).await?;
+    permissions::check(
+        &Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/{}", plan_id, decision),
+        conn.token.as_deref(),
+    )
+}
+
+fn audit(app: &AppHandle, intent: &Intent, action: &str, detail: Value) {
+    if let Some(db) = app.try_state::<LocalDb>() {
+        if let Ok(conn) = db.lock() {
+            let _ = db::record_audit(
+                &conn,
+                &intent.profile_id,
+                "notification_action",
+                action,
+                &intent.plan_id,
+                &detail,
+            );
+        }
+    }
+}
+
+async fn fire(app: &AppHandle, store: &SettingsStore, intent: &Intent) -> Result<Value, String> {
+    let conn = store.connection(Some(&intent.profile_id))?;
+    authorize(&conn, &intent.plan_id, &intent.decision).await?;
+    let payload = if intent.decision == "approve" {
+        json!({ "execute": intent.execute, "source": "notification" })
+    } else {
+        json!({ "reason": "Rejected from a notification", "source": "notification" })
+    };
+    let result = decisions::send_decision(
+        &conn.base_url,
+        conn.token,
+        &intent.plan_id,
+        &intent.decision,
+        Some(payload),
+        intent.version.as_deref(),
+        None,
+    )
+    .await;
+    let detail = match &result {
+        Ok(_) => json!({ "decision": intent.decision, "execute": intent.execute }),
+        Err(err) => json!({ "decision": intent.decision, "error": err }),
+    };
+    let action = if result.is_ok() { "sent" } else { "failed" };
+    audit(app, intent, action, detail);
+    result
+}
+
+/// Approves or rejects a plan from a notification (a banner, the tray or
+/// the lock screen) rather than from the plan view. The permissions cached
+/// for the profile are fetched again first. While the OS session is
+/// locked nothing is sent: the decision is queued and fires once the
+/// session is unlocked, after the permissions are checked once more, or is
+/// dropped after ten minutes. Approvals from a notification never execute
+/// unless `execute` is set.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn notification_action(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    intents: State<'_, NotificationIntents>,
+    profile: Option<String>,
+    plan_id: String,
+    decision: String,
+    version: Option<String>,
+    execute: Option<bool>,
+) -> Result<Value, String> {
+    let decision = decisions::validate_decision(&decision, &plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let intent = Intent {
+        profile_id: conn.profile_id.clone(),
+        plan_id: plan_id.trim().to_string(),
+        decision,
+        version,
+        execute: execute.unwrap_or(false),
+        queued_at: Instant::now(),
+        queued_at_utc: chrono::Utc::now().to_rfc3339(),
+    };
+    if !session_locked().await {
+        let result = fire(&app, &store, &intent).await?;
+        return Ok(json!({ "queued": false, "sent": true, "result": result }));
+    }
+    authorize(&conn, &intent.plan_id, &intent.decision).await?;
+    let id: String = crypto::random_bytes::<8>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    {
+        let mut pending = intents
+            .pending
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Notification intents poisoned"))?;
+        if pending.len() >= MAX_QUEUED {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "At most {} decisions can wait for an unlock",
+                MAX_QUEUED
+            )));
+        }
+        pending.insert(id.clone(), intent.clone());
+    }
+    audit(
+        &app,
+        &intent,
+        "queued",
+        json!({ "decision": intent.decision, "intent_id": id }),
+    );
+    let described = intent.describe(&id);
+    let _ = environment::emit(&app, QUEUED_EVENT, &described);
+    Ok(json!({ "queued": true, "sent": false, "intent": described }))
+}
+
+/// Decisions waiting for the session to be unlocked.
+#[tauri::command]
+pub fn list_notification_intents(intents: State<'_, NotificationIntents>) -> Result<Value, String> {
+    let pending = intents
+        .pending
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Notification intents poisoned"))?;
+    let listed: Vec<Value> = pending.iter().map(|(id, i)| i.describe(id)).collect();
+    Ok(json!({ "intents": listed }))
+}
+
+#[tauri::command]
+pub fn cancel_notification_intent(
+    app: AppHandle,
+    intents: State<'_, NotificationIntents>,
+    intent_id: String,
+) -> Result<Value, String> {
+    let removed = intents
+        .pending
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Notification intents poisoned"))?
+        .remove(intent_id.trim())
+        .ok_or_else(|| ErrorCode::NotFound.with("No such queued decision"))?;
+    audit(
+        &app,
+        &removed,
+        "cancelled",
+        json!({ "decision": removed.decision, "intent_id": intent_id }),
+    );
+    Ok(json!({ "cancelled": true, "intent_id": intent_id }))
+}
+
+/// Fires queued decisions once the session is unlocked and drops the ones
+/// that waited too long.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            release(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+async fn release(app: &AppHandle) {
+    let intents = app.state::<NotificationIntents>();
+    let expired: Vec<(String, Intent)> = match intents.pending.lock() {
+        Ok(mut pending) => {
+            let ids: Vec<String> = pending
+                .iter()
+                .filter(|(_, i)| i.queued_at.elapsed() >= INTENT_TTL)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| pending.remove(&id).map(|i| (id, i)))
+                .collect()
+        }
+        Err(_) => return,
+    };
+    for (id, intent) in expired {
+        audit(app, &intent, "expired", json!({ "intent_id": id }));
+        let _ = environment::emit(
+            app,
+            DONE_EVENT,
+            json!({ "intent": intent.describe(&id), "sent": false, "error": "Expired before the session was unlocked" }),
+        );
+    }
+    let waiting = intents
+        .pending
+        .lock()
+        .map(|p| !p.is_empty())
+        .unwrap_or(false);
+    if !waiting || session_locked().await {
+        return;
+    }
+    let ready: Vec<(String, Intent)> = match intents.pending.lock() {
+        Ok(mut pending) => pending.drain().collect(),
+        Err(_) => return,
+    };
+    let store = app.state::<SettingsStore>();
+    for (id, intent) in ready {
+        let outcome = fire(app, &store, &intent).await;
+        let (title, body) = match &outcome {
+            Ok(_) => (
+                "Queued decision sent",
+                format!(
+                    "Plan {}: {} sent after unlock",
+                    intent.plan_id, intent.decision
+                ),
+            ),
+            Err(err) => (
+                "Queued decision failed",
+                format!("Plan {}: {}", intent.plan_id, err),
+            ),
+        };
+        notify::notify_in_app(
+            app,
+            "notification_action",
+            title,
+            &body,
+            json!({ "plan_id": intent.plan_id }),
+        );
+        let _ = environment::emit(
+            app,
+            DONE_EVENT,
+            json!({
+                "intent": intent.describe(&id),
+                "sent": outcome.is_ok(),
+                "result": outcome.as_ref().ok(),
+                "error": outcome.as_ref().err(),
+            }),
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::errors::ErrorCode;
+use crate::settings::{
+    Delivery, NotificationRule, NotificationSettings, ProfileNotificationSettings, Settings,
+    SettingsStore,
+};
+use crate::{audio, digest, environment, quiet};
+
+pub const NOTIFY_EVENT: &str = "notify:event";
+
+/// Raises an OS notification and mirrors it to the webview as a
+/// `notify:event` so in-app banners stay in sync with the system tray.
+/// Notification rules and quiet hours may change the delivery.
+pub fn notify(app: &AppHandle, kind: &str, title: &str, body: &str, data: Value) {
+    route(app, Delivery::Banner, kind, title, body, data);
+}
+
+/// Emits only the in-app `notify:event`, for alerts that shouldn't interrupt
+/// the operator at the OS level unless a rule says otherwise.
+pub fn notify_in_app(app: &AppHandle, kind: &str, title: &str, body: &str, data: Value) {
+    route(app, Delivery::Badge, kind, title, body, data);
+}
+
+pub fn validate_profile(settings: &ProfileNotificationSettings) -> Result<(), String> {
+    if let Some(quiet) = &settings.quiet_hours {
+        quiet::parse_time(&quiet.start)?;
+        quiet::parse_time(&quiet.end)?;
+    }
+    if let Some(classes) = &settings.digest {
+        digest::validate(classes)?;
+    }
+    Ok(())
+}
+
+/// The profile an event belongs to: the `profile_id` in its data, or the
+/// active profile for events that carry none.
+pub fn profile_of(settings: &Settings, data: &Value) -> String {
+    data["profile_id"]
+        .as_str()
+        .filter(|id| settings.profile(id).is_some())
+        .unwrap_or(&settings.active_profile)
+        .to_string()
+}
+
+/// The notification settings in force for `profile_id`'s events: the
+/// profile's rules ahead of the global ones, and its quiet hours and digest
+/// classes in place of the global ones where it sets them.
+pub fn effective(settings: &Settings, profile_id: &str) -> NotificationSettings {
+    let mut out = settings.notifications.clone();
+    if let Some(own) = settings.profile(profile_id).map(|p| &p.notifications) {
+        out.rules = own
+            .rules
+            .iter()
+            .chain(&settings.notifications.rules)
+            .cloned()
+            .collect();
+        if let Some(quiet) = &own.quiet_hours {
+            out.quiet_hours = quiet.clone();
+        }
+        if let Some(classes) = &own.digest {
+            out.digest = classes.clone();
+        }
+    }
+    out
+}
+
+/// Applies the first matching rule, then the activity digest and quiet
+/// hours, all as scoped to the event's profile: a non-urgent event that
+/// would interrupt is reduced to a badge and kept for a later summary.
+fn route(app: &AppHandle, default: Delivery, kind: &str, title: &str, body: &str, data: Value) {
+    let (profile_id, settings) = app
+        .try_state::<SettingsStore>()
+        .map(|store| {
+            let snapshot = store.snapshot();
+            let profile_id = profile_of(&snapshot, &data);
+            let settings = effective(&snapshot, &profile_id);
+            (profile_id, settings)
+        })
+        .unwrap_or_default();
+    let mut delivery = settings
+        .rules
+        .iter()
+        .find(|rule| matches(rule, kind, &data))
+        .map(|rule| rule.delivery)
+        .unwrap_or(default);
+    if delivery != Delivery::Badge
+        && digest::batch(
+            app,
+            &profile_id,
+            &settings.digest,
+            delivery,
+            kind,
+            title,
+            body,
+            &data,
+        )
+    {
+        delivery = Delivery::Badge;
+    }
+    let quiet = &settings.quiet_hours;
+    if delivery != Delivery::Badge
+        && quiet::quiet_reason(quiet).is_some()
+        && !quiet::is_critical(quiet, &data)
+    {
+        quiet::hold(app, &profile_id, kind, title, body);
+        delivery = Delivery::Badge;
+    }
+    deliver(app, delivery, kind, title, body, data);
+}
+
+fn deliver(app: &AppHandle, delivery: Delivery, kind: &str, title: &str, body: &str, data: Value) {
+    let _ = environment::emit(
+        app,
+        NOTIFY_EVENT,
+        json!({
+            "kind": kind,
+            "title": title,
+            "body": body,
+            "delivery": delivery,
+            "data": data,
+        }),
+    );
+    if delivery == Delivery::Badge {
+        return;
+    }
+    if let Err(err) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("notification failed: {}", err);
+    }
+    if delivery == Delivery::Sound {
+        if let Err(err) = audio::play_configured(app, kind) {
+            eprintln!("alert sound failed: {}", err);
+        }
+    }
+}
+
+/// A rule field that constrains the match; empty and `*` match anything.
+fn wanted(field: &Option<String>) -> Option<&str> {
+    field
+        .as_deref()
+        .map(str::trim)
+        .filter(|f| !f.is_empty() && *f != "*")
+}
+
+fn matches(rule: &NotificationRule, kind: &str, data: &Value) -> bool {
+    let first_of = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| data[*k].as_str().map(str::to_string))
+    };
+    let same = |want: &str, have: Option<&str>| have.is_some_and(|h| h.eq_ignore_ascii_case(want));
+    if wanted(&rule.event).is_some_and(|event| !same(event, Some(kind))) {
+        return false;
+    }
+    if wanted(&rule.risk)
+        .is_some_and(|risk| !same(risk, first_of(&["risk", "risk_level"]).as_deref()))
+    {
+        return false;
+    }
+    if wanted(&rule.agent)
+        .is_some_and(|agent| !same(agent, first_of(&["agent", "agent_id"]).as_deref()))
+    {
+        return false;
+    }
+    if let Some(tag) = wanted(&rule.tag) {
+        let tags: Vec<&str> = match &data["tags"] {
+            Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+            _ => data["tag"].as_str().into_iter().collect(),
+        };
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            return false;
+        }
+    }
+    true
+}
+
+fn unknown_profile(profile_id: &str) -> String {
+    ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id))
+}
+
+/// The global rules, or with `profile` set, that profile's own rules (which
+/// are checked before the global ones for its events).
+#[tauri::command]
+pub fn get_notification_rules(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    match profile {
+        Some(id) => {
+            let own = settings.profile(&id).ok_or_else(|| unknown_profile(&id))?;
+            Ok(json!({ "profile_id": id, "rules": own.notifications.rules }))
+        }
+        None => Ok(json!({ "rules": settings.notifications.rules })),
+    }
+}
+
+/// Replaces the global rule list, or with `profile` set, that profile's;
+/// order matters, as the first match wins.
+#[tauri::command]
+pub fn set_notification_rules(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    rules: Vec<NotificationRule>,
+) -> Result<Value, String> {
+    let updated = store.update(&app, |settings| {
+        match &profile {
+            Some(id) => {
+                settings
+                    .profiles
+                    .iter_mut()
+                    .find(|p| p.id == *id)
+                    .ok_or_else(|| unknown_profile(id))?
+                    .notifications
+                    .rules = rules;
+            }
+            None => settings.notifications.rules = rules,
+        }
+        Ok(())
+    })?;
+    match profile {
+        Some(id) => {
+            let own = updated.profile(&id).ok_or_else(|| unknown_profile(&id))?;
+            Ok(json!({ "profile_id": id, "rules": own.notifications.rules }))
+        }
+        None => Ok(json!({ "rules": updated.notifications.rules })),
+    }
+}
+
+/// Reports how an event of `kind` carrying `data` would be delivered under
+/// the rules in force for `profile` (default: the event's own profile, or
+/// the active one), without raising it. `scope` tells whether the matching
+/// rule is the profile's or a global one.
+#[tauri::command]
+pub fn evaluate_notification_rules(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    kind: String,
+    data: Option<Value>,
+) -> Result<Value, String> {
+    let data = data.unwrap_or(Value::Null);
+    let settings = store.snapshot();
+    let profile_id = match profile {
+        Some(id) if settings.profile(&id).is_none() => return Err(unknown_profile(&id)),
+        Some(id) => id,
+        None => profile_of(&settings, &data),
+    };
+    let own = settings
+        .profile(&profile_id)
+        .map_or(0, |p| p.notifications.rules.len());
+    let rules = effective(&settings, &profile_id).rules;
+    let matched = rules.iter().position(|rule| matches(rule, &kind, &data));
+    Ok(json!({
+        "profile_id": profile_id,
+        "kind": kind,
+        "matched_rule": matched.map(|i| if i < own { i } else { i - own }),
+        "scope": matched.map(|i| if i < own { "profile" } else { "global" }),
+        "delivery": matched.map(|i| rules[i].delivery),
+    }))
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+use reqwest::Url;
+use tauri::async_runtime::JoinHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::errors::ErrorCode;
+use crate::settings::ProxySettings;
+use crate::{crypto, secrets, tunnel};
+
+type HmacMd5 = Hmac<Md5>;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+/// NEGOTIATE_UNICODE | NEGOTIATE_OEM | REQUEST_TARGET | NEGOTIATE_NTLM |
+/// NEGOTIATE_ALWAYS_SIGN | NEGOTIATE_EXTENDED_SESSIONSECURITY |
+/// NEGOTIATE_128 | NEGOTIATE_56.
+const FLAGS: u32 = 0xa008_8207;
+const NEGOTIATE_UNICODE: u32 = 0x1;
+const AV_EOL: u16 = 0;
+const AV_TIMESTAMP: u16 = 7;
+/// 100 ns intervals between 1601-01-01 and the Unix epoch.
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+/// Offset of the payload in an AUTHENTICATE message without version or MIC.
+const AUTHENTICATE_HEADER: usize = 64;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEAD: usize = 16 * 1024;
+const MAX_CHALLENGE_BODY: u64 = 64 * 1024;
+
+/// The parts of a CHALLENGE message the response is computed from.
+pub struct Challenge {
+    server_challenge: [u8; 8],
+    flags: u32,
+    target_info: Vec<u8>,
+}
+
+/// The NEGOTIATE message opening the handshake, without domain or
+/// workstation.
+pub fn negotiate_message() -> Vec<u8> {
+    let mut out = Vec::with_capacity(32);
+    out.extend_from_slice(SIGNATURE);
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&FLAGS.to_le_bytes());
+    out.extend_from_slice(&[0; 16]);
+    out
+}
+
+pub fn parse_challenge(message: &[u8]) -> Result<Challenge, String> {
+    let invalid = || "Proxy sent an invalid NTLM challenge".to_string();
+    if message.len() < 32 || &message[..8] != SIGNATURE || u32_at(message, 8) != Some(2) {
+        return Err(invalid());
+    }
+    let flags = u32_at(message, 20).ok_or_else(invalid)?;
+    let mut server_challenge = [0u8; 8];
+    server_challenge.copy_from_slice(&message[24..32]);
+    // Servers that predate target info stop after the reserved field.
+    let target_info = if message.len() >= 48 {
+        field(message, 40).ok_or_else(invalid)?.to_vec()
+    } else {
+        Vec::new()
+    };
+    Ok(Challenge {
+        server_challenge,
+        flags,
+        target_info,
+    })
+}
+
+/// The AUTHENTICATE message answering `challenge` with NTLMv2 responses for
+/// `username` (`DOMAIN\user`, or `user@domain` with the domain left to the
+/// server).
+pub fn authenticate_message(challenge: &Challenge, username: &str, password: &str) -> Vec<u8> {
+    let (domain, user) = split_username(username);
+    let key = nt_owf_v2(user, domain, password);
+    let (lm, nt) = responses(&key, challenge, &crypto::random_bytes(), filetime_now());
+    let flags = challenge.flags & FLAGS;
+    let text = |s: &str| {
+        if flags & NEGOTIATE_UNICODE != 0 {
+            utf16(s)
+        } else {
+            s.as_bytes().to_vec()
+        }
+    };
+    let fields = [lm, nt, text(domain), text(user), Vec::new(), Vec::new()];
+    let mut out =
+        Vec::with_capacity(AUTHENTICATE_HEADER + fields.iter().map(Vec::len).sum::<usize>());
+    out.extend_from_slice(SIGNATURE);
+    out.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = AUTHENTICATE_HEADER;
+    for field in &fields {
+        let len = u16::try_from(field.len()).unwrap_or(u16::MAX);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    out.extend_from_slice(&flags.to_le_bytes());
+    for field in &fields {
+        out.extend_from_slice(field);
+    }
+    out
+}
+
+fn split_username(username: &str) -> (&str, &str) {
+    match username.trim().split_once('\\') {
+        Some((domain, user)) => (domain, user),
+        None => ("", username.trim()),
+    }
+}
+
+/// NTOWFv2: HMAC-MD5 keyed by the MD4 password hash over the upper-cased
+/// user name and the domain.
+fn nt_owf_v2(user: &str, domain: &str, password: &str) -> [u8; 16] {
+    let hash = Md4::digest(utf16(password));
+    let mut mac = HmacMd5::new_from_slice(&hash).expect("HMAC takes any key length");
+    mac.update(&utf16(&user.to_uppercase()));
+    mac.update(&utf16(domain));
+    mac.finalize().into_bytes().into()
+}
+
+/// The LM and NT challenge responses. A server that sends its own timestamp
+/// gets that one back and an empty LM response, as NTLMv2 requires.
+fn responses(
+    key: &[u8; 16],
+    challenge: &Challenge,
+    client_challenge: &[u8; 8],
+    now: u64,
+) -> (Vec<u8>, Vec<u8>) {
+    let server_time = timestamp(&challenge.target_info);
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&server_time.unwrap_or(now).to_le_bytes());
+    blob.extend_from_slice(client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(&challenge.target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let proof = hmac_md5(key, &[&challenge.server_challenge, &blob]);
+    let mut nt = proof.to_vec();
+    nt.extend_from_slice(&blob);
+
+    let lm = match server_time {
+        Some(_) => vec![0; 24],
+        None => {
+            let mut lm = hmac_md5(key, &[&challenge.server_challenge, client_challenge]).to_vec();
+            lm.extend_from_slice(client_challenge);
+            lm
+        }
+    };
+    (lm, nt)
+}
+
+fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = HmacMd5::new_from_slice(key).expect("HMAC takes any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// The server's `MsvAvTimestamp` from the target info, if it sent one.
+fn timestamp(target_info: &[u8]) -> Option<u64> {
+    let mut rest = target_info;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = usize::from(u16::from_le_bytes([rest[2], rest[3]]));
+        let value = rest.get(4..4 + len)?;
+        match id {
+            AV_EOL => return None,
+            AV_TIMESTAMP => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+            _ => rest = &rest[4 + len..],
+        }
+    }
+    None
+}
+
+fn filetime_now() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    FILETIME_UNIX_EPOCH + u64::try_from(since_epoch.as_nanos() / 100).unwrap_or_default()
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn u32_at(message: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        message.get(at..at + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The bytes a security buffer (length, allocated length, offset) at `at`
+/// points to.
+fn field(message: &[u8], at: usize) -> Option<&[u8]> {
+    let len = usize::from(u16::from_le_bytes(
+        message.get(at..at + 2)?.try_into().ok()?,
+    ));
+    let offset = usize::try_from(u32_at(message, at + 4)?).ok()?;
+    message.get(offset..offset.checked_add(len)?)
+}
+
+/// A loopback SOCKS5 listener for one core origin behind an NTLM proxy.
+/// NTLM authenticates a connection rather than a request, and the HTTP
+/// client keeps its proxy connections to itself, so the relay opens each
+/// connection to the proxy, runs the handshake on the `CONNECT`, and hands
+/// the authenticated tunnel to the client. Like an SSH tunnel's listener it
+/// only forwards to its own origin.
+struct Relay {
+    profile_id: String,
+    proxy: ProxySettings,
+    port: u16,
+    listener: JoinHandle<()>,
+}
+
+static RELAYS: OnceLock<Mutex<HashMap<String, Relay>>> = OnceLock::new();
+
+fn relays() -> &'static Mutex<HashMap<String, Relay>> {
+    RELAYS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The local SOCKS port reaching `origin` through the profile's NTLM proxy,
+/// starting the relay on first use or after the proxy settings changed.
+pub fn relay_port(origin: &str, profile_id: &str, proxy: &ProxySettings) -> Result<u16, String> {
+    let mut relays = relays()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("NTLM relay state poisoned"))?;
+    if let Some(relay) = relays.get(origin) {
+        if relay.profile_id == profile_id && relay.proxy == *proxy {
+            return Ok(relay.port);
+        }
+    }
+    let target = tunnel::core_address(origin)?;
+    let upstream = proxy_address(&proxy.url)?;
+    let listen_err =
+        |e: std::io::Error| ErrorCode::LocalIo.with(format!("NTLM relay listener failed: {}", e));
+    let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(listen_err)?;
+    listener.set_nonblocking(true).map_err(listen_err)?;
+    let port = listener.local_addr().map_err(listen_err)?.port();
+    let account = Account {
+        profile_id: profile_id.to_string(),
+        username: proxy.username.trim().to_string(),
+    };
+    let task = tauri::async_runtime::spawn(async move {
+        match TcpListener::from_std(listener) {
+            Ok(listener) => accept_loop(listener, target, upstream, account).await,
+            Err(err) => eprintln!("NTLM relay listener failed: {}", err),
+        }
+    });
+    let relay = Relay {
+        profile_id: profile_id.to_string(),
+        proxy: proxy.clone(),
+        port,
+        listener: task,
+    };
+    if let Some(old) = relays.insert(origin.to_string(), relay) {
+        old.listener.abort();
+    }
+    Ok(port)
+}
+
+fn proxy_address(url: &str) -> Result<(String, u16), String> {
+    let url = Url::parse(url.trim())
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid proxy URL: {}", e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Proxy URL has no host"))?
+        .trim_matches(['[', ']'])
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Proxy URL has no port"))?;
+    Ok((host, port))
+}
+
+/// Stops the relays of origins that no longer use an NTLM proxy.
+pub fn retain(keep: impl Fn(&str) -> bool) {
+    let Ok(mut relays) = relays().lock() else {
+        return;
+    };
+    relays.retain(|origin, relay| {
+        let kept = keep(origin);
+        if !kept {
+            relay.listener.abort();
+        }
+        kept
+    });
+}
+
+#[derive(Clone)]
+struct Account {
+    profile_id: String,
+    username: String,
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    target: (String, u16),
+    upstream: (String, u16),
+    account: Account,
+) {
+    while let Ok((stream, peer)) = listener.accept().await {
+        let (target, upstream, account) = (target.clone(), upstream.clone(), account.clone());
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = relay(stream, &target, &upstream, &account).await {
+                eprintln!("NTLM relay connection from {} failed: {}", peer, err);
+            }
+        });
+    }
+}
+
+async fn relay(
+    mut stream: TcpStream,
+    target: &(String, u16),
+    upstream: &(String, u16),
+    account: &Account,
+) -> Result<(), String> {
+    let (host, port) = tunnel::socks_accept(&mut stream).await?;
+    if !host.eq_ignore_ascii_case(&target.0) || port != target.1 {
+        let _ = stream.write_all(&tunnel::socks_reply(0x02)).await;
+        return Err(format!("refused forward to {}:{}", host, port));
+    }
+    let password = secrets::get(&secrets::profile_proxy_password_key(&account.profile_id))
+        .and_then(|p| p.ok_or_else(|| "no proxy password is saved".to_string()));
+    let connected = match password {
+        Ok(password) => tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            connect(upstream, target, &account.username, &password),
+        )
+        .await
+        .unwrap_or_else(|_| Err("proxy handshake timed out".to_string())),
+        Err(err) => Err(err),
+    };
+    let mut remote = match connected {
+        Ok(remote) => remote,
+        Err(err) => {
+            let _ = stream.write_all(&tunnel::socks_reply(0x05)).await;
+            return Err(err);
+        }
+    };
+    stream
+        .write_all(&tunnel::socks_reply(0x00))
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::io::copy_bidirectional(&mut stream, &mut remote)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Opens a `CONNECT` tunnel to `target` through the proxy at `upstream`,
+/// answering its NTLM challenge on the same connection.
+async fn connect(
+    upstream: &(String, u16),
+    target: &(String, u16),
+    username: &str,
+    password: &str,
+) -> Result<TcpStream, String> {
+    let io = |e: std::io::Error| e.to_string();
+    let mut proxy = TcpStream::connect((upstream.0.as_str(), upstream.1))
+        .await
+        .map_err(|e| format!("proxy connect failed: {}", e))?;
+    let authority = if target.0.contains(':') {
+        format!("[{}]:{}", target.0, target.1)
+    } else {
+        format!("{}:{}", target.0, target.1)
+    };
+    send_connect(&mut proxy, &authority, &negotiate_message()).await?;
+    let head = read_head(&mut proxy).await?;
+    match status(&head) {
+        Some(200) => return Ok(proxy),
+        Some(407) => {}
+        _ => return Err(format!("proxy refused CONNECT: {}", first_line(&head))),
+    }
+    let challenge = header_values(&head, "proxy-authenticate")
+        .find_map(|v| v.strip_prefix("NTLM ").map(str::trim))
+        .ok_or_else(|| "proxy does not offer NTLM".to_string())?;
+    let challenge = STANDARD
+        .decode(challenge)
+        .map_err(|_| "Proxy sent an invalid NTLM challenge".to_string())?;
+    let challenge = parse_challenge(&challenge)?;
+    if header_values(&head, "proxy-connection")
+        .chain(header_values(&head, "connection"))
+        .any(|v| v.eq_ignore_ascii_case("close"))
+    {
+        return Err("proxy closed the connection during the NTLM handshake".to_string());
+    }
+    let length = header_values(&head, "content-length")
+        .next()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    if length > MAX_CHALLENGE_BODY {
+        return Err("proxy challenge body is too large".to_string());
+    }
+    let mut body = (&mut proxy).take(length);
+    tokio::io::copy(&mut body, &mut tokio::io::sink())
+        .await
+        .map_err(io)?;
+
+    let answer = authenticate_message(&challenge, username, password);
+    send_connect(&mut proxy, &authority, &answer).await?;
+    let head = read_head(&mut proxy).await?;
+    match status(&head) {
+        Some(200) => Ok(proxy),
+        Some(407) => Err("proxy rejected the NTLM credentials".to_string()),
+        _ => Err(format!("proxy refused CONNECT: {}", first_line(&head))),
+    }
+}
+
+async fn send_connect(
+    proxy: &mut TcpStream,
+    authority: &str,
+    message: &[u8],
+) -> Result<(), String> {
+    let request = format!(
+        "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\nProxy-Connection: keep-alive\r\nProxy-Authorization: NTLM {1}\r\n\r\n",
+        authority,
+        STANDARD.encode(message)
+    );
+    proxy
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Reads a response head byte by byte, so nothing after it is consumed.
+async fn read_head(proxy: &mut TcpStream) -> Result<String, String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD {
+            return Err("proxy response head is too large".to_string());
+        }
+        head.push(proxy.read_u8().await.map_err(|e| e.to_string())?);
+    }
+    String::from_utf8(head).map_err(|_| "proxy sent a non-UTF-8 response head".to_string())
+}
+
+fn first_line(head: &str) -> &str {
+    head.lines().next().unwrap_or_default()
+}
+
+fn status(head: &str) -> Option<u16> {
+    first_line(head).split_whitespace().nth(1)?.parse().ok()
+}
+
+fn header_values<'a>(head: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    head.lines().skip(1).filter_map(move |line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MS-NLMP 4.2.4: user "User", domain "Domain", password "Password".
+    const SERVER_CHALLENGE: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+    const CLIENT_CHALLENGE: [u8; 8] = [0xaa; 8];
+    const TARGET_INFO: [u8; 36] = [
+        0x02, 0x00, 0x0c, 0x00, 0x44, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x61, 0x00, 0x69, 0x00, 0x6e,
+        0x00, 0x01, 0x00, 0x0c, 0x00, 0x53, 0x00, 0x65, 0x00, 0x72, 0x00, 0x76, 0x00, 0x65, 0x00,
+        0x72, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn challenge(target_info: &[u8]) -> Challenge {
+        Challenge {
+            server_challenge: SERVER_CHALLENGE,
+            flags: 0xe28a_8233,
+            target_info: target_info.to_vec(),
+        }
+    }
+
+    #[test]
+    fn nt_owf_v2_matches_the_spec() {
+        assert_eq!(
+            hex(&nt_owf_v2("User", "Domain", "Password")),
+            "0c868a403bfd7a93a3001ef22ef02e3f"
+        );
+    }
+
+    #[test]
+    fn ntlmv2_responses_match_the_spec() {
+        let key = nt_owf_v2("User", "Domain", "Password");
+        let (lm, nt) = responses(&key, &challenge(&TARGET_INFO), &CLIENT_CHALLENGE, 0);
+        assert_eq!(hex(&lm), "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa");
+        assert_eq!(hex(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+        assert_eq!(&nt[16..], {
+            let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+            blob.extend_from_slice(&[0; 8]);
+            blob.extend_from_slice(&CLIENT_CHALLENGE);
+            blob.extend_from_slice(&[0; 4]);
+            blob.extend_from_slice(&TARGET_INFO);
+            blob.extend_from_slice(&[0; 4]);
+            blob
+        });
+    }
+
+    #[test]
+    fn server_timestamp_replaces_the_lm_response() {
+        let mut info = vec![0x07, 0x00, 0x08, 0x00];
+        info.extend_from_slice(&42u64.to_le_bytes());
+        info.extend_from_slice(&[0; 4]);
+        let key = nt_owf_v2("User", "Domain", "Password");
+        let (lm, nt) = responses(&key, &challenge(&info), &CLIENT_CHALLENGE, 7);
+        assert_eq!(lm, vec![0; 24]);
+        assert_eq!(&nt[24..32], &42u64.to_le_bytes());
+    }
+
+    #[test]
+    fn parses_a_challenge_message() {
+        let mut message = SIGNATURE.to_vec();
+        message.extend_from_slice(&2u32.to_le_bytes());
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&0xe28a_8233u32.to_le_bytes());
+        message.extend_from_slice(&SERVER_CHALLENGE);
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&36u16.to_le_bytes());
+        message.extend_from_slice(&36u16.to_le_bytes());
+        message.extend_from_slice(&48u32.to_le_bytes());
+        message.extend_from_slice(&TARGET_INFO);
+        let parsed = parse_challenge(&message).expect("valid challenge");
+        assert_eq!(parsed.server_challenge, SERVER_CHALLENGE);
+        assert_eq!(parsed.flags, 0xe28a_8233);
+        assert_eq!(parsed.target_info, TARGET_INFO);
+
+        message[8] = 3;
+        assert!(parse_challenge(&message).is_err());
+        assert!(parse_challenge(&message[..20]).is_err());
+    }
+
+    #[test]
+    fn authenticate_message_points_at_its_fields() {
+        let message = authenticate_message(&challenge(&TARGET_INFO), "Domain\\User", "Password");
+        assert_eq!(&message[..8], SIGNATURE);
+        assert_eq!(u32_at(&message, 8), Some(3));
+        assert_eq!(field(&message, 12).map(<[u8]>::len), Some(24));
+        assert_eq!(field(&message, 20).map(<[u8]>::len), Some(16 + 28 + 36 + 4));
+        assert_eq!(field(&message, 28), Some(utf16("Domain").as_slice()));
+        assert_eq!(field(&message, 36), Some(utf16("User").as_slice()));
+        assert_eq!(u32_at(&message, 60), Some(0xe28a_8233 & FLAGS));
+    }
+
+    #[test]
+    fn splits_down_level_and_upn_names() {
+        assert_eq!(split_username("CORP\\alice"), ("CORP", "alice"));
+        assert_eq!(
+            split_username("alice@corp.example"),
+            ("", "alice@corp.example")
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection, SettingsStore};
+use crate::{crypto, environment};
+
+const PROGRESS_EVENT: &str = "pagination:progress";
+/// Items asked for per page; a core that caps pages lower is walked all
+/// the same.
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 500;
+pub const DEFAULT_MAX_ITEMS: usize = 10_000;
+pub const MAX_ITEMS: usize = 100_000;
+/// Tries per page before the walk stops with what it has.
+const PAGE_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Where an enveloped page lists its items, when it is an object.
+const ITEM_KEYS: &[&str] = &[
+    "items", "data", "results", "records", "plans", "jobs", "events", "memories",
+];
+/// Query parameters the walk sets itself.
+const PAGE_PARAMS: &[&str] = &["limit", "offset", "cursor"];
+
+/// Walks under way, by id, and whether each was asked to stop.
+static WALKS: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+
+fn walks() -> &'static Mutex<HashMap<String, bool>> {
+    WALKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Where the next page starts: an offset for cores that list plain arrays,
+/// or the `next_cursor` an enveloped page handed back.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Resume {
+    Offset { offset: usize },
+    Cursor { cursor: String },
+}
+
+/// Everything a walk gathered. An incomplete one stopped on `error` and
+/// picks up at `resume`; a complete one that is `truncated` ended at
+/// `max_items` with more left on the core.
+pub struct Collected {
+    pub items: Vec<Value>,
+    pub pages: usize,
+    pub total: Option<u64>,
+    pub complete: bool,
+    pub truncated: bool,
+    pub error: Option<String>,
+    pub resume: Option<Resume>,
+}
+
+impl Collected {
+    /// What a walk that did not finish failed with.
+    pub fn into_result(self) -> Result<Vec<Value>, String> {
+        match self.error {
+            Some(err) if !self.complete => Err(err),
+            _ => Ok(self.items),
+        }
+    }
+}
+
+/// One page: its items, the total the core reported, and its cursor.
+/// `next` is `Some(None)` when the page was an envelope that carries no
+/// further cursor.
+struct Page {
+    items: Vec<Value>,
+    total: Option<u64>,
+    next: Option<Option<String>>,
+}
+
+fn parse_page(body: Value) -> Result<Page, String> {
+    if let Value::Array(items) = body {
+        return Ok(Page {
+            items,
+            total: None,
+            next: None,
+        });
+    }
+    let items = ITEM_KEYS
+        .iter()
+        .find_map(|key| body[*key].as_array())
+        .cloned()
+        .ok_or_else(|| ErrorCode::CoreError.with("Core did not answer with a page of items"))?;
+    let next = body
+        .get("next_cursor")
+        .map(|c| c.as_str().filter(|c| !c.is_empty()).map(str::to_string));
+    Ok(Page {
+        items,
+        total: body["total"].as_u64(),
+        next,
+    })
+}
+
+/// `path` with the walk's own paging parameters in place of any it had.
+fn page_path(path: &str, page_size: usize, at: &Resume) -> String {
+    let (bare, query) = path.split_once('?').unwrap_or((path, ""));
+    let mut pairs: Vec<String> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            !PAGE_PARAMS.contains(&name)
+        })
+        .map(str::to_string)
+        .collect();
+    pairs.push(format!("limit={}", page_size));
+    match at {
+        Resume::Offset { offset } => pairs.push(format!("offset={}", offset)),
+        Resume::Cursor { cursor } => {
+            let encoded: String = cursor
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
+                })
+                .collect();
+            pairs.push(format!("cursor={}", encoded));
+        }
+    }
+    format!("{}?{}", bare, pairs.join("&"))
+}
+
+async fn fetch(conn: &Connection, path: &str) -> Result<Value, String> {
+    let mut attempt = 1;
+    loop {
+        match crate::request_json(Method::GET, &conn.base_url, path, conn.token.clone(), None).await
+        {
+            Ok(body) => return Ok(body),
+            Err(err)
+                if attempt < PAGE_ATTEMPTS
+                    && errors::code_of(&err).is_some_and(|code| code.retryable()) =>
+            {
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn progress(app: Option<&AppHandle>, walk_id: &str, path: &str, collected: &Collected, done: bool) {
+    let Some(app) = app else {
+        return;
+    };
+    let _ = environment::emit(
+        app,
+        PROGRESS_EVENT,
+        json!({
+            "walk_id": walk_id,
+            "path": path,
+            "pages": collected.pages,
+            "items": collected.items.len(),
+            "total": collected.total,
+            "done": done,
+            "complete": done && collected.complete,
+            "error": collected.error,
+        }),
+    );
+}
+
+/// Walks every page of the core's list at `path`, from `resume` or the
+/// start, emitting `pagination:progress` as walk `walk_id` after each page
+/// when there is an `app` to emit on.
+/// Pages are asked for `page_size` items; a page that fails is tried again
+/// when its error is retryable, and a walk that still cannot go on stops
+/// with what it has and where it got to. It ends on an empty page, at the
+/// core's `total`, when no cursor comes back, or at `max_items`.
+pub async fn collect_all(
+    app: Option<&AppHandle>,
+    walk_id: &str,
+    conn: &Connection,
+    path: &str,
+    page_size: usize,
+    max_items: usize,
+    resume: Option<Resume>,
+) -> Collected {
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let mut at = resume.unwrap_or(Resume::Offset { offset: 0 });
+    let mut offset = match &at {
+        Resume::Offset { offset } => *offset,
+        Resume::Cursor { .. } => 0,
+    };
+    let mut collected = Collected {
+        items: Vec::new(),
+        pages: 0,
+        total: None,
+        complete: false,
+        truncated: false,
+        error: None,
+        resume: None,
+    };
+    if let Ok(mut walks) = walks().lock() {
+        walks.insert(walk_id.to_string(), false);
+    }
+    loop {
+        let stop = walks()
+            .lock()
+            .is_ok_and(|walks| walks.get(walk_id) == Some(&true));
+        if stop {
+            collected.error = Some(ErrorCode::InvalidInput.with("Walk cancelled"));
+            collected.resume = Some(at);
+            break;
+        }
+        let page = match fetch(conn, &page_path(path, page_size, &at))
+            .await
+            .and_then(parse_page)
+        {
+            Ok(page) => page,
+            Err(err) => {
+                collected.error = Some(err);
+                collected.resume = Some(at);
+                break;
+            }
+        };
+        collected.pages += 1;
+        collected.total = page.total.or(collected.total);
+        let fetched = page.items.len();
+        offset += fetched;
+        let room = max_items.saturating_sub(collected.items.len());
+        collected.items.extend(page.items.into_iter().take(room));
+        let next = match page.next {
+            Some(Some(cursor))
+                if at
+                    != (Resume::Cursor {
+                        cursor: cursor.clone(),
+                    }) =>
+            {
+                Some(Resume::Cursor { cursor })
+            }
+            Some(_) => None,
+            None => {
+                let reached = collected.total.is_some_and(|total| offset as u64 >= total);
+                (fetched > 0 && !reached).then_some(Resume::Offset { offset })
+            }
+        };
+        match next {
+            Some(next) if collected.items.len() < max_items => {
+                at = next;
+                progress(app, walk_id, path, &collected, false);
+            }
+            more => {
+                collected.truncated = more.is_some() || fetched > room;
+                collected.complete = true;
+                break;
+            }
+        }
+    }
+    if let Ok(mut walks) = walks().lock() {
+        walks.remove(walk_id);
+    }
+    progress(app, walk_id, path, &collected, true);
+    collected
+}
+
+/// Fetches every page of a core list in one call, for callers that want
+/// all of it: `path` is a GET route such as `/plans?status=pending`, and
+/// its `limit`, `offset` and `cursor` are the walk's. Progress comes as
+/// `pagination:progress` under `walk_id` (one is made up without it). An
+/// incomplete result carries the items so far, the error and a `resume` to
+/// pass back for the rest.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn core_request_all(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    path: String,
+    page_size: Option<usize>,
+    max_items: Option<usize>,
+    resume: Option<Resume>,
+    walk_id: Option<String>,
+) -> Result<Value, String> {
+    let path = path.trim();
+    if !path.starts_with('/') {
+        return Err(ErrorCode::InvalidInput.with("path must start with `/`"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let walk_id = walk_id.unwrap_or_else(|| {
+        let hex: String = crypto::random_bytes::<8>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("walk-{}", hex)
+    });
+    let max_items = max_items.unwrap_or(DEFAULT_MAX_ITEMS).clamp(1, MAX_ITEMS);
+    let collected = collect_all(
+        Some(&app),
+        &walk_id,
+        &conn,
+        path,
+        page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+        max_items,
+        resume,
+    )
+    .await;
+    Ok(json!({
+        "walk_id": walk_id,
+        "items": collected.items,
+        "pages": collected.pages,
+        "total": collected.total,
+        "complete": collected.complete,
+        "truncated": collected.truncated,
+        "error": collected.error,
+        "resume": collected.resume,
+    }))
+}
+
+/// Stops walk `walk_id` before its next page; it returns what it has,
+/// with a `resume` for the rest.
+#[tauri::command]
+pub fn cancel_pagination(walk_id: String) -> Result<Value, String> {
+    let mut walks = walks()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Pagination state poisoned"))?;
+    let stop = walks
+        .get_mut(&walk_id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("No walk {} is under way", walk_id)))?;
+    *stop = true;
+    Ok(json!({ "walk_id": walk_id }))
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::environment;
+
+const DRIFT_EVENT: &str = "local_exec:drift";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Files and folders tracked across every watched path; past this the
+/// rest go unwatched and the report says so.
+const MAX_ENTRIES: usize = 20_000;
+const MAX_DEPTH: usize = 16;
+/// Drift entries kept for the run's report; later ones are only counted.
+const MAX_DRIFT: usize = 200;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+type Snapshot = HashMap<PathBuf, Stamp>;
+
+#[derive(Default)]
+struct Found {
+    drift: Vec<Value>,
+    count: usize,
+    truncated: bool,
+}
+
+/// Polls the paths an executing plan references and reports changes to
+/// any of them outside the paths its steps declare they write.
+pub struct PathWatch {
+    watched: Vec<PathBuf>,
+    declared: Vec<PathBuf>,
+    step: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+    found: Arc<Mutex<Found>>,
+    thread: JoinHandle<()>,
+}
+
+/// `raw` as an absolute path, under `jail` when relative, with links
+/// resolved as far as the path exists.
+fn absolute(jail: &Path, raw: &str) -> Option<PathBuf> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let path = jail.join(raw);
+    if let Ok(real) = path.canonicalize() {
+        return Some(real);
+    }
+    match (
+        path.parent().and_then(|p| p.canonicalize().ok()),
+        path.file_name(),
+    ) {
+        (Some(dir), Some(name)) => Some(dir.join(name)),
+        _ => Some(path),
+    }
+}
+
+fn listed(value: &Value) -> impl Iterator<Item = &str> {
+    let list = match value {
+        Value::String(one) => vec![one.as_str()],
+        Value::Array(items) => items.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    list.into_iter()
+}
+
+/// The paths `plan` references (its `paths`, and each step's `paths` and
+/// `writes`), and those its steps declare they write (`writes` on the plan
+/// or a step).
+fn referenced(jail: &Path, plan: &Value) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let actions = plan["actions"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    let mut declared: Vec<PathBuf> = listed(&plan["writes"])
+        .chain(actions.iter().flat_map(|a| listed(&a["writes"])))
+        .filter_map(|p| absolute(jail, p))
+        .collect();
+    declared.sort();
+    declared.dedup();
+    let mut watched: Vec<PathBuf> = listed(&plan["paths"])
+        .chain(actions.iter().flat_map(|a| listed(&a["paths"])))
+        .filter_map(|p| absolute(jail, p))
+        .chain(declared.iter().cloned())
+        .collect();
+    watched.sort();
+    watched.dedup();
+    // A path inside another watched one is covered by it.
+    let roots: Vec<PathBuf> = watched
+        .iter()
+        .filter(|p| !watched.iter().any(|o| o != *p && p.starts_with(o)))
+        .cloned()
+        .collect();
+    (roots, declared)
+}
+
+fn stamp(meta: &fs::Metadata) -> Stamp {
+    Stamp {
+        dir: meta.is_dir(),
+        len: meta.len(),
+        modified: meta.modified().ok(),
+    }
+}
+
+/// Every file and folder under `root`, links not followed, until
+/// `MAX_ENTRIES`; true when that cut the walk short.
+fn walk(root: &Path, depth: usize, into: &mut Snapshot) -> bool {
+    let Ok(meta) = fs::symlink_metadata(root) else {
+        return false;
+    };
+    if into.len() >= MAX_ENTRIES {
+        return true;
+    }
+    into.insert(root.to_path_buf(), stamp(&meta));
+    if !meta.is_dir() || depth >= MAX_DEPTH {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir(root) else {
+        return false;
+    };
+    let mut truncated = false;
+    for entry in entries.flatten() {
+        truncated |= walk(&entry.path(), depth + 1, into);
+        if truncated {
+            break;
+        }
+    }
+    truncated
+}
+
+fn snapshot(roots: &[PathBuf]) -> (Snapshot, bool) {
+    let mut snapshot = Snapshot::new();
+    let mut truncated = false;
+    for root in roots {
+        truncated |= walk(root, 0, &mut snapshot);
+    }
+    (snapshot, truncated)
+}
+
+/// What changed between two snapshots, leaving out folders whose only
+/// change is their contents.
+fn changes<'a>(before: &'a Snapshot, after: &'a Snapshot) -> Vec<(&'a Path, &'static str)> {
+    let mut out: Vec<(&Path, &str)> = after
+        .iter()
+        .filter_map(|(path, now)| match before.get(path) {
+            None => Some((path.as_path(), "created")),
+            Some(was) if was.dir != now.dir => Some((path.as_path(), "replaced")),
+            Some(was) if !now.dir && was != now => Some((path.as_path(), "modified")),
+            _ => None,
+        })
+        .chain(
+            before
+                .keys()
+                .filter(|path| !after.contains_key(*path))
+                .map(|path| (path.as_path(), "removed")),
+        )
+        .collect();
+    out.sort();
+    out
+}
+
+impl PathWatch {
+    /// Starts watching the paths `plan` references, or `None` when it
+    /// references none. The first snapshot is the baseline.
+    pub fn start(app: &AppHandle, plan_id: &str, jail: &Path, plan: &Value) -> Option<Self> {
+        let (watched, declared) = referenced(jail, plan);
+        if watched.is_empty() {
+            return None;
+        }
+        let step = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let found = Arc::new(Mutex::new(Found::default()));
+        let (mut last, truncated) = snapshot(&watched);
+        found.lock().unwrap_or_else(|e| e.into_inner()).truncated = truncated;
+        let thread = {
+            let (app, plan_id) = (app.clone(), plan_id.to_string());
+            let (watched, declared) = (watched.clone(), declared.clone());
+            let (step, stop, found) = (step.clone(), stop.clone(), found.clone());
+            std::thread::spawn(move || loop {
+                // One last look once the run ends, for its final writes.
+                let stopping = stop.load(Ordering::SeqCst);
+                if !stopping {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                let (next, truncated) = snapshot(&watched);
+                let mut found = found.lock().unwrap_or_else(|e| e.into_inner());
+                found.truncated |= truncated;
+                for (path, change) in changes(&last, &next) {
+                    if declared.iter().any(|d| path.starts_with(d)) {
+                        continue;
+                    }
+                    let entry = json!({
+                        "path": path.display().to_string(),
+                        "change": change,
+                        "step": step.load(Ordering::SeqCst),
+                        "at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                    });
+                    let _ = environment::emit(
+                        &app,
+                        DRIFT_EVENT,
+                        json!({ "plan_id": plan_id, "drift": entry }),
+                    );
+                    found.count += 1;
+                    if found.drift.len() < MAX_DRIFT {
+                        found.drift.push(entry);
+                    }
+                }
+                last = next;
+                if stopping {
+                    break;
+                }
+            })
+        };
+        Some(Self {
+            watched,
+            declared,
+            step,
+            stop,
+            found,
+            thread,
+        })
+    }
+
+    /// Attributes changes seen from now on to step `index`.
+    pub fn step(&self, index: usize) {
+        self.step.store(index, Ordering::SeqCst);
+    }
+
+    /// Stops watching after a last look, and reports what was watched and
+    /// every change found outside the declared paths.
+    pub async fn finish(self) -> Value {
+        self.stop.store(true, Ordering::SeqCst);
+        let thread = self.thread;
+        let _ = tauri::async_runtime::spawn_blocking(move || thread.join()).await;
+        let found = self.found.lock().unwrap_or_else(|e| e.into_inner());
+        let paths = |list: &[PathBuf]| {
+            list.iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "watched": paths(&self.watched),
+            "declared": paths(&self.declared),
+            "drifted": found.count > 0,
+            "count": found.count,
+            "drift": found.drift,
+            "truncated": found.truncated,
+        })
+    }
+}
@@ -0,0 +1,639 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, Runtime, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection, Profile, Settings, SettingsStore};
+use crate::{crypto, jwt, managed, net, secrets};
+
+/// Scopes the bridge issues; `admin` implies every other one.
+const SCOPES: &[&str] = &[
+    "admin", "read", "run", "plan", "approve", "reject", "undo", "cancel",
+];
+const ACTIONS: &[&str] = &["run", "plan", "approve", "reject", "undo", "cancel"];
+
+/// Scopes reported by the core's `/auth/permissions`, keyed by base URL and
+/// token digest. These take precedence over scopes read from token claims.
+static GRANTED: OnceLock<Mutex<HashMap<String, BTreeSet<String>>>> = OnceLock::new();
+
+/// Profiles flagged read-only and their origins; mutating requests to them
+/// are refused whichever command issues them.
+static READ_ONLY: OnceLock<Mutex<ReadOnly>> = OnceLock::new();
+
+#[derive(Default)]
+struct ReadOnly {
+    origins: BTreeSet<String>,
+    profiles: BTreeSet<String>,
+    /// Whether the active profile is one of them.
+    active: bool,
+}
+
+/// POST routes that only read, and so stay available in read-only mode.
+const READ_ONLY_POSTS: &[&str] = &["/memory/recall"];
+
+/// Commands that only read or only touch the window's own state, and so
+/// stay available in read-only mode, besides those named like
+/// `OBSERVER_PREFIXES`. Every other command is refused for a read-only
+/// profile. Core requests are also held to `check` on the way out.
+const OBSERVER_COMMANDS: &[&str] = &[
+    "core_request",
+    "core_request_all",
+    "cached_core_request",
+    "cancel_pagination",
+    "read_chunk",
+    "release_chunked_body",
+    "sanitize_html",
+    "format_values",
+    "bandwidth_report",
+    "performance_report",
+    "resource_report",
+    "replica_status",
+    "tunnel_status",
+    "vault_status",
+    "cache_stats",
+    "database_encryption_status",
+    "database_schema_status",
+    "network_diagnostics",
+    "probe_core_url",
+    "detect_api_prefix",
+    "next_pending",
+    "previous",
+    "watch_plan",
+    "unwatch_plan",
+    "watch_plan_output",
+    "stop_plan_output",
+    "plan_output_transcript",
+    "start_live_events",
+    "stop_live_events",
+    "subscribe_state",
+    "unsubscribe_state",
+    "refresh_state",
+    "refresh_pending_approvals",
+    "refresh_announcements",
+    "mark_announcements_read",
+    "refresh_calendar",
+    "refresh_plan_deadlines",
+    "sync_changes",
+    "sync_usage",
+    "record_recent_item",
+    "set_current_plan",
+    "report_operator_activity",
+    "play_alert_sound",
+    "test_alert_sound",
+    "copy_to_clipboard",
+    "translate_content",
+    "confirm_close",
+    "set_profile_read_only",
+];
+const OBSERVER_PREFIXES: &[&str] = &[
+    "get_",
+    "list_",
+    "fetch_",
+    "search_",
+    "preview_",
+    "render_",
+    "summarize_",
+    "parse_",
+    "inspect_",
+    "compare_",
+    "evaluate_",
+    "validate_",
+    "scan_",
+];
+/// Reads that hand out a credential, refused for a read-only profile
+/// despite their prefix.
+const OBSERVER_REFUSED: &[&str] = &["get_profile_token"];
+/// Commands that add a profile or touch a credential, refused while the
+/// active profile is read-only whichever profile they name, so an observer
+/// cannot set up a writable profile of their own.
+const ACTIVE_PROFILE_REFUSED: &[&str] = &[
+    "upsert_profile",
+    "get_profile_token",
+    "set_profile_token",
+    "set_profile_credential",
+    "set_tunnel_password",
+    "set_proxy_password",
+];
+/// The envelope format of a read-only profile's unlock check.
+const UNLOCK_FORMAT: &str = "novaadapt-read-only-unlock";
+const MIN_UNLOCK_LEN: usize = 8;
+
+pub fn unlock_key(profile_id: &str) -> String {
+    format!("profile:{}:read_only_unlock", profile_id)
+}
+
+/// Refreshes the read-only profiles and origins from the current profiles.
+pub fn configure(profiles: &[Profile], active_profile: &str) {
+    let flagged = profiles.iter().filter(|p| p.read_only);
+    let state = ReadOnly {
+        origins: flagged
+            .clone()
+            .flat_map(|p| p.endpoints().filter_map(net::base_origin))
+            .collect(),
+        profiles: flagged.map(|p| p.id.clone()).collect(),
+        active: profiles
+            .iter()
+            .any(|p| p.read_only && p.id == active_profile),
+    };
+    *read_only().lock().unwrap_or_else(|e| e.into_inner()) = state;
+}
+
+/// The read-only profile or core a command's arguments name (`profile`,
+/// `profileId` or `baseUrl`), or the active profile when they name none.
+fn read_only_target(args: &Value) -> Option<String> {
+    if managed::policy().read_only {
+        return Some("This shell".to_string());
+    }
+    let state = read_only().lock().ok()?;
+    let profile = ["profile", "profileId"].iter().find_map(|key| {
+        args[*key]
+            .as_str()
+            .or_else(|| args[*key]["id"].as_str())
+            .filter(|id| !id.trim().is_empty())
+    });
+    if let Some(profile) = profile {
+        return state
+            .profiles
+            .contains(profile)
+            .then(|| profile.to_string());
+    }
+    if let Some(base_url) = args["baseUrl"].as_str().filter(|b| !b.trim().is_empty()) {
+        let flagged = net::base_origin(base_url).is_some_and(|o| state.origins.contains(&o));
+        return flagged.then(|| base_url.trim().to_string());
+    }
+    state.active.then(|| "The active profile".to_string())
+}
+
+/// Wraps the invoke handler so any command that could change something is
+/// refused for a read-only profile before it runs: approvals, plugin
+/// installs, imports, wipes and settings changes alike.
+pub fn observed<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command();
+        let args = match invoke.message.payload() {
+            InvokeBody::Json(args) => args,
+            InvokeBody::Raw(_) => &Value::Null,
+        };
+        if let Some(target) = refused_target(command, args) {
+            invoke.resolver.reject(read_only_error(&target));
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+/// The read-only profile or core `command` would change something for, if
+/// any.
+fn refused_target(command: &str, args: &Value) -> Option<String> {
+    if ACTIVE_PROFILE_REFUSED.contains(&command) {
+        if let Some(target) = read_only_target(&Value::Null) {
+            return Some(target);
+        }
+    }
+    let reads = !OBSERVER_REFUSED.contains(&command)
+        && (OBSERVER_COMMANDS.contains(&command)
+            || OBSERVER_PREFIXES.iter().any(|p| command.starts_with(p)));
+    if reads {
+        return None;
+    }
+    read_only_target(args)
+}
+
+/// Carries the read-only flag of every existing profile over from
+/// `before`, except `unlocking`'s, so only `set_profile_read_only` (or the
+/// managed policy) changes it.
+pub fn keep_read_only(before: &Settings, next: &mut Settings, unlocking: Option<&str>) {
+    for profile in &mut next.profiles {
+        if Some(profile.id.as_str()) == unlocking {
+            continue;
+        }
+        if let Some(previous) = before.profile(&profile.id) {
+            profile.read_only = previous.read_only;
+        }
+    }
+}
+
+/// Refuses local actions (plan execution, terminals, budget changes) for a
+/// read-only profile.
+pub fn ensure_profile_writable(profile: &Profile) -> Result<(), String> {
+    if profile.read_only {
+        return Err(read_only_error(&profile.id));
+    }
+    Ok(())
+}
+
+/// Like `ensure_profile_writable`, for commands that only know the core URL.
+pub fn ensure_writable(base_url: &str) -> Result<(), String> {
+    let flagged = managed::policy().read_only
+        || net::base_origin(base_url).is_some_and(|origin| {
+            read_only()
+                .lock()
+                .map(|state| state.origins.contains(&origin))
+                .unwrap_or(true)
+        });
+    if flagged {
+        return Err(read_only_error(base_url.trim()));
+    }
+    Ok(())
+}
+
+fn read_only() -> &'static Mutex<ReadOnly> {
+    READ_ONLY.get_or_init(|| Mutex::new(ReadOnly::default()))
+}
+
+fn read_only_error(target: &str) -> String {
+    ErrorCode::ForbiddenLocal.with(format!(
+        "{} is in read-only mode; actions and configuration changes are disabled",
+        target
+    ))
+}
+
+/// The scope a core route needs, mirroring the bridge's route table. Every
+/// method that can change something (`PUT`, `PATCH`, `DELETE` as well as
+/// `POST`) is held to the table, so none skips role enforcement; routes it
+/// does not name need `run`.
+fn required_scope(method: &Method, path: &str) -> &'static str {
+    let path = path.split('?').next().unwrap_or_default();
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || (*method == Method::POST && READ_ONLY_POSTS.contains(&path))
+    {
+        return "read";
+    }
+    let plan_action = |suffix: &str| path.starts_with("/plans/") && path.ends_with(suffix);
+    match path {
+        "/plans" => "plan",
+        "/undo" => "undo",
+        _ if plan_action("/approve")
+            || plan_action("/approve_async")
+            || plan_action("/retry_failed")
+            || plan_action("/retry_failed_async") =>
+        {
+            "approve"
+        }
+        _ if plan_action("/reject") => "reject",
+        _ if plan_action("/retry") && path.contains("/steps/") => "approve",
+        _ if plan_action("/abort") => "cancel",
+        _ if plan_action("/revise") => "plan",
+        _ if plan_action("/undo") => "undo",
+        _ if path.starts_with("/jobs/") && path.ends_with("/cancel") => "cancel",
+        _ => "run",
+    }
+}
+
+/// Refuses a request locally when it would modify a read-only profile's core,
+/// or when the operator's role does not include the scope its route needs.
+/// Tokens without recognisable scopes (static core tokens, third-party JWTs)
+/// are left for the core to judge.
+pub fn check(
+    method: &Method,
+    base_url: &str,
+    path: &str,
+    token: Option<&str>,
+) -> Result<(), String> {
+    let route = path.split('?').next().unwrap_or_default();
+    let required = required_scope(method, path);
+    if required != "read" {
+        ensure_writable(base_url)?;
+    }
+    let Some(granted) = granted(base_url, token) else {
+        return Ok(());
+    };
+    if granted.contains("admin") || granted.contains(required) {
+        return Ok(());
+    }
+    Err(ErrorCode::ForbiddenLocal.with(format!(
+        "{} {} needs the `{}` permission",
+        method, route, required
+    )))
+}
+
+fn granted(base_url: &str, token: Option<&str>) -> Option<BTreeSet<String>> {
+    let cached = cache()
+        .lock()
+        .ok()
+        .and_then(|granted| granted.get(&cache_key(base_url, token)).cloned());
+    cached.or_else(|| token_scopes(token?))
+}
+
+/// Bridge scopes carried in the token's claims, if it is a session token.
+fn token_scopes(token: &str) -> Option<BTreeSet<String>> {
+    let claims = jwt::decode_claims(token)?;
+    known_scopes(jwt::scopes(&claims))
+}
+
+fn known_scopes(scopes: Vec<String>) -> Option<BTreeSet<String>> {
+    let known: BTreeSet<String> = scopes
+        .into_iter()
+        .map(|s| s.trim().to_ascii_lowercase())
+        .filter(|s| SCOPES.contains(&s.as_str()))
+        .collect();
+    (!known.is_empty()).then_some(known)
+}
+
+fn cache() -> &'static Mutex<HashMap<String, BTreeSet<String>>> {
+    GRANTED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn cache_key(base_url: &str, token: Option<&str>) -> String {
+    let digest = Sha256::digest(token.unwrap_or_default().trim().as_bytes());
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}#{}", base_url.trim().trim_end_matches('/'), hex)
+}
+
+/// Returns the operator's role and scopes for a profile, from the core's
+/// `/auth/permissions` when it has one and from the token claims otherwise,
+/// and primes the cache that `check` enforces against.
+#[tauri::command]
+pub async fn get_permissions(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    refresh(&store.connection(profile.as_deref())?).await
+}
+
+/// `get_permissions` for a connection: asks the core again and replaces
+/// the cached scopes `check` enforces.
+pub async fn refresh(conn: &Connection) -> Result<Value, String> {
+    let key = cache_key(&conn.base_url, conn.token.as_deref());
+    let fetched = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/auth/permissions",
+        conn.token.clone(),
+        None,
+    )
+    .await;
+    let (source, role, scopes) = match fetched {
+        Ok(body) => {
+            let listed = jwt::scopes(&body);
+            let scopes = known_scopes(listed);
+            if let (Ok(mut granted), Some(scopes)) = (cache().lock(), scopes.clone()) {
+                granted.insert(key, scopes);
+            }
+            ("core", body["role"].clone(), scopes)
+        }
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+            if let Ok(mut granted) = cache().lock() {
+                granted.remove(&key);
+            }
+            let claims = conn.token.as_deref().and_then(jwt::decode_claims);
+            let role = claims
+                .as_ref()
+                .map(|c| c["role"].clone())
+                .unwrap_or(Value::Null);
+            ("token", role, conn.token.as_deref().and_then(token_scopes))
+        }
+        Err(err) => return Err(err),
+    };
+    let restricted = scopes.is_some();
+    let allows = |scope: &str| {
+        scopes
+            .as_ref()
+            .is_none_or(|s| s.contains("admin") || s.contains(scope))
+    };
+    let actions: serde_json::Map<String, Value> = ACTIONS
+        .iter()
+        .map(|action| (action.to_string(), json!(allows(action))))
+        .collect();
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "source": if restricted { source } else { "unrestricted" },
+        "role": role,
+        "scopes": scopes,
+        "actions": actions,
+    }))
+}
+
+/// Refuses `passphrase` unless it opens `check`, the unlock check stored
+/// for `profile_id`.
+fn verify_unlock(profile_id: &str, check: &str, passphrase: &str) -> Result<(), String> {
+    let check: Value =
+        serde_json::from_str(check).map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let opened = crypto::open_with_passphrase(passphrase, UNLOCK_FORMAT, &check)
+        .map_err(|_| ErrorCode::ForbiddenLocal.with("Wrong unlock passphrase"))?;
+    if opened != profile_id.as_bytes() {
+        return Err(ErrorCode::ForbiddenLocal.with("Wrong unlock passphrase"));
+    }
+    Ok(())
+}
+
+/// Turns a profile's read-only mode on or off. Turning it on sets an
+/// unlock passphrase that turning it off asks for, so an observer handed
+/// the shell cannot leave it; replacing a passphrase already set takes the
+/// old one as `current_passphrase`. A profile made read-only without one
+/// (in an older version, or by import) can only be unlocked from another,
+/// writable profile. The managed policy's `read_only` cannot be lifted here.
+#[tauri::command]
+pub fn set_profile_read_only(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile_id: String,
+    read_only: bool,
+    passphrase: Option<String>,
+    current_passphrase: Option<String>,
+) -> Result<Value, String> {
+    let profile = store
+        .snapshot()
+        .profile(&profile_id)
+        .cloned()
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    let passphrase = passphrase.unwrap_or_default();
+    let key = unlock_key(&profile_id);
+    let stored = secrets::get(&key)?;
+    if read_only {
+        if let Some(check) = &stored {
+            verify_unlock(
+                &profile_id,
+                check,
+                current_passphrase.as_deref().unwrap_or_default(),
+            )?;
+        }
+        if passphrase.chars().count() < MIN_UNLOCK_LEN {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "An unlock passphrase of at least {} characters is required",
+                MIN_UNLOCK_LEN
+            )));
+        }
+        let check =
+            crypto::seal_with_passphrase(&passphrase, UNLOCK_FORMAT, profile_id.as_bytes())?;
+        secrets::set(&key, &check.to_string())?;
+    } else if profile.read_only {
+        if managed::policy().read_only {
+            return Err(ErrorCode::ForbiddenLocal.with(format!(
+                "Profile {}: read-only mode is set by your administrator",
+                profile_id
+            )));
+        }
+        match stored {
+            Some(check) => verify_unlock(&profile_id, &check, &passphrase)?,
+            None if read_only_target(&Value::Null).is_some() => {
+                return Err(ErrorCode::ForbiddenLocal.with(format!(
+                    "Profile {} has no unlock passphrase; unlock it from a writable profile",
+                    profile_id
+                )));
+            }
+            None => {}
+        }
+    }
+    let settings = store.update_read_only(&app, &profile_id, |settings| {
+        let profile = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| ErrorCode::NotFound.with("Profile disappeared"))?;
+        profile.read_only = read_only;
+        Ok(())
+    })?;
+    if !read_only {
+        secrets::delete(&key)?;
+    }
+    db::record_audit(
+        &*db.lock()?,
+        &profile_id,
+        "settings",
+        if read_only {
+            "read_only_on"
+        } else {
+            "read_only_off"
+        },
+        &profile_id,
+        &json!({}),
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    Ok(json!({
+        "profile_id": profile_id,
+        "read_only": settings.profile(&profile_id).is_some_and(|p| p.read_only),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_need_read_and_writes_follow_the_route_table() {
+        assert_eq!(required_scope(&Method::GET, "/plans/p-1/approve"), "read");
+        assert_eq!(required_scope(&Method::HEAD, "/plans"), "read");
+        assert_eq!(required_scope(&Method::POST, "/memory/recall?q=x"), "read");
+        assert_eq!(required_scope(&Method::POST, "/plans"), "plan");
+        assert_eq!(
+            required_scope(&Method::POST, "/plans/p-1/approve"),
+            "approve"
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/plans/p-1/retry_failed_async"),
+            "approve"
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/plans/p-1/steps/2/retry"),
+            "approve"
+        );
+        assert_eq!(required_scope(&Method::POST, "/plans/p-1/reject"), "reject");
+        assert_eq!(required_scope(&Method::POST, "/jobs/j-1/cancel"), "cancel");
+        assert_eq!(required_scope(&Method::POST, "/run"), "run");
+    }
+
+    #[test]
+    fn put_patch_and_delete_do_not_skip_the_table() {
+        assert_eq!(
+            required_scope(&Method::PUT, "/plans/p-1/approve"),
+            "approve"
+        );
+        assert_eq!(required_scope(&Method::PATCH, "/plans"), "plan");
+        assert_eq!(required_scope(&Method::DELETE, "/plans/p-1"), "run");
+        assert_eq!(required_scope(&Method::DELETE, "/memory/recall"), "run");
+    }
+
+    #[test]
+    fn read_only_target_follows_the_named_profile_then_the_active_one() {
+        let flagged = Profile {
+            id: "ro".to_string(),
+            base_url: "https://ro.example".to_string(),
+            read_only: true,
+            ..Profile::default()
+        };
+        let open = Profile {
+            id: "rw".to_string(),
+            base_url: "https://rw.example".to_string(),
+            ..Profile::default()
+        };
+        configure(&[flagged, open], "ro");
+        assert_eq!(
+            read_only_target(&json!({ "profile": "ro" })).as_deref(),
+            Some("ro")
+        );
+        assert_eq!(
+            read_only_target(&json!({ "profileId": { "id": "ro" } })).as_deref(),
+            Some("ro")
+        );
+        assert_eq!(read_only_target(&json!({ "profile": "rw" })), None);
+        assert!(read_only_target(&json!({ "baseUrl": "https://ro.example/api" })).is_some());
+        assert_eq!(
+            read_only_target(&json!({ "baseUrl": "https://rw.example" })),
+            None
+        );
+        assert!(read_only_target(&json!({})).is_some());
+        assert!(ensure_writable("https://ro.example").is_err());
+        assert!(ensure_writable("https://rw.example").is_ok());
+    }
+
+    #[test]
+    fn a_read_only_active_profile_cannot_reach_credentials_or_new_profiles() {
+        let flagged = Profile {
+            id: "ro".to_string(),
+            base_url: "https://ro.example".to_string(),
+            read_only: true,
+            ..Profile::default()
+        };
+        let open = Profile {
+            id: "rw".to_string(),
+            base_url: "https://rw.example".to_string(),
+            ..Profile::default()
+        };
+        configure(&[flagged, open], "ro");
+        assert_eq!(refused_target("get_settings", &json!({})), None);
+        assert_eq!(
+            refused_target("get_plan", &json!({ "profile": "ro" })),
+            None
+        );
+        assert!(refused_target("get_profile_token", &json!({ "profileId": "ro" })).is_some());
+        assert!(refused_target("get_profile_token", &json!({ "profileId": "rw" })).is_some());
+        let new_profile = json!({ "profile": { "id": "mine", "base_url": "https://x" } });
+        assert!(refused_target("upsert_profile", &new_profile).is_some());
+        for command in [
+            "set_profile_token",
+            "set_profile_credential",
+            "set_proxy_password",
+        ] {
+            assert!(
+                refused_target(command, &json!({ "profileId": "rw" })).is_some(),
+                "{}",
+                command
+            );
+        }
+        assert_eq!(
+            refused_target("approve_plan", &json!({ "profile": "rw" })),
+            None
+        );
+    }
+
+    #[test]
+    fn only_the_unlock_passphrase_opens_its_check() {
+        let check = crypto::seal_with_passphrase("correct horse", UNLOCK_FORMAT, b"ro")
+            .unwrap()
+            .to_string();
+        assert!(verify_unlock("ro", &check, "correct horse").is_ok());
+        assert!(verify_unlock("ro", &check, "wrong horse").is_err());
+        assert!(verify_unlock("ro", &check, "").is_err());
+        assert!(verify_unlock("rw", &check, "correct horse").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::a11y::{step_target, steps};
+use crate::errors::ErrorCode;
+use crate::plan_pdf::{as_text, first_text};
+use crate::resources;
+use crate::settings::SettingsStore;
+
+/// Steps aligned per plan; past this the tail is compared by position.
+const MAX_ALIGNED_STEPS: usize = 500;
+
+/// Plan fields compared as a whole, beside the steps.
+const PLAN_FIELDS: &[&str] = &[
+    "objective",
+    "title",
+    "strategy",
+    "risk",
+    "risk_level",
+    "description",
+    "summary",
+    "rationale",
+];
+
+/// Step fields that describe a run rather than the proposal.
+const RUN_FIELDS: &[&str] = &[
+    "id",
+    "status",
+    "result",
+    "output",
+    "error",
+    "started_at",
+    "finished_at",
+    "duration_ms",
+];
+
+fn step_kind(step: &Value) -> String {
+    match step {
+        Value::String(text) => text.clone(),
+        _ => first_text(step, &["type", "action", "kind", "name"]),
+    }
+}
+
+/// What a step does and to what: two steps with the same signature are the
+/// same step, possibly with other parameters.
+fn signature(step: &Value) -> (String, String) {
+    (
+        step_kind(step).to_ascii_lowercase(),
+        step_target(step).to_ascii_lowercase(),
+    )
+}
+
+/// The step's parameters as dotted paths to leaf values, without the
+/// fields a run fills in.
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, inner) in map {
+                if prefix.is_empty() && RUN_FIELDS.contains(&key.as_str()) {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, inner, out);
+            }
+        }
+        Value::Null => {}
+        other => {
+            let key = if prefix.is_empty() { "value" } else { prefix };
+            out.insert(key.to_string(), other.clone());
+        }
+    }
+}
+
+fn field_changes(a: &BTreeMap<String, Value>, b: &BTreeMap<String, Value>) -> Vec<Value> {
+    let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|k| a.get(*k) != b.get(*k))
+        .map(|k| json!({ "key": k, "from": a.get(k), "to": b.get(k) }))
+        .collect()
+}
+
+fn params(step: &Value) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    flatten("", step, &mut out);
+    out
+}
+
+/// Pairs steps of `a` and `b` by the longest common run of signatures, so
+/// an inserted step does not make every later step look changed.
+fn align(a: &[&Value], b: &[&Value]) -> Vec<(Option<usize>, Option<usize>)> {
+    let (n, m) = (
+        a.len().min(MAX_ALIGNED_STEPS),
+        b.len().min(MAX_ALIGNED_STEPS),
+    );
+    let sa: Vec<_> = a[..n].iter().map(|s| signature(s)).collect();
+    let sb: Vec<_> = b[..m].iter().map(|s| signature(s)).collect();
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if sa[i] == sb[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if sa[i] == sb[j] {
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            pairs.push((Some(i), None));
+            i += 1;
+        } else {
+            pairs.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    pairs.extend((i..n).map(|i| (Some(i), None)));
+    pairs.extend((j..m).map(|j| (None, Some(j))));
+    // Steps past the alignment limit are paired by position.
+    for k in n.min(m).max(MAX_ALIGNED_STEPS)..a.len().max(b.len()) {
+        pairs.push(((k < a.len()).then_some(k), (k < b.len()).then_some(k)));
+    }
+    pairs
+}
+
+fn step_entry(change: &str, index_a: Option<usize>, index_b: Option<usize>, step: &Value) -> Value {
+    json!({
+        "change": change,
+        "step_a": index_a.map(|i| i + 1),
+        "step_b": index_b.map(|i| i + 1),
+        "kind": step_kind(step),
+        "target": step_target(step),
+    })
+}
+
+/// How plan `b` differs from plan `a`.
+fn compare(a: &Value, b: &Value) -> Value {
+    let steps_a = steps(a);
+    let steps_b = steps(b);
+    let mut counts = BTreeMap::from([
+        ("added", 0),
+        ("removed", 0),
+        ("changed", 0),
+        ("unchanged", 0),
+    ]);
+    let mut step_changes = Vec::new();
+    for pair in align(&steps_a, &steps_b) {
+        let entry = match pair {
+            (Some(i), Some(j)) => {
+                let changes = field_changes(&params(steps_a[i]), &params(steps_b[j]));
+                let change = if changes.is_empty() {
+                    "unchanged"
+                } else {
+                    "changed"
+                };
+                let mut entry = step_entry(change, Some(i), Some(j), steps_b[j]);
+                if i != j {
+                    entry["moved"] = json!(true);
+                }
+                entry["params"] = json!(changes);
+                entry
+            }
+            (Some(i), None) => step_entry("removed", Some(i), None, steps_a[i]),
+            (None, Some(j)) => step_entry("added", None, Some(j), steps_b[j]),
+            (None, None) => continue,
+        };
+        if let Some(count) = entry["change"].as_str().and_then(|c| counts.get_mut(c)) {
+            *count += 1;
+        }
+        step_changes.push(entry);
+    }
+
+    let targets = |steps: &[&Value]| -> Vec<String> {
+        let mut out: Vec<String> = steps
+            .iter()
+            .map(|s| step_target(s))
+            .filter(|t| !t.is_empty())
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    };
+    let (targets_a, targets_b) = (targets(&steps_a), targets(&steps_b));
+
+    let top = |plan: &Value| -> BTreeMap<String, Value> {
+        PLAN_FIELDS
+            .iter()
+            .filter(|k| !plan[**k].is_null())
+            .map(|k| (k.to_string(), plan[*k].clone()))
+            .collect()
+    };
+
+    json!({
+        "same_steps": counts["added"] + counts["removed"] + counts["changed"] == 0,
+        "summary": counts,
+        "fields": field_changes(&top(a), &top(b)),
+        "steps": step_changes,
+        "targets": {
+            "added": targets_b.iter().filter(|t| !targets_a.contains(t)).collect::<Vec<_>>(),
+            "removed": targets_a.iter().filter(|t| !targets_b.contains(t)).collect::<Vec<_>>(),
+        },
+    })
+}
+
+fn describe(id: &str, plan: &Value) -> Value {
+    json!({
+        "id": id,
+        "status": as_text(&plan["status"]),
+        "objective": first_text(plan, &["objective", "title"]),
+        "steps": steps(plan).len(),
+    })
+}
+
+/// Compares two plans step by step, typically a rejected plan (`id_a`)
+/// with the one an agent generated to replace it (`id_b`). Steps are
+/// paired by what they do and to what, then each pair's parameters are
+/// compared; `steps` lists every pair as `added`, `removed`, `changed`
+/// (with the differing `params`) or `unchanged`, alongside the targets
+/// only one plan touches and the plan-level `fields` that differ.
+#[tauri::command]
+pub async fn compare_plans(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    id_a: String,
+    id_b: String,
+) -> Result<Value, String> {
+    let (id_a, id_b) = (id_a.trim().to_string(), id_b.trim().to_string());
+    if id_a.is_empty() || id_b.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Two plan ids are required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let mut plans = Vec::with_capacity(2);
+    for id in [&id_a, &id_b] {
+        plans.push(
+            crate::request_json(
+                Method::GET,
+                &conn.base_url,
+                &format!("/plans/{}", id),
+                conn.token.clone(),
+                None,
+            )
+            .await?,
+        );
+    }
+    let mut comparison = resources::measure(resources::DIFF, || compare(&plans[0], &plans[1]));
+    comparison["a"] = describe(&id_a, &plans[0]);
+    comparison["b"] = describe(&id_b, &plans[1]);
+    Ok(comparison)
+}
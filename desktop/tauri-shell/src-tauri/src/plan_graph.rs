@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::handover;
+use crate::settings::SettingsStore;
+
+/// Fields a plan names its prerequisite plans in, by id or as objects with
+/// an `id` or `plan_id`.
+const PREREQUISITE_FIELDS: &[&str] = &["depends_on", "prerequisites", "requires"];
+/// Fields a plan lists the resources it touches in, as names or objects
+/// with an `id`, `name` or `uri`.
+const RESOURCE_FIELDS: &[&str] = &["resources", "shared_resources"];
+/// A prerequisite in one of these will never be met.
+const BLOCKING: &[&str] = &[
+    "rejected",
+    "failed",
+    "error",
+    "errored",
+    "aborted",
+    "cancelled",
+    "canceled",
+    "expired",
+];
+const DONE: &[&str] = &["approved", "executed", "completed", "succeeded"];
+/// Statuses of another plan that make sharing a resource with it worth a
+/// warning.
+const ACTIVE: &[&str] = &["pending", "approved", "executing", "running"];
+/// Plans gathered around the root, either way, before the graph is cut.
+const MAX_NODES: usize = 500;
+
+fn ids(value: &Value, keys: &[&str]) -> Vec<String> {
+    let one = |item: &Value| match item {
+        Value::String(id) => Some(id.trim().to_string()),
+        Value::Object(_) => keys
+            .iter()
+            .find_map(|k| item[*k].as_str())
+            .map(|id| id.trim().to_string()),
+        _ => None,
+    };
+    let found: Vec<String> = match value {
+        Value::Array(items) => items.iter().filter_map(one).collect(),
+        Value::Null => Vec::new(),
+        other => one(other).into_iter().collect(),
+    };
+    found.into_iter().filter(|id| !id.is_empty()).collect()
+}
+
+fn prerequisites(plan: &Value) -> BTreeSet<String> {
+    PREREQUISITE_FIELDS
+        .iter()
+        .flat_map(|field| ids(&plan[*field], &["id", "plan_id"]))
+        .collect()
+}
+
+fn resources(plan: &Value) -> BTreeSet<String> {
+    RESOURCE_FIELDS
+        .iter()
+        .flat_map(|field| ids(&plan[*field], &["id", "name", "uri"]))
+        .collect()
+}
+
+fn status(plan: Option<&Value>) -> String {
+    plan.and_then(|p| p["status"].as_str())
+        .unwrap_or("unknown")
+        .to_ascii_lowercase()
+}
+
+/// The ids reachable from `root` along `next`, nearest first, with the
+/// path each was first reached by.
+fn walk<'a>(
+    root: &'a str,
+    next: impl Fn(&str) -> Vec<&'a str>,
+) -> (Vec<&'a str>, HashMap<&'a str, Vec<&'a str>>, bool) {
+    let mut order = Vec::new();
+    let mut paths: HashMap<&str, Vec<&str>> = HashMap::from([(root, vec![root])]);
+    let mut queue = VecDeque::from([root]);
+    let mut truncated = false;
+    while let Some(id) = queue.pop_front() {
+        for found in next(id) {
+            if paths.contains_key(found) {
+                continue;
+            }
+            if paths.len() >= MAX_NODES {
+                truncated = true;
+                break;
+            }
+            let mut path = paths[id].clone();
+            path.push(found);
+            paths.insert(found, path);
+            order.push(found);
+            queue.push_back(found);
+        }
+    }
+    (order, paths, truncated)
+}
+
+/// A cycle through `root` along prerequisites, if there is one.
+fn cycle<'a>(root: &'a str, edges: &'a HashMap<String, BTreeSet<String>>) -> Option<Vec<&'a str>> {
+    let (_, paths, _) = walk(root, |id| {
+        edges
+            .get(id)
+            .map(|next| next.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    });
+    paths
+        .iter()
+        .filter(|(id, _)| **id != root)
+        .filter(|(id, _)| edges.get(**id).is_some_and(|next| next.contains(root)))
+        .map(|(_, path)| {
+            let mut path = path.clone();
+            path.push(root);
+            path
+        })
+        .min_by_key(Vec::len)
+}
+
+/// The dependency graph around `root_id` across the profile's cached plans:
+/// the prerequisites it waits on, transitively, the plans waiting on it, and
+/// the other active plans sharing a resource with it. A prerequisite that
+/// was rejected or failed, one not in the cache, one still undecided, a
+/// shared resource and a dependency cycle each come back as a warning, so
+/// the window can say so before the plan is approved.
+#[tauri::command]
+pub fn get_plan_graph(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    root_id: String,
+) -> Result<Value, String> {
+    let root_id = root_id.trim().to_string();
+    if root_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let cached = handover::cached(&*db.lock()?, &conn.profile_id, "plan")
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let plans: HashMap<String, Value> = cached
+        .into_iter()
+        .filter_map(|plan| Some((plan["id"].as_str()?.to_string(), plan)))
+        .collect();
+    if !plans.contains_key(&root_id) {
+        return Err(ErrorCode::NotFound.with(format!(
+            "Plan {} is not in the local cache; sync and try again",
+            root_id
+        )));
+    }
+
+    let requires: HashMap<String, BTreeSet<String>> = plans
+        .iter()
+        .map(|(id, plan)| (id.clone(), prerequisites(plan)))
+        .collect();
+    let mut required_by: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (id, wanted) in &requires {
+        for prerequisite in wanted {
+            required_by
+                .entry(prerequisite.as_str())
+                .or_default()
+                .push(id.as_str());
+        }
+    }
+    let (upstream, upstream_paths, upstream_cut) = walk(&root_id, |id| {
+        requires
+            .get(id)
+            .map(|next| next.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    });
+    let (downstream, _, downstream_cut) = walk(&root_id, |id| {
+        let mut next = required_by.get(id).cloned().unwrap_or_default();
+        next.sort_unstable();
+        next
+    });
+
+    let root_resources = resources(&plans[&root_id]);
+    let mut sharing: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (id, plan) in &plans {
+        if *id == root_id || !ACTIVE.contains(&status(Some(plan)).as_str()) {
+            continue;
+        }
+        for resource in resources(plan).intersection(&root_resources) {
+            if let Some(shared) = root_resources.get(resource) {
+                sharing
+                    .entry(shared.as_str())
+                    .or_default()
+                    .push(id.as_str());
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    for id in &upstream {
+        let plan = plans.get(*id);
+        let state = status(plan);
+        let path = &upstream_paths[id];
+        let code = if plan.is_none() {
+            "prerequisite_unknown"
+        } else if BLOCKING.contains(&state.as_str()) {
+            "prerequisite_rejected"
+        } else if !DONE.contains(&state.as_str()) {
+            "prerequisite_pending"
+        } else {
+            continue;
+        };
+        warnings.push(json!({
+            "code": code,
+            "plan_id": id,
+            "status": state,
+            "path": path,
+        }));
+    }
+    for (resource, holders) in &mut sharing {
+        holders.sort_unstable();
+        warnings.push(json!({
+            "code": "resource_shared",
+            "resource": resource,
+            "plan_ids": holders,
+        }));
+    }
+    let cycle = cycle(&root_id, &requires);
+    if let Some(cycle) = &cycle {
+        warnings.push(json!({ "code": "dependency_cycle", "path": cycle }));
+    }
+
+    let mut members: BTreeSet<&str> = upstream.iter().chain(&downstream).copied().collect();
+    members.insert(&root_id);
+    for holders in sharing.values() {
+        members.extend(holders.iter().copied());
+    }
+    let nodes: Vec<Value> = members
+        .iter()
+        .map(|id| {
+            let plan = plans.get(*id);
+            json!({
+                "id": id,
+                "objective": plan.map(|p| p["objective"].clone()),
+                "status": status(plan),
+                "cached": plan.is_some(),
+                "root": *id == root_id,
+                "prerequisite": upstream.contains(id),
+                "dependent": downstream.contains(id),
+            })
+        })
+        .collect();
+    let mut edges: Vec<Value> = Vec::new();
+    for id in &members {
+        for prerequisite in requires.get(*id).into_iter().flatten() {
+            if members.contains(prerequisite.as_str()) {
+                edges.push(json!({ "from": prerequisite, "to": id, "kind": "prerequisite" }));
+            }
+        }
+    }
+    for (resource, holders) in &sharing {
+        for holder in holders {
+            edges.push(json!({
+                "from": holder,
+                "to": root_id,
+                "kind": "resource",
+                "resource": resource,
+            }));
+        }
+    }
+    let blocked = warnings
+        .iter()
+        .any(|w| w["code"] == "prerequisite_rejected");
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "root_id": root_id,
+        "nodes": nodes,
+        "edges": edges,
+        "warnings": warnings,
+        "blocked": blocked,
+        "acyclic": cycle.is_none(),
+        "truncated": upstream_cut || downstream_cut,
+    }))
+}
@@ -0,0 +1,588 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::{OutputOverflow, PlanOutputSettings, SettingsStore};
+use crate::{ansi, bandwidth, environment, live, sanitize};
+
+const EVENT: &str = "plan:output";
+/// Transcript kept per plan; output past it is still relayed live.
+const MAX_TRANSCRIPT_BYTES: usize = 4 * 1024 * 1024;
+/// Consecutive failed connects before a watch gives up.
+const MAX_FAILURES: u32 = 8;
+const MIN_EVENT_BYTES: usize = 1_024;
+const DEFAULT_PAGE: u32 = 1_000;
+const MAX_PAGE: u32 = 10_000;
+
+/// The plans whose execution output is being relayed, by profile and plan.
+#[derive(Default)]
+pub struct PlanOutputStreams {
+    running: Mutex<HashMap<(String, String), JoinHandle<()>>>,
+}
+
+pub fn validate(settings: &PlanOutputSettings) -> Result<(), String> {
+    if settings.max_event_bytes < MIN_EVENT_BYTES
+        || settings.buffer_bytes < settings.max_event_bytes
+    {
+        return Err(format!(
+            "Plan output events must be at least {} bytes and fit in the buffer",
+            MIN_EVENT_BYTES
+        ));
+    }
+    if !(10..=5_000).contains(&settings.flush_ms) {
+        return Err("Plan output flush interval must be between 10 and 5000 ms".to_string());
+    }
+    Ok(())
+}
+
+fn plan_path(plan_id: &str) -> Result<String, String> {
+    let plan_id = plan_id.trim();
+    if plan_id.is_empty() || plan_id.contains(['/', '?', '#']) {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    Ok(format!("/plans/{}/stream", plan_id))
+}
+
+struct Chunk {
+    seq: i64,
+    step: usize,
+    /// `stdout`, `stderr`, or `status` for a step's status changes.
+    stream: String,
+    text: String,
+}
+
+/// What has been written down of one plan's output, so a watch started
+/// after a reload carries on where the transcript ends.
+struct Transcript {
+    profile_id: String,
+    plan_id: String,
+    seq: i64,
+    bytes: usize,
+    /// Bytes of each step's stream already transcribed.
+    seen: HashMap<(usize, String), usize>,
+    statuses: HashMap<usize, String>,
+}
+
+impl Transcript {
+    fn load(conn: &Connection, profile_id: &str, plan_id: &str) -> rusqlite::Result<Self> {
+        let mut transcript = Self {
+            profile_id: profile_id.to_string(),
+            plan_id: plan_id.to_string(),
+            seq: 0,
+            bytes: 0,
+            seen: HashMap::new(),
+            statuses: HashMap::new(),
+        };
+        let mut stmt = conn.prepare(
+            "SELECT step, stream, MAX(seq), SUM(LENGTH(CAST(text AS BLOB))), text FROM plan_output
+             WHERE profile_id = ?1 AND plan_id = ?2 GROUP BY step, stream",
+        )?;
+        let rows = stmt.query_map(params![profile_id, plan_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        for row in rows {
+            let (step, stream, seq, bytes, text) = row?;
+            transcript.seq = transcript.seq.max(seq);
+            transcript.bytes += bytes as usize;
+            // A step's latest status row holds its current status.
+            if stream == "status" {
+                transcript.statuses.insert(step as usize, text);
+            } else {
+                transcript
+                    .seen
+                    .insert((step as usize, stream), bytes as usize);
+            }
+        }
+        Ok(transcript)
+    }
+
+    /// The output `plan`'s execution results added since last seen, written
+    /// to the transcript. Each step's output is taken from its `stdout` and
+    /// `stderr` data when the executor reported them, else from `output`.
+    fn observe(&mut self, conn: &mut Connection, plan: &Value) -> rusqlite::Result<Vec<Chunk>> {
+        let mut chunks = Vec::new();
+        let results = plan["execution_results"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for (index, result) in results.iter().enumerate() {
+            let step = index + 1;
+            let status = result["status"].as_str().unwrap_or_default();
+            let stdout = result["data"]["stdout"]
+                .as_str()
+                .or_else(|| result["output"].as_str())
+                .unwrap_or_default();
+            let stderr = result["data"]["stderr"].as_str().unwrap_or_default();
+            if !status.is_empty() && self.statuses.get(&step).map(String::as_str) != Some(status) {
+                self.statuses.insert(step, status.to_string());
+                chunks.push(self.chunk(step, "status", status.to_string()));
+            }
+            for (stream, text) in [("stdout", stdout), ("stderr", stderr)] {
+                let seen = self.seen.entry((step, stream.to_string())).or_default();
+                if text.len() <= *seen {
+                    continue;
+                }
+                let mut from = *seen;
+                while !text.is_char_boundary(from) {
+                    from += 1;
+                }
+                *seen = text.len();
+                let text = text[from..].to_string();
+                chunks.push(self.chunk(step, stream, text));
+            }
+        }
+        if chunks.is_empty() {
+            return Ok(chunks);
+        }
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO plan_output (profile_id, plan_id, seq, step, stream, text, at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let at = Utc::now().to_rfc3339();
+            for chunk in &chunks {
+                if self.bytes >= MAX_TRANSCRIPT_BYTES {
+                    break;
+                }
+                self.bytes += chunk.text.len();
+                stmt.execute(params![
+                    self.profile_id,
+                    self.plan_id,
+                    chunk.seq,
+                    chunk.step as i64,
+                    chunk.stream,
+                    chunk.text,
+                    at
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(chunks)
+    }
+
+    fn chunk(&mut self, step: usize, stream: &str, text: String) -> Chunk {
+        self.seq += 1;
+        Chunk {
+            seq: self.seq,
+            step,
+            stream: stream.to_string(),
+            text,
+        }
+    }
+}
+
+/// Output waiting for the window, bounded by `buffer_bytes`.
+struct Queue {
+    settings: PlanOutputSettings,
+    chunks: VecDeque<Chunk>,
+    bytes: usize,
+    dropped_bytes: usize,
+    end: Option<Value>,
+    /// Escape sequence state of each step's stream.
+    parsers: HashMap<(usize, String), ansi::Parser>,
+}
+
+impl Queue {
+    fn push(&mut self, mut chunk: Chunk) {
+        let limit = self.settings.buffer_bytes;
+        if self.bytes + chunk.text.len() > limit {
+            match self.settings.overflow {
+                OutputOverflow::DropNewest => {
+                    self.dropped_bytes += chunk.text.len();
+                    return;
+                }
+                OutputOverflow::DropOldest => {
+                    while self.bytes + chunk.text.len() > limit {
+                        let Some(old) = self.chunks.pop_front() else {
+                            break;
+                        };
+                        self.bytes -= old.text.len();
+                        self.dropped_bytes += old.text.len();
+                    }
+                    if chunk.text.len() > limit {
+                        let mut cut = chunk.text.len() - limit;
+                        while !chunk.text.is_char_boundary(cut) {
+                            cut += 1;
+                        }
+                        self.dropped_bytes += cut;
+                        chunk.text.drain(..cut);
+                    }
+                }
+            }
+        }
+        self.bytes += chunk.text.len();
+        self.chunks.push_back(chunk);
+    }
+
+    /// Everything waiting, as events: runs of one step's stream are joined
+    /// up to `max_event_bytes` and parsed into styled spans, status changes
+    /// are sent as they are.
+    fn drain(&mut self) -> Vec<Value> {
+        let max = self.settings.max_event_bytes;
+        let mut events = Vec::new();
+        if self.dropped_bytes > 0 {
+            events.push(
+                json!({ "kind": "dropped", "bytes": std::mem::take(&mut self.dropped_bytes) }),
+            );
+        }
+        let mut current: Option<(Chunk, i64)> = None;
+        for chunk in self.chunks.drain(..) {
+            if chunk.stream == "status" {
+                events.extend(current.take().map(output_event));
+                events.push(json!({
+                    "kind": "step",
+                    "step": chunk.step,
+                    "status": chunk.text,
+                    "seq": chunk.seq,
+                }));
+                continue;
+            }
+            if let Some((open, to_seq)) = &mut current {
+                if open.step == chunk.step
+                    && open.stream == chunk.stream
+                    && open.text.len() + chunk.text.len() <= max
+                {
+                    open.text.push_str(&chunk.text);
+                    *to_seq = chunk.seq;
+                    continue;
+                }
+            }
+            events.extend(current.take().map(output_event));
+            let Chunk {
+                seq,
+                step,
+                stream,
+                mut text,
+            } = chunk;
+            while text.len() > max {
+                let mut cut = max;
+                while !text.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                let rest = text.split_off(cut);
+                let head = Chunk {
+                    seq,
+                    step,
+                    stream: stream.clone(),
+                    text,
+                };
+                events.push(output_event((head, seq)));
+                text = rest;
+            }
+            current = Some((
+                Chunk {
+                    seq,
+                    step,
+                    stream,
+                    text,
+                },
+                seq,
+            ));
+        }
+        events.extend(current.take().map(output_event));
+        self.bytes = 0;
+        for event in events.iter_mut().filter(|e| e["kind"] == "output") {
+            let key = (
+                event["step"].as_u64().unwrap_or_default() as usize,
+                event["stream"].as_str().unwrap_or_default().to_string(),
+            );
+            let spans = self
+                .parsers
+                .entry(key)
+                .or_default()
+                .feed(event["text"].as_str().unwrap_or_default());
+            event["spans"] = json!(spans);
+        }
+        events
+    }
+}
+
+fn output_event((chunk, to_seq): (Chunk, i64)) -> Value {
+    json!({
+        "kind": "output",
+        "step": chunk.step,
+        "stream": chunk.stream,
+        "from_seq": chunk.seq,
+        "to_seq": to_seq,
+        "text": chunk.text,
+    })
+}
+
+/// Relays `plan_id`'s execution output from the core as `plan:output`
+/// events while it runs: `output` events carry new stdout/stderr text per
+/// step, `step` events a step's status, `dropped` how much the window
+/// missed while it could not keep up, and `end` the plan's final status.
+/// Everything is also written to a transcript, read back with
+/// `plan_output_transcript` after a reload, and the watch resumes where the
+/// transcript ends. Replaces any watch already running on the plan.
+#[tauri::command]
+pub fn watch_plan_output(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    streams: State<'_, PlanOutputStreams>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let path = plan_path(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let key = (conn.profile_id.clone(), plan_id.trim().to_string());
+    let mut running = streams
+        .running
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Plan output state poisoned"))?;
+    running.retain(|_, handle| !handle.inner().is_finished());
+    if let Some(previous) = running.remove(&key) {
+        previous.abort();
+    }
+    let (profile_id, plan) = key.clone();
+    let task_app = app.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        run(task_app, profile_id, plan, path).await;
+    });
+    running.insert(key, handle);
+    Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id.trim(), "started": true }))
+}
+
+#[tauri::command]
+pub fn stop_plan_output(
+    store: State<'_, SettingsStore>,
+    streams: State<'_, PlanOutputStreams>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let handle = streams
+        .running
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Plan output state poisoned"))?
+        .remove(&(conn.profile_id, plan_id.trim().to_string()));
+    if let Some(handle) = &handle {
+        handle.abort();
+    }
+    Ok(json!({ "stopped": handle.is_some() }))
+}
+
+async fn run(app: AppHandle, profile_id: String, plan_id: String, path: String) {
+    let settings = app.state::<SettingsStore>().snapshot().plan_output;
+    let db = app.state::<LocalDb>();
+    let loaded = db.lock().and_then(|conn| {
+        Transcript::load(&conn, &profile_id, &plan_id)
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))
+    });
+    let queue = Arc::new(Mutex::new(Queue {
+        settings: settings.clone(),
+        chunks: VecDeque::new(),
+        bytes: 0,
+        dropped_bytes: 0,
+        end: None,
+        parsers: HashMap::new(),
+    }));
+    let flusher = tauri::async_runtime::spawn(flush(
+        app.clone(),
+        queue.clone(),
+        profile_id.clone(),
+        plan_id.clone(),
+        Duration::from_millis(settings.flush_ms),
+    ));
+    let end = match loaded {
+        Ok(mut transcript) => {
+            let mut failures = 0u32;
+            loop {
+                match follow(&app, &path, &mut transcript, &queue, &mut failures).await {
+                    Ok(Some(end)) => break end,
+                    Ok(None) => {}
+                    Err(err) => {
+                        failures += 1;
+                        if failures >= MAX_FAILURES {
+                            break json!({ "error": err });
+                        }
+                        let streaming = app
+                            .state::<SettingsStore>()
+                            .snapshot()
+                            .profile(&profile_id)
+                            .map(|p| p.streaming.clone())
+                            .unwrap_or_default();
+                        tokio::time::sleep(live::backoff(&streaming, failures)).await;
+                    }
+                }
+            }
+        }
+        Err(err) => json!({ "error": err }),
+    };
+    if let Ok(mut queue) = queue.lock() {
+        queue.end = Some(end);
+    }
+    drop(queue);
+    let _ = flusher.await;
+}
+
+/// One stream of the plan's snapshots; `Some` with the outcome once the
+/// plan is done, `None` when the core recycled the stream.
+async fn follow(
+    app: &AppHandle,
+    path: &str,
+    transcript: &mut Transcript,
+    queue: &Mutex<Queue>,
+    failures: &mut u32,
+) -> Result<Option<Value>, String> {
+    let store = app.state::<SettingsStore>();
+    let conn = store.connection(Some(&transcript.profile_id))?;
+    let streaming = store
+        .snapshot()
+        .profile(&transcript.profile_id)
+        .map(|p| p.streaming.clone())
+        .unwrap_or_default();
+    let mut response = live::open(&conn, &streaming, path, &[("interval", "0.25".into())]).await?;
+    *failures = 0;
+    let stale = Duration::from_secs(streaming.stale_after_secs);
+    let mut frames = live::Frames::default();
+    loop {
+        let chunk = match tokio::time::timeout(stale, response.chunk()).await {
+            Err(_) | Ok(Ok(None)) => return Ok(None),
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Err(e)) => {
+                return Err(ErrorCode::CoreUnreachable.with(format!("Plan stream cut: {}", e)))
+            }
+        };
+        bandwidth::transferred(&conn.base_url, bandwidth::STREAMING, 0, chunk.len() as u64);
+        for (event, data) in frames.push(&chunk) {
+            let mut value: Value =
+                serde_json::from_str(&data).unwrap_or_else(|_| json!({ "raw": data }));
+            sanitize::scrub(&mut value);
+            match event.as_str() {
+                "plan" => {
+                    let chunks = {
+                        let db = app.state::<LocalDb>();
+                        let mut conn = db.lock()?;
+                        transcript
+                            .observe(&mut conn, &value)
+                            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?
+                    };
+                    let mut queue = queue
+                        .lock()
+                        .map_err(|_| ErrorCode::Internal.with("Plan output queue poisoned"))?;
+                    for chunk in chunks {
+                        queue.push(chunk);
+                    }
+                }
+                "end" => return Ok(Some(json!({ "status": value["status"] }))),
+                "error" => return Ok(Some(json!({ "error": value["error"] }))),
+                "timeout" => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Sends what is waiting every `interval`, until the watch ends or is
+/// stopped.
+async fn flush(
+    app: AppHandle,
+    queue: Arc<Mutex<Queue>>,
+    profile_id: String,
+    plan_id: String,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let (events, end) = match queue.lock() {
+            Ok(mut queue) => (queue.drain(), queue.end.take()),
+            Err(_) => return,
+        };
+        for mut event in events.into_iter().chain(end.clone().map(|mut end| {
+            end["kind"] = json!("end");
+            end
+        })) {
+            event["profile_id"] = json!(profile_id);
+            event["plan_id"] = json!(plan_id);
+            let _ = environment::emit(&app, EVENT, event);
+        }
+        if end.is_some() || Arc::strong_count(&queue) == 1 {
+            return;
+        }
+    }
+}
+
+/// The transcript of `plan_id`'s output after `after_seq`, oldest first,
+/// at most `limit` chunks (default 1000), with output parsed into styled
+/// spans.
+#[tauri::command]
+pub fn plan_output_transcript(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    after_seq: Option<i64>,
+    limit: Option<u32>,
+) -> Result<Value, String> {
+    let conn_info = store.connection(profile.as_deref())?;
+    let limit = limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    let conn = db.lock()?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let mut stmt = conn
+        .prepare(
+            "SELECT seq, step, stream, text, at FROM plan_output
+             WHERE profile_id = ?1 AND plan_id = ?2 AND seq > ?3
+             ORDER BY seq LIMIT ?4",
+        )
+        .map_err(storage)?;
+    let mut chunks = stmt
+        .query_map(
+            params![
+                conn_info.profile_id,
+                plan_id.trim(),
+                after_seq.unwrap_or(0),
+                limit + 1
+            ],
+            |row| {
+                Ok(json!({
+                    "seq": row.get::<_, i64>(0)?,
+                    "step": row.get::<_, i64>(1)?,
+                    "stream": row.get::<_, String>(2)?,
+                    "text": row.get::<_, String>(3)?,
+                    "at": row.get::<_, String>(4)?,
+                }))
+            },
+        )
+        .map_err(storage)?
+        .collect::<rusqlite::Result<Vec<Value>>>()
+        .map_err(storage)?;
+    let more = chunks.len() > limit as usize;
+    chunks.truncate(limit as usize);
+    // Escape state is carried within the page; a page starting inside a
+    // colored run starts over from the default style.
+    let mut parsers: HashMap<(i64, String), ansi::Parser> = HashMap::new();
+    for chunk in chunks.iter_mut().filter(|c| c["stream"] != "status") {
+        let key = (
+            chunk["step"].as_i64().unwrap_or_default(),
+            chunk["stream"].as_str().unwrap_or_default().to_string(),
+        );
+        let spans = parsers
+            .entry(key)
+            .or_default()
+            .feed(chunk["text"].as_str().unwrap_or_default());
+        chunk["spans"] = json!(spans);
+    }
+    Ok(json!({
+        "profile_id": conn_info.profile_id,
+        "plan_id": plan_id.trim(),
+        "last_seq": chunks.last().map(|c| c["seq"].clone()),
+        "more": more,
+        "chunks": chunks,
+    }))
+}
@@ -0,0 +1,572 @@
+use std::fs;
+
+use chrono::Utc;
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::formatting::Formatter;
+use crate::residency;
+use crate::settings::SettingsStore;
+
+// A4 in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const FOOTER_Y: f32 = 32.0;
+const BODY_SIZE: f32 = 10.0;
+const MONO_SIZE: f32 = 8.5;
+const MAX_DIFF_LINES: usize = 2_000;
+const MAX_AUDIT_ROWS: usize = 200;
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+/// Helvetica advance widths for ASCII 32..=126, in 1/1000 em (from the
+/// standard AFM metrics).
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    let units: f32 = text
+        .chars()
+        .map(|ch| match font {
+            Font::Mono => 600.0,
+            _ => {
+                let base = match ch as u32 {
+                    code @ 32..=126 => f32::from(HELVETICA_WIDTHS[(code - 32) as usize]),
+                    _ => 600.0,
+                };
+                // Bold runs about 6% wider; wrapping errs on the safe side.
+                if matches!(font, Font::Bold) {
+                    base * 1.06
+                } else {
+                    base
+                }
+            }
+        })
+        .sum();
+    units * size / 1000.0
+}
+
+/// Encodes text for a PDF string in WinAnsiEncoding, which the standard
+/// fonts use: Latin-1 maps straight through, common typographic marks to
+/// their Windows-1252 codes, anything else to `?`.
+fn pdf_string(text: &str) -> String {
+    let mut out = String::from("(");
+    for ch in text.chars() {
+        let byte = match ch {
+            '\u{20ac}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\t' => b' ',
+            c if (' '..='~').contains(&c) || ('\u{a0}'..='\u{ff}').contains(&c) => c as u8,
+            _ => b'?',
+        };
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            32..=126 => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// A minimal PDF writer: flowing text in the standard base-14 fonts, with
+/// word wrapping and page breaks. Nothing is embedded, so the output stays
+/// small and opens anywhere.
+struct Document {
+    pages: Vec<String>,
+    y: f32,
+    footer: String,
+}
+
+impl Document {
+    fn new(footer: String) -> Self {
+        let mut doc = Self {
+            pages: Vec::new(),
+            y: 0.0,
+            footer,
+        };
+        doc.new_page();
+        doc
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(String::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn put(&mut self, x: f32, text: &str, font: Font, size: f32) {
+        let page = self.pages.last_mut().expect("document has a page");
+        page.push_str(&format!(
+            "BT /{} {:.1} Tf {:.1} {:.1} Td {} Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            self.y,
+            pdf_string(text)
+        ));
+    }
+
+    fn line(&mut self, text: &str, indent: f32, font: Font, size: f32) {
+        let leading = size * 1.35;
+        self.ensure(leading);
+        self.y -= size;
+        self.put(MARGIN + indent, text, font, size);
+        self.y -= leading - size;
+    }
+
+    /// Wraps `text` to the page width, keeping its own line breaks.
+    fn text(&mut self, text: &str, indent: f32, font: Font, size: f32) {
+        let width = PAGE_WIDTH - 2.0 * MARGIN - indent;
+        for paragraph in text.lines() {
+            if paragraph.trim().is_empty() {
+                self.line("", indent, font, size);
+                continue;
+            }
+            for wrapped in wrap(paragraph, width, font, size) {
+                self.line(&wrapped, indent, font, size);
+            }
+        }
+    }
+
+    fn heading(&mut self, title: &str) {
+        self.ensure(BODY_SIZE * 5.0);
+        self.gap(8.0);
+        self.line(title, 0.0, Font::Bold, 13.0);
+        let page = self.pages.last_mut().expect("document has a page");
+        page.push_str(&format!(
+            "0.6 G 0.5 w {:.1} {:.1} m {:.1} {:.1} l S 0 G\n",
+            MARGIN,
+            self.y + 3.0,
+            PAGE_WIDTH - MARGIN,
+            self.y + 3.0
+        ));
+        self.gap(4.0);
+    }
+
+    fn field(&mut self, label: &str, value: &str) {
+        if value.trim().is_empty() {
+            return;
+        }
+        let label_width = 110.0;
+        let width = PAGE_WIDTH - 2.0 * MARGIN - label_width;
+        let lines: Vec<String> = value
+            .lines()
+            .flat_map(|l| wrap(l, width, Font::Regular, BODY_SIZE))
+            .collect();
+        for (index, wrapped) in lines.iter().enumerate() {
+            let leading = BODY_SIZE * 1.35;
+            self.ensure(leading);
+            self.y -= BODY_SIZE;
+            if index == 0 {
+                self.put(MARGIN, label, Font::Bold, BODY_SIZE);
+            }
+            self.put(MARGIN + label_width, wrapped, Font::Regular, BODY_SIZE);
+            self.y -= leading - BODY_SIZE;
+        }
+    }
+
+    fn gap(&mut self, points: f32) {
+        self.y -= points;
+    }
+
+    fn finish(self, title: &str) -> Vec<u8> {
+        let count = self.pages.len();
+        let mut objects: Vec<String> = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            String::new(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            format!(
+                "<< /Title {} /Producer (NovaAdapt Desktop) /CreationDate (D:{}Z) >>",
+                pdf_string(title),
+                Utc::now().format("%Y%m%d%H%M%S")
+            ),
+        ];
+        let mut kids = Vec::new();
+        for (index, mut content) in self.pages.into_iter().enumerate() {
+            let footer = format!("{} - page {} of {}", self.footer, index + 1, count);
+            content.push_str(&format!(
+                "BT /F1 8.0 Tf {:.1} {:.1} Td {} Tj ET\n",
+                MARGIN,
+                FOOTER_Y,
+                pdf_string(&footer)
+            ));
+            let page_id = objects.len() + 1;
+            kids.push(format!("{} 0 R", page_id));
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                content.len(),
+                content
+            ));
+        }
+        objects[1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            count
+        );
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+        }
+        let xref = out.len();
+        out.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R /Info 6 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .as_bytes(),
+        );
+        out
+    }
+}
+
+fn wrap(text: &str, width: f32, font: Font, size: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split(' ') {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if text_width(&candidate, font, size) <= width {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // A word wider than the line is split wherever it overflows.
+        for ch in word.chars() {
+            current.push(ch);
+            if text_width(&current, font, size) > width && current.chars().count() > 1 {
+                current.pop();
+                lines.push(std::mem::take(&mut current));
+                current.push(ch);
+            }
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+pub fn as_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn first_text(plan: &Value, keys: &[&str]) -> String {
+    keys.iter()
+        .map(|k| as_text(&plan[*k]))
+        .find(|s| !s.trim().is_empty())
+        .unwrap_or_default()
+}
+
+/// The plan's changes as diff lines: its own `diff` when the core sends
+/// one, otherwise one line per proposed action.
+fn diff_lines(plan: &Value) -> Vec<String> {
+    if let Some(diff) = plan["diff"].as_str().filter(|d| !d.trim().is_empty()) {
+        return diff.lines().map(str::to_string).collect();
+    }
+    plan["actions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, action)| {
+            let kind = first_text(action, &["type", "action", "kind"]);
+            let target = first_text(action, &["target", "path", "selector", "command"]);
+            let value = first_text(action, &["value", "text", "args"]);
+            format!("+ {:>3}. {} {} {}", index + 1, kind, target, value)
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+fn plan_audit(
+    conn: &Connection,
+    profile_id: &str,
+    plan_id: &str,
+    formatter: &Formatter,
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT at, category, action FROM audit_log
+         WHERE profile_id = ?1 AND entity_id = ?2 ORDER BY id LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![profile_id, plan_id, MAX_AUDIT_ROWS as i64], |row| {
+        Ok(format!(
+            "{}  {} {}",
+            formatter.when(&row.get::<_, String>(0)?),
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Writes a printable review packet for one plan to `path`: its summary,
+/// the proposed changes, the risk report, reviewer comments and the
+/// approval decision with this shell's audit trail for the plan. Sections
+/// the core does not provide are marked as such rather than left out, so
+/// the packet's shape is the same for every plan.
+#[tauri::command]
+pub async fn export_plan_pdf(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    path: String,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    let path = path.trim().to_string();
+    if plan_id.is_empty() || path.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id and path are required"));
+    }
+    let target = residency::check("The plan PDF", &path)?;
+    let conn = store.connection(profile.as_deref())?;
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let comments = match crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}/comments", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(listed) => Some(
+            listed
+                .as_array()
+                .or_else(|| listed["comments"].as_array())
+                .cloned()
+                .unwrap_or_default(),
+        ),
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => None,
+        Err(err) => return Err(err),
+    };
+    let formatter = Formatter::for_profile(&store.snapshot(), Some(&conn.profile_id));
+    let audit = plan_audit(&*db.lock()?, &conn.profile_id, &plan_id, &formatter)
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+
+    let objective = first_text(&plan, &["objective", "title"]);
+    let mut doc = Document::new(format!("Plan {}", plan_id));
+    doc.line("Plan review packet", 0.0, Font::Bold, 18.0);
+    doc.gap(4.0);
+    doc.text(&objective, 0.0, Font::Regular, 12.0);
+
+    doc.heading("Summary");
+    doc.field("Plan", &plan_id);
+    doc.field("Status", &as_text(&plan["status"]));
+    doc.field("Strategy", &as_text(&plan["strategy"]));
+    doc.field(
+        "Created",
+        &formatter.when(&first_text(&plan, &["created_at", "created"])),
+    );
+    doc.field(
+        "Requested by",
+        &first_text(&plan, &["requested_by", "agent", "agent_id"]),
+    );
+    doc.field("Profile", &conn.profile_id);
+    doc.field("Exported", &formatter.timestamp(Utc::now()));
+    let summary = first_text(&plan, &["summary", "description", "rationale"]);
+    if !summary.is_empty() {
+        doc.gap(4.0);
+        doc.text(&summary, 0.0, Font::Regular, BODY_SIZE);
+    }
+
+    doc.heading("Changes");
+    let lines = diff_lines(&plan);
+    if lines.is_empty() {
+        doc.text(
+            "The plan proposes no actions.",
+            0.0,
+            Font::Regular,
+            BODY_SIZE,
+        );
+    }
+    for line in lines.iter().take(MAX_DIFF_LINES) {
+        doc.text(line, 0.0, Font::Mono, MONO_SIZE);
+    }
+    if lines.len() > MAX_DIFF_LINES {
+        doc.text(
+            &format!("... {} more lines not shown", lines.len() - MAX_DIFF_LINES),
+            0.0,
+            Font::Regular,
+            BODY_SIZE,
+        );
+    }
+
+    doc.heading("Risk report");
+    let risk = ["risk_report", "risk"]
+        .iter()
+        .map(|k| &plan[*k])
+        .find(|v| !v.is_null());
+    match risk {
+        Some(Value::Object(report)) => {
+            for (key, value) in report {
+                doc.field(&key.replace('_', " "), &as_text(value));
+            }
+        }
+        Some(other) => doc.field("Risk", &as_text(other)),
+        None if !plan["risk_level"].is_null() => doc.field("Risk", &as_text(&plan["risk_level"])),
+        None => doc.text(
+            "The core reported no risk assessment.",
+            0.0,
+            Font::Regular,
+            BODY_SIZE,
+        ),
+    }
+
+    doc.heading("Comments");
+    match &comments {
+        None => doc.text(
+            "This core does not provide comments.",
+            0.0,
+            Font::Regular,
+            BODY_SIZE,
+        ),
+        Some(list) if list.is_empty() => doc.text("No comments.", 0.0, Font::Regular, BODY_SIZE),
+        Some(list) => {
+            for comment in list {
+                let author = first_text(comment, &["author", "user", "created_by"]);
+                let at = formatter.when(&first_text(comment, &["created_at", "at"]));
+                doc.gap(2.0);
+                doc.text(&format!("{}  {}", author, at), 0.0, Font::Bold, BODY_SIZE);
+                doc.text(
+                    &first_text(comment, &["body", "text", "comment"]),
+                    12.0,
+                    Font::Regular,
+                    BODY_SIZE,
+                );
+            }
+        }
+    }
+
+    doc.heading("Decision");
+    doc.field("Status", &as_text(&plan["status"]));
+    doc.field(
+        "Decided by",
+        &first_text(&plan, &["decided_by", "approved_by", "rejected_by"]),
+    );
+    doc.field(
+        "Decided at",
+        &formatter.when(&first_text(
+            &plan,
+            &["decided_at", "approved_at", "rejected_at"],
+        )),
+    );
+    doc.field(
+        "Reason",
+        &first_text(&plan, &["decision_reason", "reject_reason", "reason"]),
+    );
+    if !audit.is_empty() {
+        doc.gap(6.0);
+        doc.text("Shell audit trail", 0.0, Font::Bold, BODY_SIZE);
+        for entry in &audit {
+            doc.text(entry, 0.0, Font::Mono, MONO_SIZE);
+        }
+    }
+
+    let pages = doc.pages.len();
+    let title = match objective.as_str() {
+        "" => format!("Plan {}", plan_id),
+        objective => format!("Plan {}: {}", plan_id, objective),
+    };
+    let bytes = doc.finish(&title);
+    fs::write(&target, &bytes)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write PDF failed: {}", e)))?;
+
+    let summary = json!({
+        "path": path,
+        "plan_id": plan_id,
+        "pages": pages,
+        "bytes": bytes.len(),
+        "sha256": Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+    });
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "plan",
+        "export_pdf",
+        &plan_id,
+        &summary,
+    );
+    Ok(summary)
+}
@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection as DbConnection};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{Connection, SettingsStore};
+use crate::{environment, notify, tasks};
+
+const TASK: &str = "plan_revisions";
+const REVISED_EVENT: &str = "plan:revised";
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// A revision the core has not produced by then is marked `expired`; the
+/// operator can ask again.
+const WAIT_MINUTES: i64 = 30;
+const MAX_CONSTRAINTS: usize = 50;
+const MAX_TEXT: usize = 4_000;
+
+/// What the operator wants changed: the steps (1-based) to drop or redo,
+/// constraints the replacement must respect, and free text.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RevisionFeedback {
+    pub rejected_steps: Vec<u32>,
+    pub constraints: Vec<String>,
+    pub comment: Option<String>,
+}
+
+impl RevisionFeedback {
+    fn normalized(mut self) -> Result<Self, String> {
+        self.rejected_steps.sort_unstable();
+        self.rejected_steps.dedup();
+        if self.rejected_steps.contains(&0) {
+            return Err(ErrorCode::InvalidInput.with("Rejected steps are numbered from 1"));
+        }
+        self.constraints = self
+            .constraints
+            .iter()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        self.comment = self
+            .comment
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+        if self.constraints.len() > MAX_CONSTRAINTS {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "At most {} constraints are allowed",
+                MAX_CONSTRAINTS
+            )));
+        }
+        let too_long = self
+            .constraints
+            .iter()
+            .chain(self.comment.as_ref())
+            .any(|text| text.len() > MAX_TEXT);
+        if too_long {
+            return Err(ErrorCode::InvalidInput
+                .with(format!("Feedback text must be at most {} bytes", MAX_TEXT)));
+        }
+        if self.rejected_steps.is_empty() && self.constraints.is_empty() && self.comment.is_none() {
+            return Err(ErrorCode::InvalidInput
+                .with("Feedback needs rejected steps, constraints or a comment"));
+        }
+        Ok(self)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "rejected_steps": self.rejected_steps,
+            "constraints": self.constraints,
+            "comment": self.comment,
+        })
+    }
+}
+
+fn stamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn storage(err: rusqlite::Error) -> String {
+    ErrorCode::LocalStorage.with(err.to_string())
+}
+
+fn id_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) if !id.trim().is_empty() => Some(id.trim().to_string()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The replacement's id in a revision response, when the core created it
+/// synchronously.
+fn replacement_in_response(plan_id: &str, response: &Value) -> Option<String> {
+    ["revision_plan_id", "revised_plan_id", "new_plan_id"]
+        .iter()
+        .find_map(|k| id_of(&response[*k]))
+        .or_else(|| id_of(&response["plan"]["id"]))
+        .or_else(|| id_of(&response["id"]))
+        .filter(|id| id != plan_id)
+}
+
+/// Whether `plan` says it replaces `plan_id`.
+fn revises(plan: &Value, plan_id: &str) -> bool {
+    ["revision_of", "revises", "replaces", "parent_plan_id"]
+        .iter()
+        .any(|k| id_of(&plan[*k]).as_deref() == Some(plan_id))
+}
+
+fn link(
+    conn: &DbConnection,
+    id: i64,
+    profile_id: &str,
+    plan_id: &str,
+    revision_plan_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE plan_revisions SET status = 'linked', revision_plan_id = ?1, linked_at = ?2
+         WHERE id = ?3",
+        params![revision_plan_id, stamp(Utc::now()), id],
+    )?;
+    db::record_audit(
+        conn,
+        profile_id,
+        "plan_revision",
+        "linked",
+        plan_id,
+        &json!({ "revision_plan_id": revision_plan_id }),
+    )
+}
+
+fn announce(app: &AppHandle, profile_id: &str, plan_id: &str, revision_plan_id: &str) {
+    let _ = environment::emit(
+        app,
+        REVISED_EVENT,
+        json!({
+            "profile_id": profile_id,
+            "plan_id": plan_id,
+            "revision_plan_id": revision_plan_id,
+        }),
+    );
+    notify::notify_in_app(
+        app,
+        "plan_revision",
+        "Revised plan ready",
+        &format!("Plan {} was revised as {}", plan_id, revision_plan_id),
+        json!({ "plan_id": revision_plan_id, "revision_of": plan_id }),
+    );
+}
+
+/// Asks the core to generate a replacement for `plan_id` that takes the
+/// operator's structured feedback into account. The replacement is linked
+/// to the original in the local database as soon as the core names it,
+/// either in the response or, for cores that revise in the background, on
+/// a later poll of its plans; `plan:revised` is emitted then. Waiting stops
+/// after thirty minutes.
+#[tauri::command]
+pub async fn request_revision(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    feedback: RevisionFeedback,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() || plan_id.contains(['/', '?', '#']) {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let feedback = feedback.normalized()?;
+    let conn = store.connection(profile.as_deref())?;
+    let response = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/revise", plan_id),
+        conn.token.clone(),
+        Some(json!({ "feedback": feedback.to_json() })),
+    )
+    .await?;
+    let replacement = replacement_in_response(&plan_id, &response);
+    let id = {
+        let guard = db.lock()?;
+        guard
+            .execute(
+                "INSERT INTO plan_revisions (profile_id, plan_id, feedback, requested_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    conn.profile_id,
+                    plan_id,
+                    feedback.to_json().to_string(),
+                    stamp(Utc::now())
+                ],
+            )
+            .map_err(storage)?;
+        let id = guard.last_insert_rowid();
+        db::record_audit(
+            &guard,
+            &conn.profile_id,
+            "plan_revision",
+            "requested",
+            &plan_id,
+            &feedback.to_json(),
+        )
+        .map_err(storage)?;
+        if let Some(revision) = &replacement {
+            link(&guard, id, &conn.profile_id, &plan_id, revision).map_err(storage)?;
+        }
+        id
+    };
+    if let Some(revision) = &replacement {
+        announce(&app, &conn.profile_id, &plan_id, revision);
+    }
+    Ok(json!({
+        "revision_id": id,
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "status": if replacement.is_some() { "linked" } else { "waiting" },
+        "revision_plan_id": replacement,
+        "response": response,
+    }))
+}
+
+fn row_json(row: &rusqlite::Row<'_>) -> rusqlite::Result<Value> {
+    let feedback: String = row.get(3)?;
+    Ok(json!({
+        "revision_id": row.get::<_, i64>(0)?,
+        "plan_id": row.get::<_, String>(1)?,
+        "revision_plan_id": row.get::<_, Option<String>>(2)?,
+        "feedback": serde_json::from_str::<Value>(&feedback).unwrap_or(Value::Null),
+        "status": row.get::<_, String>(4)?,
+        "requested_at": row.get::<_, String>(5)?,
+        "linked_at": row.get::<_, Option<String>>(6)?,
+    }))
+}
+
+/// The revisions a plan went through: what it was revised from and what
+/// it was revised into, with the feedback each time.
+#[tauri::command]
+pub fn get_plan_revisions(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let plan_id = plan_id.trim().to_string();
+    let guard = db.lock()?;
+    let query = |column: &str| -> Result<Vec<Value>, String> {
+        let mut stmt = guard
+            .prepare(&format!(
+                "SELECT id, plan_id, revision_plan_id, feedback, status, requested_at, linked_at
+                 FROM plan_revisions WHERE profile_id = ?1 AND {} = ?2 ORDER BY id",
+                column
+            ))
+            .map_err(storage)?;
+        let rows = stmt
+            .query_map(params![conn.profile_id, plan_id], row_json)
+            .map_err(storage)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(storage);
+        rows
+    };
+    Ok(json!({
+        "plan_id": plan_id,
+        "revised_from": query("revision_plan_id")?,
+        "revisions": query("plan_id")?,
+    }))
+}
+
+/// Links waiting revisions to the plans the core generated for them.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            poll(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+struct Waiting {
+    id: i64,
+    plan_id: String,
+    requested_at: DateTime<Utc>,
+}
+
+fn waiting(conn: &DbConnection) -> rusqlite::Result<BTreeMap<String, Vec<Waiting>>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, plan_id, requested_at FROM plan_revisions
+         WHERE status = 'waiting' ORDER BY id",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut by_profile: BTreeMap<String, Vec<Waiting>> = BTreeMap::new();
+    for (id, profile_id, plan_id, requested_at) in rows {
+        let requested_at = DateTime::parse_from_rfc3339(&requested_at)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        by_profile.entry(profile_id).or_default().push(Waiting {
+            id,
+            plan_id,
+            requested_at,
+        });
+    }
+    Ok(by_profile)
+}
+
+/// The replacement for `plan_id`: a listed plan marking itself as its
+/// revision, or the id the original names as its successor.
+async fn find_replacement(conn: &Connection, plans: &[Value], plan_id: &str) -> Option<String> {
+    if let Some(found) = plans.iter().find(|p| revises(p, plan_id)) {
+        return id_of(&found["id"]);
+    }
+    let original = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    .ok()?;
+    ["revised_by", "replaced_by", "revision_plan_id"]
+        .iter()
+        .find_map(|k| id_of(&original[*k]))
+        .filter(|id| id != plan_id)
+}
+
+async fn poll(app: &AppHandle) {
+    let by_profile = match app.state::<LocalDb>().lock() {
+        Ok(conn) => waiting(&conn).unwrap_or_default(),
+        Err(_) => return,
+    };
+    let store = app.state::<SettingsStore>();
+    for (profile_id, entries) in by_profile {
+        let Ok(conn) = store.connection(Some(&profile_id)) else {
+            continue;
+        };
+        let Ok(listed) = crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            "/plans?limit=200",
+            conn.token.clone(),
+            None,
+        )
+        .await
+        else {
+            continue;
+        };
+        let plans = listed.as_array().cloned().unwrap_or_default();
+        for entry in entries {
+            let found = find_replacement(&conn, &plans, &entry.plan_id).await;
+            let db = app.state::<LocalDb>();
+            let Ok(guard) = db.lock() else {
+                return;
+            };
+            match found {
+                Some(revision) => {
+                    let linked =
+                        link(&guard, entry.id, &profile_id, &entry.plan_id, &revision).is_ok();
+                    drop(guard);
+                    if linked {
+                        announce(app, &profile_id, &entry.plan_id, &revision);
+                    }
+                }
+                None if (Utc::now() - entry.requested_at).num_minutes() >= WAIT_MINUTES => {
+                    let _ = guard.execute(
+                        "UPDATE plan_revisions SET status = 'expired' WHERE id = ?1",
+                        [entry.id],
+                    );
+                }
+                None => {}
+            }
+        }
+    }
+}
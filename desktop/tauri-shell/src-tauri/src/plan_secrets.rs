@@ -0,0 +1,134 @@
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::attachments::{redact, secret_rules};
+use crate::settings::SettingsStore;
+use crate::stall;
+
+const MAX_FINDINGS: usize = 50;
+/// Strings past this are scanned up to it; plans rarely carry more inline.
+const MAX_SCAN_CHARS: usize = 256 * 1024;
+/// Field names whose values are secrets whatever they look like.
+const SECRET_FIELDS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "client_secret",
+    "api_key",
+    "apikey",
+    "access_key",
+    "secret_access_key",
+    "private_key",
+    "token",
+    "access_token",
+    "refresh_token",
+    "auth_token",
+];
+/// Values of secret-named fields that are placeholders, not secrets.
+const PLACEHOLDERS: &[&str] = &["", "***", "<redacted>", "[redacted]", "changeme", "xxx"];
+
+fn secret_field(key: &str) -> bool {
+    let key = key.to_ascii_lowercase().replace('-', "_");
+    SECRET_FIELDS.contains(&key.as_str())
+}
+
+/// A field holding a reference to a secret (`${DB_PASSWORD}`, a vault
+/// path) rather than the secret itself.
+fn reference(value: &str) -> bool {
+    let value = value.trim();
+    (value.starts_with("${") && value.ends_with('}'))
+        || value.starts_with("vault:")
+        || value.starts_with("secret://")
+        || PLACEHOLDERS.contains(&value.to_ascii_lowercase().as_str())
+}
+
+fn walk(path: &str, value: &Value, findings: &mut Vec<Value>) {
+    if findings.len() >= MAX_FINDINGS {
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, inner) in map {
+                let inner_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                if let Value::String(text) = inner {
+                    if secret_field(key) && !reference(text) && text.len() >= 6 {
+                        findings.push(json!({
+                            "rule": "secret_field",
+                            "path": inner_path,
+                            "excerpt": redact(text),
+                        }));
+                        continue;
+                    }
+                }
+                walk(&inner_path, inner, findings);
+            }
+        }
+        Value::Array(items) => {
+            for (index, inner) in items.iter().enumerate() {
+                walk(&format!("{}[{}]", path, index), inner, findings);
+            }
+        }
+        Value::String(text) => {
+            let end = text
+                .char_indices()
+                .nth(MAX_SCAN_CHARS)
+                .map_or(text.len(), |(i, _)| i);
+            for (rule, regex) in secret_rules() {
+                for hit in regex.find_iter(&text[..end]) {
+                    if findings.len() >= MAX_FINDINGS {
+                        return;
+                    }
+                    findings.push(json!({
+                        "rule": rule,
+                        "path": path,
+                        "excerpt": redact(hit.as_str()),
+                    }));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Credentials embedded anywhere in the plan: strings matching the secret
+/// rules shared with attachments and the clipboard guard, and values of
+/// secret-named fields. Each finding has its JSON `path` and a redacted
+/// `excerpt`.
+fn scan(plan: &Value) -> Vec<Value> {
+    let mut findings = Vec::new();
+    walk("", plan, &mut findings);
+    findings
+}
+
+/// Checks a plan for embedded secrets before it is approved, since a plan
+/// that executes with a credential inline tends to log it. The plan is
+/// read from the core, not taken from the caller.
+#[tauri::command]
+pub async fn scan_plan_secrets(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token,
+        None,
+    )
+    .await?;
+    let findings = scan(&plan);
+    Ok(json!({
+        "plan_id": plan_id,
+        "clean": findings.is_empty(),
+        "truncated": findings.len() >= MAX_FINDINGS,
+        "findings": findings,
+    }))
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::environment;
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+use crate::tasks;
+
+const TASK: &str = "plan_watch";
+const EVENT: &str = "plan:watch";
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_WATCHED: usize = 50;
+
+/// Plans the operator subscribed to, by profile and plan id, with what was
+/// last seen of each.
+#[derive(Default)]
+pub struct PlanWatches {
+    watched: Mutex<HashMap<(String, String), Seen>>,
+}
+
+#[derive(Clone, Default)]
+struct Seen {
+    /// Unset until the first poll, which sets the baseline without events.
+    status: Option<String>,
+    steps: HashMap<usize, String>,
+    completed: usize,
+    /// `None` when the core has no comments endpoint.
+    comments: Option<HashSet<String>>,
+}
+
+fn plan_key(plan_id: &str) -> Result<String, String> {
+    let plan_id = plan_id.trim();
+    if plan_id.is_empty() || plan_id.contains(['/', '?', '#']) {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    Ok(plan_id.to_string())
+}
+
+fn comment_id(comment: &Value) -> String {
+    match &comment["id"] {
+        Value::String(id) => id.clone(),
+        Value::Number(id) => id.to_string(),
+        _ => {
+            let digest = Sha256::digest(comment.to_string().as_bytes());
+            digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+}
+
+/// Subscribes to `plan_id`: its status changes, step progress and new
+/// comments arrive as `plan:watch` events until `unwatch_plan`, without
+/// refreshing the dashboard. Events carry `kind` (`watching`, `status`,
+/// `step`, `progress`, `comment`, `gone` or `error`) with the profile and
+/// plan ids.
+#[tauri::command]
+pub fn watch_plan(
+    store: State<'_, SettingsStore>,
+    watches: State<'_, PlanWatches>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let mut watched = watches
+        .watched
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Plan watch state poisoned"))?;
+    let key = (conn.profile_id.clone(), plan_id.clone());
+    if !watched.contains_key(&key) && watched.len() >= MAX_WATCHED {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "At most {} plans can be watched at once",
+            MAX_WATCHED
+        )));
+    }
+    watched.entry(key).or_default();
+    Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id, "watching": true }))
+}
+
+#[tauri::command]
+pub fn unwatch_plan(
+    store: State<'_, SettingsStore>,
+    watches: State<'_, PlanWatches>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let removed = watches
+        .watched
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Plan watch state poisoned"))?
+        .remove(&(conn.profile_id.clone(), plan_id.trim().to_string()));
+    Ok(
+        json!({ "profile_id": conn.profile_id, "plan_id": plan_id.trim(), "unwatched": removed.is_some() }),
+    )
+}
+
+#[tauri::command]
+pub fn list_watched_plans(watches: State<'_, PlanWatches>) -> Result<Value, String> {
+    let watched = watches
+        .watched
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Plan watch state poisoned"))?;
+    let plans: Vec<Value> = watched
+        .iter()
+        .map(|((profile_id, plan_id), seen)| {
+            json!({ "profile_id": profile_id, "plan_id": plan_id, "status": seen.status })
+        })
+        .collect();
+    Ok(json!({ "plans": plans }))
+}
+
+/// Polls every watched plan, and only those, every `POLL_INTERVAL`.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            poll_all(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn poll_all(app: &AppHandle) {
+    let keys: Vec<(String, String)> = match app.state::<PlanWatches>().watched.lock() {
+        Ok(watched) => watched.keys().cloned().collect(),
+        Err(_) => return,
+    };
+    for (profile_id, plan_id) in keys {
+        let previous = match app.state::<PlanWatches>().watched.lock() {
+            Ok(watched) => match watched.get(&(profile_id.clone(), plan_id.clone())) {
+                Some(seen) => seen.clone(),
+                None => continue,
+            },
+            Err(_) => return,
+        };
+        let (next, events) = match poll(app, &profile_id, &plan_id, previous).await {
+            Ok(polled) => polled,
+            Err(err) => {
+                emit(
+                    app,
+                    &profile_id,
+                    &plan_id,
+                    json!({ "kind": "error", "error": err }),
+                );
+                continue;
+            }
+        };
+        let gone = events.iter().any(|e| e["kind"] == "gone");
+        if let Ok(mut watched) = app.state::<PlanWatches>().watched.lock() {
+            let key = (profile_id.clone(), plan_id.clone());
+            // Unwatched while the poll was in flight.
+            if !watched.contains_key(&key) {
+                continue;
+            }
+            match next {
+                Some(next) if !gone => {
+                    watched.insert(key, next);
+                }
+                _ => {
+                    watched.remove(&key);
+                }
+            }
+        }
+        for event in events {
+            emit(app, &profile_id, &plan_id, event);
+        }
+    }
+}
+
+fn emit(app: &AppHandle, profile_id: &str, plan_id: &str, mut event: Value) {
+    event["profile_id"] = json!(profile_id);
+    event["plan_id"] = json!(plan_id);
+    let _ = environment::emit(app, EVENT, event);
+}
+
+/// Fetches the plan (and its comments) and compares it with `seen`. The
+/// first poll only records a baseline and reports it as `watching`.
+async fn poll(
+    app: &AppHandle,
+    profile_id: &str,
+    plan_id: &str,
+    seen: Seen,
+) -> Result<(Option<Seen>, Vec<Value>), String> {
+    let conn = app.state::<SettingsStore>().connection(Some(profile_id))?;
+    let plan = match crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(plan) => plan,
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+            return Ok((None, vec![json!({ "kind": "gone" })]));
+        }
+        Err(err) => return Err(err),
+    };
+    let comments = if seen.status.is_some() && seen.comments.is_none() {
+        // The first poll found no comments endpoint; don't ask again.
+        None
+    } else {
+        match crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/plans/{}/comments", plan_id),
+            conn.token.clone(),
+            None,
+        )
+        .await
+        {
+            Ok(listed) => Some(
+                listed
+                    .as_array()
+                    .or_else(|| listed["comments"].as_array())
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => None,
+            Err(err) => return Err(err),
+        }
+    };
+
+    let status = plan["status"].as_str().unwrap_or_default().to_string();
+    let results = plan["execution_results"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let total = ["steps", "actions"]
+        .iter()
+        .find_map(|k| plan[*k].as_array().map(Vec::len))
+        .unwrap_or(0)
+        .max(results.len());
+    let mut next = Seen {
+        status: Some(status.clone()),
+        steps: HashMap::new(),
+        completed: 0,
+        comments: None,
+    };
+    for (index, result) in results.iter().enumerate() {
+        let step_status = result["status"].as_str().unwrap_or_default();
+        if !step_status.is_empty() {
+            next.steps.insert(index + 1, step_status.to_string());
+        }
+        if matches!(
+            step_status,
+            "ok" | "success" | "succeeded" | "done" | "completed"
+        ) {
+            next.completed += 1;
+        }
+    }
+    next.comments = comments
+        .as_ref()
+        .map(|list| list.iter().map(comment_id).collect());
+
+    let Some(previous_status) = seen.status else {
+        let event = json!({
+            "kind": "watching",
+            "status": status,
+            "completed": next.completed,
+            "total": total,
+            "comments": comments.as_ref().map(Vec::len),
+        });
+        return Ok((Some(next), vec![event]));
+    };
+    let mut events = Vec::new();
+    if previous_status != status {
+        events.push(json!({ "kind": "status", "from": previous_status, "to": status }));
+    }
+    let mut steps: Vec<_> = next.steps.iter().collect();
+    steps.sort_by_key(|(step, _)| **step);
+    for (step, step_status) in steps {
+        if seen.steps.get(step) != Some(step_status) {
+            events.push(json!({
+                "kind": "step",
+                "step": step,
+                "status": step_status,
+                "from": seen.steps.get(step),
+            }));
+        }
+    }
+    if next.completed != seen.completed {
+        events.push(json!({ "kind": "progress", "completed": next.completed, "total": total }));
+    }
+    if let (Some(list), Some(known)) = (&comments, &seen.comments) {
+        for comment in list {
+            if !known.contains(&comment_id(comment)) {
+                events.push(json!({ "kind": "comment", "comment": comment }));
+            }
+        }
+    }
+    Ok((Some(next), events))
+}
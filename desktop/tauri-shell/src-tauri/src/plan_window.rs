@@ -0,0 +1,210 @@
+use chrono::{Duration, Utc};
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{sync, tenants};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 500;
+const MAX_STATUSES: usize = 16;
+const MAX_TEXT_LEN: usize = 256;
+const MAX_WITHIN_HOURS: u32 = 24 * 365 * 10;
+const DEFAULT_SORT: &str = "-created_at";
+const SORTS: &str = "`created_at`, `updated_at` or `status`, with `-` for descending";
+
+/// Narrows a plan window. Every field is optional; `text` matches the
+/// plan id or objective, `since`/`until` (RFC 3339) bound `created_at`, and
+/// `within_hours` keeps plans created in the hours before the query.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanFilter {
+    #[serde(default)]
+    pub status: Vec<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default)]
+    pub within_hours: Option<u32>,
+}
+
+/// `field` or `-field` (descending), over the indexed columns only, so a
+/// page deep into the list is read off an index rather than a sort.
+fn order_by(sort: &str) -> Result<String, String> {
+    let (field, direction) = match sort.strip_prefix('-') {
+        Some(field) => (field, "DESC"),
+        None => (sort, "ASC"),
+    };
+    let columns: &[&str] = match field {
+        "created_at" => &["created_at"],
+        "updated_at" => &["updated_at"],
+        "status" => &["status", "created_at"],
+        _ => return Err(format!("Unknown sort {:?} (use {})", sort, SORTS)),
+    };
+    Ok(columns
+        .iter()
+        .chain(&["entity_id"])
+        .map(|c| format!("{} {}", c, direction))
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
+fn parse_bound(raw: Option<&str>, name: &str) -> Result<Option<String>, String> {
+    match raw.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(raw) => chrono::DateTime::parse_from_rfc3339(raw)
+            .map(|t| Some(t.to_rfc3339()))
+            .map_err(|_| format!("`{}` must be RFC 3339", name)),
+        None => Ok(None),
+    }
+}
+
+/// The `WHERE` clause for `filter` and its parameters, after the profile.
+fn conditions(filter: &PlanFilter) -> Result<(String, Vec<SqlValue>), String> {
+    let mut clause = String::from("profile_id = ?1 AND kind = 'plan'");
+    let mut params = Vec::new();
+    let statuses: Vec<String> = filter
+        .status
+        .iter()
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if statuses.len() > MAX_STATUSES {
+        return Err(format!(
+            "At most {} statuses can be filtered on",
+            MAX_STATUSES
+        ));
+    }
+    if !statuses.is_empty() {
+        let marks: Vec<String> = (0..statuses.len())
+            .map(|i| format!("?{}", params.len() + i + 2))
+            .collect();
+        clause.push_str(&format!(" AND status IN ({})", marks.join(", ")));
+        params.extend(statuses.into_iter().map(SqlValue::Text));
+    }
+    if let Some(text) = filter
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        if text.len() > MAX_TEXT_LEN {
+            return Err(format!("Filter text is longer than {} bytes", MAX_TEXT_LEN));
+        }
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let mark = params.len() + 2;
+        clause.push_str(&format!(
+            " AND (entity_id LIKE ?{0} ESCAPE '\\'
+                 OR json_extract(payload, '$.objective') LIKE ?{0} ESCAPE '\\')",
+            mark
+        ));
+        params.push(SqlValue::Text(format!("%{}%", escaped)));
+    }
+    let within = match filter.within_hours {
+        Some(hours) if hours == 0 || hours > MAX_WITHIN_HOURS => {
+            return Err(format!(
+                "`within_hours` must be between 1 and {}",
+                MAX_WITHIN_HOURS
+            ))
+        }
+        Some(hours) => Some((Utc::now() - Duration::hours(i64::from(hours))).to_rfc3339()),
+        None => None,
+    };
+    for (bound, op) in [
+        (parse_bound(filter.since.as_deref(), "since")?, ">="),
+        (parse_bound(filter.until.as_deref(), "until")?, "<="),
+        (within, ">="),
+    ] {
+        if let Some(at) = bound {
+            clause.push_str(&format!(
+                " AND julianday(created_at) {} julianday(?{})",
+                op,
+                params.len() + 2
+            ));
+            params.push(SqlValue::Text(at));
+        }
+    }
+    Ok((clause, params))
+}
+
+/// Checks a filter and sort (empty meaning the default) without running
+/// them, for saved views.
+pub fn validate(filter: &PlanFilter, sort: &str) -> Result<(), String> {
+    if !sort.trim().is_empty() {
+        order_by(sort.trim())?;
+    }
+    conditions(filter).map(|_| ())
+}
+
+/// One window of the cached plans for a virtualized list: `limit` plans
+/// (default 100, at most 500) from `offset` in `sort` order (default
+/// `-created_at`), with the total matching `filter` so the list can size
+/// itself without holding the rest. Served from the local sync cache, so it
+/// works offline and reflects the last sync.
+#[tauri::command]
+pub fn get_plans_window(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    filter: Option<PlanFilter>,
+    sort: Option<String>,
+) -> Result<Value, String> {
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let sort = sort
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_SORT.to_string());
+    let order = order_by(&sort).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let filter = filter.unwrap_or_default();
+    let (clause, mut params) = conditions(&filter).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let scope = tenants::cache_scope(&profile_id);
+    params.insert(0, SqlValue::Text(scope.clone()));
+
+    let guard = db.lock()?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let total: i64 = guard
+        .query_row(
+            &format!("SELECT COUNT(*) FROM sync_entities WHERE {}", clause),
+            rusqlite::params_from_iter(&params),
+            |row| row.get(0),
+        )
+        .map_err(storage)?;
+    let mut stmt = guard
+        .prepare(&format!(
+            "SELECT payload FROM sync_entities WHERE {} ORDER BY {} LIMIT {} OFFSET {}",
+            clause, order, limit, offset
+        ))
+        .map_err(storage)?;
+    let items: Vec<Value> = stmt
+        .query_map(rusqlite::params_from_iter(&params), |row| {
+            row.get::<_, String>(0)
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(storage)?
+        .iter()
+        .filter_map(|raw| serde_json::from_str(raw).ok())
+        .collect();
+    let cursor = db::get_cursor(&guard, &scope, sync::CHANGES_STREAM);
+    Ok(json!({
+        "profile_id": profile_id,
+        "offset": offset,
+        "limit": limit,
+        "total": total,
+        "sort": sort,
+        "cursor": cursor,
+        "items": items,
+    }))
+}
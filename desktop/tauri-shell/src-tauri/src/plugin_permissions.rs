@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::plugins::{self, Capability};
+use crate::settings::SettingsStore;
+use crate::{crypto, environment};
+
+const REQUESTED_EVENT: &str = "plugin:permission_requested";
+const RESOLVED_EVENT: &str = "plugin:permission_resolved";
+/// How long a plugin call waits for the operator before it is refused.
+const ANSWER_WINDOW: Duration = Duration::from_secs(120);
+
+/// A plugin call stopped on a capability it was never granted nor denied.
+struct Prompt {
+    plugin_id: String,
+    capability: Capability,
+    /// What the call was about to do, as shown to the operator.
+    action: String,
+    asked_at: String,
+    at: Instant,
+    /// Every call waiting on this answer; concurrent calls for the same
+    /// plugin and capability share one prompt.
+    waiters: Vec<Sender<bool>>,
+}
+
+impl Prompt {
+    fn describe(&self, request_id: &str) -> Value {
+        json!({
+            "request_id": request_id,
+            "plugin_id": self.plugin_id,
+            "capability": self.capability,
+            "action": self.action,
+            "asked_at": self.asked_at,
+            "expires_in_secs": ANSWER_WINDOW.saturating_sub(self.at.elapsed()).as_secs(),
+        })
+    }
+}
+
+/// Open permission prompts by request id.
+#[derive(Default)]
+pub struct PermissionPrompts {
+    pending: Mutex<HashMap<String, Prompt>>,
+}
+
+impl PermissionPrompts {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, Prompt>>, String> {
+        self.pending
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Plugin permission prompts poisoned"))
+    }
+}
+
+fn refused(plugin_id: &str, capability: Capability, why: &str) -> String {
+    ErrorCode::Forbidden.with(format!(
+        "Plugin {} was {} {}",
+        plugin_id,
+        why,
+        json!(capability).as_str().unwrap_or_default()
+    ))
+}
+
+/// Asks the operator, through `plugin:permission_requested`, whether
+/// plugin `plugin_id` may use `capability` for `action`, and blocks the
+/// calling plugin thread until they answer with `answer_plugin_permission`
+/// or the window passes. Only for plugin calls, which run off the async
+/// runtime.
+pub fn ask(
+    app: &AppHandle,
+    plugin_id: &str,
+    capability: Capability,
+    action: &str,
+) -> Result<(), String> {
+    let prompts = app.state::<PermissionPrompts>();
+    let (tx, rx) = mpsc::channel();
+    let request_id = {
+        let mut pending = prompts.lock()?;
+        let shared = pending
+            .iter_mut()
+            .find(|(_, p)| p.plugin_id == plugin_id && p.capability == capability);
+        match shared {
+            Some((request_id, prompt)) => {
+                prompt.waiters.push(tx);
+                request_id.clone()
+            }
+            None => {
+                let request_id: String = crypto::random_bytes::<16>()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                let prompt = Prompt {
+                    plugin_id: plugin_id.to_string(),
+                    capability,
+                    action: action.chars().take(500).collect(),
+                    asked_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    at: Instant::now(),
+                    waiters: vec![tx],
+                };
+                let _ = environment::emit(app, REQUESTED_EVENT, prompt.describe(&request_id));
+                pending.insert(request_id.clone(), prompt);
+                request_id
+            }
+        }
+    };
+    match rx.recv_timeout(ANSWER_WINDOW) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(refused(plugin_id, capability, "denied")),
+        Err(_) => {
+            let expired = prompts
+                .lock()
+                .ok()
+                .and_then(|mut pending| pending.remove(&request_id));
+            if let Some(prompt) = expired {
+                for waiter in prompt.waiters {
+                    let _ = waiter.send(false);
+                }
+                let _ = environment::emit(
+                    app,
+                    RESOLVED_EVENT,
+                    json!({
+                        "request_id": request_id,
+                        "plugin_id": plugin_id,
+                        "capability": capability,
+                        "allowed": false,
+                        "expired": true,
+                    }),
+                );
+            }
+            Err(refused(plugin_id, capability, "not answered in time for"))
+        }
+    }
+}
+
+/// The permission prompts waiting for an answer, for a window opened after
+/// `plugin:permission_requested` was sent.
+#[tauri::command]
+pub fn list_plugin_permission_requests(
+    prompts: State<'_, PermissionPrompts>,
+) -> Result<Value, String> {
+    let pending = prompts.lock()?;
+    let mut requests: Vec<Value> = pending
+        .iter()
+        .filter(|(_, p)| p.at.elapsed() <= ANSWER_WINDOW)
+        .map(|(id, p)| p.describe(id))
+        .collect();
+    requests.sort_by(|a, b| a["asked_at"].as_str().cmp(&b["asked_at"].as_str()));
+    Ok(json!({ "requests": requests }))
+}
+
+/// Answers a permission prompt. With `remember`, the answer is kept in the
+/// plugin's grant (`capabilities`, or `denied`) and the plugin is not
+/// asked again; without, it holds for the waiting call only. Either way
+/// the answer is audited.
+#[tauri::command]
+pub fn answer_plugin_permission(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    prompts: State<'_, PermissionPrompts>,
+    request_id: String,
+    allow: bool,
+    remember: Option<bool>,
+) -> Result<Value, String> {
+    let remember = remember.unwrap_or(false);
+    let prompt = prompts.lock()?.remove(request_id.trim()).ok_or_else(|| {
+        ErrorCode::NotFound.with("The permission request was answered or has expired")
+    })?;
+    let persisted = if remember {
+        store
+            .update(&app, |settings| {
+                let grant = plugins::grant_mut(&mut settings.plugins, &prompt.plugin_id);
+                grant.capabilities.retain(|c| *c != prompt.capability);
+                grant.denied.retain(|c| *c != prompt.capability);
+                if allow {
+                    grant.capabilities.push(prompt.capability);
+                } else {
+                    grant.denied.push(prompt.capability);
+                }
+                Ok(())
+            })
+            .map(|_| ())
+    } else {
+        Ok(())
+    };
+    for waiter in &prompt.waiters {
+        let _ = waiter.send(allow);
+    }
+    let summary = json!({
+        "request_id": request_id.trim(),
+        "plugin_id": prompt.plugin_id,
+        "capability": prompt.capability,
+        "action": prompt.action,
+        "allowed": allow,
+        "remembered": remember && persisted.is_ok(),
+    });
+    let _ = db::record_audit(
+        &*db.lock()?,
+        "",
+        "plugin",
+        if allow {
+            "permission_granted"
+        } else {
+            "permission_denied"
+        },
+        &prompt.plugin_id,
+        &summary,
+    );
+    let _ = environment::emit(&app, RESOLVED_EVENT, &summary);
+    persisted?;
+    Ok(summary)
+}
+
+/// Forgets the sensitive capabilities plugin `id` was denied when prompted,
+/// so it is asked again the next time it needs one.
+#[tauri::command]
+pub fn reset_plugin_permissions(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+) -> Result<Value, String> {
+    let id = id.trim().to_string();
+    let mut cleared = Vec::new();
+    store.update(&app, |settings| {
+        let grant = plugins::grant_mut(&mut settings.plugins, &id);
+        cleared = std::mem::take(&mut grant.denied);
+        Ok(())
+    })?;
+    Ok(json!({ "id": id, "cleared": cleared }))
+}
@@ -0,0 +1,540 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::plugins::{self, Capability, Manifest};
+use crate::settings::{PluginRegistrySettings, SettingsStore};
+use crate::{bandwidth, crypto, net};
+
+const STAGING_DIR: &str = "staging/plugins";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long a verified package waits for the operator's answer.
+const CONFIRM_WINDOW: Duration = Duration::from_secs(600);
+const MAX_INDEX_BYTES: usize = 4 * 1024 * 1024;
+const MAX_PACKAGE_BYTES: usize = 32 * 1024 * 1024;
+const MAX_TRUSTED_KEYS: usize = 16;
+
+/// One plugin in the registry index.
+#[derive(Clone, Debug, Deserialize)]
+struct Listing {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    versions: Vec<Release>,
+}
+
+/// One published version. `signature` is a base64 Ed25519 signature over
+/// the package bytes; `package` may be relative to the index URL.
+#[derive(Clone, Debug, Deserialize)]
+struct Release {
+    version: String,
+    package: String,
+    sha256: String,
+    signature: String,
+    #[serde(default)]
+    published_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Index {
+    #[serde(default)]
+    plugins: Vec<Listing>,
+}
+
+/// A downloaded and verified package waiting for the operator to accept
+/// the capabilities it asks for.
+struct Staged {
+    manifest: Manifest,
+    manifest_bytes: Vec<u8>,
+    module_bytes: Vec<u8>,
+    signed_by: String,
+    at: Instant,
+}
+
+/// Verified packages by confirmation token.
+#[derive(Default)]
+pub struct PendingInstalls {
+    pending: Mutex<HashMap<String, Staged>>,
+}
+
+impl PendingInstalls {
+    fn stage(&self, staged: Staged) -> Result<String, String> {
+        let token: String = crypto::random_bytes::<16>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let mut pending = self
+            .pending
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Plugin install state poisoned"))?;
+        pending.retain(|_, s| s.at.elapsed() <= CONFIRM_WINDOW);
+        pending.insert(token.clone(), staged);
+        Ok(token)
+    }
+
+    fn take(&self, token: &str) -> Option<Staged> {
+        let mut pending = self.pending.lock().ok()?;
+        pending
+            .remove(token.trim())
+            .filter(|s| s.at.elapsed() <= CONFIRM_WINDOW)
+    }
+}
+
+pub fn decode_key(raw: &str) -> Result<VerifyingKey, String> {
+    let bytes = B64
+        .decode(raw.trim())
+        .map_err(|_| format!("Trusted key {:?} is not base64", raw))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("Trusted key {:?} is not 32 bytes", raw))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| format!("Trusted key {:?} is invalid", raw))
+}
+
+pub fn validate(settings: &PluginRegistrySettings) -> Result<(), String> {
+    let url = settings.url.trim();
+    if !url.is_empty() {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid plugin registry URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("The plugin registry must be an http/https URL".to_string());
+        }
+    }
+    if settings.trusted_keys.len() > MAX_TRUSTED_KEYS {
+        return Err(format!(
+            "At most {} trusted plugin keys are allowed",
+            MAX_TRUSTED_KEYS
+        ));
+    }
+    for key in &settings.trusted_keys {
+        decode_key(key)?;
+    }
+    Ok(())
+}
+
+/// Dotted numeric versions compare by number (`1.10` after `1.9`), with
+/// anything else falling back to text order.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Option<Vec<u64>> {
+        v.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(|p| p.parse().ok())
+            .collect()
+    };
+    match (parts(a), parts(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn latest(listing: &Listing) -> Option<&Release> {
+    listing
+        .versions
+        .iter()
+        .max_by(|a, b| compare_versions(&a.version, &b.version))
+}
+
+async fn download(url: &Url, limit: usize) -> Result<Vec<u8>, String> {
+    let response = net::client_for(url)?
+        .get(url.clone())
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| {
+            ErrorCode::CoreUnreachable.with(format!("Plugin registry unreachable: {}", e))
+        })?;
+    if !response.status().is_success() {
+        return Err(
+            ErrorCode::from_status(response.status().as_u16()).with(format!(
+                "Plugin registry answered {} for {}",
+                response.status().as_u16(),
+                url
+            )),
+        );
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > limit)
+    {
+        return Err(ErrorCode::InvalidInput.with(format!("{} is over {} bytes", url, limit)));
+    }
+    let headers = response.headers().clone();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| ErrorCode::CoreUnreachable.with(format!("Download failed: {}", e)))?;
+    bandwidth::opened(
+        url.as_str(),
+        bandwidth::DOWNLOADS,
+        bandwidth::request_bytes(&reqwest::Method::GET, url, &[], None),
+        bandwidth::response_bytes(&headers, bytes.len() as u64),
+    );
+    if bytes.len() > limit {
+        return Err(ErrorCode::InvalidInput.with(format!("{} is over {} bytes", url, limit)));
+    }
+    Ok(bytes.to_vec())
+}
+
+fn registry(store: &SettingsStore) -> Result<(Url, Vec<String>), String> {
+    let settings = store.snapshot().plugin_registry;
+    let url = settings.url.trim();
+    if url.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("No plugin registry is configured"));
+    }
+    let url = Url::parse(url).map_err(|e| ErrorCode::InvalidInput.with(e.to_string()))?;
+    Ok((url, settings.trusted_keys))
+}
+
+async fn fetch_index(url: &Url) -> Result<Vec<Listing>, String> {
+    let raw = download(url, MAX_INDEX_BYTES).await?;
+    let index: Index = serde_json::from_slice(&raw).map_err(|e| {
+        ErrorCode::CoreError.with(format!("Plugin registry index is invalid: {}", e))
+    })?;
+    Ok(index.plugins)
+}
+
+/// The trusted key that signed `package`, if any did.
+fn signer(package: &[u8], signature: &str, trusted_keys: &[String]) -> Result<String, String> {
+    let signature = B64
+        .decode(signature.trim())
+        .ok()
+        .and_then(|raw| Signature::from_slice(&raw).ok())
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Package signature is malformed"))?;
+    trusted_keys
+        .iter()
+        .find(|raw| decode_key(raw).is_ok_and(|key| key.verify_strict(package, &signature).is_ok()))
+        .cloned()
+        .ok_or_else(|| ErrorCode::Forbidden.with("Package is not signed by a trusted plugin key"))
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|_| ErrorCode::InvalidInput.with(format!("Package has no {}", name)))?;
+    let mut bytes = Vec::new();
+    entry
+        .take(MAX_PACKAGE_BYTES as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Read {} failed: {}", name, e)))?;
+    if bytes.len() > MAX_PACKAGE_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!("{} is too large", name)));
+    }
+    Ok(bytes)
+}
+
+/// Downloads `release` of plugin `id` and checks its digest, signature
+/// and manifest; only `plugin.json` and `module.wasm` are taken from it.
+async fn fetch_release(
+    index_url: &Url,
+    trusted_keys: &[String],
+    id: &str,
+    release: &Release,
+) -> Result<Staged, String> {
+    let url = index_url
+        .join(&release.package)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid package URL: {}", e)))?;
+    let package = download(&url, MAX_PACKAGE_BYTES).await?;
+    let digest: String = Sha256::digest(&package)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if !digest.eq_ignore_ascii_case(release.sha256.trim()) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Package digest {} does not match the registry's {}",
+            digest, release.sha256
+        )));
+    }
+    let signed_by = signer(&package, &release.signature, trusted_keys)?;
+    let mut archive = zip::ZipArchive::new(Cursor::new(package.as_slice()))
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Package is not a zip: {}", e)))?;
+    let manifest_bytes = read_entry(&mut archive, plugins::MANIFEST_FILE)?;
+    let module_bytes = read_entry(&mut archive, plugins::MODULE_FILE)?;
+    let manifest: Manifest = serde_json::from_slice(&manifest_bytes).map_err(|e| {
+        ErrorCode::InvalidInput.with(format!("{} is invalid: {}", plugins::MANIFEST_FILE, e))
+    })?;
+    plugins::check_manifest(&manifest, id).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    if manifest.version != release.version {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Package is version {:?}, the registry lists {:?}",
+            manifest.version, release.version
+        )));
+    }
+    Ok(Staged {
+        manifest,
+        manifest_bytes,
+        module_bytes,
+        signed_by,
+        at: Instant::now(),
+    })
+}
+
+/// Swaps the plugin's directory for the staged files, so a failed write
+/// leaves the installed version in place.
+fn write_plugin(app: &AppHandle, staged: &Staged) -> Result<(), String> {
+    let io = |e: std::io::Error| ErrorCode::LocalIo.with(e.to_string());
+    let staging = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ErrorCode::LocalIo.with(e.to_string()))?
+        .join(STAGING_DIR)
+        .join(&staged.manifest.id);
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging).map_err(io)?;
+    fs::write(staging.join(plugins::MANIFEST_FILE), &staged.manifest_bytes).map_err(io)?;
+    fs::write(staging.join(plugins::MODULE_FILE), &staged.module_bytes).map_err(io)?;
+    let root = plugins::plugins_dir(app)?;
+    fs::create_dir_all(&root).map_err(io)?;
+    let target = root.join(&staged.manifest.id);
+    remove_dir(&target)?;
+    fs::rename(&staging, &target).map_err(io)
+}
+
+fn remove_dir(dir: &Path) -> Result<(), String> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(ErrorCode::LocalIo.with(format!("Remove {} failed: {}", dir.display(), e))),
+    }
+}
+
+fn installed_version(app: &AppHandle, id: &str) -> Option<String> {
+    let dir = plugins::plugins_dir(app).ok()?.join(id);
+    let raw = fs::read(dir.join(plugins::MANIFEST_FILE)).ok()?;
+    serde_json::from_slice::<Manifest>(&raw)
+        .ok()
+        .map(|m| m.version)
+}
+
+/// The registry's plugins, each with its versions newest first, what is
+/// installed and pinned, and whether an update is available.
+#[tauri::command]
+pub async fn browse_plugin_registry(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+) -> Result<Value, String> {
+    let (url, _) = registry(&store)?;
+    let listings = fetch_index(&url).await?;
+    let grants = store.snapshot().plugins;
+    let plugins: Vec<Value> = listings
+        .into_iter()
+        .filter(|l| plugins::valid_id(&l.id))
+        .map(|mut listing| {
+            listing
+                .versions
+                .sort_by(|a, b| compare_versions(&b.version, &a.version));
+            let installed = installed_version(&app, &listing.id);
+            let pin = grants
+                .iter()
+                .find(|g| g.id == listing.id)
+                .and_then(|g| g.pin.clone());
+            let target = match &pin {
+                Some(pin) => listing.versions.iter().find(|r| r.version == *pin),
+                None => latest(&listing),
+            };
+            let update = match (&installed, target) {
+                (Some(installed), Some(target)) => {
+                    compare_versions(&target.version, installed) == Ordering::Greater
+                }
+                _ => false,
+            };
+            json!({
+                "id": listing.id,
+                "name": listing.name,
+                "description": listing.description,
+                "versions": listing.versions.iter().map(|r| json!({
+                    "version": r.version,
+                    "published_at": r.published_at,
+                })).collect::<Vec<_>>(),
+                "installed": installed,
+                "pin": pin,
+                "update_available": update,
+            })
+        })
+        .collect();
+    Ok(json!({ "registry": url.to_string(), "plugins": plugins }))
+}
+
+/// Downloads and verifies plugin `id` at `version` (default: its pin, else
+/// the latest), then asks for the operator's go-ahead: the answer lists the
+/// capabilities, core paths and events the plugin wants, and a `token` for
+/// `confirm_plugin_install`. Nothing is written until then.
+#[tauri::command]
+pub async fn install_plugin(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    pending: State<'_, PendingInstalls>,
+    id: String,
+    version: Option<String>,
+) -> Result<Value, String> {
+    let id = id.trim().to_string();
+    let (url, trusted_keys) = registry(&store)?;
+    if trusted_keys.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("No trusted plugin keys are configured"));
+    }
+    let listing = fetch_index(&url)
+        .await?
+        .into_iter()
+        .find(|l| l.id == id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("The registry has no plugin {}", id)))?;
+    let grant = store.snapshot().plugins.into_iter().find(|g| g.id == id);
+    let pin = grant.as_ref().and_then(|g| g.pin.clone());
+    let wanted = version
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .or(pin.clone());
+    if let (Some(pin), Some(wanted)) = (&pin, &wanted) {
+        if pin != wanted {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Plugin {} is pinned to {}; unpin it to install {}",
+                id, pin, wanted
+            )));
+        }
+    }
+    let release = match &wanted {
+        Some(wanted) => listing.versions.iter().find(|r| r.version == *wanted),
+        None => latest(&listing),
+    }
+    .ok_or_else(|| {
+        ErrorCode::NotFound.with(format!(
+            "The registry has no version {} of {}",
+            wanted.as_deref().unwrap_or("at all"),
+            id
+        ))
+    })?;
+    let staged = fetch_release(&url, &trusted_keys, &id, release).await?;
+    let granted = grant.map(|g| g.capabilities).unwrap_or_default();
+    let new_capabilities: Vec<Capability> = staged
+        .manifest
+        .capabilities
+        .iter()
+        .filter(|c| !granted.contains(c))
+        .copied()
+        .collect();
+    let mut out = json!({
+        "confirmation_required": true,
+        "id": id,
+        "version": staged.manifest.version,
+        "installed": installed_version(&app, &id),
+        "name": staged.manifest.name,
+        "capabilities": staged.manifest.capabilities,
+        "new_capabilities": new_capabilities,
+        "core_paths": staged.manifest.core_paths,
+        "events": staged.manifest.events,
+        "network_hosts": staged.manifest.network_hosts,
+        "signed_by": staged.signed_by,
+    });
+    out["token"] = json!(pending.stage(staged)?);
+    Ok(out)
+}
+
+/// Installs the package `install_plugin` staged under `token`, granting it
+/// `capabilities` (those of its manifest the operator accepted) and
+/// enabling it.
+#[tauri::command]
+pub async fn confirm_plugin_install(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    pending: State<'_, PendingInstalls>,
+    token: String,
+    capabilities: Vec<Capability>,
+) -> Result<Value, String> {
+    let staged = pending.take(&token).ok_or_else(|| {
+        ErrorCode::InvalidInput.with("That install expired or was already confirmed")
+    })?;
+    let manifest = staged.manifest.clone();
+    let granted: Vec<Capability> = manifest
+        .capabilities
+        .iter()
+        .filter(|c| capabilities.contains(c))
+        .copied()
+        .collect();
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || write_plugin(&app_handle, &staged))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(format!("Install task failed: {}", e)))??;
+    store.update(&app, |settings| {
+        let grant = plugins::grant_mut(&mut settings.plugins, &manifest.id);
+        grant.enabled = true;
+        grant.capabilities = granted.clone();
+        Ok(())
+    })?;
+    let out = json!({
+        "id": manifest.id,
+        "version": manifest.version,
+        "capabilities": granted,
+    });
+    let profile_id = store.snapshot().active_profile;
+    db::record_audit(
+        &*db.lock()?,
+        &profile_id,
+        "plugin",
+        "install",
+        &manifest.id,
+        &out,
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    plugins::reload(&app).await?;
+    Ok(out)
+}
+
+/// Uninstalls plugin `id` and forgets its grant and pin.
+#[tauri::command]
+pub async fn remove_plugin(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    id: String,
+) -> Result<Value, String> {
+    let id = id.trim().to_string();
+    if !plugins::valid_id(&id) {
+        return Err(ErrorCode::InvalidInput.with(format!("Invalid plugin id: {}", id)));
+    }
+    let version = installed_version(&app, &id);
+    remove_dir(&plugins::plugins_dir(&app)?.join(&id))?;
+    store.update(&app, |settings| {
+        settings.plugins.retain(|g| g.id != id);
+        Ok(())
+    })?;
+    let out = json!({ "id": id, "version": version });
+    let profile_id = store.snapshot().active_profile;
+    db::record_audit(&*db.lock()?, &profile_id, "plugin", "remove", &id, &out)
+        .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    plugins::reload(&app).await?;
+    Ok(out)
+}
+
+/// Pins plugin `id` to `version`, or unpins it when `version` is empty.
+#[tauri::command]
+pub fn pin_plugin(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+    version: Option<String>,
+) -> Result<Value, String> {
+    let id = id.trim().to_string();
+    let pin = version
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    store.update(&app, |settings| {
+        plugins::grant_mut(&mut settings.plugins, &id).pin = pin.clone();
+        Ok(())
+    })?;
+    Ok(json!({ "id": id, "pin": pin }))
+}
@@ -0,0 +1,1300 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::{Method, Url};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, EventId, Listener, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::environment;
+use crate::errors::ErrorCode;
+use crate::settings::{PluginGrant, SettingsStore};
+use crate::{core_path, net, plugin_permissions, safe_mode, ssrf, tenants};
+
+pub const SUPPORTED: bool = cfg!(feature = "plugins");
+pub const PLUGINS_DIR: &str = "plugins";
+pub const MANIFEST_FILE: &str = "plugin.json";
+pub const MODULE_FILE: &str = "module.wasm";
+/// Host functions live under this import module name.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const IMPORT_MODULE: &str = "novaadapt";
+/// Shell events a plugin with `subscribe` may receive through `on_event`.
+const SUBSCRIBABLE: &[&str] = &[
+    "approvals:pending",
+    "core:maintenance",
+    "core:maintenance_ended",
+    "deadline:expired",
+    "decision:cancelled",
+    "decision:sent",
+    "runtime:halted",
+    "schedule:fired",
+    "sync:changed",
+];
+const MAX_GRANTS: usize = 100;
+const MAX_ID_LEN: usize = 64;
+const MAX_PALETTE_ENTRIES: usize = 50;
+const MAX_CACHE_ITEMS: usize = 100;
+const KEEP_LOGS: usize = 100;
+const MAX_AUDIT_ROWS: usize = 200;
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const NETWORK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const MAX_NETWORK_BYTES: usize = 1024 * 1024;
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const MAX_NETWORK_REDIRECTS: usize = 5;
+/// Instructions' worth of fuel one call may burn before it is stopped.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const FUEL: u64 = 2_000_000_000;
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Largest JSON message passed either way across the module boundary.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+const MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// What a plugin may do. The manifest asks for capabilities; it only gets
+/// those the operator also granted in settings. The sensitive ones it asks
+/// for but was neither granted nor denied are put to the operator the
+/// first time a call needs them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// GET requests to the core, under the manifest's `core_paths`.
+    CoreRead,
+    /// Any request to the core, under the manifest's `core_paths`.
+    CoreWrite,
+    /// The local plan and job cache.
+    CacheRead,
+    /// Events to the windows, as `plugin:<id>:<event>`.
+    Emit,
+    /// Command palette entries that run one of the plugin's commands.
+    Palette,
+    /// The shell events the manifest lists, delivered to `on_event`.
+    Subscribe,
+    /// The local audit log, most recent rows first.
+    AuditRead,
+    /// HTTPS requests to the manifest's `network_hosts`, outside the core.
+    Network,
+}
+
+impl Capability {
+    /// Whether the operator is asked before a plugin first uses it.
+    pub fn sensitive(self) -> bool {
+        matches!(
+            self,
+            Capability::CoreWrite | Capability::AuditRead | Capability::Network
+        )
+    }
+}
+
+/// `plugin.json`, next to `module.wasm` in `plugins/<id>/` under the app
+/// data dir.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub capabilities: Vec<Capability>,
+    /// Core path prefixes the plugin's requests must fall under.
+    pub core_paths: Vec<String>,
+    /// Shell events it subscribes to.
+    pub events: Vec<String>,
+    /// Hosts `network` requests may go to.
+    pub network_hosts: Vec<String>,
+}
+
+/// A palette entry a plugin added; choosing it runs `command` with `input`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaletteEntry {
+    #[serde(default)]
+    pub plugin_id: String,
+    pub id: String,
+    pub title: String,
+    pub command: String,
+    #[serde(default)]
+    pub input: Value,
+}
+
+struct Loaded {
+    manifest: Manifest,
+    error: Option<String>,
+    palette: Vec<PaletteEntry>,
+    logs: VecDeque<String>,
+    listeners: Vec<EventId>,
+    #[cfg(feature = "plugins")]
+    module: Option<wasmtime::Module>,
+}
+
+#[derive(Default)]
+struct Inner {
+    plugins: BTreeMap<String, Loaded>,
+    #[cfg(feature = "plugins")]
+    runtime: Option<std::sync::Arc<wasm::Runtime>>,
+}
+
+/// The installed plugins, as last loaded from disk.
+#[derive(Default)]
+pub struct PluginHost {
+    inner: Mutex<Inner>,
+}
+
+impl PluginHost {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Inner>, String> {
+        self.inner
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Plugin host state poisoned"))
+    }
+}
+
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+pub fn validate(grants: &[PluginGrant]) -> Result<(), String> {
+    if grants.len() > MAX_GRANTS {
+        return Err(format!("At most {} plugin grants are allowed", MAX_GRANTS));
+    }
+    let mut ids = HashSet::new();
+    for grant in grants {
+        if !valid_id(&grant.id) {
+            return Err(format!(
+                "Plugin id {:?} must be 1 to {} lowercase letters, digits, - or _",
+                grant.id, MAX_ID_LEN
+            ));
+        }
+        if !ids.insert(grant.id.as_str()) {
+            return Err(format!("Duplicate plugin grant: {}", grant.id));
+        }
+    }
+    Ok(())
+}
+
+pub fn check_manifest(manifest: &Manifest, dir_name: &str) -> Result<(), String> {
+    if manifest.id != dir_name {
+        return Err(format!(
+            "Manifest id {:?} does not match its directory {:?}",
+            manifest.id, dir_name
+        ));
+    }
+    if manifest.name.trim().is_empty() {
+        return Err("Manifest name is required".to_string());
+    }
+    for prefix in &manifest.core_paths {
+        if prefix_segments(prefix).is_err() {
+            return Err(format!("Core path {:?} must be a path under /", prefix));
+        }
+    }
+    for host in &manifest.network_hosts {
+        let bare = host.trim();
+        if bare.is_empty() || bare.contains(['/', ':', '@', '*']) || bare != host {
+            return Err(format!(
+                "Network host {:?} must be a bare host name, without scheme, port or path",
+                host
+            ));
+        }
+    }
+    for event in &manifest.events {
+        if !SUBSCRIBABLE.contains(&event.as_str()) {
+            return Err(format!(
+                "Event {:?} cannot be subscribed to (use {})",
+                event,
+                SUBSCRIBABLE.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The capabilities `manifest` gets: asked for, granted and enabled.
+fn effective(manifest: &Manifest, grant: Option<&PluginGrant>) -> Vec<Capability> {
+    match grant {
+        Some(grant) if grant.enabled => manifest
+            .capabilities
+            .iter()
+            .filter(|c| grant.capabilities.contains(c))
+            .copied()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The sensitive capabilities `manifest` asks for that its grant neither
+/// includes nor denies, which are put to the operator on first use.
+fn askable(manifest: &Manifest, grant: Option<&PluginGrant>) -> Vec<Capability> {
+    match grant {
+        Some(grant) if grant.enabled => manifest
+            .capabilities
+            .iter()
+            .filter(|c| c.sensitive())
+            .filter(|c| !grant.capabilities.contains(c) && !grant.denied.contains(c))
+            .copied()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The grant for plugin `id`, added disabled and empty when it has none.
+pub fn grant_mut<'a>(grants: &'a mut Vec<PluginGrant>, id: &str) -> &'a mut PluginGrant {
+    let index = match grants.iter().position(|g| g.id == id) {
+        Some(index) => index,
+        None => {
+            grants.push(PluginGrant {
+                id: id.to_string(),
+                ..PluginGrant::default()
+            });
+            grants.len() - 1
+        }
+    };
+    &mut grants[index]
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+fn enabled(store: &SettingsStore, id: &str) -> Option<PluginGrant> {
+    store
+        .snapshot()
+        .plugins
+        .into_iter()
+        .find(|g| g.id == id && g.enabled)
+}
+
+pub fn plugins_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(PLUGINS_DIR))
+        .map_err(|e| ErrorCode::LocalIo.with(e.to_string()))
+}
+
+/// Reads one plugin directory: its manifest, and its module once compiled.
+fn discover(dir: &Path) -> (Manifest, Result<Vec<u8>, String>) {
+    let dir_name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let manifest = fs::read(dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Read {} failed: {}", MANIFEST_FILE, e))
+        .and_then(|raw| {
+            serde_json::from_slice::<Manifest>(&raw)
+                .map_err(|e| format!("{} is invalid: {}", MANIFEST_FILE, e))
+        });
+    match manifest {
+        Ok(manifest) => {
+            let module = check_manifest(&manifest, &dir_name).and_then(|_| {
+                fs::read(dir.join(MODULE_FILE))
+                    .map_err(|e| format!("Read {} failed: {}", MODULE_FILE, e))
+            });
+            (manifest, module)
+        }
+        Err(err) => (
+            Manifest {
+                id: dir_name.clone(),
+                name: dir_name,
+                ..Manifest::default()
+            },
+            Err(err),
+        ),
+    }
+}
+
+/// A `core_paths` entry's segments; a trailing `/` is allowed.
+fn prefix_segments(prefix: &str) -> Result<Vec<String>, String> {
+    match prefix.strip_suffix('/') {
+        Some("") | None => core_path::segments(prefix),
+        Some(prefix) => core_path::segments(prefix),
+    }
+}
+
+/// Whether `path` is under one of `core_paths`, compared segment by segment
+/// once decoded, so `%2e%2e` or a shared name prefix cannot leave them.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+fn may_reach(core_paths: &[String], path: &str) -> Result<bool, String> {
+    let path = core_path::segments(path)
+        .map_err(|_| ErrorCode::InvalidInput.with("Path must be a core path starting with /"))?;
+    Ok(core_paths
+        .iter()
+        .any(|prefix| prefix_segments(prefix).is_ok_and(|prefix| core_path::under(&path, &prefix))))
+}
+
+/// Refuses a network request, or a hop of one, that is not a plain `https`
+/// request to one of `network_hosts`.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+fn network_target(plugin_id: &str, network_hosts: &[String], url: &Url) -> Result<(), String> {
+    let host = url.host_str().unwrap_or_default();
+    if url.scheme() != "https" || !url.username().is_empty() || url.password().is_some() {
+        return Err(ErrorCode::InvalidInput.with("Plugins may only make plain https requests"));
+    }
+    if !network_hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+        return Err(
+            ErrorCode::Forbidden.with(format!("Plugin {} may not reach {}", plugin_id, host))
+        );
+    }
+    Ok(())
+}
+
+/// Follows a redirect only to a target the request itself could have
+/// named, so a listed host cannot send it on to one that is not.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+fn network_redirects(plugin_id: String, network_hosts: Vec<String>) -> Policy {
+    Policy::custom(move |attempt: Attempt| {
+        if attempt.previous().len() > MAX_NETWORK_REDIRECTS {
+            let error = format!("More than {} redirects", MAX_NETWORK_REDIRECTS);
+            return attempt.error(ssrf::Blocked(error));
+        }
+        let url = attempt.url().clone();
+        match network_target(&plugin_id, &network_hosts, &url)
+            .and_then(|()| ssrf::check_url(url.as_str(), &url))
+        {
+            Ok(()) => attempt.follow(),
+            Err(reason) => attempt.error(ssrf::Blocked(format!("Redirect refused: {}", reason))),
+        }
+    })
+}
+
+/// What one plugin call may touch, checked on every host function call.
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+struct Sandbox {
+    app: AppHandle,
+    plugin_id: String,
+    capabilities: Vec<Capability>,
+    /// Asked for on first use; see `askable`.
+    askable: Vec<Capability>,
+    /// The operator's answers for this call, so it is asked once per call.
+    answered: Mutex<Vec<(Capability, bool)>>,
+    core_paths: Vec<String>,
+    network_hosts: Vec<String>,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+#[derive(Deserialize)]
+struct CoreCall {
+    #[serde(default = "default_method")]
+    method: String,
+    path: String,
+    #[serde(default)]
+    payload: Option<Value>,
+    #[serde(default)]
+    profile: Option<String>,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+#[derive(Deserialize)]
+struct CacheQuery {
+    kind: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    profile: Option<String>,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+#[derive(Deserialize)]
+struct AuditQuery {
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    profile: Option<String>,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+#[derive(Deserialize)]
+struct NetworkCall {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Option<Value>,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+#[derive(Deserialize)]
+struct Emission {
+    event: String,
+    #[serde(default)]
+    payload: Value,
+}
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+impl Sandbox {
+    /// Passes when `capability` was granted, or when it is askable and the
+    /// operator allows `action` now.
+    fn require(&self, capability: Capability, action: &str) -> Result<(), String> {
+        if self.capabilities.contains(&capability) {
+            return Ok(());
+        }
+        if self.askable.contains(&capability) {
+            let mut answered = self
+                .answered
+                .lock()
+                .map_err(|_| ErrorCode::Internal.with("Plugin call state poisoned"))?;
+            if let Some((_, allowed)) = answered.iter().find(|(c, _)| *c == capability) {
+                if *allowed {
+                    return Ok(());
+                }
+            } else {
+                let answer =
+                    plugin_permissions::ask(&self.app, &self.plugin_id, capability, action);
+                answered.push((capability, answer.is_ok()));
+                return answer;
+            }
+        }
+        Err(ErrorCode::Forbidden.with(format!(
+            "Plugin {} was not granted {}",
+            self.plugin_id,
+            json!(capability).as_str().unwrap_or_default()
+        )))
+    }
+
+    /// Runs host function `name` on `request`, answering `{ok, result}` or
+    /// `{ok: false, error}` so the plugin can handle a refusal.
+    fn handle(&self, name: &str, request: Value) -> Value {
+        let result = match name {
+            "core_request" => self.core_request(request),
+            "cache_read" => self.cache_read(request),
+            "audit_read" => self.audit_read(request),
+            "http_request" => self.http_request(request),
+            "emit" => self.emit(request),
+            "add_palette_entry" => self.add_palette_entry(request),
+            "log" => self.log(request),
+            other => Err(ErrorCode::InvalidInput.with(format!("Unknown host function {}", other))),
+        };
+        match result {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        }
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(request: Value) -> Result<T, String> {
+        serde_json::from_value(request).map_err(|e| ErrorCode::InvalidInput.with(e.to_string()))
+    }
+
+    /// A core request under one of the manifest's path prefixes. Anything
+    /// but a GET needs `core_write` and goes in the local audit trail.
+    fn core_request(&self, request: Value) -> Result<Value, String> {
+        let call: CoreCall = Self::parse(request)?;
+        let method = Method::from_bytes(call.method.trim().to_ascii_uppercase().as_bytes())
+            .map_err(|_| ErrorCode::InvalidInput.with("Invalid method"))?;
+        let read = method == Method::GET;
+        let path = call.path.trim();
+        if !may_reach(&self.core_paths, path)? {
+            return Err(ErrorCode::Forbidden
+                .with(format!("Plugin {} may not reach {}", self.plugin_id, path)));
+        }
+        if !(read && self.capabilities.contains(&Capability::CoreRead)) {
+            self.require(Capability::CoreWrite, &format!("{} {}", method, path))?;
+        }
+        let store = self.app.state::<SettingsStore>();
+        let conn = store.connection(call.profile.as_deref())?;
+        let result = tauri::async_runtime::block_on(crate::request_json(
+            method.clone(),
+            &conn.base_url,
+            path,
+            conn.token.clone(),
+            call.payload,
+        ));
+        if !read {
+            let detail = json!({
+                "method": method.as_str(),
+                "path": path,
+                "ok": result.is_ok(),
+                "error": result.as_ref().err(),
+            });
+            let db = self.app.state::<LocalDb>();
+            db::record_audit(
+                &*db.lock()?,
+                &conn.profile_id,
+                "plugin",
+                "core_request",
+                &self.plugin_id,
+                &detail,
+            )
+            .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+        }
+        result
+    }
+
+    /// One cached plan or job by `id`, or the most recently synced ones.
+    fn cache_read(&self, request: Value) -> Result<Value, String> {
+        self.require(Capability::CacheRead, "read the local plan and job cache")?;
+        let query: CacheQuery = Self::parse(request)?;
+        let kind = match query.kind.trim() {
+            "plan" | "plans" => "plan",
+            "job" | "jobs" => "job",
+            _ => return Err(ErrorCode::InvalidInput.with("Kind must be `plan` or `job`")),
+        };
+        let profile_id = self
+            .app
+            .state::<SettingsStore>()
+            .connection(query.profile.as_deref())?
+            .profile_id;
+        let db = self.app.state::<LocalDb>();
+        let guard = db.lock()?;
+        let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+        let parse = |raw: String| serde_json::from_str::<Value>(&raw).unwrap_or(Value::Null);
+        if let Some(id) = query.id {
+            let item = guard
+                .query_row(
+                    "SELECT payload FROM sync_entities
+                     WHERE profile_id = ?1 AND kind = ?2 AND entity_id = ?3",
+                    params![tenants::cache_scope(&profile_id), kind, id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(storage)?
+                .map(parse);
+            return Ok(json!({ "profile_id": profile_id, "item": item }));
+        }
+        let limit = query.limit.unwrap_or(20).clamp(1, MAX_CACHE_ITEMS);
+        let mut stmt = guard
+            .prepare(
+                "SELECT payload FROM sync_entities WHERE profile_id = ?1 AND kind = ?2
+                 ORDER BY synced_at DESC LIMIT ?3",
+            )
+            .map_err(storage)?;
+        let items: Vec<Value> = stmt
+            .query_map(
+                params![tenants::cache_scope(&profile_id), kind, limit as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(storage)?
+            .into_iter()
+            .map(parse)
+            .collect();
+        Ok(json!({ "profile_id": profile_id, "items": items }))
+    }
+
+    /// The profile's most recent local audit rows, optionally of one
+    /// category.
+    fn audit_read(&self, request: Value) -> Result<Value, String> {
+        let query: AuditQuery = Self::parse(request)?;
+        let profile_id = self
+            .app
+            .state::<SettingsStore>()
+            .connection(query.profile.as_deref())?
+            .profile_id;
+        self.require(
+            Capability::AuditRead,
+            &format!("read the local audit log of profile {}", profile_id),
+        )?;
+        let limit = query.limit.unwrap_or(50).clamp(1, MAX_AUDIT_ROWS);
+        let category = query.category.unwrap_or_default().trim().to_string();
+        let db = self.app.state::<LocalDb>();
+        let guard = db.lock()?;
+        let mut stmt = guard
+            .prepare(
+                "SELECT id, at, category, action, entity_id, detail FROM audit_log
+                 WHERE profile_id = ?1 AND (?2 = '' OR category = ?2)
+                 ORDER BY id DESC LIMIT ?3",
+            )
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        let rows: Vec<Value> = stmt
+            .query_map(params![profile_id, category, limit as i64], |row| {
+                let detail: String = row.get(5)?;
+                Ok(json!({
+                    "id": row.get::<_, i64>(0)?,
+                    "at": row.get::<_, String>(1)?,
+                    "category": row.get::<_, String>(2)?,
+                    "action": row.get::<_, String>(3)?,
+                    "entity_id": row.get::<_, String>(4)?,
+                    "detail": serde_json::from_str::<Value>(&detail).unwrap_or(Value::Null),
+                }))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        Ok(json!({ "profile_id": profile_id, "rows": rows }))
+    }
+
+    /// An HTTPS request to one of the manifest's `network_hosts`, checked
+    /// like core requests against link-local and metadata targets, and
+    /// audited.
+    fn http_request(&self, request: Value) -> Result<Value, String> {
+        let call: NetworkCall = Self::parse(request)?;
+        let method = Method::from_bytes(call.method.trim().to_ascii_uppercase().as_bytes())
+            .map_err(|_| ErrorCode::InvalidInput.with("Invalid method"))?;
+        let url = Url::parse(call.url.trim())
+            .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        network_target(&self.plugin_id, &self.network_hosts, &url)?;
+        self.require(Capability::Network, &format!("{} {}", method, url.as_str()))?;
+        ssrf::check_url(url.as_str(), &url)?;
+        let redirects = network_redirects(self.plugin_id.clone(), self.network_hosts.clone());
+        let mut builder = net::client_with_redirects(&url, redirects)?
+            .request(method.clone(), url.clone())
+            .timeout(NETWORK_TIMEOUT);
+        for (name, value) in &call.headers {
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| ErrorCode::InvalidInput.with(format!("Invalid header {:?}", name)))?;
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                ErrorCode::InvalidInput.with(format!("Invalid value for header {}", name))
+            })?;
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = call.body {
+            builder = match body {
+                Value::String(text) => builder.body(text),
+                other => builder.json(&other),
+            };
+        }
+        let result = tauri::async_runtime::block_on(async {
+            let response = builder.send().await.map_err(|e| {
+                ErrorCode::CoreUnreachable.with(format!("{} unreachable: {}", host, e))
+            })?;
+            let status = response.status().as_u16();
+            let bytes = response.bytes().await.map_err(|e| {
+                ErrorCode::CoreUnreachable.with(format!("Read from {} failed: {}", host, e))
+            })?;
+            if bytes.len() > MAX_NETWORK_BYTES {
+                return Err(ErrorCode::InvalidInput.with(format!(
+                    "Response from {} is over {} bytes",
+                    host, MAX_NETWORK_BYTES
+                )));
+            }
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            Ok(json!({
+                "status": status,
+                "json": serde_json::from_str::<Value>(&text).ok(),
+                "body": text,
+            }))
+        });
+        let db = self.app.state::<LocalDb>();
+        db::record_audit(
+            &*db.lock()?,
+            "",
+            "plugin",
+            "http_request",
+            &self.plugin_id,
+            &json!({
+                "method": method.as_str(),
+                "url": url.as_str(),
+                "status": result.as_ref().ok().map(|r| r["status"].clone()),
+                "error": result.as_ref().err(),
+            }),
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+        result
+    }
+
+    fn emit(&self, request: Value) -> Result<Value, String> {
+        self.require(Capability::Emit, "send events to the windows")?;
+        let emission: Emission = Self::parse(request)?;
+        if !valid_id(&emission.event) {
+            return Err(
+                ErrorCode::InvalidInput.with("Event names are lowercase letters, digits, - or _")
+            );
+        }
+        let event = format!("plugin:{}:{}", self.plugin_id, emission.event);
+        environment::emit(&self.app, &event, emission.payload)
+            .map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+        Ok(json!({ "event": event }))
+    }
+
+    /// Adds or replaces (by id) one of the plugin's palette entries.
+    fn add_palette_entry(&self, request: Value) -> Result<Value, String> {
+        self.require(Capability::Palette, "add command palette entries")?;
+        let mut entry: PaletteEntry = Self::parse(request)?;
+        entry.plugin_id = self.plugin_id.clone();
+        if !valid_id(&entry.id) || entry.title.trim().is_empty() || entry.command.is_empty() {
+            return Err(ErrorCode::InvalidInput.with(
+                "A palette entry needs an id (lowercase letters, digits, - or _), a title and a command",
+            ));
+        }
+        let host = self.app.state::<PluginHost>();
+        let mut inner = host.lock()?;
+        let loaded = inner
+            .plugins
+            .get_mut(&self.plugin_id)
+            .ok_or_else(|| ErrorCode::NotFound.with("Plugin is no longer loaded"))?;
+        match loaded.palette.iter().position(|e| e.id == entry.id) {
+            Some(index) => loaded.palette[index] = entry.clone(),
+            None if loaded.palette.len() >= MAX_PALETTE_ENTRIES => {
+                return Err(ErrorCode::InvalidInput.with(format!(
+                    "At most {} palette entries per plugin",
+                    MAX_PALETTE_ENTRIES
+                )))
+            }
+            None => loaded.palette.push(entry.clone()),
+        }
+        Ok(json!({ "id": entry.id }))
+    }
+
+    /// Keeps the line for `list_plugins`; logging needs no capability.
+    fn log(&self, request: Value) -> Result<Value, String> {
+        let line = match request {
+            Value::String(s) => s,
+            other => other.to_string(),
+        };
+        let host = self.app.state::<PluginHost>();
+        let mut inner = host.lock()?;
+        if let Some(loaded) = inner.plugins.get_mut(&self.plugin_id) {
+            if loaded.logs.len() >= KEEP_LOGS {
+                loaded.logs.pop_front();
+            }
+            loaded.logs.push_back(format!(
+                "{} {}",
+                chrono::Utc::now().to_rfc3339(),
+                line.chars().take(2000).collect::<String>()
+            ));
+        }
+        Ok(Value::Null)
+    }
+}
+
+/// The WebAssembly side. A plugin module exports `memory` and
+/// `alloc(len) -> ptr`, and any of `init`, `on_event` and `command`; those
+/// and every host function take a JSON message as `(ptr, len)` and return
+/// one packed as `ptr << 32 | len` (0 for none). Each call gets a fresh
+/// instance with its own fuel and memory limits.
+#[cfg(feature = "plugins")]
+mod wasm {
+    use serde_json::Value;
+    use wasmtime::{
+        AsContextMut, Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits,
+        StoreLimitsBuilder, Trap, TypedFunc,
+    };
+
+    use super::{Sandbox, FUEL, IMPORT_MODULE, MAX_MEMORY_BYTES, MAX_MESSAGE_BYTES};
+    use crate::errors::ErrorCode;
+
+    const HOST_FUNCTIONS: &[&str] = &[
+        "core_request",
+        "cache_read",
+        "audit_read",
+        "http_request",
+        "emit",
+        "add_palette_entry",
+        "log",
+    ];
+
+    struct Ctx {
+        sandbox: Sandbox,
+        limits: StoreLimits,
+    }
+
+    pub struct Runtime {
+        engine: Engine,
+        linker: Linker<Ctx>,
+    }
+
+    fn unpack(packed: i64) -> (u32, u32) {
+        ((packed as u64 >> 32) as u32, packed as u32)
+    }
+
+    fn read(
+        cx: impl AsContextMut,
+        memory: Memory,
+        ptr: u32,
+        len: u32,
+    ) -> wasmtime::Result<Option<Value>> {
+        if len == 0 {
+            return Ok(None);
+        }
+        if len as usize > MAX_MESSAGE_BYTES {
+            wasmtime::bail!("message of {} bytes is over the limit", len);
+        }
+        let mut buf = vec![0; len as usize];
+        memory.read(&cx, ptr as usize, &mut buf)?;
+        Ok(Some(serde_json::from_slice(&buf).unwrap_or(Value::Null)))
+    }
+
+    fn write(
+        mut cx: impl AsContextMut,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        value: &Value,
+    ) -> wasmtime::Result<i64> {
+        let bytes = serde_json::to_vec(value)?;
+        if bytes.len() > MAX_MESSAGE_BYTES {
+            wasmtime::bail!("message of {} bytes is over the limit", bytes.len());
+        }
+        let ptr = alloc.call(&mut cx, bytes.len() as i32)?;
+        memory.write(&mut cx, ptr as u32 as usize, &bytes)?;
+        Ok(((ptr as u32 as i64) << 32) | bytes.len() as i64)
+    }
+
+    fn guest_exports(
+        caller: &mut Caller<'_, Ctx>,
+    ) -> wasmtime::Result<(Memory, TypedFunc<i32, i32>)> {
+        let memory = caller
+            .get_export("memory")
+            .and_then(Extern::into_memory)
+            .ok_or_else(|| wasmtime::format_err!("module exports no memory"))?;
+        let alloc = caller
+            .get_export("alloc")
+            .and_then(Extern::into_func)
+            .ok_or_else(|| wasmtime::format_err!("module exports no alloc"))?
+            .typed::<i32, i32>(&caller)?;
+        Ok((memory, alloc))
+    }
+
+    fn failed(err: wasmtime::Error) -> String {
+        match err.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => ErrorCode::Internal.with("Plugin ran out of fuel"),
+            _ => ErrorCode::Internal.with(format!("Plugin failed: {:#}", err)),
+        }
+    }
+
+    impl Runtime {
+        pub fn new() -> Result<Self, String> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config).map_err(failed)?;
+            let mut linker = Linker::new(&engine);
+            for name in HOST_FUNCTIONS {
+                linker
+                    .func_wrap(
+                        IMPORT_MODULE,
+                        name,
+                        move |mut caller: Caller<'_, Ctx>, ptr: i32, len: i32| {
+                            let (memory, alloc) = guest_exports(&mut caller)?;
+                            let request = read(&mut caller, memory, ptr as u32, len as u32)?;
+                            let response = caller
+                                .data()
+                                .sandbox
+                                .handle(name, request.unwrap_or(Value::Null));
+                            write(&mut caller, memory, alloc, &response)
+                        },
+                    )
+                    .map_err(failed)?;
+            }
+            Ok(Self { engine, linker })
+        }
+
+        pub fn compile(&self, bytes: &[u8]) -> Result<Module, String> {
+            Module::new(&self.engine, bytes).map_err(failed)
+        }
+
+        /// Calls `export` with `input`; `None` when the module does not
+        /// export it or returns nothing.
+        pub fn call(
+            &self,
+            module: &Module,
+            sandbox: Sandbox,
+            export: &str,
+            input: &Value,
+        ) -> Result<Option<Value>, String> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .instances(1)
+                .build();
+            let mut store = Store::new(&self.engine, Ctx { sandbox, limits });
+            store.limiter(|ctx| &mut ctx.limits);
+            store.set_fuel(FUEL).map_err(failed)?;
+            let instance = self
+                .linker
+                .instantiate(&mut store, module)
+                .map_err(failed)?;
+            let Some(func) = instance.get_func(&mut store, export) else {
+                return Ok(None);
+            };
+            let func = func.typed::<(i32, i32), i64>(&store).map_err(failed)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| ErrorCode::InvalidInput.with("Plugin exports no memory"))?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut store, "alloc")
+                .map_err(failed)?;
+            let (ptr, len) = unpack(write(&mut store, memory, alloc, input).map_err(failed)?);
+            let packed = func
+                .call(&mut store, (ptr as i32, len as i32))
+                .map_err(failed)?;
+            let (ptr, len) = unpack(packed);
+            read(&mut store, memory, ptr, len).map_err(failed)
+        }
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn call(
+    app: &AppHandle,
+    plugin_id: &str,
+    export: &str,
+    input: &Value,
+) -> Result<Option<Value>, String> {
+    let store = app.state::<SettingsStore>();
+    let grant = enabled(&store, plugin_id);
+    let host = app.state::<PluginHost>();
+    let (runtime, module, manifest) =
+        {
+            let inner = host.lock()?;
+            let loaded = inner.plugins.get(plugin_id).ok_or_else(|| {
+                ErrorCode::NotFound.with(format!("Unknown plugin: {}", plugin_id))
+            })?;
+            if let Some(err) = &loaded.error {
+                return Err(ErrorCode::InvalidInput
+                    .with(format!("Plugin {} did not load: {}", plugin_id, err)));
+            }
+            match (&inner.runtime, &loaded.module) {
+                (Some(runtime), Some(module)) => {
+                    (runtime.clone(), module.clone(), loaded.manifest.clone())
+                }
+                _ => return Err(ErrorCode::Internal.with("Plugin runtime is not ready")),
+            }
+        };
+    if grant.is_none() {
+        return Err(ErrorCode::Forbidden.with(format!("Plugin {} is not enabled", plugin_id)));
+    }
+    let sandbox = Sandbox {
+        app: app.clone(),
+        plugin_id: plugin_id.to_string(),
+        capabilities: effective(&manifest, grant.as_ref()),
+        askable: askable(&manifest, grant.as_ref()),
+        answered: Mutex::new(Vec::new()),
+        core_paths: manifest.core_paths.clone(),
+        network_hosts: manifest.network_hosts.clone(),
+    };
+    runtime.call(&module, sandbox, export, input)
+}
+
+#[cfg(not(feature = "plugins"))]
+fn call(
+    _app: &AppHandle,
+    _plugin_id: &str,
+    _export: &str,
+    _input: &Value,
+) -> Result<Option<Value>, String> {
+    Err(ErrorCode::InvalidInput.with("Plugins need a build with the `plugins` feature"))
+}
+
+/// Runs `export` off the async runtime: plugin code and its host calls
+/// block.
+async fn call_blocking(
+    app: AppHandle,
+    plugin_id: String,
+    export: &'static str,
+    input: Value,
+) -> Result<Option<Value>, String> {
+    safe_mode::check("Plugins")?;
+    tauri::async_runtime::spawn_blocking(move || call(&app, &plugin_id, export, &input))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(format!("Plugin task failed: {}", e)))?
+}
+
+/// Subscribes an enabled plugin to the events it asked for and was granted.
+fn subscribe(app: &AppHandle, manifest: &Manifest, capabilities: &[Capability]) -> Vec<EventId> {
+    if !capabilities.contains(&Capability::Subscribe) {
+        return Vec::new();
+    }
+    manifest
+        .events
+        .iter()
+        .map(|event| {
+            let app = app.clone();
+            let plugin_id = manifest.id.clone();
+            let name = event.clone();
+            app.clone().listen_any(event.clone(), move |e| {
+                let payload = serde_json::from_str(e.payload()).unwrap_or(Value::Null);
+                let input = json!({ "event": name, "payload": payload });
+                let (app, plugin_id) = (app.clone(), plugin_id.clone());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(err) = call_blocking(app, plugin_id.clone(), "on_event", input).await
+                    {
+                        eprintln!("plugin {} on_event failed: {}", plugin_id, err);
+                    }
+                });
+            })
+        })
+        .collect()
+}
+
+/// Loads every plugin under the plugins dir afresh, unsubscribing the old
+/// ones, and runs `init` on those enabled.
+pub async fn reload(app: &AppHandle) -> Result<(), String> {
+    if safe_mode::active() {
+        return Ok(());
+    }
+    let dir = plugins_dir(app)?;
+    let host = app.state::<PluginHost>();
+    let store = app.state::<SettingsStore>();
+    let grants = store.snapshot().plugins;
+    let entries: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(read) => read
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_dir())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    #[cfg(feature = "plugins")]
+    let runtime = {
+        let existing = host.lock()?.runtime.clone();
+        match existing {
+            Some(runtime) => Some(runtime),
+            None => {
+                let created = tauri::async_runtime::spawn_blocking(wasm::Runtime::new)
+                    .await
+                    .map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+                match created {
+                    Ok(runtime) => Some(std::sync::Arc::new(runtime)),
+                    Err(err) => {
+                        eprintln!("plugin runtime unavailable: {}", err);
+                        None
+                    }
+                }
+            }
+        }
+    };
+
+    let mut plugins = BTreeMap::new();
+    for path in entries {
+        let (manifest, module) = discover(&path);
+        #[cfg(feature = "plugins")]
+        let (module, error) = match (module, &runtime) {
+            (Ok(bytes), Some(runtime)) => {
+                let runtime = runtime.clone();
+                let compiled =
+                    tauri::async_runtime::spawn_blocking(move || runtime.compile(&bytes))
+                        .await
+                        .map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+                match compiled {
+                    Ok(module) => (Some(module), None),
+                    Err(err) => (None, Some(err)),
+                }
+            }
+            (Ok(_), None) => (None, Some("Plugin runtime unavailable".to_string())),
+            (Err(err), _) => (None, Some(err)),
+        };
+        #[cfg(not(feature = "plugins"))]
+        let error = Some(
+            module
+                .err()
+                .unwrap_or_else(|| "Plugins need a build with the `plugins` feature".to_string()),
+        );
+        plugins.insert(
+            manifest.id.clone(),
+            Loaded {
+                manifest,
+                error,
+                palette: Vec::new(),
+                logs: VecDeque::new(),
+                listeners: Vec::new(),
+                #[cfg(feature = "plugins")]
+                module,
+            },
+        );
+    }
+
+    let ready: Vec<(String, Vec<Capability>)> = {
+        let mut inner = host.lock()?;
+        for old in inner.plugins.values_mut() {
+            for id in old.listeners.drain(..) {
+                app.unlisten(id);
+            }
+        }
+        for loaded in plugins.values_mut() {
+            let grant = grants.iter().find(|g| g.id == loaded.manifest.id);
+            let capabilities = effective(&loaded.manifest, grant);
+            if loaded.error.is_none() && grant.is_some_and(|g| g.enabled) {
+                loaded.listeners = subscribe(app, &loaded.manifest, &capabilities);
+            }
+        }
+        inner.plugins = plugins;
+        #[cfg(feature = "plugins")]
+        {
+            inner.runtime = runtime;
+        }
+        inner
+            .plugins
+            .values()
+            .filter(|l| l.error.is_none())
+            .filter_map(|l| {
+                let grant = grants.iter().find(|g| g.id == l.manifest.id && g.enabled)?;
+                Some((l.manifest.id.clone(), effective(&l.manifest, Some(grant))))
+            })
+            .collect()
+    };
+    for (id, capabilities) in ready {
+        let input = json!({ "plugin_id": id, "capabilities": capabilities });
+        if let Err(err) = call_blocking(app.clone(), id.clone(), "init", input).await {
+            eprintln!("plugin {} init failed: {}", id, err);
+            if let Some(loaded) = host.lock()?.plugins.get_mut(&id) {
+                loaded.error = Some(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Loads the installed plugins once the app has started.
+pub fn spawn_loader(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = reload(&app).await {
+            eprintln!("loading plugins failed: {}", err);
+        }
+    });
+}
+
+/// The installed plugins with what each asked for and was granted, its load
+/// error, and its recent log lines.
+#[tauri::command]
+pub fn list_plugins(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    host: State<'_, PluginHost>,
+) -> Result<Value, String> {
+    let grants = store.snapshot().plugins;
+    let inner = host.lock()?;
+    let plugins: Vec<Value> = inner
+        .plugins
+        .values()
+        .map(|loaded| {
+            let grant = grants.iter().find(|g| g.id == loaded.manifest.id);
+            json!({
+                "manifest": loaded.manifest,
+                "enabled": grant.is_some_and(|g| g.enabled),
+                "granted": grant.map(|g| g.capabilities.clone()).unwrap_or_default(),
+                "pin": grant.and_then(|g| g.pin.clone()),
+                "effective": effective(&loaded.manifest, grant),
+                "askable": askable(&loaded.manifest, grant),
+                "denied": grant.map(|g| g.denied.clone()).unwrap_or_default(),
+                "error": loaded.error,
+                "logs": loaded.logs,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "supported": SUPPORTED,
+        "safe_mode": safe_mode::active(),
+        "dir": plugins_dir(&app)?.display().to_string(),
+        "subscribable": SUBSCRIBABLE,
+        "plugins": plugins,
+    }))
+}
+
+/// Enables or disables plugin `id` with `capabilities`, then reloads the
+/// plugins so subscriptions follow the new grant.
+#[tauri::command]
+pub async fn set_plugin_grant(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+    enabled: bool,
+    capabilities: Vec<Capability>,
+) -> Result<Value, String> {
+    let id = id.trim().to_string();
+    let mut grant = PluginGrant::default();
+    store.update(&app, |settings| {
+        let existing = grant_mut(&mut settings.plugins, &id);
+        existing.enabled = enabled;
+        existing.capabilities = capabilities;
+        grant = existing.clone();
+        Ok(())
+    })?;
+    reload(&app).await?;
+    Ok(json!({ "grant": grant }))
+}
+
+#[tauri::command]
+pub async fn reload_plugins(app: AppHandle) -> Result<Value, String> {
+    reload(&app).await?;
+    let count = app.state::<PluginHost>().lock()?.plugins.len();
+    Ok(json!({ "plugins": count }))
+}
+
+/// Runs plugin command `command` with `input` through its `command` export.
+#[tauri::command]
+pub async fn invoke_plugin(
+    app: AppHandle,
+    plugin_id: String,
+    command: String,
+    input: Option<Value>,
+) -> Result<Value, String> {
+    let message = json!({ "command": command, "input": input.unwrap_or(Value::Null) });
+    let output = call_blocking(app, plugin_id.clone(), "command", message).await?;
+    Ok(json!({ "plugin_id": plugin_id, "command": command, "output": output }))
+}
+
+/// Palette entries from the enabled plugins still granted `palette`.
+#[tauri::command]
+pub fn plugin_palette_entries(
+    store: State<'_, SettingsStore>,
+    host: State<'_, PluginHost>,
+) -> Result<Value, String> {
+    let grants = store.snapshot().plugins;
+    let inner = host.lock()?;
+    let entries: Vec<&PaletteEntry> = inner
+        .plugins
+        .values()
+        .filter(|l| {
+            let grant = grants.iter().find(|g| g.id == l.manifest.id);
+            effective(&l.manifest, grant).contains(&Capability::Palette)
+        })
+        .flat_map(|l| &l.palette)
+        .collect();
+    Ok(json!({ "entries": entries }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_paths_are_compared_segment_by_segment() {
+        let core_paths = ["/plans/".to_string(), "/jobs".to_string()];
+        assert_eq!(may_reach(&core_paths, "/plans"), Ok(true));
+        assert_eq!(may_reach(&core_paths, "/plans/p-1"), Ok(true));
+        assert_eq!(may_reach(&core_paths, "/jobs/j-1/cancel"), Ok(true));
+        assert_eq!(may_reach(&core_paths, "/plansx"), Ok(false));
+        assert_eq!(may_reach(&core_paths, "/admin"), Ok(false));
+        assert_eq!(may_reach(&["/".to_string()], "/admin"), Ok(true));
+    }
+
+    #[test]
+    fn paths_that_could_leave_core_paths_are_refused() {
+        let core_paths = ["/plans".to_string()];
+        for path in [
+            "/plans/../admin",
+            "/plans/%2e%2e/admin",
+            "/plans/%2E%2e/admin",
+            "/plans/./p-1",
+            "/plans//p-1",
+            "/plans/p-1?x=1",
+            "/plans/p-1#x",
+            "/plans/a%2f..%2fadmin",
+            "plans/p-1",
+        ] {
+            assert!(may_reach(&core_paths, path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn network_requests_and_their_redirects_stay_on_network_hosts() {
+        let hosts = ["api.example.com".to_string()];
+        let target = |url: &str| network_target("p", &hosts, &Url::parse(url).unwrap());
+        assert!(target("https://api.example.com/v1").is_ok());
+        assert!(target("https://API.example.com/v1").is_ok());
+        assert!(target("http://api.example.com/v1").is_err());
+        assert!(target("https://user@api.example.com/v1").is_err());
+        assert!(target("https://api.example.com.evil.test/v1").is_err());
+        assert!(target("https://169.254.169.254/latest").is_err());
+    }
+
+    #[test]
+    fn manifest_core_paths_must_be_plain() {
+        assert!(prefix_segments("/plans/").is_ok());
+        assert!(prefix_segments("/").is_ok());
+        assert!(prefix_segments("/plans/../admin").is_err());
+        assert!(prefix_segments("https://core.example/plans").is_err());
+        assert!(prefix_segments("").is_err());
+    }
+}
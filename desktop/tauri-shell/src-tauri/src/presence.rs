@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::features::{self, FeatureCache};
+use crate::settings::SettingsStore;
+use crate::{crypto, environment, net, notify, stall, tasks};
+
+const TASK: &str = "presence";
+const CHANGED_EVENT: &str = "presence:changed";
+const DECIDED_EVENT: &str = "presence:decided_elsewhere";
+/// Heartbeats run at this pace; cores drop a viewer after missing a few.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_JOINED: usize = 20;
+/// The core feature flag of cores that track who is on a plan.
+const FEATURE: &str = "presence";
+const ACTIVITIES: &[&str] = &["viewing", "reviewing"];
+/// How long a decision sent from here is remembered, so the status change
+/// it causes is not reported as someone else's.
+const DECIDED_HERE_TTL: Duration = Duration::from_secs(600);
+
+/// Plans this shell announced itself on, by profile and plan id.
+#[derive(Default)]
+pub struct Presence {
+    plans: Mutex<HashMap<(String, String), Joined>>,
+}
+
+#[derive(Clone)]
+struct Joined {
+    activity: String,
+    /// Other operators on the plan at the last poll, by session.
+    others: Vec<Value>,
+    /// Unset until the first poll.
+    status: Option<String>,
+}
+
+/// Decisions sent from this shell, keyed by `origin plan_id`.
+static DECIDED_HERE: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+fn decided_here() -> &'static Mutex<HashMap<String, Instant>> {
+    DECIDED_HERE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Identifies this shell's own entry in the core's presence list; one per
+/// run, so two windows of the same operator count as one viewer.
+fn session() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| {
+        crypto::random_bytes::<8>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    })
+}
+
+fn decided_key(base_url: &str, plan_id: &str) -> String {
+    format!(
+        "{} {}",
+        net::base_origin(base_url).unwrap_or_default(),
+        plan_id
+    )
+}
+
+/// Notes a decision this shell sent, so watching the plan does not report
+/// it as decided elsewhere.
+pub fn decided(base_url: &str, plan_id: &str) {
+    if let Ok(mut recent) = decided_here().lock() {
+        recent.retain(|_, at| at.elapsed() < DECIDED_HERE_TTL);
+        recent.insert(decided_key(base_url, plan_id), Instant::now());
+    }
+}
+
+fn was_decided_here(base_url: &str, plan_id: &str) -> bool {
+    decided_here().lock().is_ok_and(|recent| {
+        recent
+            .get(&decided_key(base_url, plan_id))
+            .is_some_and(|at| at.elapsed() < DECIDED_HERE_TTL)
+    })
+}
+
+/// The viewers of a presence response other than this shell. Cores answer
+/// with a list or with `{ "viewers": [...] }`.
+fn others(listed: &Value) -> Vec<Value> {
+    let mut others: Vec<Value> = listed
+        .as_array()
+        .or_else(|| listed["viewers"].as_array())
+        .into_iter()
+        .flatten()
+        .filter(|v| v["session"].as_str() != Some(session()))
+        .cloned()
+        .collect();
+    others.sort_by_key(viewer_key);
+    others
+}
+
+fn viewer_key(viewer: &Value) -> (String, String) {
+    let text = |k: &str| viewer[k].as_str().unwrap_or_default().to_string();
+    (text("session"), text("operator"))
+}
+
+fn unsupported() -> String {
+    ErrorCode::InvalidInput.with("This core does not expose presence")
+}
+
+async fn announce(
+    base_url: &str,
+    token: Option<String>,
+    plan_id: &str,
+    activity: &str,
+) -> Result<Value, String> {
+    crate::request_json(
+        Method::POST,
+        base_url,
+        &format!("/plans/{}/presence", plan_id),
+        token,
+        Some(json!({ "activity": activity, "session": session(), "client": "desktop" })),
+    )
+    .await
+    .map_err(|err| match errors::code_of(&err) {
+        Some(ErrorCode::NotFound) => unsupported(),
+        _ => err,
+    })
+}
+
+async fn list(base_url: &str, token: Option<String>, plan_id: &str) -> Result<Vec<Value>, String> {
+    crate::request_json(
+        Method::GET,
+        base_url,
+        &format!("/plans/{}/presence", plan_id),
+        token,
+        None,
+    )
+    .await
+    .map(|listed| others(&listed))
+}
+
+/// Announces that this operator is `viewing` (default) or `reviewing`
+/// `plan_id` and keeps the announcement alive until `leave_plan_presence`.
+/// Returns who else is on the plan; changes arrive as `presence:changed`,
+/// and a decision taken by someone else as `presence:decided_elsewhere`.
+#[tauri::command]
+pub async fn join_plan_presence(
+    store: State<'_, SettingsStore>,
+    features: State<'_, FeatureCache>,
+    presence: State<'_, Presence>,
+    profile: Option<String>,
+    plan_id: String,
+    activity: Option<String>,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let activity = activity
+        .map(|a| a.trim().to_ascii_lowercase())
+        .unwrap_or_else(|| "viewing".to_string());
+    if !ACTIVITIES.contains(&activity.as_str()) {
+        return Err(ErrorCode::InvalidInput.with("Activity must be `viewing` or `reviewing`"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    if features::core_flag(&store, &features, &conn, FEATURE).await == Some(false) {
+        return Err(unsupported());
+    }
+    let key = (conn.profile_id.clone(), plan_id.clone());
+    {
+        let plans = presence
+            .plans
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Presence state poisoned"))?;
+        if !plans.contains_key(&key) && plans.len() >= MAX_JOINED {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Presence is kept on at most {} plans at once",
+                MAX_JOINED
+            )));
+        }
+    }
+    announce(&conn.base_url, conn.token.clone(), &plan_id, &activity).await?;
+    let viewers = list(&conn.base_url, conn.token.clone(), &plan_id).await?;
+    presence
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Presence state poisoned"))?
+        .insert(
+            key,
+            Joined {
+                activity: activity.clone(),
+                others: viewers.clone(),
+                status: None,
+            },
+        );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "activity": activity,
+        "others": viewers,
+    }))
+}
+
+/// Withdraws this operator from `plan_id`'s presence list.
+#[tauri::command]
+pub async fn leave_plan_presence(
+    store: State<'_, SettingsStore>,
+    presence: State<'_, Presence>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let removed = presence
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Presence state poisoned"))?
+        .remove(&(conn.profile_id.clone(), plan_id.clone()));
+    if removed.is_some() {
+        let left = crate::request_json(
+            Method::DELETE,
+            &conn.base_url,
+            &format!("/plans/{}/presence?session={}", plan_id, session()),
+            conn.token.clone(),
+            None,
+        )
+        .await;
+        // The core forgets a silent viewer by itself, so only a real
+        // failure is worth reporting.
+        if let Err(err) = left {
+            if errors::code_of(&err) != Some(ErrorCode::NotFound) {
+                return Err(err);
+            }
+        }
+    }
+    Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id, "left": removed.is_some() }))
+}
+
+/// Who else is viewing or reviewing `plan_id`, without joining it.
+#[tauri::command]
+pub async fn get_plan_presence(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let viewers = list(&conn.base_url, conn.token.clone(), &plan_id)
+        .await
+        .map_err(|err| match errors::code_of(&err) {
+            Some(ErrorCode::NotFound) => unsupported(),
+            _ => err,
+        })?;
+    Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id, "others": viewers }))
+}
+
+/// Keeps every joined plan's announcement alive and reports changes.
+pub fn spawn_heartbeat(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            poll(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn poll(app: &AppHandle) {
+    let joined: Vec<((String, String), Joined)> = match app.state::<Presence>().plans.lock() {
+        Ok(plans) => plans.iter().map(|(k, j)| (k.clone(), j.clone())).collect(),
+        Err(_) => return,
+    };
+    let store = app.state::<SettingsStore>();
+    for ((profile_id, plan_id), previous) in joined {
+        let Ok(conn) = store.connection(Some(&profile_id)) else {
+            continue;
+        };
+        let viewers = match announce(
+            &conn.base_url,
+            conn.token.clone(),
+            &plan_id,
+            &previous.activity,
+        )
+        .await
+        {
+            Ok(_) => list(&conn.base_url, conn.token.clone(), &plan_id).await,
+            Err(err) => Err(err),
+        };
+        let viewers = match viewers {
+            Ok(viewers) => viewers,
+            Err(err) => {
+                tasks::failed(TASK, &err);
+                continue;
+            }
+        };
+        let plan = crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/plans/{}", plan_id),
+            conn.token.clone(),
+            None,
+        )
+        .await
+        .unwrap_or(Value::Null);
+        let status = plan["status"].as_str().map(str::to_ascii_lowercase);
+
+        {
+            let presence = app.state::<Presence>();
+            let Ok(mut plans) = presence.plans.lock() else {
+                return;
+            };
+            // Left while the poll was in flight.
+            let Some(entry) = plans.get_mut(&(profile_id.clone(), plan_id.clone())) else {
+                continue;
+            };
+            entry.others = viewers.clone();
+            if status.is_some() {
+                entry.status = status.clone();
+            }
+        }
+
+        if viewers != previous.others {
+            let _ = environment::emit(
+                app,
+                CHANGED_EVENT,
+                json!({
+                    "profile_id": profile_id,
+                    "plan_id": plan_id,
+                    "others": viewers,
+                }),
+            );
+        }
+        let decided_elsewhere = previous.status.as_deref() == Some("pending")
+            && status.as_deref().is_some_and(|s| s != "pending")
+            && !was_decided_here(&conn.base_url, &plan_id);
+        if decided_elsewhere {
+            let by = ["decided_by", "approved_by", "rejected_by"]
+                .iter()
+                .find_map(|k| plan[*k].as_str().filter(|s| !s.is_empty()))
+                .map(str::to_string);
+            let event = json!({
+                "profile_id": profile_id,
+                "plan_id": plan_id,
+                "status": status,
+                "by": by,
+            });
+            notify::notify(
+                app,
+                "presence",
+                "Plan decided elsewhere",
+                &format!(
+                    "Plan {} is now {} ({})",
+                    plan_id,
+                    status.as_deref().unwrap_or("decided"),
+                    by.as_deref().unwrap_or("another operator")
+                ),
+                event.clone(),
+            );
+            let _ = environment::emit(app, DECIDED_EVENT, event);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+
+const PREVIEW_ROOTS_ENV: &str = "NOVAADAPT_DESKTOP_PREVIEW_ROOTS";
+const PREVIEW_MAX_BYTES: u64 = 64 * 1024;
+const PREVIEW_MAX_ENTRIES: usize = 200;
+
+/// Returns metadata and a bounded, read-only preview of a local path.
+///
+/// Only paths that canonicalize under one of the allowlisted roots are served,
+/// so symlinks and `..` segments cannot escape the sandbox.
+#[tauri::command]
+pub async fn preview_path(path: String) -> Result<Value, String> {
+    tauri::async_runtime::spawn_blocking(move || preview_blocking(&path))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(format!("Preview task failed: {}", e)))?
+}
+
+/// The roots named in `PREVIEW_ROOTS_ENV`. With none configured nothing
+/// is served: defaulting to the home directory would expose keys and
+/// credentials kept there.
+pub fn allowed_roots() -> Vec<PathBuf> {
+    std::env::var_os(PREVIEW_ROOTS_ENV)
+        .map(|raw| std::env::split_paths(&raw).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|root| !root.as_os_str().is_empty())
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .collect()
+}
+
+pub fn resolve_allowed(path: &str) -> Result<PathBuf, String> {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Path is required"));
+    }
+    let resolved = fs::canonicalize(trimmed)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Path not accessible: {}", e)))?;
+    let roots = allowed_roots();
+    if roots.is_empty() {
+        return Err(ErrorCode::Forbidden.with(format!(
+            "No preview roots are configured; set {}",
+            PREVIEW_ROOTS_ENV
+        )));
+    }
+    if !roots.iter().any(|root| resolved.starts_with(root)) {
+        return Err(ErrorCode::Forbidden.with(format!(
+            "Path is outside the allowed preview roots: {}",
+            resolved.display()
+        )));
+    }
+    Ok(resolved)
+}
+
+fn preview_blocking(path: &str) -> Result<Value, String> {
+    let resolved = resolve_allowed(path)?;
+    let meta = fs::metadata(&resolved)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read metadata failed: {}", e)))?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    let mut out = json!({
+        "path": resolved.display().to_string(),
+        "kind": if meta.is_dir() { "directory" } else if meta.is_file() { "file" } else { "other" },
+        "size": meta.len(),
+        "modified": modified,
+        "readonly": meta.permissions().readonly(),
+    });
+
+    if meta.is_dir() {
+        out["entries"] = list_entries(&resolved)?;
+    } else if meta.is_file() {
+        out["preview"] = read_preview(&resolved, meta.len())?;
+    }
+    Ok(out)
+}
+
+fn list_entries(dir: &Path) -> Result<Value, String> {
+    let mut entries = Vec::new();
+    let mut truncated = false;
+    for entry in fs::read_dir(dir)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read directory failed: {}", e)))?
+    {
+        if entries.len() >= PREVIEW_MAX_ENTRIES {
+            truncated = true;
+            break;
+        }
+        let Ok(entry) = entry else { continue };
+        let meta = entry.metadata().ok();
+        entries.push(json!({
+            "name": entry.file_name().to_string_lossy(),
+            "kind": match &meta {
+                Some(m) if m.is_dir() => "directory",
+                Some(m) if m.is_file() => "file",
+                _ => "other",
+            },
+            "size": meta.as_ref().map(|m| m.len()),
+        }));
+    }
+    entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    Ok(json!({ "items": entries, "truncated": truncated }))
+}
+
+fn read_preview(file: &Path, size: u64) -> Result<Value, String> {
+    let mut buf = Vec::new();
+    fs::File::open(file)
+        .and_then(|f| f.take(PREVIEW_MAX_BYTES).read_to_end(&mut buf))
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read file failed: {}", e)))?;
+    let truncated = size > buf.len() as u64;
+    if buf.contains(&0) {
+        return Ok(json!({ "binary": true, "content": null, "truncated": truncated }));
+    }
+    Ok(json!({
+        "binary": false,
+        "content": String::from_utf8_lossy(&buf),
+        "truncated": truncated,
+    }))
+}
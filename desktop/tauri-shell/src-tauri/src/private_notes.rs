@@ -0,0 +1,459 @@
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection, SettingsStore};
+use crate::{crypto, secrets, stall};
+
+/// Tags the note envelope stored on the core.
+const NOTE_FORMAT: &str = "novaadapt-private-note";
+/// Tags a note key wrapped for sharing with teammates.
+const SHARED_KEY_FORMAT: &str = "novaadapt-note-key";
+const MAX_NOTE_BYTES: usize = 20_000;
+const MAX_TEAM_KEYS: usize = 16;
+const MAX_NAME_LEN: usize = 80;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// A profile's note keys, kept together in one keyring entry.
+#[derive(Default, Serialize, Deserialize)]
+struct NoteKeys {
+    /// Made on the first note saved; only this operator holds it.
+    personal: Option<String>,
+    teams: Vec<TeamKey>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TeamKey {
+    name: String,
+    key: String,
+    added_at: String,
+}
+
+fn keys_secret(profile_id: &str) -> String {
+    format!("profile:{}:note_keys", profile_id)
+}
+
+/// Held from a command's first read of a profile's note keys to its last
+/// write, so two commands changing them at once cannot drop each other's
+/// keys.
+static KEYS_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+fn lock_keys() -> MutexGuard<'static, ()> {
+    KEYS_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn load(_guard: &MutexGuard<'_, ()>, profile_id: &str) -> Result<NoteKeys, String> {
+    match secrets::get(&keys_secret(profile_id))? {
+        Some(raw) => serde_json::from_str(&raw)
+            .map_err(|e| ErrorCode::LocalStorage.with(format!("Note keys are unreadable: {}", e))),
+        None => Ok(NoteKeys::default()),
+    }
+}
+
+fn save(_guard: &MutexGuard<'_, ()>, profile_id: &str, keys: &NoteKeys) -> Result<(), String> {
+    let raw = serde_json::to_string(keys).map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+    secrets::set(&keys_secret(profile_id), &raw)
+}
+
+/// Names a key without revealing it: the start of its SHA-256, the same on
+/// every machine the key is shared to.
+fn fingerprint(key: &[u8; crypto::KEY_LEN]) -> String {
+    Sha256::digest(key)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Every key the profile holds, as `(id, kind, name, key)`.
+fn held(keys: &NoteKeys) -> Vec<(String, &'static str, String, [u8; crypto::KEY_LEN])> {
+    let personal = keys
+        .personal
+        .iter()
+        .filter_map(|raw| crypto::decode_key(raw).ok())
+        .map(|key| (fingerprint(&key), "personal", "Personal".to_string(), key));
+    let teams = keys.teams.iter().filter_map(|team| {
+        let key = crypto::decode_key(&team.key).ok()?;
+        Some((fingerprint(&key), "team", team.name.clone(), key))
+    });
+    personal.chain(teams).collect()
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Key passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn key_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("Key name must be 1 to {} bytes", MAX_NAME_LEN))
+        );
+    }
+    Ok(name.to_string())
+}
+
+fn describe(keys: &NoteKeys) -> Value {
+    let listed: Vec<Value> = held(keys)
+        .into_iter()
+        .map(|(id, kind, name, _)| {
+            let added_at = keys
+                .teams
+                .iter()
+                .find(|t| crypto::decode_key(&t.key).is_ok_and(|k| fingerprint(&k) == id))
+                .map(|t| t.added_at.clone());
+            json!({ "key_id": id, "kind": kind, "name": name, "added_at": added_at })
+        })
+        .collect();
+    json!({ "keys": listed })
+}
+
+fn audit(db: &LocalDb, profile_id: &str, action: &str, entity: &str, detail: Value) {
+    if let Ok(conn) = db.lock() {
+        let _ = db::record_audit(&conn, profile_id, "private_notes", action, entity, &detail);
+    }
+}
+
+fn notes_path(plan_id: &str) -> String {
+    format!("/plans/{}/private_notes", plan_id)
+}
+
+fn unsupported(err: String) -> String {
+    match errors::code_of(&err) {
+        Some(ErrorCode::NotFound) => {
+            ErrorCode::NotFound.with("This core does not store private notes for the plan")
+        }
+        _ => err,
+    }
+}
+
+/// The note keys of a profile: its personal key and the team keys shared
+/// with it, by id. The keys themselves never leave the keyring.
+#[tauri::command]
+pub fn list_note_keys(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let mut listed = describe(&load(&lock_keys(), &conn.profile_id)?);
+    listed["profile_id"] = json!(conn.profile_id);
+    Ok(listed)
+}
+
+/// Makes a new team key for notes a group of operators can all read; share
+/// it with `export_note_key`.
+#[tauri::command]
+pub fn create_note_team_key(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    name: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let name = key_name(&name)?;
+    let guard = lock_keys();
+    let mut keys = load(&guard, &conn.profile_id)?;
+    if keys.teams.len() >= MAX_TEAM_KEYS {
+        return Err(ErrorCode::InvalidInput
+            .with(format!("At most {} team note keys are kept", MAX_TEAM_KEYS)));
+    }
+    let key = crypto::generate_key();
+    keys.teams.push(TeamKey {
+        name: name.clone(),
+        key: crypto::encode_key(&key),
+        added_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+    save(&guard, &conn.profile_id, &keys)?;
+    let id = fingerprint(&key);
+    audit(
+        &db,
+        &conn.profile_id,
+        "create_key",
+        &id,
+        json!({ "name": name }),
+    );
+    Ok(json!({ "profile_id": conn.profile_id, "key_id": id, "kind": "team", "name": name }))
+}
+
+/// A note key wrapped under `passphrase`, to hand to a teammate over any
+/// channel; the passphrase goes to them separately.
+#[tauri::command]
+pub fn export_note_key(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    key_id: String,
+    passphrase: String,
+) -> Result<Value, String> {
+    check_passphrase(&passphrase)?;
+    let conn = store.connection(profile.as_deref())?;
+    let keys = load(&lock_keys(), &conn.profile_id)?;
+    let (id, kind, name, key) = held(&keys)
+        .into_iter()
+        .find(|(id, ..)| *id == key_id.trim())
+        .ok_or_else(|| ErrorCode::NotFound.with("No such note key"))?;
+    let payload = json!({ "name": name, "key": crypto::encode_key(&key) });
+    let shared = crypto::seal_with_passphrase(
+        &passphrase,
+        SHARED_KEY_FORMAT,
+        payload.to_string().as_bytes(),
+    )?;
+    audit(
+        &db,
+        &conn.profile_id,
+        "export_key",
+        &id,
+        json!({ "kind": kind }),
+    );
+    Ok(json!({ "key_id": id, "kind": kind, "shared": shared }))
+}
+
+/// Adds a key a teammate exported, so their team's notes can be read and
+/// written here. Importing a key already held does nothing.
+#[tauri::command]
+pub fn import_note_key(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    shared: Value,
+    passphrase: String,
+    name: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let opened = crypto::open_with_passphrase(&passphrase, SHARED_KEY_FORMAT, &shared)
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let payload: Value = serde_json::from_slice(&opened)
+        .map_err(|_| ErrorCode::InvalidInput.with("The shared key is malformed"))?;
+    let key = crypto::decode_key(payload["key"].as_str().unwrap_or_default())
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let id = fingerprint(&key);
+    let guard = lock_keys();
+    let mut keys = load(&guard, &conn.profile_id)?;
+    if held(&keys).iter().any(|(held, ..)| *held == id) {
+        return Ok(json!({ "profile_id": conn.profile_id, "key_id": id, "imported": false }));
+    }
+    if keys.teams.len() >= MAX_TEAM_KEYS {
+        return Err(ErrorCode::InvalidInput
+            .with(format!("At most {} team note keys are kept", MAX_TEAM_KEYS)));
+    }
+    let name = key_name(
+        name.as_deref()
+            .or(payload["name"].as_str())
+            .unwrap_or("Shared"),
+    )?;
+    keys.teams.push(TeamKey {
+        name: name.clone(),
+        key: crypto::encode_key(&key),
+        added_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+    save(&guard, &conn.profile_id, &keys)?;
+    audit(
+        &db,
+        &conn.profile_id,
+        "import_key",
+        &id,
+        json!({ "name": name }),
+    );
+    Ok(json!({ "profile_id": conn.profile_id, "key_id": id, "name": name, "imported": true }))
+}
+
+/// Forgets a team key. Notes written with it can no longer be read here.
+#[tauri::command]
+pub fn remove_note_key(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    key_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let guard = lock_keys();
+    let mut keys = load(&guard, &conn.profile_id)?;
+    let before = keys.teams.len();
+    keys.teams
+        .retain(|t| !crypto::decode_key(&t.key).is_ok_and(|k| fingerprint(&k) == key_id.trim()));
+    let removed = keys.teams.len() < before;
+    if removed {
+        save(&guard, &conn.profile_id, &keys)?;
+        audit(
+            &db,
+            &conn.profile_id,
+            "remove_key",
+            key_id.trim(),
+            json!({}),
+        );
+    }
+    Ok(json!({ "profile_id": conn.profile_id, "key_id": key_id.trim(), "removed": removed }))
+}
+
+/// Encrypts `text` here, with the personal key or the team key `key_id`,
+/// and stores only the ciphertext on the core, so its administrators
+/// cannot read it. The plan id is sealed in with the text, so a note moved
+/// to another plan fails to open. Empty text deletes the note.
+#[tauri::command]
+pub async fn save_private_note(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    text: String,
+    key_id: Option<String>,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    if text.trim().is_empty() {
+        return delete_note(&conn, &db, &plan_id).await;
+    }
+    if text.len() > MAX_NOTE_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "A private note is at most {} bytes",
+            MAX_NOTE_BYTES
+        )));
+    }
+    let (id, kind, key) = {
+        let guard = lock_keys();
+        let mut keys = load(&guard, &conn.profile_id)?;
+        match key_id.as_deref().map(str::trim).filter(|k| !k.is_empty()) {
+            Some(wanted) => held(&keys)
+                .into_iter()
+                .find(|(id, ..)| id == wanted)
+                .map(|(id, kind, _, key)| (id, kind, key))
+                .ok_or_else(|| ErrorCode::NotFound.with("No such note key"))?,
+            None => {
+                let key = match keys.personal.as_deref().map(crypto::decode_key) {
+                    Some(Ok(key)) => key,
+                    _ => {
+                        let key = crypto::generate_key();
+                        keys.personal = Some(crypto::encode_key(&key));
+                        save(&guard, &conn.profile_id, &keys)?;
+                        key
+                    }
+                };
+                (fingerprint(&key), "personal", key)
+            }
+        }
+    };
+    let written_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let sealed = json!({ "plan_id": plan_id, "text": text, "written_at": written_at });
+    let mut envelope = crypto::seal(&key, NOTE_FORMAT, sealed.to_string().as_bytes())?;
+    envelope["key_id"] = json!(id);
+    envelope["key_kind"] = json!(kind);
+    crate::request_json(
+        Method::PUT,
+        &conn.base_url,
+        &notes_path(&plan_id),
+        conn.token.clone(),
+        Some(json!({ "private_notes": envelope })),
+    )
+    .await
+    .map_err(unsupported)?;
+    audit(
+        &db,
+        &conn.profile_id,
+        "save",
+        &plan_id,
+        json!({ "key_id": id, "kind": kind, "bytes": text.len() }),
+    );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "key_id": id,
+        "kind": kind,
+        "written_at": written_at,
+    }))
+}
+
+async fn delete_note(conn: &Connection, db: &LocalDb, plan_id: &str) -> Result<Value, String> {
+    let deleted = match crate::request_json(
+        Method::DELETE,
+        &conn.base_url,
+        &notes_path(plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(_) => true,
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => false,
+        Err(err) => return Err(err),
+    };
+    if deleted {
+        audit(db, &conn.profile_id, "delete", plan_id, json!({}));
+    }
+    Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id, "deleted": deleted }))
+}
+
+/// Reads a plan's private note from the core and decrypts it here. A note
+/// under a key this profile does not hold comes back with `readable: false`
+/// and the key's id, rather than as an error.
+#[tauri::command]
+pub async fn get_private_note(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let stored = match crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &notes_path(&plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(stored) => stored,
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => Value::Null,
+        Err(err) => return Err(err),
+    };
+    let envelope = match &stored["private_notes"] {
+        Value::Null => &stored,
+        inner => inner,
+    };
+    if envelope["format"].as_str() != Some(NOTE_FORMAT) {
+        return Ok(json!({ "profile_id": conn.profile_id, "plan_id": plan_id, "note": null }));
+    }
+    let id = envelope["key_id"].as_str().unwrap_or_default();
+    let keys = load(&lock_keys(), &conn.profile_id)?;
+    let Some((_, kind, name, key)) = held(&keys).into_iter().find(|(held, ..)| held == id) else {
+        return Ok(json!({
+            "profile_id": conn.profile_id,
+            "plan_id": plan_id,
+            "note": { "readable": false, "key_id": id, "kind": envelope["key_kind"] },
+        }));
+    };
+    let opened = crypto::open(&key, NOTE_FORMAT, envelope)
+        .map_err(|e| ErrorCode::Forbidden.with(format!("Private note: {}", e)))?;
+    let sealed: Value = serde_json::from_slice(&opened)
+        .map_err(|_| ErrorCode::Forbidden.with("Private note is malformed"))?;
+    if sealed["plan_id"].as_str() != Some(plan_id.as_str()) {
+        return Err(ErrorCode::Forbidden.with("This private note was written for another plan"));
+    }
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "note": {
+            "readable": true,
+            "text": sealed["text"],
+            "written_at": sealed["written_at"],
+            "key_id": id,
+            "kind": kind,
+            "key_name": name,
+        },
+    }))
+}
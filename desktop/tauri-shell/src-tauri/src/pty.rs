@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{ansi, environment, permissions};
+
+const OUTPUT_EVENT: &str = "pty:output";
+const EXIT_EVENT: &str = "pty:exit";
+const READ_CHUNK_BYTES: usize = 8 * 1024;
+const REMOTE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Open terminal sessions keyed by the id handed to the frontend.
+#[derive(Default)]
+pub struct PtyState {
+    sessions: Mutex<HashMap<String, PtySession>>,
+    next_id: AtomicU64,
+}
+
+enum PtySession {
+    Local {
+        master: Box<dyn MasterPty + Send>,
+        writer: Box<dyn Write + Send>,
+        child: Box<dyn Child + Send + Sync>,
+    },
+    Remote {
+        base_url: String,
+        token: Option<String>,
+        remote_id: String,
+        stop: Arc<AtomicBool>,
+    },
+}
+
+impl PtyState {
+    fn allocate_id(&self) -> String {
+        format!("pty-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn insert(&self, id: String, session: PtySession) -> Result<(), String> {
+        self.sessions
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("PTY state poisoned"))?
+            .insert(id, session);
+        Ok(())
+    }
+
+    fn take(&self, id: &str) -> Result<Option<PtySession>, String> {
+        Ok(self
+            .sessions
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("PTY state poisoned"))?
+            .remove(id))
+    }
+}
+
+/// Opens a local PTY running `shell` (or the platform default shell) and
+/// streams its output as `pty:output` events.
+#[tauri::command]
+pub async fn pty_open(
+    app: AppHandle,
+    state: State<'_, PtyState>,
+    store: State<'_, SettingsStore>,
+    shell: Option<String>,
+    cwd: Option<String>,
+    cols: Option<u16>,
+    rows: Option<u16>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    if let Some(active) = settings.profile(&settings.active_profile) {
+        permissions::ensure_profile_writable(active)?;
+    }
+    let pair = native_pty_system()
+        .openpty(pty_size(cols, rows))
+        .map_err(|e| ErrorCode::LocalIo.with(format!("PTY open failed: {}", e)))?;
+
+    let mut cmd = match shell.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(program) => CommandBuilder::new(program),
+        None => CommandBuilder::new_default_prog(),
+    };
+    if let Some(dir) = cwd.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        cmd.cwd(dir);
+    }
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("PTY spawn failed: {}", e)))?;
+    drop(pair.slave);
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("PTY reader failed: {}", e)))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("PTY writer failed: {}", e)))?;
+
+    let id = state.allocate_id();
+    state.insert(
+        id.clone(),
+        PtySession::Local {
+            master: pair.master,
+            writer,
+            child,
+        },
+    )?;
+    spawn_local_reader(app, id.clone(), reader);
+    Ok(json!({ "session_id": id, "kind": "local" }))
+}
+
+/// Opens a terminal session on the core (and therefore on its agent host) and
+/// relays its output through the same `pty:output` events as local sessions.
+#[tauri::command]
+pub async fn pty_open_remote(
+    app: AppHandle,
+    state: State<'_, PtyState>,
+    base_url: String,
+    token: Option<String>,
+    command: Option<String>,
+    cwd: Option<String>,
+    shell: Option<String>,
+) -> Result<Value, String> {
+    let created = crate::request_json(
+        Method::POST,
+        &base_url,
+        "/terminal/sessions",
+        token.clone(),
+        Some(json!({ "command": command, "cwd": cwd, "shell": shell })),
+    )
+    .await?;
+    let remote_id = created["id"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| ErrorCode::CoreError.with("Core did not return a terminal session id"))?
+        .to_string();
+
+    let id = state.allocate_id();
+    let stop = Arc::new(AtomicBool::new(false));
+    state.insert(
+        id.clone(),
+        PtySession::Remote {
+            base_url: base_url.clone(),
+            token: token.clone(),
+            remote_id: remote_id.clone(),
+            stop: stop.clone(),
+        },
+    )?;
+    tauri::async_runtime::spawn(poll_remote_output(
+        app,
+        id.clone(),
+        base_url,
+        token,
+        remote_id.clone(),
+        stop,
+    ));
+    Ok(json!({ "session_id": id, "kind": "remote", "remote_id": remote_id }))
+}
+
+#[tauri::command]
+pub async fn pty_write(
+    state: State<'_, PtyState>,
+    session_id: String,
+    data: String,
+) -> Result<Value, String> {
+    let remote = {
+        let mut sessions = state
+            .sessions
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("PTY state poisoned"))?;
+        match sessions.get_mut(&session_id) {
+            Some(PtySession::Local { writer, .. }) => {
+                writer
+                    .write_all(data.as_bytes())
+                    .and_then(|_| writer.flush())
+                    .map_err(|e| ErrorCode::LocalIo.with(format!("PTY write failed: {}", e)))?;
+                return Ok(json!({ "session_id": session_id, "bytes": data.len() }));
+            }
+            Some(PtySession::Remote {
+                base_url,
+                token,
+                remote_id,
+                ..
+            }) => (base_url.clone(), token.clone(), remote_id.clone()),
+            None => {
+                return Err(ErrorCode::NotFound.with(format!("Unknown PTY session: {}", session_id)))
+            }
+        }
+    };
+    let (base_url, token, remote_id) = remote;
+    let path = format!("/terminal/sessions/{}/input", remote_id);
+    crate::request_json(
+        Method::POST,
+        &base_url,
+        &path,
+        token,
+        Some(json!({ "input": data })),
+    )
+    .await?;
+    Ok(json!({ "session_id": session_id, "bytes": data.len() }))
+}
+
+#[tauri::command]
+pub fn pty_resize(
+    state: State<'_, PtyState>,
+    session_id: String,
+    cols: u16,
+    rows: u16,
+) -> Result<Value, String> {
+    let sessions = state
+        .sessions
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("PTY state poisoned"))?;
+    match sessions.get(&session_id) {
+        Some(PtySession::Local { master, .. }) => {
+            master
+                .resize(pty_size(Some(cols), Some(rows)))
+                .map_err(|e| ErrorCode::LocalIo.with(format!("PTY resize failed: {}", e)))?;
+            Ok(json!({ "session_id": session_id, "resized": true }))
+        }
+        // The core's terminal sessions are pipe-backed and have no window size.
+        Some(PtySession::Remote { .. }) => {
+            Ok(json!({ "session_id": session_id, "resized": false }))
+        }
+        None => Err(ErrorCode::NotFound.with(format!("Unknown PTY session: {}", session_id))),
+    }
+}
+
+#[tauri::command]
+pub async fn pty_close(state: State<'_, PtyState>, session_id: String) -> Result<Value, String> {
+    match state.take(&session_id)? {
+        Some(PtySession::Local { mut child, .. }) => {
+            // Dropping the master closes the PTY; the reader thread then emits the exit event.
+            let _ = child.kill();
+            Ok(json!({ "session_id": session_id, "closed": true }))
+        }
+        Some(PtySession::Remote {
+            base_url,
+            token,
+            remote_id,
+            stop,
+        }) => {
+            stop.store(true, Ordering::Relaxed);
+            let path = format!("/terminal/sessions/{}/close", remote_id);
+            crate::request_json(Method::POST, &base_url, &path, token, Some(json!({}))).await?;
+            Ok(json!({ "session_id": session_id, "closed": true }))
+        }
+        None => Err(ErrorCode::NotFound.with(format!("Unknown PTY session: {}", session_id))),
+    }
+}
+
+fn pty_size(cols: Option<u16>, rows: Option<u16>) -> PtySize {
+    PtySize {
+        rows: rows.filter(|r| *r > 0).unwrap_or(24),
+        cols: cols.filter(|c| *c > 0).unwrap_or(80),
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+fn spawn_local_reader(app: AppHandle, id: String, mut reader: Box<dyn Read + Send>) {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; READ_CHUNK_BYTES];
+        let mut pending: Vec<u8> = Vec::new();
+        let mut parser = ansi::Parser::default();
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    pending.extend_from_slice(&buf[..n]);
+                    let text = drain_utf8(&mut pending);
+                    if !text.is_empty() {
+                        emit_output(&app, &id, &mut parser, text);
+                    }
+                }
+            }
+        }
+        if !pending.is_empty() {
+            let text = String::from_utf8_lossy(&pending).into_owned();
+            emit_output(&app, &id, &mut parser, text);
+        }
+
+        let exit_code = match app.state::<PtyState>().take(&id) {
+            Ok(Some(PtySession::Local { mut child, .. })) => {
+                child.wait().ok().map(|status| status.exit_code())
+            }
+            _ => None,
+        };
+        let _ = environment::emit(
+            &app,
+            EXIT_EVENT,
+            json!({ "session_id": id, "exit_code": exit_code }),
+        );
+    });
+}
+
+async fn poll_remote_output(
+    app: AppHandle,
+    id: String,
+    base_url: String,
+    token: Option<String>,
+    remote_id: String,
+    stop: Arc<AtomicBool>,
+) {
+    let mut since_seq: u64 = 0;
+    let mut parser = ansi::Parser::default();
+    while !stop.load(Ordering::Relaxed) {
+        let path = format!(
+            "/terminal/sessions/{}/output?since_seq={}&limit=400",
+            remote_id, since_seq
+        );
+        let out = match crate::request_json(Method::GET, &base_url, &path, token.clone(), None)
+            .await
+        {
+            Ok(out) => out,
+            Err(err) => {
+                let _ =
+                    environment::emit(&app, EXIT_EVENT, json!({ "session_id": id, "error": err }));
+                break;
+            }
+        };
+        let mut data = String::new();
+        for chunk in out["chunks"].as_array().into_iter().flatten() {
+            data.push_str(chunk["data"].as_str().unwrap_or_default());
+        }
+        if !data.is_empty() {
+            emit_output(&app, &id, &mut parser, data);
+        }
+        since_seq = out["next_seq"].as_u64().unwrap_or(since_seq);
+        if out["open"] == Value::Bool(false) {
+            let _ = environment::emit(
+                &app,
+                EXIT_EVENT,
+                json!({ "session_id": id, "exit_code": out["exit_code"] }),
+            );
+            break;
+        }
+        tokio::time::sleep(REMOTE_POLL_INTERVAL).await;
+    }
+    let _ = app.state::<PtyState>().take(&id);
+}
+
+/// Emits `pty:output` with the raw `data` and its styled `spans`.
+fn emit_output(app: &AppHandle, id: &str, parser: &mut ansi::Parser, data: String) {
+    let spans = parser.feed(&data);
+    let _ = environment::emit(
+        app,
+        OUTPUT_EVENT,
+        json!({ "session_id": id, "data": data, "spans": spans }),
+    );
+}
+
+/// Returns the longest valid UTF-8 prefix of `pending`, keeping any partial
+/// trailing code point buffered for the next read.
+fn drain_utf8(pending: &mut Vec<u8>) -> String {
+    match std::str::from_utf8(pending) {
+        Ok(text) => {
+            let out = text.to_string();
+            pending.clear();
+            out
+        }
+        Err(err) if err.error_len().is_none() => {
+            let valid = err.valid_up_to();
+            let out = String::from_utf8_lossy(&pending[..valid]).into_owned();
+            pending.drain(..valid);
+            out
+        }
+        Err(_) => {
+            let out = String::from_utf8_lossy(pending).into_owned();
+            pending.clear();
+            out
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::{BTreeSet, HashSet};
+
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::environment;
+use crate::errors::ErrorCode;
+use crate::notify;
+use crate::settings::{Profile, QuickAction, SettingsStore};
+
+const REQUESTED_EVENT: &str = "quick_action:requested";
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+const MAX_ACTIONS: usize = 50;
+const MAX_NAME_LEN: usize = 80;
+const MAX_PARAM_LEN: usize = 64;
+
+/// The `{name}` placeholders in `template`, or why it is malformed.
+pub fn scan(template: &str, names: &mut BTreeSet<String>) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        if rest[..open].contains('}') {
+            return Err(format!("stray }} in {:?}", template));
+        }
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| format!("unclosed placeholder in {:?}", template))?;
+        let name = &after[..close];
+        if name.is_empty()
+            || name.len() > MAX_PARAM_LEN
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!(
+                "placeholder {{{}}} must be letters, digits or _",
+                name
+            ));
+        }
+        names.insert(name.to_string());
+        rest = &after[close + 1..];
+    }
+    if rest.contains('}') {
+        return Err(format!("stray }} in {:?}", template));
+    }
+    Ok(())
+}
+
+fn scan_value(value: &Value, names: &mut BTreeSet<String>) -> Result<(), String> {
+    match value {
+        Value::String(s) => scan(s, names),
+        Value::Array(items) => items.iter().try_for_each(|v| scan_value(v, names)),
+        Value::Object(map) => map.values().try_for_each(|v| scan_value(v, names)),
+        _ => Ok(()),
+    }
+}
+
+/// The parameters `action` needs to run.
+pub fn parameters(action: &QuickAction) -> Result<BTreeSet<String>, String> {
+    let mut names = BTreeSet::new();
+    scan(&action.path, &mut names)?;
+    if let Some(payload) = &action.payload {
+        scan_value(payload, &mut names)?;
+    }
+    Ok(names)
+}
+
+pub fn validate(actions: &[QuickAction], profiles: &[Profile]) -> Result<(), String> {
+    if actions.len() > MAX_ACTIONS {
+        return Err(format!("At most {} quick actions are allowed", MAX_ACTIONS));
+    }
+    let mut ids = HashSet::new();
+    for action in actions {
+        if action.id.trim().is_empty() {
+            return Err("Quick action id is required".to_string());
+        }
+        if !ids.insert(action.id.as_str()) {
+            return Err(format!("Duplicate quick action id: {}", action.id));
+        }
+        let name = action.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(format!(
+                "Quick action name must be 1 to {} bytes",
+                MAX_NAME_LEN
+            ));
+        }
+        let label = |e: String| format!("Quick action {:?}: {}", name, e);
+        if !METHODS.contains(&action.method.as_str()) {
+            return Err(label(format!(
+                "method must be one of {}",
+                METHODS.join(", ")
+            )));
+        }
+        if !action.path.starts_with('/') || action.path.contains("://") {
+            return Err(label(
+                "path must be a core path starting with /".to_string(),
+            ));
+        }
+        if action.payload.is_some() && action.method == "GET" {
+            return Err(label("a GET request takes no payload".to_string()));
+        }
+        parameters(action).map_err(label)?;
+        if let Some(profile) = &action.profile {
+            if !profiles.iter().any(|p| p.id == *profile) {
+                return Err(label(format!("unknown profile {}", profile)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters, so a
+/// parameter stays one path segment.
+pub fn encode_segment(raw: &str) -> String {
+    raw.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub fn fill(template: &str, params: &Map<String, Value>, encode: bool) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let close = after.find('}').unwrap_or(after.len());
+        let value = params.get(&after[..close]).map(text_of).unwrap_or_default();
+        out.push_str(&if encode {
+            encode_segment(&value)
+        } else {
+            value
+        });
+        rest = after.get(close + 1..).unwrap_or_default();
+    }
+    out.push_str(rest);
+    out
+}
+
+fn fill_value(value: &Value, params: &Map<String, Value>) -> Value {
+    match value {
+        Value::String(s) => {
+            let whole = s
+                .strip_prefix('{')
+                .and_then(|s| s.strip_suffix('}'))
+                .filter(|name| !name.contains(['{', '}']));
+            match whole.and_then(|name| params.get(name)) {
+                Some(param) => param.clone(),
+                None => Value::String(fill(s, params, false)),
+            }
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| fill_value(v, params)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), fill_value(v, params)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The request `action` sends with `params`, once every parameter it needs
+/// is given.
+fn render(
+    action: &QuickAction,
+    params: &Map<String, Value>,
+) -> Result<(String, Option<Value>), String> {
+    let missing: Vec<String> = parameters(action)
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?
+        .into_iter()
+        .filter(|name| {
+            params
+                .get(name)
+                .is_none_or(|v| text_of(v).trim().is_empty())
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Quick action {:?} needs {}",
+            action.name,
+            missing.join(", ")
+        )));
+    }
+    let path = fill(&action.path, params, true);
+    let payload = action.payload.as_ref().map(|p| fill_value(p, params));
+    Ok((path, payload))
+}
+
+fn describe(action: &QuickAction) -> Value {
+    json!({
+        "action": action,
+        "parameters": parameters(action).unwrap_or_default(),
+    })
+}
+
+fn find(store: &SettingsStore, id: &str) -> Result<QuickAction, String> {
+    store
+        .snapshot()
+        .quick_actions
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown quick action: {}", id)))
+}
+
+/// Sends the action's request and records it in the local audit trail.
+async fn execute(
+    store: &SettingsStore,
+    db: &LocalDb,
+    action: &QuickAction,
+    params: &Map<String, Value>,
+) -> Result<Value, String> {
+    let (path, payload) = render(action, params)?;
+    let conn = store.connection(action.profile.as_deref())?;
+    let method = Method::from_bytes(action.method.as_bytes())
+        .map_err(|_| ErrorCode::InvalidInput.with("Invalid method"))?;
+    let result =
+        crate::request_json(method, &conn.base_url, &path, conn.token.clone(), payload).await;
+    let out = json!({
+        "profile_id": conn.profile_id,
+        "id": action.id,
+        "name": action.name,
+        "method": action.method,
+        "path": path,
+        "ok": result.is_ok(),
+        "error": result.as_ref().err(),
+    });
+    db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "quick_action",
+        "run",
+        &action.id,
+        &out,
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    let response = result?;
+    Ok(json!({ "result": response, "request": out }))
+}
+
+/// Runs the tray entry for action `id`. One that needs parameters or a
+/// confirmation is handed to the window as `quick_action:requested`
+/// instead; the outcome of one run here is a notification.
+pub fn run_from_tray(app: &AppHandle, id: &str) {
+    let Ok(action) = find(&app.state::<SettingsStore>(), id) else {
+        return;
+    };
+    if action.confirm || !parameters(&action).unwrap_or_default().is_empty() {
+        crate::tray::show_main_window(app);
+        let _ = environment::emit(app, REQUESTED_EVENT, describe(&action));
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = execute(
+            &app.state::<SettingsStore>(),
+            &app.state::<LocalDb>(),
+            &action,
+            &Map::new(),
+        )
+        .await;
+        let (title, body) = match &result {
+            Ok(_) => (
+                format!("{} done", action.name),
+                "The core accepted the request".to_string(),
+            ),
+            Err(err) => (format!("{} failed", action.name), err.clone()),
+        };
+        notify::notify(
+            &app,
+            "quick_action",
+            &title,
+            &body,
+            json!({ "id": action.id }),
+        );
+    });
+}
+
+#[tauri::command]
+pub fn list_quick_actions(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let actions: Vec<Value> = store
+        .snapshot()
+        .quick_actions
+        .iter()
+        .map(describe)
+        .collect();
+    Ok(json!({ "actions": actions }))
+}
+
+/// Adds `action`, or replaces the one with its id; a new action without an
+/// id gets one.
+#[tauri::command]
+pub fn upsert_quick_action(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    mut action: QuickAction,
+) -> Result<Value, String> {
+    action.name = action.name.trim().to_string();
+    action.method = action.method.trim().to_ascii_uppercase();
+    action.path = action.path.trim().to_string();
+    if action.id.trim().is_empty() {
+        let hex: String = crate::crypto::random_bytes::<8>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        action.id = format!("action-{}", hex);
+    }
+    store.update(&app, |settings| {
+        match settings
+            .quick_actions
+            .iter_mut()
+            .find(|a| a.id == action.id)
+        {
+            Some(existing) => *existing = action.clone(),
+            None => settings.quick_actions.push(action.clone()),
+        }
+        Ok(())
+    })?;
+    Ok(describe(&action))
+}
+
+#[tauri::command]
+pub fn delete_quick_action(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+) -> Result<Value, String> {
+    store.update(&app, |settings| {
+        let before = settings.quick_actions.len();
+        settings.quick_actions.retain(|a| a.id != id);
+        if settings.quick_actions.len() == before {
+            return Err(ErrorCode::NotFound.with(format!("Unknown quick action: {}", id)));
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "deleted": id }))
+}
+
+/// Runs quick action `id` with `params`. An action that asks for
+/// confirmation first returns the rendered request with
+/// `confirmation_required`, and is sent when called again with `confirmed`.
+#[tauri::command]
+pub async fn run_quick_action(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    id: String,
+    params: Option<Map<String, Value>>,
+    confirmed: Option<bool>,
+) -> Result<Value, String> {
+    let action = find(&store, &id)?;
+    let params = params.unwrap_or_default();
+    if action.confirm && !confirmed.unwrap_or(false) {
+        let (path, payload) = render(&action, &params)?;
+        let profile_id = store.connection(action.profile.as_deref())?.profile_id;
+        return Ok(json!({
+            "confirmation_required": true,
+            "id": action.id,
+            "name": action.name,
+            "profile_id": profile_id,
+            "method": action.method,
+            "path": path,
+            "payload": payload,
+        }));
+    }
+    execute(&store, &db, &action, &params).await
+}
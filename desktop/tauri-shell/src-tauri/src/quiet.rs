@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{QuietHours, SettingsStore};
+use crate::{notify, tasks};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DIGEST_LIMIT: usize = 200;
+
+/// Notifications held back while quiet, delivered as one summary when the
+/// quiet period ends.
+#[derive(Default)]
+pub struct QuietDigest {
+    held: Mutex<Vec<Value>>,
+}
+
+/// Last OS Do-Not-Disturb / Focus reading; refreshed by the watcher since
+/// querying it spawns a process or reads a file.
+static OS_DND: AtomicBool = AtomicBool::new(false);
+
+/// Until when notifications are muted by hand, on every profile.
+static MUTED_UNTIL: Mutex<Option<DateTime<Utc>>> = Mutex::new(None);
+
+const TASK: &str = "quiet_hours";
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            OS_DND.store(os_dnd_active().await, Ordering::Relaxed);
+            flush_if_loud(&app);
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Mutes notifications until `until`, or unmutes them with `None`. Held
+/// ones are summarised once the mute ends, as after quiet hours.
+pub fn set_muted(until: Option<DateTime<Utc>>) {
+    if let Ok(mut muted) = MUTED_UNTIL.lock() {
+        *muted = until;
+    }
+}
+
+pub fn muted_until() -> Option<DateTime<Utc>> {
+    let mut muted = MUTED_UNTIL.lock().ok()?;
+    if muted.is_some_and(|until| until <= Utc::now()) {
+        *muted = None;
+    }
+    *muted
+}
+
+/// Why notifications are currently quiet, if they are.
+pub fn quiet_reason(settings: &QuietHours) -> Option<&'static str> {
+    if muted_until().is_some() {
+        Some("muted")
+    } else if settings.enabled && in_quiet_hours(settings, Local::now().time()) {
+        Some("quiet_hours")
+    } else if settings.respect_os_dnd && OS_DND.load(Ordering::Relaxed) {
+        Some("os_dnd")
+    } else {
+        None
+    }
+}
+
+/// Events at critical risk still pop up and sound while quiet, unless the
+/// override is turned off.
+pub fn is_critical(settings: &QuietHours, data: &Value) -> bool {
+    settings.critical_override
+        && ["risk", "risk_level"].iter().any(|k| {
+            data[*k]
+                .as_str()
+                .is_some_and(|r| r.eq_ignore_ascii_case("critical"))
+        })
+}
+
+/// Keeps a notification for the summary sent when `profile_id`'s quiet
+/// period ends.
+pub fn hold(app: &AppHandle, profile_id: &str, kind: &str, title: &str, body: &str) {
+    let Some(digest) = app.try_state::<QuietDigest>() else {
+        return;
+    };
+    let Ok(mut held) = digest.held.lock() else {
+        return;
+    };
+    if held.len() < DIGEST_LIMIT {
+        held.push(json!({
+            "profile_id": profile_id,
+            "kind": kind,
+            "title": title,
+            "body": body,
+            "at": chrono::Utc::now().to_rfc3339(),
+        }));
+    }
+}
+
+/// `HH:MM` in local time; a window whose end is before its start spans
+/// midnight.
+pub fn parse_time(raw: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(raw.trim(), "%H:%M")
+        .map_err(|_| format!("Quiet hours time must be HH:MM, got {:?}", raw))
+}
+
+fn in_quiet_hours(settings: &QuietHours, now: NaiveTime) -> bool {
+    let (Ok(start), Ok(end)) = (parse_time(&settings.start), parse_time(&settings.end)) else {
+        return false;
+    };
+    if start <= end {
+        now >= start && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+/// Sends the held notifications of every profile that is no longer quiet,
+/// one summary per profile.
+fn flush_if_loud(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().snapshot();
+    let digest = app.state::<QuietDigest>();
+    let mut released: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    match digest.held.lock() {
+        Ok(mut held) if !held.is_empty() => {
+            held.retain(|n| {
+                let profile_id = notify::profile_of(&settings, n);
+                let quiet = &notify::effective(&settings, &profile_id).quiet_hours;
+                if quiet_reason(quiet).is_some() {
+                    return true;
+                }
+                released.entry(profile_id).or_default().push(n.clone());
+                false
+            });
+        }
+        _ => return,
+    }
+    for (profile_id, held) in released {
+        let titles: Vec<&str> = held.iter().filter_map(|n| n["title"].as_str()).collect();
+        let body = match titles.as_slice() {
+            [only] => only.to_string(),
+            [first, rest @ ..] => format!("{} and {} more", first, rest.len()),
+            [] => String::new(),
+        };
+        notify::notify(
+            app,
+            "digest",
+            &format!("{} notifications while quiet", held.len()),
+            &body,
+            json!({ "profile_id": profile_id, "notifications": held }),
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn os_dnd_active() -> bool {
+    tokio::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()
+        .await
+        .is_ok_and(|out| {
+            out.status.success() && String::from_utf8_lossy(&out.stdout).trim() == "false"
+        })
+}
+
+/// Focus modes leave assertion records in this file while active.
+#[cfg(target_os = "macos")]
+async fn os_dnd_active() -> bool {
+    let Some(home) = std::env::var_os("HOME") else {
+        return false;
+    };
+    let path = std::path::Path::new(&home).join("Library/DoNotDisturb/DB/Assertions.json");
+    let Ok(raw) = tokio::fs::read(path).await else {
+        return false;
+    };
+    serde_json::from_slice::<Value>(&raw).is_ok_and(|doc| {
+        doc["data"].as_array().is_some_and(|entries| {
+            entries.iter().any(|entry| {
+                entry["storeAssertionRecords"]
+                    .as_array()
+                    .is_some_and(|records| !records.is_empty())
+            })
+        })
+    })
+}
+
+#[cfg(target_os = "windows")]
+async fn os_dnd_active() -> bool {
+    tokio::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings",
+            "/v",
+            "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+        ])
+        .output()
+        .await
+        .is_ok_and(|out| {
+            out.status.success() && String::from_utf8_lossy(&out.stdout).contains("0x0")
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+async fn os_dnd_active() -> bool {
+    false
+}
+
+#[tauri::command]
+pub fn get_quiet_status(
+    store: State<'_, SettingsStore>,
+    digest: State<'_, QuietDigest>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let snapshot = store.snapshot();
+    let profile_id = profile.unwrap_or_else(|| snapshot.active_profile.clone());
+    if snapshot.profile(&profile_id).is_none() {
+        return Err(ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)));
+    }
+    let settings = notify::effective(&snapshot, &profile_id).quiet_hours;
+    let held = digest
+        .held
+        .lock()
+        .map(|h| {
+            h.iter()
+                .filter(|n| notify::profile_of(&snapshot, n) == profile_id)
+                .count()
+        })
+        .unwrap_or(0);
+    Ok(json!({
+        "profile_id": profile_id,
+        "quiet": quiet_reason(&settings).is_some(),
+        "reason": quiet_reason(&settings),
+        "os_dnd": OS_DND.load(Ordering::Relaxed),
+        "muted_until": muted_until().map(|t| t.to_rfc3339()),
+        "held": held,
+        "quiet_hours": settings,
+    }))
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::errors::ErrorCode;
+use crate::{bandwidth, environment, maintenance, net, tasks};
+
+const TASK: &str = "rate_limits";
+const QUOTA_EVENT: &str = "core:rate_limit";
+const TICK_INTERVAL: Duration = Duration::from_secs(2);
+/// Share of the quota background traffic leaves to the operator, and the
+/// least it leaves.
+const RESERVE_PERCENT: u64 = 20;
+const MIN_RESERVE: u64 = 5;
+/// A background request that would wait longer fails with `RATE_LIMITED`
+/// instead, for its loop to try again on its next pass.
+const MAX_WAIT: Duration = Duration::from_secs(20);
+/// How long a 429 without `Retry-After` holds background requests.
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(5);
+/// A reset further off than this is taken for a Unix time.
+const MAX_RESET_DELTA: u64 = 1_000_000_000;
+
+struct Quota {
+    base_url: String,
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<(Instant, DateTime<Utc>)>,
+    /// Set by a 429 until its `Retry-After` runs out.
+    blocked: Option<(Instant, DateTime<Utc>)>,
+    /// When the next background request may go, to spread what is left of
+    /// the quota over the rest of its window.
+    next_background: Instant,
+    /// Background requests delayed or refused since the window opened.
+    held: u64,
+    /// Bumped on every change, for the watcher to tell what to announce.
+    version: u64,
+}
+
+impl Quota {
+    fn reserve(&self) -> u64 {
+        self.limit
+            .map_or(MIN_RESERVE, |limit| limit * RESERVE_PERCENT / 100)
+            .max(MIN_RESERVE)
+    }
+
+    fn describe(&self) -> Value {
+        let now = Instant::now();
+        let reset = self.reset.filter(|(at, _)| *at > now);
+        let blocked = self.blocked.filter(|(at, _)| *at > now);
+        json!({
+            "base_url": self.base_url,
+            "limit": self.limit,
+            "remaining": self.remaining,
+            "reserve": self.reserve(),
+            "reset_at": reset.map(|(_, at)| at.to_rfc3339()),
+            "reset_in_seconds": reset.map(|(at, _)| at.duration_since(now).as_secs()),
+            "retry_at": blocked.map(|(_, at)| at.to_rfc3339()),
+            "retry_in_seconds": blocked.map(|(at, _)| at.duration_since(now).as_secs()),
+            "background_held": self.held,
+        })
+    }
+}
+
+/// What each core last said about its rate limit, keyed by origin.
+static QUOTAS: OnceLock<Mutex<HashMap<String, Quota>>> = OnceLock::new();
+
+fn quotas() -> &'static Mutex<HashMap<String, Quota>> {
+    QUOTAS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The first number in a header value: `100` from `100, 100;w=60`.
+fn number(raw: &str) -> Option<u64> {
+    raw.split([',', ';'])
+        .next()?
+        .trim()
+        .trim_matches('"')
+        .parse()
+        .ok()
+}
+
+fn header(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok().and_then(number))
+}
+
+/// `limit`, `remaining` and `reset` from the combined `RateLimit` header,
+/// in either the `limit=100, remaining=5, reset=30` form or the later
+/// `"default";r=5;t=30` one.
+fn combined(headers: &HeaderMap) -> (Option<u64>, Option<u64>, Option<u64>) {
+    let Some(raw) = headers.get("ratelimit").and_then(|v| v.to_str().ok()) else {
+        return (None, None, None);
+    };
+    let mut found = (None, None, None);
+    for part in raw.split([',', ';']) {
+        let Some((key, value)) = part.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').parse().ok();
+        match key.trim() {
+            "limit" => found.0 = value,
+            "remaining" | "r" => found.1 = value,
+            "reset" | "t" => found.2 = value,
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Takes in the quota a core reported on a response: the `RateLimit-*`
+/// headers, their `X-RateLimit-*` forerunners or the combined `RateLimit`
+/// header, and `Retry-After` on a 429.
+pub fn observe(base_url: &str, status: u16, headers: &HeaderMap) {
+    let Some(origin) = net::base_origin(base_url) else {
+        return;
+    };
+    let (mut limit, mut remaining, mut reset) = combined(headers);
+    limit = limit.or_else(|| header(headers, &["ratelimit-limit", "x-ratelimit-limit"]));
+    remaining =
+        remaining.or_else(|| header(headers, &["ratelimit-remaining", "x-ratelimit-remaining"]));
+    reset = reset.or_else(|| header(headers, &["ratelimit-reset", "x-ratelimit-reset"]));
+    let limited = status == 429;
+    if !limited && limit.is_none() && remaining.is_none() {
+        return;
+    }
+    let now = Instant::now();
+    let reset = reset.map(|secs| {
+        let secs = if secs > MAX_RESET_DELTA {
+            (secs as i64 - Utc::now().timestamp()).max(0) as u64
+        } else {
+            secs
+        };
+        let delta = Duration::from_secs(secs);
+        (
+            now + delta,
+            Utc::now() + chrono::Duration::from_std(delta).unwrap_or_default(),
+        )
+    });
+    let Ok(mut quotas) = quotas().lock() else {
+        return;
+    };
+    let quota = quotas.entry(origin).or_insert_with(|| Quota {
+        base_url: base_url.trim().trim_end_matches('/').to_string(),
+        limit: None,
+        remaining: None,
+        reset: None,
+        blocked: None,
+        next_background: now,
+        held: 0,
+        version: 0,
+    });
+    let window_ended = quota.reset.is_some_and(|(at, _)| at <= now);
+    if window_ended {
+        quota.held = 0;
+    }
+    quota.limit = limit.or(quota.limit);
+    quota.remaining = remaining.or(if limited { Some(0) } else { quota.remaining });
+    quota.reset = reset.or(quota.reset.filter(|_| !window_ended));
+    if limited {
+        let delay = maintenance::retry_after(headers)
+            .or_else(|| quota.reset.map(|(at, _)| at.saturating_duration_since(now)))
+            .filter(|d| !d.is_zero())
+            .unwrap_or(DEFAULT_BACKOFF);
+        quota.blocked = Some((
+            now + delay,
+            Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default(),
+        ));
+    }
+    quota.version += 1;
+}
+
+/// Holds a background request to the core's quota, so the pollers and the
+/// sync never spend what the operator's own requests need: once the
+/// remaining quota is down to the reserve they wait for the window to
+/// reset, and above it they are spread over the rest of the window. After
+/// a 429 they wait out its `Retry-After`. A wait longer than `MAX_WAIT`
+/// fails with `RATE_LIMITED` instead. Interactive requests are never held.
+pub async fn pace(base_url: &str) -> Result<(), String> {
+    let Some(origin) = net::base_origin(base_url) else {
+        return Ok(());
+    };
+    let background = bandwidth::current() == bandwidth::POLLING;
+    let wait = {
+        let Ok(mut quotas) = quotas().lock() else {
+            return Ok(());
+        };
+        let Some(quota) = quotas.get_mut(&origin) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let reset = quota.reset.map(|(at, _)| at).filter(|at| *at > now);
+        if reset.is_none() && quota.reset.is_some() {
+            // The window the counts were for is over.
+            quota.remaining = quota.limit;
+            quota.reset = None;
+            quota.held = 0;
+            quota.version += 1;
+        }
+        let reserve = quota.reserve();
+        let wait = match (quota.blocked.map(|(at, _)| at), quota.remaining, reset) {
+            (Some(until), _, _) if until > now => until - now,
+            (_, Some(remaining), Some(reset)) if remaining <= reserve => reset - now,
+            (_, Some(remaining), Some(reset)) => {
+                let spacing =
+                    (reset - now) / (remaining - reserve).clamp(1, u32::MAX as u64) as u32;
+                let start = quota.next_background.max(now);
+                if background {
+                    quota.next_background = start + spacing;
+                }
+                start - now
+            }
+            _ => Duration::ZERO,
+        };
+        if !background {
+            // Counted until the core reports again.
+            quota.remaining = quota.remaining.map(|r| r.saturating_sub(1));
+            return Ok(());
+        }
+        if wait > MAX_WAIT {
+            quota.held += 1;
+            quota.version += 1;
+            return Err(ErrorCode::RateLimited.with(format!(
+                "Background request held back to keep the core's rate limit for the operator; \
+                 retry in {}s",
+                wait.as_secs().max(1)
+            )));
+        }
+        quota.remaining = quota.remaining.map(|r| r.saturating_sub(1));
+        if !wait.is_zero() {
+            quota.held += 1;
+            quota.version += 1;
+        }
+        wait
+    };
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+    Ok(())
+}
+
+/// Emits `core:rate_limit` for each core whose quota changed since the
+/// last tick.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        let mut announced: HashMap<String, u64> = HashMap::new();
+        loop {
+            let mut changed = Vec::new();
+            if let Ok(quotas) = quotas().lock() {
+                for (origin, quota) in quotas.iter() {
+                    if announced.insert(origin.clone(), quota.version) != Some(quota.version) {
+                        changed.push(quota.describe());
+                    }
+                }
+            }
+            for payload in changed {
+                let _ = environment::emit(&app, QUOTA_EVENT, &payload);
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+/// The last quota each core reported, with the share held back for the
+/// operator and how many background requests waited for it.
+#[tauri::command]
+pub fn get_rate_limits() -> Result<Value, String> {
+    let quotas = quotas()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Rate limit state poisoned"))?;
+    let cores: Vec<Value> = quotas.values().map(Quota::describe).collect();
+    Ok(json!({ "cores": cores }))
+}
@@ -0,0 +1,195 @@
+use chrono::{TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+
+const KINDS: &[&str] = &["plan", "agent", "search"];
+/// A visit counts half as much after this long.
+const HALF_LIFE_MS: f64 = 7.0 * 86_400_000.0;
+/// Items kept per profile; the lowest ranked go first.
+const MAX_ITEMS: usize = 500;
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 200;
+const MAX_ID_LEN: usize = 512;
+const MAX_LABEL_LEN: usize = 512;
+
+/// Frecency is a sum of visits, each decaying by half every `HALF_LIFE_MS`.
+/// Every item decays at the same rate, so instead of the score the table
+/// keeps `log2(score)` projected to time zero: ordering by it is ordering
+/// by the current score at any moment, and the ranking stays an index scan.
+fn rank(score: f64, at_ms: i64) -> f64 {
+    score.log2() + at_ms as f64 / HALF_LIFE_MS
+}
+
+fn score(rank: f64, now_ms: i64) -> f64 {
+    (rank - now_ms as f64 / HALF_LIFE_MS).exp2()
+}
+
+fn parse_kind(raw: &str) -> Result<&'static str, String> {
+    let raw = raw.trim().to_ascii_lowercase();
+    KINDS.iter().find(|k| **k == raw).copied().ok_or_else(|| {
+        ErrorCode::InvalidInput.with(format!("Kind must be one of {}", KINDS.join(", ")))
+    })
+}
+
+/// Searches are one item per query, however they were capitalized or spaced.
+fn item_key(kind: &str, raw: &str) -> String {
+    if kind == "search" {
+        raw.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    } else {
+        raw.trim().to_string()
+    }
+}
+
+fn visit(
+    conn: &Connection,
+    profile_id: &str,
+    kind: &str,
+    item_id: &str,
+    label: &str,
+) -> rusqlite::Result<()> {
+    let now = Utc::now().timestamp_millis();
+    let previous: Option<f64> = conn
+        .query_row(
+            "SELECT rank FROM recent_items WHERE profile_id = ?1 AND kind = ?2 AND item_id = ?3",
+            params![profile_id, kind, item_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let next = rank(previous.map_or(0.0, |r| score(r, now)) + 1.0, now);
+    conn.execute(
+        "INSERT INTO recent_items (profile_id, kind, item_id, label, visits, first_ms, last_ms, rank)
+         VALUES (?1, ?2, ?3, ?4, 1, ?5, ?5, ?6)
+         ON CONFLICT(profile_id, kind, item_id) DO UPDATE SET
+             label = CASE WHEN excluded.label = '' THEN label ELSE excluded.label END,
+             visits = visits + 1, last_ms = excluded.last_ms, rank = excluded.rank",
+        params![profile_id, kind, item_id, label, now, next],
+    )?;
+    conn.execute(
+        "DELETE FROM recent_items WHERE profile_id = ?1 AND rank < (
+             SELECT rank FROM recent_items WHERE profile_id = ?1
+             ORDER BY rank DESC LIMIT 1 OFFSET ?2)",
+        params![profile_id, MAX_ITEMS as i64 - 1],
+    )?;
+    Ok(())
+}
+
+/// Counts a visit to a plan, agent or search (`item_id` is the query) for
+/// the profile's recent items.
+#[tauri::command]
+pub fn record_recent_item(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    kind: String,
+    item_id: String,
+    label: Option<String>,
+) -> Result<Value, String> {
+    let kind = parse_kind(&kind)?;
+    let item_id = item_key(kind, &item_id);
+    if item_id.is_empty() || item_id.len() > MAX_ID_LEN {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("Item id must be 1 to {} bytes", MAX_ID_LEN))
+        );
+    }
+    let label: String = label
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_LABEL_LEN)
+        .collect();
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    visit(&*db.lock()?, &profile_id, kind, &item_id, &label)
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    Ok(json!({ "profile_id": profile_id, "kind": kind, "id": item_id }))
+}
+
+/// The profile's recent plans, agents and searches (or one `kind`), most
+/// likely needed first: visits weigh by how recent they are, halving each
+/// week, so a plan opened daily outranks one opened once this morning.
+#[tauri::command]
+pub fn get_recent_items(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    kind: Option<String>,
+    limit: Option<usize>,
+) -> Result<Value, String> {
+    let kind = kind
+        .as_deref()
+        .filter(|k| !k.trim().is_empty())
+        .map(parse_kind)
+        .transpose()?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let now = Utc::now().timestamp_millis();
+    let guard = db.lock()?;
+    let mut stmt = guard
+        .prepare(
+            "SELECT kind, item_id, label, visits, first_ms, last_ms, rank FROM recent_items
+             WHERE profile_id = ?1 AND (?2 IS NULL OR kind = ?2)
+             ORDER BY rank DESC LIMIT ?3",
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let at = |ms: i64| {
+        Utc.timestamp_millis_opt(ms)
+            .single()
+            .map(|t| t.to_rfc3339())
+    };
+    let items: Vec<Value> = stmt
+        .query_map(params![profile_id, kind, limit as i64], |row| {
+            Ok(json!({
+                "kind": row.get::<_, String>(0)?,
+                "id": row.get::<_, String>(1)?,
+                "label": row.get::<_, String>(2)?,
+                "visits": row.get::<_, i64>(3)?,
+                "first_visited_at": at(row.get(4)?),
+                "last_visited_at": at(row.get(5)?),
+                "score": (score(row.get(6)?, now) * 1000.0).round() / 1000.0,
+            }))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    Ok(json!({ "profile_id": profile_id, "items": items }))
+}
+
+/// Forgets the profile's recent items: all of them, one `kind`, or one item.
+#[tauri::command]
+pub fn clear_recent_items(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    kind: Option<String>,
+    item_id: Option<String>,
+) -> Result<Value, String> {
+    let kind = kind
+        .as_deref()
+        .filter(|k| !k.trim().is_empty())
+        .map(parse_kind)
+        .transpose()?;
+    let item_id = match (kind, item_id) {
+        (Some(kind), Some(id)) => Some(item_key(kind, &id)),
+        (None, Some(_)) => {
+            return Err(ErrorCode::InvalidInput.with("Forgetting one item needs its kind"))
+        }
+        (_, None) => None,
+    };
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let removed = db
+        .lock()?
+        .execute(
+            "DELETE FROM recent_items WHERE profile_id = ?1
+             AND (?2 IS NULL OR kind = ?2) AND (?3 IS NULL OR item_id = ?3)",
+            params![profile_id, kind, item_id],
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    Ok(json!({ "profile_id": profile_id, "removed": removed }))
+}
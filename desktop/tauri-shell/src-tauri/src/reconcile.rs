@@ -0,0 +1,112 @@
+use serde_json::{json, Map, Value};
+
+use crate::decisions;
+
+/// The parts of a plan a decision is about. Any other change (comments,
+/// timestamps, metadata) bumps its version without changing what was
+/// decided on.
+const DECISION_FIELDS: &[&str] = &[
+    "objective",
+    "strategy",
+    "actions",
+    "diff",
+    "risk",
+    "risk_level",
+    "risk_report",
+];
+
+/// How a queued decision stands against the plan as it is now.
+pub enum Reconciled {
+    /// Nothing the decision depends on changed; send it.
+    Clean,
+    /// The plan already is where the decision would take it.
+    AlreadyApplied(String),
+    /// The plan moved in a way the operator has to look at first.
+    Conflict(Value),
+}
+
+/// What is kept of a plan when a decision on it is queued: its status,
+/// version and the fields the decision depends on.
+pub fn snapshot(plan: &Value) -> Value {
+    let mut kept = Map::new();
+    kept.insert("status".to_string(), plan["status"].clone());
+    kept.insert("version".to_string(), json!(decisions::plan_version(plan)));
+    for field in DECISION_FIELDS {
+        if let Some(value) = plan.get(*field) {
+            kept.insert(field.to_string(), value.clone());
+        }
+    }
+    Value::Object(kept)
+}
+
+fn settled_statuses(decision: &str) -> &'static [&'static str] {
+    match decision {
+        "approve" => &[
+            "approved",
+            "executing",
+            "running",
+            "executed",
+            "succeeded",
+            "completed",
+        ],
+        "reject" => &["rejected"],
+        _ => &[],
+    }
+}
+
+/// Three-way comparison of a queued `decision` between `base` (the plan
+/// when the decision was queued), and `current`. A plan whose version moved
+/// but whose decision fields did not is still clean. Without a base, from
+/// entries queued before snapshots were kept, the `pinned` version is all
+/// there is to compare.
+pub fn reconcile(
+    decision: &str,
+    base: Option<&Value>,
+    pinned: Option<&str>,
+    current: &Value,
+) -> Reconciled {
+    let status = current["status"].as_str().unwrap_or_default();
+    if settled_statuses(decision).contains(&status) {
+        return Reconciled::AlreadyApplied(status.to_string());
+    }
+    let current_version = decisions::plan_version(current);
+    let mut fields = Vec::new();
+    if status != "pending" {
+        fields.push(json!({
+            "field": "status",
+            "base": base.map_or(json!("pending"), |b| b["status"].clone()),
+            "queued": decision,
+            "current": status,
+        }));
+    }
+    match base {
+        Some(base) => {
+            for field in DECISION_FIELDS {
+                let before = base.get(*field).unwrap_or(&Value::Null);
+                let now = current.get(*field).unwrap_or(&Value::Null);
+                if before != now {
+                    fields.push(json!({ "field": field, "base": before, "current": now }));
+                }
+            }
+        }
+        None => {
+            if let Some(pinned) = pinned.filter(|p| Some(*p) != current_version.as_deref()) {
+                fields.push(json!({
+                    "field": "version",
+                    "base": pinned,
+                    "current": current_version,
+                }));
+            }
+        }
+    }
+    if fields.is_empty() {
+        return Reconciled::Clean;
+    }
+    Reconciled::Conflict(json!({
+        "reason": "Plan changed after the decision was queued",
+        "decision": decision,
+        "base_version": base.map_or(json!(pinned), |b| b["version"].clone()),
+        "current_version": current_version,
+        "fields": fields,
+    }))
+}
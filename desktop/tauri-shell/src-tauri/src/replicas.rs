@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{self, Profile, ReplicaSettings, SettingsStore};
+use crate::{api_prefix, environment, net, routing, tasks};
+
+const TASK: &str = "replicas";
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const CHANGED_EVENT: &str = "core:endpoint_changed";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REPLICAS: usize = 8;
+const MIN_CHECK_SECS: u64 = 5;
+const MAX_CHECK_SECS: u64 = 3_600;
+
+#[derive(Clone, Default, Serialize)]
+struct Endpoint {
+    url: String,
+    /// `None` until the first health check or failed request.
+    healthy: Option<bool>,
+    latency_ms: Option<u64>,
+    last_error: Option<String>,
+    checked_at: Option<String>,
+}
+
+struct Group {
+    settings: ReplicaSettings,
+    endpoints: Vec<Endpoint>,
+    active: usize,
+    checked: Option<Instant>,
+}
+
+impl Group {
+    fn new(profile: &Profile) -> Self {
+        Self {
+            settings: profile.replicas.clone(),
+            endpoints: profile
+                .endpoints()
+                .map(|url| Endpoint {
+                    url: url.to_string(),
+                    ..Endpoint::default()
+                })
+                .collect(),
+            active: 0,
+            checked: None,
+        }
+    }
+
+    fn matches(&self, profile: &Profile) -> bool {
+        self.settings == profile.replicas
+            && self
+                .endpoints
+                .iter()
+                .map(|e| e.url.as_str())
+                .eq(profile.endpoints())
+    }
+
+    /// The endpoint to move to, if any: the fastest healthy one when the
+    /// active endpoint is not known to be healthy, or when it beats the
+    /// active one by `switch_margin_pct`.
+    fn better(&self) -> Option<usize> {
+        let fastest = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.healthy == Some(true))
+            .min_by_key(|(_, e)| e.latency_ms.unwrap_or(u64::MAX))
+            .map(|(i, _)| i)
+            .filter(|i| *i != self.active)?;
+        let active = &self.endpoints[self.active];
+        if active.healthy != Some(true) {
+            return Some(fastest);
+        }
+        let (current, candidate) = (active.latency_ms?, self.endpoints[fastest].latency_ms?);
+        let margin = 100 + u64::from(self.settings.switch_margin_pct);
+        (candidate * margin < current * 100).then_some(fastest)
+    }
+
+    fn switch(&mut self, profile_id: &str, to: usize, reason: &str) -> Value {
+        let from = std::mem::replace(&mut self.active, to);
+        json!({
+            "profile_id": profile_id,
+            "from": self.endpoints[from].url,
+            "to": self.endpoints[to].url,
+            "reason": reason,
+            "latency_ms": self.endpoints[to].latency_ms,
+            "at": Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Replicas {
+    groups: HashMap<String, Group>,
+    /// Endpoint changes made in the request path, emitted by the checker.
+    pending: Vec<Value>,
+}
+
+static REPLICAS: OnceLock<Mutex<Replicas>> = OnceLock::new();
+
+fn replicas() -> &'static Mutex<Replicas> {
+    REPLICAS.get_or_init(|| Mutex::new(Replicas::default()))
+}
+
+/// Tracks the profiles that have replicas; a profile whose endpoints or
+/// replica settings changed starts over on `base_url`.
+pub fn configure(profiles: &[Profile]) {
+    let mut state = replicas().lock().unwrap_or_else(|e| e.into_inner());
+    state.groups.retain(|id, group| {
+        profiles
+            .iter()
+            .any(|p| p.id == *id && !p.replicas.urls.is_empty() && group.matches(p))
+    });
+    for profile in profiles.iter().filter(|p| !p.replicas.urls.is_empty()) {
+        state
+            .groups
+            .entry(profile.id.clone())
+            .or_insert_with(|| Group::new(profile));
+    }
+}
+
+pub fn validate(profile: &Profile) -> Result<(), String> {
+    let replicas = &profile.replicas;
+    if replicas.urls.len() > MAX_REPLICAS {
+        return Err(format!("At most {} replica URLs are allowed", MAX_REPLICAS));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for url in profile.endpoints() {
+        settings::validate_base_url(url).map_err(|e| format!("Replica {}: {}", url, e))?;
+        if !seen.insert(net::base_origin(url)) {
+            return Err(format!("Replica {} repeats another endpoint", url));
+        }
+    }
+    if !replicas.health_path.starts_with('/') {
+        return Err("Replica health path must start with /".to_string());
+    }
+    if !(MIN_CHECK_SECS..=MAX_CHECK_SECS).contains(&replicas.check_interval_secs) {
+        return Err(format!(
+            "Replica check interval must be between {} and {} seconds",
+            MIN_CHECK_SECS, MAX_CHECK_SECS
+        ));
+    }
+    Ok(())
+}
+
+/// The base URL requests for `profile` should go to right now.
+pub fn active(profile: &Profile) -> String {
+    replicas()
+        .lock()
+        .ok()
+        .and_then(|state| {
+            let group = state.groups.get(&profile.id)?;
+            Some(group.endpoints.get(group.active)?.url.clone())
+        })
+        .unwrap_or_else(|| profile.base_url.clone())
+}
+
+/// Marks the endpoint at `base_url` down after a request to it could not
+/// be completed, moving its profile to the fastest healthy endpoint (or the
+/// next one not known to be down) when it was the active one. Returns the
+/// endpoint now active when that is a different one, so the caller can try
+/// again there.
+pub fn fail(base_url: &str, error: &str) -> Option<String> {
+    let origin = net::base_origin(base_url)?;
+    let mut state = replicas().lock().ok()?;
+    let Replicas { groups, pending } = &mut *state;
+    for (profile_id, group) in groups.iter_mut() {
+        let Some(index) = group
+            .endpoints
+            .iter()
+            .position(|e| net::base_origin(&e.url).as_deref() == Some(origin.as_str()))
+        else {
+            continue;
+        };
+        let endpoint = &mut group.endpoints[index];
+        endpoint.healthy = Some(false);
+        endpoint.last_error = Some(error.to_string());
+        endpoint.checked_at = Some(Utc::now().to_rfc3339());
+        if index == group.active {
+            let count = group.endpoints.len();
+            let next = group.better().or_else(|| {
+                (1..count)
+                    .map(|k| (index + k) % count)
+                    .find(|i| group.endpoints[*i].healthy != Some(false))
+            });
+            if let Some(next) = next {
+                pending.push(group.switch(profile_id, next, "failover"));
+            }
+        }
+        return (group.active != index).then(|| group.endpoints[group.active].url.clone());
+    }
+    None
+}
+
+/// Time to a successful answer from `path` on the endpoint, in ms.
+async fn probe(base_url: String, path: String) -> (String, Result<u64, String>) {
+    let result = async {
+        let url = Url::parse(&format!(
+            "{}{}{}",
+            base_url.trim().trim_end_matches('/'),
+            api_prefix::for_request(&base_url, &path),
+            path
+        ))
+        .map_err(|e| format!("Invalid URL: {}", e))?;
+        let client = net::client_for(&url)?;
+        let mut request = client
+            .get(url)
+            .timeout(PROBE_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Invalid request: {}", e))?;
+        net::sign(&mut request).await?;
+        let started = Instant::now();
+        let response = client
+            .execute(request)
+            .await
+            .map_err(|e| format!("Health check failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Health check answered {}", status.as_u16()));
+        }
+        Ok(started.elapsed().as_millis() as u64)
+    }
+    .await;
+    (base_url, result)
+}
+
+/// Health-checks every endpoint of the profiles that have replicas once
+/// per `check_interval_secs`, moving each to its fastest healthy endpoint,
+/// and emits `core:endpoint_changed` whenever a profile's active endpoint
+/// changes.
+pub fn spawn_checker(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            check(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+async fn check(app: &AppHandle) {
+    let due: Vec<(String, Vec<String>, String)> = {
+        let mut state = replicas().lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .groups
+            .iter_mut()
+            .filter(|(_, group)| {
+                group.checked.is_none_or(|at| {
+                    at.elapsed() >= Duration::from_secs(group.settings.check_interval_secs)
+                })
+            })
+            .map(|(id, group)| {
+                group.checked = Some(Instant::now());
+                (
+                    id.clone(),
+                    group.endpoints.iter().map(|e| e.url.clone()).collect(),
+                    group.settings.health_path.clone(),
+                )
+            })
+            .collect()
+    };
+    for (profile_id, urls, path) in due {
+        let mut probes = tokio::task::JoinSet::new();
+        for url in urls {
+            probes.spawn(probe(url, path.clone()));
+        }
+        let mut results = Vec::new();
+        while let Some(Ok(result)) = probes.join_next().await {
+            results.push(result);
+        }
+        let mut state = replicas().lock().unwrap_or_else(|e| e.into_inner());
+        let Replicas { groups, pending } = &mut *state;
+        // Settings may have changed while the probes ran.
+        let Some(group) = groups.get_mut(&profile_id) else {
+            continue;
+        };
+        let now = Utc::now().to_rfc3339();
+        for (url, result) in results {
+            let Some(endpoint) = group.endpoints.iter_mut().find(|e| e.url == url) else {
+                continue;
+            };
+            endpoint.checked_at = Some(now.clone());
+            match result {
+                Ok(ms) => {
+                    endpoint.healthy = Some(true);
+                    endpoint.latency_ms = Some(ms);
+                    endpoint.last_error = None;
+                }
+                Err(err) => {
+                    endpoint.healthy = Some(false);
+                    endpoint.last_error = Some(err);
+                }
+            }
+        }
+        if let Some(next) = group.better() {
+            let reason = if group.endpoints[group.active].healthy == Some(true) {
+                "faster"
+            } else {
+                "unhealthy"
+            };
+            pending.push(group.switch(&profile_id, next, reason));
+        }
+    }
+    let changes = std::mem::take(&mut replicas().lock().unwrap_or_else(|e| e.into_inner()).pending);
+    for change in changes {
+        let _ = environment::emit(app, CHANGED_EVENT, &change);
+    }
+}
+
+/// A profile's endpoints with their last health check, the one requests
+/// currently go to, and where its reads are routed.
+#[tauri::command]
+pub fn replica_status(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = store.snapshot();
+    let profile = settings
+        .profile(&conn.profile_id)
+        .ok_or_else(|| ErrorCode::NotFound.with("Profile disappeared"))?;
+    let state = replicas().lock().unwrap_or_else(|e| e.into_inner());
+    let endpoints = match state.groups.get(&profile.id) {
+        Some(group) => json!(group.endpoints),
+        None => json!([{ "url": profile.base_url }]),
+    };
+    Ok(json!({
+        "profile_id": profile.id,
+        "active": conn.base_url,
+        "endpoints": endpoints,
+        "settings": profile.replicas,
+        "read_routing": routing::describe(&profile.id),
+    }))
+}
@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+
+const MAX_ENTRIES: usize = 500;
+const DEFAULT_LIMIT: usize = 100;
+
+/// The most recent core requests, newest last; kept in memory only.
+static HISTORY: OnceLock<Mutex<VecDeque<Value>>> = OnceLock::new();
+
+fn history() -> &'static Mutex<VecDeque<Value>> {
+    HISTORY.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Without the query string, which may carry ids or filters better left
+/// out of a log.
+fn display(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+/// Records one request sent to `url`. `final_url` is where the last
+/// redirect led, when it led anywhere else.
+pub fn record(
+    method: &Method,
+    url: &Url,
+    final_url: Option<&Url>,
+    outcome: Result<u16, String>,
+    sent_at: DateTime<Utc>,
+) {
+    let redirected = final_url.filter(|f| f.as_str() != url.as_str());
+    let (status, error) = match outcome {
+        Ok(status) => (Some(status), None),
+        Err(err) => (None, Some(err)),
+    };
+    let entry = json!({
+        "at": sent_at.to_rfc3339(),
+        "method": method.as_str(),
+        "url": display(url),
+        "final_url": redirected.map(display),
+        "redirected": redirected.is_some(),
+        "status": status,
+        "error": error,
+        "ms": (Utc::now() - sent_at).num_milliseconds().max(0),
+    });
+    let mut history = history().lock().unwrap_or_else(|e| e.into_inner());
+    if history.len() >= MAX_ENTRIES {
+        history.pop_front();
+    }
+    history.push_back(entry);
+}
+
+/// The latest `limit` core requests, newest first, each with the URL asked
+/// for and, after redirects, the `final_url` that answered.
+#[tauri::command]
+pub fn get_request_history(limit: Option<usize>) -> Value {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_ENTRIES);
+    let history = history().lock().unwrap_or_else(|e| e.into_inner());
+    let entries: Vec<&Value> = history.iter().rev().take(limit).collect();
+    json!({ "entries": entries, "total": history.len() })
+}
@@ -0,0 +1,344 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+use crate::cloud_auth;
+use crate::errors::ErrorCode;
+use crate::settings::DataResidencySettings;
+
+/// Filesystems whose files live on another machine.
+#[cfg(target_os = "linux")]
+const NETWORK_FS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "9p",
+    "ncpfs",
+    "coda",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.gcsfuse",
+    "fuse.davfs",
+    "fuse.glusterfs",
+];
+#[cfg(target_os = "macos")]
+const NETWORK_FS: &[&str] = &["smbfs", "nfs", "afpfs", "webdav", "cifs", "ftp"];
+
+static SETTINGS: Mutex<Option<DataResidencySettings>> = Mutex::new(None);
+
+pub fn configure(settings: &DataResidencySettings) {
+    *SETTINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(settings.clone());
+}
+
+fn current() -> DataResidencySettings {
+    SETTINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// `~/Exports` under the user's home; other paths as they are.
+fn expand(raw: &str) -> PathBuf {
+    let raw = raw.trim();
+    match raw.strip_prefix("~/").or(raw.strip_prefix("~\\")) {
+        Some(rest) => cloud_auth::home_dir().map_or_else(|| PathBuf::from(raw), |h| h.join(rest)),
+        None => PathBuf::from(raw),
+    }
+}
+
+pub fn validate(settings: &DataResidencySettings) -> Result<(), String> {
+    for dir in &settings.allowed_dirs {
+        if !expand(dir).is_absolute() {
+            return Err(format!(
+                "data_residency.allowed_dirs: {} is not an absolute path",
+                dir
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Media {
+    Local,
+    Removable,
+    Network,
+}
+
+impl Media {
+    fn as_str(self) -> &'static str {
+        match self {
+            Media::Local => "local",
+            Media::Removable => "removable",
+            Media::Network => "network",
+        }
+    }
+}
+
+/// The file `target` names once links are followed: the file itself when
+/// it exists, else its folder's real path and the name.
+fn resolve(target: &Path) -> Result<PathBuf, String> {
+    if !target.is_absolute() {
+        return Err("the path is not absolute".to_string());
+    }
+    if let Ok(real) = target.canonicalize() {
+        return Ok(real);
+    }
+    // Writing through a dangling link would create its target, wherever
+    // that is.
+    if fs::symlink_metadata(target).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err("it is a link to a file that does not exist".to_string());
+    }
+    let name = target
+        .file_name()
+        .ok_or_else(|| "it names no file".to_string())?;
+    let dir = target
+        .parent()
+        .ok_or_else(|| "it has no folder".to_string())?
+        .canonicalize()
+        .map_err(|e| format!("its folder cannot be resolved ({})", e))?;
+    Ok(dir.join(name))
+}
+
+/// `\040` and friends, as `/proc/self/mountinfo` escapes mount points.
+#[cfg(target_os = "linux")]
+fn unescape(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|d| {
+            std::str::from_utf8(d)
+                .ok()
+                .and_then(|d| u8::from_str_radix(d, 8).ok())
+        });
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The mount holding `path`, then the block device behind it: what sysfs
+/// says of the disk (removable, or attached over USB), and the desktop's
+/// own mount folders for removable media.
+#[cfg(target_os = "linux")]
+fn media(path: &Path) -> Result<Media, String> {
+    let info = fs::read_to_string("/proc/self/mountinfo").map_err(|e| e.to_string())?;
+    // `36 35 98:0 /root /mnt rw,noatime master:1 - ext3 /dev/sda1 rw`
+    let (mount_point, device, fstype, source) = info
+        .lines()
+        .filter_map(|line| {
+            let (left, right) = line.split_once(" - ")?;
+            let mut left = left.split(' ');
+            let device = left.nth(2)?;
+            let mount_point = PathBuf::from(unescape(left.nth(1)?));
+            let mut right = right.split(' ');
+            Some((mount_point, device, right.next()?, right.next()?))
+        })
+        .filter(|(mount_point, ..)| path.starts_with(mount_point))
+        // The deepest mount wins; of several on one point, the last.
+        .max_by_key(|(mount_point, ..)| mount_point.components().count())
+        .ok_or_else(|| "no mount holds it".to_string())?;
+    if NETWORK_FS.contains(&fstype) || source.starts_with("//") || source.contains(":/") {
+        return Ok(Media::Network);
+    }
+    if let Ok(sys) = fs::canonicalize(format!("/sys/dev/block/{}", device)) {
+        // Partitions sit in their disk's folder, which has the flag.
+        let removable = [sys.join("removable"), sys.join("../removable")]
+            .iter()
+            .any(|f| fs::read_to_string(f).is_ok_and(|v| v.trim() == "1"));
+        if removable || sys.to_string_lossy().contains("/usb") {
+            return Ok(Media::Removable);
+        }
+    }
+    if mount_point.starts_with("/media") || mount_point.starts_with("/run/media") {
+        return Ok(Media::Removable);
+    }
+    Ok(Media::Local)
+}
+
+/// `mount` for the filesystem type, then `diskutil` for whether the disk
+/// is internal and fixed.
+#[cfg(target_os = "macos")]
+fn media(path: &Path) -> Result<Media, String> {
+    let out = std::process::Command::new("/sbin/mount")
+        .output()
+        .map_err(|e| e.to_string())?;
+    let listing = String::from_utf8_lossy(&out.stdout);
+    // `/dev/disk4s1 on /Volumes/USB (msdos, local, nodev, nosuid)`
+    let (mount_point, fstype) = listing
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            Some((mount_point, options.split([',', ')']).next()?.trim()))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| Path::new(mount_point).components().count())
+        .ok_or_else(|| "no mount holds it".to_string())?;
+    if NETWORK_FS.contains(&fstype) {
+        return Ok(Media::Network);
+    }
+    let out = std::process::Command::new("/usr/sbin/diskutil")
+        .args(["info", "-plist", mount_point])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let disk: Value = plist::from_bytes(&out.stdout).map_err(|e| e.to_string())?;
+    let external = disk["Internal"] == json!(false)
+        || disk["RemovableMedia"] == json!(true)
+        || disk["Ejectable"] == json!(true);
+    Ok(if external {
+        Media::Removable
+    } else {
+        Media::Local
+    })
+}
+
+/// UNC paths are shares; for a drive letter, .NET's drive type, which
+/// unlike `fsutil` is not translated.
+#[cfg(windows)]
+fn media(path: &Path) -> Result<Media, String> {
+    let text = path.to_string_lossy();
+    let bare = text.strip_prefix(r"\\?\").unwrap_or(&text);
+    if bare.starts_with(r"UNC\") || bare.starts_with(r"\\") {
+        return Ok(Media::Network);
+    }
+    let letter = bare
+        .chars()
+        .next()
+        .filter(|c| c.is_ascii_alphabetic() && bare[1..].starts_with(':'))
+        .ok_or_else(|| "it is not on a drive".to_string())?;
+    let out = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            &format!("[System.IO.DriveInfo]::new('{}').DriveType", letter),
+        ])
+        .output()
+        .map_err(|e| e.to_string())?;
+    match String::from_utf8_lossy(&out.stdout).trim() {
+        "Fixed" | "Ram" => Ok(Media::Local),
+        "Removable" | "CDRom" => Ok(Media::Removable),
+        "Network" => Ok(Media::Network),
+        other => Err(format!("drive type {:?}", other)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn media(_path: &Path) -> Result<Media, String> {
+    Err("this platform cannot tell".to_string())
+}
+
+/// Why `target` may not be written under `settings`, and the storage it
+/// is on when that was looked at.
+fn verdict(
+    settings: &DataResidencySettings,
+    target: &Path,
+) -> (Result<PathBuf, String>, Option<Media>) {
+    let resolved = match resolve(target) {
+        Ok(resolved) => resolved,
+        Err(e) => return (Err(e), None),
+    };
+    if !settings.allowed_dirs.is_empty() {
+        let inside = settings
+            .allowed_dirs
+            .iter()
+            .filter_map(|dir| expand(dir).canonicalize().ok())
+            .any(|dir| resolved.starts_with(dir));
+        if !inside {
+            return (
+                Err(format!(
+                    "it is outside the allowed folders ({})",
+                    settings.allowed_dirs.join(", ")
+                )),
+                None,
+            );
+        }
+    }
+    if !settings.deny_removable && !settings.deny_network {
+        return (Ok(resolved), None);
+    }
+    // Storage that cannot be identified is refused, not assumed local.
+    let media = match media(&resolved) {
+        Ok(media) => media,
+        Err(e) => {
+            return (
+                Err(format!("its storage cannot be identified ({})", e)),
+                None,
+            )
+        }
+    };
+    let refused = match media {
+        Media::Network if settings.deny_network => Some("it is on a network share"),
+        Media::Removable if settings.deny_removable => Some("it is on removable media"),
+        _ => None,
+    };
+    match refused {
+        Some(why) => (Err(why.to_string()), Some(media)),
+        None => (Ok(resolved), Some(media)),
+    }
+}
+
+/// Where an export, report or backup named `what` may go: `path` with
+/// links resolved, which the caller writes to, or a `FORBIDDEN_LOCAL`
+/// error under `data_residency`. Without restrictions, `path` as given.
+pub fn check(what: &str, path: &str) -> Result<PathBuf, String> {
+    let settings = current();
+    let target = PathBuf::from(path.trim());
+    if !settings.restricts() {
+        return Ok(target);
+    }
+    verdict(&settings, &target).0.map_err(|why| {
+        ErrorCode::ForbiddenLocal.with(format!(
+            "{} may not be written to {}: {}",
+            what,
+            path.trim(),
+            why
+        ))
+    })
+}
+
+/// The data residency rules in force and, for `path`, whether exports may
+/// be written there and what storage it is on, so a save dialog can say
+/// so before anything is generated.
+#[tauri::command]
+pub fn check_data_residency(path: Option<String>) -> Result<Value, String> {
+    let settings = current();
+    let mut out = json!({
+        "restricted": settings.restricts(),
+        "allowed_dirs": settings.allowed_dirs,
+        "deny_removable": settings.deny_removable,
+        "deny_network": settings.deny_network,
+    });
+    if let Some(path) = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty()) {
+        let (result, media) = if settings.restricts() {
+            verdict(&settings, Path::new(&path))
+        } else {
+            (Ok(PathBuf::from(&path)), None)
+        };
+        out["path"] = json!(path);
+        out["allowed"] = json!(result.is_ok());
+        out["reason"] = json!(result.err());
+        out["media"] = json!(media.map(Media::as_str));
+    }
+    Ok(out)
+}
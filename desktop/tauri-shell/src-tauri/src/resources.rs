@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+use crate::latency;
+
+/// Response bodies parsed by `request_json`: CPU per request.
+pub const REQUESTS: &str = "requests";
+/// `plan_compare`: CPU per comparison.
+pub const DIFF: &str = "diff";
+/// `log_search`: CPU per scan.
+pub const SEARCH: &str = "search";
+/// `response_cache`: bytes held.
+pub const CACHE: &str = "response_cache";
+
+/// What each subsystem should stay under on a low-end kiosk: CPU per call,
+/// or bytes held.
+const BUDGETS: &[(&str, Budget)] = &[
+    (REQUESTS, Budget::Cpu(Duration::from_millis(25))),
+    (DIFF, Budget::Cpu(Duration::from_millis(50))),
+    (SEARCH, Budget::Cpu(Duration::from_millis(250))),
+    (CACHE, Budget::Memory(32 * 1024 * 1024)),
+];
+/// Endpoints broken out under `requests`; the rest are counted as `other`.
+const MAX_ENDPOINTS: usize = 100;
+const TOP_ENDPOINTS: usize = 20;
+
+#[derive(Clone, Copy)]
+enum Budget {
+    Cpu(Duration),
+    Memory(u64),
+}
+
+#[derive(Default)]
+struct Cpu {
+    calls: u64,
+    total: Duration,
+    max: Duration,
+    over_budget: u64,
+    bytes: u64,
+}
+
+impl Cpu {
+    fn add(&mut self, took: Duration, bytes: u64, budget: Option<Duration>) {
+        self.calls += 1;
+        self.total += took;
+        self.max = self.max.max(took);
+        self.bytes += bytes;
+        if budget.is_some_and(|b| took > b) {
+            self.over_budget += 1;
+        }
+    }
+
+    fn describe(&self) -> Value {
+        let ms = |d: Duration| (d.as_secs_f64() * 1_000.0 * 100.0).round() / 100.0;
+        json!({
+            "calls": self.calls,
+            "cpu_ms": ms(self.total),
+            "avg_cpu_ms": if self.calls == 0 { 0.0 } else { ms(self.total / self.calls as u32) },
+            "max_cpu_ms": ms(self.max),
+            "over_budget": self.over_budget,
+            "bytes": self.bytes,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    bytes: u64,
+    peak: u64,
+    allocated: u64,
+    entries: u64,
+}
+
+struct Stats {
+    since: DateTime<Utc>,
+    cpu: HashMap<&'static str, Cpu>,
+    memory: HashMap<&'static str, Memory>,
+    endpoints: HashMap<String, Cpu>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            since: Utc::now(),
+            cpu: HashMap::new(),
+            memory: HashMap::new(),
+            endpoints: HashMap::new(),
+        }
+    }
+}
+
+static STATS: OnceLock<Mutex<Stats>> = OnceLock::new();
+
+fn stats() -> &'static Mutex<Stats> {
+    STATS.get_or_init(|| Mutex::new(Stats::default()))
+}
+
+fn budget(subsystem: &str) -> Option<Budget> {
+    BUDGETS
+        .iter()
+        .find(|(name, _)| *name == subsystem)
+        .map(|(_, b)| *b)
+}
+
+fn cpu_budget(subsystem: &str) -> Option<Duration> {
+    match budget(subsystem)? {
+        Budget::Cpu(limit) => Some(limit),
+        Budget::Memory(_) => None,
+    }
+}
+
+/// Charges the time until it is dropped to its subsystem. Only for
+/// synchronous, CPU-bound sections: with nothing awaited meanwhile, the
+/// time measured is time the thread spent on them.
+pub struct Timer {
+    subsystem: &'static str,
+    started: Instant,
+}
+
+pub fn timer(subsystem: &'static str) -> Timer {
+    Timer {
+        subsystem,
+        started: Instant::now(),
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let took = self.started.elapsed();
+        if let Ok(mut stats) = stats().lock() {
+            stats
+                .cpu
+                .entry(self.subsystem)
+                .or_default()
+                .add(took, 0, cpu_budget(self.subsystem));
+        }
+    }
+}
+
+/// Runs `work` under a [`Timer`] for `subsystem`.
+pub fn measure<T>(subsystem: &'static str, work: impl FnOnce() -> T) -> T {
+    let _timer = timer(subsystem);
+    work()
+}
+
+/// Charges parsing one core response of `bytes` to `requests`, and to its
+/// endpoint.
+pub fn request(method: &Method, path: &str, bytes: usize, took: Duration) {
+    let endpoint = latency::endpoint(method, path);
+    let budget = cpu_budget(REQUESTS);
+    if let Ok(mut stats) = stats().lock() {
+        stats
+            .cpu
+            .entry(REQUESTS)
+            .or_default()
+            .add(took, bytes as u64, budget);
+        let key =
+            if stats.endpoints.len() < MAX_ENDPOINTS || stats.endpoints.contains_key(&endpoint) {
+                endpoint
+            } else {
+                "other".to_string()
+            };
+        stats
+            .endpoints
+            .entry(key)
+            .or_default()
+            .add(took, bytes as u64, budget);
+    }
+}
+
+/// Notes `subsystem` now holds `bytes` more, in one more entry.
+pub fn allocated(subsystem: &'static str, bytes: usize) {
+    if let Ok(mut stats) = stats().lock() {
+        let memory = stats.memory.entry(subsystem).or_default();
+        memory.bytes += bytes as u64;
+        memory.allocated += bytes as u64;
+        memory.entries += 1;
+        memory.peak = memory.peak.max(memory.bytes);
+    }
+}
+
+/// Notes `subsystem` let go of an entry of `bytes`.
+pub fn freed(subsystem: &'static str, bytes: usize) {
+    if let Ok(mut stats) = stats().lock() {
+        let memory = stats.memory.entry(subsystem).or_default();
+        memory.bytes = memory.bytes.saturating_sub(bytes as u64);
+        memory.entries = memory.entries.saturating_sub(1);
+    }
+}
+
+/// About what a JSON value keeps on the heap: its strings and keys plus a
+/// slot per element. Cheaper than serializing it, and close enough to
+/// compare one run with another.
+pub fn json_bytes(value: &Value) -> usize {
+    let slot = std::mem::size_of::<Value>();
+    slot + match value {
+        Value::String(s) => s.len(),
+        Value::Array(items) => items.iter().map(json_bytes).sum(),
+        Value::Object(map) => map.iter().map(|(k, v)| k.len() + json_bytes(v)).sum(),
+        _ => 0,
+    }
+}
+
+/// The process as the OS sees it: resident memory and its peak, and CPU
+/// time used since start. Linux only; `null` elsewhere.
+fn process() -> Value {
+    if !cfg!(target_os = "linux") {
+        return Value::Null;
+    }
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let kib = |name: &str| {
+        status
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|n| n.parse::<u64>().ok())
+            .map(|k| k * 1024)
+    };
+    // Fields 14 and 15 (utime, stime) after the parenthesised command name,
+    // in clock ticks of 1/100 s.
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap_or_default();
+    let ticks: Option<u64> = stat.rsplit_once(')').and_then(|(_, rest)| {
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        Some(fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?)
+    });
+    json!({
+        "rss_bytes": kib("VmRSS:"),
+        "peak_rss_bytes": kib("VmHWM:"),
+        "cpu_ms": ticks.map(|t| t * 10),
+        "threads": status
+            .lines()
+            .find_map(|l| l.strip_prefix("Threads:"))
+            .and_then(|n| n.trim().parse::<u64>().ok()),
+    })
+}
+
+/// Where the shell spends memory and CPU, for diagnosing slow approval
+/// kiosks in the field: the process totals, then per subsystem the CPU
+/// spent parsing core responses, comparing plans and searching logs, and
+/// the bytes the response cache holds, each against its budget, with the
+/// endpoints that cost most to parse. `reset` starts the counters again
+/// after reporting (cache bytes held are kept).
+#[tauri::command]
+pub fn resource_report(reset: Option<bool>) -> Result<Value, String> {
+    let mut stats = stats()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Resource stats poisoned"))?;
+    let mut subsystems = Vec::new();
+    for (name, budget) in BUDGETS {
+        let mut entry = match budget {
+            Budget::Cpu(limit) => {
+                let cpu = stats.cpu.get(name);
+                let mut entry = cpu
+                    .map(Cpu::describe)
+                    .unwrap_or_else(|| Cpu::default().describe());
+                entry["budget_cpu_ms"] = json!(limit.as_millis() as u64);
+                entry["over"] = json!(cpu.is_some_and(|c| c.over_budget > 0));
+                entry
+            }
+            Budget::Memory(limit) => {
+                let memory = stats.memory.get(name);
+                let bytes = memory.map_or(0, |m| m.bytes);
+                json!({
+                    "bytes": bytes,
+                    "peak_bytes": memory.map_or(0, |m| m.peak),
+                    "allocated_bytes": memory.map_or(0, |m| m.allocated),
+                    "entries": memory.map_or(0, |m| m.entries),
+                    "budget_bytes": limit,
+                    "over": memory.is_some_and(|m| m.peak > *limit),
+                })
+            }
+        };
+        entry["subsystem"] = json!(name);
+        subsystems.push(entry);
+    }
+    let mut endpoints: Vec<(&String, &Cpu)> = stats.endpoints.iter().collect();
+    endpoints.sort_by_key(|(_, c)| std::cmp::Reverse(c.total));
+    let endpoints: Vec<Value> = endpoints
+        .into_iter()
+        .take(TOP_ENDPOINTS)
+        .map(|(endpoint, cpu)| {
+            let mut entry = cpu.describe();
+            entry["endpoint"] = json!(endpoint);
+            entry
+        })
+        .collect();
+    let report = json!({
+        "since": stats.since.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "process": process(),
+        "subsystems": subsystems,
+        "endpoints": endpoints,
+    });
+    if reset.unwrap_or(false) {
+        let memory = std::mem::take(&mut stats.memory);
+        *stats = Stats::default();
+        stats.memory = memory
+            .into_iter()
+            .map(|(name, m)| {
+                (
+                    name,
+                    Memory {
+                        peak: m.bytes,
+                        allocated: 0,
+                        ..m
+                    },
+                )
+            })
+            .collect();
+    }
+    Ok(report)
+}
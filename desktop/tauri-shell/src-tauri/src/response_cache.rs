@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use data_encoding::HEXLOWER;
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::environment;
+use crate::errors::ErrorCode;
+use crate::resources;
+use crate::tenants;
+
+const UPDATED_EVENT: &str = "cache:updated";
+const DEFAULT_TTL: Duration = Duration::from_secs(15);
+const DEFAULT_STALE: Duration = Duration::from_secs(300);
+const MAX_TTL_SECS: u64 = 3600;
+const MAX_STALE_SECS: u64 = 86_400;
+const MAX_ENTRIES: usize = 256;
+
+struct Entry {
+    url: String,
+    fetched: Instant,
+    value: Value,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.url.len() + resources::json_bytes(&self.value)
+    }
+}
+
+/// Core GET responses keyed by URL and token, served stale while a
+/// background refresh runs.
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    refreshing: Mutex<HashSet<String>>,
+}
+
+enum Lookup {
+    Fresh(Value),
+    Stale(Value, Duration),
+    Missing,
+}
+
+impl ResponseCache {
+    fn lookup(&self, key: &str, ttl: Duration, stale: Duration) -> Lookup {
+        let Ok(entries) = self.entries.lock() else {
+            return Lookup::Missing;
+        };
+        match entries.get(key) {
+            Some(entry) if entry.fetched.elapsed() < ttl => Lookup::Fresh(entry.value.clone()),
+            Some(entry) if entry.fetched.elapsed() < ttl + stale => {
+                Lookup::Stale(entry.value.clone(), entry.fetched.elapsed())
+            }
+            _ => Lookup::Missing,
+        }
+    }
+
+    /// Stores `value` and reports whether it differs from what was cached.
+    fn store(&self, key: &str, url: &str, value: Value) -> bool {
+        let Ok(mut entries) = self.entries.lock() else {
+            return true;
+        };
+        let changed = entries.get(key).is_none_or(|entry| entry.value != value);
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched)
+                .map(|(k, _)| k.clone());
+            if let Some(evicted) = oldest.and_then(|oldest| entries.remove(&oldest)) {
+                resources::freed(resources::CACHE, evicted.size());
+            }
+        }
+        let entry = Entry {
+            url: url.to_string(),
+            fetched: Instant::now(),
+            value,
+        };
+        resources::allocated(resources::CACHE, key.len() + entry.size());
+        if let Some(replaced) = entries.insert(key.to_string(), entry) {
+            resources::freed(resources::CACHE, key.len() + replaced.size());
+        }
+        changed
+    }
+
+    /// Drops every entry for the core at `base_url`, after a change was
+    /// sent to it.
+    pub fn invalidate(&self, base_url: &str) {
+        let base = base_url.trim().trim_end_matches('/');
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|key, entry| {
+                let keep = !entry.url.starts_with(base);
+                if !keep {
+                    resources::freed(resources::CACHE, key.len() + entry.size());
+                }
+                keep
+            });
+        }
+    }
+}
+
+/// The cache key: the URL plus a digest of the token, so one token's
+/// responses are never served to another, and the tenant, which a header
+/// may carry instead of the URL.
+fn cache_key(url: &str, token: Option<&str>, tenant: Option<&str>) -> String {
+    let digest = Sha256::digest(token.unwrap_or_default().as_bytes());
+    format!(
+        "{}#{}#{}",
+        url,
+        HEXLOWER.encode(&digest[..8]),
+        tenant.unwrap_or_default()
+    )
+}
+
+fn secs(raw: Option<u64>, default: Duration, max: u64, name: &str) -> Result<Duration, String> {
+    match raw {
+        None => Ok(default),
+        Some(s) if s <= max => Ok(Duration::from_secs(s)),
+        Some(_) => Err(ErrorCode::InvalidInput.with(format!("{} must be at most {}", name, max))),
+    }
+}
+
+/// Refetches `path` in the background and emits `cache:updated` when the
+/// fresh copy differs. One refresh per key runs at a time.
+fn revalidate(
+    app: AppHandle,
+    key: String,
+    url: String,
+    base: String,
+    path: String,
+    token: Option<String>,
+) {
+    let cache = app.state::<ResponseCache>();
+    let started = cache
+        .refreshing
+        .lock()
+        .map(|mut refreshing| refreshing.insert(key.clone()))
+        .unwrap_or(false);
+    if !started {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let fetched = crate::request_json(Method::GET, &base, &path, token, None).await;
+        let cache = app.state::<ResponseCache>();
+        if let Ok(mut refreshing) = cache.refreshing.lock() {
+            refreshing.remove(&key);
+        }
+        if let Ok(value) = fetched {
+            if cache.store(&key, &url, value.clone()) {
+                let _ = environment::emit(
+                    &app,
+                    UPDATED_EVENT,
+                    json!({ "url": url, "path": path, "data": value }),
+                );
+            }
+        }
+    });
+}
+
+/// GETs `path` from the core through the response cache. Within
+/// `ttl_seconds` of the last fetch the cached copy is returned as is; for
+/// `stale_seconds` after that it is returned at once while a background
+/// refresh runs, which emits `cache:updated` with `{url, path, data}` if
+/// the core's copy changed. Past both, or with `refresh`, the core is asked
+/// directly.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn cached_core_request(
+    app: AppHandle,
+    cache: State<'_, ResponseCache>,
+    base_url: String,
+    token: Option<String>,
+    path: String,
+    ttl_seconds: Option<u64>,
+    stale_seconds: Option<u64>,
+    refresh: Option<bool>,
+) -> Result<Value, String> {
+    let ttl = secs(ttl_seconds, DEFAULT_TTL, MAX_TTL_SECS, "ttl_seconds")?;
+    let stale = secs(
+        stale_seconds,
+        DEFAULT_STALE,
+        MAX_STALE_SECS,
+        "stale_seconds",
+    )?;
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    };
+    let url = format!("{}{}", base_url.trim().trim_end_matches('/'), path);
+    let key = cache_key(&url, token.as_deref(), tenants::tag(&base_url).as_deref());
+    if !refresh.unwrap_or(false) {
+        match cache.lookup(&key, ttl, stale) {
+            Lookup::Fresh(value) => {
+                return Ok(json!({ "data": value, "cached": true, "stale": false }));
+            }
+            Lookup::Stale(value, age) => {
+                revalidate(app, key, url, base_url, path, token);
+                return Ok(json!({
+                    "data": value,
+                    "cached": true,
+                    "stale": true,
+                    "age_seconds": age.as_secs(),
+                }));
+            }
+            Lookup::Missing => {}
+        }
+    }
+    let value = crate::request_json(Method::GET, &base_url, &path, token, None).await?;
+    cache.store(&key, &url, value.clone());
+    Ok(json!({ "data": value, "cached": false, "stale": false }))
+}
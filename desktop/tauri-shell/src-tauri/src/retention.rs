@@ -0,0 +1,403 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{RetentionSettings, SettingsStore};
+use crate::{screenshot, spill, tasks, thumbnails};
+
+const TASK: &str = "retention";
+/// The first pass waits for startup to settle; later ones run a few times a
+/// day, which is plenty for day-granular retention.
+const FIRST_PASS_DELAY: Duration = Duration::from_secs(60);
+const PASS_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+const MAX_DAYS: u32 = 36_500;
+/// The database is rebuilt once free pages pass both bounds; below them
+/// the space is simply reused by later writes.
+const VACUUM_MIN_FREE_BYTES: i64 = 8 * 1024 * 1024;
+const VACUUM_MIN_FREE_SHARE: f64 = 0.25;
+
+/// Outcome of the most recent compaction, for `cache_stats`.
+static LAST_PASS: OnceLock<Mutex<Value>> = OnceLock::new();
+
+fn last_pass() -> &'static Mutex<Value> {
+    LAST_PASS.get_or_init(|| Mutex::new(Value::Null))
+}
+
+pub fn validate(settings: &RetentionSettings) -> Result<(), String> {
+    let limits = [
+        ("plans", settings.plans_days),
+        ("logs", settings.logs_days),
+        ("audit", settings.audit_days),
+        ("metrics", settings.metrics_days),
+        ("files", settings.files_days),
+    ];
+    for (name, days) in limits {
+        if days.is_some_and(|d| d == 0 || d > MAX_DAYS) {
+            return Err(format!(
+                "Retention for {} must be between 1 and {} days",
+                name, MAX_DAYS
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Enforces the retention settings over the local database and the file
+/// caches, then compacts the database when deletions left enough of it
+/// empty.
+pub fn spawn_compactor(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        tokio::time::sleep(FIRST_PASS_DELAY).await;
+        loop {
+            let settings = app.state::<SettingsStore>().snapshot().retention;
+            let pass = {
+                let app = app.clone();
+                tauri::async_runtime::spawn_blocking(move || compact(&app, &settings)).await
+            };
+            match pass {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => tasks::failed(TASK, &err),
+                Err(err) => tasks::failed(TASK, &format!("Compaction task failed: {}", err)),
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(PASS_INTERVAL).await;
+        }
+    });
+}
+
+fn cutoff(days: Option<u32>) -> Option<String> {
+    days.map(|d| (Utc::now() - chrono::Duration::days(i64::from(d))).to_rfc3339())
+}
+
+fn compact(app: &AppHandle, settings: &RetentionSettings) -> Result<Value, String> {
+    let db = app.state::<LocalDb>();
+    let conn = db.lock()?;
+    let removed = prune_rows(&conn, settings)
+        .map_err(|e| ErrorCode::LocalStorage.with(format!("Retention pass failed: {}", e)))?;
+    let vacuumed = vacuum_if_sparse(&conn)
+        .map_err(|e| ErrorCode::LocalStorage.with(format!("Compaction failed: {}", e)))?;
+
+    let files_removed: u64 = settings.files_days.map_or(0, |days| {
+        let max_age = Duration::from_secs(u64::from(days) * 86_400);
+        file_dirs(app)
+            .iter()
+            .map(|(_, dir)| prune_files(dir, max_age))
+            .sum()
+    });
+
+    let summary = json!({
+        "at": Utc::now().to_rfc3339(),
+        "removed": removed,
+        "files_removed": files_removed,
+        "reclaimed_bytes": vacuumed,
+    });
+    let any_removed = files_removed > 0 || removed.values().any(|n| n.as_u64() != Some(0));
+    if any_removed {
+        let _ = db::record_audit(&conn, "", "retention", "compacted", "", &summary);
+    }
+    if let Ok(mut last) = last_pass().lock() {
+        *last = summary.clone();
+    }
+    Ok(summary)
+}
+
+/// Deletes rows older than their category's retention. Timestamps are
+/// compared through `julianday`, since the tables mix RFC 3339 spellings.
+fn prune_rows(
+    conn: &Connection,
+    settings: &RetentionSettings,
+) -> rusqlite::Result<serde_json::Map<String, Value>> {
+    let mut removed = serde_json::Map::new();
+    let mut run = |category: &str, cutoff: &Option<String>, sql: &[&str]| {
+        let Some(cutoff) = cutoff else {
+            return Ok::<_, rusqlite::Error>(());
+        };
+        let mut count = 0;
+        for statement in sql {
+            count += conn.execute(statement, params![cutoff])?;
+        }
+        removed.insert(category.to_string(), json!(count));
+        Ok(())
+    };
+    run(
+        "plans",
+        &cutoff(settings.plans_days),
+        &[
+            "DELETE FROM sync_entities WHERE julianday(synced_at) < julianday(?1)",
+            "DELETE FROM plan_deadlines
+             WHERE expired_at IS NOT NULL AND julianday(expired_at) < julianday(?1)",
+            "DELETE FROM scheduled_approvals
+             WHERE status NOT IN ('scheduled', 'firing', 'conflict')
+               AND julianday(COALESCE(fired_at, created_at)) < julianday(?1)",
+            "DELETE FROM plan_output WHERE julianday(at) < julianday(?1)",
+            "DELETE FROM translations WHERE julianday(at) < julianday(?1)",
+            // Each entity keeps its last state from before the cutoff, so
+            // dashboards from the cutoff on can still be rebuilt.
+            "DELETE FROM sync_history WHERE julianday(recorded_at) < julianday(?1)
+               AND EXISTS (SELECT 1 FROM sync_history later
+                   WHERE later.profile_id = sync_history.profile_id
+                     AND later.kind = sync_history.kind
+                     AND later.entity_id = sync_history.entity_id
+                     AND later.id > sync_history.id
+                     AND julianday(later.recorded_at) < julianday(?1))",
+            "DELETE FROM sync_history WHERE payload IS NULL
+               AND julianday(recorded_at) < julianday(?1)",
+        ],
+    )?;
+    // Lines without a parsed time are left to the per-source line cap.
+    run(
+        "logs",
+        &cutoff(settings.logs_days),
+        &["DELETE FROM log_cache WHERE at IS NOT NULL AND julianday(at) < julianday(?1)"],
+    )?;
+    run(
+        "audit",
+        &cutoff(settings.audit_days),
+        &["DELETE FROM audit_log WHERE julianday(at) < julianday(?1)"],
+    )?;
+    if let Some(days) = settings.metrics_days {
+        let cutoff_ms = Utc::now().timestamp_millis() - i64::from(days) * 86_400_000;
+        let count = conn.execute(
+            "DELETE FROM metric_samples WHERE at_ms < ?1",
+            params![cutoff_ms],
+        )? + conn.execute(
+            "DELETE FROM latency_samples WHERE at_ms < ?1",
+            params![cutoff_ms],
+        )? + conn.execute(
+            "DELETE FROM bandwidth_daily WHERE day < ?1",
+            params![(Utc::now() - chrono::Duration::days(i64::from(days)))
+                .format("%Y-%m-%d")
+                .to_string()],
+        )?;
+        removed.insert("metrics".to_string(), json!(count));
+    }
+    Ok(removed)
+}
+
+/// Rebuilds the database when enough of it is free pages, and returns the
+/// bytes given back to the file system.
+fn vacuum_if_sparse(conn: &Connection) -> rusqlite::Result<i64> {
+    let (pages, free, page_size) = page_counts(conn)?;
+    let free_bytes = free * page_size;
+    if free_bytes < VACUUM_MIN_FREE_BYTES || (free as f64) < pages as f64 * VACUUM_MIN_FREE_SHARE {
+        return Ok(0);
+    }
+    conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+    let (after, _, _) = page_counts(conn)?;
+    Ok((pages - after).max(0) * page_size)
+}
+
+fn page_counts(conn: &Connection) -> rusqlite::Result<(i64, i64, i64)> {
+    let pragma = |name: &str| conn.query_row(&format!("PRAGMA {}", name), [], |r| r.get(0));
+    Ok((
+        pragma("page_count")?,
+        pragma("freelist_count")?,
+        pragma("page_size")?,
+    ))
+}
+
+/// Directories of cached files that retention applies to.
+fn file_dirs<R: Runtime>(app: &AppHandle<R>) -> Vec<(&'static str, PathBuf)> {
+    let mut dirs = Vec::new();
+    if let Ok(cache) = app.path().app_cache_dir() {
+        dirs.push(("thumbnails", cache.join(thumbnails::CACHE_DIR)));
+    }
+    if let Ok(data) = app.path().app_data_dir() {
+        dirs.push(("screenshots", data.join(screenshot::STAGING_DIR)));
+    }
+    dirs
+}
+
+fn prune_files(dir: &Path, max_age: Duration) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| m.modified().ok())
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if stale && fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+fn dir_usage(dir: &Path, prefix: &str) -> (u64, u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+        .filter_map(|e| e.metadata().ok().filter(|m| m.is_file()))
+        .fold((0, 0), |(files, bytes), m| (files + 1, bytes + m.len()))
+}
+
+/// Row count, stored payload size and oldest timestamp per category. The
+/// size counts the text and blob payloads, not SQLite's own overhead.
+const CATEGORIES: &[(&str, &str)] = &[
+    (
+        "plans",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(payload)), 0), MIN(synced_at)
+         FROM sync_entities WHERE kind = 'plan'",
+    ),
+    (
+        "jobs",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(payload)), 0), MIN(synced_at)
+         FROM sync_entities WHERE kind = 'job'",
+    ),
+    (
+        "logs",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(line)), 0), MIN(at) FROM log_cache",
+    ),
+    (
+        "audit",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(detail) + LENGTH(action) + LENGTH(entity_id)), 0),
+             MIN(at)
+         FROM audit_log",
+    ),
+    (
+        "metrics",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(name) + 16), 0),
+             strftime('%Y-%m-%dT%H:%M:%SZ', MIN(at_ms) / 1000, 'unixepoch')
+         FROM metric_samples",
+    ),
+    (
+        "latency",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(origin) + LENGTH(endpoint) + 32), 0),
+             strftime('%Y-%m-%dT%H:%M:%SZ', MIN(at_ms) / 1000, 'unixepoch')
+         FROM latency_samples",
+    ),
+    (
+        "memory",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(content) + LENGTH(payload)), 0), MIN(cached_at)
+         FROM memory_cache",
+    ),
+    (
+        "bandwidth",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(profile_id) + LENGTH(subsystem) + 40), 0), MIN(day)
+         FROM bandwidth_daily",
+    ),
+    (
+        "usage",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(model) + LENGTH(agent) + LENGTH(plan_id) + 40), 0),
+             MIN(day)
+         FROM usage_daily",
+    ),
+    (
+        "transcripts",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(text AS BLOB)) + 64), 0), MIN(at)
+         FROM plan_output",
+    ),
+    (
+        "recent",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(item_id) + LENGTH(label) + 48), 0),
+             strftime('%Y-%m-%dT%H:%M:%SZ', MIN(first_ms) / 1000, 'unixepoch')
+         FROM recent_items",
+    ),
+    (
+        "schedules",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(COALESCE(outcome, '')) + 64), 0), MIN(created_at)
+         FROM scheduled_approvals",
+    ),
+    (
+        "deadlines",
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(objective) + 64), 0), MIN(expires_at)
+         FROM plan_deadlines",
+    ),
+];
+
+/// Disk usage of the local cache: the database file (with its write-ahead
+/// log) and, per category, its rows and payload bytes; then the file
+/// caches. Also reports the retention in force and the last compaction.
+#[tauri::command]
+pub fn cache_stats(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+) -> Result<Value, String> {
+    let conn = db.lock()?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let mut categories = Vec::new();
+    for (category, sql) in CATEGORIES {
+        let (rows, bytes, oldest) = conn
+            .query_row(sql, [], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })
+            .map_err(storage)?;
+        categories.push(json!({
+            "category": category,
+            "rows": rows,
+            "bytes": bytes,
+            "oldest_at": oldest,
+        }));
+    }
+    let (pages, free, page_size) = page_counts(&conn).map_err(storage)?;
+    let wal_bytes = conn
+        .path()
+        .filter(|p| !p.is_empty())
+        .and_then(|p| fs::metadata(format!("{}-wal", p)).ok())
+        .map_or(0, |m| m.len());
+    let database = json!({
+        "path": conn.path(),
+        "bytes": pages * page_size,
+        "wal_bytes": wal_bytes,
+        "free_bytes": free * page_size,
+    });
+    drop(conn);
+
+    let mut files: Vec<Value> = file_dirs(&app)
+        .into_iter()
+        .map(|(category, dir)| {
+            let (count, bytes) = dir_usage(&dir, "");
+            json!({
+                "category": category,
+                "path": dir.display().to_string(),
+                "files": count,
+                "bytes": bytes,
+            })
+        })
+        .collect();
+    let spill_dir = std::env::temp_dir();
+    let (count, bytes) = dir_usage(&spill_dir, spill::FILE_PREFIX);
+    files.push(json!({
+        "category": "spilled_bodies",
+        "path": spill_dir.display().to_string(),
+        "files": count,
+        "bytes": bytes,
+    }));
+
+    let total = pages * page_size
+        + wal_bytes as i64
+        + files
+            .iter()
+            .map(|f| f["bytes"].as_i64().unwrap_or(0))
+            .sum::<i64>();
+    Ok(json!({
+        "database": database,
+        "categories": categories,
+        "files": files,
+        "total_bytes": total,
+        "retention": store.snapshot().retention,
+        "last_compaction": last_pass().lock().map(|v| v.clone()).unwrap_or(Value::Null),
+    }))
+}
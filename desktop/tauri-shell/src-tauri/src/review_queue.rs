@@ -0,0 +1,246 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::decisions;
+use crate::errors::ErrorCode;
+use crate::handover;
+use crate::settings::{Connection, SettingsStore};
+
+/// Entries kept per queue; settled ones are dropped first past this.
+const MAX_ENTRIES: usize = 1_000;
+
+/// One profile's review session. `order` only ever grows at the end, with
+/// plans in the order they were first seen pending, so the cursor keeps
+/// its place however the core sorts its list between two key presses.
+#[derive(Default)]
+struct Queue {
+    order: Vec<String>,
+    /// Each plan as last fetched, for display and its version check.
+    plans: HashMap<String, Value>,
+    /// Plans still pending on the last refresh.
+    pending: HashSet<String>,
+    current: Option<String>,
+}
+
+impl Queue {
+    fn position(&self) -> Option<usize> {
+        let current = self.current.as_ref()?;
+        self.order.iter().position(|id| id == current)
+    }
+
+    /// Merges a fresh plan list: new pending plans join the end (oldest
+    /// first), plans that stopped being pending stay where they are but
+    /// are skipped.
+    fn refresh(&mut self, listed: Vec<Value>) {
+        let mut fresh: Vec<(Option<chrono::DateTime<chrono::Utc>>, String)> = Vec::new();
+        self.pending.clear();
+        for plan in listed {
+            let id = handover::id_of(&plan);
+            if id.is_empty() {
+                continue;
+            }
+            if handover::status_of(&plan) == "pending" {
+                self.pending.insert(id.clone());
+                if !self.plans.contains_key(&id) {
+                    fresh.push((handover::timestamp(&plan["created_at"]), id.clone()));
+                }
+            }
+            self.plans.insert(id, plan);
+        }
+        fresh.sort();
+        self.order.extend(fresh.into_iter().map(|(_, id)| id));
+        if self.order.len() > MAX_ENTRIES {
+            let current = self.current.clone();
+            let mut excess = self.order.len() - MAX_ENTRIES;
+            let pending = &self.pending;
+            self.order.retain(|id| {
+                let drop = excess > 0 && !pending.contains(id) && Some(id) != current.as_ref();
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+            let kept: HashSet<&String> = self.order.iter().collect();
+            self.plans.retain(|id, _| kept.contains(id));
+        }
+    }
+
+    /// Moves to the next pending plan after the cursor, wrapping round to
+    /// plans skipped earlier. Returns whether it wrapped.
+    fn forward(&mut self) -> bool {
+        let start = self.position().map_or(0, |p| p + 1);
+        let len = self.order.len();
+        let found = (0..len)
+            .map(|i| (start + i) % len)
+            .find(|i| self.pending.contains(&self.order[*i]));
+        self.current = found.map(|i| self.order[i].clone());
+        found.is_some_and(|i| i < start)
+    }
+
+    /// Moves to the nearest pending plan before the cursor; stays put at
+    /// the start.
+    fn back(&mut self) {
+        let Some(position) = self.position() else {
+            self.forward();
+            return;
+        };
+        if let Some(i) = (0..position)
+            .rev()
+            .find(|i| self.pending.contains(&self.order[*i]))
+        {
+            self.current = Some(self.order[i].clone());
+        }
+    }
+
+    fn describe(&self, profile_id: &str, wrapped: bool) -> Value {
+        let current = self
+            .current
+            .as_ref()
+            .filter(|id| self.pending.contains(*id));
+        let ahead = self.position().map_or(self.pending.len(), |p| {
+            self.order[p + 1..]
+                .iter()
+                .filter(|id| self.pending.contains(*id))
+                .count()
+        });
+        let index = self.position().map(|p| {
+            self.order[..=p]
+                .iter()
+                .filter(|id| self.pending.contains(*id))
+                .count()
+        });
+        json!({
+            "profile_id": profile_id,
+            "current": current.and_then(|id| self.plans.get(id)),
+            "position": current.and(index),
+            "pending": self.pending.len(),
+            "ahead": ahead,
+            "wrapped": wrapped,
+        })
+    }
+}
+
+static QUEUES: OnceLock<Mutex<HashMap<String, Queue>>> = OnceLock::new();
+
+fn queues() -> &'static Mutex<HashMap<String, Queue>> {
+    QUEUES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn with_queue<T>(profile_id: &str, f: impl FnOnce(&mut Queue) -> T) -> Result<T, String> {
+    let mut queues = queues()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Review queue state poisoned"))?;
+    Ok(f(queues.entry(profile_id.to_string()).or_default()))
+}
+
+async fn listed(conn: &Connection) -> Result<Vec<Value>, String> {
+    let listed = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/plans?limit=200",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    Ok(listed.as_array().cloned().unwrap_or_default())
+}
+
+/// Moves the profile's review cursor to the next pending plan and returns
+/// it as `current`, with its `position` among the pending plans and how
+/// many are `ahead`. Plans keep the place they were first seen in, so
+/// reordering, new arrivals and plans decided elsewhere never make the
+/// cursor jump; after the last one it wraps to any that were skipped.
+/// `restart` starts again from the oldest.
+#[tauri::command]
+pub async fn next_pending(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    restart: Option<bool>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let listed = listed(&conn).await?;
+    with_queue(&conn.profile_id, |queue| {
+        if restart.unwrap_or(false) {
+            *queue = Queue::default();
+        }
+        queue.refresh(listed);
+        let wrapped = queue.forward();
+        queue.describe(&conn.profile_id, wrapped)
+    })
+}
+
+/// Moves the cursor back to the previous plan that is still pending.
+#[tauri::command]
+pub async fn previous(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let listed = listed(&conn).await?;
+    with_queue(&conn.profile_id, |queue| {
+        queue.refresh(listed);
+        queue.back();
+        queue.describe(&conn.profile_id, false)
+    })
+}
+
+/// Sends `decision` (`approve` or `reject`) for the plan under the cursor,
+/// checked against the version it was shown at, then moves on to the next
+/// pending plan. The result is the decision's response as `result` plus the
+/// queue as [`next_pending`] reports it. A plan changed since it was shown
+/// fails with `PLAN_CONFLICT` and the cursor stays on it.
+#[tauri::command]
+pub async fn decide_current(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    decision: String,
+    payload: Option<Value>,
+    override_confirmation: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let current = with_queue(&conn.profile_id, |queue| {
+        queue
+            .current
+            .clone()
+            .filter(|id| queue.pending.contains(id))
+            .map(|id| {
+                let version = queue.plans.get(&id).and_then(decisions::plan_version);
+                (id, version)
+            })
+    })?;
+    let Some((plan_id, version)) = current else {
+        return Err(
+            ErrorCode::InvalidInput.with("No plan under the review cursor; call next_pending")
+        );
+    };
+    let decision = decisions::validate_decision(&decision, &plan_id)?;
+    let result = decisions::send_decision(
+        &conn.base_url,
+        conn.token.clone(),
+        &plan_id,
+        &decision,
+        payload,
+        version.as_deref(),
+        override_confirmation.as_deref(),
+    )
+    .await?;
+    with_queue(&conn.profile_id, |queue| queue.pending.remove(&plan_id))?;
+    // A failed refresh still moves on over what the queue already knows.
+    let listed = listed(&conn).await.ok();
+    with_queue(&conn.profile_id, |queue| {
+        if let Some(listed) = listed {
+            queue.refresh(listed);
+            queue.pending.remove(&plan_id);
+        }
+        // Another call may have moved the cursor meanwhile; leave it there.
+        let wrapped = queue.current.as_deref() == Some(plan_id.as_str()) && queue.forward();
+        let mut described = queue.describe(&conn.profile_id, wrapped);
+        described["decided"] = json!(plan_id);
+        described["result"] = result;
+        described
+    })
+}
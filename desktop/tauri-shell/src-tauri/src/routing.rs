@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::settings::{self, Profile, ReadRoutingSettings};
+use crate::{net, replicas};
+
+const MAX_HOLD_SECS: u64 = 3_600;
+
+struct Route {
+    settings: ReadRoutingSettings,
+    read_url: String,
+    /// Reads go to the primary until then.
+    held_until: Option<Instant>,
+    /// Why they do: `write` or `fallback`.
+    held_for: &'static str,
+}
+
+impl Route {
+    fn hold(&mut self, secs: u64, reason: &'static str) {
+        let until = Instant::now() + Duration::from_secs(secs);
+        if self.held_until.is_none_or(|held| held < until) {
+            self.held_until = Some(until);
+            self.held_for = reason;
+        }
+    }
+
+    fn held(&self) -> Option<Duration> {
+        self.held_until
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
+    }
+}
+
+#[derive(Default)]
+struct Routes {
+    /// Every write endpoint's origin, to its profile.
+    profiles: HashMap<String, String>,
+    routes: HashMap<String, Route>,
+}
+
+static ROUTES: OnceLock<Mutex<Routes>> = OnceLock::new();
+
+fn routes() -> &'static Mutex<Routes> {
+    ROUTES.get_or_init(|| Mutex::new(Routes::default()))
+}
+
+/// Tracks the profiles with a read endpoint; holds in progress survive
+/// unless the profile's routing settings changed.
+pub fn configure(profiles: &[Profile]) {
+    let mut state = routes().lock().unwrap_or_else(|e| e.into_inner());
+    let mut previous = std::mem::take(&mut state.routes);
+    state.profiles.clear();
+    for profile in profiles {
+        let Some(read_url) = profile.read_routing.read_url.as_deref() else {
+            continue;
+        };
+        for origin in profile.endpoints().filter_map(net::base_origin) {
+            state.profiles.insert(origin, profile.id.clone());
+        }
+        let route = match previous.remove(&profile.id) {
+            Some(route) if route.settings == profile.read_routing => route,
+            _ => Route {
+                settings: profile.read_routing.clone(),
+                read_url: read_url.trim().trim_end_matches('/').to_string(),
+                held_until: None,
+                held_for: "",
+            },
+        };
+        state.routes.insert(profile.id.clone(), route);
+    }
+}
+
+pub fn validate(profile: &Profile) -> Result<(), String> {
+    let routing = &profile.read_routing;
+    if let Some(url) = &routing.read_url {
+        settings::validate_base_url(url).map_err(|e| format!("Read endpoint: {}", e))?;
+        let origin = net::base_origin(url);
+        if profile
+            .endpoints()
+            .any(|endpoint| net::base_origin(endpoint) == origin)
+        {
+            return Err("Read endpoint must differ from the write endpoints".to_string());
+        }
+    }
+    if routing.pin_after_write_secs > MAX_HOLD_SECS
+        || routing.fallback_secs == 0
+        || routing.fallback_secs > MAX_HOLD_SECS
+    {
+        return Err(format!(
+            "Read routing holds must be at most {} seconds (fallback at least 1)",
+            MAX_HOLD_SECS
+        ));
+    }
+    Ok(())
+}
+
+/// The read endpoint to send a request for `base_url` to instead, when it
+/// is a GET or HEAD on a profile with one and reads are not held on the
+/// primary.
+pub fn route(method: &Method, base_url: &str) -> Option<String> {
+    if method != Method::GET && method != Method::HEAD {
+        return None;
+    }
+    let origin = net::base_origin(base_url)?;
+    let state = routes().lock().ok()?;
+    let route = state.routes.get(state.profiles.get(&origin)?)?;
+    route.held().is_none().then(|| route.read_url.clone())
+}
+
+/// Holds reads on the primary for `pin_after_write_secs`; called when a
+/// change is sent to `base_url` and again when it is answered.
+pub fn wrote(base_url: &str) {
+    let Some(origin) = net::base_origin(base_url) else {
+        return;
+    };
+    let mut state = routes().lock().unwrap_or_else(|e| e.into_inner());
+    let Routes { profiles, routes } = &mut *state;
+    if let Some(route) = profiles.get(&origin).and_then(|id| routes.get_mut(id)) {
+        let secs = route.settings.pin_after_write_secs;
+        route.hold(secs, "write");
+    }
+}
+
+/// Where to retry a request that failed on `tried`: a read that went to
+/// the read endpoint is held on the primary for `fallback_secs` and sent
+/// back to `base_url`; anything else fails over between replicas.
+pub fn failed(read: bool, base_url: &str, tried: &str, error: &str) -> Option<String> {
+    if !read {
+        return replicas::fail(tried, error);
+    }
+    let origin = net::base_origin(base_url)?;
+    let mut state = routes().lock().unwrap_or_else(|e| e.into_inner());
+    let Routes { profiles, routes } = &mut *state;
+    let route = routes.get_mut(profiles.get(&origin)?)?;
+    let secs = route.settings.fallback_secs;
+    route.hold(secs, "fallback");
+    Some(base_url.to_string())
+}
+
+/// The read endpoint of profile `profile_id` and any hold on the primary.
+pub fn describe(profile_id: &str) -> Value {
+    let state = routes().lock().unwrap_or_else(|e| e.into_inner());
+    match state.routes.get(profile_id) {
+        Some(route) => {
+            let held = route.held();
+            json!({
+                "read_url": route.read_url,
+                "reads_on_primary": held.is_some(),
+                "held_for": held.map(|_| route.held_for),
+                "held_ms": held.map(|left| left.as_millis() as u64),
+            })
+        }
+        None => Value::Null,
+    }
+}
@@ -0,0 +1,94 @@
+use std::ffi::OsString;
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::errors::ErrorCode;
+
+/// Starts the shell without plugins, scheduled approvals, local execution
+/// or the on-disk cache, to get past a corrupt cache or a plugin that
+/// breaks startup.
+pub const SAFE_MODE_ARG: &str = "--safe-mode";
+/// Set to `1` for the same effect as the flag, e.g. from a desktop shortcut.
+pub const SAFE_MODE_ENV: &str = "NOVAADAPT_SAFE_MODE";
+
+/// What safe mode turns off, for the window's banner.
+const DISABLED: &[(&str, &str)] = &[
+    ("plugins", "Plugins are not loaded"),
+    ("scheduled_approvals", "Scheduled approvals do not fire"),
+    ("local_exec", "Local plan execution is off"),
+    (
+        "local_cache",
+        "The local database is not opened; cached plans start empty",
+    ),
+];
+
+/// The arguments to relaunch with once this process has exited.
+static RELAUNCH: Mutex<Option<Vec<OsString>>> = Mutex::new(None);
+
+fn from_env() -> bool {
+    std::env::var(SAFE_MODE_ENV).is_ok_and(|v| v.trim() == "1")
+}
+
+pub fn active() -> bool {
+    static ACTIVE: OnceLock<bool> = OnceLock::new();
+    *ACTIVE.get_or_init(|| std::env::args().any(|arg| arg == SAFE_MODE_ARG) || from_env())
+}
+
+/// Refuses `what` while in safe mode.
+pub fn check(what: &str) -> Result<(), String> {
+    if active() {
+        return Err(ErrorCode::ForbiddenLocal.with(format!(
+            "{} is off in safe mode; restart normally to use it",
+            what
+        )));
+    }
+    Ok(())
+}
+
+/// Starts the process queued by `restart_safe_mode`; called once the app
+/// has drained and is exiting.
+pub fn relaunch(app: &AppHandle) {
+    let args = RELAUNCH.lock().ok().and_then(|mut args| args.take());
+    if let Some(args) = args {
+        let mut env = app.env();
+        env.args_os = args;
+        tauri::process::restart(&env);
+    }
+}
+
+#[tauri::command]
+pub fn get_safe_mode() -> Result<Value, String> {
+    let disabled: Vec<Value> = if active() {
+        DISABLED
+            .iter()
+            .map(|(id, label)| json!({ "id": id, "label": label }))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(json!({ "active": active(), "disabled": disabled }))
+}
+
+/// Quits (draining like any other quit) and starts again in safe mode, or
+/// back in normal mode when `enabled` is false.
+#[tauri::command]
+pub fn restart_safe_mode(app: AppHandle, enabled: bool) -> Result<Value, String> {
+    let mut args: Vec<OsString> = std::env::args_os()
+        .filter(|arg| arg != SAFE_MODE_ARG)
+        .collect();
+    if enabled {
+        args.push(SAFE_MODE_ARG.into());
+    } else if from_env() {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Safe mode was set through {}; unset it and restart",
+            SAFE_MODE_ENV
+        )));
+    }
+    *RELAUNCH
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Relaunch state poisoned"))? = Some(args);
+    app.exit(0);
+    Ok(json!({ "restarting": true, "safe_mode": enabled }))
+}
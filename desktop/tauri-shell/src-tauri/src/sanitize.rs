@@ -0,0 +1,472 @@
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+use crate::markdown;
+
+const MAX_INPUT_BYTES: usize = 1024 * 1024;
+const MAX_NESTING: usize = 64;
+
+/// Tags kept as they are (minus attributes not allowed below).
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+const VOID_TAGS: &[&str] = &["br", "hr", "img", "input", "meta", "link", "source", "wbr"];
+/// Tags dropped together with everything inside them.
+const DROPPED_WITH_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "template",
+    "noscript", "noembed", "svg", "math", "textarea", "title", "xmp", "select", "head",
+];
+
+/// Cleans core-provided HTML down to inert markup: scripts, styles, frames
+/// and embedded documents go with their content, every other unknown tag is
+/// unwrapped to its text, and of attributes only `title`, link `href`s with
+/// a safe scheme, table spans, list starts and `language-*` classes survive.
+/// Event handlers, `style`, `javascript:`/`data:` URLs and images (which
+/// would load remote content) never make it through. The output is
+/// well-formed: every tag it opens, it closes.
+#[tauri::command]
+pub fn sanitize_html(html: String) -> Result<Value, String> {
+    if html.len() > MAX_INPUT_BYTES {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "HTML is {} bytes; at most {} are sanitized",
+            html.len(),
+            MAX_INPUT_BYTES
+        )));
+    }
+    Ok(json!({ "html": clean(&html) }))
+}
+
+/// Whether a response field holds markup meant to be rendered, by the
+/// core's naming convention (`html`, `body_html`, ...).
+fn is_rich_field(key: &str) -> bool {
+    key == "html" || key.ends_with("_html")
+}
+
+/// Sanitizes, in place, every rich-content field anywhere in a core
+/// response, so the webview only ever receives clean markup whichever
+/// command fetched it.
+pub fn scrub(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                match field {
+                    Value::String(html) if is_rich_field(key) => *html = clean(html),
+                    other => scrub(other),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub),
+        _ => {}
+    }
+}
+
+pub fn clean(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<String> = Vec::new();
+    let bytes = html.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'<' {
+            let end = html[i..].find('<').map_or(html.len(), |n| i + n);
+            text_into(&html[i..end], &mut out);
+            i = end;
+            continue;
+        }
+        let rest = &html[i..];
+        if rest.starts_with("<!--") {
+            i = rest.find("-->").map_or(html.len(), |n| i + n + 3);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            i = rest.find('>').map_or(html.len(), |n| i + n + 1);
+            continue;
+        }
+        let closing = rest.starts_with("</");
+        let name_start = if closing { 2 } else { 1 };
+        let name_len = rest[name_start..]
+            .bytes()
+            .take_while(|b| b.is_ascii_alphanumeric() || *b == b'-')
+            .count();
+        let starts_alpha = rest
+            .as_bytes()
+            .get(name_start)
+            .is_some_and(u8::is_ascii_alphabetic);
+        if name_len == 0 || !starts_alpha {
+            out.push_str("&lt;");
+            i += 1;
+            continue;
+        }
+        let name = rest[name_start..name_start + name_len].to_ascii_lowercase();
+        let (attrs, tag_len) = parse_attributes(&rest[name_start + name_len..]);
+        let tag_end = i + name_start + name_len + tag_len;
+
+        if closing {
+            if let Some(depth) = open.iter().rposition(|t| *t == name) {
+                for tag in open.drain(depth..).rev() {
+                    out.push_str(&format!("</{}>", tag));
+                }
+            }
+            i = tag_end;
+            continue;
+        }
+        if DROPPED_WITH_CONTENT.contains(&name.as_str()) {
+            let self_closing = html[..tag_end].ends_with("/>");
+            i = if self_closing {
+                tag_end
+            } else {
+                skip_element(html, tag_end, &name)
+            };
+            continue;
+        }
+        if name == "img" {
+            // The alt text stands in for the image.
+            if let Some((_, alt)) = attrs.iter().find(|(k, _)| k == "alt") {
+                out.push_str(&escape_text(alt));
+            }
+            i = tag_end;
+            continue;
+        }
+        if ALLOWED_TAGS.contains(&name.as_str()) && open.len() < MAX_NESTING {
+            out.push('<');
+            out.push_str(&name);
+            for (key, value) in allowed_attributes(&name, &attrs) {
+                out.push_str(&format!(" {}=\"{}\"", key, value));
+            }
+            out.push('>');
+            if !VOID_TAGS.contains(&name.as_str()) {
+                open.push(name);
+            }
+        }
+        i = tag_end;
+    }
+    for tag in open.into_iter().rev() {
+        out.push_str(&format!("</{}>", tag));
+    }
+    out
+}
+
+/// Reads a tag's attributes up to its closing `>`, returning them (names
+/// lowercased, values entity-decoded) and the length consumed.
+fn parse_attributes(rest: &str) -> (Vec<(String, String)>, usize) {
+    let bytes = rest.as_bytes();
+    let mut attrs = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return (attrs, bytes.len());
+        }
+        if bytes[i] == b'>' {
+            return (attrs, i + 1);
+        }
+        let name_start = i;
+        while i < bytes.len()
+            && !bytes[i].is_ascii_whitespace()
+            && !matches!(bytes[i], b'=' | b'>' | b'/')
+        {
+            i += 1;
+        }
+        let name = rest[name_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                Some(quote @ (b'"' | b'\'')) => {
+                    let end = rest[i + 1..]
+                        .find(*quote as char)
+                        .map_or(bytes.len(), |n| i + 1 + n);
+                    value = decode_entities(&rest[i + 1..end]);
+                    i = (end + 1).min(bytes.len());
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = decode_entities(&rest[start..i]);
+                }
+            }
+        }
+        if !name.is_empty() {
+            attrs.push((name, value));
+        }
+    }
+}
+
+/// The attributes kept on an allowed tag, already escaped for output.
+fn allowed_attributes(tag: &str, attrs: &[(String, String)]) -> Vec<(&'static str, String)> {
+    let digits = |v: &str| {
+        let v = v.trim();
+        (!v.is_empty() && v.len() <= 4 && v.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| v.to_string())
+    };
+    let mut kept = Vec::new();
+    for (key, value) in attrs {
+        let allowed = match (tag, key.as_str()) {
+            (_, "title") => Some(("title", escape_text(value))),
+            ("a", "href") => markdown::safe_url(value).map(|url| ("href", url)),
+            ("td" | "th", "colspan") => digits(value).map(|v| ("colspan", v)),
+            ("td" | "th", "rowspan") => digits(value).map(|v| ("rowspan", v)),
+            ("ol", "start") => digits(value).map(|v| ("start", v)),
+            ("code" | "pre" | "span", "class") => language_class(value).map(|v| ("class", v)),
+            _ => None,
+        };
+        if let Some(pair) = allowed {
+            if !kept.iter().any(|(k, _)| *k == pair.0) {
+                kept.push(pair);
+            }
+        }
+    }
+    if tag == "a" && kept.iter().any(|(k, _)| *k == "href") {
+        kept.push(("rel", markdown::LINK_REL.to_string()));
+    }
+    kept
+}
+
+/// Keeps only `language-*` classes, for code highlighting.
+fn language_class(value: &str) -> Option<String> {
+    let classes: Vec<&str> = value
+        .split_whitespace()
+        .filter(|c| {
+            c.strip_prefix("language-").is_some_and(|lang| {
+                !lang.is_empty()
+                    && lang
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '+'))
+            })
+        })
+        .collect();
+    (!classes.is_empty()).then(|| classes.join(" "))
+}
+
+/// Skips the element opened just before `from`, up to its matching close
+/// (or the end of the input), and returns where the input resumes.
+fn skip_element(html: &str, from: usize, name: &str) -> usize {
+    let lower = html[from..].to_ascii_lowercase();
+    let close = format!("</{}", name);
+    let mut search = 0;
+    let mut level = 1;
+    let open = format!("<{}", name);
+    // Raw-text elements cannot nest; the first close ends them.
+    let raw_text = matches!(
+        name,
+        "script" | "style" | "textarea" | "title" | "xmp" | "noscript" | "noembed"
+    );
+    while let Some(at) = lower[search..].find('<').map(|n| search + n) {
+        let after = lower.as_bytes().get(at + name.len() + 2).copied();
+        let boundary =
+            |b: Option<u8>| b.is_none_or(|b| b.is_ascii_whitespace() || b == b'>' || b == b'/');
+        if lower[at..].starts_with(&close) && boundary(after) {
+            level -= 1;
+            if level == 0 || raw_text {
+                let end = lower[at..].find('>').map_or(lower.len(), |n| at + n + 1);
+                return from + end;
+            }
+        } else if !raw_text
+            && lower[at..].starts_with(&open)
+            && boundary(lower.as_bytes().get(at + name.len() + 1).copied())
+        {
+            level += 1;
+        }
+        search = at + 1;
+    }
+    html.len()
+}
+
+/// Text content, with bare `&` and stray markup characters escaped but
+/// existing entities left as they are.
+fn text_into(text: &str, out: &mut String) {
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '&' if entity_len(&text[index..]).is_some() => out.push('&'),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// The length of a well-formed character reference at the start of `text`
+/// (`&amp;`, `&#39;`, `&#x27;`).
+fn entity_len(text: &str) -> Option<usize> {
+    let body = text.strip_prefix('&')?;
+    let end = body.find(';')?;
+    let name = &body[..end];
+    let valid = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        !hex.is_empty() && hex.len() <= 6 && hex.bytes().all(|b| b.is_ascii_hexdigit())
+    } else if let Some(dec) = name.strip_prefix('#') {
+        !dec.is_empty() && dec.len() <= 7 && dec.bytes().all(|b| b.is_ascii_digit())
+    } else {
+        !name.is_empty() && name.len() <= 32 && name.bytes().all(|b| b.is_ascii_alphanumeric())
+    };
+    valid.then_some(end + 2)
+}
+
+/// Decodes numeric references and the basic named ones, so attribute
+/// checks see what the browser would.
+fn decode_entities(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        let candidate = &rest[at..];
+        let Some(len) = entity_len(candidate) else {
+            out.push('&');
+            rest = &candidate[1..];
+            continue;
+        };
+        let name = &candidate[1..len - 1];
+        let decoded = match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "colon" => Some(':'),
+            "tab" => Some('\t'),
+            "newline" => Some('\n'),
+            _ => name
+                .strip_prefix("#x")
+                .or_else(|| name.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| name.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(ch) => out.push(ch),
+            None => out.push_str(&candidate[..len]),
+        }
+        rest = &candidate[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_handlers_and_unsafe_links_are_dropped() {
+        assert_eq!(clean("<p>hi<script>alert(1)</script></p>"), "<p>hi</p>");
+        assert_eq!(
+            clean("<p onclick=\"x()\" style=\"color:red\">hi</p>"),
+            "<p>hi</p>"
+        );
+        assert_eq!(clean("<a href=\"javascript:alert(1)\">x</a>"), "<a>x</a>");
+        assert_eq!(
+            clean("<a href=\"&#106;avascript:alert(1)\">x</a>"),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            clean("<a href=\"https://core.example/\">x</a>"),
+            format!(
+                "<a href=\"https://core.example/\" rel=\"{}\">x</a>",
+                markdown::LINK_REL
+            )
+        );
+        assert_eq!(clean("<img src=x onerror=alert(1) alt=\"pic\">"), "pic");
+        assert_eq!(
+            clean("<iframe src=\"https://x.example\">inside</iframe>ok"),
+            "ok"
+        );
+    }
+
+    #[test]
+    fn the_output_is_well_formed() {
+        assert_eq!(
+            clean("<b>bold <i>both</b> plain"),
+            "<b>bold <i>both</i></b> plain"
+        );
+        assert_eq!(clean("<ul><li>one"), "<ul><li>one</li></ul>");
+        assert_eq!(clean("1 < 2 & </p>"), "1 &lt; 2 &amp; ");
+        assert_eq!(clean("<blink>text</blink>"), "text");
+    }
+
+    #[test]
+    fn rich_fields_are_scrubbed_wherever_they_are() {
+        let mut value = json!({
+            "title": "<b>kept as text</b>",
+            "body_html": "<script>x()</script><p>ok</p>",
+            "steps": [{ "html": "<img src=x alt=\"a\">", "detail": { "notes_html": "<em onclick=\"x\">n</em>" } }],
+            "htmlish": "<i>untouched</i>",
+            "html": 3,
+        });
+        scrub(&mut value);
+        assert_eq!(value["title"], "<b>kept as text</b>");
+        assert_eq!(value["body_html"], "<p>ok</p>");
+        assert_eq!(value["steps"][0]["html"], "a");
+        assert_eq!(value["steps"][0]["detail"]["notes_html"], "<em>n</em>");
+        assert_eq!(value["htmlish"], "<i>untouched</i>");
+        assert_eq!(value["html"], 3);
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::attachments;
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+
+pub const SUPPORTED: bool = cfg!(feature = "screenshot");
+pub const STAGING_DIR: &str = "staging/screenshots";
+/// Older captures are pruned so the staging area does not grow unbounded.
+const KEEP_STAGED: usize = 50;
+
+/// Desktop coordinates in physical pixels; the region must sit on a single
+/// monitor.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(not(feature = "screenshot"), allow(dead_code))]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[cfg_attr(not(feature = "screenshot"), allow(dead_code))]
+enum Mode {
+    Screen,
+    Window,
+    Region(Region),
+}
+
+/// Captures the primary screen (`screen`), the focused window (`window`) or
+/// a `region` as PNG into the app's staging area. With `plan_id`, the image
+/// then goes through the attachment pipeline and is uploaded to that plan
+/// as evidence.
+#[tauri::command]
+pub async fn capture_screenshot(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    mode: String,
+    region: Option<Region>,
+    plan_id: Option<String>,
+) -> Result<Value, String> {
+    let mode = match (mode.trim(), region) {
+        ("screen", _) => Mode::Screen,
+        ("window", _) => Mode::Window,
+        ("region", Some(region)) if region.width > 0 && region.height > 0 => Mode::Region(region),
+        ("region", _) => {
+            return Err(ErrorCode::InvalidInput.with("Region mode needs a non-empty region"))
+        }
+        (other, _) => {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Unknown capture mode: {} (use screen, window or region)",
+                other
+            )))
+        }
+    };
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ErrorCode::LocalIo.with(e.to_string()))?
+        .join(STAGING_DIR);
+    let (path, width, height) = tauri::async_runtime::spawn_blocking(move || {
+        let (png, width, height) = capture(mode)?;
+        let path = stage(&dir, &png)?;
+        Ok::<_, String>((path, width, height))
+    })
+    .await
+    .map_err(|e| ErrorCode::Internal.with(format!("Capture task failed: {}", e)))??;
+
+    let mut out = json!({
+        "path": path.display().to_string(),
+        "width": width,
+        "height": height,
+        "attachment": Value::Null,
+    });
+    if let Some(plan_id) = plan_id.filter(|p| !p.trim().is_empty()) {
+        out["attachment"] =
+            attachments::upload(&store, &db, profile.as_deref(), &plan_id, path).await?;
+    }
+    Ok(out)
+}
+
+fn stage(dir: &Path, png: &[u8]) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Create staging dir failed: {}", e)))?;
+    let path = dir.join(format!(
+        "screenshot-{}.png",
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    fs::write(&path, png)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write screenshot failed: {}", e)))?;
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut staged: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+            .collect();
+        // Names embed the capture time, so lexical order is age order.
+        staged.sort();
+        let excess = staged.len().saturating_sub(KEEP_STAGED);
+        for old in &staged[..excess] {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(feature = "screenshot")]
+fn capture(mode: Mode) -> Result<(Vec<u8>, u32, u32), String> {
+    use std::io::Cursor;
+    use xcap::image::ImageFormat;
+    use xcap::{Monitor, Window};
+
+    let failed = |e: xcap::XCapError| ErrorCode::LocalIo.with(format!("Capture failed: {}", e));
+    let image = match mode {
+        Mode::Screen => {
+            let monitors = Monitor::all().map_err(failed)?;
+            let monitor = monitors
+                .iter()
+                .find(|m| m.is_primary().unwrap_or(false))
+                .or_else(|| monitors.first())
+                .ok_or_else(|| ErrorCode::LocalIo.with("No monitor to capture"))?;
+            monitor.capture_image().map_err(failed)?
+        }
+        Mode::Window => {
+            let windows = Window::all().map_err(failed)?;
+            let window = windows
+                .iter()
+                .find(|w| w.is_focused().unwrap_or(false) && !w.is_minimized().unwrap_or(false))
+                .ok_or_else(|| ErrorCode::LocalIo.with("No focused window to capture"))?;
+            window.capture_image().map_err(failed)?
+        }
+        Mode::Region(region) => {
+            let monitor = Monitor::from_point(region.x, region.y).map_err(failed)?;
+            let (left, top) = (monitor.x().map_err(failed)?, monitor.y().map_err(failed)?);
+            let (x, y) = ((region.x - left) as u32, (region.y - top) as u32);
+            let (width, height) = (
+                monitor.width().map_err(failed)?,
+                monitor.height().map_err(failed)?,
+            );
+            let fits = x.checked_add(region.width).is_some_and(|r| r <= width)
+                && y.checked_add(region.height).is_some_and(|b| b <= height);
+            if !fits {
+                return Err(ErrorCode::InvalidInput.with("Region must fit on a single monitor"));
+            }
+            monitor
+                .capture_region(x, y, region.width, region.height)
+                .map_err(failed)?
+        }
+    };
+    let (width, height) = image.dimensions();
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| ErrorCode::Internal.with(format!("PNG encode failed: {}", e)))?;
+    Ok((png.into_inner(), width, height))
+}
+
+#[cfg(not(feature = "screenshot"))]
+fn capture(_mode: Mode) -> Result<(Vec<u8>, u32, u32), String> {
+    Err(ErrorCode::InvalidInput
+        .with("Screenshot capture needs a build with the `screenshot` feature"))
+}
@@ -0,0 +1,43 @@
+//! Thin wrapper over the OS keyring (Keychain, Credential Manager, Secret Service).
+
+use crate::errors::ErrorCode;
+
+const SERVICE: &str = "com.novaadapt.desktop";
+
+fn entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, name)
+        .map_err(|e| ErrorCode::KeyringUnavailable.with(format!("Keyring unavailable: {}", e)))
+}
+
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(ErrorCode::KeyringUnavailable.with(format!("Keyring read failed: {}", e))),
+    }
+}
+
+pub fn set(name: &str, value: &str) -> Result<(), String> {
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| ErrorCode::KeyringUnavailable.with(format!("Keyring write failed: {}", e)))
+}
+
+pub fn delete(name: &str) -> Result<(), String> {
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(ErrorCode::KeyringUnavailable.with(format!("Keyring delete failed: {}", e))),
+    }
+}
+
+pub fn profile_token_key(profile_id: &str) -> String {
+    format!("profile:{}:token", profile_id)
+}
+
+pub fn profile_ssh_password_key(profile_id: &str) -> String {
+    format!("profile:{}:ssh_password", profile_id)
+}
+
+pub fn profile_proxy_password_key(profile_id: &str) -> String {
+    format!("profile:{}:proxy_password", profile_id)
+}
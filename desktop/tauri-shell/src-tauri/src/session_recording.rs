@@ -0,0 +1,448 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::handover::DECISION_CATEGORIES;
+use crate::{environment, residency};
+
+const FORMAT: &str = "novaadapt-session";
+const VERSION: u64 = 1;
+const CHANGED_EVENT: &str = "session-recording:changed";
+const STEP_EVENT: &str = "session-replay:step";
+const FINISHED_EVENT: &str = "session-replay:finished";
+const MAX_ENTRIES: usize = 20_000;
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+/// Larger command arguments and event payloads are kept as their size.
+const MAX_ENTRY_BYTES: usize = 16 * 1024;
+const MAX_STRING: usize = 2_000;
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+/// Idle stretches longer than this replay as this long.
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(5);
+const MAX_SPEED: f64 = 20.0;
+/// The recorder's own commands, which say nothing about the triage.
+const UNRECORDED: &[&str] = &[
+    "start_session_recording",
+    "get_session_recording",
+    "stop_session_recording",
+    "load_session_recording",
+    "replay_session_recording",
+    "stop_session_replay",
+];
+/// Argument and payload keys whose values never go into a session file.
+const SENSITIVE: &[&str] = &[
+    "token",
+    "password",
+    "passphrase",
+    "secret",
+    "credential",
+    "recovery_key",
+    "private_key",
+    "api_key",
+    "code",
+    "otp",
+    "phrase",
+    "cookie",
+    "authorization",
+];
+
+struct Recording {
+    title: String,
+    operator: String,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    entries: Vec<Value>,
+    bytes: usize,
+    dropped: usize,
+}
+
+/// Checked before taking the lock, so commands and events cost nothing
+/// while no session is being recorded.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+fn current() -> &'static Mutex<Option<Recording>> {
+    static CURRENT: OnceLock<Mutex<Option<Recording>>> = OnceLock::new();
+    CURRENT.get_or_init(|| Mutex::new(None))
+}
+
+fn replay() -> &'static Mutex<Option<tauri::async_runtime::JoinHandle<()>>> {
+    static REPLAY: OnceLock<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>> = OnceLock::new();
+    REPLAY.get_or_init(|| Mutex::new(None))
+}
+
+fn sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE.iter().any(|s| key.contains(s))
+}
+
+/// Secrets redacted and long strings cut, so a session file can be handed
+/// to trainees.
+fn scrub(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = if sensitive(k) && !v.is_null() {
+                        json!("[redacted]")
+                    } else {
+                        scrub(v)
+                    };
+                    (k.clone(), v)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(scrub).collect()),
+        Value::String(s) if s.len() > MAX_STRING => {
+            let mut end = MAX_STRING;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            json!(format!("{}… ({} bytes)", &s[..end], s.len()))
+        }
+        other => other.clone(),
+    }
+}
+
+fn record(kind: &str, name: &str, data: Value) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut data = scrub(&data);
+    let mut size = data.to_string().len();
+    if size > MAX_ENTRY_BYTES {
+        data = json!({ "truncated": true, "bytes": size });
+        size = 64;
+    }
+    let Ok(mut guard) = current().lock() else {
+        return;
+    };
+    let Some(recording) = guard.as_mut() else {
+        return;
+    };
+    if recording.entries.len() >= MAX_ENTRIES || recording.bytes + size > MAX_BYTES {
+        recording.dropped += 1;
+        return;
+    }
+    recording.bytes += size;
+    let seq = recording.entries.len() as u64 + 1;
+    recording.entries.push(json!({
+        "seq": seq,
+        "t_ms": recording.started.elapsed().as_millis() as u64,
+        "at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "kind": kind,
+        "name": name,
+        "data": data,
+    }));
+}
+
+/// Wraps the invoke handler so each command the frontend sends is noted,
+/// with its arguments, while a session is being recorded.
+pub fn recorded<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command();
+        if RECORDING.load(Ordering::Relaxed) && !UNRECORDED.contains(&command) {
+            let args = match invoke.message.payload() {
+                InvokeBody::Json(args) => args.clone(),
+                InvokeBody::Raw(bytes) => json!({ "raw_bytes": bytes.len() }),
+            };
+            record(
+                "command",
+                command,
+                json!({ "window": invoke.message.webview_ref().label(), "args": args }),
+            );
+        }
+        handler(invoke)
+    }
+}
+
+/// An event the backend sent the frontend: a state transition.
+pub fn event(name: &str, payload: &Value) {
+    if RECORDING.load(Ordering::Relaxed) && !name.starts_with("session-") {
+        record("event", name, payload.clone());
+    }
+}
+
+/// A row written to the local audit log; approvals, rejections and step
+/// gates are filed as decisions.
+pub fn audited(profile_id: &str, category: &str, action: &str, entity_id: &str, detail: &Value) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let kind = if DECISION_CATEGORIES.contains(&category) {
+        "decision"
+    } else {
+        "audit"
+    };
+    record(
+        kind,
+        &format!("{}:{}", category, action),
+        json!({
+            "profile_id": profile_id,
+            "category": category,
+            "action": action,
+            "entity_id": entity_id,
+            "detail": detail,
+        }),
+    );
+}
+
+fn status(recording: Option<&Recording>) -> Value {
+    match recording {
+        None => json!({ "recording": false }),
+        Some(r) => json!({
+            "recording": true,
+            "title": r.title,
+            "operator": r.operator,
+            "started_at": r.started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "entries": r.entries.len(),
+            "bytes": r.bytes,
+            "dropped": r.dropped,
+        }),
+    }
+}
+
+fn counts(entries: &[Value]) -> Value {
+    let mut by_kind = Map::new();
+    for entry in entries {
+        let kind = entry["kind"].as_str().unwrap_or("other").to_string();
+        let n = by_kind.get(&kind).and_then(Value::as_u64).unwrap_or(0);
+        by_kind.insert(kind, json!(n + 1));
+    }
+    Value::Object(by_kind)
+}
+
+/// Starts recording the commands the frontend sends, the events the
+/// backend answers with and the decisions and audit rows it writes, until
+/// stopped. Opt-in and visible: `session-recording:changed` is emitted and
+/// the start is audited. Secrets in arguments and payloads are redacted;
+/// no screen content is captured.
+#[tauri::command]
+pub fn start_session_recording(
+    app: AppHandle,
+    db: State<'_, LocalDb>,
+    title: Option<String>,
+    operator: Option<String>,
+) -> Result<Value, String> {
+    let mut guard = current()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Session recorder poisoned"))?;
+    if guard.is_some() {
+        return Err(ErrorCode::InvalidInput.with("A session is already being recorded"));
+    }
+    let recording = Recording {
+        title: title.unwrap_or_default().trim().to_string(),
+        operator: operator.unwrap_or_default().trim().to_string(),
+        started_at: Utc::now(),
+        started: Instant::now(),
+        entries: Vec::new(),
+        bytes: 0,
+        dropped: 0,
+    };
+    let summary = status(Some(&recording));
+    *guard = Some(recording);
+    RECORDING.store(true, Ordering::Relaxed);
+    drop(guard);
+    let _ = db::record_audit(
+        &*db.lock()?,
+        "",
+        "session_recording",
+        "started",
+        "",
+        &summary,
+    );
+    let _ = environment::emit(&app, CHANGED_EVENT, &summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn get_session_recording() -> Result<Value, String> {
+    let guard = current()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Session recorder poisoned"))?;
+    Ok(status(guard.as_ref()))
+}
+
+/// Stops recording and writes the session to `path` as a replayable
+/// session file, or discards it when `path` is not given.
+#[tauri::command]
+pub fn stop_session_recording(
+    app: AppHandle,
+    db: State<'_, LocalDb>,
+    path: Option<String>,
+) -> Result<Value, String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    let target = path
+        .as_deref()
+        .map(|p| residency::check("The session recording", p))
+        .transpose()?;
+    let recording = {
+        let mut guard = current()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Session recorder poisoned"))?;
+        RECORDING.store(false, Ordering::Relaxed);
+        guard
+            .take()
+            .ok_or_else(|| ErrorCode::InvalidInput.with("No session is being recorded"))?
+    };
+    let ended_at = Utc::now();
+    let mut summary = status(Some(&recording));
+    summary["recording"] = json!(false);
+    summary["ended_at"] = json!(ended_at.to_rfc3339_opts(SecondsFormat::Secs, true));
+    summary["counts"] = counts(&recording.entries);
+    summary["path"] = json!(path);
+    if let Some(target) = &target {
+        let file = json!({
+            "format": FORMAT,
+            "version": VERSION,
+            "title": recording.title,
+            "operator": recording.operator,
+            "app_version": env!("CARGO_PKG_VERSION"),
+            "started_at": recording.started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "ended_at": ended_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "dropped": recording.dropped,
+            "entries": recording.entries,
+        });
+        fs::write(target, file.to_string()).map_err(|e| {
+            ErrorCode::LocalIo.with(format!("Write session recording failed: {}", e))
+        })?;
+    }
+    let _ = db::record_audit(
+        &*db.lock()?,
+        "",
+        "session_recording",
+        if target.is_some() {
+            "saved"
+        } else {
+            "discarded"
+        },
+        "",
+        &summary,
+    );
+    let _ = environment::emit(&app, CHANGED_EVENT, &summary);
+    Ok(summary)
+}
+
+fn read_session(path: &str) -> Result<Value, String> {
+    let path = path.trim();
+    let too_big = fs::metadata(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read {} failed: {}", path, e)))?
+        .len()
+        > MAX_FILE_BYTES;
+    if too_big {
+        return Err(ErrorCode::InvalidInput.with("Session file is too large"));
+    }
+    let raw = fs::read_to_string(path)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read {} failed: {}", path, e)))?;
+    let session: Value = serde_json::from_str(&raw)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Not a session file: {}", e)))?;
+    if session["format"] != FORMAT || !session["entries"].is_array() {
+        return Err(ErrorCode::InvalidInput.with("Not a session file"));
+    }
+    if session["version"].as_u64().is_none_or(|v| v > VERSION) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Session file version {} is newer than this shell reads",
+            session["version"]
+        )));
+    }
+    Ok(session)
+}
+
+/// Reads a session file for study: its header, counts per kind, the
+/// decisions taken in order, and every entry.
+#[tauri::command]
+pub fn load_session_recording(path: String) -> Result<Value, String> {
+    let mut session = read_session(&path)?;
+    let entries = session["entries"].as_array().cloned().unwrap_or_default();
+    session["counts"] = counts(&entries);
+    session["decisions"] = json!(entries
+        .iter()
+        .filter(|e| e["kind"] == "decision")
+        .collect::<Vec<_>>());
+    session["duration_ms"] = entries.last().map_or(json!(0), |e| e["t_ms"].clone());
+    Ok(session)
+}
+
+/// Plays a session file back to the frontend as `session-replay:step`
+/// events, one per entry from `from_seq`, at the pace it was recorded
+/// (`speed` times faster; idle stretches are shortened), then
+/// `session-replay:finished`. Nothing is sent to a core: commands are
+/// shown, not run again. A replay in progress is replaced.
+#[tauri::command]
+pub fn replay_session_recording(
+    app: AppHandle,
+    path: String,
+    speed: Option<f64>,
+    from_seq: Option<u64>,
+) -> Result<Value, String> {
+    let speed = speed.unwrap_or(1.0);
+    if !(speed > 0.0 && speed <= MAX_SPEED) {
+        return Err(ErrorCode::InvalidInput
+            .with(format!("Speed must be above 0 and at most {}", MAX_SPEED)));
+    }
+    let session = read_session(&path)?;
+    let from = from_seq.unwrap_or(0);
+    let entries: Vec<Value> = session["entries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|e| e["seq"].as_u64().unwrap_or(0) >= from)
+        .cloned()
+        .collect();
+    let total = entries.len();
+    let task_app = app.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        let mut last = entries
+            .first()
+            .and_then(|e| e["t_ms"].as_u64())
+            .unwrap_or(0);
+        for (index, entry) in entries.into_iter().enumerate() {
+            let t = entry["t_ms"].as_u64().unwrap_or(last);
+            let gap = Duration::from_millis(t.saturating_sub(last)).min(MAX_REPLAY_GAP);
+            last = t;
+            tokio::time::sleep(gap.div_f64(speed)).await;
+            let mut step = entry;
+            step["index"] = json!(index);
+            step["total"] = json!(total);
+            let _ = task_app.emit(STEP_EVENT, step);
+        }
+        let _ = task_app.emit(FINISHED_EVENT, json!({ "completed": true }));
+    });
+    let mut running = replay()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Session replay poisoned"))?;
+    if let Some(previous) = running.replace(handle) {
+        previous.abort();
+    }
+    Ok(json!({
+        "title": session["title"],
+        "operator": session["operator"],
+        "started_at": session["started_at"],
+        "entries": total,
+        "speed": speed,
+    }))
+}
+
+#[tauri::command]
+pub fn stop_session_replay(app: AppHandle) -> Result<Value, String> {
+    let handle = replay()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Session replay poisoned"))?
+        .take();
+    let stopped = handle.is_some_and(|h| {
+        h.abort();
+        true
+    });
+    if stopped {
+        let _ = app.emit(FINISHED_EVENT, json!({ "completed": false }));
+    }
+    Ok(json!({ "stopped": stopped }))
+}
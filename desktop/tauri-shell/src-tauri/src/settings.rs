@@ -0,0 +1,2300 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::errors::{self, ErrorCode};
+use crate::plan_window::PlanFilter;
+use crate::plugins::Capability;
+use crate::{
+    airgap, allowlist, announcements, api_prefix, auto_approval, availability, bandwidth, calendar,
+    chat_webhooks, clipboard, cookies, core_signing, crypto, device_login, digest, environment,
+    exec_windows, formatting, justifications, live, macro_pad, managed, net, notify, permissions,
+    plan_output, plugin_registry, plugins, quick_actions, quiet, replicas, residency, retention,
+    routing, secrets, ssrf, stall, state, status_endpoint, telemetry, tenants, ticketing, tls,
+    translation, transport, tray, two_person, vault, views, watchdog, webhook_receiver,
+};
+
+pub const SCHEMA_VERSION: u32 = 1;
+pub const CHANGED_EVENT: &str = "settings:changed";
+pub const DEFAULT_PROFILE_ID: &str = "default";
+const DEFAULT_BASE_URL: &str = "http://127.0.0.1:8787";
+const SETTINGS_FILE: &str = "settings.enc";
+const SETTINGS_FORMAT: &str = "novaadapt-settings";
+const SETTINGS_KEY_NAME: &str = "settings:key";
+
+/// Everything the shell persists. Unknown or missing fields fall back to
+/// defaults so older files keep loading as the schema grows.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub schema_version: u32,
+    pub active_profile: String,
+    pub profiles: Vec<Profile>,
+    pub notifications: NotificationSettings,
+    pub desktop: DesktopSettings,
+    pub attachments: AttachmentSettings,
+    pub clipboard: ClipboardSettings,
+    pub telemetry: TelemetrySettings,
+    pub retention: RetentionSettings,
+    pub watchdog: WatchdogSettings,
+    pub polling: PollingSettings,
+    pub plan_output: PlanOutputSettings,
+    pub stall_detection: StallSettings,
+    /// Slack or Teams channels that selected plan events are posted to.
+    pub chat_webhooks: Vec<ChatWebhook>,
+    /// Named plan list filters, in the order the user arranged them.
+    pub saved_views: Vec<SavedView>,
+    /// Site-specific core calls the operator can run by name.
+    pub quick_actions: Vec<QuickAction>,
+    /// What each installed plugin is allowed to do.
+    pub plugins: Vec<PluginGrant>,
+    pub plugin_registry: PluginRegistrySettings,
+    pub announcements: AnnouncementSettings,
+    pub airgap: AirgapSettings,
+    /// Cores the shell may send requests to (URL prefixes, `https://*.`
+    /// for a domain); empty allows any.
+    pub allowed_base_urls: Vec<String>,
+    /// Addresses core requests may reach despite being link-local or a
+    /// metadata endpoint, and how many redirects they follow.
+    pub request_targets: RequestTargetSettings,
+    /// How reports and exports write times and numbers.
+    pub formatting: FormattingSettings,
+    /// Where exports, reports and backups may be written; admins pin it
+    /// through the managed policy.
+    pub data_residency: DataResidencySettings,
+    /// Low-data mode, for metered connections.
+    pub bandwidth: BandwidthSettings,
+    /// The loopback receiver for signed callbacks and the local automations
+    /// they trigger.
+    pub webhook_receiver: WebhookReceiverSettings,
+    /// The read-only badge endpoint for external status bars.
+    pub status_endpoint: StatusEndpointSettings,
+    /// Stream Deck and macro-pad buttons and what they do.
+    pub macro_pad: MacroPadSettings,
+    /// Whether the operator is at their desk, as told to the cores.
+    pub availability: AvailabilitySettings,
+    /// Canned approval and rejection reasons operators pick from.
+    pub reason_templates: Vec<ReasonTemplate>,
+    pub translation: TranslationSettings,
+    /// Free-form frontend preferences (form defaults, toggles).
+    pub ui: Map<String, Value>,
+}
+
+/// A connection to one core. Credentials live in the keyring, never here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    /// The path the core's API is mounted under on every endpoint, such as
+    /// `/api` behind a gateway; empty for a core at the root. Found when
+    /// the profile connects (see `detect_api_prefix`), so unset until then
+    /// and again whenever `base_url` changes.
+    pub api_prefix: Option<String>,
+    /// Shown on every event from this profile; `prod` also puts execute-on-
+    /// approve and rollback behind a typed confirmation phrase.
+    pub environment: EnvironmentClass,
+    pub budget: BudgetSettings,
+    pub network: NetworkSettings,
+    pub tunnel: TunnelSettings,
+    pub proxy: ProxySettings,
+    pub core_auth: CoreAuthSettings,
+    /// Minutes before token expiry to warn; defaults to 10.
+    pub token_warning_minutes: Option<u32>,
+    /// Observer mode: every mutating request and local action is refused by
+    /// the shell, so the dashboard can be shown or handed over safely.
+    pub read_only: bool,
+    /// Set when a budget alert paused auto-approval; cleared by the operator.
+    pub auto_approval_paused: bool,
+    pub auto_approval: AutoApprovalSettings,
+    /// Notification rules and schedules for this profile's events, layered
+    /// over the global `notifications`.
+    pub notifications: ProfileNotificationSettings,
+    /// Times when approving with `execute=true` is blocked or downgraded.
+    pub execution_windows: Vec<ExecutionWindow>,
+    /// Team calendars whose events are change freezes, applied like
+    /// `execution_windows`.
+    pub calendars: Vec<CalendarSource>,
+    /// A second operator's code before critical plans are approved.
+    pub two_person: TwoPersonSettings,
+    /// Plans whose approval or rejection is refused without a reason.
+    pub justification: JustificationPolicy,
+    pub vault: VaultSettings,
+    pub ticketing: TicketingSettings,
+    pub device_login: DeviceLoginSettings,
+    pub streaming: StreamingSettings,
+    /// How payloads the core signs are checked before they are trusted.
+    pub core_signing: CoreSigningSettings,
+    pub replicas: ReplicaSettings,
+    pub read_routing: ReadRoutingSettings,
+    /// Keeps cookies the core (or an SSO gateway in front of it) sets, in
+    /// an encrypted jar that survives restarts, for cores that use a
+    /// session cookie instead of a bearer token.
+    pub cookie_jar: bool,
+    pub redirects: RedirectSettings,
+    pub tls: TlsSettings,
+    /// The time zone the core's operators work in, shown beside local time
+    /// (`UTC` when empty); an IANA name or an offset like `+05:30`.
+    pub core_time_zone: String,
+    /// The workspace requests are scoped to, on a core hosting several.
+    pub tenant: TenantSettings,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvironmentClass {
+    #[default]
+    Dev,
+    Staging,
+    Prod,
+}
+
+/// Which redirects the core's responses may send requests on to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectMode {
+    /// Up to `max` redirects, to any origin. Credentials are never sent
+    /// on to another origin; a hop that would carry them is refused.
+    #[default]
+    Follow,
+    /// Up to `max` redirects within the origin that was asked.
+    SameOrigin,
+    /// The redirect response is returned as an error.
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedirectSettings {
+    pub mode: RedirectMode,
+    /// Capped by `request_targets.max_redirects`.
+    pub max: usize,
+}
+
+impl Default for RedirectSettings {
+    fn default() -> Self {
+        Self {
+            mode: RedirectMode::Follow,
+            max: 3,
+        }
+    }
+}
+
+/// The oldest TLS protocol version a connection may use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// What the profile's connections to its core may negotiate.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    pub min_version: TlsVersion,
+    /// Cipher suites offered, by name such as `TLS13_AES_256_GCM_SHA384`;
+    /// empty offers every suite the shell supports.
+    pub cipher_suites: Vec<String>,
+}
+
+/// Sends the profile's GET traffic to a read replica and everything else
+/// to `base_url` (or its active replica).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadRoutingSettings {
+    /// Reads stay on the primary while this is unset.
+    pub read_url: Option<String>,
+    /// Reads go to the primary this long after every change sent, so an
+    /// approval is seen as applied before the replica has caught up.
+    pub pin_after_write_secs: u64,
+    /// Reads go to the primary this long after one failed on the replica.
+    pub fallback_secs: u64,
+}
+
+impl Default for ReadRoutingSettings {
+    fn default() -> Self {
+        Self {
+            read_url: None,
+            pin_after_write_secs: 10,
+            fallback_secs: 30,
+        }
+    }
+}
+
+/// How requests name the tenant on a multi-tenant core, and which one they
+/// are for. Cached plans and jobs are kept apart per tenant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantSettings {
+    /// Empty for a single-tenant core: requests go out unscoped.
+    pub tenant_id: String,
+    pub scope: TenantScope,
+    /// Carries the tenant with `header` scope.
+    pub header: String,
+    /// Put before every path with `path` scope; `{tenant}` is replaced.
+    pub path_prefix: String,
+    /// Lists the tenants the token can see; never scoped itself.
+    pub discovery_path: String,
+}
+
+impl Default for TenantSettings {
+    fn default() -> Self {
+        Self {
+            tenant_id: String::new(),
+            scope: TenantScope::Header,
+            header: "X-Tenant-ID".to_string(),
+            path_prefix: "/tenants/{tenant}".to_string(),
+            discovery_path: "/tenants".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantScope {
+    #[default]
+    Header,
+    Path,
+}
+
+/// Further base URLs serving the same core. Requests go to the fastest
+/// healthy endpoint, `base_url` included, and fail over to the next one when
+/// it stops answering.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicaSettings {
+    pub urls: Vec<String>,
+    /// Probed on every endpoint, without a token, to tell healthy from not.
+    pub health_path: String,
+    pub check_interval_secs: u64,
+    /// How much faster (in percent) a healthy endpoint must be to take over
+    /// from a healthy active one, so near-equal replicas do not flap.
+    pub switch_margin_pct: u32,
+}
+
+impl Default for ReplicaSettings {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            health_path: "/health".to_string(),
+            check_interval_secs: 15,
+            switch_margin_pct: 25,
+        }
+    }
+}
+
+/// Keepalive and reconnect policy for the profile's live event stream.
+/// Middleboxes drop idle connections after anything from seconds to hours,
+/// so every bound is adjustable per network.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingSettings {
+    /// TCP keepalive probe interval. The core's streams carry no pings of
+    /// their own, so this is what keeps an idle stream's path open.
+    pub keepalive_secs: u64,
+    /// A stream that delivered nothing for this long is presumed cut and
+    /// reopened from the last event seen.
+    pub stale_after_secs: u64,
+    /// Streams are recycled after this long even when healthy (the core
+    /// caps it at 300).
+    pub max_connection_secs: u64,
+    pub reconnect_initial_ms: u64,
+    pub reconnect_max_ms: u64,
+    pub reconnect_multiplier: f64,
+    /// Random spread of each reconnect delay, as a share of it (0.0–1.0).
+    pub reconnect_jitter: f64,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            keepalive_secs: 30,
+            stale_after_secs: 90,
+            max_connection_secs: 240,
+            reconnect_initial_ms: 500,
+            reconnect_max_ms: 30_000,
+            reconnect_multiplier: 2.0,
+            reconnect_jitter: 0.2,
+        }
+    }
+}
+
+/// Reads the core credential from HashiCorp Vault instead of the keyring.
+/// The fetched credential is held in memory only; the Vault token (`token`
+/// auth) or AppRole secret id is the one thing kept, in the keyring.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VaultSettings {
+    pub enabled: bool,
+    /// e.g. `https://vault.example.com:8200`.
+    pub address: String,
+    /// Enterprise namespace, sent as `X-Vault-Namespace`.
+    pub namespace: String,
+    /// Secret path under `/v1/`, e.g. `secret/data/novaadapt/core` (KV v2)
+    /// or `kv/novaadapt` (KV v1).
+    pub path: String,
+    /// Field of the secret holding the credential; defaults to `token`.
+    pub field: String,
+    pub auth: VaultAuth,
+    /// Auth mount; defaults to the method name (`approle`, `oidc`).
+    pub mount: String,
+    /// AppRole role id.
+    pub role_id: String,
+    /// OIDC role; empty uses the mount's default role.
+    pub role: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultAuth {
+    #[default]
+    Token,
+    Approle,
+    Oidc,
+}
+
+/// Files every approve/reject decision on the profile's plans as a ticket
+/// in Jira or ServiceNow: the first decision on a plan opens one, later
+/// ones comment on it. The API token (Jira) or password (ServiceNow) is
+/// kept in the keyring.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TicketingSettings {
+    pub enabled: bool,
+    pub provider: TicketProvider,
+    /// e.g. `https://example.atlassian.net` or
+    /// `https://example.service-now.com`.
+    pub url: String,
+    /// Jira account email or ServiceNow user name.
+    pub username: String,
+    /// Jira project key the issues are created in.
+    pub project: String,
+    /// Jira issue type; defaults to `Task`.
+    pub issue_type: String,
+    /// ServiceNow table; defaults to `incident`.
+    pub table: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketProvider {
+    #[default]
+    Jira,
+    #[serde(rename = "servicenow")]
+    ServiceNow,
+}
+
+/// OAuth device authorization grant (RFC 8628) for signing in where no
+/// browser can be opened on this machine. Endpoints are discovered from
+/// `issuer` unless given; the access token becomes the profile's bearer
+/// token.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceLoginSettings {
+    /// e.g. `https://login.example.com/realms/novaadapt`; its
+    /// `/.well-known/openid-configuration` supplies the endpoints.
+    pub issuer: String,
+    pub device_authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    /// Space-separated scopes to request.
+    pub scope: String,
+    /// Sent as `audience` for providers that need one (Auth0, Okta).
+    pub audience: String,
+}
+
+/// A recurring window, in local time, during which execute-on-approve is
+/// held back. `end` before `start` wraps past midnight; `days` name the days
+/// a window starts on (`mon`..`sun`), empty meaning every day.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionWindow {
+    pub name: String,
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    pub action: WindowAction,
+}
+
+impl Default for ExecutionWindow {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            days: Vec::new(),
+            start: "00:00".to_string(),
+            end: "00:00".to_string(),
+            action: WindowAction::Block,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowAction {
+    /// Refuse the approval unless an override is confirmed.
+    Block,
+    /// Approve without executing.
+    Downgrade,
+}
+
+/// A calendar of change freezes: each event (or each whose summary contains
+/// `filter`) holds execute-on-approve back with `action` while it runs.
+/// Recurring events are expanded. Times with a `TZID` are read as this
+/// machine's local time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarSource {
+    pub id: String,
+    pub name: String,
+    pub kind: CalendarKind,
+    /// The `.ics` (or `webcal://`) URL, or the CalDAV calendar collection.
+    pub url: String,
+    /// User for HTTP Basic auth, whose password is kept in the keyring;
+    /// empty sends no credentials.
+    pub username: String,
+    /// Case-insensitive summary match; empty takes every event.
+    pub filter: String,
+    pub action: WindowAction,
+    pub refresh_minutes: u32,
+}
+
+impl Default for CalendarSource {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            kind: CalendarKind::Ics,
+            url: String::new(),
+            username: String::new(),
+            filter: String::new(),
+            action: WindowAction::Block,
+            refresh_minutes: 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalendarKind {
+    #[default]
+    Ics,
+    Caldav,
+}
+
+/// Pending plans the shell approves without waiting for an operator, one
+/// policy per profile so staging can approve low-risk plans on its own
+/// while production waits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoApprovalSettings {
+    pub enabled: bool,
+    /// Highest risk approved: `low`, `medium` or `high`. Critical plans, and
+    /// plans that state no risk, always wait for an operator.
+    pub max_risk: String,
+    /// Only plans from these agents; empty for any agent.
+    pub agents: Vec<String>,
+    /// Approve with `execute=true`; held while a blocking execution window
+    /// is active.
+    pub execute: bool,
+}
+
+impl Default for AutoApprovalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_risk: "low".to_string(),
+            agents: Vec::new(),
+            execute: false,
+        }
+    }
+}
+
+/// Two-person rule for plans at critical risk: the approval is sent only
+/// after a second operator's one-time code is confirmed. `totp` codes come
+/// from an authenticator app enrolled per approver (its secret is kept in
+/// the keyring); `core` codes are issued by the core to the second operator.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TwoPersonSettings {
+    pub enabled: bool,
+    pub method: TwoPersonMethod,
+    pub approvers: Vec<SecondApprover>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwoPersonMethod {
+    #[default]
+    Totp,
+    Core,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecondApprover {
+    pub id: String,
+    pub name: String,
+    /// The operator who enrolled the approver, and so saw its secret; their
+    /// own approvals are never confirmed with it.
+    pub enrolled_by: String,
+}
+
+/// Decisions that must carry a justification: a plan matching any rule
+/// is only approved with a `justification`, or rejected with a `reason`,
+/// of at least the rule's length. A reason template counts as written text.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JustificationPolicy {
+    pub rules: Vec<JustificationRule>,
+}
+
+/// One class of plans. Every field that is set must match
+/// (case-insensitive) and unset ones match anything; `risk`, `agent` and
+/// `tag` are read from the plan like notification rules read them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JustificationRule {
+    /// `approve` or `reject`; unset for both.
+    pub decision: Option<String>,
+    pub risk: Option<String>,
+    pub agent: Option<String>,
+    pub tag: Option<String>,
+    /// Characters the justification needs, after trimming.
+    pub min_length: usize,
+}
+
+impl Default for JustificationRule {
+    fn default() -> Self {
+        Self {
+            decision: None,
+            risk: None,
+            agent: None,
+            tag: None,
+            min_length: 10,
+        }
+    }
+}
+
+/// A reason an operator can send instead of typing one, by passing its id
+/// as `reason_template`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReasonTemplate {
+    pub id: String,
+    /// Shown in the picker.
+    pub label: String,
+    pub text: String,
+    /// `approve` or `reject`; unset for both.
+    pub decision: Option<String>,
+}
+
+/// Spend thresholds in USD; `None` disables the corresponding check.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetSettings {
+    pub daily_usd: Option<f64>,
+    pub monthly_usd: Option<f64>,
+    pub pause_auto_approval: bool,
+}
+
+/// How the shell behaves as a desktop app rather than as a window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopSettings {
+    /// Closing the main window hides it to the tray; pollers keep running.
+    pub close_to_tray: bool,
+    /// When launched at login, stay in the tray instead of opening the window.
+    pub start_minimized: bool,
+}
+
+impl Default for DesktopSettings {
+    fn default() -> Self {
+        Self {
+            close_to_tray: true,
+            start_minimized: false,
+        }
+    }
+}
+
+/// What happens to text the frontend copies through `copy_to_clipboard`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    /// Check copied text for secrets (tokens, private keys) first, and
+    /// clear the clipboard afterwards.
+    pub guard: bool,
+    pub action: ClipboardGuardAction,
+    /// With the guard on, clear the clipboard this long after a copy unless
+    /// something else was copied since; 0 leaves it.
+    pub clear_after_seconds: u64,
+}
+
+impl Default for ClipboardSettings {
+    fn default() -> Self {
+        Self {
+            guard: false,
+            action: ClipboardGuardAction::default(),
+            clear_after_seconds: 30,
+        }
+    }
+}
+
+/// What the clipboard guard does with text that contains a secret.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardGuardAction {
+    /// Copy the text with each secret replaced by `[redacted]`.
+    #[default]
+    Strip,
+    /// Copy nothing.
+    Refuse,
+}
+
+/// Checks every file goes through before it is uploaded to the core.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachmentSettings {
+    pub max_bytes: u64,
+    /// MIME types allowed to upload; `type/*` matches a whole family and an
+    /// empty list allows anything.
+    pub allowed_mime: Vec<String>,
+    /// clamd endpoint: a Unix socket path or `host:port`. Empty skips the
+    /// virus scan.
+    pub clamav: String,
+    /// Refuse files that look like they contain credentials.
+    pub block_secrets: bool,
+}
+
+impl Default for AttachmentSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: 25 * 1024 * 1024,
+            allowed_mime: [
+                "image/*",
+                "text/*",
+                "application/json",
+                "application/pdf",
+                "application/zip",
+            ]
+            .iter()
+            .map(|m| m.to_string())
+            .collect(),
+            clamav: String::new(),
+            block_secrets: true,
+        }
+    }
+}
+
+/// OpenTelemetry export of the shell's own spans (commands, core requests,
+/// sync) over OTLP/HTTP. Off by default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    /// Collector base URL, e.g. `http://otel-collector:4318`; spans go to
+    /// `/v1/traces` under it.
+    pub endpoint: String,
+    /// Extra request headers for the collector, e.g. an ingest key.
+    pub headers: BTreeMap<String, String>,
+    pub service_name: String,
+    /// Share of traces exported, 0.0 to 1.0, decided per trace id.
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            headers: BTreeMap::new(),
+            service_name: "novaadapt-desktop".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+/// How many days the local cache keeps each kind of data before the
+/// compaction job drops it; `None` keeps it indefinitely.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// Cached plans and jobs the core no longer lists, their history for
+    /// `get_dashboard_at`, expired deadlines, fired approval schedules, plan
+    /// output transcripts and cached translations.
+    pub plans_days: Option<u32>,
+    /// Cached core log lines.
+    pub logs_days: Option<u32>,
+    /// The shell's own audit trail.
+    pub audit_days: Option<u32>,
+    /// Metric samples, request latencies and bandwidth rollups.
+    pub metrics_days: Option<u32>,
+    /// Thumbnail cache and staged screenshots.
+    pub files_days: Option<u32>,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            plans_days: Some(90),
+            logs_days: Some(7),
+            audit_days: None,
+            metrics_days: Some(400),
+            files_days: Some(30),
+        }
+    }
+}
+
+/// Bounds for the dashboard poller, which speeds up while plans, jobs and
+/// events keep changing and backs off while the core is quiet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PollingSettings {
+    /// Off polls at exactly the interval the window asked for.
+    pub adaptive: bool,
+    pub min_interval_ms: u64,
+    pub max_interval_ms: u64,
+}
+
+impl Default for PollingSettings {
+    fn default() -> Self {
+        Self {
+            adaptive: true,
+            min_interval_ms: 2_000,
+            max_interval_ms: 30_000,
+        }
+    }
+}
+
+/// How plan execution output is relayed to the window. Every chunk goes to
+/// the transcript; these bounds only apply to live events.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanOutputSettings {
+    /// Output waiting for the window beyond this is dropped per `overflow`.
+    pub buffer_bytes: usize,
+    /// Waiting output is sent this often, each step's stream coalesced into
+    /// as few events as `max_event_bytes` allows.
+    pub flush_ms: u64,
+    pub max_event_bytes: usize,
+    pub overflow: OutputOverflow,
+}
+
+impl Default for PlanOutputSettings {
+    fn default() -> Self {
+        Self {
+            buffer_bytes: 256 * 1024,
+            flush_ms: 100,
+            max_event_bytes: 16 * 1024,
+            overflow: OutputOverflow::DropOldest,
+        }
+    }
+}
+
+/// When an executing plan counts as possibly stalled: no step progress
+/// reported by the core for `after_minutes`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StallSettings {
+    pub enabled: bool,
+    pub after_minutes: u32,
+}
+
+impl Default for StallSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            after_minutes: 10,
+        }
+    }
+}
+
+/// A Slack or Teams incoming webhook that plan events are posted to, for
+/// people who do not run the shell. The webhook URL is itself a credential,
+/// so it is kept in the keyring, not here.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatWebhook {
+    pub id: String,
+    pub name: String,
+    pub kind: ChatKind,
+    pub enabled: bool,
+    /// Events posted (`plan_approved`, `plan_rejected`, `execution_failed`,
+    /// `execution_completed`); empty posts all of them.
+    pub events: Vec<String>,
+    /// Profiles whose events are posted; empty posts every profile's.
+    pub profiles: Vec<String>,
+    /// Message text by event, with `{name}` placeholders (`plan_id`,
+    /// `objective`, `status`, `reason`, `risk`, `error`, `profile`,
+    /// `event`); events without one use the built-in text.
+    pub templates: BTreeMap<String, String>,
+    /// Posts beyond this many a minute are dropped and counted in the next
+    /// one.
+    pub max_per_minute: u32,
+}
+
+impl Default for ChatWebhook {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            kind: ChatKind::Slack,
+            enabled: true,
+            events: Vec::new(),
+            profiles: Vec::new(),
+            templates: BTreeMap::new(),
+            max_per_minute: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatKind {
+    #[default]
+    Slack,
+    Teams,
+}
+
+/// A named filter and sort for the plan list, shared by every window.
+/// `profile` pins the view to one profile; unset, it follows the active one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedView {
+    pub id: String,
+    pub name: String,
+    pub profile: Option<String>,
+    pub filter: PlanFilter,
+    /// As `get_plans_window` takes it; empty for the default order.
+    pub sort: String,
+}
+
+/// A core request the operator defined. `{name}` placeholders in `path`
+/// and in `payload` strings are filled from the parameters given when it
+/// runs; a payload string that is only a placeholder takes the parameter's
+/// JSON value as-is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickAction {
+    pub id: String,
+    pub name: String,
+    pub method: String,
+    pub path: String,
+    pub payload: Option<Value>,
+    /// Runs only after the rendered request was shown and confirmed.
+    pub confirm: bool,
+    /// Sent to this profile's core; unset, to the active profile's.
+    pub profile: Option<String>,
+    /// Also listed in the tray menu.
+    pub tray: bool,
+}
+
+impl Default for QuickAction {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            method: "POST".to_string(),
+            path: String::new(),
+            payload: None,
+            confirm: true,
+            profile: None,
+            tray: false,
+        }
+    }
+}
+
+/// The operator's grant for one plugin: it runs only when `enabled`, and
+/// only with the capabilities both its manifest asks for and listed here.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginGrant {
+    pub id: String,
+    pub enabled: bool,
+    pub capabilities: Vec<Capability>,
+    /// Sensitive capabilities the operator refused when the plugin first
+    /// asked; calls needing them fail without asking again.
+    pub denied: Vec<Capability>,
+    /// Installs and updates from the registry stay on this version.
+    pub pin: Option<String>,
+}
+
+/// Where plugin packages come from. A package installs only when it is
+/// signed by one of `trusted_keys` (base64 Ed25519 public keys).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginRegistrySettings {
+    /// The registry index; empty when no registry is configured.
+    pub url: String,
+    pub trusted_keys: Vec<String>,
+}
+
+/// The feed of announcements, changelogs and advisories shown in the app.
+/// It must be signed by one of `trusted_keys` (base64 Ed25519 public keys);
+/// read from the core, a feed the profile's `core_signing` verifies is
+/// taken too.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnouncementSettings {
+    /// Fetching the feed in the background.
+    pub enabled: bool,
+    /// The feed; empty reads `/announcements` from the active profile's core.
+    pub url: String,
+    pub trusted_keys: Vec<String>,
+    pub refresh_minutes: u32,
+}
+
+impl Default for AnnouncementSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            url: String::new(),
+            trusted_keys: Vec::new(),
+            refresh_minutes: 60,
+        }
+    }
+}
+
+/// Where requests to a core may go. Link-local and cloud metadata
+/// addresses are refused unless listed in `allowed_addresses` (addresses or
+/// CIDR ranges).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestTargetSettings {
+    pub allowed_addresses: Vec<String>,
+    pub max_redirects: usize,
+}
+
+impl Default for RequestTargetSettings {
+    fn default() -> Self {
+        Self {
+            allowed_addresses: Vec::new(),
+            max_redirects: 3,
+        }
+    }
+}
+
+/// Verification of payloads the core signs: live events and plans in
+/// air-gap bundles arrive as `{ "signed": "<JSON text>", "signature":
+/// "<base64>" }`. With `hmac` the shared secret (HMAC-SHA256) is kept in
+/// the keyring; with `ed25519` `public_key` is the core's base64 key. A
+/// payload failing verification is dropped; with `require`, so is one that
+/// is not signed at all.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoreSigningSettings {
+    pub mode: CoreSigningMode,
+    pub public_key: String,
+    pub require: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreSigningMode {
+    #[default]
+    Off,
+    Hmac,
+    Ed25519,
+}
+
+/// Plan bundles and decision files carried across an air gap. Either side
+/// accepts a file only when it is signed by one of `trusted_keys` (base64
+/// Ed25519 public keys of the other side's shells).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AirgapSettings {
+    pub trusted_keys: Vec<String>,
+    /// How the decisions of an applied decision file are sent to the core.
+    pub replay: ReplaySettings,
+}
+
+/// `ordered` sends a decision file's decisions one at a time in the order
+/// they were made; `parallel` sends every decision whose dependencies have
+/// gone through at once, `max_parallel` at a time. Either way a decision
+/// waits for the ones it depends on and is skipped if one of them failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayMode {
+    #[default]
+    Ordered,
+    Parallel,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplaySettings {
+    pub mode: ReplayMode,
+    pub max_parallel: usize,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: ReplayMode::Ordered,
+            max_parallel: 4,
+        }
+    }
+}
+
+/// Locale and time zone for text the backend writes: PDF packets, spoken
+/// summaries, handover reports and `format_values`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormattingSettings {
+    /// A BCP 47 tag such as `de-DE`; the operating system's when empty.
+    pub locale: String,
+    /// `local` (the operating system's), `UTC`, an IANA name such as
+    /// `Europe/Berlin`, or an offset like `-03:00`.
+    pub time_zone: String,
+    /// Also show each time in the profile's `core_time_zone` when that
+    /// differs.
+    pub core_time: bool,
+}
+
+impl Default for FormattingSettings {
+    fn default() -> Self {
+        Self {
+            locale: String::new(),
+            time_zone: "local".to_string(),
+            core_time: true,
+        }
+    }
+}
+
+/// Limits on where the shell writes files it produces: plan PDFs, audit
+/// and metrics exports, air-gap bundles and decision files, settings
+/// bundles and local backups. Paths must be absolute and are checked with
+/// links resolved.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataResidencySettings {
+    /// Folders (and their subfolders) files may be written to; `~/` is the
+    /// user's home. Empty allows any folder.
+    pub allowed_dirs: Vec<String>,
+    /// Refuse USB drives, memory cards and other external disks.
+    pub deny_removable: bool,
+    /// Refuse network shares (SMB, NFS, WebDAV and the like).
+    pub deny_network: bool,
+}
+
+impl DataResidencySettings {
+    pub fn restricts(&self) -> bool {
+        !self.allowed_dirs.is_empty() || self.deny_removable || self.deny_network
+    }
+}
+
+/// For operators on metered connections.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthSettings {
+    /// Stops the background plan and job sync (which fetches every plan
+    /// ahead of its being opened) and attachment thumbnails. Counting goes
+    /// on either way; see `bandwidth_report`.
+    pub low_data: bool,
+}
+
+/// A listener on `127.0.0.1:<port>` that takes `POST /hooks/<type>` from
+/// the core, CI or monitoring on this machine (or through a tunnel).
+/// Callbacks must be signed with the receiver secret; the signing key
+/// lives in the keyring, not here.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookReceiverSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Every enabled automation whose `callback_type` matches runs, in
+    /// this order.
+    pub automations: Vec<WebhookAutomation>,
+}
+
+impl Default for WebhookReceiverSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47821,
+            automations: Vec::new(),
+        }
+    }
+}
+
+/// Machine translation of plan text into the operator's language through
+/// a LibreTranslate or DeepL endpoint, with the source language detected
+/// by the provider. Off by default, since plan text leaves the machine;
+/// the API key is kept in the keyring (`set_translation_key`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+    pub enabled: bool,
+    pub provider: TranslationProvider,
+    /// e.g. `https://libretranslate.example.org` or
+    /// `https://api-free.deepl.com`.
+    pub endpoint: String,
+    /// Language code translated into, such as `de` or `pt-BR`; empty uses
+    /// the language of `formatting.locale` or the OS.
+    pub target_language: String,
+    /// Plan fields translated wherever they appear, steps and agent
+    /// messages included.
+    pub fields: Vec<String>,
+}
+
+impl Default for TranslationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: TranslationProvider::default(),
+            endpoint: String::new(),
+            target_language: String::new(),
+            fields: ["objective", "description", "summary", "message", "content"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationProvider {
+    #[default]
+    Libretranslate,
+    Deepl,
+}
+
+/// `GET http://127.0.0.1:<port>/status`, the pending-approval count and
+/// core health as JSON (or `?format=text` for the bare count), for
+/// Polybar, xbar or Stream Deck widgets. It only reads.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusEndpointSettings {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for StatusEndpointSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47822,
+        }
+    }
+}
+
+/// Physical buttons: a Stream Deck plugin or macro-pad software presses
+/// them with `POST http://127.0.0.1:<port>/press/<id>` and the pad token,
+/// and a pad the window reads over WebHID through `press_macro_button`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroPadSettings {
+    /// The press listener; `press_macro_button` works either way.
+    pub enabled: bool,
+    pub port: u16,
+    pub buttons: Vec<MacroButton>,
+}
+
+impl Default for MacroPadSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47823,
+            buttons: Vec::new(),
+        }
+    }
+}
+
+/// The operator's availability, published to every profile's core that
+/// routes approval requests by it. A status set by hand wins over the
+/// automatic one until it is cleared or runs out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvailabilitySettings {
+    /// Publishing to the cores; the status is still tracked locally.
+    pub enabled: bool,
+    /// Away after this long without keyboard or mouse input; 0 never.
+    pub idle_minutes: u32,
+    /// Away while the OS session is locked.
+    pub away_on_lock: bool,
+}
+
+impl Default for AvailabilitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            idle_minutes: 10,
+            away_on_lock: true,
+        }
+    }
+}
+
+/// One button. Approve and reject act on the plan open in the window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroButton {
+    /// What the device sends: a Stream Deck action's id, a key name such
+    /// as `F13`, or any label the pad software is set up with.
+    pub id: String,
+    pub action: MacroAction,
+    /// Approve and reject only go through on a second press within a few
+    /// seconds of the first.
+    pub confirm: bool,
+    /// How long `mute_notifications` mutes for; pressing it again unmutes.
+    pub mute_minutes: u32,
+}
+
+impl Default for MacroButton {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            action: MacroAction::default(),
+            confirm: true,
+            mute_minutes: 60,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroAction {
+    #[default]
+    ApproveCurrent,
+    RejectCurrent,
+    MuteNotifications,
+}
+
+/// What one callback type does locally.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookAutomation {
+    pub id: String,
+    pub name: String,
+    /// The `<type>` in `/hooks/<type>`.
+    pub callback_type: String,
+    pub enabled: bool,
+    /// The profile notified or prefetched; unset, the active one.
+    pub profile: Option<String>,
+    pub action: AutomationAction,
+}
+
+impl Default for WebhookAutomation {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            callback_type: String::new(),
+            enabled: true,
+            profile: None,
+            action: AutomationAction::default(),
+        }
+    }
+}
+
+/// `notify` raises a `webhook` notification, delivered as the notification
+/// rules say, with `{name}` placeholders filled from the callback's JSON
+/// fields. `prefetch` pulls the profile's plan and job changes into the
+/// local cache. `script` runs `program` (an absolute path) with `args`,
+/// the callback's body on standard input and a scrubbed environment.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AutomationAction {
+    Notify {
+        title: String,
+        #[serde(default)]
+        body: String,
+    },
+    Prefetch {
+        #[serde(default)]
+        full: bool,
+    },
+    Script {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+}
+
+impl Default for AutomationAction {
+    fn default() -> Self {
+        AutomationAction::Notify {
+            title: String::new(),
+            body: String::new(),
+        }
+    }
+}
+
+/// What gives when a window cannot keep up with a plan's output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputOverflow {
+    /// Keep the latest output, which is usually what explains a failure.
+    #[default]
+    DropOldest,
+    DropNewest,
+}
+
+/// When a core request counts as slow, and when a stuck read is abandoned
+/// and sent again, well before the hard request timeout.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogSettings {
+    pub slow_after_ms: u64,
+    /// `None` only warns; reads are never abandoned.
+    pub abort_after_ms: Option<u64>,
+    pub max_retries: u32,
+    /// Expected duration per path prefix (e.g. `/plans/` or `/logs/export`),
+    /// for endpoints that are slow by design; the longest match wins.
+    pub expected_ms: BTreeMap<String, u64>,
+}
+
+impl Default for WatchdogSettings {
+    fn default() -> Self {
+        Self {
+            slow_after_ms: 4_000,
+            abort_after_ms: Some(10_000),
+            max_retries: 1,
+            expected_ms: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Checked in order; the first rule matching an event decides how it is
+    /// delivered.
+    pub rules: Vec<NotificationRule>,
+    pub quiet_hours: QuietHours,
+    pub sounds: SoundSettings,
+    pub digest: DigestSettings,
+}
+
+/// A profile's own notification settings for events from that profile.
+/// Its rules are checked before the global ones; quiet hours and digest
+/// classes replace the global ones where set.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileNotificationSettings {
+    pub rules: Vec<NotificationRule>,
+    pub quiet_hours: Option<QuietHours>,
+    pub digest: Option<DigestSettings>,
+}
+
+/// Event classes whose non-urgent notifications are batched into one
+/// summary instead of each interrupting the operator.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestSettings {
+    /// Minutes between summaries, keyed by notification kind or `*` for
+    /// every kind not listed; unlisted kinds are delivered as they happen.
+    pub classes: BTreeMap<String, u32>,
+}
+
+/// Alert sounds per event class: a built-in name (see `audio::BUILTIN`),
+/// `none`, or the absolute path of a custom audio file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub enabled: bool,
+    /// Overrides keyed by class (`new_plan`, `plan_failed`, `core_down`, or a
+    /// notification kind); unlisted classes use the defaults.
+    pub classes: BTreeMap<String, String>,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            classes: BTreeMap::new(),
+        }
+    }
+}
+
+impl SoundSettings {
+    pub fn sound_for(&self, class: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let sound = self
+            .classes
+            .get(class)
+            .map(String::as_str)
+            .unwrap_or(match class {
+                "new_plan" => "chime",
+                "plan_failed" => "alert",
+                "core_down" => "alarm",
+                _ => "ping",
+            });
+        (sound != "none").then(|| sound.to_string())
+    }
+}
+
+/// While quiet, popups and sounds are held back and summarised once the
+/// quiet period ends; critical-risk events still get through when
+/// `critical_override` is on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    /// Local `HH:MM`; `end` before `start` spans midnight.
+    pub start: String,
+    pub end: String,
+    /// Also stay quiet while the OS Do-Not-Disturb / Focus mode is on.
+    pub respect_os_dnd: bool,
+    pub critical_override: bool,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+            respect_os_dnd: true,
+            critical_override: true,
+        }
+    }
+}
+
+/// Matches an event when every field that is set matches (case-insensitive);
+/// unset fields match anything. `event` is the notification kind (`budget`,
+/// `deadline`, `token`, ...) or `*`; `risk`, `agent` and `tag` are read from
+/// the event data (`risk`/`risk_level`, `agent`/`agent_id`, `tags`/`tag`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationRule {
+    pub event: Option<String>,
+    pub risk: Option<String>,
+    pub agent: Option<String>,
+    pub tag: Option<String>,
+    pub delivery: Delivery,
+}
+
+/// `badge` only counts the event in the app, `banner` also raises an OS
+/// notification, `sound` plays one with it, and `actionable` additionally
+/// asks the app to show the event's actions (approve, reject, ...).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    Badge,
+    #[default]
+    Banner,
+    Sound,
+    Actionable,
+}
+
+/// Name resolution for reaching the core during DNS outages or behind
+/// split-horizon DNS. The defaults use the system resolver unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Hostname to addresses, used instead of any DNS lookup.
+    pub host_overrides: BTreeMap<String, Vec<String>>,
+    /// Nameservers (`ip` or `ip:port`) queried instead of the system resolver.
+    pub dns_servers: Vec<String>,
+    /// When set, `dns_servers` are queried over DNS-over-HTTPS and must
+    /// present a certificate for this name.
+    pub doh_server_name: Option<String>,
+    pub ip_family: IpFamily,
+    /// Experimental: send requests to the core over HTTP/3 (QUIC) first,
+    /// falling back to TCP. Needs a build with the `http3` feature.
+    pub http3: bool,
+}
+
+/// Which address family to reach the core over. The preferred family is
+/// tried first and the other joins the race if it has not connected within
+/// a few hundred milliseconds (happy eyeballs); the `*_only` modes drop the
+/// other family entirely.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    /// The resolver's order, unchanged.
+    #[default]
+    Auto,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+/// SSH jump host the profile's traffic is tunnelled through when enabled.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunnelSettings {
+    pub enabled: bool,
+    pub host: String,
+    /// Defaults to 22.
+    pub port: Option<u16>,
+    pub user: String,
+    pub auth: TunnelAuth,
+    /// Pinned host key (`SHA256:...`); without one the key must already be
+    /// in `~/.ssh/known_hosts`.
+    pub host_key_fingerprint: Option<String>,
+}
+
+/// How the shell authenticates to the jump host. Passwords live in the
+/// keyring, never in the settings file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelAuth {
+    #[default]
+    Agent,
+    Password,
+}
+
+/// Outbound HTTP proxy for the profile's core traffic.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// `http://host:port`; empty connects directly.
+    pub url: String,
+    pub auth: ProxyAuth,
+    /// NTLM account, `DOMAIN\user` or `user@domain`.
+    pub username: String,
+}
+
+/// Proxy credentials. `negotiate` sends a Kerberos ticket for `HTTP/<proxy
+/// host>` from the user's login session (GSSAPI or SSPI). `ntlm` answers the
+/// proxy's NTLMv2 challenge as `username`, with the password saved by
+/// `set_proxy_password`, on every connection through it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyAuth {
+    #[default]
+    None,
+    Negotiate,
+    Ntlm,
+}
+
+/// How requests authenticate to the core. Secrets live in the keyring
+/// (`set_profile_credential`); only non-secret names are kept here.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoreAuthSettings {
+    pub mode: CoreAuth,
+    /// Header carrying the API key; defaults to `X-API-Key`.
+    pub header: Option<String>,
+    /// Basic auth user name.
+    pub username: String,
+    /// AWS region the core's endpoint is signed for.
+    pub region: String,
+    /// AWS service name in the signing scope; defaults to `execute-api`.
+    pub service: String,
+    /// Named profile in the shared AWS credentials file; defaults to
+    /// `AWS_PROFILE`, then `default`.
+    pub aws_profile: String,
+    /// GCP identity token audience: the Cloud Run URL or the IAP OAuth
+    /// client id. Defaults to the core's origin.
+    pub audience: String,
+    /// Service account `gcloud` impersonates when the metadata server is
+    /// not reachable.
+    pub service_account: String,
+}
+
+/// `bearer` sends the profile token, `api_key` a static key in a custom
+/// header, `basic` a user name and password, and `negotiate` a Kerberos
+/// ticket for `HTTP/<core host>`. `aws_sigv4` signs each request with the
+/// AWS credentials found in the environment, and `gcp_identity` sends a
+/// Google-signed identity token, in `Authorization` or, when `header` names
+/// `Proxy-Authorization` (IAP), alongside the profile token.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreAuth {
+    #[default]
+    Bearer,
+    ApiKey,
+    Basic,
+    Negotiate,
+    AwsSigv4,
+    GcpIdentity,
+}
+
+impl CoreAuth {
+    /// The keyring entry holding this mode's secret, if it has one.
+    pub fn secret_key(self, profile_id: &str) -> Option<String> {
+        match self {
+            CoreAuth::Bearer => Some(secrets::profile_token_key(profile_id)),
+            CoreAuth::ApiKey => Some(format!("profile:{}:api_key", profile_id)),
+            CoreAuth::Basic => Some(format!("profile:{}:basic_password", profile_id)),
+            CoreAuth::Negotiate | CoreAuth::AwsSigv4 | CoreAuth::GcpIdentity => None,
+        }
+    }
+}
+
+/// Resolved endpoint and credential for talking to one profile's core.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    pub profile_id: String,
+    pub base_url: String,
+    pub token: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            active_profile: DEFAULT_PROFILE_ID.to_string(),
+            profiles: vec![Profile::default()],
+            notifications: NotificationSettings::default(),
+            desktop: DesktopSettings::default(),
+            attachments: AttachmentSettings::default(),
+            clipboard: ClipboardSettings::default(),
+            telemetry: TelemetrySettings::default(),
+            retention: RetentionSettings::default(),
+            watchdog: WatchdogSettings::default(),
+            polling: PollingSettings::default(),
+            plan_output: PlanOutputSettings::default(),
+            stall_detection: StallSettings::default(),
+            chat_webhooks: Vec::new(),
+            saved_views: Vec::new(),
+            quick_actions: Vec::new(),
+            plugins: Vec::new(),
+            plugin_registry: PluginRegistrySettings::default(),
+            announcements: AnnouncementSettings::default(),
+            airgap: AirgapSettings::default(),
+            allowed_base_urls: Vec::new(),
+            request_targets: RequestTargetSettings::default(),
+            formatting: FormattingSettings::default(),
+            data_residency: DataResidencySettings::default(),
+            bandwidth: BandwidthSettings::default(),
+            webhook_receiver: WebhookReceiverSettings::default(),
+            status_endpoint: StatusEndpointSettings::default(),
+            macro_pad: MacroPadSettings::default(),
+            availability: AvailabilitySettings::default(),
+            reason_templates: Vec::new(),
+            translation: TranslationSettings::default(),
+            ui: Map::new(),
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            id: DEFAULT_PROFILE_ID.to_string(),
+            name: "Default".to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            api_prefix: None,
+            environment: EnvironmentClass::default(),
+            budget: BudgetSettings::default(),
+            network: NetworkSettings::default(),
+            tunnel: TunnelSettings::default(),
+            proxy: ProxySettings::default(),
+            core_auth: CoreAuthSettings::default(),
+            token_warning_minutes: None,
+            read_only: false,
+            auto_approval_paused: false,
+            auto_approval: AutoApprovalSettings::default(),
+            notifications: ProfileNotificationSettings::default(),
+            execution_windows: Vec::new(),
+            calendars: Vec::new(),
+            two_person: TwoPersonSettings::default(),
+            justification: JustificationPolicy::default(),
+            vault: VaultSettings::default(),
+            ticketing: TicketingSettings::default(),
+            device_login: DeviceLoginSettings::default(),
+            streaming: StreamingSettings::default(),
+            core_signing: CoreSigningSettings::default(),
+            replicas: ReplicaSettings::default(),
+            read_routing: ReadRoutingSettings::default(),
+            cookie_jar: false,
+            redirects: RedirectSettings::default(),
+            tls: TlsSettings::default(),
+            core_time_zone: String::new(),
+            tenant: TenantSettings::default(),
+        }
+    }
+}
+
+impl Profile {
+    /// `base_url` followed by the profile's replica URLs.
+    pub fn endpoints(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.base_url.as_str()).chain(self.replicas.urls.iter().map(String::as_str))
+    }
+}
+
+impl Settings {
+    pub fn profile(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.id == id)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.profiles.is_empty() {
+            return Err("At least one profile is required".to_string());
+        }
+        let mut seen = std::collections::HashSet::new();
+        for profile in &self.profiles {
+            if profile.id.trim().is_empty() {
+                return Err("Profile id is required".to_string());
+            }
+            if !seen.insert(profile.id.as_str()) {
+                return Err(format!("Duplicate profile id: {}", profile.id));
+            }
+            validate_base_url(&profile.base_url)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            net::validate(&profile.network)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            net::validate_auth(&profile.proxy, &profile.core_auth)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            vault::validate(&profile.vault)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            ticketing::validate(&profile.ticketing)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            device_login::validate(&profile.device_login)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            exec_windows::validate(&profile.execution_windows)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            calendar::validate(&profile.calendars)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            notify::validate_profile(&profile.notifications)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            auto_approval::validate(profile)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            two_person::validate(&profile.two_person)
+                .map_err(|e| format!("Profile {}: {}", profile.id, errors::detail_of(&e)))?;
+            justifications::validate_policy(&profile.justification)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            live::validate(&profile.streaming)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            core_signing::validate(&profile.core_signing)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            ssrf::validate_redirects(&profile.redirects)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            tls::validate(&profile.tls).map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            replicas::validate(profile).map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            routing::validate(profile).map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            tenants::validate(&profile.tenant)
+                .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            if let Some(prefix) = &profile.api_prefix {
+                api_prefix::validate(prefix)
+                    .map_err(|e| format!("Profile {}: {}", profile.id, e))?;
+            }
+            if !profile.core_time_zone.trim().is_empty() {
+                formatting::zone(&profile.core_time_zone)
+                    .map_err(|e| format!("Profile {}: core_time_zone: {}", profile.id, e))?;
+            }
+            if profile.tunnel.enabled
+                && (profile.tunnel.host.trim().is_empty() || profile.tunnel.user.trim().is_empty())
+            {
+                return Err(format!(
+                    "Profile {}: SSH tunnel needs a jump host and user",
+                    profile.id
+                ));
+            }
+        }
+        if self.attachments.max_bytes == 0 {
+            return Err("Attachment size limit must be above zero".to_string());
+        }
+        telemetry::validate(&self.telemetry)?;
+        retention::validate(&self.retention)?;
+        watchdog::validate(&self.watchdog)?;
+        state::validate(&self.polling)?;
+        plan_output::validate(&self.plan_output)?;
+        stall::validate(&self.stall_detection)?;
+        chat_webhooks::validate(&self.chat_webhooks, &self.profiles)?;
+        views::validate(&self.saved_views, &self.profiles)?;
+        quick_actions::validate(&self.quick_actions, &self.profiles)?;
+        justifications::validate_templates(&self.reason_templates)?;
+        translation::validate(&self.translation)?;
+        plugins::validate(&self.plugins)?;
+        plugin_registry::validate(&self.plugin_registry)?;
+        announcements::validate(&self.announcements)?;
+        clipboard::validate(&self.clipboard)?;
+        airgap::validate(&self.airgap)?;
+        allowlist::validate(&self.allowed_base_urls, &self.profiles)?;
+        ssrf::validate(&self.request_targets)?;
+        formatting::validate(&self.formatting)?;
+        residency::validate(&self.data_residency)?;
+        webhook_receiver::validate(&self.webhook_receiver, &self.profiles)?;
+        status_endpoint::validate(&self.status_endpoint, &self.webhook_receiver)?;
+        macro_pad::validate(
+            &self.macro_pad,
+            &self.webhook_receiver,
+            &self.status_endpoint,
+        )?;
+        availability::validate(&self.availability)?;
+        let quiet = &self.notifications.quiet_hours;
+        quiet::parse_time(&quiet.start)?;
+        quiet::parse_time(&quiet.end)?;
+        digest::validate(&self.notifications.digest)?;
+        if self.profile(&self.active_profile).is_none() {
+            return Err(format!(
+                "Active profile does not exist: {}",
+                self.active_profile
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_base_url(raw: &str) -> Result<(), String> {
+    let parsed = Url::parse(raw.trim()).map_err(|e| format!("Invalid base URL: {}", e))?;
+    if !transport::supports(parsed.scheme()) {
+        return Err(format!(
+            "Base URLs must be http, https, {} or {}",
+            transport::UNIX,
+            transport::MOCK
+        ));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err("Base URL needs a host".to_string());
+    }
+    if matches!(parsed.scheme(), transport::GRPC | transport::GRPCS) && parsed.port().is_none() {
+        return Err("gRPC base URLs need a port, e.g. grpc://core.local:50051".to_string());
+    }
+    Ok(())
+}
+
+/// Encrypted-at-rest settings file. The AES key is generated on first run and
+/// kept in the OS keyring; if the keyring is unavailable the store degrades to
+/// memory-only so the shell still starts.
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    key: Option<[u8; crypto::KEY_LEN]>,
+    current: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn open(app: &AppHandle) -> Self {
+        let store = match Self::open_persistent(app) {
+            Ok(store) => store,
+            Err(err) => {
+                eprintln!(
+                    "settings store unavailable, using in-memory settings: {}",
+                    err
+                );
+                Self {
+                    path: None,
+                    key: None,
+                    current: Mutex::new(managed::apply(Settings::default())),
+                }
+            }
+        };
+        let profiles = store.snapshot().profiles;
+        net::configure(&profiles);
+        cookies::configure(&profiles);
+        permissions::configure(&profiles, &store.snapshot().active_profile);
+        environment::configure(&profiles, &store.snapshot().active_profile);
+        exec_windows::configure(&profiles);
+        calendar::configure(&profiles);
+        two_person::configure(&profiles);
+        justifications::configure(&profiles, &store.snapshot().reason_templates);
+        vault::configure(&profiles);
+        ticketing::configure(&profiles);
+        replicas::configure(&profiles);
+        routing::configure(&profiles);
+        tenants::configure(&profiles);
+        api_prefix::configure(&profiles);
+        bandwidth::configure(&profiles, &store.snapshot().bandwidth);
+        telemetry::configure(&store.snapshot().telemetry);
+        allowlist::configure(&store.snapshot().allowed_base_urls);
+        ssrf::configure(&store.snapshot().request_targets);
+        residency::configure(&store.snapshot().data_residency);
+        watchdog::configure(&store.snapshot().watchdog);
+        store
+    }
+
+    /// Default settings held only in memory, for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            key: None,
+            current: Mutex::new(Settings::default()),
+        }
+    }
+
+    fn open_persistent(app: &AppHandle) -> Result<Self, String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("App data dir unavailable: {}", e))?;
+        fs::create_dir_all(&dir).map_err(|e| format!("Create app data dir failed: {}", e))?;
+        let key = match secrets::get(SETTINGS_KEY_NAME)? {
+            Some(encoded) => crypto::decode_key(&encoded)?,
+            None => {
+                let key = crypto::generate_key();
+                secrets::set(SETTINGS_KEY_NAME, &crypto::encode_key(&key))?;
+                key
+            }
+        };
+        let path = dir.join(SETTINGS_FILE);
+        let settings = if path.exists() {
+            let raw = fs::read(&path).map_err(|e| format!("Read settings failed: {}", e))?;
+            let envelope: Value = serde_json::from_slice(&raw)
+                .map_err(|e| format!("Settings file is corrupted: {}", e))?;
+            let plaintext = crypto::open(&key, SETTINGS_FORMAT, &envelope)?;
+            let value: Value = serde_json::from_slice(&plaintext)
+                .map_err(|e| format!("Settings payload is corrupted: {}", e))?;
+            migrate(value)?
+        } else {
+            Settings::default()
+        };
+        Ok(Self {
+            path: Some(path),
+            key: Some(key),
+            current: Mutex::new(managed::apply(settings)),
+        })
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub fn snapshot(&self) -> Settings {
+        self.current.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Resolves `profile_id` (or the active profile) to its base URL (the
+    /// active replica, when it has replicas) and keyring token, or the
+    /// Vault-held credential when the profile uses Vault.
+    pub fn connection(&self, profile_id: Option<&str>) -> Result<Connection, String> {
+        let settings = self.snapshot();
+        let id = profile_id
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .unwrap_or(&settings.active_profile);
+        let profile = settings
+            .profile(id)
+            .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", id)))?;
+        let token = if profile.vault.enabled {
+            vault::credential(&profile.id)
+        } else {
+            secrets::get(&secrets::profile_token_key(&profile.id))?
+        };
+        Ok(Connection {
+            profile_id: profile.id.clone(),
+            base_url: replicas::active(profile),
+            token,
+        })
+    }
+
+    /// Applies `change` to a copy of the settings, validates and persists the
+    /// result, then broadcasts it as a `settings:changed` event. Profiles'
+    /// read-only flags are left as they were.
+    pub fn update<F>(&self, app: &AppHandle, change: F) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings) -> Result<(), String>,
+    {
+        self.apply(app, change, None, None)
+    }
+
+    /// `update` for `permissions::set_profile_read_only`, the one change
+    /// that may flip `profile_id`'s read-only flag.
+    pub fn update_read_only<F>(
+        &self,
+        app: &AppHandle,
+        profile_id: &str,
+        change: F,
+    ) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings) -> Result<(), String>,
+    {
+        self.apply(app, change, Some(profile_id), None)
+    }
+
+    /// `update` for `two_person::enroll_second_approver`, the one change
+    /// that may add second approvers to `profile_id`.
+    pub fn update_enrollment<F>(
+        &self,
+        app: &AppHandle,
+        profile_id: &str,
+        change: F,
+    ) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings) -> Result<(), String>,
+    {
+        self.apply(app, change, None, Some(profile_id))
+    }
+
+    fn apply<F>(
+        &self,
+        app: &AppHandle,
+        change: F,
+        unlocking: Option<&str>,
+        enrolling: Option<&str>,
+    ) -> Result<Settings, String>
+    where
+        F: FnOnce(&mut Settings) -> Result<(), String>,
+    {
+        let mut guard = self
+            .current
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Settings state poisoned"))?;
+        let mut next = guard.clone();
+        change(&mut next)?;
+        api_prefix::forget_moved(&guard.profiles, &mut next.profiles);
+        let dropped = two_person::keep_enrollment(&guard.profiles, &mut next.profiles, enrolling)?;
+        permissions::keep_read_only(&guard, &mut next, unlocking);
+        managed::enforce(&guard, &mut next)?;
+        next.schema_version = SCHEMA_VERSION;
+        next.validate()
+            .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+        self.persist(&next)
+            .map_err(|e| ErrorCode::LocalStorage.with(e))?;
+        net::configure(&next.profiles);
+        cookies::configure(&next.profiles);
+        permissions::configure(&next.profiles, &next.active_profile);
+        environment::configure(&next.profiles, &next.active_profile);
+        exec_windows::configure(&next.profiles);
+        calendar::configure(&next.profiles);
+        two_person::configure(&next.profiles);
+        justifications::configure(&next.profiles, &next.reason_templates);
+        vault::configure(&next.profiles);
+        ticketing::configure(&next.profiles);
+        replicas::configure(&next.profiles);
+        routing::configure(&next.profiles);
+        tenants::configure(&next.profiles);
+        api_prefix::configure(&next.profiles);
+        bandwidth::configure(&next.profiles, &next.bandwidth);
+        telemetry::configure(&next.telemetry);
+        allowlist::configure(&next.allowed_base_urls);
+        ssrf::configure(&next.request_targets);
+        residency::configure(&next.data_residency);
+        watchdog::configure(&next.watchdog);
+        *guard = next.clone();
+        drop(guard);
+        for key in &dropped {
+            let _ = secrets::delete(key);
+        }
+        tray::set_quick_actions(app, &next.quick_actions);
+        let _ = environment::emit(app, CHANGED_EVENT, &next);
+        Ok(next)
+    }
+
+    /// Forgets every setting: the file and its key are deleted and the
+    /// defaults take over in memory. The store's key is gone from the
+    /// keyring after this, so the shell must restart before settings are
+    /// written again.
+    pub fn wipe(&self) -> Result<(), String> {
+        let mut guard = self
+            .current
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Settings state poisoned"))?;
+        *guard = managed::apply(Settings::default());
+        if let Some(path) = &self.path {
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(
+                        ErrorCode::LocalIo.with(format!("Delete settings file failed: {}", e))
+                    )
+                }
+            }
+        }
+        secrets::delete(SETTINGS_KEY_NAME)
+    }
+
+    fn persist(&self, settings: &Settings) -> Result<(), String> {
+        let (Some(path), Some(key)) = (&self.path, &self.key) else {
+            return Ok(());
+        };
+        let plaintext =
+            serde_json::to_vec(settings).map_err(|e| format!("Encode settings failed: {}", e))?;
+        let envelope = crypto::seal(key, SETTINGS_FORMAT, &plaintext)?;
+        let tmp = path.with_extension("enc.tmp");
+        fs::write(&tmp, envelope.to_string())
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| format!("Write settings failed: {}", e))
+    }
+}
+
+/// Upgrades a decoded settings document to the current schema version.
+pub fn migrate(mut raw: Value) -> Result<Settings, String> {
+    let version = raw["schema_version"].as_u64().unwrap_or(0) as u32;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Settings were written by a newer shell (schema {} > {})",
+            version, SCHEMA_VERSION
+        ));
+    }
+    // v0 documents predate the version marker but share the v1 shape.
+    raw["schema_version"] = json!(SCHEMA_VERSION);
+    serde_json::from_value(raw).map_err(|e| format!("Settings schema mismatch: {}", e))
+}
+
+/// RFC 7386 JSON merge patch: objects merge recursively, `null` removes keys.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target_map) = target {
+        for (key, value) in patch_map {
+            if value.is_null() {
+                target_map.remove(key);
+            } else {
+                merge_patch(target_map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_settings(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let mut out = serde_json::to_value(store.snapshot()).map_err(|e| e.to_string())?;
+    out["persistent"] = json!(store.is_persistent());
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    patch: Value,
+) -> Result<Settings, String> {
+    store.update(&app, |settings| {
+        let mut doc = serde_json::to_value(&*settings).map_err(|e| e.to_string())?;
+        merge_patch(&mut doc, &patch);
+        *settings = serde_json::from_value(doc).map_err(|e| format!("Invalid settings: {}", e))?;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn upsert_profile(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Profile,
+) -> Result<Settings, String> {
+    store.update(&app, |settings| {
+        match settings.profiles.iter_mut().find(|p| p.id == profile.id) {
+            Some(existing) => *existing = profile,
+            None => settings.profiles.push(profile),
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn delete_profile(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+) -> Result<Settings, String> {
+    let approvers: Vec<String> = store
+        .snapshot()
+        .profile(&profile_id)
+        .map(|p| {
+            p.two_person
+                .approvers
+                .iter()
+                .map(|a| a.id.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let calendars: Vec<String> = store
+        .snapshot()
+        .profile(&profile_id)
+        .map(|p| p.calendars.iter().map(|c| c.id.clone()).collect())
+        .unwrap_or_default();
+    let updated = store.update(&app, |settings| {
+        settings.profiles.retain(|p| p.id != profile_id);
+        for view in &mut settings.saved_views {
+            if view.profile.as_deref() == Some(profile_id.as_str()) {
+                view.profile = None;
+            }
+        }
+        // A pinned action could otherwise end up sent to another core.
+        settings
+            .quick_actions
+            .retain(|a| a.profile.as_deref() != Some(profile_id.as_str()));
+        if settings.active_profile == profile_id {
+            if let Some(first) = settings.profiles.first() {
+                settings.active_profile = first.id.clone();
+            }
+        }
+        Ok(())
+    })?;
+    for mode in [CoreAuth::Bearer, CoreAuth::ApiKey, CoreAuth::Basic] {
+        if let Some(key) = mode.secret_key(&profile_id) {
+            secrets::delete(&key)?;
+        }
+    }
+    secrets::delete(&secrets::profile_ssh_password_key(&profile_id))?;
+    secrets::delete(&secrets::profile_proxy_password_key(&profile_id))?;
+    secrets::delete(&ticketing::ticketing_secret_key(&profile_id))?;
+    secrets::delete(&core_signing::secret_key(&profile_id))?;
+    secrets::delete(&permissions::unlock_key(&profile_id))?;
+    for approver in approvers {
+        secrets::delete(&two_person::approver_secret_key(&profile_id, &approver))?;
+    }
+    for source in calendars {
+        secrets::delete(&calendar::calendar_secret_key(&profile_id, &source))?;
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn set_profile_token(profile_id: String, token: Option<String>) -> Result<Value, String> {
+    let key = secrets::profile_token_key(&profile_id);
+    let token = token.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    match token {
+        Some(value) => secrets::set(&key, value)?,
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": token.is_some() }))
+}
+
+/// Stores (or with `None`, clears) the core credential for one auth mode:
+/// the bearer token, API key, or Basic password.
+#[tauri::command]
+pub fn set_profile_credential(
+    profile_id: String,
+    mode: CoreAuth,
+    secret: Option<String>,
+) -> Result<Value, String> {
+    let key = mode.secret_key(&profile_id).ok_or_else(|| {
+        ErrorCode::InvalidInput.with(format!(
+            "{} auth uses ambient credentials and stores no secret",
+            json!(mode)
+        ))
+    })?;
+    let secret = secret.filter(|v| !v.trim().is_empty());
+    match secret.as_deref() {
+        Some(value) => secrets::set(&key, value)?,
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "profile_id": profile_id, "mode": mode, "stored": secret.is_some() }))
+}
+
+/// Stores (or with `None`, clears) the password for a profile's SSH tunnel.
+#[tauri::command]
+pub fn set_tunnel_password(profile_id: String, password: Option<String>) -> Result<Value, String> {
+    let key = secrets::profile_ssh_password_key(&profile_id);
+    let password = password.filter(|p| !p.is_empty());
+    match password.as_deref() {
+        Some(value) => secrets::set(&key, value)?,
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": password.is_some() }))
+}
+
+/// Stores (or with `None`, clears) the password for a profile's NTLM proxy.
+#[tauri::command]
+pub fn set_proxy_password(profile_id: String, password: Option<String>) -> Result<Value, String> {
+    let key = secrets::profile_proxy_password_key(&profile_id);
+    let password = password.filter(|p| !p.is_empty());
+    match password.as_deref() {
+        Some(value) => secrets::set(&key, value)?,
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": password.is_some() }))
+}
+
+#[tauri::command]
+pub fn get_profile_token(profile_id: String) -> Result<Option<String>, String> {
+    secrets::get(&secrets::profile_token_key(&profile_id))
+}
@@ -0,0 +1,127 @@
+use std::fs;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{self, Settings, SettingsStore};
+use crate::{crypto, residency, secrets};
+
+const BUNDLE_FORMAT: &str = "novaadapt-settings-bundle";
+const BUNDLE_VERSION: u64 = 1;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Writes the full shell configuration to a passphrase-encrypted bundle.
+///
+/// Secrets are never exported: the bundle only lists which keyring entries
+/// each profile expects so they can be re-entered after import.
+#[tauri::command]
+pub fn export_settings_bundle(
+    store: State<'_, SettingsStore>,
+    path: String,
+    passphrase: String,
+) -> Result<Value, String> {
+    check_passphrase(&passphrase)?;
+    let target = residency::check("The settings bundle", &path)?;
+    let snapshot = store.snapshot();
+    let secret_refs: Vec<Value> = snapshot
+        .profiles
+        .iter()
+        .map(|p| {
+            let key = secrets::profile_token_key(&p.id);
+            let present = secrets::get(&key).ok().flatten().is_some();
+            json!({ "profile_id": p.id, "secret": key, "present": present })
+        })
+        .collect();
+    let payload = json!({
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "settings": snapshot,
+        "secret_refs": secret_refs,
+    });
+
+    let plaintext = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
+    let mut envelope = crypto::seal_with_passphrase(&passphrase, BUNDLE_FORMAT, &plaintext)?;
+    envelope["version"] = json!(BUNDLE_VERSION);
+    fs::write(&target, envelope.to_string())
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Write bundle failed: {}", e)))?;
+
+    Ok(json!({
+        "path": path.trim(),
+        "profiles": snapshot.profiles.len(),
+        "secret_refs": payload["secret_refs"],
+    }))
+}
+
+/// Loads a bundle produced by `export_settings_bundle`.
+///
+/// `mode` is `merge` (default: bundle profiles replace same-id profiles, UI
+/// preferences are overlaid) or `replace` (bundle settings win outright).
+#[tauri::command]
+pub fn import_settings_bundle(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    path: String,
+    passphrase: String,
+    mode: Option<String>,
+) -> Result<Value, String> {
+    let raw = fs::read(path.trim())
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read bundle failed: {}", e)))?;
+    let envelope: Value = serde_json::from_slice(&raw)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Bundle is not valid JSON: {}", e)))?;
+    if envelope["version"].as_u64().unwrap_or(0) > BUNDLE_VERSION {
+        return Err(ErrorCode::InvalidInput.with("Bundle was created by a newer shell"));
+    }
+    let plaintext = crypto::open_with_passphrase(&passphrase, BUNDLE_FORMAT, &envelope)
+        .map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    let payload: Value = serde_json::from_slice(&plaintext)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Bundle payload is corrupted: {}", e)))?;
+    let imported = settings::migrate(payload["settings"].clone())?;
+
+    let replace = mode.as_deref().map(str::trim) == Some("replace");
+    let updated = store.update(&app, |current| {
+        if replace {
+            *current = imported;
+        } else {
+            merge_into(current, imported);
+        }
+        Ok(())
+    })?;
+
+    let missing_secrets: Vec<Value> = payload["secret_refs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["present"].as_bool() == Some(true))
+        .filter(|r| {
+            let key = r["secret"].as_str().unwrap_or_default();
+            secrets::get(key).ok().flatten().is_none()
+        })
+        .cloned()
+        .collect();
+
+    Ok(json!({
+        "mode": if replace { "replace" } else { "merge" },
+        "profiles": updated.profiles.len(),
+        "missing_secrets": missing_secrets,
+    }))
+}
+
+fn merge_into(current: &mut Settings, imported: Settings) {
+    for profile in imported.profiles {
+        match current.profiles.iter_mut().find(|p| p.id == profile.id) {
+            Some(existing) => *existing = profile,
+            None => current.profiles.push(profile),
+        }
+    }
+    current.ui.extend(imported.ui);
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Bundle passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,475 @@
+use std::collections::HashSet;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{self, Profile, SettingsStore};
+use crate::{allowlist, api_prefix, environment, jwt, net, secrets, ssrf};
+
+const PROGRESS_EVENT: &str = "setup:progress";
+/// Where a core is mounted behind a reverse proxy, most common first.
+const API_PREFIXES: &[&str] = &["", "/api", "/api/v1", "/v1", "/novaadapt"];
+/// Reply headers a gateway names the path it mounts the core under with.
+const PREFIX_HINT_HEADERS: &[&str] = &["x-novaadapt-api-prefix", "x-forwarded-prefix"];
+/// A route every core serves that needs the token when auth is on.
+const AUTH_PROBE_PATH: &str = "/models";
+/// Scopes the desktop needs for everything it offers; `admin` implies all.
+const WANTED_SCOPES: &[&str] = &["read", "plan", "approve", "reject", "undo", "cancel"];
+const ADMIN_SCOPE: &str = "admin";
+
+/// Steps of one setup run, reported as `setup:progress` events and in the
+/// final result.
+struct Progress<'a> {
+    app: &'a AppHandle,
+    steps: Vec<Value>,
+}
+
+impl Progress<'_> {
+    fn start(&self, step: &str, detail: &str) {
+        let _ = environment::emit(
+            self.app,
+            PROGRESS_EVENT,
+            json!({ "step": step, "status": "running", "detail": detail }),
+        );
+    }
+
+    fn finish(&mut self, step: &str, status: &str, detail: impl Into<String>, data: Value) {
+        let entry = json!({
+            "step": step,
+            "status": status,
+            "detail": detail.into(),
+            "data": data,
+        });
+        let _ = environment::emit(self.app, PROGRESS_EVENT, &entry);
+        self.steps.push(entry);
+    }
+
+    fn result(self, ok: bool, extra: Value) -> Value {
+        let mut out = json!({ "ok": ok, "steps": self.steps });
+        if let (Some(out), Value::Object(extra)) = (out.as_object_mut(), extra) {
+            out.extend(extra);
+        }
+        out
+    }
+}
+
+/// A pasted address as a URL: `http://` is assumed when no scheme is given,
+/// and a trailing `/health` or `/` is dropped.
+fn normalize(raw: &str) -> Result<Url, String> {
+    let raw = raw.trim();
+    let with_scheme = if raw.contains("://") {
+        raw.to_string()
+    } else {
+        format!("http://{}", raw)
+    };
+    let trimmed = with_scheme.trim_end_matches('/');
+    let trimmed = trimmed.strip_suffix("/health").unwrap_or(trimmed);
+    settings::validate_base_url(trimmed).map_err(|e| ErrorCode::InvalidInput.with(e))?;
+    allowlist::check(trimmed)?;
+    let url = Url::parse(trimmed)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    ssrf::check_url(trimmed, &url)?;
+    Ok(url)
+}
+
+async fn get(url: &str, token: Option<&str>) -> Result<reqwest::Response, String> {
+    let parsed =
+        Url::parse(url).map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    let mut req = net::client_for(&parsed)?.get(parsed);
+    if let Some(token) = token {
+        req = req.bearer_auth(token);
+    }
+    req.send().await.map_err(|e| {
+        if let Some(reason) = ssrf::blocked_in(&e) {
+            return ErrorCode::RequestTargetBlocked.with(reason);
+        }
+        let code = if e.is_timeout() {
+            ErrorCode::CoreTimeout
+        } else {
+            ErrorCode::CoreUnreachable
+        };
+        code.with(format!("{}: {}", url, e))
+    })
+}
+
+/// Where the core answered: the address it is reached through and the
+/// path its API is mounted under there.
+pub struct FoundCore {
+    pub root: String,
+    pub prefix: String,
+    /// `probe` (one of the common prefixes, or none), `hint` (named by a
+    /// gateway header) or `redirect` (a redirect added to the path).
+    pub source: &'static str,
+}
+
+impl FoundCore {
+    pub fn base_url(&self) -> String {
+        format!("{}{}", self.root, self.prefix)
+    }
+}
+
+/// The prefix a gateway names in its reply, if it names a usable one.
+fn hinted_prefix(headers: &HeaderMap) -> Option<String> {
+    PREFIX_HINT_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .map(|hint| hint.trim().trim_end_matches('/'))
+        .find(|hint| !hint.is_empty() && api_prefix::validate(hint).is_ok())
+        .map(str::to_string)
+}
+
+/// The core behind `url`: `/health` is asked for under each prefix a
+/// gateway hints at in its replies, then under the common ones, until one
+/// answers as NovaAdapt. Redirects (to HTTPS, a moved host, or the path a
+/// gateway mounts the core at) are followed and kept; one that stays under
+/// `url` becomes part of the prefix.
+pub async fn find_core(url: &Url) -> Result<FoundCore, String> {
+    let root = url.as_str().trim_end_matches('/');
+    let mut candidates: Vec<(String, &'static str)> = API_PREFIXES
+        .iter()
+        .map(|prefix| (prefix.to_string(), "probe"))
+        .collect();
+    let mut tried = HashSet::new();
+    let mut last_err = None;
+    let mut next = 0;
+    while let Some((prefix, source)) = candidates.get(next).cloned() {
+        next += 1;
+        if !tried.insert(prefix.clone()) {
+            continue;
+        }
+        // Nothing listening there is final; other prefixes will not help.
+        let response = get(&format!("{}{}/health", root, prefix), None).await?;
+        if let Some(hint) = hinted_prefix(response.headers()) {
+            if !tried.contains(&hint) {
+                candidates.insert(next, (hint, "hint"));
+            }
+        }
+        let final_url = response.url().as_str().to_string();
+        if !response.status().is_success() {
+            last_err = Some(format!(
+                "{}{}/health: HTTP {}",
+                root,
+                prefix,
+                response.status()
+            ));
+            continue;
+        }
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        let is_core = body["service"]
+            .as_str()
+            .is_some_and(|s| s.starts_with("novaadapt"));
+        if !is_core {
+            last_err = Some(format!(
+                "{}{}/health did not answer as a NovaAdapt core",
+                root, prefix
+            ));
+            continue;
+        }
+        let base = final_url.split('?').next().unwrap_or_default();
+        let base = base.trim_end_matches('/');
+        let base = base.strip_suffix("/health").unwrap_or(base);
+        let (found_root, found_prefix) = match base.strip_prefix(root) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => (root, rest),
+            _ => match base.strip_suffix(prefix.as_str()) {
+                Some(moved) if !prefix.is_empty() => (moved, prefix.as_str()),
+                _ => (base, ""),
+            },
+        };
+        return Ok(FoundCore {
+            root: found_root.to_string(),
+            prefix: found_prefix.to_string(),
+            source: if found_prefix == prefix {
+                source
+            } else {
+                "redirect"
+            },
+        });
+    }
+    Err(ErrorCode::NotFound
+        .with(last_err.unwrap_or_else(|| "No NovaAdapt core found at that address".to_string())))
+}
+
+/// Whether the core wants a token, and whether `token` is accepted.
+async fn check_token(base: &str, token: Option<&str>) -> Result<Value, String> {
+    let url = format!("{}{}", base, AUTH_PROBE_PATH);
+    let anonymous = get(&url, None).await?.status();
+    let required = matches!(anonymous, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN);
+    let Some(token) = token else {
+        if required {
+            return Err(ErrorCode::AuthExpired.with("This core needs an API token"));
+        }
+        return Ok(json!({ "required": false }));
+    };
+    let status = get(&url, Some(token)).await?.status();
+    if !status.is_success() {
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "The core refused the token (HTTP {})",
+            status.as_u16()
+        )));
+    }
+    Ok(json!({ "required": required, "accepted": true }))
+}
+
+/// The token's scopes and which of the desktop's are missing; an opaque
+/// token is the core's own API token and can do everything.
+fn scope_report(token: &str) -> (Vec<String>, Vec<&'static str>, bool) {
+    let Some(claims) = jwt::decode_claims(token) else {
+        return (Vec::new(), Vec::new(), false);
+    };
+    let scopes = jwt::scopes(&claims);
+    let missing = if scopes.is_empty() || scopes.iter().any(|s| s == ADMIN_SCOPE) {
+        Vec::new()
+    } else {
+        WANTED_SCOPES
+            .iter()
+            .filter(|wanted| !scopes.iter().any(|s| s == *wanted))
+            .copied()
+            .collect()
+    };
+    (scopes, missing, true)
+}
+
+fn profile_id_for(name: &str, taken: &[Profile]) -> String {
+    let mut slug = String::new();
+    for c in name.trim().to_ascii_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    let slug = match slug.trim_end_matches('-') {
+        "" => "core".to_string(),
+        s => s.to_string(),
+    };
+    let mut id = slug.clone();
+    let mut n = 2;
+    while taken.iter().any(|p| p.id == id) {
+        id = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    id
+}
+
+/// Saves the profile and makes it active. On a first run the untouched
+/// default profile is replaced rather than kept beside it.
+fn create_profile(
+    app: &AppHandle,
+    store: &SettingsStore,
+    name: &str,
+    found: &FoundCore,
+    token: Option<&str>,
+) -> Result<String, String> {
+    let mut created = String::new();
+    store.update(app, |settings| {
+        // The default profile's prefix may have been detected already.
+        let untouched = settings.profiles.first().map(|p| Profile {
+            api_prefix: p.api_prefix.clone(),
+            ..Profile::default()
+        });
+        let pristine = settings.profiles.len() == 1
+            && serde_json::to_value(&settings.profiles[0]).ok()
+                == serde_json::to_value(untouched).ok();
+        if pristine {
+            settings.profiles.clear();
+        }
+        let id = profile_id_for(name, &settings.profiles);
+        settings.profiles.push(Profile {
+            id: id.clone(),
+            name: name.to_string(),
+            base_url: found.root.clone(),
+            api_prefix: Some(found.prefix.clone()),
+            ..Profile::default()
+        });
+        settings.active_profile = id.clone();
+        created = id;
+        Ok(())
+    })?;
+    if let Some(token) = token {
+        secrets::set(&secrets::profile_token_key(&created), token)?;
+    }
+    Ok(created)
+}
+
+/// Looks for a NovaAdapt core at a pasted address, for the first page of
+/// the setup wizard.
+#[tauri::command]
+pub async fn probe_core_url(url: String) -> Result<Value, String> {
+    let parsed = normalize(&url)?;
+    let found = find_core(&parsed).await?;
+    Ok(json!({
+        "base_url": found.root,
+        "api_prefix": found.prefix,
+        "source": found.source,
+        "redirected": found.root != parsed.as_str().trim_end_matches('/'),
+    }))
+}
+
+/// Runs the first-run setup: finds the core at `url`, checks `token` and
+/// its scopes, creates the profile and makes it active, then runs a smoke
+/// test through it. Each step is emitted as `setup:progress` while it runs
+/// and when it ends; the result lists them all. A failed step ends the run,
+/// and no profile is created unless the core and token check out.
+#[tauri::command]
+pub async fn run_first_run_setup(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    url: String,
+    token: Option<String>,
+    name: Option<String>,
+) -> Result<Value, String> {
+    let token = token
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string);
+    let mut progress = Progress {
+        app: &app,
+        steps: Vec::new(),
+    };
+
+    progress.start("probe", "Looking for the core");
+    let found = match normalize(&url) {
+        Ok(parsed) => find_core(&parsed).await,
+        Err(err) => Err(err),
+    };
+    let found = match found {
+        Ok(found) => found,
+        Err(err) => {
+            progress.finish("probe", "failed", err, Value::Null);
+            return Ok(progress.result(false, Value::Null));
+        }
+    };
+    progress.finish(
+        "probe",
+        "ok",
+        format!("Found a core at {}", found.base_url()),
+        json!({ "base_url": found.root, "api_prefix": found.prefix, "source": found.source }),
+    );
+    let base_url = found.root.clone();
+
+    progress.start("token", "Checking the token");
+    match check_token(&found.base_url(), token.as_deref()).await {
+        Ok(data) => {
+            let detail = if data["required"] == json!(false) {
+                "The core does not require a token"
+            } else {
+                "The core accepted the token"
+            };
+            progress.finish("token", "ok", detail, data);
+        }
+        Err(err) => {
+            progress.finish("token", "failed", err, Value::Null);
+            return Ok(progress.result(false, json!({ "base_url": base_url })));
+        }
+    }
+
+    match token.as_deref() {
+        Some(token) => {
+            let (scopes, missing, is_jwt) = scope_report(token);
+            let (status, detail) = if !is_jwt {
+                (
+                    "ok",
+                    "An API token; it is not limited to scopes".to_string(),
+                )
+            } else if missing.is_empty() {
+                (
+                    "ok",
+                    "The token has every scope the desktop uses".to_string(),
+                )
+            } else {
+                (
+                    "warning",
+                    format!(
+                        "The token lacks {}; those actions will be refused",
+                        missing.join(", ")
+                    ),
+                )
+            };
+            progress.finish(
+                "scopes",
+                status,
+                detail,
+                json!({ "scopes": scopes, "missing": missing }),
+            );
+        }
+        None => progress.finish("scopes", "skipped", "No token given", Value::Null),
+    }
+
+    progress.start("profile", "Creating the profile");
+    let name = name
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .or_else(|| Url::parse(&base_url).ok()?.host_str().map(str::to_string))
+        .unwrap_or_else(|| "NovaAdapt".to_string());
+    let profile_id = match create_profile(&app, &store, &name, &found, token.as_deref()) {
+        Ok(id) => id,
+        Err(err) => {
+            progress.finish("profile", "failed", err, Value::Null);
+            return Ok(progress.result(false, json!({ "base_url": base_url })));
+        }
+    };
+    progress.finish(
+        "profile",
+        "ok",
+        format!("Created profile {}", name),
+        json!({ "profile_id": profile_id }),
+    );
+
+    progress.start("smoke_test", "Running a smoke test");
+    let conn = store.connection(Some(&profile_id))?;
+    let health = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/health?deep=1",
+        conn.token.clone(),
+        None,
+    )
+    .await;
+    let plans = crate::request_json(Method::GET, &conn.base_url, "/plans", conn.token, None).await;
+    let ok = match (&health, &plans) {
+        (Ok(health), Ok(_)) => {
+            let failing: Vec<&String> = health["checks"]
+                .as_object()
+                .map(|checks| {
+                    checks
+                        .iter()
+                        .filter(|(_, c)| c["ok"] == json!(false))
+                        .map(|(name, _)| name)
+                        .collect()
+                })
+                .unwrap_or_default();
+            if failing.is_empty() {
+                progress.finish("smoke_test", "ok", "The core is healthy", Value::Null);
+            } else {
+                progress.finish(
+                    "smoke_test",
+                    "warning",
+                    format!(
+                        "Connected, but the core reports failing checks: {}",
+                        failing
+                            .iter()
+                            .map(|s| s.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    health["checks"].clone(),
+                );
+            }
+            true
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            progress.finish("smoke_test", "failed", err.clone(), Value::Null);
+            false
+        }
+    };
+    Ok(progress.result(
+        ok,
+        json!({ "base_url": base_url, "api_prefix": found.prefix, "profile_id": profile_id }),
+    ))
+}
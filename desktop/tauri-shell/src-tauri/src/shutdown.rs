@@ -0,0 +1,216 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, CloseRequestApi, Manager, RunEvent};
+
+use crate::approval_schedule;
+use crate::db::{self, LocalDb};
+use crate::decisions::{self, ScheduledDecisions};
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+use crate::{environment, net, safe_mode, telemetry};
+
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+const DRAIN_POLL: Duration = Duration::from_millis(100);
+const DRAINING_EVENT: &str = "shutdown:draining";
+const CONFIRM_EVENT: &str = "shutdown:confirm";
+const MAIN_WINDOW: &str = "main";
+
+/// Set once quitting starts: new mutations are refused from then on.
+static DRAINING: AtomicBool = AtomicBool::new(false);
+/// Set once draining finished, so the final exit request goes through.
+static DRAINED: AtomicBool = AtomicBool::new(false);
+/// Set when the operator confirmed closing over work in flight.
+static CLOSE_CONFIRMED: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+tokio::task_local! {
+    /// Marks the drain task, whose own requests are let through.
+    static DRAINER: ();
+}
+
+/// Held for the duration of one mutating core request.
+pub struct Mutation(());
+
+impl Drop for Mutation {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a mutating request as in flight, or refuses it with
+/// `SHUTTING_DOWN` once the shell is draining. Reads pass untracked.
+pub fn begin(method: &Method) -> Result<Option<Mutation>, String> {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(None);
+    }
+    if DRAINING.load(Ordering::SeqCst) && DRAINER.try_with(|_| ()).is_err() {
+        return Err(ErrorCode::ShuttingDown.with("Not sending new changes while quitting"));
+    }
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    Ok(Some(Mutation(())))
+}
+
+/// Handles the app's exit request: the first one is held back while
+/// `drain` runs, which exits for real when done.
+pub fn on_run_event(app: &AppHandle, event: RunEvent) {
+    if let RunEvent::Exit = event {
+        safe_mode::relaunch(app);
+        return;
+    }
+    if let RunEvent::ExitRequested { api, code, .. } = event {
+        if DRAINED.load(Ordering::SeqCst) {
+            return;
+        }
+        api.prevent_exit();
+        if !DRAINING.swap(true, Ordering::SeqCst) {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let summary = DRAINER.scope((), drain(&app)).await;
+                if let Ok(conn) = app.state::<LocalDb>().lock() {
+                    let _ = db::record_audit(&conn, "", "shutdown", "drained", "", &summary);
+                    // Folds the write-ahead log back into the database file.
+                    let _ = conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)");
+                }
+                DRAINED.store(true, Ordering::SeqCst);
+                app.exit(code.unwrap_or(0));
+            });
+        }
+    }
+}
+
+/// Sends or keeps every decision still in its undo window, waits for
+/// in-flight mutations, then flushes telemetry.
+async fn drain(app: &AppHandle) -> Value {
+    let _ = environment::emit(
+        app,
+        DRAINING_EVENT,
+        json!({ "in_flight": IN_FLIGHT.load(Ordering::SeqCst) }),
+    );
+
+    let mut sent = 0;
+    let mut deferred = Vec::new();
+    for (ticket_id, entry) in app.state::<ScheduledDecisions>().take_all() {
+        if entry.decision == "approve" {
+            if let Some(id) = defer(app, &entry) {
+                deferred.push(
+                    json!({ "ticket_id": ticket_id, "schedule_id": id, "plan_id": entry.plan_id }),
+                );
+                continue;
+            }
+        }
+        // Rejections, and approvals for cores no profile owns, go out now:
+        // the operator has already decided and can no longer undo.
+        let _ = decisions::send_decision(
+            &entry.base_url,
+            entry.token,
+            &entry.plan_id,
+            &entry.decision,
+            entry.payload,
+            entry.version.as_deref(),
+            None,
+        )
+        .await;
+        sent += 1;
+    }
+
+    let started = tokio::time::Instant::now();
+    while IN_FLIGHT.load(Ordering::SeqCst) > 0 && started.elapsed() < DRAIN_TIMEOUT {
+        tokio::time::sleep(DRAIN_POLL).await;
+    }
+    let abandoned = IN_FLIGHT.load(Ordering::SeqCst);
+    let spans = telemetry::flush().await.unwrap_or(0);
+
+    json!({
+        "sent": sent,
+        "deferred": deferred,
+        "abandoned_in_flight": abandoned,
+        "spans_exported": spans,
+    })
+}
+
+/// Moves an approval from its undo window into the persistent schedule at
+/// the time it would have gone out, so the next launch sends it (within the
+/// usual grace period) after re-checking the plan.
+fn defer(app: &AppHandle, entry: &decisions::Scheduled) -> Option<i64> {
+    // The safe-mode database is gone once the process exits; the approval
+    // goes out now instead.
+    if safe_mode::active() {
+        return None;
+    }
+    let origin = net::base_origin(&entry.base_url)?;
+    let profile_id = app
+        .state::<SettingsStore>()
+        .snapshot()
+        .profiles
+        .into_iter()
+        .find(|p| {
+            p.endpoints()
+                .any(|url| net::base_origin(url).as_deref() == Some(origin.as_str()))
+        })?
+        .id;
+    let fire_at = DateTime::parse_from_rfc3339(&entry.fires_at)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let execute = entry
+        .payload
+        .as_ref()
+        .is_some_and(|p| p["execute"].as_bool() == Some(true));
+    let db = app.state::<LocalDb>();
+    let conn = db.lock().ok()?;
+    let id = approval_schedule::insert(
+        &conn,
+        &profile_id,
+        &entry.plan_id,
+        execute,
+        fire_at,
+        entry.version.as_deref(),
+        approval_schedule::DEFAULT_GRACE_MINUTES,
+        None,
+    )
+    .ok()?;
+    let _ = db::record_audit(
+        &conn,
+        &profile_id,
+        "shutdown",
+        "deferred_approval",
+        &entry.plan_id,
+        &json!({ "schedule_id": id, "fire_at": entry.fires_at, "execute": execute }),
+    );
+    Some(id)
+}
+
+/// Called when the main window is about to close for good: with a change
+/// in flight or a decision in its undo window, the close is held and
+/// `shutdown:confirm` asks the operator first.
+pub fn on_close_requested(app: &AppHandle, api: &CloseRequestApi) {
+    if CLOSE_CONFIRMED.load(Ordering::SeqCst) || DRAINING.load(Ordering::SeqCst) {
+        return;
+    }
+    let in_flight = IN_FLIGHT.load(Ordering::SeqCst);
+    let undo_window = app.state::<ScheduledDecisions>().len();
+    if in_flight == 0 && undo_window == 0 {
+        return;
+    }
+    api.prevent_close();
+    let _ = environment::emit(
+        app,
+        CONFIRM_EVENT,
+        json!({ "in_flight": in_flight, "undo_window": undo_window }),
+    );
+}
+
+/// Closes the main window after the operator confirmed `shutdown:confirm`.
+#[tauri::command]
+pub fn confirm_close(app: AppHandle) -> Result<Value, String> {
+    CLOSE_CONFIRMED.store(true, Ordering::SeqCst);
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        window
+            .close()
+            .map_err(|e| ErrorCode::Internal.with(format!("Could not close window: {}", e)))?;
+    }
+    Ok(json!({ "closing": true }))
+}
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::crypto;
+use crate::errors::ErrorCode;
+use crate::sanitize;
+
+/// Bodies larger than this are written to a temp file instead of memory.
+const SPILL_THRESHOLD: usize = 8 * 1024 * 1024;
+const MAX_CHUNK: usize = 1024 * 1024;
+pub const FILE_PREFIX: &str = "novaadapt-spill-";
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub enum Body {
+    Buffered(String),
+    /// Handle descriptor returned to the caller in place of the JSON body.
+    Spilled(Value),
+}
+
+/// Reads a response body, buffering it in memory up to `SPILL_THRESHOLD` and
+/// streaming the rest to disk if it grows past that.
+pub async fn read_body(mut response: reqwest::Response) -> Result<Body, String> {
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let read_err =
+        |e: reqwest::Error| ErrorCode::CoreUnreachable.with(format!("Read response failed: {}", e));
+    let mut buffer = Vec::new();
+    let mut spilled: Option<(String, tokio::fs::File)> = None;
+    let mut total = 0usize;
+    while let Some(chunk) = response.chunk().await.map_err(read_err)? {
+        total += chunk.len();
+        if let Some((_, file)) = spilled.as_mut() {
+            file.write_all(&chunk).await.map_err(write_err)?;
+            continue;
+        }
+        buffer.extend_from_slice(&chunk);
+        if buffer.len() > SPILL_THRESHOLD {
+            let handle = new_handle();
+            let mut file = tokio::fs::File::create(path_for(&handle))
+                .await
+                .map_err(write_err)?;
+            file.write_all(&buffer).await.map_err(write_err)?;
+            buffer = Vec::new();
+            spilled = Some((handle, file));
+        }
+    }
+    match spilled {
+        Some((handle, mut file)) => {
+            file.flush().await.map_err(write_err)?;
+            Ok(Body::Spilled(json!({
+                "spilled": true,
+                "handle": handle,
+                "size_bytes": total,
+                "content_type": content_type,
+            })))
+        }
+        None => Ok(Body::Buffered(
+            String::from_utf8_lossy(&buffer).into_owned(),
+        )),
+    }
+}
+
+/// Sanitizes a spilled JSON body with `sanitize::scrub`, rewriting its file
+/// and `size_bytes`, so chunks read back are as clean as a buffered body.
+/// A body that is not JSON is left as it is, as a buffered one would be.
+pub async fn scrub(handle: &mut Value) -> Result<(), String> {
+    let path = resolve(handle["handle"].as_str().unwrap_or_default())?;
+    let body = tokio::fs::read(&path).await.map_err(read_file_err)?;
+    let Ok(mut value) = serde_json::from_slice::<Value>(&body) else {
+        return Ok(());
+    };
+    drop(body);
+    sanitize::scrub(&mut value);
+    let cleaned =
+        serde_json::to_vec(&value).map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+    tokio::fs::write(&path, &cleaned).await.map_err(write_err)?;
+    handle["size_bytes"] = json!(cleaned.len());
+    Ok(())
+}
+
+/// Reads up to `length` bytes of a spilled body starting at `offset`,
+/// base64-encoded since chunk boundaries need not fall on UTF-8 boundaries.
+#[tauri::command]
+pub async fn read_chunk(
+    handle: String,
+    offset: u64,
+    length: Option<usize>,
+) -> Result<Value, String> {
+    let path = resolve(&handle)?;
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| ErrorCode::NotFound.with(format!("Spilled body not found: {}", e)))?;
+    let size = file.metadata().await.map_err(read_file_err)?.len();
+    file.seek(std::io::SeekFrom::Start(offset.min(size)))
+        .await
+        .map_err(read_file_err)?;
+    let want = length.unwrap_or(MAX_CHUNK).clamp(1, MAX_CHUNK);
+    let mut data = Vec::with_capacity(want);
+    (&mut file)
+        .take(want as u64)
+        .read_to_end(&mut data)
+        .await
+        .map_err(read_file_err)?;
+    let next = offset.min(size) + data.len() as u64;
+    Ok(json!({
+        "handle": handle,
+        "offset": offset,
+        "length": data.len(),
+        "next_offset": next,
+        "size_bytes": size,
+        "eof": next >= size,
+        "encoding": "base64",
+        "data": B64.encode(&data),
+    }))
+}
+
+#[tauri::command]
+pub async fn release_chunked_body(handle: String) -> Result<Value, String> {
+    let path = resolve(&handle)?;
+    let removed = tokio::fs::remove_file(&path).await.is_ok();
+    Ok(json!({ "handle": handle, "released": removed }))
+}
+
+/// Deletes spill files left behind by earlier sessions.
+pub fn sweep_stale() {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let is_spill = entry.file_name().to_string_lossy().starts_with(FILE_PREFIX);
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if is_spill && stale {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Deletes every spill file, including ones still held by a handle.
+pub fn sweep_all() -> usize {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(FILE_PREFIX))
+        .filter(|e| std::fs::remove_file(e.path()).is_ok())
+        .count()
+}
+
+fn new_handle() -> String {
+    crypto::random_bytes::<16>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn path_for(handle: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}{}.body", FILE_PREFIX, handle))
+}
+
+/// Handles are generated hex ids; anything else could escape the temp dir.
+fn resolve(handle: &str) -> Result<PathBuf, String> {
+    if handle.len() != 32 || !handle.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ErrorCode::InvalidInput.with("Invalid body handle"));
+    }
+    Ok(path_for(handle))
+}
+
+fn write_err(e: std::io::Error) -> String {
+    ErrorCode::LocalIo.with(format!("Spill write failed: {}", e))
+}
+
+fn read_file_err(e: std::io::Error) -> String {
+    ErrorCode::LocalIo.with(format!("Spill read failed: {}", e))
+}
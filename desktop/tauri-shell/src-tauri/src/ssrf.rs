@@ -0,0 +1,391 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Url;
+
+use crate::errors::ErrorCode;
+use crate::settings::{RedirectMode, RedirectSettings, RequestTargetSettings};
+
+const MAX_REDIRECTS_CAP: usize = 10;
+
+/// Cloud instance metadata endpoints outside the link-local ranges.
+const METADATA_ADDRS: &[IpAddr] = &[
+    // Alibaba Cloud.
+    IpAddr::V4(Ipv4Addr::new(100, 100, 100, 200)),
+    // AWS over IPv6.
+    IpAddr::V6(Ipv6Addr::new(0xfd00, 0x0ec2, 0, 0, 0, 0, 0, 0x0254)),
+];
+
+static SETTINGS: Mutex<Option<RequestTargetSettings>> = Mutex::new(None);
+
+/// Why a request target was refused. Raised from the resolver and the
+/// redirect policy too, where it travels inside reqwest's error; see
+/// `blocked_in`.
+#[derive(Debug)]
+pub struct Blocked(pub String);
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for Blocked {}
+
+fn settings() -> RequestTargetSettings {
+    SETTINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+pub fn configure(settings: &RequestTargetSettings) {
+    *SETTINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(settings.clone());
+}
+
+/// An address or CIDR range (`169.254.169.254`, `fe80::/10`).
+fn parse_range(raw: &str) -> Result<(IpAddr, u8), String> {
+    let raw = raw.trim();
+    let (addr, prefix) = raw.split_once('/').unwrap_or((raw, ""));
+    let addr: IpAddr = addr
+        .parse()
+        .map_err(|_| format!("Invalid address in allowed_addresses: {}", raw))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = if prefix.is_empty() {
+        max
+    } else {
+        prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|p| *p <= max)
+            .ok_or_else(|| format!("Invalid prefix length in allowed_addresses: {}", raw))?
+    };
+    Ok((addr, prefix))
+}
+
+fn in_range(ip: IpAddr, (net, prefix): (IpAddr, u8)) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+pub fn validate(settings: &RequestTargetSettings) -> Result<(), String> {
+    for entry in &settings.allowed_addresses {
+        parse_range(entry)?;
+    }
+    if settings.max_redirects > MAX_REDIRECTS_CAP {
+        return Err(format!(
+            "request_targets.max_redirects must be at most {}",
+            MAX_REDIRECTS_CAP
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_redirects(redirects: &RedirectSettings) -> Result<(), String> {
+    if redirects.max > MAX_REDIRECTS_CAP {
+        return Err(format!(
+            "redirects.max must be at most {}",
+            MAX_REDIRECTS_CAP
+        ));
+    }
+    Ok(())
+}
+
+/// Why `ip` may not be contacted, if it may not: link-local addresses
+/// (where cloud metadata services live), known metadata endpoints and the
+/// unspecified address, unless `allowed_addresses` covers it. IPv4-mapped
+/// IPv6 addresses are judged as the IPv4 address they carry.
+fn refusal(ip: IpAddr, settings: &RequestTargetSettings) -> Option<String> {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+    let allowed = settings
+        .allowed_addresses
+        .iter()
+        .filter_map(|raw| parse_range(raw).ok())
+        .any(|range| in_range(ip, range));
+    if allowed {
+        return None;
+    }
+    let link_local = match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => in_range(
+            IpAddr::V6(v6),
+            (IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0)), 10),
+        ),
+    };
+    if link_local {
+        Some(format!("{} is a link-local address", ip))
+    } else if METADATA_ADDRS.contains(&ip) {
+        Some(format!("{} is a cloud metadata address", ip))
+    } else if ip.is_unspecified() {
+        Some(format!("{} is the unspecified address", ip))
+    } else {
+        None
+    }
+}
+
+/// The resolved addresses a request may use; an error when none is left.
+pub fn filter_resolved(
+    host: &str,
+    addrs: Vec<std::net::SocketAddr>,
+) -> Result<Vec<std::net::SocketAddr>, Blocked> {
+    let settings = settings();
+    let mut refused = None;
+    let kept: Vec<_> = addrs
+        .into_iter()
+        .filter(|addr| match refusal(addr.ip(), &settings) {
+            Some(reason) => {
+                refused = Some(reason);
+                false
+            }
+            None => true,
+        })
+        .collect();
+    match refused {
+        Some(reason) if kept.is_empty() => Err(Blocked(format!("{} resolves to {}", host, reason))),
+        _ => Ok(kept),
+    }
+}
+
+fn check_target(url: &Url, settings: &RequestTargetSettings) -> Result<(), String> {
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(format!(
+            "{} carries user info before the host; put credentials in the profile instead",
+            url.host_str().unwrap_or_default()
+        ));
+    }
+    let ip = url.host_str().and_then(|h| {
+        h.trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .ok()
+    });
+    match ip.and_then(|ip| refusal(ip, settings)) {
+        Some(reason) => Err(reason),
+        None => Ok(()),
+    }
+}
+
+/// Refuses a request whose target is not the core it claims to be: a base
+/// URL or final URL with `user@` before the host, a path that moved the
+/// request to another host, or a literal address that is link-local or a
+/// metadata endpoint. Names that resolve to those addresses are refused by
+/// the resolver, and redirects by `redirect_policy`.
+pub fn check_url(base: &str, url: &Url) -> Result<(), String> {
+    let blocked = |reason: String| ErrorCode::RequestTargetBlocked.with(reason);
+    let settings = settings();
+    let base = Url::parse(base.trim())
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid URL: {}", e)))?;
+    check_target(&base, &settings).map_err(blocked)?;
+    check_target(url, &settings).map_err(blocked)?;
+    if base.host_str() != url.host_str()
+        || base.port_or_known_default() != url.port_or_known_default()
+    {
+        return Err(blocked(format!(
+            "The request path moved the target from {} to {}",
+            base.host_str().unwrap_or_default(),
+            url.host_str().unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// Follows redirects as the profile's `redirects` allow: none, within the
+/// origin asked, or anywhere, at most `redirects.max` (and
+/// `request_targets.max_redirects`) of them, and none to a target
+/// `check_url` would refuse. reqwest drops `Authorization`, `Cookie` and
+/// proxy credentials when a hop changes host or port; a hop to another
+/// origin that would still carry credentials (a scheme change alone, or a
+/// `custom_credentials` header such as an API key) is refused instead.
+pub fn redirect_policy(redirects: &RedirectSettings, custom_credentials: bool) -> Policy {
+    let redirects = redirects.clone();
+    if redirects.mode == RedirectMode::None {
+        return Policy::none();
+    }
+    Policy::custom(move |attempt: Attempt| {
+        let settings = settings();
+        let limit = redirects.max.min(settings.max_redirects);
+        if attempt.previous().len() > limit {
+            let error = Blocked(format!("More than {} redirects", limit));
+            return attempt.error(error);
+        }
+        let (Some(first), Some(last)) = (attempt.previous().first(), attempt.previous().last())
+        else {
+            return attempt.follow();
+        };
+        if redirects.mode == RedirectMode::SameOrigin && !same_origin(first, attempt.url()) {
+            let reason = format!(
+                "Redirect refused: {} is another origin, and this profile follows same-origin redirects only",
+                attempt.url().origin().ascii_serialization()
+            );
+            return attempt.error(Blocked(reason));
+        }
+        if !same_origin(last, attempt.url()) {
+            let stripped = last.host_str() != attempt.url().host_str()
+                || last.port_or_known_default() != attempt.url().port_or_known_default();
+            if !stripped || custom_credentials {
+                let reason = format!(
+                    "Redirect refused: it would send this profile's credentials to {}",
+                    attempt.url().origin().ascii_serialization()
+                );
+                return attempt.error(Blocked(reason));
+            }
+        }
+        match check_target(attempt.url(), &settings) {
+            Ok(()) => attempt.follow(),
+            Err(reason) => attempt.error(Blocked(format!("Redirect refused: {}", reason))),
+        }
+    })
+}
+
+/// The reason a failed request was refused by this layer, if it was.
+pub fn blocked_in(err: &reqwest::Error) -> Option<String> {
+    let mut source: Option<&(dyn StdError + 'static)> = Some(err);
+    while let Some(current) = source {
+        if let Some(blocked) = current.downcast_ref::<Blocked>() {
+            return Some(blocked.0.clone());
+        }
+        source = current.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn target(url: &str) -> Result<(), String> {
+        check_target(&Url::parse(url).unwrap(), &RequestTargetSettings::default())
+    }
+
+    #[test]
+    fn metadata_and_link_local_targets_are_refused() {
+        for url in [
+            "http://user:pw@core.example/",
+            "http://token@core.example/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[fe80::1]/",
+            "http://[::ffff:169.254.169.254]/",
+            "http://100.100.100.200/",
+            "http://[fd00:ec2::254]/",
+            "http://0.0.0.0:8080/",
+        ] {
+            assert!(target(url).is_err(), "{}", url);
+        }
+        for url in [
+            "https://core.example/api",
+            "http://127.0.0.1:8787/",
+            "http://10.0.0.5/",
+            "http://[::1]/",
+        ] {
+            assert!(target(url).is_ok(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn allowed_addresses_lift_a_refusal() {
+        let settings = RequestTargetSettings {
+            allowed_addresses: vec!["169.254.0.0/16".to_string()],
+            ..RequestTargetSettings::default()
+        };
+        let url = Url::parse("http://169.254.10.1/").unwrap();
+        assert!(check_target(&url, &settings).is_ok());
+        let url = Url::parse("http://100.100.100.200/").unwrap();
+        assert!(check_target(&url, &settings).is_err());
+        assert!(validate(&RequestTargetSettings {
+            allowed_addresses: vec!["169.254.0.0/33".to_string()],
+            ..RequestTargetSettings::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn the_path_may_not_move_the_request() {
+        let base = "https://core.example/api";
+        assert!(check_url(base, &Url::parse("https://core.example/api/plans").unwrap()).is_ok());
+        let moved = Url::parse("https://other.example/plans").unwrap();
+        assert!(check_url(base, &moved).is_err());
+        let port = Url::parse("https://core.example:8443/plans").unwrap();
+        assert!(check_url(base, &port).is_err());
+    }
+
+    /// A server answering every request with a redirect to `location`,
+    /// given the port it listens on.
+    async fn redirecting(location: impl Fn(u16) -> String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let location = location(port);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    location
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://127.0.0.1:{}/", port)
+    }
+
+    async fn get(url: &str, mode: RedirectMode, custom_credentials: bool) -> reqwest::Result<u16> {
+        let redirects = RedirectSettings {
+            mode,
+            ..RedirectSettings::default()
+        };
+        let client = reqwest::Client::builder()
+            .redirect(redirect_policy(&redirects, custom_credentials))
+            .no_proxy()
+            .build()
+            .unwrap();
+        client.get(url).send().await.map(|r| r.status().as_u16())
+    }
+
+    #[tokio::test]
+    async fn redirects_to_refused_targets_are_not_followed() {
+        let url = redirecting(|_| "http://169.254.169.254/latest/meta-data".to_string()).await;
+        let err = get(&url, RedirectMode::Follow, false).await.unwrap_err();
+        assert!(blocked_in(&err).is_some_and(|reason| reason.contains("link-local")));
+
+        let url = redirecting(|_| "http://127.0.0.2:1/".to_string()).await;
+        let err = get(&url, RedirectMode::SameOrigin, false)
+            .await
+            .unwrap_err();
+        assert!(blocked_in(&err).is_some_and(|reason| reason.contains("same-origin")));
+
+        let url = redirecting(|port| format!("http://localhost:{}/", port)).await;
+        let err = get(&url, RedirectMode::Follow, true).await.unwrap_err();
+        assert!(blocked_in(&err).is_some_and(|reason| reason.contains("credentials")));
+    }
+
+    #[tokio::test]
+    async fn redirects_off_return_the_redirect() {
+        let url = redirecting(|_| "http://169.254.169.254/".to_string()).await;
+        assert_eq!(get(&url, RedirectMode::None, false).await.unwrap(), 302);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{SettingsStore, StallSettings};
+use crate::{environment, notify, tasks};
+
+const TASK: &str = "stall_detection";
+const STALLED_EVENT: &str = "plan:stalled";
+const RESUMED_EVENT: &str = "plan:resumed";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_AFTER_MINUTES: u32 = 24 * 60;
+/// Statuses of plans that are running.
+const RUNNING: &[&str] = &["executing", "running", "in_progress"];
+
+/// Executing plans by profile and plan id, with when their progress last
+/// changed.
+#[derive(Default)]
+pub struct StallTracker {
+    plans: Mutex<HashMap<(String, String), Progress>>,
+}
+
+struct Progress {
+    fingerprint: String,
+    changed: Instant,
+    stalled: bool,
+    /// The step the plan was on, 1-based.
+    step: usize,
+    objective: String,
+}
+
+pub fn validate(settings: &StallSettings) -> Result<(), String> {
+    if settings.after_minutes == 0 || settings.after_minutes > MAX_AFTER_MINUTES {
+        return Err(format!(
+            "Stall detection window must be 1 to {} minutes",
+            MAX_AFTER_MINUTES
+        ));
+    }
+    Ok(())
+}
+
+fn running(plan: &Value) -> bool {
+    plan["status"]
+        .as_str()
+        .is_some_and(|s| RUNNING.contains(&s.to_ascii_lowercase().as_str()))
+}
+
+/// What changes when the plan makes progress: its counters, each step's
+/// status and the core's own update stamp.
+fn fingerprint(plan: &Value) -> String {
+    let statuses: Option<Vec<&Value>> = plan["execution_results"]
+        .as_array()
+        .map(|results| results.iter().map(|r| &r["status"]).collect());
+    let parts = json!([
+        plan["progress_completed"],
+        plan["progress_total"],
+        plan["updated_at"],
+        statuses,
+    ]);
+    let digest = Sha256::digest(parts.to_string().as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The step the plan is on: the first one without a finished result.
+fn current_step(plan: &Value) -> usize {
+    let done = plan["execution_results"]
+        .as_array()
+        .map(|results| {
+            results
+                .iter()
+                .take_while(|r| {
+                    matches!(
+                        r["status"].as_str().unwrap_or_default(),
+                        "ok" | "success" | "succeeded" | "done" | "completed" | "skipped"
+                    )
+                })
+                .count()
+        })
+        .or_else(|| plan["progress_completed"].as_u64().map(|n| n as usize))
+        .unwrap_or(0);
+    done + 1
+}
+
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            check(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+async fn check(app: &AppHandle) {
+    let store = app.state::<SettingsStore>();
+    let settings = store.snapshot();
+    let tracker = app.state::<StallTracker>();
+    if !settings.stall_detection.enabled {
+        if let Ok(mut plans) = tracker.plans.lock() {
+            plans.clear();
+        }
+        return;
+    }
+    let after = Duration::from_secs(u64::from(settings.stall_detection.after_minutes) * 60);
+    for profile in &settings.profiles {
+        let Ok(conn) = store.connection(Some(&profile.id)) else {
+            continue;
+        };
+        let Ok(listed) = crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            "/plans?limit=200",
+            conn.token.clone(),
+            None,
+        )
+        .await
+        else {
+            // An unreachable core says nothing about its plans' progress.
+            continue;
+        };
+        let executing: Vec<&Value> = listed
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|p| running(p))
+            .collect();
+        let mut events = Vec::new();
+        {
+            let Ok(mut plans) = tracker.plans.lock() else {
+                return;
+            };
+            plans.retain(|(profile_id, plan_id), _| {
+                profile_id != &profile.id
+                    || executing
+                        .iter()
+                        .any(|p| p["id"].as_str() == Some(plan_id.as_str()))
+            });
+            for plan in &executing {
+                let Some(plan_id) = plan["id"].as_str() else {
+                    continue;
+                };
+                let print = fingerprint(plan);
+                let step = current_step(plan);
+                let objective = plan["objective"].as_str().unwrap_or_default().to_string();
+                let key = (profile.id.clone(), plan_id.to_string());
+                let entry = plans.entry(key).or_insert_with(|| Progress {
+                    fingerprint: print.clone(),
+                    changed: Instant::now(),
+                    stalled: false,
+                    step,
+                    objective: objective.clone(),
+                });
+                if entry.fingerprint != print {
+                    if entry.stalled {
+                        events.push((RESUMED_EVENT, plan_id.to_string(), step, 0));
+                    }
+                    entry.fingerprint = print;
+                    entry.changed = Instant::now();
+                    entry.stalled = false;
+                    entry.step = step;
+                    entry.objective = objective;
+                } else if !entry.stalled && entry.changed.elapsed() >= after {
+                    entry.stalled = true;
+                    events.push((
+                        STALLED_EVENT,
+                        plan_id.to_string(),
+                        step,
+                        entry.changed.elapsed().as_secs(),
+                    ));
+                }
+            }
+        }
+        for (event, plan_id, step, idle_seconds) in events {
+            let payload = json!({
+                "profile_id": profile.id,
+                "plan_id": plan_id,
+                "step": step,
+                "idle_seconds": idle_seconds,
+            });
+            if event == STALLED_EVENT {
+                notify::notify(
+                    app,
+                    "stall",
+                    "Plan may be stalled",
+                    &format!(
+                        "Plan {} has not progressed past step {} in {} minutes",
+                        plan_id,
+                        step,
+                        idle_seconds / 60
+                    ),
+                    payload.clone(),
+                );
+            }
+            let _ = environment::emit(app, event, payload);
+        }
+    }
+}
+
+/// Executing plans that have made no progress within the configured
+/// window.
+#[tauri::command]
+pub fn list_stalled_plans(tracker: State<'_, StallTracker>) -> Result<Value, String> {
+    let plans = tracker
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Stall tracker poisoned"))?;
+    let stalled: Vec<Value> = plans
+        .iter()
+        .filter(|(_, p)| p.stalled)
+        .map(|((profile_id, plan_id), p)| {
+            json!({
+                "profile_id": profile_id,
+                "plan_id": plan_id,
+                "objective": p.objective,
+                "step": p.step,
+                "idle_seconds": p.changed.elapsed().as_secs(),
+            })
+        })
+        .collect();
+    Ok(json!({ "plans": stalled }))
+}
+
+pub fn plan_key(plan_id: &str) -> Result<String, String> {
+    let plan_id = plan_id.trim();
+    if plan_id.is_empty() || plan_id.contains(['/', '?', '#']) {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    Ok(plan_id.to_string())
+}
+
+/// Restarts the clock for a plan the operator acted on, so it is not
+/// reported again before the window passes anew.
+fn reset(tracker: &StallTracker, profile_id: &str, plan_id: &str) {
+    if let Ok(mut plans) = tracker.plans.lock() {
+        if let Some(entry) = plans.get_mut(&(profile_id.to_string(), plan_id.to_string())) {
+            entry.changed = Instant::now();
+            entry.stalled = false;
+        }
+    }
+}
+
+/// Stops tracking a plan whose execution was aborted.
+pub fn forget(tracker: &StallTracker, profile_id: &str, plan_id: &str) {
+    if let Ok(mut plans) = tracker.plans.lock() {
+        plans.remove(&(profile_id.to_string(), plan_id.to_string()));
+    }
+}
+
+fn audit(db: &LocalDb, profile_id: &str, action: &str, plan_id: &str, detail: &Value) {
+    if let Ok(conn) = db.lock() {
+        let _ = db::record_audit(&conn, profile_id, "stall", action, plan_id, detail);
+    }
+}
+
+/// Asks the core to run one step of an executing plan again; `step` is
+/// 1-based and defaults to the step the plan stalled on.
+#[tauri::command]
+pub async fn retry_step(
+    store: State<'_, SettingsStore>,
+    tracker: State<'_, StallTracker>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    step: Option<usize>,
+) -> Result<Value, String> {
+    let plan_id = plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    let step = match step {
+        Some(0) => return Err(ErrorCode::InvalidInput.with("Steps are numbered from 1")),
+        Some(step) => step,
+        None => tracker
+            .plans
+            .lock()
+            .ok()
+            .and_then(|plans| {
+                plans
+                    .get(&(conn.profile_id.clone(), plan_id.clone()))
+                    .map(|p| p.step)
+            })
+            .ok_or_else(|| {
+                ErrorCode::InvalidInput.with("Step is required for a plan that is not tracked")
+            })?,
+    };
+    let result = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/steps/{}/retry", plan_id, step),
+        conn.token.clone(),
+        Some(json!({ "source": "stall_detection" })),
+    )
+    .await?;
+    reset(&tracker, &conn.profile_id, &plan_id);
+    audit(
+        &db,
+        &conn.profile_id,
+        "retry_step",
+        &plan_id,
+        &json!({ "step": step }),
+    );
+    Ok(json!({ "plan_id": plan_id, "step": step, "result": result }))
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State, WebviewWindow};
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::settings::{PollingSettings, SettingsStore};
+use crate::{claims, environment, metrics, tasks, tray};
+
+const DASHBOARD_EVENT: &str = "state:dashboard";
+const PLAN_EVENT_PREFIX: &str = "state:plans:";
+const ERROR_EVENT: &str = "state:error";
+const DASHBOARD_PATH: &str = "/dashboard/data?plans_limit=100&jobs_limit=100&events_limit=50";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_INTERVAL: Duration = Duration::from_secs(60);
+const IDLE_WAIT: Duration = Duration::from_secs(1);
+/// How fast the activity estimate forgets: a quiet half-life halves it.
+const ACTIVITY_HALF_LIFE: Duration = Duration::from_secs(120);
+/// The activity at which a window's requested interval is used as is;
+/// busier cores are polled proportionally faster, quieter ones slower.
+const REFERENCE_PER_MINUTE: f64 = 1.0;
+
+/// Dashboard changes per minute, as an exponentially decaying count: a
+/// burst speeds polling up at once and a quiet spell slows it down over a
+/// few half-lives.
+#[derive(Default)]
+struct Activity {
+    count: f64,
+    at: Option<Instant>,
+}
+
+impl Activity {
+    /// A decaying count holds rate × (half-life / ln 2) in steady state.
+    fn mean_life() -> f64 {
+        ACTIVITY_HALF_LIFE.as_secs_f64() / std::f64::consts::LN_2
+    }
+
+    /// The first observation starts from the reference rate, so polling
+    /// begins at the requested interval and adapts from there.
+    fn observe(&mut self, changes: usize) {
+        let now = Instant::now();
+        match self.at {
+            Some(at) => {
+                let halves = (now - at).as_secs_f64() / ACTIVITY_HALF_LIFE.as_secs_f64();
+                self.count *= 0.5f64.powf(halves);
+            }
+            None => self.count = REFERENCE_PER_MINUTE * Self::mean_life() / 60.0,
+        }
+        self.at = Some(now);
+        self.count += changes as f64;
+    }
+
+    fn per_minute(&self) -> f64 {
+        let Some(at) = self.at else {
+            return REFERENCE_PER_MINUTE;
+        };
+        let halves = at.elapsed().as_secs_f64() / ACTIVITY_HALF_LIFE.as_secs_f64();
+        self.count * 0.5f64.powf(halves) * 60.0 / Self::mean_life()
+    }
+
+    /// `base` scaled by how busy the core is against `REFERENCE_PER_MINUTE`,
+    /// held within the configured bounds.
+    fn interval(&self, base: Duration, polling: &PollingSettings) -> Duration {
+        if !polling.adaptive {
+            return base;
+        }
+        let min = Duration::from_millis(polling.min_interval_ms);
+        let max = Duration::from_millis(polling.max_interval_ms);
+        let rate = self.per_minute();
+        if rate <= f64::EPSILON {
+            return max;
+        }
+        base.mul_f64((REFERENCE_PER_MINUTE / rate).min(1e6))
+            .clamp(min, max)
+    }
+}
+
+#[derive(Default)]
+struct Snapshot {
+    profile_id: String,
+    revision: u64,
+    dashboard: Value,
+    plans: HashMap<String, Value>,
+    updated_at: Option<String>,
+}
+
+/// The backend's copy of the active profile's dashboard. One poller keeps it
+/// current and pushes changes as events, so every window renders the same
+/// state without polling the core itself.
+#[derive(Default)]
+pub struct StateStore {
+    snapshot: Mutex<Snapshot>,
+    /// Push interval requested per subscribed window; the fastest one wins.
+    subscribers: Mutex<HashMap<String, Duration>>,
+    activity: Mutex<Activity>,
+    /// The interval the poller last slept for.
+    polling: Mutex<Option<Duration>>,
+}
+
+impl StateStore {
+    /// The next poll interval: the fastest window's request, adapted to
+    /// recent activity unless `polling.adaptive` is off.
+    fn interval(&self, polling: &PollingSettings) -> Option<Duration> {
+        let base = self.subscribers.lock().ok()?.values().min().copied()?;
+        let interval = self.activity.lock().ok()?.interval(base, polling);
+        if let Ok(mut current) = self.polling.lock() {
+            *current = Some(interval);
+        }
+        Some(interval)
+    }
+
+    fn describe(&self) -> Value {
+        let interval = self.polling.lock().ok().and_then(|p| *p);
+        let activity = self.activity.lock().map_or(0.0, |a| a.per_minute());
+        match self.snapshot.lock() {
+            Ok(snapshot) => json!({
+                "profile_id": snapshot.profile_id,
+                "revision": snapshot.revision,
+                "updated_at": snapshot.updated_at,
+                "data": snapshot.dashboard,
+                "polling": {
+                    "interval_ms": interval.map(|i| i.as_millis() as u64),
+                    "changes_per_minute": (activity * 100.0).round() / 100.0,
+                },
+            }),
+            Err(_) => Value::Null,
+        }
+    }
+}
+
+/// Plans, jobs and events that appeared, changed or went away between two
+/// dashboards. Metrics and health are left out: they move on every poll.
+fn changes(before: &Value, after: &Value) -> usize {
+    ["plans", "jobs", "events"]
+        .iter()
+        .map(|key| {
+            let index = |data: &Value| -> HashMap<String, Value> {
+                data[*key]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| {
+                        let id = match &item["id"] {
+                            Value::String(id) => id.clone(),
+                            Value::Null => return None,
+                            other => other.to_string(),
+                        };
+                        Some((id, item.clone()))
+                    })
+                    .collect()
+            };
+            let (old, new) = (index(before), index(after));
+            new.iter()
+                .filter(|(id, item)| old.get(*id) != Some(*item))
+                .count()
+                + old.keys().filter(|id| !new.contains_key(*id)).count()
+        })
+        .sum()
+}
+
+pub fn validate(polling: &PollingSettings) -> Result<(), String> {
+    let (min, max) = (
+        MIN_INTERVAL.as_millis() as u64,
+        MAX_INTERVAL.as_millis() as u64,
+    );
+    if polling.min_interval_ms < min
+        || polling.max_interval_ms > max
+        || polling.min_interval_ms > polling.max_interval_ms
+    {
+        return Err(format!(
+            "Polling bounds must satisfy {} <= min <= max <= {} ms",
+            min, max
+        ));
+    }
+    Ok(())
+}
+
+const TASK: &str = "state";
+
+pub fn spawn_pusher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let polling = app.state::<SettingsStore>().snapshot().polling;
+            let Some(interval) = app.state::<StateStore>().interval(&polling) else {
+                tokio::time::sleep(IDLE_WAIT).await;
+                continue;
+            };
+            if let Err(err) = refresh(&app).await {
+                tasks::failed(TASK, &err);
+                let _ = environment::emit(&app, ERROR_EVENT, json!({ "error": err }));
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Fetches the active profile's dashboard and emits what changed:
+/// `state:dashboard` when anything did, plus `state:plans:<id>` for each
+/// plan that was added, modified or dropped.
+async fn refresh(app: &AppHandle) -> Result<Value, String> {
+    let conn = app.state::<SettingsStore>().connection(None)?;
+    let mut data = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        DASHBOARD_PATH,
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    claims::annotate(&conn.base_url, &mut data);
+    let plans: HashMap<String, Value> = data["plans"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|plan| Some((plan["id"].as_str()?.to_string(), plan.clone())))
+        .collect();
+    let pending = plans
+        .values()
+        .filter(|p| p["status"].as_str() == Some("pending"))
+        .count();
+    tray::set_pending(app, &conn.profile_id, pending);
+    metrics::record_snapshot(&app.state::<LocalDb>(), &conn.profile_id, &data["metrics"]);
+
+    let store = app.state::<StateStore>();
+    let (changed_plans, removed_plans, dashboard_changed, health_change) = {
+        let mut snapshot = store
+            .snapshot
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("State store poisoned"))?;
+        // A profile switch starts over; its first dashboard is not activity.
+        let switched = snapshot.profile_id != conn.profile_id;
+        if switched {
+            *snapshot = Snapshot {
+                profile_id: conn.profile_id.clone(),
+                ..Snapshot::default()
+            };
+        }
+        let changed: Vec<(String, Value)> = plans
+            .iter()
+            .filter(|(id, plan)| snapshot.plans.get(*id) != Some(*plan))
+            .map(|(id, plan)| (id.clone(), plan.clone()))
+            .collect();
+        let removed: Vec<String> = snapshot
+            .plans
+            .keys()
+            .filter(|id| !plans.contains_key(*id))
+            .cloned()
+            .collect();
+        let dashboard_changed = snapshot.dashboard != data;
+        // Kept in the audit trail so a shift handover can show it.
+        let health_change = (!switched
+            && !snapshot.dashboard.is_null()
+            && snapshot.dashboard["health"] != data["health"])
+            .then(|| json!({ "from": snapshot.dashboard["health"], "to": data["health"] }));
+        if !switched {
+            if let Ok(mut activity) = store.activity.lock() {
+                activity.observe(changes(&snapshot.dashboard, &data));
+            }
+        }
+        if dashboard_changed {
+            snapshot.revision += 1;
+            snapshot.dashboard = data;
+            snapshot.plans = plans;
+        }
+        snapshot.updated_at = Some(chrono::Utc::now().to_rfc3339());
+        (changed, removed, dashboard_changed, health_change)
+    };
+    if let Some(detail) = health_change {
+        if let Ok(guard) = app.state::<LocalDb>().lock() {
+            let _ = db::record_audit(&guard, &conn.profile_id, "health", "changed", "", &detail);
+        }
+    }
+
+    let current = store.describe();
+    if dashboard_changed {
+        let _ = environment::emit(app, DASHBOARD_EVENT, &current);
+    }
+    for (id, plan) in changed_plans {
+        let _ = environment::emit(
+            app,
+            &plan_event(&id),
+            json!({ "profile_id": conn.profile_id, "plan": plan }),
+        );
+    }
+    for id in removed_plans {
+        let _ = environment::emit(
+            app,
+            &plan_event(&id),
+            json!({ "profile_id": conn.profile_id, "plan_id": id, "removed": true }),
+        );
+    }
+    Ok(current)
+}
+
+/// Event names only allow alphanumerics and `-/:_`.
+fn plan_event(plan_id: &str) -> String {
+    let safe: String = plan_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", PLAN_EVENT_PREFIX, safe)
+}
+
+/// Subscribes the calling window to state pushes every `interval_ms`
+/// (default 5 s, 1–60 s) and returns the current snapshot. With adaptive
+/// polling the interval is what a core with about one change a minute
+/// gets; busier and quieter cores are polled faster and slower, within
+/// `polling.min_interval_ms`/`max_interval_ms`.
+#[tauri::command]
+pub fn subscribe_state(
+    window: WebviewWindow,
+    state: State<'_, StateStore>,
+    interval_ms: Option<u64>,
+) -> Result<Value, String> {
+    let interval = interval_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_INTERVAL)
+        .clamp(MIN_INTERVAL, MAX_INTERVAL);
+    state
+        .subscribers
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("State store poisoned"))?
+        .insert(window.label().to_string(), interval);
+    Ok(state.describe())
+}
+
+/// Stops pushes for the calling window; the poller idles once no window is
+/// subscribed.
+#[tauri::command]
+pub fn unsubscribe_state(
+    window: WebviewWindow,
+    state: State<'_, StateStore>,
+) -> Result<Value, String> {
+    let remaining = {
+        let mut subscribers = state
+            .subscribers
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("State store poisoned"))?;
+        subscribers.remove(window.label());
+        subscribers.len()
+    };
+    Ok(json!({ "subscribers": remaining }))
+}
+
+/// Refreshes the store now (e.g. right after an approval) and pushes any
+/// change to every subscribed window.
+#[tauri::command]
+pub async fn refresh_state(app: AppHandle) -> Result<Value, String> {
+    refresh(&app).await
+}
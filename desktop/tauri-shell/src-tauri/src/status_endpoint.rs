@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+use tokio::net::TcpStream;
+
+use crate::loopback;
+use crate::settings::{SettingsStore, StatusEndpointSettings, WebhookReceiverSettings};
+use crate::tray::PendingApprovals;
+
+const TASK: &str = "status_endpoint";
+const STATUS_PATH: &str = "/status";
+
+static SERVED: AtomicU64 = AtomicU64::new(0);
+
+pub fn validate(
+    settings: &StatusEndpointSettings,
+    receiver: &WebhookReceiverSettings,
+) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    if settings.port < 1024 {
+        return Err("Status endpoint port must be 1024 or above".to_string());
+    }
+    if receiver.enabled && receiver.port == settings.port {
+        return Err("Status endpoint and webhook receiver need different ports".to_string());
+    }
+    Ok(())
+}
+
+/// Serves `/status` while the endpoint is enabled.
+pub fn spawn_endpoint(app: AppHandle) {
+    loopback::spawn(
+        &app,
+        TASK,
+        |settings| {
+            let endpoint = &settings.status_endpoint;
+            endpoint.enabled.then_some(endpoint.port)
+        },
+        serve,
+    );
+}
+
+/// Answers one request. Only `GET /status` addressed to this machine by
+/// name or loopback address is served.
+async fn serve(app: AppHandle, port: u16, mut stream: TcpStream) {
+    let request = match loopback::read_request(&mut stream, 0).await {
+        Ok(request) => request,
+        Err((status, reason)) => {
+            loopback::respond(&mut stream, status, "text/plain", reason).await;
+            return;
+        }
+    };
+    if !request.addressed_locally(port) {
+        loopback::respond(&mut stream, 403, "text/plain", "Host not allowed").await;
+        return;
+    }
+    if request.method != "GET" {
+        loopback::respond(&mut stream, 405, "text/plain", "Read only").await;
+        return;
+    }
+    if request.path != STATUS_PATH {
+        loopback::respond(&mut stream, 404, "text/plain", "Try /status").await;
+        return;
+    }
+    let mut status = app.state::<PendingApprovals>().summary();
+    status["at"] = json!(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+    SERVED.fetch_add(1, Ordering::Relaxed);
+    if request.query.split('&').any(|pair| pair == "format=text") {
+        let count = format!("{}\n", status["pending"]);
+        loopback::respond(&mut stream, 200, "text/plain", &count).await;
+    } else {
+        loopback::respond_json(&mut stream, 200, &status).await;
+    }
+}
+
+/// Whether the status endpoint is listening, its URL, and how many
+/// requests it has served this session.
+#[tauri::command]
+pub fn get_status_endpoint(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let settings = store.snapshot().status_endpoint;
+    let listening = loopback::listening(TASK);
+    Ok(json!({
+        "enabled": settings.enabled,
+        "port": settings.port,
+        "listening": listening.port.is_some(),
+        "url": listening
+            .port
+            .map(|port| format!("http://127.0.0.1:{}{}", port, STATUS_PATH)),
+        "served": SERVED.load(Ordering::Relaxed),
+        "last_error": listening.last_error,
+    }))
+}
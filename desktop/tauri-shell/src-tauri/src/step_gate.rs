@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::features::{self, FeatureCache};
+use crate::settings::SettingsStore;
+use crate::{decisions, environment, notify, stall, tasks};
+
+const TASK: &str = "step_gate";
+const AWAITING_EVENT: &str = "plan:step_awaiting";
+const DONE_EVENT: &str = "plan:step_gate_done";
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// The core feature flag of cores that can hold execution before each step.
+const FEATURE: &str = "step_gated_approval";
+/// Step statuses meaning the core is holding before the step.
+const AWAITING: &[&str] = &["awaiting_approval", "awaiting_confirmation", "gated"];
+/// Plan statuses after which no step will wait any more.
+const FINISHED: &[&str] = &[
+    "completed",
+    "succeeded",
+    "failed",
+    "rejected",
+    "aborted",
+    "cancelled",
+    "undone",
+];
+
+/// Plans approved in step-gated mode, by profile and plan id.
+#[derive(Default)]
+pub struct StepGates {
+    plans: Mutex<HashMap<(String, String), Gate>>,
+}
+
+#[derive(Clone, Default)]
+struct Gate {
+    /// The step the core holds execution before, as the core identifies it.
+    awaiting: Option<String>,
+    /// 1-based position of `awaiting`, for display.
+    position: Option<usize>,
+    approved: Vec<String>,
+}
+
+impl Gate {
+    fn describe(&self, profile_id: &str, plan_id: &str) -> Value {
+        json!({
+            "profile_id": profile_id,
+            "plan_id": plan_id,
+            "awaiting_step": self.awaiting,
+            "position": self.position,
+            "approved_steps": self.approved,
+        })
+    }
+}
+
+fn id_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) if !id.trim().is_empty() => Some(id.trim().to_string()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The step the core is waiting on: `awaiting_step` when it names one,
+/// otherwise the first step or result whose status says it is held. Steps
+/// without an id are identified by their 1-based position.
+fn awaiting_step(plan: &Value) -> Option<(String, Option<usize>)> {
+    let steps = crate::a11y::steps(plan);
+    if let Some(id) = id_of(&plan["awaiting_step"]).or_else(|| id_of(&plan["awaiting_step_id"])) {
+        let position = steps
+            .iter()
+            .position(|s| id_of(&s["id"]).as_deref() == Some(id.as_str()))
+            .map(|i| i + 1)
+            .or_else(|| id.parse().ok());
+        return Some((id, position));
+    }
+    let results = plan["execution_results"].as_array();
+    let held = |v: &Value| {
+        v["status"]
+            .as_str()
+            .is_some_and(|s| AWAITING.contains(&s.to_ascii_lowercase().as_str()))
+    };
+    steps
+        .iter()
+        .enumerate()
+        .find(|(i, step)| held(step) || results.and_then(|r| r.get(*i)).is_some_and(held))
+        .map(|(i, step)| {
+            let id = id_of(&step["id"]).unwrap_or_else(|| (i + 1).to_string());
+            (id, Some(i + 1))
+        })
+}
+
+fn finished(plan: &Value) -> bool {
+    plan["status"]
+        .as_str()
+        .is_some_and(|s| FINISHED.contains(&s.to_ascii_lowercase().as_str()))
+}
+
+fn audit(app: &AppHandle, profile_id: &str, action: &str, plan_id: &str, detail: &Value) {
+    if let Some(db) = app.try_state::<LocalDb>() {
+        if let Ok(conn) = db.lock() {
+            let _ = db::record_audit(&conn, profile_id, "step_gate", action, plan_id, detail);
+        }
+    }
+}
+
+/// Approves `plan_id` so that it executes one step at a time: the core
+/// holds before each step until `approve_step` is called for it. Refused
+/// up front when the core reports it cannot gate steps. The backend then
+/// follows the plan and emits `plan:step_awaiting` (with a notification)
+/// whenever a step waits, and `plan:step_gate_done` once the plan finishes.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn approve_plan_step_gated(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    features: State<'_, FeatureCache>,
+    gates: State<'_, StepGates>,
+    profile: Option<String>,
+    plan_id: String,
+    version: Option<String>,
+    override_confirmation: Option<String>,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let conn = store.connection(profile.as_deref())?;
+    if features::core_flag(&store, &features, &conn, FEATURE).await == Some(false) {
+        return Err(ErrorCode::InvalidInput.with("This core does not support step-gated approval"));
+    }
+    let result = decisions::send_decision(
+        &conn.base_url,
+        conn.token.clone(),
+        &plan_id,
+        "approve",
+        Some(json!({ "execute": true, "step_gated": true })),
+        version.as_deref(),
+        override_confirmation.as_deref(),
+    )
+    .await?;
+    let gate = Gate::default();
+    let described = gate.describe(&conn.profile_id, &plan_id);
+    gates
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Step gate state poisoned"))?
+        .insert((conn.profile_id.clone(), plan_id.clone()), gate);
+    audit(
+        &app,
+        &conn.profile_id,
+        "approved",
+        &plan_id,
+        &json!({ "step_gated": true }),
+    );
+    Ok(json!({ "gate": described, "result": result }))
+}
+
+/// Lets the step the core is holding on run. `step_id` must be the step
+/// that is awaiting confirmation, so a stale click cannot release a later
+/// step, and nothing is released before the core holds on one.
+#[tauri::command]
+pub async fn approve_step(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    gates: State<'_, StepGates>,
+    profile: Option<String>,
+    plan_id: String,
+    step_id: String,
+) -> Result<Value, String> {
+    let plan_id = stall::plan_key(&plan_id)?;
+    let step_id = step_id.trim().to_string();
+    if step_id.is_empty() || step_id.contains(['/', '?', '#']) {
+        return Err(ErrorCode::InvalidInput.with("Step id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let key = (conn.profile_id.clone(), plan_id.clone());
+    let awaiting = gates
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Step gate state poisoned"))?
+        .get(&key)
+        .map(|gate| gate.awaiting.clone());
+    match awaiting {
+        None => {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Plan {} was not approved in step-gated mode here",
+                plan_id
+            )))
+        }
+        Some(Some(waiting)) if waiting == step_id => {}
+        Some(Some(waiting)) => {
+            return Err(ErrorCode::PlanConflict.with(format!(
+                "Step {} is awaiting confirmation, not step {}",
+                waiting, step_id
+            )))
+        }
+        Some(None) => {
+            return Err(ErrorCode::PlanConflict.with(format!(
+                "No step of plan {} is awaiting confirmation yet",
+                plan_id
+            )))
+        }
+    }
+    let result = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/steps/{}/approve", plan_id, step_id),
+        conn.token.clone(),
+        Some(json!({ "source": "desktop" })),
+    )
+    .await?;
+    let described = {
+        let mut plans = gates
+            .plans
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Step gate state poisoned"))?;
+        plans.get_mut(&key).map(|gate| {
+            gate.awaiting = None;
+            gate.position = None;
+            gate.approved.push(step_id.clone());
+            gate.describe(&conn.profile_id, &plan_id)
+        })
+    };
+    audit(
+        &app,
+        &conn.profile_id,
+        "step_approved",
+        &plan_id,
+        &json!({ "step_id": step_id }),
+    );
+    Ok(json!({ "gate": described, "result": result }))
+}
+
+/// Plans approved in step-gated mode and the step each is waiting on.
+#[tauri::command]
+pub fn list_step_gates(gates: State<'_, StepGates>) -> Result<Value, String> {
+    let plans = gates
+        .plans
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Step gate state poisoned"))?;
+    let listed: Vec<Value> = plans
+        .iter()
+        .map(|((profile_id, plan_id), gate)| gate.describe(profile_id, plan_id))
+        .collect();
+    Ok(json!({ "gates": listed }))
+}
+
+/// Follows step-gated plans and reports each step as it starts waiting.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            poll(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn poll(app: &AppHandle) {
+    let keys: Vec<(String, String)> = match app.state::<StepGates>().plans.lock() {
+        Ok(plans) => plans.keys().cloned().collect(),
+        Err(_) => return,
+    };
+    let store = app.state::<SettingsStore>();
+    for (profile_id, plan_id) in keys {
+        let Ok(conn) = store.connection(Some(&profile_id)) else {
+            continue;
+        };
+        let plan = match crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/plans/{}", plan_id),
+            conn.token.clone(),
+            None,
+        )
+        .await
+        {
+            Ok(plan) => plan,
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => Value::Null,
+            Err(_) => continue,
+        };
+        let gates = app.state::<StepGates>();
+        let key = (profile_id.clone(), plan_id.clone());
+        if plan.is_null() || finished(&plan) {
+            let removed = gates.plans.lock().ok().and_then(|mut p| p.remove(&key));
+            if let Some(gate) = removed {
+                let mut event = gate.describe(&profile_id, &plan_id);
+                event["status"] = plan["status"].clone();
+                let _ = environment::emit(app, DONE_EVENT, event);
+            }
+            continue;
+        }
+        let Some((step_id, position)) = awaiting_step(&plan) else {
+            continue;
+        };
+        let described = {
+            let Ok(mut plans) = gates.plans.lock() else {
+                return;
+            };
+            let Some(gate) = plans.get_mut(&key) else {
+                continue;
+            };
+            if gate.awaiting.as_deref() == Some(step_id.as_str()) {
+                continue;
+            }
+            gate.awaiting = Some(step_id.clone());
+            gate.position = position;
+            gate.describe(&profile_id, &plan_id)
+        };
+        let steps = crate::a11y::steps(&plan);
+        let step = position.and_then(|p| steps.get(p.checked_sub(1)?).copied());
+        let what = step
+            .map(|s| crate::plan_pdf::first_text(s, &["description", "type", "action", "name"]))
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| format!("step {}", step_id));
+        notify::notify(
+            app,
+            "step_gate",
+            "Step awaiting approval",
+            &format!("Plan {}: {} is waiting for confirmation", plan_id, what),
+            json!({
+                "plan_id": plan_id,
+                "step_id": step_id,
+                "risk": step.map(|s| s["risk"].clone()).unwrap_or(Value::Null),
+            }),
+        );
+        let _ = environment::emit(app, AWAITING_EVENT, described);
+    }
+}
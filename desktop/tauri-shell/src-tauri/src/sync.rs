@@ -0,0 +1,467 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection as CoreConnection, SettingsStore};
+use crate::{bandwidth, chat_webhooks, environment, pagination, tasks, tenants, trace_context};
+
+/// Cursor stream holding the id of the last core audit event reconciled.
+pub const CHANGES_STREAM: &str = "changes";
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+pub const CHANGED_EVENT: &str = "sync:changed";
+/// A change page this full may have dropped older changes (the core returns
+/// the newest matches first), so the sync falls back to a full snapshot.
+const PAGE_LIMIT: usize = 500;
+const SNAPSHOT_LIMIT: usize = 500;
+/// Most plans or jobs a full snapshot walks; any further ones are not
+/// cached.
+const SNAPSHOT_MAX_ITEMS: usize = pagination::MAX_ITEMS;
+const KINDS: &[Kind] = &[Kind::Plan, Kind::Job];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Plan,
+    Job,
+}
+
+impl Kind {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "plan" | "plans" => Some(Kind::Plan),
+            "job" | "jobs" => Some(Kind::Job),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Plan => "plan",
+            Kind::Job => "job",
+        }
+    }
+
+    fn collection(self) -> &'static str {
+        match self {
+            Kind::Plan => "/plans",
+            Kind::Job => "/jobs",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Outcome {
+    changes: usize,
+    updated: usize,
+    removed: usize,
+}
+
+/// Keeps every profile's plan and job cache current in the background, so a
+/// shell that was offline for hours only pulls what changed once the core is
+/// back. Low-data mode pauses it; `sync_changes` still pulls on demand.
+const TASK: &str = "sync";
+
+pub fn spawn_syncer(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let profiles = if bandwidth::low_data() {
+                Vec::new()
+            } else {
+                app.state::<SettingsStore>().snapshot().profiles
+            };
+            for profile in profiles {
+                let Ok(conn) = app.state::<SettingsStore>().connection(Some(&profile.id)) else {
+                    continue;
+                };
+                let synced = trace_context::within(
+                    None,
+                    "sync",
+                    sync_profile(&app, &conn, &app.state::<LocalDb>(), false),
+                )
+                .await;
+                match synced {
+                    Ok(result) => {
+                        if result["updated"].as_u64() > Some(0)
+                            || result["removed"].as_u64() > Some(0)
+                        {
+                            let _ = environment::emit(&app, CHANGED_EVENT, &result);
+                        }
+                    }
+                    Err(err) => tasks::failed(TASK, &err),
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    });
+}
+
+/// Reconciles the core's changes since the profile's cursor into the local
+/// cache. The core's audit log is the change feed: each plan or job it
+/// mentions is re-fetched, or dropped when the core no longer has it. The
+/// first sync, or one that fell too far behind, takes a full snapshot.
+/// Rows and cursor are kept under the profile's tenant scope.
+pub async fn sync_profile(
+    app: &AppHandle,
+    conn: &CoreConnection,
+    db: &LocalDb,
+    full: bool,
+) -> Result<Value, String> {
+    let scope = tenants::cache_scope(&conn.profile_id);
+    let cursor = if full {
+        None
+    } else {
+        db::get_cursor(&*db.lock()?, &scope, CHANGES_STREAM).and_then(|c| c.parse::<i64>().ok())
+    };
+
+    let (mode, next, outcome) = match cursor {
+        Some(since) => match incremental(conn, db, &scope, since).await? {
+            Some((next, outcome)) => ("incremental", next, outcome),
+            None => {
+                let (next, outcome) = snapshot(app, conn, db, &scope).await?;
+                ("full", next, outcome)
+            }
+        },
+        None => {
+            let (next, outcome) = snapshot(app, conn, db, &scope).await?;
+            ("full", next, outcome)
+        }
+    };
+
+    db::set_cursor(&*db.lock()?, &scope, CHANGES_STREAM, &next.to_string())
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "mode": mode,
+        "cursor": next,
+        "changes": outcome.changes,
+        "updated": outcome.updated,
+        "removed": outcome.removed,
+    }))
+}
+
+/// Applies the changes after `since`, or returns `None` when there were
+/// more than one page holds.
+async fn incremental(
+    conn: &CoreConnection,
+    db: &LocalDb,
+    scope: &str,
+    since: i64,
+) -> Result<Option<(i64, Outcome)>, String> {
+    let path = format!("/events?since_id={}&limit={}", since, PAGE_LIMIT);
+    let events =
+        crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None).await?;
+    let events = events.as_array().cloned().unwrap_or_default();
+    if events.len() >= PAGE_LIMIT {
+        return Ok(None);
+    }
+
+    let next = events
+        .iter()
+        .filter_map(|e| e["id"].as_i64())
+        .fold(since, i64::max);
+    let touched: BTreeSet<(Kind, String)> = events
+        .iter()
+        .filter_map(|e| {
+            let kind = Kind::parse(e["entity_type"].as_str()?)?;
+            let id = e["entity_id"].as_str().filter(|id| !id.is_empty())?;
+            Some((kind, id.to_string()))
+        })
+        .collect();
+
+    let mut outcome = Outcome {
+        changes: events.len(),
+        ..Outcome::default()
+    };
+    for (kind, id) in touched {
+        let path = format!("{}/{}", kind.collection(), id);
+        match crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None)
+            .await
+        {
+            Ok(item) => {
+                let guard = db.lock()?;
+                let previous = previous_status(&guard, scope, kind, &id);
+                outcome.updated += upsert(&guard, scope, kind, &id, &item)
+                    .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+                if let Some(previous) = previous {
+                    chat_webhooks::status_changed(&conn.profile_id, &previous, &item);
+                }
+            }
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+                outcome.removed += remove(&*db.lock()?, scope, kind, &id)
+                    .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(Some((next, outcome)))
+}
+
+/// Replaces the cached plans and jobs with the core's current lists, every
+/// page of them, shown as `pagination:progress` walks named
+/// `sync:<profile>:<kind>`. A walk that fails part way replaces nothing,
+/// as the missing items would be dropped. The cursor is read first, so a
+/// change landing mid-snapshot is re-fetched by the next incremental sync
+/// rather than missed.
+async fn snapshot(
+    app: &AppHandle,
+    conn: &CoreConnection,
+    db: &LocalDb,
+    scope: &str,
+) -> Result<(i64, Outcome), String> {
+    let head = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/events?limit=1",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let next = head[0]["id"].as_i64().unwrap_or(0);
+
+    let mut outcome = Outcome::default();
+    for kind in KINDS {
+        let walk_id = format!("sync:{}:{}", conn.profile_id, kind.as_str());
+        let items = pagination::collect_all(
+            Some(app),
+            &walk_id,
+            conn,
+            kind.collection(),
+            SNAPSHOT_LIMIT,
+            SNAPSHOT_MAX_ITEMS,
+            None,
+        )
+        .await
+        .into_result()?;
+        let items: Vec<(String, Value)> = items
+            .iter()
+            .filter_map(|item| Some((item["id"].as_str()?.to_string(), item.clone())))
+            .collect();
+
+        let mut guard = db.lock()?;
+        let tx = guard
+            .transaction()
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        let (updated, removed) = replace_kind(&tx, &conn.profile_id, scope, *kind, &items)
+            .and_then(|counts| tx.commit().map(|_| counts))
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        outcome.updated += updated;
+        outcome.removed += removed;
+    }
+    Ok((next, outcome))
+}
+
+/// The cached status of a plan about to be replaced; `None` for jobs and
+/// for plans seen for the first time, whose status did not change.
+fn previous_status(
+    conn: &Connection,
+    profile_id: &str,
+    kind: Kind,
+    entity_id: &str,
+) -> Option<String> {
+    if kind != Kind::Plan {
+        return None;
+    }
+    conn.query_row(
+        "SELECT json_extract(payload, '$.status') FROM sync_entities
+         WHERE profile_id = ?1 AND kind = 'plan' AND entity_id = ?2",
+        params![profile_id, entity_id],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .ok()
+    .flatten()
+}
+
+fn upsert(
+    conn: &Connection,
+    profile_id: &str,
+    kind: Kind,
+    entity_id: &str,
+    item: &Value,
+) -> rusqlite::Result<usize> {
+    let payload = item.to_string();
+    let changed = conn.execute(
+        "INSERT INTO sync_entities (profile_id, kind, entity_id, payload, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(profile_id, kind, entity_id) DO UPDATE SET payload = excluded.payload,
+             synced_at = excluded.synced_at
+         WHERE payload != excluded.payload",
+        params![
+            profile_id,
+            kind.as_str(),
+            entity_id,
+            payload,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    if changed > 0 {
+        record_history(conn, profile_id, kind.as_str(), entity_id, Some(&payload))?;
+    }
+    Ok(changed)
+}
+
+/// Appends an entity's new state (`None` once the core no longer has it) to
+/// the history `time_travel` rebuilds past dashboards from.
+fn record_history(
+    conn: &Connection,
+    profile_id: &str,
+    kind: &str,
+    entity_id: &str,
+    payload: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_history (profile_id, kind, entity_id, payload, recorded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            profile_id,
+            kind,
+            entity_id,
+            payload,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+fn remove(
+    conn: &Connection,
+    profile_id: &str,
+    kind: Kind,
+    entity_id: &str,
+) -> rusqlite::Result<usize> {
+    let removed = conn.execute(
+        "DELETE FROM sync_entities WHERE profile_id = ?1 AND kind = ?2 AND entity_id = ?3",
+        params![profile_id, kind.as_str(), entity_id],
+    )?;
+    if removed > 0 {
+        record_history(conn, profile_id, kind.as_str(), entity_id, None)?;
+    }
+    Ok(removed)
+}
+
+/// Returns how many rows changed and how many were dropped.
+fn replace_kind(
+    conn: &Connection,
+    profile_id: &str,
+    scope: &str,
+    kind: Kind,
+    items: &[(String, Value)],
+) -> rusqlite::Result<(usize, usize)> {
+    let keep: BTreeSet<&str> = items.iter().map(|(id, _)| id.as_str()).collect();
+    let mut stale = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare("SELECT entity_id FROM sync_entities WHERE profile_id = ?1 AND kind = ?2")?;
+        let rows = stmt.query_map(params![scope, kind.as_str()], |row| row.get::<_, String>(0))?;
+        for id in rows {
+            let id = id?;
+            if !keep.contains(id.as_str()) {
+                stale.push(id);
+            }
+        }
+    }
+    for id in &stale {
+        remove(conn, scope, kind, id)?;
+    }
+    let mut updated = 0;
+    for (id, item) in items {
+        let previous = previous_status(conn, scope, kind, id);
+        updated += upsert(conn, scope, kind, id, item)?;
+        if let Some(previous) = previous {
+            chat_webhooks::status_changed(profile_id, &previous, item);
+        }
+    }
+    Ok((updated, stale.len()))
+}
+
+/// Sets a cached plan's status ahead of the next sync, for changes the
+/// shell made itself. Returns whether the plan was cached.
+pub fn set_plan_status(
+    conn: &Connection,
+    profile_id: &str,
+    plan_id: &str,
+    status: &str,
+) -> rusqlite::Result<bool> {
+    let scope = tenants::cache_scope(profile_id);
+    let changed = conn.execute(
+        "UPDATE sync_entities SET payload = json_set(payload, '$.status', ?3), synced_at = ?4
+         WHERE profile_id = ?1 AND kind = 'plan' AND entity_id = ?2",
+        params![scope, plan_id, status, chrono::Utc::now().to_rfc3339()],
+    )?;
+    if changed > 0 {
+        conn.execute(
+            "INSERT INTO sync_history (profile_id, kind, entity_id, payload, recorded_at)
+             SELECT profile_id, kind, entity_id, payload, synced_at FROM sync_entities
+             WHERE profile_id = ?1 AND kind = 'plan' AND entity_id = ?2",
+            params![scope, plan_id],
+        )?;
+    }
+    Ok(changed > 0)
+}
+
+/// Pulls the core's changes since the last sync (`full` re-downloads
+/// everything) and reconciles them into the local plan and job cache.
+#[tauri::command]
+pub async fn sync_changes(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    full: Option<bool>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let result = trace_context::within(
+        None,
+        "sync",
+        sync_profile(&app, &conn, &db, full.unwrap_or(false)),
+    )
+    .await?;
+    if result["updated"].as_u64() > Some(0) || result["removed"].as_u64() > Some(0) {
+        let _ = environment::emit(&app, CHANGED_EVENT, &result);
+    }
+    Ok(result)
+}
+
+/// Reads the cached plans or jobs, newest sync first; available offline.
+#[tauri::command]
+pub fn get_synced(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    kind: String,
+    limit: Option<usize>,
+) -> Result<Value, String> {
+    let kind = Kind::parse(kind.trim())
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Kind must be `plans` or `jobs`"))?;
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let scope = tenants::cache_scope(&profile_id);
+    let limit = limit.unwrap_or(SNAPSHOT_LIMIT).clamp(1, SNAPSHOT_LIMIT);
+    let guard = db.lock()?;
+    let cursor = db::get_cursor(&guard, &scope, CHANGES_STREAM);
+    let mut stmt = guard
+        .prepare(
+            "SELECT payload FROM sync_entities WHERE profile_id = ?1 AND kind = ?2
+             ORDER BY synced_at DESC LIMIT ?3",
+        )
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+    let items: Vec<Value> = stmt
+        .query_map(params![scope, kind.as_str(), limit as i64], |row| {
+            row.get::<_, String>(0)
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?
+        .iter()
+        .filter_map(|raw| serde_json::from_str(raw).ok())
+        .collect();
+    Ok(json!({
+        "profile_id": profile_id,
+        "kind": kind.as_str(),
+        "cursor": cursor,
+        "items": items,
+    }))
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tokio::task::AbortHandle;
+
+use crate::bandwidth;
+use crate::errors::ErrorCode;
+
+type Body = Arc<dyn Fn(AppHandle) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// One long-running background subsystem (poller, watcher, scheduler).
+struct Task {
+    body: Body,
+    app: AppHandle,
+    abort: Option<AbortHandle>,
+    /// Bumped on every start, so a stopped run cannot overwrite the
+    /// status of the run that replaced it.
+    generation: u64,
+    status: &'static str,
+    started_at: DateTime<Utc>,
+    last_run: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+    runs: u64,
+    restarts: u32,
+}
+
+static TASKS: OnceLock<Mutex<BTreeMap<&'static str, Task>>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<BTreeMap<&'static str, Task>> {
+    TASKS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Starts `body` as the background task `name` and keeps it in the
+/// registry, so it can be listed and restarted. The body gets its own
+/// handle to the app each time it is (re)started.
+pub fn spawn<F, Fut>(app: &AppHandle, name: &'static str, body: F)
+where
+    F: Fn(AppHandle) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let body: Body = Arc::new(move |app| Box::pin(body(app)));
+    if let Ok(mut tasks) = registry().lock() {
+        tasks.insert(
+            name,
+            Task {
+                body,
+                app: app.clone(),
+                abort: None,
+                generation: 0,
+                status: "starting",
+                started_at: Utc::now(),
+                last_run: None,
+                last_error: None,
+                last_error_at: None,
+                runs: 0,
+                restarts: 0,
+            },
+        );
+    }
+    start(name);
+}
+
+fn start(name: &'static str) {
+    let Ok(mut tasks) = registry().lock() else {
+        return;
+    };
+    let Some(task) = tasks.get_mut(name) else {
+        return;
+    };
+    task.generation += 1;
+    task.status = "running";
+    task.started_at = Utc::now();
+    let generation = task.generation;
+    let handle = tauri::async_runtime::spawn(bandwidth::within(
+        bandwidth::POLLING,
+        (task.body)(task.app.clone()),
+    ));
+    task.abort = Some(handle.inner().abort_handle());
+    drop(tasks);
+
+    // Notices how the run ended: returned, was stopped, or panicked.
+    tauri::async_runtime::spawn(async move {
+        let ended = handle.await;
+        let Ok(mut tasks) = registry().lock() else {
+            return;
+        };
+        let Some(task) = tasks.get_mut(name).filter(|t| t.generation == generation) else {
+            return;
+        };
+        task.abort = None;
+        match ended {
+            Ok(()) => task.status = "finished",
+            Err(tauri::Error::JoinError(err)) if err.is_cancelled() => task.status = "stopped",
+            Err(err) => {
+                task.status = "panicked";
+                task.last_error = Some(format!("Task panicked: {}", err));
+                task.last_error_at = Some(Utc::now());
+            }
+        }
+    });
+}
+
+/// Records that one pass of the task's loop completed.
+pub fn ran(name: &str) {
+    if let Ok(mut tasks) = registry().lock() {
+        if let Some(task) = tasks.get_mut(name) {
+            task.last_run = Some(Utc::now());
+            task.runs += 1;
+        }
+    }
+}
+
+/// Records an error from the task's current pass; it is kept after later
+/// passes succeed, with its time.
+pub fn failed(name: &str, err: &str) {
+    if let Ok(mut tasks) = registry().lock() {
+        if let Some(task) = tasks.get_mut(name) {
+            task.last_error = Some(err.to_string());
+            task.last_error_at = Some(Utc::now());
+        }
+    }
+}
+
+fn describe(name: &str, task: &Task) -> Value {
+    json!({
+        "name": name,
+        "status": task.status,
+        "started_at": task.started_at.to_rfc3339(),
+        "last_run_at": task.last_run.map(|t| t.to_rfc3339()),
+        "runs": task.runs,
+        "last_error": task.last_error,
+        "last_error_at": task.last_error_at.map(|t| t.to_rfc3339()),
+        "restarts": task.restarts,
+    })
+}
+
+#[tauri::command]
+pub fn list_background_tasks() -> Result<Value, String> {
+    let tasks = registry()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Task registry poisoned"))?;
+    let list: Vec<Value> = tasks
+        .iter()
+        .map(|(name, task)| describe(name, task))
+        .collect();
+    Ok(json!({ "tasks": list }))
+}
+
+/// Stops the named task wherever it is in its loop and starts it afresh,
+/// e.g. after it panicked or seems stuck. Its counters carry over.
+#[tauri::command]
+pub fn restart_task(name: String) -> Result<Value, String> {
+    let key = {
+        let mut tasks = registry()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Task registry poisoned"))?;
+        let (key, task) = tasks
+            .iter_mut()
+            .find(|(key, _)| **key == name)
+            .ok_or_else(|| {
+                ErrorCode::NotFound.with(format!("Unknown background task: {}", name))
+            })?;
+        if let Some(abort) = task.abort.take() {
+            abort.abort();
+        }
+        task.restarts += 1;
+        *key
+    };
+    start(key);
+    let tasks = registry()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Task registry poisoned"))?;
+    Ok(tasks
+        .get(key)
+        .map(|task| describe(key, task))
+        .unwrap_or(Value::Null))
+}
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::errors::ErrorCode;
+use crate::settings::TelemetrySettings;
+use crate::{net, tasks};
+
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Spans held while the collector is slow or down; the oldest go first.
+const MAX_BUFFERED: usize = 2048;
+const MAX_BATCH: usize = 512;
+const SCOPE_NAME: &str = "novaadapt-desktop";
+
+/// OTLP span kinds.
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Internal = 1,
+    Client = 3,
+}
+
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub kind: Kind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, Value)>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Exporter {
+    settings: TelemetrySettings,
+    buffer: VecDeque<Span>,
+    last_error: Option<String>,
+    last_export: Option<DateTime<Utc>>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EXPORTED: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static EXPORTER: OnceLock<Mutex<Exporter>> = OnceLock::new();
+
+fn exporter() -> &'static Mutex<Exporter> {
+    EXPORTER.get_or_init(|| Mutex::new(Exporter::default()))
+}
+
+pub fn configure(settings: &TelemetrySettings) {
+    let mut exporter = exporter().lock().unwrap_or_else(|e| e.into_inner());
+    exporter.settings = settings.clone();
+    if !settings.enabled {
+        exporter.buffer.clear();
+    }
+    ENABLED.store(
+        settings.enabled && !settings.endpoint.trim().is_empty(),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn validate(settings: &TelemetrySettings) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&settings.sample_ratio) {
+        return Err("Telemetry sample ratio must be between 0 and 1".to_string());
+    }
+    if !settings.enabled {
+        return Ok(());
+    }
+    let parsed = Url::parse(settings.endpoint.trim())
+        .map_err(|e| format!("Invalid telemetry endpoint: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Telemetry endpoint must be http(s)".to_string());
+    }
+    for name in settings.headers.keys() {
+        reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("Invalid telemetry header name: {}", name))?;
+    }
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether spans of `trace_id` are exported. The decision hangs off the
+/// trace id alone, so every span of a trace agrees; with export off every
+/// trace counts as sampled, leaving the choice to the core.
+pub fn sampled(trace_id: &str) -> bool {
+    if !enabled() {
+        return true;
+    }
+    let ratio = exporter()
+        .lock()
+        .map(|e| e.settings.sample_ratio)
+        .unwrap_or(1.0);
+    if ratio >= 1.0 {
+        return true;
+    }
+    let bucket = trace_id
+        .get(..16)
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .unwrap_or(0);
+    (bucket as f64 / u64::MAX as f64) < ratio
+}
+
+/// Queues a finished span for export; a no-op while export is off.
+pub fn record(span: Span) {
+    if !enabled() || !sampled(&span.trace_id) {
+        return;
+    }
+    let Ok(mut exporter) = exporter().lock() else {
+        return;
+    };
+    if exporter.buffer.len() >= MAX_BUFFERED {
+        exporter.buffer.pop_front();
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    exporter.buffer.push_back(span);
+}
+
+fn unix_nanos(at: SystemTime) -> String {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        // OTLP/JSON carries 64-bit integers as strings.
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+fn key_values<'a>(attributes: impl IntoIterator<Item = (&'a str, &'a Value)>) -> Vec<Value> {
+    attributes
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": any_value(value) }))
+        .collect()
+}
+
+fn encode_span(span: &Span) -> Value {
+    let mut out = json!({
+        "traceId": span.trace_id,
+        "spanId": span.span_id,
+        "name": span.name,
+        "kind": span.kind as i32,
+        "startTimeUnixNano": unix_nanos(span.start),
+        "endTimeUnixNano": unix_nanos(span.end),
+        "attributes": key_values(span.attributes.iter().map(|(k, v)| (*k, v))),
+        "status": match &span.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        },
+    });
+    if let Some(parent) = &span.parent_span_id {
+        out["parentSpanId"] = json!(parent);
+    }
+    out
+}
+
+/// One OTLP `ExportTraceServiceRequest` in its JSON encoding.
+fn encode(settings: &TelemetrySettings, spans: &[Span]) -> Value {
+    let service = match settings.service_name.trim() {
+        "" => SCOPE_NAME,
+        name => name,
+    };
+    let resource = [
+        ("service.name", json!(service)),
+        ("service.version", json!(env!("CARGO_PKG_VERSION"))),
+        ("os.type", json!(std::env::consts::OS)),
+    ];
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": key_values(resource.iter().map(|(k, v)| (*k, v))) },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans.iter().map(encode_span).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+fn traces_url(endpoint: &str) -> Result<Url, String> {
+    let endpoint = endpoint.trim().trim_end_matches('/');
+    let url = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    Url::parse(&url).map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid endpoint: {}", e)))
+}
+
+async fn export_batch() -> Result<usize, String> {
+    let (settings, batch) = {
+        let mut exporter = exporter()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Telemetry state poisoned"))?;
+        let take = exporter.buffer.len().min(MAX_BATCH);
+        let batch: Vec<Span> = exporter.buffer.drain(..take).collect();
+        (exporter.settings.clone(), batch)
+    };
+    if batch.is_empty() {
+        return Ok(0);
+    }
+    let url = traces_url(&settings.endpoint)?;
+    let mut req = net::client_for(&url)?
+        .post(url)
+        .timeout(EXPORT_TIMEOUT)
+        .json(&encode(&settings, &batch));
+    for (name, value) in &settings.headers {
+        req = req.header(name.trim(), value);
+    }
+    let result = match req.send().await {
+        Ok(response) if response.status().is_success() => Ok(batch.len()),
+        Ok(response) => Err(ErrorCode::from_status(response.status().as_u16())
+            .with(format!("Collector answered {}", response.status().as_u16()))),
+        Err(e) => Err(ErrorCode::CoreUnreachable.with(format!("Collector unreachable: {}", e))),
+    };
+    match &result {
+        Ok(count) => {
+            EXPORTED.fetch_add(*count as u64, Ordering::Relaxed);
+        }
+        // A failed batch is dropped rather than re-queued, so a collector
+        // that rejects it cannot wedge the buffer.
+        Err(_) => {
+            DROPPED.fetch_add(batch.len() as u64, Ordering::Relaxed);
+        }
+    }
+    if let Ok(mut exporter) = exporter().lock() {
+        match &result {
+            Ok(_) => {
+                exporter.last_error = None;
+                exporter.last_export = Some(Utc::now());
+            }
+            Err(err) => exporter.last_error = Some(err.clone()),
+        }
+    }
+    result
+}
+
+const TASK: &str = "telemetry";
+
+/// Ships buffered spans to the collector every few seconds while export is
+/// enabled.
+pub fn spawn_exporter(app: AppHandle) {
+    tasks::spawn(&app, TASK, |_| async move {
+        loop {
+            tokio::time::sleep(EXPORT_INTERVAL).await;
+            if enabled() {
+                if let Err(err) = export_batch().await {
+                    tasks::failed(TASK, &err);
+                }
+            }
+            tasks::ran(TASK);
+        }
+    });
+}
+
+fn status() -> Value {
+    let Ok(exporter) = exporter().lock() else {
+        return Value::Null;
+    };
+    json!({
+        "enabled": enabled(),
+        "endpoint": exporter.settings.endpoint,
+        "sample_ratio": exporter.settings.sample_ratio,
+        "buffered": exporter.buffer.len(),
+        "exported": EXPORTED.load(Ordering::Relaxed),
+        "dropped": DROPPED.load(Ordering::Relaxed),
+        "last_export_at": exporter.last_export.map(|t| t.to_rfc3339()),
+        "last_error": exporter.last_error,
+    })
+}
+
+/// Exports what is buffered, if export is on; used on the way out.
+pub async fn flush() -> Result<usize, String> {
+    if !enabled() {
+        return Ok(0);
+    }
+    export_batch().await
+}
+
+#[tauri::command]
+pub fn get_telemetry_status() -> Value {
+    status()
+}
+
+/// Exports whatever is buffered now, e.g. to check a collector setup.
+#[tauri::command]
+pub async fn flush_telemetry() -> Result<Value, String> {
+    if !enabled() {
+        return Err(ErrorCode::InvalidInput.with("Telemetry export is not enabled"));
+    }
+    let sent = export_batch().await?;
+    let mut out = status();
+    out["sent"] = json!(sent);
+    Ok(out)
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use reqwest::header::HeaderName;
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::response_cache::ResponseCache;
+use crate::settings::{self, Profile, SettingsStore, TenantScope, TenantSettings};
+use crate::{environment, net};
+
+const CHANGED_EVENT: &str = "tenant:changed";
+const MAX_TENANT_ID: usize = 128;
+const MAX_TENANTS: usize = 500;
+/// Probed by health checks, which belong to no tenant.
+const UNSCOPED_PATHS: &[&str] = &["/health"];
+
+#[derive(Default)]
+struct Tenants {
+    /// Every endpoint's origin, to its profile.
+    profiles: HashMap<String, String>,
+    /// The profiles scoped to a tenant.
+    scoped: HashMap<String, TenantSettings>,
+}
+
+static TENANTS: OnceLock<Mutex<Tenants>> = OnceLock::new();
+
+fn tenants() -> &'static Mutex<Tenants> {
+    TENANTS.get_or_init(|| Mutex::new(Tenants::default()))
+}
+
+/// Tracks the profiles with a tenant set, by the origin of each endpoint
+/// they send requests to.
+pub fn configure(profiles: &[Profile]) {
+    let mut next = Tenants::default();
+    for profile in profiles
+        .iter()
+        .filter(|p| !p.tenant.tenant_id.trim().is_empty())
+    {
+        let endpoints = profile
+            .endpoints()
+            .chain(profile.read_routing.read_url.as_deref());
+        for origin in endpoints.filter_map(net::base_origin) {
+            next.profiles.insert(origin, profile.id.clone());
+        }
+        next.scoped
+            .insert(profile.id.clone(), profile.tenant.clone());
+    }
+    *tenants().lock().unwrap_or_else(|e| e.into_inner()) = next;
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_TENANT_ID
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+pub fn validate(tenant: &TenantSettings) -> Result<(), String> {
+    let id = tenant.tenant_id.trim();
+    if !id.is_empty() && !valid_id(id) {
+        return Err(format!(
+            "tenant.tenant_id must be at most {} letters, digits, `.`, `_` or `-`",
+            MAX_TENANT_ID
+        ));
+    }
+    match tenant.scope {
+        TenantScope::Header => {
+            HeaderName::from_bytes(tenant.header.trim().as_bytes())
+                .map_err(|_| format!("tenant.header `{}` is not a header name", tenant.header))?;
+        }
+        TenantScope::Path => {
+            if !tenant.path_prefix.starts_with('/') || !tenant.path_prefix.contains("{tenant}") {
+                return Err("tenant.path_prefix must start with `/` and contain `{tenant}`".into());
+            }
+        }
+    }
+    if !tenant.discovery_path.starts_with('/') {
+        return Err("tenant.discovery_path must start with `/`".into());
+    }
+    Ok(())
+}
+
+/// How a request names its tenant.
+pub enum Scope {
+    Header(String, String),
+    /// Goes before the request's path.
+    Prefix(String),
+}
+
+/// The tenant scope for a request to `base_url` at `path`, if its profile
+/// has a tenant. Discovery and health checks go out unscoped, as do paths
+/// that already carry the prefix.
+pub fn scope(base_url: &str, path: &str) -> Option<Scope> {
+    let origin = net::base_origin(base_url)?;
+    let state = tenants().lock().ok()?;
+    let tenant = state.scoped.get(state.profiles.get(&origin)?)?;
+    let bare = path.split(['?', '#']).next().unwrap_or(path);
+    if bare == tenant.discovery_path.trim_end_matches('/') || UNSCOPED_PATHS.contains(&bare) {
+        return None;
+    }
+    let id = tenant.tenant_id.trim();
+    match tenant.scope {
+        TenantScope::Header => Some(Scope::Header(
+            tenant.header.trim().to_string(),
+            id.to_string(),
+        )),
+        TenantScope::Path => {
+            let prefix = tenant
+                .path_prefix
+                .trim_end_matches('/')
+                .replace("{tenant}", id);
+            let scoped = bare == prefix || bare.starts_with(&format!("{}/", prefix));
+            (!scoped).then_some(Scope::Prefix(prefix))
+        }
+    }
+}
+
+/// The tenant requests to `base_url` are scoped to, for keys of caches
+/// that are shared across profiles.
+pub fn tag(base_url: &str) -> Option<String> {
+    let origin = net::base_origin(base_url)?;
+    let state = tenants().lock().ok()?;
+    let profile_id = state.profiles.get(&origin)?;
+    Some(state.scoped.get(profile_id)?.tenant_id.trim().to_string())
+}
+
+/// The key `profile_id`'s rows in the local plan and job cache (and its
+/// sync cursor) are filed under: the profile id, tagged with its tenant
+/// when it has one, so one tenant's plans are never shown as another's
+/// and each tenant syncs from its own cursor.
+pub fn cache_scope(profile_id: &str) -> String {
+    let tenant = tenants().lock().ok().and_then(|s| {
+        s.scoped
+            .get(profile_id)
+            .map(|t| t.tenant_id.trim().to_string())
+    });
+    match tenant {
+        Some(tenant) => format!("{}#tenant:{}", profile_id, tenant),
+        None => profile_id.to_string(),
+    }
+}
+
+/// `[{id, name}]` from a bare list, or one under `tenants`, `items` or
+/// `data`; entries may be plain ids.
+fn listed(raw: &Value) -> Vec<Value> {
+    let list = raw.as_array().or_else(|| {
+        ["tenants", "items", "data"]
+            .iter()
+            .find_map(|k| raw[*k].as_array())
+    });
+    list.into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let id = match entry {
+                Value::String(id) => id.clone(),
+                _ => entry["id"]
+                    .as_str()
+                    .or(entry["tenant_id"].as_str())?
+                    .to_string(),
+            };
+            let name = entry["name"]
+                .as_str()
+                .or(entry["display_name"].as_str())
+                .unwrap_or(&id)
+                .to_string();
+            Some(json!({ "id": id, "name": name }))
+        })
+        .take(MAX_TENANTS)
+        .collect()
+}
+
+async fn discover(
+    conn: &settings::Connection,
+    tenant: &TenantSettings,
+) -> Result<Vec<Value>, String> {
+    let raw = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &tenant.discovery_path,
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    Ok(listed(&raw))
+}
+
+fn tenant_of(store: &SettingsStore, profile_id: &str) -> TenantSettings {
+    store
+        .snapshot()
+        .profile(profile_id)
+        .map(|p| p.tenant.clone())
+        .unwrap_or_default()
+}
+
+/// The tenants the core at the profile lists for its token, from
+/// `tenant.discovery_path`, with the one requests are scoped to marked.
+#[tauri::command]
+pub async fn list_tenants(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let tenant = tenant_of(&store, &conn.profile_id);
+    let current = tenant.tenant_id.trim().to_string();
+    let mut tenants = discover(&conn, &tenant).await?;
+    for entry in &mut tenants {
+        entry["current"] = json!(entry["id"] == current.as_str());
+    }
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "current": (!current.is_empty()).then_some(current),
+        "scope": tenant.scope,
+        "tenants": tenants,
+    }))
+}
+
+/// Scopes the profile's requests to `tenant_id` from now on (through
+/// `tenant.header` or `tenant.path_prefix`), or to none when it is empty.
+/// A tenant the core's discovery does not list is refused; cores without
+/// discovery take it on trust. The profile's cached plans and jobs and its
+/// sync cursor switch with it, and `tenant:changed` is emitted so views
+/// reload.
+#[tauri::command]
+pub async fn switch_tenant(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    cache: State<'_, ResponseCache>,
+    profile: Option<String>,
+    tenant_id: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let tenant_id = tenant_id.unwrap_or_default().trim().to_string();
+    if !tenant_id.is_empty() && !valid_id(&tenant_id) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Tenant ids are at most {} letters, digits, `.`, `_` or `-`",
+            MAX_TENANT_ID
+        )));
+    }
+    let tenant = tenant_of(&store, &conn.profile_id);
+    if !tenant_id.is_empty() {
+        match discover(&conn, &tenant).await {
+            Ok(list) if !list.is_empty() && !list.iter().any(|t| t["id"] == tenant_id.as_str()) => {
+                return Err(ErrorCode::NotFound.with(format!(
+                    "The core lists no tenant {} for this profile",
+                    tenant_id
+                )));
+            }
+            Ok(_) => {}
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let previous = tenant.tenant_id.trim().to_string();
+    let updated = store.update(&app, |settings| {
+        let profile = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == conn.profile_id)
+            .ok_or_else(|| {
+                ErrorCode::NotFound.with(format!("Unknown profile: {}", conn.profile_id))
+            })?;
+        profile.tenant.tenant_id = tenant_id.clone();
+        Ok(())
+    })?;
+    if let Some(profile) = updated.profile(&conn.profile_id) {
+        for endpoint in profile.endpoints() {
+            cache.invalidate(endpoint);
+        }
+    }
+    let _ = db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "tenant",
+        "switched",
+        &tenant_id,
+        &json!({ "from": previous, "to": tenant_id }),
+    );
+    let summary = json!({
+        "profile_id": conn.profile_id,
+        "tenant_id": (!tenant_id.is_empty()).then_some(&tenant_id),
+        "previous": (!previous.is_empty()).then_some(&previous),
+        "cache_scope": cache_scope(&conn.profile_id),
+    });
+    let _ = environment::emit(&app, CHANGED_EVENT, &summary);
+    Ok(summary)
+}
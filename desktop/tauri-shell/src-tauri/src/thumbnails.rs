@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::{ImageFormat, ImageReader, Limits};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+use crate::{attachments, bandwidth};
+
+pub const SCHEME: &str = "thumb";
+const DEFAULT_EDGE: u32 = 256;
+const MIN_EDGE: u32 = 32;
+const MAX_EDGE: u32 = 1024;
+pub const CACHE_DIR: &str = "thumbnails";
+/// Decoding is bounded so a crafted image cannot exhaust memory.
+const MAX_SOURCE_DIMENSION: u32 = 16_384;
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+/// Formats the image crate is built to decode here.
+pub const THUMBNAIL_MIME: &[&str] = &["image/png"];
+
+/// The URL the webview loads a thumbnail from. Windows serves custom
+/// schemes over `http://<scheme>.localhost`.
+pub fn url(sha256: &str, profile: &str, plan_id: &str, attachment_id: &str) -> String {
+    let base = if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    };
+    let mut url = Url::parse(&format!("{}/{}", base, sha256)).expect("thumbnail base URL parses");
+    url.query_pairs_mut()
+        .append_pair("profile", profile)
+        .append_pair("plan", plan_id)
+        .append_pair("attachment", attachment_id);
+    url.to_string()
+}
+
+/// Serves `thumb://localhost/<sha256>?profile=&plan=&attachment=&size=`:
+/// a PNG no larger than `size` pixels (256 by default) on its longer edge.
+/// Thumbnails are cached on disk by the content hash, so a cached one is
+/// served without asking the core; a fresh one is only generated after the
+/// fetched content is checked against that hash. In low-data mode only
+/// cached thumbnails are served.
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    let uri = request.uri().to_string();
+    tauri::async_runtime::spawn(async move {
+        let response = match serve(&app, &uri).await {
+            Ok(png) => Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "image/png")
+                .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+                .body(Cow::Owned(png)),
+            Err(err) => {
+                let status = match errors::code_of(&err) {
+                    Some(ErrorCode::InvalidInput) => StatusCode::BAD_REQUEST,
+                    Some(ErrorCode::NotFound) => StatusCode::NOT_FOUND,
+                    Some(ErrorCode::Forbidden) => StatusCode::FORBIDDEN,
+                    _ => StatusCode::BAD_GATEWAY,
+                };
+                Response::builder()
+                    .status(status)
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(Cow::Owned(err.into_bytes()))
+            }
+        };
+        match response {
+            Ok(response) => responder.respond(response),
+            Err(e) => responder.respond(
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Cow::Owned(e.to_string().into_bytes()))
+                    .unwrap_or_default(),
+            ),
+        }
+    });
+}
+
+fn cache_dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("No cache directory: {}", e)))?
+        .join(CACHE_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Create thumbnail cache failed: {}", e)))?;
+    Ok(dir)
+}
+
+fn edge_of(size: &str) -> u32 {
+    size.parse::<u32>()
+        .unwrap_or(DEFAULT_EDGE)
+        .clamp(MIN_EDGE, MAX_EDGE)
+}
+
+/// Where the thumbnail of `sha256` at `size` pixels is cached, whether or
+/// not it has been made yet.
+pub fn cached_path<R: Runtime>(
+    app: &AppHandle<R>,
+    sha256: &str,
+    size: &str,
+) -> Result<PathBuf, String> {
+    Ok(cache_dir(app)?.join(format!("{}-{}.png", sha256, edge_of(size))))
+}
+
+async fn serve<R: Runtime>(app: &AppHandle<R>, uri: &str) -> Result<Vec<u8>, String> {
+    let parsed = Url::parse(uri)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid thumbnail URL: {}", e)))?;
+    let sha256 = parsed.path().trim_matches('/').to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ErrorCode::InvalidInput.with("Thumbnail URL must name a SHA-256 hash"));
+    }
+    let query = |key: &str| {
+        parsed
+            .query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    let size = query("size").unwrap_or_default();
+    let edge = edge_of(&size);
+    let cached = cached_path(app, &sha256, &size)?;
+    if let Ok(png) = fs::read(&cached) {
+        return Ok(png);
+    }
+    if bandwidth::low_data() {
+        return Err(ErrorCode::Forbidden.with("Thumbnails are off in low-data mode"));
+    }
+
+    let (Some(plan_id), Some(attachment_id)) = (query("plan"), query("attachment")) else {
+        return Err(ErrorCode::NotFound.with("Thumbnail not cached and no attachment named"));
+    };
+    let profile = query("profile").filter(|p| !p.is_empty());
+    let fetched = attachments::fetch(
+        &app.state::<SettingsStore>(),
+        profile.as_deref(),
+        &plan_id,
+        &attachment_id,
+    )
+    .await?;
+    let actual: String = Sha256::digest(&fetched.bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != sha256 {
+        return Err(ErrorCode::CoreError.with("Attachment content does not match its hash"));
+    }
+
+    let png = tauri::async_runtime::spawn_blocking(move || render(&fetched.bytes, edge))
+        .await
+        .map_err(|e| ErrorCode::Internal.with(format!("Thumbnail task failed: {}", e)))??;
+    // Written beside the final name and renamed, so a reader never sees a
+    // partial file.
+    let partial = cached.with_extension("part");
+    if fs::write(&partial, &png).is_ok() && fs::rename(&partial, &cached).is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    Ok(png)
+}
+
+fn render(bytes: &[u8], edge: u32) -> Result<Vec<u8>, String> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Read image failed: {}", e)))?;
+    if reader.format().is_none() {
+        return Err(ErrorCode::InvalidInput.with("Not an image format thumbnails are made for"));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    reader.limits(limits);
+    let source = reader
+        .decode()
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Decode image failed: {}", e)))?;
+    let thumbnail = if source.width() <= edge && source.height() <= edge {
+        source
+    } else {
+        source.thumbnail(edge, edge)
+    };
+    let mut png = Vec::new();
+    thumbnail
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| ErrorCode::Internal.with(format!("Encode thumbnail failed: {}", e)))?;
+    Ok(png)
+}
@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::{Method, Url};
+use rusqlite::{params, Connection as DbConnection, OptionalExtension};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Profile, SettingsStore, TicketProvider, TicketingSettings};
+use crate::{environment, net, notify, secrets, tasks};
+
+const TASK: &str = "ticketing";
+const SYNCED_EVENT: &str = "ticket:synced";
+const FAILED_EVENT: &str = "ticket:failed";
+const SYNC_INTERVAL: Duration = Duration::from_secs(15);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const BATCH: usize = 20;
+/// A decision still not filed after this many attempts waits for the
+/// operator to retry it.
+const MAX_ATTEMPTS: u32 = 8;
+const FIRST_RETRY_SECS: i64 = 30;
+const MAX_RETRY_SECS: i64 = 60 * 60;
+/// Jira caps summaries at 255 characters.
+const MAX_SUMMARY_CHARS: usize = 240;
+
+struct Decided {
+    profile_id: String,
+    plan_id: String,
+    decision: String,
+    detail: Value,
+}
+
+/// Ticketing settings by core origin, with the profile they belong to.
+static CONFIG: OnceLock<Mutex<HashMap<String, (String, TicketingSettings)>>> = OnceLock::new();
+/// Decisions sent since the last sync pass, which moves them to the outbox.
+static DECIDED: OnceLock<Mutex<Vec<Decided>>> = OnceLock::new();
+
+fn config() -> &'static Mutex<HashMap<String, (String, TicketingSettings)>> {
+    CONFIG.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn decided_queue() -> &'static Mutex<Vec<Decided>> {
+    DECIDED.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+pub fn ticketing_secret_key(profile_id: &str) -> String {
+    format!("profile:{}:ticketing_secret", profile_id)
+}
+
+pub fn configure(profiles: &[Profile]) {
+    let map = profiles
+        .iter()
+        .filter(|p| p.ticketing.enabled)
+        .flat_map(|p| {
+            p.endpoints()
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, (p.id.clone(), p.ticketing.clone())))
+        })
+        .collect();
+    *config().lock().unwrap_or_else(|e| e.into_inner()) = map;
+}
+
+pub fn validate(settings: &TicketingSettings) -> Result<(), String> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    let parsed =
+        Url::parse(settings.url.trim()).map_err(|e| format!("Invalid ticketing URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Ticketing URL must be http(s)".to_string());
+    }
+    if settings.username.trim().is_empty() {
+        return Err("Ticketing needs a user name".to_string());
+    }
+    match settings.provider {
+        TicketProvider::Jira => {
+            let project = settings.project.trim();
+            if project.is_empty()
+                || !project
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err("Jira ticketing needs a project key".to_string());
+            }
+        }
+        TicketProvider::ServiceNow => {
+            if !table(settings)
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err("ServiceNow table must be a table name".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn provider_name(provider: TicketProvider) -> &'static str {
+    match provider {
+        TicketProvider::Jira => "jira",
+        TicketProvider::ServiceNow => "servicenow",
+    }
+}
+
+fn table(settings: &TicketingSettings) -> &str {
+    match settings.table.trim() {
+        "" => "incident",
+        table => table,
+    }
+}
+
+fn issue_type(settings: &TicketingSettings) -> &str {
+    match settings.issue_type.trim() {
+        "" => "Task",
+        issue_type => issue_type,
+    }
+}
+
+/// Queues a decision the core accepted for filing, when the profile that
+/// owns `base_url` has ticketing enabled. Called by `decisions` once the
+/// decision is sent, whichever path sent it.
+pub fn decided(base_url: &str, plan_id: &str, decision: &str, payload: Option<&Value>) {
+    let Some(origin) = net::base_origin(base_url) else {
+        return;
+    };
+    let Some(profile_id) = config()
+        .lock()
+        .ok()
+        .and_then(|c| c.get(&origin).map(|(id, _)| id.clone()))
+    else {
+        return;
+    };
+    let payload = payload.cloned().unwrap_or(Value::Null);
+    let detail = json!({
+        "execute": payload["execute"],
+        "reason": payload["reason"],
+        "step_gated": payload["step_gated"],
+        "decided_at": stamp(Utc::now()),
+    });
+    if let Ok(mut queue) = decided_queue().lock() {
+        queue.push(Decided {
+            profile_id,
+            plan_id: plan_id.to_string(),
+            decision: decision.to_string(),
+            detail,
+        });
+    }
+}
+
+fn stamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn storage(err: rusqlite::Error) -> String {
+    ErrorCode::LocalStorage.with(err.to_string())
+}
+
+struct Link {
+    ticket_id: String,
+    ticket_key: String,
+    ticket_url: Option<String>,
+}
+
+fn link_for(
+    conn: &DbConnection,
+    profile_id: &str,
+    plan_id: &str,
+    provider: &str,
+) -> rusqlite::Result<Option<Link>> {
+    conn.query_row(
+        "SELECT ticket_id, ticket_key, ticket_url FROM ticket_links
+         WHERE profile_id = ?1 AND plan_id = ?2 AND provider = ?3",
+        params![profile_id, plan_id, provider],
+        |row| {
+            Ok(Link {
+                ticket_id: row.get(0)?,
+                ticket_key: row.get(1)?,
+                ticket_url: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+fn save_link(
+    conn: &DbConnection,
+    profile_id: &str,
+    plan_id: &str,
+    provider: &str,
+    link: &Link,
+) -> rusqlite::Result<()> {
+    let now = stamp(Utc::now());
+    conn.execute(
+        "INSERT INTO ticket_links
+             (profile_id, plan_id, provider, ticket_id, ticket_key, ticket_url, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+         ON CONFLICT(profile_id, plan_id, provider) DO UPDATE SET
+             ticket_id = excluded.ticket_id,
+             ticket_key = excluded.ticket_key,
+             ticket_url = excluded.ticket_url,
+             updated_at = excluded.updated_at",
+        params![
+            profile_id,
+            plan_id,
+            provider,
+            link.ticket_id,
+            link.ticket_key,
+            link.ticket_url,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+fn truncated(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn decision_label(decision: &str, detail: &Value) -> &'static str {
+    match decision {
+        "approve" if detail["step_gated"].as_bool() == Some(true) => "approved step by step",
+        "approve" if detail["execute"].as_bool() == Some(true) => "approved for execution",
+        "approve" => "approved",
+        _ => "rejected",
+    }
+}
+
+/// What the ticket says about the plan and the decision; `plan` is `Null`
+/// when the core could not be asked.
+fn describe(plan_id: &str, plan: &Value, decision: &str, detail: &Value) -> (String, String) {
+    let objective = plan["objective"].as_str().unwrap_or_default().trim();
+    let label = decision_label(decision, detail);
+    let summary = if objective.is_empty() {
+        format!("NovaAdapt plan {} {}", plan_id, label)
+    } else {
+        format!("NovaAdapt plan {} {}: {}", plan_id, label, objective)
+    };
+    let mut lines = vec![format!("Plan: {}", plan_id)];
+    if !objective.is_empty() {
+        lines.push(format!("Objective: {}", objective));
+    }
+    if let Some(risk) = ["risk", "risk_level"]
+        .iter()
+        .find_map(|k| plan[*k].as_str())
+    {
+        lines.push(format!("Risk: {}", risk));
+    }
+    let steps = crate::a11y::steps(plan);
+    if !steps.is_empty() {
+        lines.push(format!("Steps: {}", steps.len()));
+    }
+    lines.push(format!("Decision: {}", label));
+    if let Some(reason) = detail["reason"].as_str().filter(|r| !r.trim().is_empty()) {
+        lines.push(format!("Reason: {}", reason.trim()));
+    }
+    if let Some(at) = detail["decided_at"].as_str() {
+        lines.push(format!("Decided at: {}", at));
+    }
+    (truncated(&summary, MAX_SUMMARY_CHARS), lines.join("\n"))
+}
+
+fn endpoint(settings: &TicketingSettings, path: &str) -> Result<Url, String> {
+    let url = format!("{}{}", settings.url.trim().trim_end_matches('/'), path);
+    Url::parse(&url)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid ticketing URL: {}", e)))
+}
+
+async fn call(
+    settings: &TicketingSettings,
+    secret: &str,
+    method: Method,
+    url: Url,
+    body: Value,
+) -> Result<Value, String> {
+    let client = net::client_for(&url)?;
+    let response = client
+        .request(method, url)
+        .timeout(REQUEST_TIMEOUT)
+        .basic_auth(settings.username.trim(), Some(secret))
+        .header("Accept", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| {
+            let code = if e.is_timeout() {
+                ErrorCode::CoreTimeout
+            } else {
+                ErrorCode::CoreUnreachable
+            };
+            code.with(format!("Ticketing request failed: {}", e))
+        })?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        // Jira lists `errorMessages` and per-field `errors`; ServiceNow
+        // sends `error.message`.
+        let mut reasons: Vec<String> = body["errorMessages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m.as_str().map(str::to_string))
+            .collect();
+        if let Some(fields) = body["errors"].as_object() {
+            reasons.extend(fields.iter().map(|(k, v)| format!("{}: {}", k, v)));
+        }
+        if let Some(message) = body["error"]["message"].as_str() {
+            reasons.push(message.to_string());
+        }
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "{} {}: {}",
+            provider_name(settings.provider),
+            status.as_u16(),
+            reasons.join("; ")
+        )));
+    }
+    Ok(body)
+}
+
+/// Opens a ticket for the plan, or comments on the one opened for an
+/// earlier decision. A linked ticket that no longer exists is replaced.
+async fn file(
+    settings: &TicketingSettings,
+    secret: &str,
+    plan_id: &str,
+    summary: &str,
+    description: &str,
+    existing: Option<Link>,
+) -> Result<(Link, bool), String> {
+    if let Some(link) = existing {
+        let (path, method, body) = match settings.provider {
+            TicketProvider::Jira => (
+                format!("/rest/api/2/issue/{}/comment", link.ticket_key),
+                Method::POST,
+                json!({ "body": description }),
+            ),
+            TicketProvider::ServiceNow => (
+                format!("/api/now/table/{}/{}", table(settings), link.ticket_id),
+                Method::PATCH,
+                json!({ "work_notes": description }),
+            ),
+        };
+        match call(settings, secret, method, endpoint(settings, &path)?, body).await {
+            Ok(_) => return Ok((link, false)),
+            Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let link = match settings.provider {
+        TicketProvider::Jira => {
+            let created = call(
+                settings,
+                secret,
+                Method::POST,
+                endpoint(settings, "/rest/api/2/issue")?,
+                json!({
+                    "fields": {
+                        "project": { "key": settings.project.trim() },
+                        "issuetype": { "name": issue_type(settings) },
+                        "summary": summary,
+                        "description": description,
+                        "labels": ["novaadapt"],
+                    }
+                }),
+            )
+            .await?;
+            let key = created["key"].as_str().unwrap_or_default().to_string();
+            Link {
+                ticket_id: created["id"].as_str().unwrap_or(&key).to_string(),
+                ticket_url: (!key.is_empty()).then(|| {
+                    format!(
+                        "{}/browse/{}",
+                        settings.url.trim().trim_end_matches('/'),
+                        key
+                    )
+                }),
+                ticket_key: key,
+            }
+        }
+        TicketProvider::ServiceNow => {
+            let created = call(
+                settings,
+                secret,
+                Method::POST,
+                endpoint(settings, &format!("/api/now/table/{}", table(settings)))?,
+                json!({
+                    "short_description": summary,
+                    "description": description,
+                    "correlation_id": plan_id,
+                    "correlation_display": "NovaAdapt",
+                }),
+            )
+            .await?;
+            let sys_id = created["result"]["sys_id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            Link {
+                ticket_key: created["result"]["number"]
+                    .as_str()
+                    .unwrap_or(&sys_id)
+                    .to_string(),
+                ticket_url: (!sys_id.is_empty()).then(|| {
+                    format!(
+                        "{}/nav_to.do?uri={}.do?sys_id={}",
+                        settings.url.trim().trim_end_matches('/'),
+                        table(settings),
+                        sys_id
+                    )
+                }),
+                ticket_id: sys_id,
+            }
+        }
+    };
+    if link.ticket_id.is_empty() || link.ticket_key.is_empty() {
+        return Err(ErrorCode::CoreError.with(format!(
+            "{} did not say which ticket it created",
+            provider_name(settings.provider)
+        )));
+    }
+    Ok((link, true))
+}
+
+/// Files queued decisions every `SYNC_INTERVAL`, retrying failures with a
+/// doubling delay.
+pub fn spawn_sync(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            sync(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    });
+}
+
+struct Queued {
+    id: i64,
+    profile_id: String,
+    plan_id: String,
+    decision: String,
+    detail: Value,
+    attempts: u32,
+}
+
+fn take_due(conn: &DbConnection) -> rusqlite::Result<Vec<Queued>> {
+    let queued: Vec<Decided> = decided_queue()
+        .lock()
+        .map(|mut q| q.drain(..).collect())
+        .unwrap_or_default();
+    let now = stamp(Utc::now());
+    for entry in &queued {
+        conn.execute(
+            "INSERT INTO ticket_outbox
+                 (profile_id, plan_id, decision, detail, next_attempt_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![
+                entry.profile_id,
+                entry.plan_id,
+                entry.decision,
+                entry.detail.to_string(),
+                now
+            ],
+        )?;
+    }
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, plan_id, decision, detail, attempts FROM ticket_outbox
+         WHERE status = 'pending' AND next_attempt_at <= ?1
+         ORDER BY id LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![now, BATCH as i64], |row| {
+        let detail: String = row.get(4)?;
+        Ok(Queued {
+            id: row.get(0)?,
+            profile_id: row.get(1)?,
+            plan_id: row.get(2)?,
+            decision: row.get(3)?,
+            detail: serde_json::from_str(&detail).unwrap_or(Value::Null),
+            attempts: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+async fn sync(app: &AppHandle) {
+    let db = app.state::<LocalDb>();
+    let due = match db.lock() {
+        Ok(conn) => take_due(&conn).unwrap_or_default(),
+        Err(_) => return,
+    };
+    let store = app.state::<SettingsStore>();
+    for entry in due {
+        let settings = store.snapshot();
+        let ticketing = settings
+            .profile(&entry.profile_id)
+            .map(|p| p.ticketing.clone())
+            .filter(|t| t.enabled);
+        let Some(ticketing) = ticketing else {
+            // Ticketing was switched off for the profile (or it was
+            // removed) after the decision was queued.
+            if let Ok(conn) = db.lock() {
+                let _ = conn.execute("DELETE FROM ticket_outbox WHERE id = ?1", [entry.id]);
+            }
+            continue;
+        };
+        let outcome = deliver(app, &ticketing, &entry).await;
+        let Ok(conn) = db.lock() else {
+            return;
+        };
+        match outcome {
+            Ok((link, created)) => {
+                let provider = provider_name(ticketing.provider);
+                let _ = save_link(&conn, &entry.profile_id, &entry.plan_id, provider, &link);
+                let _ = conn.execute("DELETE FROM ticket_outbox WHERE id = ?1", [entry.id]);
+                let detail = json!({
+                    "provider": provider,
+                    "decision": entry.decision,
+                    "ticket": link.ticket_key,
+                    "created": created,
+                });
+                let _ = db::record_audit(
+                    &conn,
+                    &entry.profile_id,
+                    "ticketing",
+                    "filed",
+                    &entry.plan_id,
+                    &detail,
+                );
+                drop(conn);
+                let _ = environment::emit(
+                    app,
+                    SYNCED_EVENT,
+                    json!({
+                        "profile_id": entry.profile_id,
+                        "plan_id": entry.plan_id,
+                        "provider": provider,
+                        "ticket_key": link.ticket_key,
+                        "ticket_url": link.ticket_url,
+                        "created": created,
+                    }),
+                );
+            }
+            Err(err) => {
+                let attempts = entry.attempts + 1;
+                // Bad credentials or a rejected field will not fix
+                // themselves; those wait for the operator at once.
+                let retry = errors::code_of(&err)
+                    .is_none_or(|c| c.retryable() || c == ErrorCode::CoreMaintenance);
+                let give_up = !retry || attempts >= MAX_ATTEMPTS;
+                let delay = (FIRST_RETRY_SECS << (attempts - 1).min(10)).min(MAX_RETRY_SECS);
+                let next = stamp(Utc::now() + chrono::Duration::seconds(delay));
+                let _ = conn.execute(
+                    "UPDATE ticket_outbox SET attempts = ?1, last_error = ?2, next_attempt_at = ?3,
+                         status = ?4
+                     WHERE id = ?5",
+                    params![
+                        attempts,
+                        err,
+                        next,
+                        if give_up { "failed" } else { "pending" },
+                        entry.id
+                    ],
+                );
+                if give_up {
+                    let _ = db::record_audit(
+                        &conn,
+                        &entry.profile_id,
+                        "ticketing",
+                        "failed",
+                        &entry.plan_id,
+                        &json!({ "decision": entry.decision, "attempts": attempts, "error": err }),
+                    );
+                    drop(conn);
+                    let payload = json!({
+                        "profile_id": entry.profile_id,
+                        "plan_id": entry.plan_id,
+                        "error": err,
+                    });
+                    notify::notify_in_app(
+                        app,
+                        "ticketing",
+                        "Ticket not filed",
+                        &format!(
+                            "The {} decision on plan {} could not be filed: {}",
+                            entry.decision, entry.plan_id, err
+                        ),
+                        payload.clone(),
+                    );
+                    let _ = environment::emit(app, FAILED_EVENT, payload);
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(
+    app: &AppHandle,
+    ticketing: &TicketingSettings,
+    entry: &Queued,
+) -> Result<(Link, bool), String> {
+    let secret = secrets::get(&ticketing_secret_key(&entry.profile_id))?.ok_or_else(|| {
+        ErrorCode::InvalidInput.with("No ticketing credential is stored for this profile")
+    })?;
+    // The ticket is still filed, with less in it, when the core is away.
+    let plan = match app
+        .state::<SettingsStore>()
+        .connection(Some(&entry.profile_id))
+    {
+        Ok(conn) => crate::request_json(
+            Method::GET,
+            &conn.base_url,
+            &format!("/plans/{}", entry.plan_id),
+            conn.token,
+            None,
+        )
+        .await
+        .unwrap_or(Value::Null),
+        Err(_) => Value::Null,
+    };
+    let (summary, description) = describe(&entry.plan_id, &plan, &entry.decision, &entry.detail);
+    let existing = {
+        let db = app.state::<LocalDb>();
+        let conn = db.lock()?;
+        link_for(
+            &conn,
+            &entry.profile_id,
+            &entry.plan_id,
+            provider_name(ticketing.provider),
+        )
+        .map_err(storage)?
+    };
+    file(
+        ticketing,
+        &secret,
+        &entry.plan_id,
+        &summary,
+        &description,
+        existing,
+    )
+    .await
+}
+
+/// Stores (or with `None`, clears) the profile's Jira API token or
+/// ServiceNow password in the keyring.
+#[tauri::command]
+pub fn set_ticketing_secret(
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+    secret: Option<String>,
+) -> Result<Value, String> {
+    if store.snapshot().profile(&profile_id).is_none() {
+        return Err(ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)));
+    }
+    let key = ticketing_secret_key(&profile_id);
+    let secret = secret.filter(|v| !v.trim().is_empty());
+    match secret.as_deref() {
+        Some(value) => secrets::set(&key, value.trim())?,
+        None => secrets::delete(&key)?,
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": secret.is_some() }))
+}
+
+/// The profile's ticketing configuration (never the credential), with the
+/// decisions still waiting to be filed and those that gave up.
+#[tauri::command]
+pub fn get_ticketing_status(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let id = profile
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or(settings.active_profile.clone());
+    let profile = settings
+        .profile(&id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", id)))?;
+    let conn = db.lock()?;
+    let pending: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM ticket_outbox WHERE profile_id = ?1 AND status = 'pending'",
+            [&id],
+            |row| row.get(0),
+        )
+        .map_err(storage)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT plan_id, decision, attempts, last_error, created_at FROM ticket_outbox
+             WHERE profile_id = ?1 AND status = 'failed' ORDER BY id DESC LIMIT 50",
+        )
+        .map_err(storage)?;
+    let failed: Vec<Value> = stmt
+        .query_map([&id], |row| {
+            Ok(json!({
+                "plan_id": row.get::<_, String>(0)?,
+                "decision": row.get::<_, String>(1)?,
+                "attempts": row.get::<_, u32>(2)?,
+                "error": row.get::<_, Option<String>>(3)?,
+                "queued_at": row.get::<_, String>(4)?,
+            }))
+        })
+        .map_err(storage)?
+        .collect::<rusqlite::Result<_>>()
+        .map_err(storage)?;
+    Ok(json!({
+        "profile_id": id,
+        "enabled": profile.ticketing.enabled,
+        "provider": provider_name(profile.ticketing.provider),
+        "url": profile.ticketing.url,
+        "credential_stored": secrets::get(&ticketing_secret_key(&id)).ok().flatten().is_some(),
+        "pending": pending,
+        "failed": failed,
+    }))
+}
+
+/// Puts the profile's failed decisions back in the queue for the next
+/// sync pass, e.g. after the credential was fixed.
+#[tauri::command]
+pub fn retry_ticket_sync(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let requeued = db
+        .lock()?
+        .execute(
+            "UPDATE ticket_outbox SET status = 'pending', attempts = 0, next_attempt_at = ?1
+             WHERE profile_id = ?2 AND status = 'failed'",
+            params![stamp(Utc::now()), conn.profile_id],
+        )
+        .map_err(storage)?;
+    Ok(json!({ "profile_id": conn.profile_id, "requeued": requeued }))
+}
+
+/// The ticket filed for a plan's decisions, if any.
+#[tauri::command]
+pub fn get_plan_ticket(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let provider = store
+        .snapshot()
+        .profile(&conn.profile_id)
+        .map(|p| provider_name(p.ticketing.provider))
+        .unwrap_or("jira");
+    let link =
+        link_for(&*db.lock()?, &conn.profile_id, plan_id.trim(), provider).map_err(storage)?;
+    Ok(json!({
+        "plan_id": plan_id.trim(),
+        "provider": provider,
+        "ticket": link.map(|l| json!({
+            "id": l.ticket_id,
+            "key": l.ticket_key,
+            "url": l.ticket_url,
+        })),
+    }))
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::handover;
+use crate::settings::SettingsStore;
+use crate::tenants;
+
+/// Plans or jobs listed per section; the counts cover every one.
+const SECTION_LIMIT: usize = 500;
+/// How far back before the moment `recent_changes` reaches.
+const RECENT_WINDOW_MINUTES: i64 = 60;
+const RECENT_LIMIT: usize = 200;
+const ACTIVE_JOBS: &[&str] = &["queued", "running"];
+
+/// Each entity's last recorded state at `at`, leaving out those the core
+/// had stopped listing by then.
+fn state_at(conn: &Connection, scope: &str, at: &str) -> rusqlite::Result<Vec<(String, Value)>> {
+    let mut stmt = conn.prepare(
+        "SELECT h.kind, h.payload FROM sync_history h
+         WHERE h.profile_id = ?1 AND h.payload IS NOT NULL
+           AND h.id = (SELECT MAX(x.id) FROM sync_history x
+               WHERE x.profile_id = h.profile_id AND x.kind = h.kind
+                 AND x.entity_id = h.entity_id
+                 AND julianday(x.recorded_at) <= julianday(?2))",
+    )?;
+    let rows = stmt.query_map(params![scope, at], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(kind, raw)| Some((kind, serde_json::from_str(&raw).ok()?)))
+        .collect())
+}
+
+/// The changes recorded between `since` and `at`, newest first.
+fn changes_between(
+    conn: &Connection,
+    scope: &str,
+    since: &str,
+    at: &str,
+) -> rusqlite::Result<Vec<Value>> {
+    let mut stmt = conn.prepare(
+        "SELECT kind, entity_id, lower(json_extract(payload, '$.status')), recorded_at
+         FROM sync_history
+         WHERE profile_id = ?1 AND julianday(recorded_at) > julianday(?2)
+           AND julianday(recorded_at) <= julianday(?3)
+         ORDER BY id DESC LIMIT ?4",
+    )?;
+    let rows = stmt.query_map(params![scope, since, at, RECENT_LIMIT as i64], |row| {
+        let status: Option<String> = row.get(2)?;
+        Ok(json!({
+            "kind": row.get::<_, String>(0)?,
+            "id": row.get::<_, String>(1)?,
+            // No status: the core stopped listing it.
+            "status": status.unwrap_or_else(|| "removed".to_string()),
+            "at": row.get::<_, String>(3)?,
+        }))
+    })?;
+    rows.collect()
+}
+
+fn section(items: Vec<&Value>, listed: impl Fn(&str) -> bool) -> Value {
+    let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+    for item in &items {
+        *by_status.entry(handover::status_of(item)).or_default() += 1;
+    }
+    let matching: Vec<&Value> = items
+        .iter()
+        .copied()
+        .filter(|item| listed(&handover::status_of(item)))
+        .collect();
+    json!({
+        "total": items.len(),
+        "by_status": by_status,
+        "listed": matching.iter().take(SECTION_LIMIT).collect::<Vec<_>>(),
+        "listed_truncated": matching.len() > SECTION_LIMIT,
+    })
+}
+
+/// Rebuilds the dashboard as the local cache had it at `timestamp` (RFC
+/// 3339), from the history every sync appends to: the plans and jobs then
+/// known, counted by status, with the pending plans and active jobs
+/// listed, and the changes in the hour before. `history_from` is the
+/// oldest moment the history reaches; retention keeps it rebuildable from
+/// `retention.plans_days` ago, and the cache only knows what it synced, so
+/// a moment when the shell was not running shows its last sync before then.
+#[tauri::command]
+pub fn get_dashboard_at(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    timestamp: String,
+) -> Result<Value, String> {
+    let at = DateTime::parse_from_rfc3339(timestamp.trim())
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| ErrorCode::InvalidInput.with("`timestamp` must be RFC 3339"))?;
+    if at > Utc::now() {
+        return Err(ErrorCode::InvalidInput.with("`timestamp` must not be in the future"));
+    }
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let scope = tenants::cache_scope(&profile_id);
+    let stamp = at.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let since = (at - Duration::minutes(RECENT_WINDOW_MINUTES))
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+    let guard = db.lock()?;
+    let storage = |e: rusqlite::Error| ErrorCode::LocalStorage.with(e.to_string());
+    let history_from: Option<String> = guard
+        .query_row(
+            "SELECT MIN(recorded_at) FROM sync_history WHERE profile_id = ?1",
+            params![scope],
+            |row| row.get(0),
+        )
+        .map_err(storage)?;
+    let entities = state_at(&guard, &scope, &stamp).map_err(storage)?;
+    let recent = changes_between(&guard, &scope, &since, &stamp).map_err(storage)?;
+    let of_kind = |kind: &str| -> Vec<&Value> {
+        entities
+            .iter()
+            .filter(|(k, _)| k == kind)
+            .map(|(_, item)| item)
+            .collect()
+    };
+    let before_history = history_from
+        .as_deref()
+        .and_then(|from| DateTime::parse_from_rfc3339(from).ok())
+        .is_none_or(|from| at < from);
+    Ok(json!({
+        "profile_id": profile_id,
+        "at": stamp,
+        "history_from": history_from,
+        "before_history": before_history,
+        "plans": section(of_kind("plan"), |status| status == "pending"),
+        "jobs": section(of_kind("job"), |status| ACTIVE_JOBS.contains(&status)),
+        "recent_changes": recent,
+    }))
+}
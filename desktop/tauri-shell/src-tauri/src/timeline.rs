@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::settings::SettingsStore;
+
+const EVENT_LIMIT: usize = 500;
+const HISTORY_LIMIT: usize = 500;
+
+/// Core audit actions that map onto a fixed stage; anything else recorded
+/// against the plan before a decision counts as review activity.
+const DECISION_ACTIONS: &[&str] = &["create", "approve", "approve_async", "reject"];
+
+struct Entry {
+    stage: &'static str,
+    at: DateTime<Utc>,
+    label: String,
+    source: &'static str,
+    detail: Value,
+}
+
+/// Rebuilds a plan's lifecycle (created → reviewed → approved → executing
+/// steps → done/failed) from the plan record, its core audit events and the
+/// action log, with the time spent in each stage.
+#[tauri::command]
+pub async fn get_plan_timeline(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let plan = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let events = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!(
+            "/events?entity_type=plan&entity_id={}&limit={}",
+            plan_id, EVENT_LIMIT
+        ),
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    // Step timing comes from the action log; older cores without `/history`
+    // still get a timeline, just without per-step times.
+    let history = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/history?limit={}", HISTORY_LIMIT),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    .unwrap_or(Value::Null);
+
+    let entries = build(&plan, &events, &history);
+    Ok(render(&plan, entries))
+}
+
+fn build(plan: &Value, events: &Value, history: &Value) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let events: Vec<&Value> = events.as_array().into_iter().flatten().collect();
+    let event_at = |action: &str| {
+        events
+            .iter()
+            .filter(|e| e["action"].as_str() == Some(action))
+            .filter_map(|e| parse_time(&e["created_at"]))
+            .min()
+    };
+
+    if let Some(at) = parse_time(&plan["created_at"]).or_else(|| event_at("create")) {
+        entries.push(Entry {
+            stage: "created",
+            at,
+            label: "Plan created".to_string(),
+            source: "plan",
+            detail: json!({ "strategy": plan["strategy"], "model": plan["model"] }),
+        });
+    }
+
+    let decided_at = parse_time(&plan["approved_at"]).or_else(|| parse_time(&plan["rejected_at"]));
+    for event in &events {
+        let action = event["action"].as_str().unwrap_or_default();
+        let Some(at) = parse_time(&event["created_at"]) else {
+            continue;
+        };
+        if DECISION_ACTIONS.contains(&action) || decided_at.is_some_and(|d| at > d) {
+            continue;
+        }
+        entries.push(Entry {
+            stage: "reviewed",
+            at,
+            label: format!("Review: {}", action.replace('_', " ")),
+            source: "event",
+            detail: json!({ "event_id": event["id"], "status": event["status"] }),
+        });
+    }
+
+    if let Some(at) = parse_time(&plan["approved_at"])
+        .or_else(|| event_at("approve"))
+        .or_else(|| event_at("approve_async"))
+    {
+        entries.push(Entry {
+            stage: "approved",
+            at,
+            label: "Approved".to_string(),
+            source: "plan",
+            detail: Value::Null,
+        });
+    }
+    if let Some(at) = parse_time(&plan["rejected_at"]).or_else(|| event_at("reject")) {
+        entries.push(Entry {
+            stage: "rejected",
+            at,
+            label: "Rejected".to_string(),
+            source: "plan",
+            detail: json!({ "reason": plan["reject_reason"] }),
+        });
+    }
+    if let Some(at) = parse_time(&plan["execution_started_at"]) {
+        entries.push(Entry {
+            stage: "executing",
+            at,
+            label: "Execution started".to_string(),
+            source: "plan",
+            detail: json!({ "total": plan["progress_total"] }),
+        });
+    }
+
+    let logged: HashMap<i64, &Value> = history
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| Some((item["id"].as_i64()?, item)))
+        .collect();
+    let results: Vec<&Value> = plan["execution_results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    let log_ids: Vec<i64> = plan["action_log_ids"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_i64)
+        .collect();
+    for (index, log_id) in log_ids.iter().enumerate() {
+        let Some(item) = logged.get(log_id) else {
+            continue;
+        };
+        let Some(at) = parse_time(&item["created_at"]) else {
+            continue;
+        };
+        let action = &item["action"];
+        let status = item["status"]
+            .as_str()
+            .or_else(|| results.get(index).and_then(|r| r["status"].as_str()))
+            .unwrap_or("unknown");
+        entries.push(Entry {
+            stage: "step",
+            at,
+            label: format!(
+                "Step {}: {} ({})",
+                index + 1,
+                action["type"].as_str().unwrap_or("action"),
+                status
+            ),
+            source: "action_log",
+            detail: json!({ "index": index, "action_log_id": log_id, "status": status }),
+        });
+    }
+
+    let status = plan["status"].as_str().unwrap_or_default();
+    if matches!(status, "executed" | "failed") {
+        let at = parse_time(&plan["executed_at"]).or_else(|| parse_time(&plan["updated_at"]));
+        if let Some(at) = at {
+            let (stage, label) = if status == "executed" {
+                ("done", "Done")
+            } else {
+                ("failed", "Failed")
+            };
+            entries.push(Entry {
+                stage,
+                at,
+                label: label.to_string(),
+                source: "plan",
+                detail: json!({ "error": plan["execution_error"] }),
+            });
+        }
+    }
+
+    entries.sort_by_key(|e| (e.at, stage_rank(e.stage)));
+    entries
+}
+
+/// Breaks ties between entries stamped in the same instant.
+fn stage_rank(stage: &str) -> u8 {
+    match stage {
+        "created" => 0,
+        "reviewed" => 1,
+        "approved" | "rejected" => 2,
+        "executing" => 3,
+        "step" => 4,
+        _ => 5,
+    }
+}
+
+fn render(plan: &Value, entries: Vec<Entry>) -> Value {
+    let first_at = |stages: &[&str]| {
+        entries
+            .iter()
+            .find(|e| stages.contains(&e.stage))
+            .map(|e| e.at)
+    };
+    let created = first_at(&["created"]);
+    let decided = first_at(&["approved", "rejected"]);
+    let started = first_at(&["executing"]);
+    let finished = first_at(&["done", "failed"]);
+    let span = |from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>| match (from, to) {
+        (Some(from), Some(to)) => Some((to - from).num_milliseconds().max(0)),
+        _ => None,
+    };
+
+    let now = Utc::now();
+    let terminal = finished.is_some() || first_at(&["rejected"]).is_some();
+    let items: Vec<Value> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            // The last entry of a plan still in flight runs until now.
+            let until = entries
+                .get(i + 1)
+                .map(|next| next.at)
+                .or((!terminal).then_some(now));
+            json!({
+                "stage": entry.stage,
+                "at": entry.at.to_rfc3339(),
+                "label": entry.label,
+                "source": entry.source,
+                "duration_ms": span(Some(entry.at), until),
+                "detail": entry.detail,
+            })
+        })
+        .collect();
+
+    json!({
+        "plan_id": plan["id"],
+        "objective": plan["objective"],
+        "status": plan["status"],
+        "entries": items,
+        "durations": {
+            "review_ms": span(created, decided),
+            "queued_ms": span(decided, started),
+            "execution_ms": span(started, finished.or((!terminal).then_some(now))),
+            "total_ms": span(created, finished.or(decided.filter(|_| terminal)).or(Some(now))),
+        },
+    })
+}
+
+/// Plans and events carry RFC 3339 stamps; the action log uses SQLite's
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+fn parse_time(raw: &Value) -> Option<DateTime<Utc>> {
+    let raw = raw.as_str()?.trim();
+    DateTime::parse_from_rfc3339(raw)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use rustls::crypto::ring;
+use rustls::{
+    version, AlertDescription, ClientConfig, RootCertStore, SupportedCipherSuite,
+    SupportedProtocolVersion,
+};
+use serde_json::{json, Value};
+
+use crate::errors::ErrorCode;
+use crate::settings::{TlsSettings, TlsVersion};
+
+/// The cipher suites the shell can offer, strongest first.
+fn supported() -> Vec<SupportedCipherSuite> {
+    ring::default_provider().cipher_suites
+}
+
+fn name(suite: SupportedCipherSuite) -> &'static str {
+    suite.suite().as_str().unwrap_or("UNKNOWN")
+}
+
+fn is_tls13(suite: SupportedCipherSuite) -> bool {
+    suite.tls13().is_some()
+}
+
+fn chosen(policy: &TlsSettings) -> Vec<SupportedCipherSuite> {
+    supported()
+        .into_iter()
+        .filter(|suite| {
+            policy.cipher_suites.is_empty()
+                || policy
+                    .cipher_suites
+                    .iter()
+                    .any(|wanted| wanted.trim().eq_ignore_ascii_case(name(*suite)))
+        })
+        .filter(|suite| policy.min_version == TlsVersion::Tls12 || is_tls13(*suite))
+        .collect()
+}
+
+pub fn validate(policy: &TlsSettings) -> Result<(), String> {
+    let known = supported();
+    for wanted in &policy.cipher_suites {
+        if !known
+            .iter()
+            .any(|suite| wanted.trim().eq_ignore_ascii_case(name(*suite)))
+        {
+            return Err(format!(
+                "Unknown TLS cipher suite {:?} (known: {})",
+                wanted,
+                known
+                    .iter()
+                    .map(|s| name(*s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    if chosen(policy).is_empty() {
+        return Err("TLS 1.3 needs at least one TLS13_ cipher suite".to_string());
+    }
+    Ok(())
+}
+
+/// The rustls configuration for a profile's connections: only the versions
+/// from `min_version` up and only the listed suites are offered, against
+/// the same web PKI roots the default client trusts.
+pub fn client_config(policy: &TlsSettings) -> Result<ClientConfig, String> {
+    let mut provider = ring::default_provider();
+    provider.cipher_suites = chosen(policy);
+    let versions: &[&'static SupportedProtocolVersion] = match policy.min_version {
+        TlsVersion::Tls12 => &[&version::TLS13, &version::TLS12],
+        TlsVersion::Tls13 => &[&version::TLS13],
+    };
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let mut config = ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(versions)
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid TLS policy: {}", e)))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    // reqwest leaves ALPN to a preconfigured client; the shell speaks HTTP/1.1.
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Why a failed request's handshake could not meet the TLS policy, if that
+/// is what failed it: the core offered no allowed version or suite, or
+/// refused the ones offered. Certificate errors are not policy failures.
+pub fn violation(err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut next = Some(err);
+    while let Some(current) = next {
+        // An `io::Error` hides the error it wraps from `source()`.
+        let found = current.downcast_ref::<rustls::Error>().or_else(|| {
+            current
+                .downcast_ref::<std::io::Error>()?
+                .get_ref()?
+                .downcast_ref::<rustls::Error>()
+        });
+        if let Some(tls) = found {
+            return match tls {
+                rustls::Error::PeerIncompatible(_)
+                | rustls::Error::AlertReceived(
+                    AlertDescription::HandshakeFailure
+                    | AlertDescription::ProtocolVersion
+                    | AlertDescription::InsufficientSecurity,
+                ) => Some(tls.to_string()),
+                _ => None,
+            };
+        }
+        next = current.source();
+    }
+    None
+}
+
+/// The cipher suites a profile's TLS policy can list, with the protocol
+/// version each belongs to.
+#[tauri::command]
+pub fn list_tls_cipher_suites() -> Result<Value, String> {
+    let suites: Vec<Value> = supported()
+        .into_iter()
+        .map(|suite| {
+            json!({
+                "name": name(suite),
+                "version": if is_tls13(suite) { "1.3" } else { "1.2" },
+            })
+        })
+        .collect();
+    Ok(json!({ "cipher_suites": suites }))
+}
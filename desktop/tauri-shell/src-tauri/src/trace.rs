@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::{self, ErrorCode};
+use crate::settings::SettingsStore;
+
+/// Spans beyond this are dropped rather than shipped to the webview.
+const MAX_SPANS: usize = 5_000;
+
+struct Span {
+    id: String,
+    parent: Option<String>,
+    name: String,
+    /// Nanoseconds since the Unix epoch.
+    start: i128,
+    end: i128,
+    status: Value,
+    attributes: Value,
+}
+
+impl Span {
+    fn parse(raw: &Value) -> Option<Self> {
+        let id = text(&raw["span_id"]).or_else(|| text(&raw["id"]))?;
+        let start = instant(raw, "start")?;
+        let end = instant(raw, "end").unwrap_or(start).max(start);
+        Some(Self {
+            id,
+            parent: text(&raw["parent_span_id"])
+                .or_else(|| text(&raw["parent_id"]))
+                .filter(|p| !p.is_empty()),
+            name: text(&raw["name"]).unwrap_or_else(|| "span".to_string()),
+            start,
+            end,
+            status: raw["status"].clone(),
+            attributes: raw["attributes"].clone(),
+        })
+    }
+}
+
+fn text(raw: &Value) -> Option<String> {
+    match raw {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Accepts OTLP-style `<edge>_time_unix_nano` (number or numeric string) or
+/// an RFC 3339 `<edge>_time`.
+fn instant(raw: &Value, edge: &str) -> Option<i128> {
+    let nanos = &raw[format!("{}_time_unix_nano", edge)];
+    nanos
+        .as_u64()
+        .map(i128::from)
+        .or_else(|| nanos.as_str()?.parse().ok())
+        .or_else(|| {
+            let at = DateTime::parse_from_rfc3339(raw[format!("{}_time", edge)].as_str()?).ok()?;
+            Some(i128::from(at.timestamp_nanos_opt()?))
+        })
+}
+
+fn to_ms(nanos: i128) -> f64 {
+    nanos as f64 / 1_000_000.0
+}
+
+/// Fetches a plan's step-level spans and returns them as a tree with each
+/// span's offset, duration and self time, plus the critical path: the chain
+/// of spans that determined the trace's end-to-end time.
+#[tauri::command]
+pub async fn get_plan_trace(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let plan_id = plan_id.trim().to_string();
+    if plan_id.is_empty() {
+        return Err(ErrorCode::InvalidInput.with("Plan id is required"));
+    }
+    let conn = store.connection(profile.as_deref())?;
+    let raw = match crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}/trace", plan_id),
+        conn.token.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(raw) => raw,
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::NotFound) => {
+            return Ok(json!({ "plan_id": plan_id, "supported": false, "spans": 0 }));
+        }
+        Err(err) => return Err(err),
+    };
+    let spans: Vec<Span> = raw["spans"]
+        .as_array()
+        .or_else(|| raw.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(Span::parse)
+        .take(MAX_SPANS)
+        .collect();
+    let mut out = build(spans);
+    out["plan_id"] = json!(plan_id);
+    out["trace_id"] = raw["trace_id"].clone();
+    Ok(out)
+}
+
+fn build(spans: Vec<Span>) -> Value {
+    let Some(origin) = spans.iter().map(|s| s.start).min() else {
+        return json!({ "supported": true, "spans": 0, "roots": [], "critical_path": [] });
+    };
+    let finish = spans.iter().map(|s| s.end).max().unwrap_or(origin);
+
+    let index: HashMap<&str, usize> = spans
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.id.as_str(), i))
+        .collect();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); spans.len()];
+    let mut roots = Vec::new();
+    for (i, span) in spans.iter().enumerate() {
+        // Spans whose parent was not exported are promoted to roots.
+        match span.parent.as_deref().and_then(|p| index.get(p)) {
+            Some(&parent) if parent != i => children[parent].push(i),
+            _ => roots.push(i),
+        }
+    }
+    for list in &mut children {
+        list.sort_by_key(|&i| spans[i].start);
+    }
+    roots.sort_by_key(|&i| spans[i].start);
+
+    let mut critical = vec![false; spans.len()];
+    let mut path = Vec::new();
+    if let Some(&last) = roots.iter().max_by_key(|&&i| spans[i].end) {
+        critical_path(&spans, &children, last, &mut critical, &mut path);
+    }
+    path.sort_by_key(|&(i, _)| spans[i].start);
+
+    let mut visited = vec![false; spans.len()];
+    let mut tree: Vec<Value> = roots
+        .iter()
+        .map(|&i| node(&spans, &children, &critical, &mut visited, origin, i))
+        .collect();
+    // Parent cycles leave spans unreachable from any root; show them flat.
+    for i in 0..spans.len() {
+        if !visited[i] {
+            tree.push(node(&spans, &children, &critical, &mut visited, origin, i));
+        }
+    }
+    json!({
+        "supported": true,
+        "spans": spans.len(),
+        "total_ms": to_ms(finish - origin),
+        "critical_ms": to_ms(path.iter().map(|(_, own)| own).sum()),
+        "critical_path": path
+            .iter()
+            .map(|&(i, own)| {
+                json!({ "span_id": spans[i].id, "name": spans[i].name, "own_ms": to_ms(own) })
+            })
+            .collect::<Vec<_>>(),
+        "roots": tree,
+    })
+}
+
+/// Walks back from the span's end: the child finishing last before the
+/// cursor is on the path, then the cursor moves to that child's start, so
+/// sequential children are all counted and overlapped ones are skipped.
+/// Each path entry carries the time it contributed itself, outside its
+/// on-path children.
+fn critical_path(
+    spans: &[Span],
+    children: &[Vec<usize>],
+    at: usize,
+    critical: &mut [bool],
+    path: &mut Vec<(usize, i128)>,
+) {
+    if critical[at] {
+        return;
+    }
+    critical[at] = true;
+    let slot = path.len();
+    path.push((at, 0));
+    let span = &spans[at];
+    let mut own = span.end - span.start;
+    let mut cursor = span.end;
+    loop {
+        let next = children[at]
+            .iter()
+            .copied()
+            .filter(|&c| !critical[c] && spans[c].end <= cursor)
+            .max_by_key(|&c| spans[c].end);
+        let Some(child) = next else {
+            break;
+        };
+        own -= (spans[child].end.min(span.end) - spans[child].start.max(span.start)).max(0);
+        critical_path(spans, children, child, critical, path);
+        cursor = spans[child].start;
+    }
+    path[slot].1 = own.max(0);
+}
+
+/// Duration not covered by any child, merging overlapping children.
+fn self_time(spans: &[Span], children: &[Vec<usize>], at: usize) -> i128 {
+    let span = &spans[at];
+    let mut covered = 0;
+    let mut reach = span.start;
+    for &c in &children[at] {
+        let start = spans[c].start.max(reach);
+        let end = spans[c].end.min(span.end);
+        if end > start {
+            covered += end - start;
+            reach = end;
+        }
+    }
+    (span.end - span.start - covered).max(0)
+}
+
+fn node(
+    spans: &[Span],
+    children: &[Vec<usize>],
+    critical: &[bool],
+    visited: &mut [bool],
+    origin: i128,
+    at: usize,
+) -> Value {
+    visited[at] = true;
+    let span = &spans[at];
+    let mut kids = Vec::new();
+    for &c in &children[at] {
+        if !visited[c] {
+            kids.push(node(spans, children, critical, visited, origin, c));
+        }
+    }
+    json!({
+        "span_id": span.id,
+        "name": span.name,
+        "start": DateTime::<Utc>::from_timestamp_nanos(span.start as i64).to_rfc3339(),
+        "offset_ms": to_ms(span.start - origin),
+        "duration_ms": to_ms(span.end - span.start),
+        "self_ms": to_ms(self_time(spans, children, at)),
+        "critical": critical[at],
+        "status": span.status,
+        "attributes": span.attributes,
+        "children": kids,
+    })
+}
@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::time::SystemTime;
+
+use reqwest::{Method, Url};
+use serde_json::json;
+
+use crate::crypto;
+use crate::telemetry::{self, Kind, Span};
+
+/// The logical operation the current task is serving: its trace and the
+/// span its core requests hang under.
+#[derive(Clone)]
+struct Operation {
+    trace_id: String,
+    span_id: String,
+}
+
+tokio::task_local! {
+    static CURRENT: Operation;
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A random W3C trace id: 32 lowercase hex digits, never all zero.
+pub fn new_trace_id() -> String {
+    loop {
+        let bytes = crypto::random_bytes::<16>();
+        if bytes.iter().any(|b| *b != 0) {
+            return hex(&bytes);
+        }
+    }
+}
+
+fn new_span_id() -> String {
+    loop {
+        let bytes = crypto::random_bytes::<8>();
+        if bytes.iter().any(|b| *b != 0) {
+            return hex(&bytes);
+        }
+    }
+}
+
+fn valid_trace_id(raw: &str) -> bool {
+    raw.len() == 32
+        && raw
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && raw.bytes().any(|b| b != b'0')
+}
+
+/// Runs `operation` as one trace: every core request it makes carries the
+/// same trace id, each as a child span of the operation's span, which is
+/// exported under `name` when telemetry is on. A caller-supplied id (the
+/// frontend's, shared across its retries) is used when valid; otherwise a
+/// fresh one. Nested scopes join the outer operation.
+pub async fn within<T, F>(trace_id: Option<&str>, name: &str, operation: F) -> Result<T, String>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    if CURRENT.try_with(|_| ()).is_ok() {
+        return operation.await;
+    }
+    let op = Operation {
+        trace_id: trace_id
+            .map(|id| id.trim().to_ascii_lowercase())
+            .filter(|id| valid_trace_id(id))
+            .unwrap_or_else(new_trace_id),
+        span_id: new_span_id(),
+    };
+    let start = SystemTime::now();
+    let result = CURRENT.scope(op.clone(), operation).await;
+    telemetry::record(Span {
+        trace_id: op.trace_id,
+        span_id: op.span_id,
+        parent_span_id: None,
+        name: name.to_string(),
+        kind: Kind::Internal,
+        start,
+        end: SystemTime::now(),
+        attributes: Vec::new(),
+        error: result.as_ref().err().cloned(),
+    });
+    result
+}
+
+/// One outgoing core request's place in the trace.
+pub struct Outgoing {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+}
+
+/// A new client span under the current operation, or a trace of its own
+/// outside any operation.
+pub fn outgoing() -> Outgoing {
+    let parent = CURRENT.try_with(Clone::clone).ok();
+    Outgoing {
+        trace_id: parent
+            .as_ref()
+            .map(|p| p.trace_id.clone())
+            .unwrap_or_else(new_trace_id),
+        span_id: new_span_id(),
+        parent_span_id: parent.map(|p| p.span_id),
+        start: SystemTime::now(),
+    }
+}
+
+impl Outgoing {
+    /// The W3C `traceparent` header value for this request.
+    pub fn traceparent(&self) -> String {
+        let flags = if telemetry::sampled(&self.trace_id) {
+            "01"
+        } else {
+            "00"
+        };
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, flags)
+    }
+
+    /// Records the request's client span once the core answered (with its
+    /// status) or the request failed.
+    pub fn finish(self, method: &Method, url: &Url, outcome: Result<u16, &str>) {
+        let mut attributes = vec![
+            ("http.request.method", json!(method.as_str())),
+            ("server.address", json!(url.host_str().unwrap_or_default())),
+            ("url.path", json!(url.path())),
+        ];
+        if let Some(port) = url.port_or_known_default() {
+            attributes.push(("server.port", json!(port)));
+        }
+        let error = match outcome {
+            Ok(status) => {
+                attributes.push(("http.response.status_code", json!(status)));
+                (status >= 400).then(|| format!("HTTP {}", status))
+            }
+            Err(err) => Some(err.to_string()),
+        };
+        telemetry::record(Span {
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_span_id: self.parent_span_id,
+            name: method.as_str().to_string(),
+            kind: Kind::Client,
+            start: self.start,
+            end: SystemTime::now(),
+            attributes,
+            error,
+        });
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use reqwest::Url;
+use rusqlite::{params, OptionalExtension};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::LocalDb;
+use crate::errors::ErrorCode;
+use crate::settings::{SettingsStore, TranslationProvider, TranslationSettings};
+use crate::{formatting, net, secrets};
+
+pub const KEY_NAME: &str = "translation:api_key";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// Longer text is left as it is rather than sent off.
+const MAX_TEXT_LEN: usize = 5_000;
+/// Distinct texts translated per payload; the rest stay untranslated.
+const MAX_TEXTS: usize = 200;
+const MAX_FIELDS: usize = 50;
+/// Appended to a field's name for its translation.
+const SUFFIX: &str = "_translated";
+
+pub fn validate(settings: &TranslationSettings) -> Result<(), String> {
+    let language = settings.target_language.trim();
+    if !language.is_empty() && !valid_language(language) {
+        return Err(format!(
+            "translation.target_language: `{}` is not a language code like de or pt-BR",
+            language
+        ));
+    }
+    if settings.fields.len() > MAX_FIELDS
+        || settings
+            .fields
+            .iter()
+            .any(|f| f.trim().is_empty() || f.ends_with(SUFFIX))
+    {
+        return Err(format!(
+            "translation.fields must be at most {} field names, none ending in `{}`",
+            MAX_FIELDS, SUFFIX
+        ));
+    }
+    if !settings.enabled {
+        return Ok(());
+    }
+    let parsed = Url::parse(settings.endpoint.trim())
+        .map_err(|e| format!("Invalid translation endpoint: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Translation endpoint must be http(s)".to_string());
+    }
+    Ok(())
+}
+
+fn valid_language(code: &str) -> bool {
+    let mut parts = code.split('-');
+    parts
+        .next()
+        .is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()))
+        && parts.all(|p| (2..=4).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// `de` for `de-AT` or `DE`.
+fn primary(code: &str) -> String {
+    code.split('-').next().unwrap_or(code).to_ascii_lowercase()
+}
+
+fn provider_name(provider: TranslationProvider) -> &'static str {
+    match provider {
+        TranslationProvider::Libretranslate => "libretranslate",
+        TranslationProvider::Deepl => "deepl",
+    }
+}
+
+/// One text in the target language, and the language it was detected in.
+#[derive(Clone)]
+struct Translated {
+    text: String,
+    source: Option<String>,
+}
+
+impl Translated {
+    /// Text already in the target language comes back as it was and gets
+    /// no translation.
+    fn changes(&self, original: &str, target: &str) -> bool {
+        self.text != original
+            && self
+                .source
+                .as_deref()
+                .is_none_or(|s| primary(s) != primary(target))
+    }
+}
+
+fn cache_key(provider: TranslationProvider, target: &str, text: &str) -> String {
+    let digest = Sha256::digest(format!("{}\n{}\n{}", provider_name(provider), target, text));
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn request(
+    settings: &TranslationSettings,
+    api_key: Option<&str>,
+    text: &str,
+    target: &str,
+) -> Result<Translated, String> {
+    let base = settings.endpoint.trim().trim_end_matches('/');
+    let (path, body) = match settings.provider {
+        TranslationProvider::Libretranslate => {
+            let mut body = json!({
+                "q": text,
+                "source": "auto",
+                "target": primary(target),
+                "format": "text",
+            });
+            if let Some(key) = api_key {
+                body["api_key"] = json!(key);
+            }
+            ("/translate", body)
+        }
+        TranslationProvider::Deepl => (
+            "/v2/translate",
+            json!({ "text": [text], "target_lang": target.to_ascii_uppercase() }),
+        ),
+    };
+    let url = Url::parse(&format!("{}{}", base, path))
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid translation URL: {}", e)))?;
+    let client = net::client_for(&url)?;
+    let mut builder = client
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header("Accept", "application/json")
+        .json(&body);
+    if settings.provider == TranslationProvider::Deepl {
+        let key = api_key.ok_or_else(|| {
+            ErrorCode::InvalidInput.with("DeepL needs an API key; set one with set_translation_key")
+        })?;
+        builder = builder.header("Authorization", format!("DeepL-Auth-Key {}", key));
+    }
+    let response = builder.send().await.map_err(|e| {
+        let code = if e.is_timeout() {
+            ErrorCode::CoreTimeout
+        } else {
+            ErrorCode::CoreUnreachable
+        };
+        code.with(format!("Translation request failed: {}", e))
+    })?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        // LibreTranslate answers `error`, DeepL `message`.
+        let reason = body["error"]
+            .as_str()
+            .or_else(|| body["message"].as_str())
+            .unwrap_or_default();
+        return Err(ErrorCode::from_status(status.as_u16()).with(format!(
+            "{} {}: {}",
+            provider_name(settings.provider),
+            status.as_u16(),
+            reason
+        )));
+    }
+    let (text, source) = match settings.provider {
+        TranslationProvider::Libretranslate => (
+            body["translatedText"].as_str(),
+            body["detectedLanguage"]["language"].as_str(),
+        ),
+        TranslationProvider::Deepl => (
+            body["translations"][0]["text"].as_str(),
+            body["translations"][0]["detected_source_language"].as_str(),
+        ),
+    };
+    let text = text.ok_or_else(|| {
+        ErrorCode::CoreError.with(format!(
+            "{} answered without a translation",
+            provider_name(settings.provider)
+        ))
+    })?;
+    Ok(Translated {
+        text: text.to_string(),
+        source: source.map(|s| s.to_ascii_lowercase()),
+    })
+}
+
+fn collect(value: &Value, fields: &[String], texts: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map {
+                match item.as_str() {
+                    Some(text) if fields.contains(key) => {
+                        if !text.trim().is_empty() && text.len() <= MAX_TEXT_LEN {
+                            texts.insert(text.to_string());
+                        }
+                    }
+                    _ => collect(item, fields, texts),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect(item, fields, texts)),
+        _ => {}
+    }
+}
+
+fn annotate(
+    value: &mut Value,
+    fields: &[String],
+    done: &HashMap<String, Translated>,
+    marker: &Map<String, Value>,
+    target: &str,
+) {
+    match value {
+        Value::Object(map) => {
+            let mut added = Vec::new();
+            for (key, item) in map.iter_mut() {
+                match item.as_str() {
+                    Some(text) if fields.contains(key) => {
+                        let Some(translated) = done.get(text).filter(|t| t.changes(text, target))
+                        else {
+                            continue;
+                        };
+                        let mut entry = marker.clone();
+                        entry.insert("text".to_string(), json!(translated.text));
+                        entry.insert("source_language".to_string(), json!(translated.source));
+                        added.push((format!("{}{}", key, SUFFIX), Value::Object(entry)));
+                    }
+                    _ => annotate(item, fields, done, marker, target),
+                }
+            }
+            map.extend(added);
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| annotate(item, fields, done, marker, target)),
+        _ => {}
+    }
+}
+
+/// Translates the configured fields of `content`, wherever they sit, into
+/// `target`: each gets a `<field>_translated` sibling marked
+/// `machine_translated`, and the original is left as it was. Translations
+/// are cached locally, so text is sent to the provider once; a provider
+/// that fails leaves the rest untranslated and is reported under
+/// `translation.error`.
+async fn translate(
+    settings: &TranslationSettings,
+    db: &LocalDb,
+    mut content: Value,
+    target: &str,
+) -> Result<Value, String> {
+    let provider = settings.provider;
+    let mut texts = BTreeSet::new();
+    collect(&content, &settings.fields, &mut texts);
+    let skipped = texts.len().saturating_sub(MAX_TEXTS);
+    let mut done = HashMap::new();
+    let mut missing = Vec::new();
+    {
+        let conn = db.lock()?;
+        for text in texts.into_iter().take(MAX_TEXTS) {
+            let cached = conn
+                .query_row(
+                    "SELECT text, source_language FROM translations WHERE key = ?1",
+                    params![cache_key(provider, target, &text)],
+                    |row| {
+                        Ok(Translated {
+                            text: row.get(0)?,
+                            source: row.get(1)?,
+                        })
+                    },
+                )
+                .optional()
+                .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+            match cached {
+                Some(hit) => {
+                    done.insert(text, hit);
+                }
+                None => missing.push(text),
+            }
+        }
+    }
+    let cached = done.len();
+    let api_key = secrets::get(KEY_NAME)?;
+    let mut fresh = Vec::new();
+    let mut error = None;
+    for text in missing {
+        match request(settings, api_key.as_deref(), &text, target).await {
+            Ok(translated) => fresh.push((text, translated)),
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        }
+    }
+    if !fresh.is_empty() {
+        let conn = db.lock()?;
+        let now = chrono::Utc::now().to_rfc3339();
+        for (text, translated) in &fresh {
+            conn.execute(
+                "INSERT OR REPLACE INTO translations
+                     (key, provider, source_language, target_language, text, at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    cache_key(provider, target, text),
+                    provider_name(provider),
+                    translated.source,
+                    target,
+                    translated.text,
+                    now
+                ],
+            )
+            .map_err(|e| ErrorCode::LocalStorage.with(e.to_string()))?;
+        }
+    }
+    done.extend(fresh);
+    let marker = json!({
+        "machine_translated": true,
+        "provider": provider_name(provider),
+        "target_language": target,
+    });
+    let marker = marker.as_object().cloned().unwrap_or_default();
+    let translated = done
+        .iter()
+        .filter(|(text, t)| t.changes(text, target))
+        .count();
+    annotate(&mut content, &settings.fields, &done, &marker, target);
+    let summary = json!({
+        "machine_translated": translated > 0,
+        "provider": provider_name(provider),
+        "target_language": target,
+        "translated": translated,
+        "unchanged": done.len() - translated,
+        "cached": cached,
+        "skipped": skipped,
+        "error": error,
+    });
+    match &mut content {
+        Value::Object(map) => {
+            map.insert("translation".to_string(), summary);
+            Ok(content)
+        }
+        _ => Ok(json!({ "content": content, "translation": summary })),
+    }
+}
+
+/// The integration's settings and the language to translate into, or an
+/// error while it is off.
+fn configured(
+    store: &SettingsStore,
+    target_language: Option<String>,
+) -> Result<(TranslationSettings, String), String> {
+    let settings = store.snapshot();
+    let translation = settings.translation.clone();
+    if !translation.enabled {
+        return Err(ErrorCode::InvalidInput.with("Translation is turned off in settings"));
+    }
+    let target = target_language
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .or_else(|| Some(translation.target_language.trim().to_string()).filter(|t| !t.is_empty()))
+        .or_else(|| formatting::operator_language(&settings.formatting))
+        .ok_or_else(|| {
+            ErrorCode::InvalidInput
+                .with("No language to translate into; set translation.target_language")
+        })?;
+    if !valid_language(&target) {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "`{}` is not a language code like de or pt-BR",
+            target
+        )));
+    }
+    Ok((translation, target))
+}
+
+/// Plan `plan_id` from the core with its description, step text and agent
+/// messages translated into the operator's language (or `target_language`).
+#[tauri::command]
+pub async fn get_plan_translated(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    target_language: Option<String>,
+) -> Result<Value, String> {
+    let (settings, target) = configured(&store, target_language)?;
+    let conn = store.connection(profile.as_deref())?;
+    let plan = crate::request_json(
+        reqwest::Method::GET,
+        &conn.base_url,
+        &format!("/plans/{}", plan_id.trim()),
+        conn.token,
+        None,
+    )
+    .await?;
+    translate(&settings, &db, plan, &target).await
+}
+
+/// Translates `content` the window already has, such as agent messages
+/// from the live event stream.
+#[tauri::command]
+pub async fn translate_content(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    content: Value,
+    target_language: Option<String>,
+) -> Result<Value, String> {
+    let (settings, target) = configured(&store, target_language)?;
+    translate(&settings, &db, content, &target).await
+}
+
+/// Stores (or with `None`, clears) the provider's API key in the keyring.
+#[tauri::command]
+pub fn set_translation_key(api_key: Option<String>) -> Result<Value, String> {
+    let api_key = api_key.filter(|k| !k.trim().is_empty());
+    match api_key.as_deref() {
+        Some(key) => secrets::set(KEY_NAME, key.trim())?,
+        None => secrets::delete(KEY_NAME)?,
+    }
+    Ok(json!({ "stored": api_key.is_some() }))
+}
@@ -0,0 +1,768 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::errors::ErrorCode;
+use crate::fault_injection::{self, Outcome};
+use crate::grpc::{self, GrpcTransport};
+use crate::settings::{EnvironmentClass, SettingsStore};
+use crate::{cookies, http3, net, sanitize, spill, ssrf, tls};
+
+/// Cores reached over HTTP(S), through the shell's proxy, TLS and auth
+/// settings.
+pub const HTTP: &str = "http";
+/// `http+unix://<percent-encoded socket path>`: a core on this machine
+/// listening on a Unix socket.
+pub const UNIX: &str = "http+unix";
+/// `mock://<name>`: an in-process core answering from routes set with
+/// `set_mock_core` or a replayed recording.
+pub const MOCK: &str = "mock";
+/// `grpc://host:port` / `grpcs://host:port`: a core behind a gRPC gateway,
+/// in builds with the `grpc` feature.
+pub const GRPC: &str = "grpc";
+pub const GRPCS: &str = "grpcs";
+const UNIX_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest response read from a socket core.
+const MAX_UNIX_RESPONSE: usize = 16 * 1024 * 1024;
+/// Exchanges one recording holds; later ones are counted but dropped.
+const MAX_RECORDED: usize = 2_000;
+/// Requests a mock core remembers for `get_mock_requests`.
+const MAX_MOCK_REQUESTS: usize = 500;
+
+/// One request to a core, after the shell's policy checks passed.
+pub struct CoreRequest {
+    pub method: Method,
+    /// The endpoint the request goes to: the profile's base URL or the
+    /// replica routing picked.
+    pub base_url: String,
+    pub url: Url,
+    pub token: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Value>,
+}
+
+impl CoreRequest {
+    pub fn path(&self) -> String {
+        match self.url.query() {
+            Some(query) => format!("{}?{}", self.url.path(), query),
+            None => self.url.path().to_string(),
+        }
+    }
+}
+
+pub struct CoreResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// Where the response came from, after any redirect followed.
+    pub url: Url,
+    pub body: spill::Body,
+}
+
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<CoreResponse, String>> + Send + 'a>>;
+
+/// How `request_json` reaches a core. Implementations only move bytes:
+/// routing, permissions, interlocks and response handling stay in
+/// `request_json` for every transport. A transport that cannot reach the
+/// core fails with `CORE_UNREACHABLE` or `CORE_TIMEOUT`, which lets
+/// replica failover apply.
+pub trait CoreTransport: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_>;
+}
+
+/// Injected transports by base URL origin, taking the place of the one
+/// its scheme would pick.
+static INSTALLED: OnceLock<Mutex<HashMap<String, Arc<dyn CoreTransport>>>> = OnceLock::new();
+static MOCKS: OnceLock<Mutex<HashMap<String, Arc<MockTransport>>>> = OnceLock::new();
+static RECORDING: OnceLock<Mutex<Option<Recording>>> = OnceLock::new();
+
+fn installed() -> &'static Mutex<HashMap<String, Arc<dyn CoreTransport>>> {
+    INSTALLED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn mocks() -> &'static Mutex<HashMap<String, Arc<MockTransport>>> {
+    MOCKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn recording() -> &'static Mutex<Option<Recording>> {
+    RECORDING.get_or_init(|| Mutex::new(None))
+}
+
+/// Whether a base URL's scheme names a transport this shell has.
+pub fn supports(scheme: &str) -> bool {
+    matches!(scheme, "http" | "https" | UNIX | MOCK)
+        || (grpc::SUPPORTED && matches!(scheme, GRPC | GRPCS))
+}
+
+/// Sends every request for `base_url`'s origin through `transport`
+/// instead of the one its scheme picks.
+pub fn install(base_url: &str, transport: Arc<dyn CoreTransport>) -> Result<(), String> {
+    let origin = net::base_origin(base_url)
+        .ok_or_else(|| ErrorCode::InvalidInput.with(format!("Invalid base URL: {}", base_url)))?;
+    installed()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Transport state poisoned"))?
+        .insert(origin, transport);
+    Ok(())
+}
+
+/// Undoes [`install`] for `base_url`'s origin.
+#[cfg(test)]
+pub fn remove(base_url: &str) {
+    if let (Some(origin), Ok(mut installed)) = (net::base_origin(base_url), installed().lock()) {
+        installed.remove(&origin);
+    }
+}
+
+/// The transport a request to `url` goes over.
+pub fn for_url(url: &Url) -> Result<Arc<dyn CoreTransport>, String> {
+    if let Some(origin) = net::base_origin(url.as_str()) {
+        if let Some(injected) = installed()
+            .lock()
+            .ok()
+            .and_then(|t| t.get(&origin).cloned())
+        {
+            return Ok(injected);
+        }
+    }
+    match url.scheme() {
+        "http" | "https" => Ok(Arc::new(HttpTransport)),
+        UNIX => Ok(Arc::new(UnixSocketTransport)),
+        GRPC | GRPCS if grpc::SUPPORTED => Ok(Arc::new(GrpcTransport)),
+        MOCK => {
+            let name = url.host_str().unwrap_or_default();
+            let mock = mocks().lock().ok().and_then(|m| m.get(name).cloned());
+            match mock {
+                Some(mock) => Ok(mock),
+                None => Err(ErrorCode::CoreUnreachable.with(format!(
+                    "No mock core named {:?}; set one with set_mock_core",
+                    name
+                ))),
+            }
+        }
+        other => Err(ErrorCode::InvalidInput.with(format!(
+            "No transport for {}:// base URLs (use http, https, {} or {})",
+            other, UNIX, MOCK
+        ))),
+    }
+}
+
+/// Sends `request` over `transport`, adding the exchange to the recording
+/// if one is running for its endpoint. A running fault injection scenario
+/// gets the request first and may delay, fail or answer it instead.
+pub async fn send(
+    transport: &dyn CoreTransport,
+    request: CoreRequest,
+) -> Result<CoreResponse, String> {
+    match fault_injection::apply(&request).await {
+        Outcome::Send => {}
+        Outcome::Fail(err) => return Err(err),
+        Outcome::LoseResponse(err) => {
+            let _ = transport.send(request).await;
+            return Err(err);
+        }
+        Outcome::Respond(status, headers, body) => {
+            return Ok(CoreResponse {
+                status,
+                headers,
+                url: request.url,
+                body: spill::Body::Buffered(body),
+            })
+        }
+    }
+    let recorded = recording().lock().ok().and_then(|r| {
+        r.as_ref()
+            .filter(|r| r.covers(&request.base_url))
+            .map(|_| (request.method.clone(), request.path(), request.body.clone()))
+    });
+    let response = transport.send(request).await;
+    if let (Some((method, path, body)), Ok(response)) = (recorded, &response) {
+        if let Ok(mut guard) = recording().lock() {
+            if let Some(recording) = guard.as_mut() {
+                recording.push(method, path, body, response, transport.name());
+            }
+        }
+    }
+    response
+}
+
+fn unreachable(e: impl std::fmt::Display) -> String {
+    ErrorCode::CoreUnreachable.with(format!("Request failed: {}", e))
+}
+
+/// The shell's own HTTP client: proxies, pinning, core auth and request
+/// signing from the profile, cookies, the slow-request watchdog, and
+/// HTTP/3 first where the profile opts in.
+pub struct HttpTransport;
+
+impl CoreTransport for HttpTransport {
+    fn name(&self) -> &'static str {
+        HTTP
+    }
+
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            ssrf::check_url(&request.base_url, &request.url)?;
+            let client = net::client_for(&request.url)?;
+            let mut req = net::authorize(
+                client.request(request.method.clone(), request.url.clone()),
+                &request.url,
+                request.token,
+            )?;
+            if let Some(cookie) = cookies::header_for(&request.url) {
+                req = req.header(reqwest::header::COOKIE, cookie);
+            }
+            for (name, value) in &request.headers {
+                req = req.header(name.as_str(), value);
+            }
+            if let Some(body) = request.body {
+                req = req.json(&body);
+            }
+            let mut built = req
+                .build()
+                .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid request: {}", e)))?;
+            net::sign(&mut built).await?;
+            let response = http3::execute(&client, built, &request.base_url)
+                .await
+                .map_err(|e| {
+                    if let Some(reason) = ssrf::blocked_in(&e) {
+                        ErrorCode::RequestTargetBlocked.with(reason)
+                    } else if e.is_timeout() {
+                        ErrorCode::CoreTimeout.with(format!("Request failed: {}", e))
+                    } else if let Some(reason) = tls::violation(&e) {
+                        ErrorCode::TlsPolicy.with(format!("TLS handshake failed: {}", reason))
+                    } else {
+                        unreachable(e)
+                    }
+                })?;
+            net::observe_peer(&request.base_url, response.remote_addr());
+            cookies::store_from(&request.url, response.headers());
+            Ok(CoreResponse {
+                status: response.status(),
+                headers: response.headers().clone(),
+                url: response.url().clone(),
+                body: spill::read_body(response).await?,
+            })
+        })
+    }
+}
+
+/// HTTP/1.0 over a Unix socket, for a core on the same machine that does
+/// not listen on TCP. Only bearer tokens authenticate; proxies, TLS and
+/// the profile's other auth modes do not apply to a socket.
+pub struct UnixSocketTransport;
+
+fn socket_path(url: &Url) -> Result<String, String> {
+    let path = percent_decode(url.host_str().unwrap_or_default()).unwrap_or_default();
+    if !path.starts_with('/') {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "{}:// base URLs name the socket as a percent-encoded absolute path, e.g. {}://%2Frun%2Fnovaadapt.sock",
+            UNIX, UNIX
+        )));
+    }
+    Ok(path)
+}
+
+fn percent_decode(raw: &str) -> Option<String> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = raw.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Splits a raw HTTP/1.x response into its status, headers and body.
+fn parse_response(raw: &[u8]) -> Result<(StatusCode, HeaderMap, Vec<u8>), String> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| unreachable("the socket closed before the response headers ended"))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| unreachable("the socket did not answer with an HTTP status line"))?;
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.trim().as_bytes()),
+            HeaderValue::from_str(value.trim()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    Ok((status, headers, raw[split + 4..].to_vec()))
+}
+
+impl CoreTransport for UnixSocketTransport {
+    fn name(&self) -> &'static str {
+        UNIX
+    }
+
+    #[cfg(unix)]
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        Box::pin(async move {
+            let socket = socket_path(&request.url)?;
+            let body = request
+                .body
+                .as_ref()
+                .map(|b| b.to_string())
+                .unwrap_or_default();
+            // HTTP/1.0 so the core closes the connection instead of
+            // chunking the response.
+            let mut head = format!(
+                "{} {} HTTP/1.0\r\nHost: localhost\r\nAccept: application/json\r\n",
+                request.method,
+                request.path()
+            );
+            if let Some(token) = request.token.as_deref().filter(|t| !t.is_empty()) {
+                head.push_str(&format!("Authorization: Bearer {}\r\n", token));
+            }
+            for (name, value) in &request.headers {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            if request.body.is_some() {
+                head.push_str("Content-Type: application/json\r\n");
+            }
+            head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+            let exchange = async {
+                let mut stream = tokio::net::UnixStream::connect(&socket)
+                    .await
+                    .map_err(|e| unreachable(format!("{}: {}", socket, e)))?;
+                stream
+                    .write_all(head.as_bytes())
+                    .await
+                    .map_err(unreachable)?;
+                stream
+                    .write_all(body.as_bytes())
+                    .await
+                    .map_err(unreachable)?;
+                let mut raw = Vec::new();
+                stream
+                    .take(MAX_UNIX_RESPONSE as u64 + 1)
+                    .read_to_end(&mut raw)
+                    .await
+                    .map_err(unreachable)?;
+                if raw.len() > MAX_UNIX_RESPONSE {
+                    return Err(ErrorCode::CoreError.with(format!(
+                        "Socket response is larger than {} bytes",
+                        MAX_UNIX_RESPONSE
+                    )));
+                }
+                Ok(raw)
+            };
+            let raw = tokio::time::timeout(UNIX_TIMEOUT, exchange)
+                .await
+                .map_err(|_| {
+                    ErrorCode::CoreTimeout.with(format!(
+                        "Request failed: no answer from {} in {}s",
+                        socket,
+                        UNIX_TIMEOUT.as_secs()
+                    ))
+                })??;
+            let (status, headers, body) = parse_response(&raw)?;
+            Ok(CoreResponse {
+                status,
+                headers,
+                url: request.url,
+                body: spill::Body::Buffered(String::from_utf8_lossy(&body).into_owned()),
+            })
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            socket_path(&request.url)?;
+            Err(ErrorCode::InvalidInput
+                .with("Unix socket cores are not available on this platform"))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockRoute {
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Matched against the request path; with a `?` also against the query.
+    pub path: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub body: Value,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// An in-process core. Routes listed more than once for the same request
+/// answer in turn, the last one repeating, so a sequence of states (a plan
+/// pending, then approved) can be scripted or replayed.
+pub struct MockTransport {
+    routes: Vec<MockRoute>,
+    hits: Mutex<HashMap<String, usize>>,
+    received: Mutex<Vec<Value>>,
+}
+
+impl MockTransport {
+    pub fn new(routes: Vec<MockRoute>) -> Self {
+        MockTransport {
+            routes,
+            hits: Mutex::new(HashMap::new()),
+            received: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn answer(&self, method: &Method, path: &str) -> (u16, Value, HashMap<String, String>) {
+        let bare = path.split('?').next().unwrap_or_default();
+        let matching: Vec<&MockRoute> = self
+            .routes
+            .iter()
+            .filter(|r| r.method.eq_ignore_ascii_case(method.as_str()))
+            .filter(|r| {
+                if r.path.contains('?') {
+                    r.path == path
+                } else {
+                    r.path == bare
+                }
+            })
+            .collect();
+        if matching.is_empty() {
+            return (
+                404,
+                json!({ "error": format!("No mock route for {} {}", method, bare) }),
+                HashMap::new(),
+            );
+        }
+        let key = format!("{} {}", method, path);
+        let hit = self
+            .hits
+            .lock()
+            .map(|mut hits| {
+                let n = hits.entry(key).or_insert(0);
+                *n += 1;
+                *n - 1
+            })
+            .unwrap_or(0);
+        let route = matching[hit.min(matching.len() - 1)];
+        (route.status, route.body.clone(), route.headers.clone())
+    }
+}
+
+impl CoreTransport for MockTransport {
+    fn name(&self) -> &'static str {
+        MOCK
+    }
+
+    fn send(&self, request: CoreRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let path = request.path();
+            if let Ok(mut received) = self.received.lock() {
+                if received.len() >= MAX_MOCK_REQUESTS {
+                    received.remove(0);
+                }
+                received.push(json!({
+                    "at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                    "method": request.method.as_str(),
+                    "path": path,
+                    "body": request.body,
+                }));
+            }
+            let (status, body, extra) = self.answer(&request.method, &path);
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                reqwest::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            for (name, value) in extra {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(&value),
+                ) {
+                    headers.insert(name, value);
+                }
+            }
+            let text = match body {
+                Value::Null => String::new(),
+                Value::String(raw) => raw,
+                other => other.to_string(),
+            };
+            Ok(CoreResponse {
+                status: StatusCode::from_u16(status).unwrap_or(StatusCode::OK),
+                headers,
+                url: request.url,
+                body: spill::Body::Buffered(text),
+            })
+        })
+    }
+}
+
+struct Recording {
+    profile_id: String,
+    origins: Vec<String>,
+    started_at: String,
+    exchanges: Vec<Value>,
+    dropped: usize,
+}
+
+impl Recording {
+    fn covers(&self, base_url: &str) -> bool {
+        net::base_origin(base_url).is_some_and(|o| self.origins.contains(&o))
+    }
+
+    fn push(
+        &mut self,
+        method: Method,
+        path: String,
+        request: Option<Value>,
+        response: &CoreResponse,
+        transport: &str,
+    ) {
+        if self.exchanges.len() >= MAX_RECORDED {
+            self.dropped += 1;
+            return;
+        }
+        // Spilled bodies are too large to keep; the handle is meaningless
+        // once replayed.
+        let mut body = match &response.body {
+            spill::Body::Buffered(text) => {
+                serde_json::from_str(text).unwrap_or_else(|_| json!(text))
+            }
+            spill::Body::Spilled(_) => Value::Null,
+        };
+        sanitize::scrub(&mut body);
+        self.exchanges.push(json!({
+            "method": method.as_str(),
+            "path": path,
+            "request": request,
+            "status": response.status.as_u16(),
+            "body": body,
+            "transport": transport,
+        }));
+    }
+}
+
+/// Starts recording every exchange with the profile's core (its base URL
+/// and replicas), replacing any recording in progress.
+#[tauri::command]
+pub fn start_core_recording(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = store.snapshot();
+    let origins: Vec<String> = settings
+        .profile(&conn.profile_id)
+        .map(|p| p.endpoints().filter_map(net::base_origin).collect())
+        .unwrap_or_default();
+    let started_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    *recording()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Recording state poisoned"))? = Some(Recording {
+        profile_id: conn.profile_id.clone(),
+        origins,
+        started_at: started_at.clone(),
+        exchanges: Vec::new(),
+        dropped: 0,
+    });
+    Ok(json!({ "profile_id": conn.profile_id, "started_at": started_at }))
+}
+
+/// Stops the recording and returns it. Response bodies are scrubbed like
+/// any other core response; request headers, tokens included, are never
+/// kept. Pass it to `replay_core_recording` to serve it as a mock core.
+#[tauri::command]
+pub fn stop_core_recording() -> Result<Value, String> {
+    let recording = recording()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Recording state poisoned"))?
+        .take()
+        .ok_or_else(|| ErrorCode::NotFound.with("No core recording is running"))?;
+    Ok(json!({
+        "profile_id": recording.profile_id,
+        "started_at": recording.started_at,
+        "stopped_at": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "exchanges": recording.exchanges,
+        "dropped": recording.dropped,
+    }))
+}
+
+/// Drops every origin `mock` stands in for.
+fn uninstall(mock: &Arc<MockTransport>) {
+    if let Ok(mut installed) = installed().lock() {
+        let target = Arc::as_ptr(mock) as *const ();
+        installed.retain(|_, t| Arc::as_ptr(t) as *const () != target);
+    }
+}
+
+pub fn mock_name(name: &str) -> Result<String, String> {
+    let name = name.trim().to_ascii_lowercase();
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(ErrorCode::InvalidInput.with(
+            "Mock core names are up to 64 letters, digits and dashes, used as mock://<name>",
+        ));
+    }
+    Ok(name)
+}
+
+fn register_mock(
+    store: &SettingsStore,
+    name: &str,
+    routes: Vec<MockRoute>,
+    stands_in_for: Option<String>,
+) -> Result<Value, String> {
+    let name = mock_name(name)?;
+    let stands_in_for = stands_in_for
+        .map(|b| b.trim().trim_end_matches('/').to_string())
+        .filter(|b| !b.is_empty());
+    if let Some(base_url) = &stands_in_for {
+        let origin = net::base_origin(base_url);
+        let settings = store.snapshot();
+        let prod = settings.profiles.iter().find(|p| {
+            p.environment == EnvironmentClass::Prod
+                && p.endpoints().any(|e| net::base_origin(e) == origin)
+        });
+        if let Some(prod) = prod {
+            return Err(ErrorCode::ForbiddenLocal.with(format!(
+                "{} is a production profile's core; a mock cannot stand in for it",
+                prod.id
+            )));
+        }
+    }
+    if let Some(bad) = routes
+        .iter()
+        .find(|r| !r.path.starts_with('/') || Method::from_bytes(r.method.as_bytes()).is_err())
+    {
+        return Err(ErrorCode::InvalidInput.with(format!(
+            "Mock route {} {} needs a method and a path starting with /",
+            bad.method, bad.path
+        )));
+    }
+    let count = routes.len();
+    let mock = Arc::new(MockTransport::new(routes));
+    let replaced = mocks()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Transport state poisoned"))?
+        .insert(name.clone(), mock.clone());
+    if let Some(old) = replaced {
+        uninstall(&old);
+    }
+    if let Some(base_url) = &stands_in_for {
+        install(base_url, mock)?;
+    }
+    Ok(json!({
+        "name": name,
+        "base_url": format!("{}://{}", MOCK, name),
+        "stands_in_for": stands_in_for,
+        "routes": count,
+    }))
+}
+
+/// Serves `routes` as the core at `mock://<name>`, replacing any mock of
+/// that name. Point a profile's base URL at it, or pass `stands_in_for`
+/// with a non-production core's base URL to answer its requests instead,
+/// to drive every command without a live core.
+#[tauri::command]
+pub fn set_mock_core(
+    store: State<'_, SettingsStore>,
+    name: String,
+    routes: Vec<MockRoute>,
+    stands_in_for: Option<String>,
+) -> Result<Value, String> {
+    register_mock(&store, &name, routes, stands_in_for)
+}
+
+/// Serves a recording from `stop_core_recording` as `mock://<name>`,
+/// answering each request with what the core answered, in order.
+#[tauri::command]
+pub fn replay_core_recording(
+    store: State<'_, SettingsStore>,
+    name: String,
+    recording: Value,
+    stands_in_for: Option<String>,
+) -> Result<Value, String> {
+    let routes = recording["exchanges"]
+        .as_array()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Recording has no exchanges"))?
+        .iter()
+        .filter_map(|exchange| {
+            Some(MockRoute {
+                method: exchange["method"].as_str()?.to_string(),
+                path: exchange["path"].as_str()?.to_string(),
+                status: exchange["status"].as_u64().map_or(200, |s| s as u16),
+                body: exchange["body"].clone(),
+                headers: HashMap::new(),
+            })
+        })
+        .collect();
+    register_mock(&store, &name, routes, stands_in_for)
+}
+
+/// The requests the mock core has received, oldest first, and clears them.
+#[tauri::command]
+pub fn get_mock_requests(name: String) -> Result<Value, String> {
+    let name = mock_name(&name)?;
+    let mock = mocks()
+        .lock()
+        .ok()
+        .and_then(|m| m.get(&name).cloned())
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("No mock core named {:?}", name)))?;
+    let received: Vec<Value> = mock
+        .received
+        .lock()
+        .map(|mut r| r.drain(..).collect())
+        .unwrap_or_default();
+    Ok(json!({ "name": name, "requests": received }))
+}
+
+/// Removes the mock core and hands any core it stood in for back to its
+/// own transport.
+#[tauri::command]
+pub fn remove_mock_core(name: String) -> Result<Value, String> {
+    let name = mock_name(&name)?;
+    let removed = mocks()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Transport state poisoned"))?
+        .remove(&name);
+    if let Some(mock) = &removed {
+        uninstall(mock);
+    }
+    Ok(json!({ "name": name, "removed": removed.is_some() }))
+}
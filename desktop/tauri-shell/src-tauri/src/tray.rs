@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{SecondsFormat, Utc};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, State, WindowEvent};
+
+use crate::errors::{self, ErrorCode};
+use crate::settings::{QuickAction, SettingsStore};
+use crate::{environment, quick_actions, tasks};
+
+const TRAY_ID: &str = "main";
+const MAIN_WINDOW: &str = "main";
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+const PENDING_EVENT: &str = "approvals:pending";
+/// Menu ids of quick actions carry this prefix before the action id.
+const QUICK_ACTION_PREFIX: &str = "quick_action:";
+
+/// Plans awaiting approval per profile. The tray tooltip, the dock/taskbar
+/// badge and `approvals:pending` are all rendered from this one map.
+#[derive(Default)]
+pub struct PendingApprovals {
+    by_profile: Mutex<HashMap<String, usize>>,
+    /// How each profile's core answered the last count: `ok`,
+    /// `maintenance` or `unreachable`, with when.
+    health: Mutex<HashMap<String, (&'static str, String)>>,
+}
+
+impl PendingApprovals {
+    fn total(&self) -> usize {
+        self.by_profile
+            .lock()
+            .map(|counts| counts.values().sum())
+            .unwrap_or(0)
+    }
+
+    fn record_health(&self, profile_id: &str, result: Result<(), &str>) {
+        let health = match result {
+            Ok(()) => "ok",
+            Err(err) if errors::code_of(err) == Some(ErrorCode::CoreMaintenance) => "maintenance",
+            Err(_) => "unreachable",
+        };
+        if let Ok(mut map) = self.health.lock() {
+            map.insert(
+                profile_id.to_string(),
+                (
+                    health,
+                    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                ),
+            );
+        }
+    }
+
+    /// The pending count and core health per profile, and overall: `core`
+    /// is `ok` when every polled core answered, `down` when none did, and
+    /// `degraded` in between.
+    pub fn summary(&self) -> Value {
+        let counts = self
+            .by_profile
+            .lock()
+            .map(|c| c.clone())
+            .unwrap_or_default();
+        let health = self.health.lock().map(|h| h.clone()).unwrap_or_default();
+        let mut ids: Vec<&String> = counts.keys().chain(health.keys()).collect();
+        ids.sort();
+        ids.dedup();
+        let profiles: Vec<Value> = ids
+            .iter()
+            .map(|id| {
+                let (state, checked_at) = health
+                    .get(*id)
+                    .map(|(s, at)| (*s, Some(at.as_str())))
+                    .unwrap_or(("unknown", None));
+                json!({
+                    "profile_id": id,
+                    "pending": counts.get(*id).copied().unwrap_or(0),
+                    "health": state,
+                    "checked_at": checked_at,
+                })
+            })
+            .collect();
+        let up = health.values().filter(|(s, _)| *s == "ok").count();
+        let core = match (up, health.len()) {
+            (_, 0) => "unknown",
+            (up, all) if up == all => "ok",
+            (0, _) => "down",
+            _ => "degraded",
+        };
+        json!({
+            "pending": counts.values().sum::<usize>(),
+            "core": core,
+            "profiles": profiles,
+        })
+    }
+}
+
+/// Builds the tray icon and keeps the app alive in it when the main window
+/// is closed, if `desktop.close_to_tray` is set.
+pub fn install(app: &AppHandle) -> tauri::Result<()> {
+    let actions = app.state::<SettingsStore>().snapshot().quick_actions;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("NovaAdapt Desktop")
+        .menu(&menu(app, &actions)?)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => show_main_window(app),
+            "quit" => app.exit(0),
+            id => {
+                if let Some(action) = id.strip_prefix(QUICK_ACTION_PREFIX) {
+                    quick_actions::run_from_tray(app, action);
+                }
+            }
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let handle = app.clone();
+        window.on_window_event(move |event| {
+            if let WindowEvent::CloseRequested { api, .. } = event {
+                let store = handle.state::<SettingsStore>();
+                if store.snapshot().desktop.close_to_tray {
+                    api.prevent_close();
+                    if let Some(window) = handle.get_webview_window(MAIN_WINDOW) {
+                        let _ = window.hide();
+                    }
+                } else {
+                    crate::shutdown::on_close_requested(&handle, api);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Show, the quick actions marked for the tray, then Quit.
+fn menu(app: &AppHandle, actions: &[QuickAction]) -> tauri::Result<Menu<tauri::Wry>> {
+    let menu = Menu::with_items(
+        app,
+        &[&MenuItem::with_id(
+            app,
+            "show",
+            "Show NovaAdapt",
+            true,
+            None::<&str>,
+        )?],
+    )?;
+    let listed: Vec<&QuickAction> = actions.iter().filter(|a| a.tray).collect();
+    if !listed.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        for action in listed {
+            let id = format!("{}{}", QUICK_ACTION_PREFIX, action.id);
+            menu.append(&MenuItem::with_id(
+                app,
+                id,
+                &action.name,
+                true,
+                None::<&str>,
+            )?)?;
+        }
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+    }
+    menu.append(&MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?)?;
+    Ok(menu)
+}
+
+/// Rebuilds the tray menu after the quick actions changed.
+pub fn set_quick_actions(app: &AppHandle, actions: &[QuickAction]) {
+    if let (Some(tray), Ok(menu)) = (app.tray_by_id(TRAY_ID), menu(app, actions)) {
+        let _ = tray.set_menu(Some(menu));
+    }
+}
+
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+/// Counts pending plans for every profile in the background, so the badge
+/// stays current while the window is hidden in the tray.
+const TASK: &str = "tray";
+
+pub fn spawn_poller(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let profiles = app.state::<SettingsStore>().snapshot().profiles;
+            let state = app.state::<PendingApprovals>();
+            if let Ok(mut counts) = state.by_profile.lock() {
+                counts.retain(|id, _| profiles.iter().any(|p| p.id == *id));
+            }
+            if let Ok(mut health) = state.health.lock() {
+                health.retain(|id, _| profiles.iter().any(|p| p.id == *id));
+            }
+            for profile in profiles {
+                let result = refresh_profile(&app, &profile.id).await;
+                state.record_health(
+                    &profile.id,
+                    result.as_ref().map(|_| ()).map_err(String::as_str),
+                );
+                if let Err(err) = result {
+                    tasks::failed(TASK, &err);
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn refresh_profile(app: &AppHandle, profile_id: &str) -> Result<usize, String> {
+    let conn = app.state::<SettingsStore>().connection(Some(profile_id))?;
+    let plans = crate::request_json(
+        Method::GET,
+        &conn.base_url,
+        "/plans?limit=200",
+        conn.token.clone(),
+        None,
+    )
+    .await?;
+    let pending = plans
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|p| p["status"].as_str() == Some("pending"))
+        .count();
+    set_pending(app, &conn.profile_id, pending);
+    Ok(pending)
+}
+
+/// Records a profile's pending count, from this poller or from the state
+/// store's dashboard refresh, and re-renders the tray and badge.
+pub fn set_pending(app: &AppHandle, profile_id: &str, pending: usize) {
+    let state = app.state::<PendingApprovals>();
+    if let Ok(mut counts) = state.by_profile.lock() {
+        counts.insert(profile_id.to_string(), pending);
+    }
+    render(app, &state);
+}
+
+fn render(app: &AppHandle, state: &PendingApprovals) {
+    let total = state.total();
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let tooltip = match total {
+            0 => "NovaAdapt Desktop".to_string(),
+            1 => "NovaAdapt Desktop: 1 plan awaiting approval".to_string(),
+            n => format!("NovaAdapt Desktop: {} plans awaiting approval", n),
+        };
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        set_badge(&window, total);
+    }
+    let _ = environment::emit(app, PENDING_EVENT, json!({ "pending": total }));
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_badge(window: &tauri::WebviewWindow, count: usize) {
+    let _ = window.set_badge_count((count > 0).then_some(count as i64));
+}
+
+/// The Windows taskbar has no numeric badge; a red dot overlay marks that
+/// approvals are waiting and the tray tooltip carries the count.
+#[cfg(target_os = "windows")]
+fn set_badge(window: &tauri::WebviewWindow, count: usize) {
+    const SIZE: u32 = 16;
+    let overlay = (count > 0).then(|| {
+        let center = (SIZE as f32 - 1.0) / 2.0;
+        let rgba: Vec<u8> = (0..SIZE * SIZE)
+            .flat_map(|i| {
+                let (x, y) = ((i % SIZE) as f32, (i / SIZE) as f32);
+                let inside = (x - center).powi(2) + (y - center).powi(2) <= center.powi(2);
+                if inside {
+                    [220, 38, 38, 255]
+                } else {
+                    [0, 0, 0, 0]
+                }
+            })
+            .collect();
+        tauri::image::Image::new_owned(rgba, SIZE, SIZE)
+    });
+    let _ = window.set_overlay_icon(overlay);
+}
+
+/// Re-counts pending approvals for one profile (or the active one) right
+/// away, e.g. after the operator approved or rejected a plan.
+#[tauri::command]
+pub async fn refresh_pending_approvals(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    let pending = refresh_profile(&app, &profile_id).await?;
+    let total = app.state::<PendingApprovals>().total();
+    Ok(json!({ "profile_id": profile_id, "pending": pending, "total": total }))
+}
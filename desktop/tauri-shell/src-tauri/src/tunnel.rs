@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::Url;
+use russh::client::{self, Handle};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::agent::AgentIdentity;
+use russh::keys::{HashAlg, PublicKeyOrCertificate};
+use serde_json::{json, Value};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::errors::ErrorCode;
+use crate::net::{self, TunnelRoute};
+use crate::settings::{Profile, SettingsStore, TunnelAuth, TunnelSettings};
+use crate::{environment, secrets, tasks};
+
+const STATUS_EVENT: &str = "tunnel:status";
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(15);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_SSH_PORT: u16 = 22;
+
+/// Open SSH sessions and the last reported status, keyed by profile id.
+#[derive(Default)]
+pub struct Tunnels {
+    active: Mutex<HashMap<String, Tunnel>>,
+    status: Mutex<HashMap<String, Value>>,
+}
+
+/// A jump-host session plus the loopback listener forwarding into it. The
+/// listener speaks just enough SOCKS5 for the profile's HTTP client to hand
+/// over the core's address, and only forwards to that address.
+struct Tunnel {
+    settings: TunnelSettings,
+    base_url: String,
+    session: Arc<Handle<HostKeyCheck>>,
+    listener: JoinHandle<()>,
+}
+
+impl Tunnels {
+    fn close(&self, profile_id: &str) -> bool {
+        let tunnel = self
+            .active
+            .lock()
+            .ok()
+            .and_then(|mut active| active.remove(profile_id));
+        let Some(tunnel) = tunnel else {
+            return false;
+        };
+        tunnel.listener.abort();
+        net::set_tunnel(&tunnel.base_url, None);
+        tauri::async_runtime::spawn(async move {
+            let _ = tunnel
+                .session
+                .disconnect(russh::Disconnect::ByApplication, "", "en")
+                .await;
+        });
+        true
+    }
+
+    fn is_up(&self, profile_id: &str) -> bool {
+        self.active
+            .lock()
+            .map(|active| active.contains_key(profile_id))
+            .unwrap_or(false)
+    }
+}
+
+/// Keeps a tunnel open for every profile that enables one, reconnecting
+/// dropped sessions and closing tunnels whose settings changed.
+const TASK: &str = "tunnel";
+
+pub fn spawn_supervisor(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            supervise(&app).await;
+            tasks::ran(TASK);
+            tokio::time::sleep(SUPERVISE_INTERVAL).await;
+        }
+    });
+}
+
+async fn supervise(app: &AppHandle) {
+    let profiles = app.state::<SettingsStore>().snapshot().profiles;
+    let tunnels = app.state::<Tunnels>();
+    let stale: Vec<(String, bool)> = tunnels
+        .active
+        .lock()
+        .map(|active| {
+            active
+                .iter()
+                .filter_map(|(id, tunnel)| {
+                    let profile = profiles.iter().find(|p| p.id == *id);
+                    let wanted = profile.is_some_and(|p| {
+                        p.tunnel.enabled
+                            && p.tunnel == tunnel.settings
+                            && p.base_url == tunnel.base_url
+                    });
+                    let dropped = tunnel.session.is_closed();
+                    (!wanted || dropped).then(|| (id.clone(), profile.is_some()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    for (profile_id, exists) in stale {
+        tunnels.close(&profile_id);
+        if exists {
+            set_status(app, &profile_id, json!({ "state": "down" }));
+        } else if let Ok(mut status) = tunnels.status.lock() {
+            status.remove(&profile_id);
+        }
+    }
+
+    for profile in profiles.iter().filter(|p| p.tunnel.enabled) {
+        if tunnels.is_up(&profile.id) {
+            continue;
+        }
+        net::set_tunnel(&profile.base_url, Some(TunnelRoute::Down));
+        set_status(app, &profile.id, json!({ "state": "connecting" }));
+        match open(profile).await {
+            Ok((tunnel, local_port)) => {
+                if let Ok(mut active) = tunnels.active.lock() {
+                    active.insert(profile.id.clone(), tunnel);
+                }
+                net::set_tunnel(&profile.base_url, Some(TunnelRoute::Up(local_port)));
+                set_status(
+                    app,
+                    &profile.id,
+                    json!({
+                        "state": "up",
+                        "local_port": local_port,
+                        "connected_at": chrono::Utc::now().to_rfc3339(),
+                    }),
+                );
+            }
+            Err(err) => set_status(app, &profile.id, json!({ "state": "error", "error": err })),
+        }
+    }
+    for profile in profiles.iter().filter(|p| !p.tunnel.enabled) {
+        let known = tunnels
+            .status
+            .lock()
+            .map(|s| s.contains_key(&profile.id))
+            .unwrap_or(false);
+        if known {
+            net::set_tunnel(&profile.base_url, None);
+            if let Ok(mut status) = tunnels.status.lock() {
+                status.remove(&profile.id);
+            }
+        }
+    }
+}
+
+fn set_status(app: &AppHandle, profile_id: &str, mut status: Value) {
+    status["profile_id"] = json!(profile_id);
+    status["updated_at"] = json!(chrono::Utc::now().to_rfc3339());
+    if let Ok(mut all) = app.state::<Tunnels>().status.lock() {
+        all.insert(profile_id.to_string(), status.clone());
+    }
+    let _ = environment::emit(app, STATUS_EVENT, &status);
+}
+
+/// Reports tunnel state (`connecting`, `up`, `down`, `error`) for one profile
+/// or, without `profile`, for every profile that has a tunnel.
+#[tauri::command]
+pub fn tunnel_status(
+    store: State<'_, SettingsStore>,
+    tunnels: State<'_, Tunnels>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let status = tunnels
+        .status
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Tunnel state poisoned"))?;
+    match profile {
+        Some(_) => {
+            let conn = store.connection(profile.as_deref())?;
+            Ok(status
+                .get(&conn.profile_id)
+                .cloned()
+                .unwrap_or_else(|| json!({ "profile_id": conn.profile_id, "state": "disabled" })))
+        }
+        None => Ok(json!({ "tunnels": status.values().cloned().collect::<Vec<_>>() })),
+    }
+}
+
+/// Drops a profile's SSH session and reconnects it straight away.
+#[tauri::command]
+pub async fn restart_tunnel(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    tunnels: State<'_, Tunnels>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let restarted = tunnels.close(&conn.profile_id);
+    supervise(&app).await;
+    let status = tunnels
+        .status
+        .lock()
+        .ok()
+        .and_then(|s| s.get(&conn.profile_id).cloned())
+        .unwrap_or_else(|| json!({ "profile_id": conn.profile_id, "state": "disabled" }));
+    Ok(json!({ "restarted": restarted, "status": status }))
+}
+
+async fn open(profile: &Profile) -> Result<(Tunnel, u16), String> {
+    let settings = &profile.tunnel;
+    let (target_host, target_port) = core_address(&profile.base_url)?;
+    let host = settings.host.trim().to_string();
+    let port = settings.port.unwrap_or(DEFAULT_SSH_PORT);
+    let rejected = Arc::new(Mutex::new(None));
+    let check = HostKeyCheck {
+        host: host.clone(),
+        port,
+        pinned: settings.host_key_fingerprint.clone(),
+        rejected: rejected.clone(),
+    };
+    let config = Arc::new(client::Config {
+        keepalive_interval: Some(KEEPALIVE_INTERVAL),
+        ..Default::default()
+    });
+    let mut session = match client::connect(config, (host.as_str(), port), check).await {
+        Ok(session) => session,
+        Err(err) => {
+            let reason = rejected.lock().ok().and_then(|mut r| r.take());
+            return Err(reason.unwrap_or_else(|| {
+                ErrorCode::CoreUnreachable.with(format!("SSH connect to {} failed: {}", host, err))
+            }));
+        }
+    };
+    authenticate(&mut session, profile).await?;
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Tunnel listener failed: {}", e)))?;
+    let local_port = listener
+        .local_addr()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Tunnel listener failed: {}", e)))?
+        .port();
+    let session = Arc::new(session);
+    let task = tauri::async_runtime::spawn(accept_loop(
+        listener,
+        session.clone(),
+        target_host,
+        target_port,
+    ));
+    Ok((
+        Tunnel {
+            settings: settings.clone(),
+            base_url: profile.base_url.clone(),
+            session,
+            listener: task,
+        },
+        local_port,
+    ))
+}
+
+pub fn core_address(base_url: &str) -> Result<(String, u16), String> {
+    let url = Url::parse(base_url.trim())
+        .map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid base URL: {}", e)))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Base URL has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| ErrorCode::InvalidInput.with("Base URL has no port"))?;
+    Ok((host, port))
+}
+
+async fn authenticate(session: &mut Handle<HostKeyCheck>, profile: &Profile) -> Result<(), String> {
+    let user = profile.tunnel.user.trim();
+    let ssh_err =
+        |e: russh::Error| ErrorCode::CoreUnreachable.with(format!("SSH session failed: {}", e));
+    let accepted = match profile.tunnel.auth {
+        TunnelAuth::Password => {
+            let password = secrets::get(&secrets::profile_ssh_password_key(&profile.id))?
+                .ok_or_else(|| {
+                    ErrorCode::InvalidInput.with("No SSH password stored for this profile")
+                })?;
+            session
+                .authenticate_password(user, password)
+                .await
+                .map_err(ssh_err)?
+                .success()
+        }
+        TunnelAuth::Agent => {
+            let hash_alg = session
+                .best_supported_rsa_hash()
+                .await
+                .map_err(ssh_err)?
+                .flatten();
+            #[cfg(unix)]
+            let agent = AgentClient::connect_env().await;
+            #[cfg(windows)]
+            let agent = AgentClient::connect_pageant().await;
+            let mut agent = agent
+                .map_err(|e| ErrorCode::LocalIo.with(format!("SSH agent unavailable: {}", e)))?;
+            authenticate_with_agent(session, user, hash_alg, &mut agent).await?
+        }
+    };
+    if accepted {
+        Ok(())
+    } else {
+        Err(ErrorCode::Forbidden.with(format!("SSH authentication as {} was rejected", user)))
+    }
+}
+
+/// Offers each agent identity in turn until the jump host accepts one.
+async fn authenticate_with_agent<R>(
+    session: &mut Handle<HostKeyCheck>,
+    user: &str,
+    hash_alg: Option<HashAlg>,
+    agent: &mut AgentClient<R>,
+) -> Result<bool, String>
+where
+    R: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let identities = agent
+        .request_identities()
+        .await
+        .map_err(|e| ErrorCode::LocalIo.with(format!("SSH agent failed: {}", e)))?;
+    for identity in identities {
+        let AgentIdentity::PublicKey { key, .. } = identity else {
+            continue;
+        };
+        let result = session
+            .authenticate_publickey_with(user, key, hash_alg, agent)
+            .await
+            .map_err(|e| {
+                ErrorCode::CoreUnreachable.with(format!("SSH agent auth failed: {}", e))
+            })?;
+        if result.success() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    session: Arc<Handle<HostKeyCheck>>,
+    target_host: String,
+    target_port: u16,
+) {
+    while let Ok((stream, peer)) = listener.accept().await {
+        let session = session.clone();
+        let host = target_host.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = relay(stream, peer, &session, &host, target_port).await {
+                eprintln!("tunnel connection from {} failed: {}", peer, err);
+            }
+        });
+    }
+}
+
+async fn relay(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    session: &Handle<HostKeyCheck>,
+    target_host: &str,
+    target_port: u16,
+) -> Result<(), String> {
+    let (host, port) = socks_accept(&mut stream).await?;
+    if !host.eq_ignore_ascii_case(target_host) || port != target_port {
+        let _ = stream.write_all(&socks_reply(0x02)).await;
+        return Err(format!("refused forward to {}:{}", host, port));
+    }
+    let channel = match session
+        .channel_open_direct_tcpip(
+            target_host,
+            u32::from(target_port),
+            peer.ip().to_string(),
+            u32::from(peer.port()),
+        )
+        .await
+    {
+        Ok(channel) => channel,
+        Err(err) => {
+            let _ = stream.write_all(&socks_reply(0x05)).await;
+            return Err(err.to_string());
+        }
+    };
+    stream
+        .write_all(&socks_reply(0x00))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut remote = channel.into_stream();
+    tokio::io::copy_bidirectional(&mut stream, &mut remote)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Server side of a SOCKS5 handshake: no-auth greeting, then one CONNECT
+/// request. Returns the requested host and port.
+pub async fn socks_accept(stream: &mut TcpStream) -> Result<(String, u16), String> {
+    let io = |e: std::io::Error| e.to_string();
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await.map_err(io)?;
+    if greeting[0] != 5 {
+        return Err("not a SOCKS5 client".to_string());
+    }
+    let mut methods = vec![0u8; usize::from(greeting[1])];
+    stream.read_exact(&mut methods).await.map_err(io)?;
+    if !methods.contains(&0) {
+        let _ = stream.write_all(&[5, 0xff]).await;
+        return Err("client offered no usable SOCKS auth method".to_string());
+    }
+    stream.write_all(&[5, 0]).await.map_err(io)?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await.map_err(io)?;
+    if request[1] != 1 {
+        let _ = stream.write_all(&socks_reply(0x07)).await;
+        return Err("only SOCKS CONNECT is supported".to_string());
+    }
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await.map_err(io)?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let len = stream.read_u8().await.map_err(io)?;
+            let mut name = vec![0u8; usize::from(len)];
+            stream.read_exact(&mut name).await.map_err(io)?;
+            String::from_utf8(name).map_err(|_| "invalid SOCKS host name".to_string())?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await.map_err(io)?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => {
+            let _ = stream.write_all(&socks_reply(0x08)).await;
+            return Err("unsupported SOCKS address type".to_string());
+        }
+    };
+    let port = stream.read_u16().await.map_err(io)?;
+    Ok((host, port))
+}
+
+pub fn socks_reply(code: u8) -> [u8; 10] {
+    [5, code, 0, 1, 0, 0, 0, 0, 0, 0]
+}
+
+/// Accepts the jump host's key only if it matches the pinned fingerprint, or
+/// `~/.ssh/known_hosts` when none is pinned. Rejections are recorded so the
+/// caller can report the presented fingerprint.
+struct HostKeyCheck {
+    host: String,
+    port: u16,
+    pinned: Option<String>,
+    rejected: Arc<Mutex<Option<String>>>,
+}
+
+impl client::Handler for HostKeyCheck {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKeyOrCertificate,
+    ) -> Result<bool, Self::Error> {
+        let key = server_public_key.public_key();
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        let verdict = match self
+            .pinned
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            Some(pinned) if pinned == fingerprint => Ok(()),
+            Some(_) => Err(format!(
+                "SSH host key for {} does not match the pinned fingerprint (got {})",
+                self.host, fingerprint
+            )),
+            None => match russh::keys::check_known_hosts(&self.host, self.port, &key) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(format!(
+                    "SSH host key for {} is not in known_hosts; pin {} to trust it",
+                    self.host, fingerprint
+                )),
+                Err(err) => Err(format!(
+                    "SSH host key check for {} failed: {}",
+                    self.host, err
+                )),
+            },
+        };
+        match verdict {
+            Ok(()) => Ok(true),
+            Err(reason) => {
+                if let Ok(mut rejected) = self.rejected.lock() {
+                    *rejected = Some(ErrorCode::Forbidden.with(reason));
+                }
+                Ok(false)
+            }
+        }
+    }
+}
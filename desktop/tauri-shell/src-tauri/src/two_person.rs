@@ -0,0 +1,702 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::db::{self, LocalDb};
+use crate::decisions::PlanRead;
+use crate::errors::ErrorCode;
+use crate::settings::{Profile, SecondApprover, SettingsStore, TwoPersonMethod, TwoPersonSettings};
+use crate::{crypto, jwt, net, quick_actions, secrets};
+
+/// RFC 6238 defaults, which is what authenticator apps assume.
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes from the step before or after are accepted, for clock drift.
+const SKEW_STEPS: i64 = 1;
+/// How long a confirmed second approval waits for the approval it covers.
+const CONFIRMATION_TTL: Duration = Duration::from_secs(300);
+const MAX_APPROVERS: usize = 20;
+/// Wrong confirmations a profile may have before codes are refused for
+/// `LOCKOUT`, which keeps a guessed six-digit code out of reach.
+const MAX_FAILURES: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+const MAX_NAME_LEN: usize = 80;
+const ISSUER: &str = "NovaAdapt";
+
+/// Two-person settings per core origin, refreshed from the profiles.
+static CONFIG: OnceLock<Mutex<HashMap<String, (String, TwoPersonSettings)>>> = OnceLock::new();
+/// Confirmed second approvals keyed by `origin plan_id`, spent by the
+/// approval they cover.
+static CONFIRMED: OnceLock<Mutex<HashMap<String, Confirmation>>> = OnceLock::new();
+/// Core-issued codes waiting to be typed in, by `origin plan_id`.
+static ISSUED: OnceLock<Mutex<HashMap<String, Issued>>> = OnceLock::new();
+/// The last TOTP step used per approver, so a code is good once.
+static USED_STEPS: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+/// Failed confirmations per profile since the last good one.
+static FAILURES: OnceLock<Mutex<HashMap<String, Failures>>> = OnceLock::new();
+
+#[derive(Clone)]
+struct Confirmation {
+    first: String,
+    second: String,
+    method: TwoPersonMethod,
+    at: Instant,
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+struct Issued {
+    digest: String,
+    approver: String,
+    expires: Instant,
+}
+
+fn config() -> &'static Mutex<HashMap<String, (String, TwoPersonSettings)>> {
+    CONFIG.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn confirmed() -> &'static Mutex<HashMap<String, Confirmation>> {
+    CONFIRMED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn issued() -> &'static Mutex<HashMap<String, Issued>> {
+    ISSUED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn used_steps() -> &'static Mutex<HashMap<String, i64>> {
+    USED_STEPS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn failures() -> &'static Mutex<HashMap<String, Failures>> {
+    FAILURES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Refuses confirmations for a profile locked out by wrong codes.
+fn ensure_not_locked(profile_id: &str) -> Result<(), String> {
+    let mut failures = failures()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Confirmation state poisoned"))?;
+    let Some(entry) = failures.get_mut(profile_id) else {
+        return Ok(());
+    };
+    match entry.locked_until {
+        Some(until) if until > Instant::now() => Err(ErrorCode::ForbiddenLocal.with(format!(
+            "Too many wrong confirmation codes; try again in {} minutes",
+            (until - Instant::now()).as_secs().div_ceil(60)
+        ))),
+        Some(_) => {
+            failures.remove(profile_id);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed confirmation, locking the profile out after
+/// `MAX_FAILURES`; a good one clears the count.
+fn record_attempt(profile_id: &str, ok: bool) {
+    let Ok(mut failures) = failures().lock() else {
+        return;
+    };
+    if ok {
+        failures.remove(profile_id);
+        return;
+    }
+    let entry = failures.entry(profile_id.to_string()).or_default();
+    entry.count += 1;
+    if entry.count >= MAX_FAILURES {
+        entry.count = 0;
+        entry.locked_until = Some(Instant::now() + LOCKOUT);
+    }
+}
+
+/// Carries each approver's `enrolled_by` over from `before`, so editing the
+/// settings cannot move an approver onto another operator. Only
+/// `enroll_second_approver` adds approvers, to `enrolling`; any other new
+/// one is refused. Returns the keyring keys of the approvers `after` drops.
+pub fn keep_enrollment(
+    before: &[Profile],
+    after: &mut [Profile],
+    enrolling: Option<&str>,
+) -> Result<Vec<String>, String> {
+    for profile in after.iter_mut() {
+        let previous = before.iter().find(|p| p.id == profile.id);
+        for approver in &mut profile.two_person.approvers {
+            let old = previous.and_then(|previous| {
+                previous
+                    .two_person
+                    .approvers
+                    .iter()
+                    .find(|a| a.id == approver.id)
+            });
+            match old {
+                Some(old) => approver.enrolled_by = old.enrolled_by.clone(),
+                None if enrolling == Some(profile.id.as_str()) => {}
+                None => {
+                    return Err(ErrorCode::InvalidInput.with(format!(
+                        "Profile {}: second approvers are added by enrolling them",
+                        profile.id
+                    )))
+                }
+            }
+        }
+    }
+    Ok(before
+        .iter()
+        .flat_map(|previous| {
+            let kept = after.iter().find(|p| p.id == previous.id);
+            previous
+                .two_person
+                .approvers
+                .iter()
+                .filter(move |approver| {
+                    !kept
+                        .is_some_and(|p| p.two_person.approvers.iter().any(|a| a.id == approver.id))
+                })
+                .map(|approver| approver_secret_key(&previous.id, &approver.id))
+        })
+        .collect())
+}
+
+pub fn configure(profiles: &[Profile]) {
+    let map = profiles
+        .iter()
+        .filter(|p| p.two_person.enabled)
+        .flat_map(|p| {
+            p.endpoints()
+                .filter_map(net::base_origin)
+                .map(|origin| (origin, (p.id.clone(), p.two_person.clone())))
+        })
+        .collect();
+    *config().lock().unwrap_or_else(|e| e.into_inner()) = map;
+}
+
+pub fn validate(settings: &TwoPersonSettings) -> Result<(), String> {
+    if settings.approvers.len() > MAX_APPROVERS {
+        return Err(
+            ErrorCode::InvalidInput.with(format!("At most {} second approvers", MAX_APPROVERS))
+        );
+    }
+    let mut ids = std::collections::HashSet::new();
+    for approver in &settings.approvers {
+        if approver.id.trim().is_empty() || !ids.insert(approver.id.as_str()) {
+            return Err(
+                ErrorCode::InvalidInput.with("Second approver ids must be unique and non-empty")
+            );
+        }
+        if approver.name.trim().is_empty() || approver.name.len() > MAX_NAME_LEN {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Second approver name must be 1 to {} bytes",
+                MAX_NAME_LEN
+            )));
+        }
+    }
+    if settings.enabled && settings.method == TwoPersonMethod::Totp && settings.approvers.is_empty()
+    {
+        return Err(
+            ErrorCode::InvalidInput.with("Two-person approval by TOTP needs an enrolled approver")
+        );
+    }
+    Ok(())
+}
+
+pub fn approver_secret_key(profile_id: &str, approver_id: &str) -> String {
+    format!("profile:{}:approver:{}", profile_id, approver_id)
+}
+
+fn key(base_url: &str, plan_id: &str) -> String {
+    format!(
+        "{} {}",
+        net::base_origin(base_url).unwrap_or_default(),
+        plan_id
+    )
+}
+
+/// Plans the core rates at critical risk, or flags as critical.
+fn is_critical(plan: &Value) -> bool {
+    plan["critical"].as_bool() == Some(true)
+        || ["risk", "risk_level"].iter().any(|k| {
+            plan[*k]
+                .as_str()
+                .is_some_and(|r| r.eq_ignore_ascii_case("critical"))
+        })
+}
+
+/// Who is approving: the token's user when it names one, else the OS user.
+pub fn first_identity(token: Option<&str>) -> String {
+    let claims = token.and_then(jwt::decode_claims);
+    let from_token = claims.as_ref().and_then(|claims| {
+        ["preferred_username", "email", "sub"]
+            .iter()
+            .find_map(|k| claims[*k].as_str().filter(|v| !v.is_empty()))
+            .map(str::to_string)
+    });
+    from_token
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "operator".to_string())
+}
+
+fn totp(secret: &[u8], step: i64) -> Result<u32, String> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret)
+        .map_err(|e| ErrorCode::Internal.with(e.to_string()))?;
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Ok(bin % 10u32.pow(DIGITS))
+}
+
+/// The step `code` is valid for, if any, around now.
+fn verify_totp(secret: &[u8], code: &str) -> Result<Option<i64>, String> {
+    let Ok(code) = code.trim().replace(' ', "").parse::<u32>() else {
+        return Ok(None);
+    };
+    let now = chrono::Utc::now().timestamp() / STEP_SECS;
+    for step in now - SKEW_STEPS..=now + SKEW_STEPS {
+        if totp(secret, step)? == code {
+            return Ok(Some(step));
+        }
+    }
+    Ok(None)
+}
+
+fn equal_digest(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn hex_digest(code: &str) -> String {
+    Sha256::digest(code.trim().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Holds an approval of a critical plan until a second operator has
+/// confirmed it, for any core a profile enables the two-person rule on.
+/// Returns the confirmation that covers it; `redeem` spends it once the
+/// approval is otherwise cleared to go.
+pub async fn guard(plan: &mut PlanRead<'_>) -> Result<Option<Value>, String> {
+    let (base_url, plan_id) = (plan.base_url(), plan.plan_id());
+    let origin = net::base_origin(base_url).unwrap_or_default();
+    let Some((profile_id, settings)) = config()
+        .lock()
+        .ok()
+        .and_then(|map| map.get(&origin).cloned())
+    else {
+        return Ok(None);
+    };
+    if !is_critical(plan.get().await?) {
+        return Ok(None);
+    }
+    let found = confirmed()
+        .lock()
+        .ok()
+        .and_then(|map| map.get(&key(base_url, plan_id)).cloned())
+        .filter(|c| c.at.elapsed() <= CONFIRMATION_TTL);
+    match found {
+        Some(confirmation) => Ok(Some(json!({
+            "first": confirmation.first,
+            "second": confirmation.second,
+            "method": confirmation.method,
+        }))),
+        None => Err(ErrorCode::TwoPersonRequired.with(json!({
+            "message": "This plan is critical; a second operator must confirm the approval",
+            "plan_id": plan_id,
+            "profile_id": profile_id,
+            "method": settings.method,
+            "approvers": settings.approvers,
+        }))),
+    }
+}
+
+/// Spends the confirmation `guard` found for `plan_id`.
+pub fn redeem(base_url: &str, plan_id: &str) {
+    if let Ok(mut map) = confirmed().lock() {
+        map.remove(&key(base_url, plan_id));
+    }
+}
+
+fn profile_settings(store: &SettingsStore, profile_id: &str) -> Result<TwoPersonSettings, String> {
+    let settings = store
+        .snapshot()
+        .profile(profile_id)
+        .map(|p| p.two_person.clone())
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    Ok(settings)
+}
+
+/// Adds a second approver to the profile with a fresh TOTP secret, returned
+/// once (with an `otpauth://` URI for a QR code) for their authenticator
+/// app; the shell keeps it only in the keyring. The enrolling operator has
+/// seen the secret, so the approver never confirms that operator's own
+/// approvals.
+#[tauri::command]
+pub fn enroll_second_approver(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    name: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let profile_id = conn.profile_id;
+    let enrolled_by = first_identity(conn.token.as_deref());
+    let name = name.trim().to_string();
+    let hex: String = crypto::random_bytes::<6>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let approver = SecondApprover {
+        id: format!("approver-{}", hex),
+        name: name.clone(),
+        enrolled_by: enrolled_by.clone(),
+    };
+    let secret = BASE32_NOPAD.encode(&crypto::random_bytes::<20>());
+    let key = approver_secret_key(&profile_id, &approver.id);
+    secrets::set(&key, &secret)?;
+    let added = store.update_enrollment(&app, &profile_id, |settings| {
+        let profile = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| ErrorCode::NotFound.with("Profile disappeared"))?;
+        profile.two_person.approvers.push(approver.clone());
+        Ok(())
+    });
+    if let Err(err) = added {
+        let _ = secrets::delete(&key);
+        return Err(err);
+    }
+    db::record_audit(
+        &*db.lock()?,
+        &profile_id,
+        "approval",
+        "second_approver_enrolled",
+        &approver.id,
+        &json!({ "name": name, "enrolled_by": enrolled_by }),
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    let label = quick_actions::encode_segment(&format!("{}:{}", ISSUER, name));
+    Ok(json!({
+        "profile_id": profile_id,
+        "approver": approver,
+        "secret": secret,
+        "otpauth_uri": format!(
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            label, secret, ISSUER, DIGITS, STEP_SECS
+        ),
+    }))
+}
+
+#[tauri::command]
+pub fn remove_second_approver(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    approver_id: String,
+) -> Result<Value, String> {
+    let profile_id = store.connection(profile.as_deref())?.profile_id;
+    store.update(&app, |settings| {
+        let profile = settings
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| ErrorCode::NotFound.with("Profile disappeared"))?;
+        let before = profile.two_person.approvers.len();
+        profile.two_person.approvers.retain(|a| a.id != approver_id);
+        if profile.two_person.approvers.len() == before {
+            return Err(ErrorCode::NotFound.with(format!("Unknown approver: {}", approver_id)));
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "profile_id": profile_id, "removed": approver_id }))
+}
+
+/// Asks the core to send a one-time code for `plan_id` to a second
+/// operator. The core answers with the code's SHA-256 digest, which the
+/// typed code is checked against here.
+#[tauri::command]
+pub async fn request_confirmation_code(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+    plan_id: String,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    if profile_settings(&store, &conn.profile_id)?.method != TwoPersonMethod::Core {
+        return Err(ErrorCode::InvalidInput.with("This profile confirms with TOTP codes"));
+    }
+    let body = crate::request_json(
+        Method::POST,
+        &conn.base_url,
+        &format!("/plans/{}/confirmation_code", plan_id),
+        conn.token,
+        Some(json!({})),
+    )
+    .await?;
+    let digest = body["digest"]
+        .as_str()
+        .map(str::to_ascii_lowercase)
+        .filter(|d| d.len() == 64 && d.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| ErrorCode::CoreError.with("Core returned no code digest"))?;
+    let approver = body["approver"]
+        .as_str()
+        .filter(|a| !a.trim().is_empty())
+        .ok_or_else(|| ErrorCode::CoreError.with("Core did not name the second approver"))?
+        .to_string();
+    let ttl = body["expires_in_seconds"].as_u64().unwrap_or(300).min(900);
+    issued()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Confirmation state poisoned"))?
+        .insert(
+            key(&conn.base_url, &plan_id),
+            Issued {
+                digest,
+                approver: approver.clone(),
+                expires: Instant::now() + Duration::from_secs(ttl),
+            },
+        );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "approver": approver,
+        "expires_in_seconds": ttl,
+    }))
+}
+
+/// Checks a second operator's code for `plan_id` (from `approver_id`'s
+/// authenticator, or the one the core issued) and records both operators
+/// in the audit trail. The next approval of the plan within five minutes
+/// is then let through, once. After `MAX_FAILURES` wrong codes the profile
+/// takes none for `LOCKOUT`.
+#[tauri::command]
+pub fn confirm_second_approval(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    plan_id: String,
+    code: String,
+    approver_id: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let settings = profile_settings(&store, &conn.profile_id)?;
+    if !settings.enabled {
+        return Err(ErrorCode::InvalidInput.with("Two-person approval is off for this profile"));
+    }
+    ensure_not_locked(&conn.profile_id)?;
+    let first = first_identity(conn.token.as_deref());
+    let entry = key(&conn.base_url, &plan_id);
+    let verified: Result<String, String> = match settings.method {
+        TwoPersonMethod::Totp => {
+            let approver = match approver_id.as_deref() {
+                Some(id) => settings.approvers.iter().find(|a| a.id == id),
+                None if settings.approvers.len() == 1 => settings.approvers.first(),
+                None => None,
+            }
+            .ok_or_else(|| ErrorCode::InvalidInput.with("Choose which approver is confirming"))?;
+            if approver.enrolled_by.trim().is_empty() {
+                return Err(ErrorCode::ForbiddenLocal.with(format!(
+                    "{} was enrolled without a record of who enrolled them; enroll them again",
+                    approver.name
+                )));
+            }
+            if approver.enrolled_by.eq_ignore_ascii_case(&first) {
+                return Err(ErrorCode::ForbiddenLocal.with(format!(
+                    "{} enrolled {} and cannot confirm their own approval with them",
+                    first, approver.name
+                )));
+            }
+            let secret = secrets::get(&approver_secret_key(&conn.profile_id, &approver.id))?
+                .and_then(|s| BASE32_NOPAD.decode(s.as_bytes()).ok())
+                .ok_or_else(|| {
+                    ErrorCode::LocalStorage.with(format!("No TOTP secret for {}", approver.name))
+                })?;
+            let replay_key = format!("{} {}", conn.profile_id, approver.id);
+            let mut used = used_steps()
+                .lock()
+                .map_err(|_| ErrorCode::Internal.with("Confirmation state poisoned"))?;
+            match verify_totp(&secret, &code)? {
+                Some(step) if used.get(&replay_key).is_none_or(|last| step > *last) => {
+                    used.insert(replay_key, step);
+                    Ok(approver.name.clone())
+                }
+                Some(_) => Err(ErrorCode::ForbiddenLocal
+                    .with("That code was already used; wait for the next one")),
+                None => Err(ErrorCode::ForbiddenLocal.with("The code is wrong or has expired")),
+            }
+        }
+        TwoPersonMethod::Core => {
+            let mut pending = issued()
+                .lock()
+                .map_err(|_| ErrorCode::Internal.with("Confirmation state poisoned"))?;
+            match pending.get(&entry) {
+                Some(issued) if issued.expires < Instant::now() => {
+                    pending.remove(&entry);
+                    Err(ErrorCode::ForbiddenLocal.with("The code has expired; request a new one"))
+                }
+                Some(issued) if equal_digest(&issued.digest, &hex_digest(&code)) => {
+                    let approver = issued.approver.clone();
+                    pending.remove(&entry);
+                    Ok(approver)
+                }
+                Some(_) => Err(ErrorCode::ForbiddenLocal.with("The code is wrong")),
+                None => Err(ErrorCode::ForbiddenLocal.with("No code was requested for this plan")),
+            }
+        }
+    };
+    let verified = verified.and_then(|second| {
+        if second.eq_ignore_ascii_case(&first) {
+            Err(ErrorCode::ForbiddenLocal.with(format!(
+                "{} cannot confirm their own approval; a second operator must",
+                first
+            )))
+        } else {
+            Ok(second)
+        }
+    });
+    record_attempt(&conn.profile_id, verified.is_ok());
+    let mut detail = Map::new();
+    detail.insert("first".to_string(), json!(first));
+    detail.insert("method".to_string(), json!(settings.method));
+    match &verified {
+        Ok(second) => detail.insert("second".to_string(), json!(second)),
+        Err(err) => detail.insert("error".to_string(), json!(err)),
+    };
+    db::record_audit(
+        &*db.lock()?,
+        &conn.profile_id,
+        "approval",
+        if verified.is_ok() {
+            "second_approval"
+        } else {
+            "second_approval_failed"
+        },
+        &plan_id,
+        &Value::Object(detail),
+    )
+    .map_err(|e| ErrorCode::LocalStorage.with(format!("Audit write failed: {}", e)))?;
+    let second = verified?;
+    confirmed()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Confirmation state poisoned"))?
+        .insert(
+            entry,
+            Confirmation {
+                first: first.clone(),
+                second: second.clone(),
+                method: settings.method,
+                at: Instant::now(),
+            },
+        );
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "plan_id": plan_id,
+        "confirmed": true,
+        "first": first,
+        "second": second,
+        "expires_in_seconds": CONFIRMATION_TTL.as_secs(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 secret of RFC 6238 appendix B.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn totp_matches_the_rfc_6238_vectors() {
+        for (time, code) in [
+            (59, 287_082),
+            (1_111_111_109, 81_804),
+            (1_234_567_890, 5_924),
+            (2_000_000_000, 279_037),
+        ] {
+            assert_eq!(totp(RFC_SECRET, time / STEP_SECS).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn verify_totp_accepts_the_current_step_and_nothing_else() {
+        let now = chrono::Utc::now().timestamp() / STEP_SECS;
+        let code = format!("{:06}", totp(RFC_SECRET, now).unwrap());
+        assert!(verify_totp(RFC_SECRET, &code).unwrap().is_some());
+        let spaced = format!("{} {}", &code[..3], &code[3..]);
+        assert!(verify_totp(RFC_SECRET, &spaced).unwrap().is_some());
+        let stale = format!("{:06}", totp(RFC_SECRET, now - 10).unwrap());
+        if stale != code {
+            assert_eq!(verify_totp(RFC_SECRET, &stale).unwrap(), None);
+        }
+        assert_eq!(verify_totp(RFC_SECRET, "not a code").unwrap(), None);
+    }
+
+    fn profile(approvers: &[(&str, &str)]) -> Profile {
+        let mut profile = Profile {
+            id: "prod".to_string(),
+            ..Profile::default()
+        };
+        profile.two_person.approvers = approvers
+            .iter()
+            .map(|(id, enrolled_by)| SecondApprover {
+                id: id.to_string(),
+                name: id.to_string(),
+                enrolled_by: enrolled_by.to_string(),
+            })
+            .collect();
+        profile
+    }
+
+    #[test]
+    fn settings_edits_cannot_add_or_reassign_approvers() {
+        let before = [profile(&[("a-1", "alice")])];
+        let mut after = [profile(&[("a-1", "mallory")])];
+        assert_eq!(keep_enrollment(&before, &mut after, None), Ok(Vec::new()));
+        assert_eq!(after[0].two_person.approvers[0].enrolled_by, "alice");
+
+        let mut added = [profile(&[("a-1", "alice"), ("a-2", "bob")])];
+        assert!(keep_enrollment(&before, &mut added, None).is_err());
+        assert!(keep_enrollment(&[], &mut added, None).is_err());
+        assert!(keep_enrollment(&before, &mut added, Some("prod")).is_ok());
+        assert!(keep_enrollment(&before, &mut added, Some("dev")).is_err());
+    }
+
+    #[test]
+    fn dropped_approvers_lose_their_secrets() {
+        let before = [profile(&[("a-1", "alice"), ("a-2", "bob")])];
+        let mut after = [profile(&[("a-2", "bob")])];
+        assert_eq!(
+            keep_enrollment(&before, &mut after, None),
+            Ok(vec![approver_secret_key("prod", "a-1")])
+        );
+        assert_eq!(keep_enrollment(&before, &mut [], None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn digests_compare_by_value() {
+        assert!(equal_digest(&hex_digest("123456"), &hex_digest(" 123456 ")));
+        assert!(!equal_digest(&hex_digest("123456"), &hex_digest("123457")));
+        assert!(!equal_digest("ab", "abc"));
+    }
+}
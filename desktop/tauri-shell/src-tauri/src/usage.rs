@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::{self, LocalDb};
+use crate::errors::{self, ErrorCode};
+use crate::settings::{Connection as CoreConnection, SettingsStore};
+
+const USAGE_STREAM: &str = "usage";
+const PAGE_LIMIT: usize = 500;
+const MAX_PAGES: usize = 20;
+
+#[derive(Default)]
+struct UsageRecord {
+    at: DateTime<Utc>,
+    model: String,
+    agent: String,
+    plan_id: String,
+    calls: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+    cost_usd: f64,
+}
+
+/// Pulls usage from the core and folds it into the local daily rollups.
+#[tauri::command]
+pub async fn sync_usage(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    sync_profile(&conn, &db).await
+}
+
+/// Cores exposing `/usage/records` provide per-call attribution (agent, plan);
+/// older cores only expose cumulative per-model counters on
+/// `/runtime/governance`, which are diffed against the last seen snapshot.
+pub async fn sync_profile(conn: &CoreConnection, db: &LocalDb) -> Result<Value, String> {
+    let cursor = db::get_cursor(&*db.lock()?, &conn.profile_id, USAGE_STREAM);
+
+    match fetch_records(conn, cursor).await {
+        Ok((records, next_cursor)) => {
+            let guard = db.lock()?;
+            for record in &records {
+                add_record(&guard, &conn.profile_id, record).map_err(|e| e.to_string())?;
+            }
+            if let Some(next) = next_cursor {
+                db::set_cursor(&guard, &conn.profile_id, USAGE_STREAM, &next)
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(json!({
+                "profile_id": conn.profile_id,
+                "source": "records",
+                "ingested": records.len(),
+            }))
+        }
+        // Don't mask an expired token behind the fallback.
+        Err(err) if errors::code_of(&err) == Some(ErrorCode::AuthExpired) => Err(err),
+        Err(_) => {
+            let governance = crate::request_json(
+                Method::GET,
+                &conn.base_url,
+                "/runtime/governance",
+                conn.token.clone(),
+                None,
+            )
+            .await?;
+            let guard = db.lock()?;
+            let ingested = ingest_governance_deltas(&guard, &conn.profile_id, &governance)
+                .map_err(|e| e.to_string())?;
+            Ok(json!({
+                "profile_id": conn.profile_id,
+                "source": "governance",
+                "ingested": ingested,
+            }))
+        }
+    }
+}
+
+/// Returns spend time-series bucketed by `period` (`day` or `week`),
+/// optionally split by `group_by` (`model`, `agent`, or `plan`).
+#[tauri::command]
+pub fn get_usage_series(
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    profile: Option<String>,
+    period: Option<String>,
+    group_by: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<Value, String> {
+    let conn = store.connection(profile.as_deref())?;
+    let bucket = match period.as_deref().unwrap_or("day") {
+        "day" => "day",
+        "week" => "week",
+        other => return Err(ErrorCode::InvalidInput.with(format!("Unsupported period: {}", other))),
+    };
+    let key = match group_by.as_deref().unwrap_or("") {
+        "" => "''",
+        "model" => "model",
+        "agent" => "agent",
+        "plan" => "plan_id",
+        other => {
+            return Err(ErrorCode::InvalidInput.with(format!("Unsupported group_by: {}", other)))
+        }
+    };
+    let sql = format!(
+        "SELECT {bucket}, {key}, SUM(calls), SUM(input_tokens), SUM(output_tokens), SUM(cost_usd)
+         FROM usage_daily
+         WHERE profile_id = ?1 AND day >= ?2 AND day <= ?3
+         GROUP BY {bucket}, {key}
+         ORDER BY {bucket}, {key}"
+    );
+    let guard = db.lock()?;
+    let mut stmt = guard.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            params![
+                conn.profile_id,
+                since.as_deref().unwrap_or("0000-00-00"),
+                until.as_deref().unwrap_or("9999-99-99"),
+            ],
+            |row| {
+                Ok(json!({
+                    "bucket": row.get::<_, String>(0)?,
+                    "key": row.get::<_, String>(1)?,
+                    "calls": row.get::<_, i64>(2)?,
+                    "input_tokens": row.get::<_, i64>(3)?,
+                    "output_tokens": row.get::<_, i64>(4)?,
+                    "cost_usd": row.get::<_, f64>(5)?,
+                }))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(json!({
+        "profile_id": conn.profile_id,
+        "period": bucket,
+        "group_by": group_by,
+        "series": rows,
+    }))
+}
+
+/// Total cost recorded for `profile_id` between two inclusive `YYYY-MM-DD` days.
+pub fn spend_between(conn: &Connection, profile_id: &str, from_day: &str, to_day: &str) -> f64 {
+    conn.query_row(
+        "SELECT COALESCE(SUM(cost_usd), 0) FROM usage_daily
+         WHERE profile_id = ?1 AND day >= ?2 AND day <= ?3",
+        (profile_id, from_day, to_day),
+        |row| row.get(0),
+    )
+    .unwrap_or(0.0)
+}
+
+async fn fetch_records(
+    conn: &CoreConnection,
+    mut cursor: Option<String>,
+) -> Result<(Vec<UsageRecord>, Option<String>), String> {
+    let mut records = Vec::new();
+    for _ in 0..MAX_PAGES {
+        let mut path = format!("/usage/records?limit={}", PAGE_LIMIT);
+        if let Some(since) = &cursor {
+            path.push_str(&format!("&since={}", since));
+        }
+        let page =
+            crate::request_json(Method::GET, &conn.base_url, &path, conn.token.clone(), None)
+                .await?;
+        let Some(items) = page["records"].as_array() else {
+            return Err("Core did not return usage records".to_string());
+        };
+        records.extend(items.iter().filter_map(parse_record));
+        let next = page["next_cursor"].as_str().map(str::to_string);
+        let done = items.len() < PAGE_LIMIT || next.is_none() || next == cursor;
+        if next.is_some() {
+            cursor = next;
+        }
+        if done {
+            break;
+        }
+    }
+    Ok((records, cursor))
+}
+
+fn parse_record(item: &Value) -> Option<UsageRecord> {
+    let at = item["timestamp"]
+        .as_str()
+        .or_else(|| item["created_at"].as_str())
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())?
+        .with_timezone(&Utc);
+    let text = |key: &str| item[key].as_str().unwrap_or_default().to_string();
+    Some(UsageRecord {
+        at,
+        model: text("model"),
+        agent: text("agent"),
+        plan_id: text("plan_id"),
+        calls: item["calls"].as_i64().unwrap_or(1),
+        input_tokens: item["input_tokens"].as_i64().unwrap_or(0),
+        output_tokens: item["output_tokens"].as_i64().unwrap_or(0),
+        cost_usd: item["cost_usd"]
+            .as_f64()
+            .or_else(|| item["estimated_cost_usd"].as_f64())
+            .unwrap_or(0.0),
+    })
+}
+
+fn add_record(conn: &Connection, profile_id: &str, r: &UsageRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO usage_daily
+            (profile_id, day, week, model, agent, plan_id, calls, input_tokens, output_tokens, cost_usd)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(profile_id, day, model, agent, plan_id) DO UPDATE SET
+            calls = calls + excluded.calls,
+            input_tokens = input_tokens + excluded.input_tokens,
+            output_tokens = output_tokens + excluded.output_tokens,
+            cost_usd = cost_usd + excluded.cost_usd",
+        params![
+            profile_id,
+            r.at.format("%Y-%m-%d").to_string(),
+            r.at.format("%G-W%V").to_string(),
+            r.model,
+            r.agent,
+            r.plan_id,
+            r.calls,
+            r.input_tokens,
+            r.output_tokens,
+            r.cost_usd,
+        ],
+    )?;
+    Ok(())
+}
+
+fn ingest_governance_deltas(
+    conn: &Connection,
+    profile_id: &str,
+    governance: &Value,
+) -> rusqlite::Result<usize> {
+    let mut ingested = 0;
+    let now = Utc::now();
+    for (model, stats) in governance["per_model"].as_object().into_iter().flatten() {
+        let calls = stats["calls"].as_i64().unwrap_or(0);
+        let cost = stats["estimated_cost_usd"].as_f64().unwrap_or(0.0);
+        let (prev_calls, prev_cost): (i64, f64) = conn
+            .query_row(
+                "SELECT calls, cost_usd FROM usage_model_counters WHERE profile_id = ?1 AND model = ?2",
+                (profile_id, model),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap_or((0, 0.0));
+        // Counters going backwards means the core's usage was reset.
+        let (delta_calls, delta_cost) = if calls < prev_calls || cost < prev_cost {
+            (calls, cost)
+        } else {
+            (calls - prev_calls, cost - prev_cost)
+        };
+        if delta_calls > 0 || delta_cost > 0.0 {
+            add_record(
+                conn,
+                profile_id,
+                &UsageRecord {
+                    at: now,
+                    model: model.clone(),
+                    calls: delta_calls,
+                    cost_usd: delta_cost,
+                    ..Default::default()
+                },
+            )?;
+            ingested += 1;
+        }
+        conn.execute(
+            "INSERT INTO usage_model_counters (profile_id, model, calls, cost_usd)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(profile_id, model) DO UPDATE SET calls = excluded.calls,
+                 cost_usd = excluded.cost_usd",
+            params![profile_id, model, calls, cost],
+        )?;
+    }
+    Ok(ingested)
+}
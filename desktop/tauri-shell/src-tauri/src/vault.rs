@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::errors::ErrorCode;
+use crate::settings::{Profile, SettingsStore, VaultAuth, VaultSettings};
+use crate::{crypto, environment, net, secrets, tasks};
+
+const RENEW_INTERVAL: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// Static secrets carry no lease, so they are re-read this often to pick up
+/// rotations.
+const DEFAULT_REFRESH: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_FIELD: &str = "token";
+/// Vault's documented default for CLI-style OIDC logins; the role must list
+/// `http://localhost:8250/oidc/callback` as an allowed redirect.
+const OIDC_PORT: u16 = 8250;
+const OIDC_TIMEOUT: Duration = Duration::from_secs(300);
+const ERROR_EVENT: &str = "vault:error";
+const REAUTH_EVENT: &str = "vault:reauth_required";
+const OIDC_URL_EVENT: &str = "vault:oidc_url";
+
+struct Session {
+    token: String,
+    issued: Instant,
+    /// `None` for a token that never expires.
+    ttl: Option<Duration>,
+    renewable: bool,
+}
+
+impl Session {
+    fn expired(&self) -> bool {
+        self.ttl.is_some_and(|ttl| self.issued.elapsed() >= ttl)
+    }
+
+    /// Renewed once two thirds of the TTL have passed.
+    fn wants_renewal(&self) -> bool {
+        self.renewable
+            && self
+                .ttl
+                .is_some_and(|ttl| self.issued.elapsed() >= ttl.mul_f64(2.0 / 3.0))
+    }
+}
+
+struct Held {
+    value: String,
+    fetched: Instant,
+    refresh_after: Duration,
+}
+
+#[derive(Default)]
+struct ProfileState {
+    settings: VaultSettings,
+    session: Option<Session>,
+    credential: Option<Held>,
+    last_error: Option<String>,
+}
+
+/// Vault-backed profiles by id. Credentials and Vault tokens here live only
+/// in memory.
+static STATE: OnceLock<Mutex<HashMap<String, ProfileState>>> = OnceLock::new();
+
+fn state() -> &'static Mutex<HashMap<String, ProfileState>> {
+    STATE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn vault_secret_key(profile_id: &str) -> String {
+    format!("profile:{}:vault_secret", profile_id)
+}
+
+/// Tracks which profiles use Vault; a profile whose Vault settings changed
+/// drops its session and credential so the next renewal starts over.
+pub fn configure(profiles: &[Profile]) {
+    let mut state = state().lock().unwrap_or_else(|e| e.into_inner());
+    state.retain(|id, entry| {
+        profiles
+            .iter()
+            .any(|p| p.id == *id && p.vault.enabled && p.vault == entry.settings)
+    });
+    for profile in profiles.iter().filter(|p| p.vault.enabled) {
+        state
+            .entry(profile.id.clone())
+            .or_insert_with(|| ProfileState {
+                settings: profile.vault.clone(),
+                ..ProfileState::default()
+            });
+    }
+}
+
+pub fn enabled(profile_id: &str) -> bool {
+    state()
+        .lock()
+        .map(|s| s.contains_key(profile_id))
+        .unwrap_or(false)
+}
+
+/// The core credential last fetched for the profile, if any.
+pub fn credential(profile_id: &str) -> Option<String> {
+    state()
+        .lock()
+        .ok()?
+        .get(profile_id)?
+        .credential
+        .as_ref()
+        .map(|held| held.value.clone())
+}
+
+pub fn validate(vault: &VaultSettings) -> Result<(), String> {
+    if !vault.enabled {
+        return Ok(());
+    }
+    let parsed =
+        Url::parse(vault.address.trim()).map_err(|e| format!("Invalid Vault address: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Vault address must be http(s)".to_string());
+    }
+    if vault.path.trim().trim_matches('/').is_empty() {
+        return Err("Vault secret path is required".to_string());
+    }
+    if vault.auth == VaultAuth::Approle && vault.role_id.trim().is_empty() {
+        return Err("Vault AppRole auth needs a role id".to_string());
+    }
+    Ok(())
+}
+
+fn mount(vault: &VaultSettings) -> &str {
+    match vault.mount.trim().trim_matches('/') {
+        "" => match vault.auth {
+            VaultAuth::Token => "token",
+            VaultAuth::Approle => "approle",
+            VaultAuth::Oidc => "oidc",
+        },
+        mount => mount,
+    }
+}
+
+fn endpoint(vault: &VaultSettings, path: &str) -> Result<Url, String> {
+    let url = format!(
+        "{}/v1/{}",
+        vault.address.trim().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    Url::parse(&url).map_err(|e| ErrorCode::InvalidInput.with(format!("Invalid Vault URL: {}", e)))
+}
+
+async fn call(
+    vault: &VaultSettings,
+    method: Method,
+    url: Url,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> Result<Value, String> {
+    let client = net::client_for(&url)?;
+    let mut req = client.request(method, url).timeout(REQUEST_TIMEOUT);
+    if let Some(token) = token {
+        req = req.header("X-Vault-Token", token);
+    }
+    if !vault.namespace.trim().is_empty() {
+        req = req.header("X-Vault-Namespace", vault.namespace.trim());
+    }
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let response = req.send().await.map_err(|e| {
+        let code = if e.is_timeout() {
+            ErrorCode::CoreTimeout
+        } else {
+            ErrorCode::CoreUnreachable
+        };
+        code.with(format!("Vault request failed: {}", e))
+    })?;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        let reasons: Vec<&str> = body["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        // Vault answers 403 for a dead token as well as a missing policy.
+        let code = match status.as_u16() {
+            401 | 403 => ErrorCode::AuthExpired,
+            code => ErrorCode::from_status(code),
+        };
+        return Err(code.with(format!("Vault {}: {}", status.as_u16(), reasons.join("; "))));
+    }
+    Ok(body)
+}
+
+fn session_from(auth: &Value) -> Option<Session> {
+    let token = auth["client_token"].as_str()?.to_string();
+    let ttl = auth["lease_duration"]
+        .as_u64()
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    Some(Session {
+        token,
+        issued: Instant::now(),
+        ttl,
+        renewable: auth["renewable"].as_bool().unwrap_or(false),
+    })
+}
+
+async fn login(profile_id: &str, vault: &VaultSettings) -> Result<Session, String> {
+    let missing = |what: &str| {
+        ErrorCode::AuthExpired.with(format!(
+            "No Vault {} stored for profile {}",
+            what, profile_id
+        ))
+    };
+    match vault.auth {
+        VaultAuth::Token => {
+            let token =
+                secrets::get(&vault_secret_key(profile_id))?.ok_or_else(|| missing("token"))?;
+            let url = endpoint(vault, "auth/token/lookup-self")?;
+            let body = call(vault, Method::GET, url, Some(&token), None).await?;
+            let data = &body["data"];
+            Ok(Session {
+                token,
+                issued: Instant::now(),
+                ttl: data["ttl"]
+                    .as_u64()
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs),
+                renewable: data["renewable"].as_bool().unwrap_or(false),
+            })
+        }
+        VaultAuth::Approle => {
+            let secret_id = secrets::get(&vault_secret_key(profile_id))?
+                .ok_or_else(|| missing("AppRole secret id"))?;
+            let url = endpoint(vault, &format!("auth/{}/login", mount(vault)))?;
+            let body = call(
+                vault,
+                Method::POST,
+                url,
+                None,
+                Some(json!({ "role_id": vault.role_id.trim(), "secret_id": secret_id })),
+            )
+            .await?;
+            session_from(&body["auth"])
+                .ok_or_else(|| ErrorCode::CoreError.with("Vault login returned no token"))
+        }
+        VaultAuth::Oidc => Err(ErrorCode::AuthExpired
+            .with("Vault OIDC session has expired; sign in again with vault_oidc_login")),
+    }
+}
+
+/// Reads the credential field from a KV v2 (`data.data`) or v1 (`data`)
+/// response; a secret with a lease is refreshed at two thirds of it.
+async fn fetch(vault: &VaultSettings, token: &str) -> Result<Held, String> {
+    let url = endpoint(vault, vault.path.trim().trim_matches('/'))?;
+    let body = call(vault, Method::GET, url, Some(token), None).await?;
+    let field = match vault.field.trim() {
+        "" => DEFAULT_FIELD,
+        field => field,
+    };
+    let value = body["data"]["data"][field]
+        .as_str()
+        .or_else(|| body["data"][field].as_str())
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| {
+            ErrorCode::NotFound.with(format!(
+                "Vault secret {} has no `{}` field",
+                vault.path.trim(),
+                field
+            ))
+        })?;
+    let refresh_after = body["lease_duration"]
+        .as_u64()
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs).mul_f64(2.0 / 3.0))
+        .unwrap_or(DEFAULT_REFRESH);
+    Ok(Held {
+        value: value.to_string(),
+        fetched: Instant::now(),
+        refresh_after,
+    })
+}
+
+/// Logs in, renews or re-fetches as needed so the profile holds a current
+/// credential. Network calls run without the state lock held.
+async fn ensure(profile_id: &str, force: bool) -> Result<(), String> {
+    let (vault, session, stale) = {
+        let state = state()
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Vault state poisoned"))?;
+        let entry = state.get(profile_id).ok_or_else(|| {
+            ErrorCode::InvalidInput.with(format!("Profile {} does not use Vault", profile_id))
+        })?;
+        let session = entry
+            .session
+            .as_ref()
+            .filter(|s| !s.expired())
+            .map(|s| (s.token.clone(), s.wants_renewal()));
+        let stale = force
+            || entry
+                .credential
+                .as_ref()
+                .is_none_or(|held| held.fetched.elapsed() >= held.refresh_after);
+        (entry.settings.clone(), session, stale)
+    };
+
+    let mut renewed = None;
+    let token = match session {
+        Some((token, false)) => token,
+        Some((token, true)) => {
+            let url = endpoint(&vault, "auth/token/renew-self")?;
+            let body = call(&vault, Method::POST, url, Some(&token), Some(json!({}))).await;
+            let session = match body.ok().and_then(|b| session_from(&b["auth"])) {
+                Some(session) => session,
+                None => login(profile_id, &vault).await?,
+            };
+            let token = session.token.clone();
+            renewed = Some(session);
+            token
+        }
+        None => {
+            let session = login(profile_id, &vault).await?;
+            let token = session.token.clone();
+            renewed = Some(session);
+            token
+        }
+    };
+    let held = if stale {
+        Some(fetch(&vault, &token).await?)
+    } else {
+        None
+    };
+
+    let mut state = state()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Vault state poisoned"))?;
+    if let Some(entry) = state.get_mut(profile_id).filter(|e| e.settings == vault) {
+        if let Some(session) = renewed {
+            entry.session = Some(session);
+        }
+        if let Some(held) = held {
+            entry.credential = Some(held);
+        }
+        entry.last_error = None;
+    }
+    Ok(())
+}
+
+fn record_error(profile_id: &str, err: &str) -> bool {
+    let Ok(mut state) = state().lock() else {
+        return false;
+    };
+    let Some(entry) = state.get_mut(profile_id) else {
+        return false;
+    };
+    let changed = entry.last_error.as_deref() != Some(err);
+    entry.last_error = Some(err.to_string());
+    changed
+}
+
+/// Keeps every Vault-backed profile's session renewed and its credential
+/// current. Failures are announced once per distinct error; an OIDC session
+/// that can no longer be renewed asks the operator to sign in again.
+const TASK: &str = "vault";
+
+pub fn spawn_renewer(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            let ids: Vec<String> = state()
+                .lock()
+                .map(|s| s.keys().cloned().collect())
+                .unwrap_or_default();
+            for profile_id in ids {
+                let Err(err) = ensure(&profile_id, false).await else {
+                    continue;
+                };
+                tasks::failed(TASK, &err);
+                if record_error(&profile_id, &err) {
+                    let payload = json!({ "profile_id": profile_id, "error": err });
+                    let reauth = crate::errors::code_of(&err) == Some(ErrorCode::AuthExpired);
+                    let _ = environment::emit(
+                        &app,
+                        if reauth { REAUTH_EVENT } else { ERROR_EVENT },
+                        &payload,
+                    );
+                }
+            }
+            tasks::ran(TASK);
+            tokio::time::sleep(RENEW_INTERVAL).await;
+        }
+    });
+}
+
+fn status(profile_id: &str) -> Value {
+    let Ok(state) = state().lock() else {
+        return Value::Null;
+    };
+    let Some(entry) = state.get(profile_id) else {
+        return json!({ "profile_id": profile_id, "enabled": false });
+    };
+    let session = entry.session.as_ref().filter(|s| !s.expired());
+    json!({
+        "profile_id": profile_id,
+        "enabled": true,
+        "auth": entry.settings.auth,
+        "authenticated": session.is_some(),
+        "session_expires_in_seconds": session
+            .and_then(|s| s.ttl.map(|ttl| ttl.saturating_sub(s.issued.elapsed()).as_secs())),
+        "credential_loaded": entry.credential.is_some(),
+        "credential_age_seconds": entry.credential.as_ref().map(|h| h.fetched.elapsed().as_secs()),
+        "refresh_in_seconds": entry
+            .credential
+            .as_ref()
+            .map(|h| h.refresh_after.saturating_sub(h.fetched.elapsed()).as_secs()),
+        "last_error": entry.last_error,
+    })
+}
+
+/// Reports the profile's Vault session and credential state; never the
+/// credential itself.
+#[tauri::command]
+pub fn vault_status(store: State<'_, SettingsStore>, profile: Option<String>) -> Value {
+    let settings = store.snapshot();
+    let id = profile
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or(settings.active_profile);
+    status(&id)
+}
+
+/// Re-reads the credential from Vault now, logging in first if needed.
+#[tauri::command]
+pub async fn vault_refresh(
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let id = profile
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or(settings.active_profile);
+    if let Err(err) = ensure(&id, true).await {
+        record_error(&id, &err);
+        return Err(err);
+    }
+    Ok(status(&id))
+}
+
+/// Stores (or with `None`, clears) the profile's Vault token (`token` auth)
+/// or AppRole secret id in the keyring.
+#[tauri::command]
+pub fn set_vault_secret(
+    store: State<'_, SettingsStore>,
+    profile_id: String,
+    secret: Option<String>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let profile = settings
+        .profile(&profile_id)
+        .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown profile: {}", profile_id)))?;
+    if profile.vault.auth == VaultAuth::Oidc {
+        return Err(ErrorCode::InvalidInput
+            .with("OIDC auth stores no secret; sign in with vault_oidc_login"));
+    }
+    let key = vault_secret_key(&profile_id);
+    let secret = secret.filter(|v| !v.trim().is_empty());
+    match secret.as_deref() {
+        Some(value) => secrets::set(&key, value.trim())?,
+        None => secrets::delete(&key)?,
+    }
+    if let Ok(mut state) = state().lock() {
+        if let Some(entry) = state.get_mut(&profile_id) {
+            entry.session = None;
+        }
+    }
+    Ok(json!({ "profile_id": profile_id, "stored": secret.is_some() }))
+}
+
+/// Signs in to Vault through the OIDC provider in the system browser, then
+/// fetches the credential. The authorization URL is also emitted as
+/// `vault:oidc_url` in case the browser cannot be opened from here.
+#[tauri::command]
+pub async fn vault_oidc_login(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    profile: Option<String>,
+) -> Result<Value, String> {
+    let settings = store.snapshot();
+    let id = profile
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or(settings.active_profile.clone());
+    let vault = settings
+        .profile(&id)
+        .map(|p| p.vault.clone())
+        .filter(|v| v.enabled && v.auth == VaultAuth::Oidc)
+        .ok_or_else(|| {
+            ErrorCode::InvalidInput.with(format!("Profile {} does not use Vault OIDC", id))
+        })?;
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, OIDC_PORT))
+        .await
+        .map_err(|e| {
+            ErrorCode::LocalIo.with(format!("OIDC callback port {} busy: {}", OIDC_PORT, e))
+        })?;
+    let redirect_uri = format!("http://localhost:{}/oidc/callback", OIDC_PORT);
+    let nonce: String = crypto::random_bytes::<16>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let url = endpoint(&vault, &format!("auth/{}/oidc/auth_url", mount(&vault)))?;
+    let body = call(
+        &vault,
+        Method::POST,
+        url,
+        None,
+        Some(json!({
+            "role": vault.role.trim(),
+            "redirect_uri": redirect_uri,
+            "client_nonce": nonce,
+        })),
+    )
+    .await?;
+    let auth_url = body["data"]["auth_url"]
+        .as_str()
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| {
+            ErrorCode::CoreError
+                .with("Vault returned no OIDC auth URL; check the role's redirect URIs")
+        })?
+        .to_string();
+    let _ = environment::emit(
+        &app,
+        OIDC_URL_EVENT,
+        json!({ "profile_id": id, "url": auth_url }),
+    );
+    open_browser(&auth_url);
+
+    let (code, oidc_state) = tokio::time::timeout(OIDC_TIMEOUT, await_callback(&listener))
+        .await
+        .map_err(|_| ErrorCode::CoreTimeout.with("OIDC sign-in was not completed in time"))??;
+    let mut url = endpoint(&vault, &format!("auth/{}/oidc/callback", mount(&vault)))?;
+    url.query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("state", &oidc_state)
+        .append_pair("client_nonce", &nonce);
+    let body = call(&vault, Method::GET, url, None, None).await?;
+    let session = session_from(&body["auth"])
+        .ok_or_else(|| ErrorCode::CoreError.with("Vault OIDC callback returned no token"))?;
+
+    if let Ok(mut state) = state().lock() {
+        if let Some(entry) = state.get_mut(&id) {
+            entry.session = Some(session);
+            entry.last_error = None;
+        }
+    }
+    ensure(&id, true).await?;
+    Ok(status(&id))
+}
+
+/// Serves the browser's one redirect to `/oidc/callback` and returns its
+/// `code` and `state`.
+async fn await_callback(listener: &TcpListener) -> Result<(String, String), String> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| ErrorCode::LocalIo.with(format!("OIDC callback failed: {}", e)))?;
+        let mut buf = vec![0u8; 8192];
+        let read = stream.read(&mut buf).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buf[..read]);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or_default();
+        let Ok(url) = Url::parse(&format!("http://localhost{}", target)) else {
+            continue;
+        };
+        if url.path() != "/oidc/callback" {
+            let _ = stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await;
+            continue;
+        }
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let page = "<html><body>Signed in to Vault. You can close this tab.</body></html>";
+        let _ = stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    page.len(),
+                    page
+                )
+                .as_bytes(),
+            )
+            .await;
+        return match (params.get("code"), params.get("state")) {
+            (Some(code), Some(state)) => Ok((code.clone(), state.clone())),
+            _ => Err(ErrorCode::AuthExpired.with(format!(
+                "OIDC sign-in failed: {}",
+                params
+                    .get("error_description")
+                    .or_else(|| params.get("error"))
+                    .map(String::as_str)
+                    .unwrap_or("no code returned")
+            ))),
+        };
+    }
+}
+
+fn open_browser(url: &str) {
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg(url).spawn();
+    #[cfg(target_os = "windows")]
+    let result = std::process::Command::new("cmd")
+        .args(["/C", "start", "", url])
+        .spawn();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let result = std::process::Command::new("xdg-open").arg(url).spawn();
+    if let Err(err) = result {
+        eprintln!("could not open the browser for Vault sign-in: {}", err);
+    }
+}
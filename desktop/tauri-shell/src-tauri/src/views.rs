@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::errors::ErrorCode;
+use crate::settings::{Profile, SavedView, SettingsStore};
+use crate::{crypto, plan_window};
+
+const MAX_VIEWS: usize = 100;
+const MAX_NAME_LEN: usize = 80;
+
+pub fn validate(views: &[SavedView], profiles: &[Profile]) -> Result<(), String> {
+    if views.len() > MAX_VIEWS {
+        return Err(format!("At most {} saved views are allowed", MAX_VIEWS));
+    }
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    for view in views {
+        if view.id.trim().is_empty() {
+            return Err("Saved view id is required".to_string());
+        }
+        if !ids.insert(view.id.as_str()) {
+            return Err(format!("Duplicate saved view id: {}", view.id));
+        }
+        let name = view.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(format!(
+                "Saved view name must be 1 to {} bytes",
+                MAX_NAME_LEN
+            ));
+        }
+        if !names.insert(name.to_lowercase()) {
+            return Err(format!("A saved view is already named {:?}", name));
+        }
+        if let Some(profile) = &view.profile {
+            if !profiles.iter().any(|p| p.id == *profile) {
+                return Err(format!(
+                    "Saved view {:?}: unknown profile {}",
+                    name, profile
+                ));
+            }
+        }
+        plan_window::validate(&view.filter, &view.sort)
+            .map_err(|e| format!("Saved view {:?}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn new_id() -> String {
+    let bytes: [u8; 8] = crypto::random_bytes();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("view-{}", hex)
+}
+
+fn tidy(mut view: SavedView) -> SavedView {
+    view.name = view.name.trim().to_string();
+    view.sort = view.sort.trim().to_string();
+    view.profile = view
+        .profile
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    view
+}
+
+/// The saved views in their order; every window gets changes through
+/// `settings:changed`.
+#[tauri::command]
+pub fn list_views(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    Ok(json!({ "views": store.snapshot().saved_views }))
+}
+
+/// Saves `view` at the end of the list under a new id.
+#[tauri::command]
+pub fn create_view(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    view: SavedView,
+) -> Result<SavedView, String> {
+    let view = SavedView {
+        id: new_id(),
+        ..tidy(view)
+    };
+    store.update(&app, |settings| {
+        settings.saved_views.push(view.clone());
+        Ok(())
+    })?;
+    Ok(view)
+}
+
+/// Replaces the saved view with `view.id`, keeping its place in the list.
+#[tauri::command]
+pub fn update_view(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    view: SavedView,
+) -> Result<SavedView, String> {
+    let view = tidy(view);
+    store.update(&app, |settings| {
+        let existing = settings
+            .saved_views
+            .iter_mut()
+            .find(|v| v.id == view.id)
+            .ok_or_else(|| ErrorCode::NotFound.with(format!("Unknown saved view: {}", view.id)))?;
+        *existing = view.clone();
+        Ok(())
+    })?;
+    Ok(view)
+}
+
+/// Puts the saved views in the order of `ids`, which must name each of
+/// them once.
+#[tauri::command]
+pub fn reorder_views(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    ids: Vec<String>,
+) -> Result<Value, String> {
+    let settings = store.update(&app, |settings| {
+        let mut reordered = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let index = settings
+                .saved_views
+                .iter()
+                .position(|v| v.id == *id)
+                .ok_or_else(|| {
+                    ErrorCode::InvalidInput.with(format!("Unknown or repeated saved view: {}", id))
+                })?;
+            reordered.push(settings.saved_views.remove(index));
+        }
+        if !settings.saved_views.is_empty() {
+            return Err(ErrorCode::InvalidInput.with(format!(
+                "Reorder must list every saved view ({} left out)",
+                settings.saved_views.len()
+            )));
+        }
+        settings.saved_views = reordered;
+        Ok(())
+    })?;
+    Ok(json!({ "views": settings.saved_views }))
+}
+
+#[tauri::command]
+pub fn delete_view(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+) -> Result<Value, String> {
+    let settings = store.update(&app, |settings| {
+        let before = settings.saved_views.len();
+        settings.saved_views.retain(|v| v.id != id);
+        if settings.saved_views.len() == before {
+            return Err(ErrorCode::NotFound.with(format!("Unknown saved view: {}", id)));
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "deleted": id, "views": settings.saved_views }))
+}
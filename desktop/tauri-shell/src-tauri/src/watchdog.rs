@@ -0,0 +1,267 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use reqwest::{Method, Request, Response};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::settings::WatchdogSettings;
+use crate::{environment, net, tasks};
+
+const TASK: &str = "watchdog";
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const SLOW_EVENT: &str = "core:slow_request";
+const MAX_RETRIES: u32 = 3;
+/// Slow and abandoned requests kept for `list_slow_requests`.
+const MAX_RECENT: usize = 50;
+
+struct InFlight {
+    method: Method,
+    base_url: String,
+    endpoint: String,
+    started: Instant,
+    expected: Duration,
+    attempt: u32,
+    warned: bool,
+}
+
+impl InFlight {
+    fn report(&self, kind: &str) -> Value {
+        let elapsed = self.started.elapsed();
+        json!({
+            "kind": kind,
+            "method": self.method.as_str(),
+            "base_url": self.base_url,
+            "endpoint": self.endpoint,
+            "elapsed_ms": elapsed.as_millis() as u64,
+            "expected_ms": self.expected.as_millis() as u64,
+            "attempt": self.attempt,
+            "at": Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    settings: WatchdogSettings,
+    next_id: u64,
+    in_flight: HashMap<u64, InFlight>,
+    /// Reports raised in the request path, emitted by the watcher.
+    pending: Vec<Value>,
+    recent: VecDeque<Value>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<Registry> {
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+pub fn configure(settings: &WatchdogSettings) {
+    registry()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .settings = settings.clone();
+}
+
+pub fn validate(settings: &WatchdogSettings) -> Result<(), String> {
+    let hard = net::REQUEST_TIMEOUT.as_millis() as u64;
+    if settings.slow_after_ms == 0 {
+        return Err("Slow request threshold must be above zero".to_string());
+    }
+    if let Some(abort) = settings.abort_after_ms {
+        if abort <= settings.slow_after_ms || abort >= hard {
+            return Err(format!(
+                "Request abort threshold must be above the slow threshold and below the {}ms timeout",
+                hard
+            ));
+        }
+    }
+    if settings.max_retries > MAX_RETRIES {
+        return Err(format!("Watchdog retries must be at most {}", MAX_RETRIES));
+    }
+    for (prefix, ms) in &settings.expected_ms {
+        if !prefix.starts_with('/') || *ms == 0 {
+            return Err(format!(
+                "Expected duration for {} needs a path starting with / and a duration above zero",
+                prefix
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The expected duration of the longest `expected_ms` prefix matching
+/// `path`, if any.
+fn listed(settings: &WatchdogSettings, path: &str) -> Option<Duration> {
+    settings
+        .expected_ms
+        .iter()
+        .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, ms)| Duration::from_millis(*ms))
+}
+
+/// Removes a request from the registry when it finishes, however it ends.
+struct Watch(u64);
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = registry.in_flight.remove(&self.0) {
+            if entry.warned {
+                let report = entry.report("completed");
+                registry.pending.push(report);
+            }
+        }
+    }
+}
+
+/// Sends `request`, watching it against its expected duration. Safe
+/// methods still waiting for the core's answer past the abort threshold
+/// (twice the expected duration for endpoints listed in `expected_ms`) are
+/// abandoned and sent again, up to `max_retries` times; the last attempt
+/// runs to the hard timeout. Other methods are only reported: the core may
+/// already have applied them, so a replay could repeat or misreport the
+/// change.
+pub async fn execute(
+    client: &reqwest::Client,
+    request: Request,
+    base_url: &str,
+) -> reqwest::Result<Response> {
+    let method = request.method().clone();
+    let endpoint = request.url().path().to_string();
+    let (expected, abort_after, retries) = {
+        let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+        let settings = &registry.settings;
+        let listed = listed(settings, &endpoint);
+        let abort_after = settings.abort_after_ms.map(|ms| {
+            let abort = Duration::from_millis(ms);
+            listed.map_or(abort, |expected| abort.max(expected * 2))
+        });
+        (
+            listed.unwrap_or(Duration::from_millis(settings.slow_after_ms)),
+            abort_after,
+            settings.max_retries,
+        )
+    };
+    let abort_after = abort_after.filter(|_| method.is_safe());
+
+    let mut request = request;
+    let mut attempt = 1;
+    loop {
+        let watch = begin(&method, base_url, &endpoint, expected, attempt);
+        let spare = (attempt <= retries)
+            .then(|| abort_after.and_then(|_| request.try_clone()))
+            .flatten();
+        let Some(spare) = spare else {
+            let result = client.execute(request).await;
+            drop(watch);
+            return result;
+        };
+        let limit = abort_after.unwrap_or(net::REQUEST_TIMEOUT);
+        match tokio::time::timeout(limit, client.execute(request)).await {
+            Ok(result) => {
+                drop(watch);
+                return result;
+            }
+            Err(_) => {
+                abandon(watch);
+                request = spare;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn begin(
+    method: &Method,
+    base_url: &str,
+    endpoint: &str,
+    expected: Duration,
+    attempt: u32,
+) -> Watch {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    registry.next_id += 1;
+    let id = registry.next_id;
+    registry.in_flight.insert(
+        id,
+        InFlight {
+            method: method.clone(),
+            base_url: base_url.to_string(),
+            endpoint: endpoint.to_string(),
+            started: Instant::now(),
+            expected,
+            attempt,
+            warned: false,
+        },
+    );
+    Watch(id)
+}
+
+/// Reports an abandoned attempt in place of its completion.
+fn abandon(watch: Watch) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = registry.in_flight.remove(&watch.0) {
+        let report = entry.report("aborted");
+        registry.pending.push(report);
+    }
+    drop(registry);
+    drop(watch);
+}
+
+/// Emits `core:slow_request` once for each request past its expected
+/// duration, then again when it completes or is abandoned for a retry.
+pub fn spawn_watcher(app: AppHandle) {
+    tasks::spawn(&app, TASK, |app| async move {
+        loop {
+            tick(&app);
+            tasks::ran(TASK);
+            tokio::time::sleep(TICK_INTERVAL).await;
+        }
+    });
+}
+
+fn tick(app: &AppHandle) {
+    let reports = {
+        let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+        let mut reports = std::mem::take(&mut registry.pending);
+        for entry in registry.in_flight.values_mut() {
+            if !entry.warned && entry.started.elapsed() > entry.expected {
+                entry.warned = true;
+                reports.push(entry.report("slow"));
+            }
+        }
+        for report in &reports {
+            if registry.recent.len() == MAX_RECENT {
+                registry.recent.pop_front();
+            }
+            registry.recent.push_back(report.clone());
+        }
+        reports
+    };
+    for report in reports {
+        let _ = environment::emit(app, SLOW_EVENT, &report);
+    }
+}
+
+/// Requests running past their expected duration right now, and the most
+/// recent slow, completed-late and abandoned ones.
+#[tauri::command]
+pub fn list_slow_requests() -> Result<Value, String> {
+    let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let mut in_flight: Vec<Value> = registry
+        .in_flight
+        .values()
+        .filter(|entry| entry.started.elapsed() > entry.expected)
+        .map(|entry| entry.report("slow"))
+        .collect();
+    in_flight.sort_by_key(|r| std::cmp::Reverse(r["elapsed_ms"].as_u64()));
+    Ok(json!({
+        "in_flight": in_flight,
+        "recent": registry.recent.iter().rev().collect::<Vec<_>>(),
+        "settings": registry.settings,
+    }))
+}
@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use data_encoding::HEXLOWER_PERMISSIVE;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::Sha256;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use crate::db::{self, LocalDb};
+use crate::errors::ErrorCode;
+use crate::loopback::{self, Request};
+use crate::settings::{
+    AutomationAction, Profile, SettingsStore, WebhookAutomation, WebhookReceiverSettings,
+};
+use crate::{crypto, environment, notify, quick_actions, safe_mode, secrets, sync};
+
+const TASK: &str = "webhook_receiver";
+const RAN_EVENT: &str = "webhook_automation:ran";
+pub const SECRET_KEY: &str = "webhook_receiver:secret";
+const HOOK_PREFIX: &str = "/hooks/";
+const SIGNATURE_HEADER: &str = "x-novaadapt-signature";
+const TIMESTAMP_HEADER: &str = "x-novaadapt-timestamp";
+/// Callbacks signed further than this from now are refused, and replays
+/// within it are caught.
+const MAX_SKEW_SECS: i64 = 300;
+const MAX_BODY_BYTES: usize = 64 * 1024;
+const MAX_AUTOMATIONS: usize = 50;
+const MAX_NAME_LEN: usize = 80;
+const MAX_TYPE_LEN: usize = 64;
+const DEFAULT_SCRIPT_TIMEOUT_SECS: u64 = 60;
+const MAX_SCRIPT_TIMEOUT_SECS: u64 = 600;
+const MAX_OUTPUT_BYTES: usize = 4096;
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SystemRoot",
+    "USERPROFILE",
+];
+
+#[derive(Default)]
+struct Receiver {
+    received: u64,
+    rejected: u64,
+    /// Signatures seen within `MAX_SKEW_SECS`, to refuse replays.
+    seen: HashMap<String, Instant>,
+}
+
+static RECEIVER: OnceLock<Mutex<Receiver>> = OnceLock::new();
+
+fn receiver() -> &'static Mutex<Receiver> {
+    RECEIVER.get_or_init(|| Mutex::new(Receiver::default()))
+}
+
+fn valid_type(kind: &str) -> bool {
+    !kind.is_empty()
+        && kind.len() <= MAX_TYPE_LEN
+        && kind
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+pub fn validate(settings: &WebhookReceiverSettings, profiles: &[Profile]) -> Result<(), String> {
+    if settings.enabled && settings.port < 1024 {
+        return Err("Webhook receiver port must be 1024 or above".to_string());
+    }
+    if settings.automations.len() > MAX_AUTOMATIONS {
+        return Err(format!(
+            "At most {} webhook automations are allowed",
+            MAX_AUTOMATIONS
+        ));
+    }
+    let mut ids = HashSet::new();
+    for automation in &settings.automations {
+        if automation.id.trim().is_empty() || !ids.insert(automation.id.as_str()) {
+            return Err(format!(
+                "Webhook automation ids must be unique: {:?}",
+                automation.id
+            ));
+        }
+        let label = |e: &str| format!("Webhook automation {}: {}", automation.id, e);
+        if automation.name.len() > MAX_NAME_LEN {
+            return Err(label(&format!(
+                "name must be at most {} bytes",
+                MAX_NAME_LEN
+            )));
+        }
+        if !valid_type(&automation.callback_type) {
+            return Err(label("callback type must be letters, digits, ., - or _"));
+        }
+        if let Some(profile) = &automation.profile {
+            if !profiles.iter().any(|p| &p.id == profile) {
+                return Err(label(&format!("unknown profile {}", profile)));
+            }
+        }
+        match &automation.action {
+            AutomationAction::Notify { title, body } => {
+                if title.trim().is_empty() {
+                    return Err(label("a notification needs a title"));
+                }
+                for template in [title, body] {
+                    quick_actions::scan(template, &mut Default::default())
+                        .map_err(|e| label(&e))?;
+                }
+            }
+            AutomationAction::Prefetch { .. } => {}
+            AutomationAction::Script {
+                program,
+                timeout_secs,
+                ..
+            } => {
+                if !std::path::Path::new(program.trim()).is_absolute() {
+                    return Err(label("script program must be an absolute path"));
+                }
+                if timeout_secs.is_some_and(|t| t == 0 || t > MAX_SCRIPT_TIMEOUT_SECS) {
+                    return Err(label(&format!(
+                        "script timeout must be 1 to {} seconds",
+                        MAX_SCRIPT_TIMEOUT_SECS
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn kind_of(action: &AutomationAction) -> &'static str {
+    match action {
+        AutomationAction::Notify { .. } => "notify",
+        AutomationAction::Prefetch { .. } => "prefetch",
+        AutomationAction::Script { .. } => "script",
+    }
+}
+
+/// Takes callbacks while the receiver is enabled.
+pub fn spawn_receiver(app: AppHandle) {
+    loopback::spawn(
+        &app,
+        TASK,
+        |settings| {
+            let receiver = &settings.webhook_receiver;
+            receiver.enabled.then_some(receiver.port)
+        },
+        |app, _, stream| async move { serve(&app, stream).await },
+    );
+}
+
+/// Checks `X-NovaAdapt-Signature: sha256=<hex>`, an HMAC-SHA256 of
+/// `<timestamp>.<body>` with the receiver secret, where the timestamp is
+/// `X-NovaAdapt-Timestamp` in Unix seconds.
+fn authenticate(callback: &Request) -> Result<(), (u16, &'static str)> {
+    let secret = secrets::get(SECRET_KEY)
+        .ok()
+        .flatten()
+        .ok_or((503, "No receiver secret is set"))?;
+    let timestamp = callback
+        .headers
+        .get(TIMESTAMP_HEADER)
+        .and_then(|t| t.parse::<i64>().ok())
+        .ok_or((401, "Missing or invalid timestamp"))?;
+    if (chrono::Utc::now().timestamp() - timestamp).abs() > MAX_SKEW_SECS {
+        return Err((401, "Timestamp outside the allowed window"));
+    }
+    let signature = callback
+        .headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|s| s.strip_prefix("sha256="))
+        .and_then(|hex| HEXLOWER_PERMISSIVE.decode(hex.as_bytes()).ok())
+        .ok_or((401, "Missing or invalid signature"))?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(&callback.body);
+    mac.verify_slice(&signature)
+        .map_err(|_| (401, "Signature does not match"))?;
+    let mut receiver = receiver().lock().map_err(|_| (503, "Receiver poisoned"))?;
+    let window = Duration::from_secs(MAX_SKEW_SECS as u64 * 2);
+    receiver.seen.retain(|_, at| at.elapsed() < window);
+    if receiver
+        .seen
+        .insert(HEXLOWER_PERMISSIVE.encode(&signature), Instant::now())
+        .is_some()
+    {
+        return Err((401, "Callback was already received"));
+    }
+    Ok(())
+}
+
+async fn serve(app: &AppHandle, mut stream: TcpStream) {
+    let read = loopback::read_request(&mut stream, MAX_BODY_BYTES).await;
+    let accepted = read.and_then(|callback| {
+        if callback.method != "POST" {
+            return Err((405, "Callbacks are POSTed"));
+        }
+        let callback_type = callback
+            .path
+            .strip_prefix(HOOK_PREFIX)
+            .filter(|t| valid_type(t))
+            .map(str::to_string)
+            .ok_or((404, "Callbacks go to /hooks/<type>"))?;
+        authenticate(&callback)?;
+        let payload: Value = if callback.body.is_empty() {
+            json!({})
+        } else {
+            serde_json::from_slice(&callback.body).map_err(|_| (400, "Body is not JSON"))?
+        };
+        let automations: Vec<WebhookAutomation> = app
+            .state::<SettingsStore>()
+            .snapshot()
+            .webhook_receiver
+            .automations
+            .into_iter()
+            .filter(|a| a.enabled && a.callback_type == callback_type)
+            .collect();
+        if automations.is_empty() {
+            return Err((404, "No automation for this callback type"));
+        }
+        Ok((callback_type, payload, callback.body, automations))
+    });
+    let (callback_type, payload, body, automations) = match accepted {
+        Ok(accepted) => accepted,
+        Err((status, reason)) => {
+            if let Ok(mut receiver) = receiver().lock() {
+                receiver.rejected += 1;
+            }
+            loopback::respond_json(&mut stream, status, &json!({ "error": reason })).await;
+            return;
+        }
+    };
+    if let Ok(mut receiver) = receiver().lock() {
+        receiver.received += 1;
+    }
+    loopback::respond_json(
+        &mut stream,
+        202,
+        &json!({ "callback_type": callback_type, "automations": automations.len() }),
+    )
+    .await;
+    drop(stream);
+    for automation in &automations {
+        let outcome = run(app, automation, &callback_type, &payload, &body).await;
+        let profile_id = app
+            .state::<SettingsStore>()
+            .connection(automation.profile.as_deref())
+            .map(|c| c.profile_id)
+            .unwrap_or_default();
+        let summary = json!({
+            "automation_id": automation.id,
+            "name": automation.name,
+            "callback_type": callback_type,
+            "kind": kind_of(&automation.action),
+            "profile_id": profile_id,
+            "ok": outcome.is_ok(),
+            "result": outcome.as_ref().ok(),
+            "error": outcome.as_ref().err(),
+        });
+        if let Ok(conn) = app.state::<LocalDb>().lock() {
+            let _ = db::record_audit(
+                &conn,
+                &profile_id,
+                "webhook_automation",
+                if outcome.is_ok() { "ran" } else { "failed" },
+                &automation.id,
+                &summary,
+            );
+        }
+        let _ = environment::emit(app, RAN_EVENT, summary);
+    }
+}
+
+async fn run(
+    app: &AppHandle,
+    automation: &WebhookAutomation,
+    callback_type: &str,
+    payload: &Value,
+    body: &[u8],
+) -> Result<Value, String> {
+    let store = app.state::<SettingsStore>();
+    match &automation.action {
+        AutomationAction::Notify { title, body } => {
+            let conn = store.connection(automation.profile.as_deref())?;
+            let mut params = payload.as_object().cloned().unwrap_or_else(Map::new);
+            params.insert("callback_type".to_string(), json!(callback_type));
+            let mut data = params.clone();
+            data.insert("profile_id".to_string(), json!(conn.profile_id));
+            let (title, body) = (
+                quick_actions::fill(title, &params, false),
+                quick_actions::fill(body, &params, false),
+            );
+            notify::notify(app, "webhook", &title, &body, Value::Object(data));
+            Ok(json!({ "title": title }))
+        }
+        AutomationAction::Prefetch { full } => {
+            let conn = store.connection(automation.profile.as_deref())?;
+            sync::sync_profile(app, &conn, &app.state::<LocalDb>(), *full).await
+        }
+        AutomationAction::Script {
+            program,
+            args,
+            timeout_secs,
+        } => {
+            safe_mode::check("Webhook scripts")?;
+            let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_SCRIPT_TIMEOUT_SECS));
+            run_script(program.trim(), args, callback_type, body, timeout).await
+        }
+    }
+}
+
+async fn run_script(
+    program: &str,
+    args: &[String],
+    callback_type: &str,
+    body: &[u8],
+    timeout: Duration,
+) -> Result<Value, String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .env_clear()
+        .env("NOVAADAPT_CALLBACK_TYPE", callback_type)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    for key in PASSTHROUGH_ENV {
+        if let Some(value) = std::env::var_os(key) {
+            cmd.env(key, value);
+        }
+    }
+    let started = Instant::now();
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Script failed to start: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(body).await;
+    }
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            ErrorCode::LocalIo.with(format!(
+                "Script timed out after {} seconds",
+                timeout.as_secs()
+            ))
+        })?
+        .map_err(|e| ErrorCode::LocalIo.with(format!("Script failed: {}", e)))?;
+    let text = |bytes: &[u8]| {
+        String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_OUTPUT_BYTES)]).into_owned()
+    };
+    let result = json!({
+        "exit_code": output.status.code(),
+        "duration_ms": started.elapsed().as_millis() as u64,
+        "stdout": text(&output.stdout),
+        "stderr": text(&output.stderr),
+    });
+    if !output.status.success() {
+        return Err(ErrorCode::LocalIo.with(result));
+    }
+    Ok(result)
+}
+
+/// Whether the receiver is listening, on which URL, whether a secret is
+/// set, what it has taken and refused this session, and the automations.
+#[tauri::command]
+pub fn get_webhook_receiver(store: State<'_, SettingsStore>) -> Result<Value, String> {
+    let settings = store.snapshot().webhook_receiver;
+    let listening = loopback::listening(TASK);
+    let receiver = receiver()
+        .lock()
+        .map_err(|_| ErrorCode::Internal.with("Webhook receiver poisoned"))?;
+    Ok(json!({
+        "enabled": settings.enabled,
+        "port": settings.port,
+        "listening": listening.port.is_some(),
+        "url": listening
+            .port
+            .map(|port| format!("http://127.0.0.1:{}{}<type>", port, HOOK_PREFIX)),
+        "secret_set": secrets::get(SECRET_KEY).ok().flatten().is_some(),
+        "received": receiver.received,
+        "rejected": receiver.rejected,
+        "last_error": listening.last_error,
+        "automations": settings.automations,
+    }))
+}
+
+/// Adds `automation` to the mapping table, or replaces the one with its
+/// id; a new one without an id gets one.
+#[tauri::command]
+pub fn upsert_webhook_automation(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    mut automation: WebhookAutomation,
+) -> Result<Value, String> {
+    automation.name = automation.name.trim().to_string();
+    automation.callback_type = automation.callback_type.trim().to_string();
+    if automation.id.trim().is_empty() {
+        let hex: String = crypto::random_bytes::<8>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        automation.id = format!("automation-{}", hex);
+    }
+    store.update(&app, |settings| {
+        let automations = &mut settings.webhook_receiver.automations;
+        match automations.iter_mut().find(|a| a.id == automation.id) {
+            Some(existing) => *existing = automation.clone(),
+            None => automations.push(automation.clone()),
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "automation": automation }))
+}
+
+#[tauri::command]
+pub fn delete_webhook_automation(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    id: String,
+) -> Result<Value, String> {
+    store.update(&app, |settings| {
+        let automations = &mut settings.webhook_receiver.automations;
+        let before = automations.len();
+        automations.retain(|a| a.id != id);
+        if automations.len() == before {
+            return Err(ErrorCode::NotFound.with(format!("Unknown webhook automation: {}", id)));
+        }
+        Ok(())
+    })?;
+    Ok(json!({ "deleted": id }))
+}
+
+/// Generates a new receiver secret and returns it this once; callbacks
+/// signed with the old one are refused from now on.
+#[tauri::command]
+pub fn rotate_webhook_receiver_secret(db: State<'_, LocalDb>) -> Result<Value, String> {
+    let secret: String = crypto::random_bytes::<32>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    secrets::set(SECRET_KEY, &secret)?;
+    let _ = db::record_audit(
+        &*db.lock()?,
+        "",
+        "webhook_automation",
+        "secret_rotated",
+        "",
+        &json!({}),
+    );
+    Ok(json!({ "secret": secret }))
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, LocalDb};
+use crate::decisions::ScheduledDecisions;
+use crate::errors::ErrorCode;
+use crate::settings::{CoreAuth, SettingsStore};
+use crate::{
+    airgap, audit_export, calendar, chat_webhooks, cookies, core_signing, crypto, macro_pad,
+    permissions, secrets, spill, ticketing, translation, vault, webhook_receiver,
+};
+
+const CONFIRM_WINDOW: Duration = Duration::from_secs(60);
+/// Long enough for the command's answer to reach the webview before the
+/// process restarts.
+const RESTART_DELAY: Duration = Duration::from_millis(750);
+
+/// The confirmation code handed out by the first `wipe_local_data` call;
+/// single-use.
+#[derive(Default)]
+pub struct WipeConfirmation {
+    pending: Mutex<Option<(String, Instant)>>,
+}
+
+impl WipeConfirmation {
+    fn issue(&self) -> Result<String, String> {
+        let code: String = crypto::random_bytes::<3>()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        *self
+            .pending
+            .lock()
+            .map_err(|_| ErrorCode::Internal.with("Wipe state poisoned"))? =
+            Some((code.clone(), Instant::now()));
+        Ok(code)
+    }
+
+    fn redeem(&self, code: &str) -> bool {
+        let Ok(mut pending) = self.pending.lock() else {
+            return false;
+        };
+        pending.take().is_some_and(|(expected, at)| {
+            expected.eq_ignore_ascii_case(code.trim()) && at.elapsed() <= CONFIRM_WINDOW
+        })
+    }
+}
+
+/// Every keyring entry the shell may have written for `profile_id`.
+fn profile_secrets(profile_id: &str) -> Vec<String> {
+    let mut names: Vec<String> = [CoreAuth::Bearer, CoreAuth::ApiKey, CoreAuth::Basic]
+        .iter()
+        .filter_map(|mode| mode.secret_key(profile_id))
+        .collect();
+    names.push(secrets::profile_ssh_password_key(profile_id));
+    names.push(secrets::profile_proxy_password_key(profile_id));
+    names.push(vault::vault_secret_key(profile_id));
+    names.push(ticketing::ticketing_secret_key(profile_id));
+    names.push(core_signing::secret_key(profile_id));
+    names.push(permissions::unlock_key(profile_id));
+    names
+}
+
+/// Removes everything in `dir` except the files named in `keep`, and
+/// returns how many entries went.
+fn clear_dir(dir: &Path, keep: &[String]) -> Result<usize, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            return Err(ErrorCode::LocalIo.with(format!("Read {} failed: {}", dir.display(), e)))
+        }
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        if keep
+            .iter()
+            .any(|k| entry.file_name().to_string_lossy() == *k)
+        {
+            continue;
+        }
+        let path = entry.path();
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        result.map_err(|e| {
+            ErrorCode::LocalIo.with(format!("Delete {} failed: {}", path.display(), e))
+        })?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Offboards this workstation. Called without `confirmation` it only returns
+/// a short-lived code; called again with that code it drops decisions still
+/// in their undo window, deletes every credential the shell keeps in the
+/// keyring, empties the local database (audit trail included), deletes the
+/// settings store, the file caches and spilled bodies, clears the webview's
+/// storage, and restarts the shell in its first-run state.
+///
+/// Keyring entries are found through the configured profiles, so those of
+/// profiles removed earlier were already deleted with them.
+#[tauri::command]
+pub fn wipe_local_data(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    db: State<'_, LocalDb>,
+    confirmation_state: State<'_, WipeConfirmation>,
+    scheduled: State<'_, ScheduledDecisions>,
+    confirmation: Option<String>,
+) -> Result<Value, String> {
+    let Some(code) = confirmation else {
+        let code = confirmation_state.issue()?;
+        return Ok(json!({
+            "confirmation_required": true,
+            "confirmation": code,
+            "expires_in_seconds": CONFIRM_WINDOW.as_secs(),
+        }));
+    };
+    if !confirmation_state.redeem(&code) {
+        return Err(ErrorCode::InvalidInput.with("Wipe confirmation is invalid or has expired"));
+    }
+
+    let dropped_decisions = scheduled.cancel_all(&app);
+    let settings = store.snapshot();
+    let mut names: Vec<String> = settings
+        .profiles
+        .iter()
+        .flat_map(|p| profile_secrets(&p.id))
+        .collect();
+    names.push(audit_export::SIGNING_KEY_SECRET.to_string());
+    names.push(airgap::SIGNING_KEY_SECRET.to_string());
+    names.push(cookies::JAR_KEY_NAME.to_string());
+    names.push(webhook_receiver::SECRET_KEY.to_string());
+    names.push(macro_pad::TOKEN_KEY.to_string());
+    names.push(translation::KEY_NAME.to_string());
+    names.extend(settings.profiles.iter().flat_map(|p| {
+        p.calendars
+            .iter()
+            .map(|c| calendar::calendar_secret_key(&p.id, &c.id))
+    }));
+    names.extend(
+        settings
+            .chat_webhooks
+            .iter()
+            .map(|w| chat_webhooks::url_secret_key(&w.id)),
+    );
+    let mut keyring_errors = Vec::new();
+    for name in &names {
+        if let Err(err) = secrets::delete(name) {
+            keyring_errors.push(err);
+        }
+    }
+    vault::configure(&[]);
+    calendar::configure(&[]);
+    ticketing::configure(&[]);
+
+    let rows = {
+        let conn = db.lock()?;
+        db::wipe(&conn)
+            .map_err(|e| ErrorCode::LocalStorage.with(format!("Wipe database failed: {}", e)))?
+    };
+    store.wipe()?;
+
+    // The database stays open until the restart, so its files are emptied
+    // above rather than deleted here.
+    let keep: Vec<String> = ["", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| format!("{}{}", db::DB_FILE, suffix))
+        .collect();
+    let mut files = 0;
+    if let Ok(dir) = app.path().app_data_dir() {
+        files += clear_dir(&dir, &keep)?;
+    }
+    if let Ok(dir) = app.path().app_cache_dir() {
+        files += clear_dir(&dir, &[])?;
+    }
+    let spilled = spill::sweep_all();
+    let browsing_data_cleared = app
+        .webview_windows()
+        .values()
+        .all(|window| window.clear_all_browsing_data().is_ok());
+
+    let restart = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(RESTART_DELAY).await;
+        restart.restart();
+    });
+    Ok(json!({
+        "wiped": true,
+        "keyring_entries": names.len() - keyring_errors.len(),
+        "keyring_errors": keyring_errors,
+        "database_rows": rows,
+        "files": files,
+        "spilled_bodies": spilled,
+        "dropped_decisions": dropped_decisions,
+        "browsing_data_cleared": browsing_data_cleared,
+        "restarting": true,
+    }))
+}
@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 const DEFAULT_BASE_URL = "http://127.0.0.1:8787";
 const DEFAULT_REFRESH_INTERVAL_MS = 5000;
@@ -8,6 +9,7 @@ const hasTauri = typeof window !== "undefined" && !!window.__TAURI_INTERNALS__;
 const baseUrlInput = document.querySelector("#baseUrl");
 const tokenInput = document.querySelector("#token");
 const rememberTokenInput = document.querySelector("#rememberToken");
+const undoWindowInput = document.querySelector("#undoWindowSeconds");
 const autoRefreshInput = document.querySelector("#autoRefresh");
 const testConnectionBtn = document.querySelector("#testConnectionBtn");
 const refreshBtn = document.querySelector("#refreshBtn");
@@ -40,6 +42,74 @@ const terminalOutputEl = document.querySelector("#terminalOutput");
 const eventsEl = document.querySelector("#events");
 const controlArtifactsEl = document.querySelector("#controlArtifacts");
 const summaryEl = document.querySelector("#summary");
+const planOutputEl = document.querySelector("#planOutput");
+const planHistoryEl = document.querySelector("#planHistory");
+const planHistoryCountEl = document.querySelector("#planHistoryCount");
+const planHistoryTextEl = document.querySelector("#planHistoryText");
+const planHistoryStatusEl = document.querySelector("#planHistoryStatus");
+const planHistorySortEl = document.querySelector("#planHistorySort");
+const planHistoryViewEl = document.querySelector("#planHistoryView");
+const quickActionListEl = document.querySelector("#quickActionList");
+const quickActionNameEl = document.querySelector("#quickActionName");
+const quickActionMethodEl = document.querySelector("#quickActionMethod");
+const quickActionPathEl = document.querySelector("#quickActionPath");
+const quickActionPayloadEl = document.querySelector("#quickActionPayload");
+const quickActionConfirmEl = document.querySelector("#quickActionConfirm");
+const quickActionTrayEl = document.querySelector("#quickActionTray");
+const saveQuickActionBtn = document.querySelector("#saveQuickActionBtn");
+const quickActionOutputEl = document.querySelector("#quickActionOutput");
+const safeModeStatusEl = document.querySelector("#safeModeStatus");
+const safeModeBtn = document.querySelector("#safeModeBtn");
+const managedStatusEl = document.querySelector("#managedStatus");
+const environmentBannerEl = document.querySelector("#environmentBanner");
+const allowedBaseUrlsEl = document.querySelector("#allowedBaseUrls");
+const saveAllowlistBtn = document.querySelector("#saveAllowlistBtn");
+const clipboardGuardEl = document.querySelector("#clipboardGuard");
+const clipboardClearSecondsEl = document.querySelector("#clipboardClearSeconds");
+const saveClipboardBtn = document.querySelector("#saveClipboardBtn");
+const secondApproversEl = document.querySelector("#secondApprovers");
+const secondApproverNameEl = document.querySelector("#secondApproverName");
+const enrollApproverBtn = document.querySelector("#enrollApproverBtn");
+const enrollApproverOutEl = document.querySelector("#enrollApproverOut");
+const airgapStatusEl = document.querySelector("#airgapStatus");
+const airgapPathEl = document.querySelector("#airgapPath");
+const offlinePlansEl = document.querySelector("#offlinePlans");
+const airgapOutEl = document.querySelector("#airgapOut");
+const cookieJarStatusEl = document.querySelector("#cookieJarStatus");
+const cookieJarToggleEl = document.querySelector("#cookieJarToggle");
+const clearCookiesBtn = document.querySelector("#clearCookiesBtn");
+const cookieListEl = document.querySelector("#cookieList");
+const redirectModeEl = document.querySelector("#redirectMode");
+const redirectMaxEl = document.querySelector("#redirectMax");
+const saveRedirectsBtn = document.querySelector("#saveRedirectsBtn");
+const comparePlanAEl = document.querySelector("#comparePlanA");
+const comparePlanBEl = document.querySelector("#comparePlanB");
+const comparePlansBtn = document.querySelector("#comparePlansBtn");
+const planComparisonSummaryEl = document.querySelector("#planComparisonSummary");
+const planComparisonEl = document.querySelector("#planComparison");
+const refreshRequestHistoryBtn = document.querySelector("#refreshRequestHistoryBtn");
+const requestHistoryEl = document.querySelector("#requestHistory");
+const setupUrlEl = document.querySelector("#setupUrl");
+const setupTokenEl = document.querySelector("#setupToken");
+const setupNameEl = document.querySelector("#setupName");
+const runSetupBtn = document.querySelector("#runSetupBtn");
+const setupStepsEl = document.querySelector("#setupSteps");
+const configCheckStatusEl = document.querySelector("#configCheckStatus");
+const configProblemsEl = document.querySelector("#configProblems");
+const validateConfigBtn = document.querySelector("#validateConfigBtn");
+const pluginStatusEl = document.querySelector("#pluginStatus");
+const pluginListEl = document.querySelector("#pluginList");
+const pluginPaletteEl = document.querySelector("#pluginPalette");
+const reloadPluginsBtn = document.querySelector("#reloadPluginsBtn");
+const pluginOutputEl = document.querySelector("#pluginOutput");
+const pluginRegistryUrlEl = document.querySelector("#pluginRegistryUrl");
+const pluginRegistryKeysEl = document.querySelector("#pluginRegistryKeys");
+const pluginRegistryListEl = document.querySelector("#pluginRegistryList");
+const saveRegistryBtn = document.querySelector("#saveRegistryBtn");
+const browseRegistryBtn = document.querySelector("#browseRegistryBtn");
+const saveViewBtn = document.querySelector("#saveViewBtn");
+const moveViewUpBtn = document.querySelector("#moveViewUpBtn");
+const deleteViewBtn = document.querySelector("#deleteViewBtn");
 const actionStatusEl = document.querySelector("#actionStatus");
 const planCountEl = document.querySelector("#planCount");
 const jobCountEl = document.querySelector("#jobCount");
@@ -56,6 +126,8 @@ const pauseRuntimeBtn = document.querySelector("#pauseRuntimeBtn");
 const resumeRuntimeBtn = document.querySelector("#resumeRuntimeBtn");
 const resetUsageBtn = document.querySelector("#resetUsageBtn");
 const cancelAllJobsBtn = document.querySelector("#cancelAllJobsBtn");
+const emergencyHaltBtn = document.querySelector("#emergencyHaltBtn");
+const wipeLocalDataBtn = document.querySelector("#wipeLocalDataBtn");
 const governanceOutputEl = document.querySelector("#governanceOutput");
 const iotStatusEl = document.querySelector("#iotStatus");
 const entityDomainInput = document.querySelector("#entityDomainInput");
@@ -94,6 +166,7 @@ const templateGalleryEl = document.querySelector("#templateGallery");
 const templateOutputEl = document.querySelector("#templateOutput");
 
 let refreshTimer = null;
+let coreMaintenance = false;
 let liveRefreshTimer = null;
 let refreshInFlight = false;
 let queuedRefresh = false;
@@ -101,6 +174,32 @@ let scheduledRefreshTimer = null;
 let terminalPollTimer = null;
 let terminalActiveSessionId = "";
 let terminalNextSeq = 0;
+// Raw output of the attached session, re-parsed for ANSI styling on each poll
+// so colors set in one chunk carry into the next.
+const TERMINAL_MAX_CHARS = 200000;
+let terminalRaw = "";
+// plan id -> { ticketId, decision, remaining } for decisions in their undo window.
+const scheduledDecisions = new Map();
+// plan id -> latest `deadline:*` payload from the backend deadline watcher.
+const planDeadlines = new Map();
+// plan id -> `get_plan_timeline` result for plan cards with the timeline open.
+const planTimelines = new Map();
+const watchedPlans = new Set();
+const stalledPlans = new Map();
+const stepGates = new Map();
+// The plan whose execution output is shown; its text is rebuilt from the
+// backend transcript after a reload.
+const PLAN_OUTPUT_MAX_CHARS = 200000;
+const planOutputState = { planId: null, lastSeq: 0, spans: [] };
+// Cached plan history, rendered as a virtual list: only the pages around the
+// visible rows are fetched from `get_plans_window` and kept.
+const PLAN_HISTORY_ROW_HEIGHT = 34;
+const PLAN_HISTORY_PAGE = 200;
+const PLAN_HISTORY_KEEP_PAGES = 6;
+const planHistoryState = { total: 0, pages: new Map(), loading: new Set(), generation: 0 };
+// Saved views from the settings store; a selected view is queried as saved.
+let savedViews = [];
+
 const liveState = {
   enabled: false,
   connected: false,
@@ -108,6 +207,156 @@ const liveState = {
   consecutiveErrors: 0,
 };
 
+// In the Tauri shell, preferences live in the backend's encrypted settings
+// store (token in the OS keyring); the browser build keeps using localStorage.
+const PREF_PREFIX = "novaadapt.desktop.";
+const TOKEN_PREF = `${PREF_PREFIX}token`;
+const BASE_URL_PREF = `${PREF_PREFIX}baseUrl`;
+const PLAN_OUTPUT_PREF = `${PREF_PREFIX}planOutput`;
+const prefsCache = new Map();
+const prefsRemoved = new Set();
+let prefsFlushTimer = null;
+let storedToken = "";
+let storedBaseUrl = "";
+
+function prefGet(key) {
+  if (!hasTauri) return localStorage.getItem(key);
+  return prefsCache.has(key) ? prefsCache.get(key) : null;
+}
+
+function prefSet(key, value) {
+  if (!hasTauri) {
+    localStorage.setItem(key, value);
+    return;
+  }
+  const next = String(value);
+  if (prefsCache.get(key) === next) return;
+  prefsCache.set(key, next);
+  prefsRemoved.delete(key);
+  schedulePrefsFlush();
+}
+
+function prefRemove(key) {
+  if (!hasTauri) {
+    localStorage.removeItem(key);
+    return;
+  }
+  if (!prefsCache.has(key)) return;
+  prefsCache.delete(key);
+  prefsRemoved.add(key);
+  schedulePrefsFlush();
+}
+
+function schedulePrefsFlush() {
+  if (prefsFlushTimer) return;
+  prefsFlushTimer = window.setTimeout(() => {
+    prefsFlushTimer = null;
+    flushPrefs().catch((err) => console.warn("Saving settings failed", err));
+  }, 250);
+}
+
+async function flushPrefs() {
+  const ui = {};
+  for (const [key, value] of prefsCache) {
+    if (key !== TOKEN_PREF) ui[key] = value;
+  }
+  for (const key of prefsRemoved) ui[key] = null;
+  prefsRemoved.clear();
+  await invoke("update_settings", { patch: { ui } });
+
+  const baseUrl = prefsCache.get(BASE_URL_PREF) || DEFAULT_BASE_URL;
+  if (baseUrl !== storedBaseUrl) {
+    await invoke("upsert_profile", { profile: { id: "default", name: "Default", base_url: baseUrl } });
+    storedBaseUrl = baseUrl;
+  }
+  const token = prefsCache.get(TOKEN_PREF) || "";
+  if (token !== storedToken) {
+    await invoke("set_profile_token", { profileId: "default", token: token || null });
+    storedToken = token;
+  }
+}
+
+// Backend command errors are `CODE: detail` (see `get_error_catalog`); known
+// codes get an operator-facing message, with the detail kept for context.
+const ERROR_MESSAGES = {
+  AUTH_EXPIRED: "The API token was rejected or has expired. Update it and retry.",
+  FORBIDDEN: "This action is not permitted for the current token or machine.",
+  FORBIDDEN_LOCAL: "Your role does not allow this action, so the shell did not send it.",
+  REQUEST_TARGET_BLOCKED: "The request target was refused (user info in the URL, a link-local or metadata address, or too many redirects). Check the Base URL.",
+  NOT_FOUND: "The item no longer exists. Refresh to see the current state.",
+  PLAN_CONFLICT: "The plan changed since it was loaded. Refresh and review it again.",
+  RATE_LIMITED: "The core is rate limiting requests. Wait a moment and retry.",
+  CORE_UNREACHABLE: "The core is unreachable. Check the Base URL and network.",
+  TLS_POLICY: "The core does not offer a TLS version or cipher suite this profile's TLS policy allows.",
+  CORE_TIMEOUT: "The core did not respond in time. Retry shortly.",
+  CORE_ERROR: "The core reported an error. Retry, or check the core logs.",
+  CORE_MAINTENANCE: "The core is under maintenance. Polling resumes automatically when it is back.",
+  TWO_PERSON_REQUIRED: "This plan is critical. A second operator must enter their confirmation code first.",
+  JUSTIFICATION_REQUIRED: "This plan needs a written justification. Add one, or pick a reason template.",
+  EXECUTION_WINDOW: "Execution is held during this window. Approve without executing, or confirm an override.",
+  CONFIRMATION_REQUIRED: "This is a production profile. Type the confirmation phrase to execute or roll back.",
+  SHUTTING_DOWN: "The app is shutting down and is no longer sending changes.",
+  INVALID_INPUT: "The request was invalid.",
+  LOCAL_STORAGE: "Local storage on this machine failed.",
+  KEYRING_UNAVAILABLE: "The OS keyring is unavailable.",
+  LOCAL_IO: "A local file could not be read or written.",
+  INTERNAL: "Unexpected shell error.",
+};
+
+const RETRYABLE_ERROR_CODES = new Set(["RATE_LIMITED", "CORE_UNREACHABLE", "CORE_TIMEOUT", "CORE_ERROR"]);
+
+function errorCode(err) {
+  const match = /^([A-Z_]+): /.exec(String(err?.message || err));
+  return match && ERROR_MESSAGES[match[1]] ? match[1] : "";
+}
+
+function describeError(err) {
+  const text = String(err?.message || err);
+  const code = errorCode(err);
+  if (!code) return text;
+  const detail = text.slice(code.length + 2);
+  if (code === "PLAN_CONFLICT") {
+    try {
+      const conflict = JSON.parse(detail);
+      return `${ERROR_MESSAGES[code]} (now ${conflict?.plan?.status || "unknown"})`;
+    } catch {
+      // Conflicts raised by the core itself carry plain text.
+    }
+  }
+  return `${ERROR_MESSAGES[code]} (${detail})`;
+}
+
+function planVersion(plan) {
+  const value = plan?.version ?? plan?.etag ?? plan?.updated_at;
+  return value === undefined || value === null || value === "" ? null : String(value);
+}
+
+async function hydratePrefs() {
+  if (!hasTauri) return;
+  const settings = await invoke("get_settings");
+  const ui = settings?.ui || {};
+  for (const [key, value] of Object.entries(ui)) {
+    if (value !== null && value !== undefined) prefsCache.set(key, String(value));
+  }
+  const profile = (settings?.profiles || []).find((item) => item.id === settings?.active_profile);
+  if (profile?.base_url) {
+    prefsCache.set(BASE_URL_PREF, profile.base_url);
+    storedBaseUrl = profile.base_url;
+  }
+  // Refused while the active profile is read-only; its token stays in the backend.
+  storedToken =
+    (await invoke("get_profile_token", { profileId: profile?.id || "default" }).catch(() => "")) || "";
+  if (storedToken) prefsCache.set(TOKEN_PREF, storedToken);
+
+  // One-time import of preferences saved by earlier builds.
+  const legacyKeys = Object.keys(localStorage).filter((key) => key.startsWith(PREF_PREFIX));
+  if (legacyKeys.length && !Object.keys(ui).length) {
+    for (const key of legacyKeys) prefsCache.set(key, localStorage.getItem(key) || "");
+    await flushPrefs();
+    for (const key of legacyKeys) localStorage.removeItem(key);
+  }
+}
+
 function normalizeBaseUrl(value) {
   const raw = String(value || "").trim();
   if (!raw) throw new Error("Base URL is required");
@@ -140,90 +389,106 @@ function saveConfig() {
     baseUrl = DEFAULT_BASE_URL;
   }
   const token = (tokenInput?.value || "").trim();
-  localStorage.setItem("novaadapt.desktop.baseUrl", baseUrl);
-  localStorage.setItem("novaadapt.desktop.rememberToken", rememberTokenInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.baseUrl", baseUrl);
+  prefSet("novaadapt.desktop.rememberToken", rememberTokenInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.undoWindowSeconds", (undoWindowInput?.value || "").trim());
   if (rememberTokenInput?.checked) {
-    localStorage.setItem("novaadapt.desktop.token", token);
+    prefSet("novaadapt.desktop.token", token);
   } else {
-    localStorage.removeItem("novaadapt.desktop.token");
-  }
-  localStorage.setItem("novaadapt.desktop.objective", (objectiveInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.strategy", strategySelect?.value || "single");
-  localStorage.setItem("novaadapt.desktop.candidates", (candidatesInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.execute", executeToggle?.checked ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.autoRepairAttempts", (autoRepairAttemptsInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.repairStrategy", repairStrategySelect?.value || "decompose");
-  localStorage.setItem("novaadapt.desktop.repairModel", (repairModelInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.repairCandidates", (repairCandidatesInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.repairFallbacks", (repairFallbacksInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.terminalCommand", (terminalCommandInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.terminalCwd", (terminalCwdInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.terminalShell", (terminalShellInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.autoRefresh", autoRefreshInput?.checked ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.liveStream", liveState.enabled ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.budgetLimit", (budgetLimitInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.maxActiveRuns", (maxActiveRunsInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.iotDomain", (entityDomainInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.iotPrefix", (entityPrefixInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.mqttTopic", (mqttTopicInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.mqttPayload", mqttPayloadInput?.value || "");
-  localStorage.setItem("novaadapt.desktop.mqttRetain", mqttRetainInput?.checked ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.visionGoal", visionGoalInput?.value || "");
-  localStorage.setItem("novaadapt.desktop.visionAppName", (visionAppNameInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.controlAllowDangerous", controlAllowDangerousInput?.checked ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.mobileGoal", (mobileGoalInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.mobilePlatform", mobilePlatformSelect?.value || "android");
-  localStorage.setItem("novaadapt.desktop.mobilePreferAppium", mobilePreferAppiumInput?.checked ? "1" : "0");
-  localStorage.setItem("novaadapt.desktop.mobileAction", mobileActionInput?.value || "");
-  localStorage.setItem("novaadapt.desktop.templateTag", (templateTagInput?.value || "").trim());
-  localStorage.setItem("novaadapt.desktop.templateManifest", templateManifestInput?.value || "");
+    prefRemove("novaadapt.desktop.token");
+  }
+  prefSet("novaadapt.desktop.objective", (objectiveInput?.value || "").trim());
+  prefSet("novaadapt.desktop.strategy", strategySelect?.value || "single");
+  prefSet("novaadapt.desktop.candidates", (candidatesInput?.value || "").trim());
+  prefSet("novaadapt.desktop.execute", executeToggle?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.autoRepairAttempts", (autoRepairAttemptsInput?.value || "").trim());
+  prefSet("novaadapt.desktop.repairStrategy", repairStrategySelect?.value || "decompose");
+  prefSet("novaadapt.desktop.repairModel", (repairModelInput?.value || "").trim());
+  prefSet("novaadapt.desktop.repairCandidates", (repairCandidatesInput?.value || "").trim());
+  prefSet("novaadapt.desktop.repairFallbacks", (repairFallbacksInput?.value || "").trim());
+  prefSet("novaadapt.desktop.terminalCommand", (terminalCommandInput?.value || "").trim());
+  prefSet("novaadapt.desktop.terminalCwd", (terminalCwdInput?.value || "").trim());
+  prefSet("novaadapt.desktop.terminalShell", (terminalShellInput?.value || "").trim());
+  prefSet("novaadapt.desktop.autoRefresh", autoRefreshInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.liveStream", liveState.enabled ? "1" : "0");
+  prefSet("novaadapt.desktop.budgetLimit", (budgetLimitInput?.value || "").trim());
+  prefSet("novaadapt.desktop.maxActiveRuns", (maxActiveRunsInput?.value || "").trim());
+  prefSet("novaadapt.desktop.iotDomain", (entityDomainInput?.value || "").trim());
+  prefSet("novaadapt.desktop.iotPrefix", (entityPrefixInput?.value || "").trim());
+  prefSet("novaadapt.desktop.mqttTopic", (mqttTopicInput?.value || "").trim());
+  prefSet("novaadapt.desktop.mqttPayload", mqttPayloadInput?.value || "");
+  prefSet("novaadapt.desktop.mqttRetain", mqttRetainInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.visionGoal", visionGoalInput?.value || "");
+  prefSet("novaadapt.desktop.visionAppName", (visionAppNameInput?.value || "").trim());
+  prefSet("novaadapt.desktop.controlAllowDangerous", controlAllowDangerousInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.mobileGoal", (mobileGoalInput?.value || "").trim());
+  prefSet("novaadapt.desktop.mobilePlatform", mobilePlatformSelect?.value || "android");
+  prefSet("novaadapt.desktop.mobilePreferAppium", mobilePreferAppiumInput?.checked ? "1" : "0");
+  prefSet("novaadapt.desktop.mobileAction", mobileActionInput?.value || "");
+  prefSet("novaadapt.desktop.templateTag", (templateTagInput?.value || "").trim());
+  prefSet("novaadapt.desktop.templateManifest", templateManifestInput?.value || "");
 }
 
 function loadConfig() {
-  const rememberedBase = localStorage.getItem("novaadapt.desktop.baseUrl") || DEFAULT_BASE_URL;
+  const rememberedBase = prefGet("novaadapt.desktop.baseUrl") || DEFAULT_BASE_URL;
   try {
     baseUrlInput.value = normalizeBaseUrl(rememberedBase);
   } catch {
     baseUrlInput.value = DEFAULT_BASE_URL;
   }
-  const rememberToken = (localStorage.getItem("novaadapt.desktop.rememberToken") || "0") === "1";
+  const rememberToken = (prefGet("novaadapt.desktop.rememberToken") || "0") === "1";
   rememberTokenInput.checked = rememberToken;
-  tokenInput.value = rememberToken ? (localStorage.getItem("novaadapt.desktop.token") || "") : "";
-  objectiveInput.value = localStorage.getItem("novaadapt.desktop.objective") || "";
-  strategySelect.value = localStorage.getItem("novaadapt.desktop.strategy") || "single";
-  candidatesInput.value = localStorage.getItem("novaadapt.desktop.candidates") || "";
-  executeToggle.checked = (localStorage.getItem("novaadapt.desktop.execute") || "0") === "1";
-  autoRepairAttemptsInput.value = localStorage.getItem("novaadapt.desktop.autoRepairAttempts") || "0";
-  repairStrategySelect.value = localStorage.getItem("novaadapt.desktop.repairStrategy") || "decompose";
-  repairModelInput.value = localStorage.getItem("novaadapt.desktop.repairModel") || "";
-  repairCandidatesInput.value = localStorage.getItem("novaadapt.desktop.repairCandidates") || "";
-  repairFallbacksInput.value = localStorage.getItem("novaadapt.desktop.repairFallbacks") || "";
-  terminalCommandInput.value = localStorage.getItem("novaadapt.desktop.terminalCommand") || "";
-  terminalCwdInput.value = localStorage.getItem("novaadapt.desktop.terminalCwd") || "";
-  terminalShellInput.value = localStorage.getItem("novaadapt.desktop.terminalShell") || "";
-  autoRefreshInput.checked = (localStorage.getItem("novaadapt.desktop.autoRefresh") || "1") !== "0";
-  liveState.enabled = (localStorage.getItem("novaadapt.desktop.liveStream") || "0") === "1";
-  budgetLimitInput.value = localStorage.getItem("novaadapt.desktop.budgetLimit") || "";
-  maxActiveRunsInput.value = localStorage.getItem("novaadapt.desktop.maxActiveRuns") || "";
-  entityDomainInput.value = localStorage.getItem("novaadapt.desktop.iotDomain") || "";
-  entityPrefixInput.value = localStorage.getItem("novaadapt.desktop.iotPrefix") || "";
-  mqttTopicInput.value = localStorage.getItem("novaadapt.desktop.mqttTopic") || "";
-  mqttPayloadInput.value = localStorage.getItem("novaadapt.desktop.mqttPayload") || "";
-  mqttRetainInput.checked = (localStorage.getItem("novaadapt.desktop.mqttRetain") || "0") === "1";
-  visionGoalInput.value = localStorage.getItem("novaadapt.desktop.visionGoal") || "";
-  visionAppNameInput.value = localStorage.getItem("novaadapt.desktop.visionAppName") || "";
-  controlAllowDangerousInput.checked = (localStorage.getItem("novaadapt.desktop.controlAllowDangerous") || "0") === "1";
-  mobileGoalInput.value = localStorage.getItem("novaadapt.desktop.mobileGoal") || "";
-  mobilePlatformSelect.value = localStorage.getItem("novaadapt.desktop.mobilePlatform") || "android";
-  mobilePreferAppiumInput.checked = (localStorage.getItem("novaadapt.desktop.mobilePreferAppium") || "0") === "1";
-  mobileActionInput.value = localStorage.getItem("novaadapt.desktop.mobileAction") || "{\"type\":\"open_app\",\"package\":\"com.android.settings\"}";
-  templateTagInput.value = localStorage.getItem("novaadapt.desktop.templateTag") || "";
-  templateManifestInput.value = localStorage.getItem("novaadapt.desktop.templateManifest") || "";
+  if (undoWindowInput) undoWindowInput.value = prefGet("novaadapt.desktop.undoWindowSeconds") || "";
+  tokenInput.value = rememberToken ? (prefGet("novaadapt.desktop.token") || "") : "";
+  objectiveInput.value = prefGet("novaadapt.desktop.objective") || "";
+  strategySelect.value = prefGet("novaadapt.desktop.strategy") || "single";
+  candidatesInput.value = prefGet("novaadapt.desktop.candidates") || "";
+  executeToggle.checked = (prefGet("novaadapt.desktop.execute") || "0") === "1";
+  autoRepairAttemptsInput.value = prefGet("novaadapt.desktop.autoRepairAttempts") || "0";
+  repairStrategySelect.value = prefGet("novaadapt.desktop.repairStrategy") || "decompose";
+  repairModelInput.value = prefGet("novaadapt.desktop.repairModel") || "";
+  repairCandidatesInput.value = prefGet("novaadapt.desktop.repairCandidates") || "";
+  repairFallbacksInput.value = prefGet("novaadapt.desktop.repairFallbacks") || "";
+  terminalCommandInput.value = prefGet("novaadapt.desktop.terminalCommand") || "";
+  terminalCwdInput.value = prefGet("novaadapt.desktop.terminalCwd") || "";
+  terminalShellInput.value = prefGet("novaadapt.desktop.terminalShell") || "";
+  autoRefreshInput.checked = (prefGet("novaadapt.desktop.autoRefresh") || "1") !== "0";
+  liveState.enabled = (prefGet("novaadapt.desktop.liveStream") || "0") === "1";
+  budgetLimitInput.value = prefGet("novaadapt.desktop.budgetLimit") || "";
+  maxActiveRunsInput.value = prefGet("novaadapt.desktop.maxActiveRuns") || "";
+  entityDomainInput.value = prefGet("novaadapt.desktop.iotDomain") || "";
+  entityPrefixInput.value = prefGet("novaadapt.desktop.iotPrefix") || "";
+  mqttTopicInput.value = prefGet("novaadapt.desktop.mqttTopic") || "";
+  mqttPayloadInput.value = prefGet("novaadapt.desktop.mqttPayload") || "";
+  mqttRetainInput.checked = (prefGet("novaadapt.desktop.mqttRetain") || "0") === "1";
+  visionGoalInput.value = prefGet("novaadapt.desktop.visionGoal") || "";
+  visionAppNameInput.value = prefGet("novaadapt.desktop.visionAppName") || "";
+  controlAllowDangerousInput.checked = (prefGet("novaadapt.desktop.controlAllowDangerous") || "0") === "1";
+  mobileGoalInput.value = prefGet("novaadapt.desktop.mobileGoal") || "";
+  mobilePlatformSelect.value = prefGet("novaadapt.desktop.mobilePlatform") || "android";
+  mobilePreferAppiumInput.checked = (prefGet("novaadapt.desktop.mobilePreferAppium") || "0") === "1";
+  mobileActionInput.value = prefGet("novaadapt.desktop.mobileAction") || "{\"type\":\"open_app\",\"package\":\"com.android.settings\"}";
+  templateTagInput.value = prefGet("novaadapt.desktop.templateTag") || "";
+  templateManifestInput.value = prefGet("novaadapt.desktop.templateManifest") || "";
+}
+
+// W3C trace context: one trace per operator action and a span per attempt,
+// so retries appear as child spans of the same trace.
+function randomHex(bytes) {
+  const buf = new Uint8Array(bytes);
+  crypto.getRandomValues(buf);
+  if (buf.every((b) => b === 0)) buf[0] = 1;
+  return Array.from(buf, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+function newTraceId() {
+  return randomHex(16);
 }
 
 async function coreRequest(method, path, payload = null) {
   const normalizedPath = path.startsWith("/") ? path : `/${path}`;
   const { baseUrl, token } = currentConfig();
+  const traceId = newTraceId();
   if (hasTauri) {
     return requestWithRetries(async () =>
       invoke("core_request", {
@@ -232,6 +497,7 @@ async function coreRequest(method, path, payload = null) {
         token: token || null,
         path: normalizedPath,
         payload,
+        traceId,
       }),
     );
   }
@@ -243,7 +509,7 @@ async function coreRequest(method, path, payload = null) {
   const response = await requestWithRetries(() =>
     fetchWithTimeout(`${baseUrl.replace(/\/$/, "")}${normalizedPath}`, {
       method: String(method || "GET").toUpperCase(),
-      headers,
+      headers: { ...headers, traceparent: `00-${traceId}-${randomHex(8)}-01` },
       body: payload === null ? undefined : JSON.stringify(payload),
     }),
   );
@@ -261,6 +527,31 @@ async function coreRequest(method, path, payload = null) {
   return parsed;
 }
 
+// Stale-while-revalidate GETs: the backend answers from its response cache
+// at once and emits `cache:updated` when a background refresh finds a
+// different copy, which is handed to the `onUpdate` registered for the path.
+const cacheUpdateHandlers = new Map();
+
+async function cachedCoreGet(path, onUpdate) {
+  const normalizedPath = path.startsWith("/") ? path : `/${path}`;
+  if (!hasTauri) return coreRequest("GET", normalizedPath);
+  const { baseUrl, token } = currentConfig();
+  if (onUpdate) cacheUpdateHandlers.set(normalizedPath, onUpdate);
+  const result = await requestWithRetries(async () =>
+    invoke("cached_core_request", { baseUrl, token: token || null, path: normalizedPath }),
+  );
+  return result.data;
+}
+
+function watchResponseCache() {
+  if (!hasTauri) return;
+  listen("cache:updated", ({ payload }) => {
+    const { baseUrl } = currentConfig();
+    if (!String(payload.url || "").startsWith(baseUrl.replace(/\/$/, ""))) return;
+    cacheUpdateHandlers.get(payload.path)?.(payload.data);
+  });
+}
+
 async function fetchWithTimeout(url, options) {
   const controller = new AbortController();
   const timeout = window.setTimeout(() => controller.abort(), DEFAULT_REQUEST_TIMEOUT_MS);
@@ -286,7 +577,8 @@ async function requestWithRetries(fn, maxAttempts = 3) {
       return out;
     } catch (error) {
       lastError = error;
-      if (attempt >= maxAttempts) break;
+      const code = errorCode(error);
+      if (attempt >= maxAttempts || (code && !RETRYABLE_ERROR_CODES.has(code))) break;
       await sleep(150 * attempt);
     }
   }
@@ -378,13 +670,1436 @@ async function rejectPlan(planId, reason) {
   });
 }
 
-async function undoPlan(planId, execute = false, markOnly = true) {
-  return coreRequest("POST", `/plans/${encodeURIComponent(planId)}/undo`, {
-    execute,
-    mark_only: markOnly,
+function undoWindowSeconds() {
+  if (!hasTauri) return 0;
+  const value = Number.parseInt(undoWindowInput?.value || "0", 10);
+  return Number.isFinite(value) ? Math.max(0, Math.min(60, value)) : 0;
+}
+
+// With an undo window set, the backend holds the call and counts down over
+// `decision:*` events; otherwise the decision is sent straight away.
+// The backend re-checks `version` and sends If-Match, failing with
+// PLAN_CONFLICT (detail: JSON with the refreshed plan) if the plan moved on.
+async function decidePlan(planId, decision, payload, version = null) {
+  if (!hasTauri) {
+    return decision === "approve" ? approvePlan(planId, payload) : rejectPlan(planId, payload?.reason);
+  }
+  const { baseUrl, token } = currentConfig();
+  const delaySeconds = undoWindowSeconds();
+  if (!delaySeconds) {
+    const result = await sendPlanDecision({ baseUrl, token: token || null, planId, decision, payload, version });
+    if (decision === "approve" && payload?.execute) followPlanOutput(planId).catch(() => {});
+    return result;
+  }
+  const args = { baseUrl, token: token || null, planId, decision, payload, delaySeconds, version };
+  let ticket;
+  try {
+    ticket = await invoke("schedule_plan_decision", args);
+  } catch (err) {
+    if (errorCode(err) !== "CONFIRMATION_REQUIRED") throw err;
+    await confirmProductionAction(err);
+    ticket = await invoke("schedule_plan_decision", args);
+  }
+  scheduledDecisions.set(planId, { ticketId: ticket.ticket_id, decision, remaining: ticket.delay_seconds });
+  return ticket;
+}
+
+// An approval that would execute inside a blocking execution window fails
+// with EXECUTION_WINDOW; typing back the code from its detail overrides it once.
+async function sendPlanDecision(args) {
+  args = { traceId: newTraceId(), ...args };
+  try {
+    return await invoke("send_plan_decision", args);
+  } catch (err) {
+    if (errorCode(err) === "TWO_PERSON_REQUIRED") {
+      await confirmSecondApprover(err);
+      return sendPlanDecision(args);
+    }
+    if (errorCode(err) === "CONFIRMATION_REQUIRED") {
+      await confirmProductionAction(err);
+      return sendPlanDecision(args);
+    }
+    if (errorCode(err) !== "EXECUTION_WINDOW") throw err;
+    let held;
+    try {
+      held = JSON.parse(String(err?.message || err).slice("EXECUTION_WINDOW: ".length));
+    } catch {
+      throw err;
+    }
+    const typed = window.prompt(
+      `${held.message}\n\nType ${held.confirmation} within ${held.expires_in_seconds}s to execute anyway.`,
+    );
+    if (typed === null || !typed.trim()) throw err;
+    return invoke("send_plan_decision", { ...args, overrideConfirmation: typed.trim() });
+  }
+}
+
+// Critical plans under the two-person rule come back with TWO_PERSON_REQUIRED;
+// the second operator's code (from their authenticator, or one the core
+// sends them) is checked in the backend before the approval is retried.
+async function confirmSecondApprover(err) {
+  let held;
+  try {
+    held = JSON.parse(String(err?.message || err).slice("TWO_PERSON_REQUIRED: ".length));
+  } catch {
+    throw err;
+  }
+  const profile = held.profile_id;
+  let approverId = null;
+  let who;
+  if (held.method === "core") {
+    const issued = await invoke("request_confirmation_code", { profile, planId: held.plan_id });
+    who = `The core sent a code to ${issued.approver}; it expires in ${issued.expires_in_seconds}s.`;
+  } else {
+    let approver = held.approvers[0];
+    if (held.approvers.length > 1) {
+      const names = held.approvers.map((a, i) => `${i + 1}. ${a.name}`).join("\n");
+      const picked = window.prompt(`${held.message}\n\nWho is confirming?\n${names}`, "1");
+      if (picked === null) throw err;
+      approver = held.approvers[Number(picked) - 1];
+      if (!approver) throw err;
+    }
+    approverId = approver.id;
+    who = `${approver.name}: enter the code from your authenticator app.`;
+  }
+  const code = window.prompt(`${held.message}\n\n${who}`);
+  if (code === null || !code.trim()) throw err;
+  return invoke("confirm_second_approval", {
+    profile,
+    planId: held.plan_id,
+    code: code.trim(),
+    approverId,
+  });
+}
+
+// Execute-on-approve and rollback against a prod-class profile come back
+// with CONFIRMATION_REQUIRED; the phrase typed here arms that one action in
+// the backend for a short while before the call is repeated.
+async function confirmProductionAction(err) {
+  let held;
+  try {
+    held = JSON.parse(String(err?.message || err).slice("CONFIRMATION_REQUIRED: ".length));
+  } catch {
+    throw err;
+  }
+  const typed = window.prompt(`${held.message}\n\nType "${held.phrase}" to continue.`);
+  if (typed === null || !typed.trim()) throw err;
+  return invoke("confirm_production_action", {
+    profile: held.profile_id,
+    action: held.action,
+    planId: held.plan_id,
+    phrase: typed.trim(),
+  });
+}
+
+async function cancelPlanDecision(planId) {
+  const entry = scheduledDecisions.get(planId);
+  if (!entry) return null;
+  scheduledDecisions.delete(planId);
+  return invoke("cancel_plan_decision", { ticketId: entry.ticketId });
+}
+
+function watchScheduledDecisions() {
+  if (!hasTauri) return;
+  listen("decision:tick", ({ payload }) => {
+    const entry = scheduledDecisions.get(payload.plan_id);
+    if (entry) entry.remaining = payload.remaining_seconds;
+    setActionStatus(`Sending ${payload.decision} in ${payload.remaining_seconds}s`, "neutral");
+  });
+  listen("decision:sent", ({ payload }) => {
+    scheduledDecisions.delete(payload.plan_id);
+    setActionStatus(`${payload.decision} ${payload.ok ? "sent" : "failed"}`, payload.ok ? "ok" : "error");
+    if (!payload.ok) summaryEl.textContent = describeError(payload.error);
+    scheduleRefresh();
+  });
+  listen("deadline:tick", ({ payload }) => {
+    planDeadlines.set(payload.plan_id, payload);
+  });
+  listen("deadline:expired", ({ payload }) => {
+    planDeadlines.set(payload.plan_id, { ...payload, expired: true });
+    scheduleRefresh();
+  });
+  listen("decision:cancelled", ({ payload }) => {
+    scheduledDecisions.delete(payload.plan_id);
+    setActionStatus(`${payload.decision} cancelled`, "neutral");
+    scheduleRefresh();
+  });
+}
+
+function watchTokenExpiry() {
+  if (!hasTauri) return;
+  listen("token:expiring", ({ payload }) => {
+    const minutes = Math.max(1, Math.ceil(payload.expires_in_seconds / 60));
+    setActionStatus(`API token expires in ${minutes} min`, "error");
+  });
+  listen("token:expired", () => {
+    setActionStatus("API token expired", "error");
+    summaryEl.textContent = ERROR_MESSAGES.AUTH_EXPIRED;
   });
 }
 
+// In safe mode the badge lists what is off; the button restarts into or
+// out of it after the usual quit checks.
+function watchSafeMode() {
+  if (!hasTauri) return;
+  invoke("get_safe_mode")
+    .then((state) => {
+      if (safeModeStatusEl) {
+        safeModeStatusEl.hidden = !state.active;
+        safeModeStatusEl.title = (state.disabled || []).map((d) => d.label).join("\n");
+      }
+      if (safeModeBtn) safeModeBtn.textContent = state.active ? "Leave Safe Mode" : "Safe Mode";
+      safeModeBtn?.addEventListener("click", () => {
+        const enabled = !state.active;
+        const prompt = enabled
+          ? "Restart in safe mode? Plugins, scheduled approvals, local execution and the local cache stay off until you restart normally."
+          : "Restart normally?";
+        if (!window.confirm(prompt)) return;
+        runAction("Restarting", () => invoke("restart_safe_mode", { enabled }), false).catch(() => {});
+      });
+    })
+    .catch(() => {});
+}
+
+const SETUP_BADGES = { ok: "ok", failed: "error", warning: "neutral", skipped: "neutral", running: "neutral" };
+
+// One row per step, updated in place as `setup:progress` reports it.
+function renderSetupStep(step) {
+  if (!setupStepsEl) return;
+  let row = setupStepsEl.querySelector(`[data-step="${step.step}"]`);
+  if (!row) {
+    row = document.createElement("div");
+    row.className = "plan";
+    row.dataset.step = step.step;
+    setupStepsEl.appendChild(row);
+  }
+  row.innerHTML = `
+    <div class="plan-head">
+      <strong>${escapeHTML(step.detail || step.step)}</strong>
+      <span class="badge ${SETUP_BADGES[step.status] || "neutral"}">${escapeHTML(step.status)}</span>
+    </div>`;
+}
+
+function watchSetup() {
+  if (!hasTauri) return;
+  listen("setup:progress", ({ payload }) => renderSetupStep(payload));
+  runSetupBtn?.addEventListener("click", () => {
+    const url = setupUrlEl?.value.trim();
+    if (!url) return;
+    if (setupStepsEl) setupStepsEl.innerHTML = "";
+    const args = {
+      url,
+      token: setupTokenEl?.value.trim() || null,
+      name: setupNameEl?.value.trim() || null,
+    };
+    runAction("Setting up", () => invoke("run_first_run_setup", args), false)
+      .then(async (result) => {
+        if (!result.profile_id) return;
+        if (setupTokenEl) setupTokenEl.value = "";
+        baseUrlInput.value = result.base_url;
+        await refresh();
+      })
+      .catch(() => {});
+  });
+}
+
+function renderConfigProblems(report) {
+  if (!configProblemsEl) return;
+  const problems = report.problems || [];
+  if (configCheckStatusEl) {
+    configCheckStatusEl.textContent = problems.length
+      ? `${report.errors} error(s), ${report.warnings} warning(s).`
+      : "No problems found.";
+  }
+  configProblemsEl.innerHTML = "";
+  for (const problem of problems) {
+    const card = document.createElement("div");
+    card.className = "plan";
+    const kind = problem.severity === "error" ? "error" : "neutral";
+    card.innerHTML = `
+      <div class="plan-head">
+        <strong>${escapeHTML(problem.message)}</strong>
+        <span class="badge ${kind}">${escapeHTML(problem.severity)}</span>
+      </div>
+      <p class="event-meta">${escapeHTML(`${problem.profile_id || "global"} · ${problem.field}`)}</p>
+      <p>${escapeHTML(problem.fix)}</p>`;
+    configProblemsEl.appendChild(card);
+  }
+}
+
+// The startup check only reports when something is wrong; the button runs
+// it again on demand.
+function watchConfigCheck() {
+  if (!hasTauri) return;
+  listen("config:problems", ({ payload }) => renderConfigProblems(payload));
+  validateConfigBtn?.addEventListener("click", () => {
+    runAction("Checking configuration", () => invoke("validate_config"), false)
+      .then(renderConfigProblems)
+      .catch(() => {});
+  });
+}
+
+// An administrator's policy is shown as a badge listing what it enforces;
+// the backend refuses changes to the settings it locks.
+function watchManagedPolicy() {
+  if (!hasTauri || !managedStatusEl) return;
+  invoke("get_managed_policy")
+    .then((policy) => {
+      if (!policy.managed && !policy.error) return;
+      managedStatusEl.hidden = false;
+      if (policy.error) {
+        managedStatusEl.className = "badge error";
+        managedStatusEl.title = `Policy ignored: ${policy.error}`;
+        return;
+      }
+      const lines = [`Managed by ${policy.source}`];
+      if (policy.read_only) lines.push("Read-only");
+      if (policy.allowed_base_urls.length) lines.push(`Allowed cores: ${policy.allowed_base_urls.join(", ")}`);
+      if (policy.locked.length) lines.push(`Locked: ${policy.locked.join(", ")}`);
+      managedStatusEl.title = lines.join("\n");
+    })
+    .catch(() => {});
+}
+
+// Staging and prod profiles carry a banner; on prod, executing and rolling
+// back also ask for a typed phrase (see `confirmProductionAction`).
+function watchEnvironment() {
+  if (!hasTauri || !environmentBannerEl) return;
+  const render = () =>
+    invoke("get_environment")
+      .then((env) => {
+        environmentBannerEl.hidden = env.environment === "dev";
+        environmentBannerEl.classList.toggle("prod", env.environment === "prod");
+        environmentBannerEl.textContent =
+          env.environment === "prod"
+            ? `Production · ${env.profile_id} · execute and rollback need a typed confirmation`
+            : `Staging · ${env.profile_id}`;
+      })
+      .catch(() => {});
+  render();
+  listen("settings:changed", render);
+}
+
+// Requests to a core off this list are refused by the backend, token and
+// all, whatever base URL the form holds.
+function watchAllowlist() {
+  if (!hasTauri || !allowedBaseUrlsEl) return;
+  invoke("get_settings")
+    .then((settings) => {
+      allowedBaseUrlsEl.value = (settings.allowed_base_urls || []).join("\n");
+    })
+    .catch(() => {});
+  saveAllowlistBtn?.addEventListener("click", () => {
+    const allowed_base_urls = allowedBaseUrlsEl.value
+      .split("\n")
+      .map((u) => u.trim())
+      .filter(Boolean);
+    runAction("Saving allowed cores", () => invoke("update_settings", { patch: { allowed_base_urls } }), false).catch(
+      () => {},
+    );
+  });
+}
+
+// Copies go through the backend so the clipboard guard can strip or refuse
+// secrets and clear the clipboard afterwards.
+async function copyText(text) {
+  if (!hasTauri) return navigator.clipboard?.writeText(text);
+  const out = await invoke("copy_to_clipboard", { text });
+  if (out.redacted?.length) setActionStatus(`Copied with ${out.redacted.join(", ")} redacted`, "neutral");
+  return out;
+}
+
+function watchClipboardGuard() {
+  if (!hasTauri || !clipboardGuardEl) return;
+  invoke("get_settings")
+    .then((settings) => {
+      const clipboard = settings.clipboard || {};
+      clipboardGuardEl.value = clipboard.guard ? clipboard.action : "off";
+      clipboardClearSecondsEl.value = clipboard.clear_after_seconds ?? "";
+    })
+    .catch(() => {});
+  saveClipboardBtn?.addEventListener("click", () => {
+    const mode = clipboardGuardEl.value;
+    const clipboard = { guard: mode !== "off", clear_after_seconds: Number(clipboardClearSecondsEl.value || 0) };
+    if (mode !== "off") clipboard.action = mode;
+    runAction("Saving clipboard guard", () => invoke("update_settings", { patch: { clipboard } }), false).catch(() => {});
+  });
+  listen("clipboard:cleared", ({ payload }) => {
+    if (payload.cleared) setActionStatus("Clipboard cleared", "neutral");
+  });
+}
+
+async function renderSecondApprovers() {
+  const settings = await invoke("get_settings");
+  const profile = (settings.profiles || []).find((item) => item.id === settings.active_profile);
+  const approvers = profile?.two_person?.approvers || [];
+  secondApproversEl.innerHTML = approvers.length
+    ? approvers
+        .map(
+          (a) => `<div class="plan"><div class="plan-head"><span class="plan-id">${escapeHTML(a.name)}</span>
+            <button class="secondary" data-remove-approver="${escapeHTML(a.id)}">Remove</button></div></div>`,
+        )
+        .join("")
+    : `<p class="event-meta">No second approvers enrolled for this profile.</p>`;
+}
+
+function watchSecondApprovers() {
+  if (!hasTauri || !secondApproversEl) return;
+  renderSecondApprovers().catch(() => {});
+  listen("settings:changed", () => renderSecondApprovers().catch(() => {}));
+  enrollApproverBtn?.addEventListener("click", () => {
+    const name = secondApproverNameEl.value.trim();
+    if (!name) return;
+    runAction("Enrolling approver", () => invoke("enroll_second_approver", { name }), false)
+      .then((out) => {
+        secondApproverNameEl.value = "";
+        enrollApproverOutEl.textContent = `Add this to ${out.approver.name}'s authenticator app (shown once):\n${out.otpauth_uri}\n\nSecret: ${out.secret}`;
+      })
+      .catch(() => {});
+  });
+  secondApproversEl.addEventListener("click", (event) => {
+    const approverId = event.target?.dataset?.removeApprover;
+    if (!approverId) return;
+    runAction("Removing approver", () => invoke("remove_second_approver", { approverId }), false).catch(() => {});
+  });
+}
+
+async function renderCookieJar() {
+  const jar = await invoke("get_cookie_jar");
+  cookieJarToggleEl.checked = jar.enabled;
+  if (jar.enabled && !jar.persistent) {
+    cookieJarStatusEl.textContent = "The keyring is unavailable, so cookies are kept until the app quits.";
+  }
+  cookieListEl.innerHTML = jar.cookies.length
+    ? jar.cookies
+        .map(
+          (c) => `<div class="plan"><div class="plan-head"><span class="plan-id">${escapeHTML(c.name)}</span>
+            <span class="event-meta">${escapeHTML(c.domain + c.path)} · ${c.session ? "session" : `until ${escapeHTML(c.expires_at)}`}</span></div></div>`,
+        )
+        .join("")
+    : `<p class="event-meta">No cookies stored for this profile.</p>`;
+}
+
+function watchCookieJar() {
+  if (!hasTauri || !cookieListEl) return;
+  renderCookieJar().catch(() => {});
+  listen("settings:changed", () => renderCookieJar().catch(() => {}));
+  cookieJarToggleEl.addEventListener("change", () => {
+    const enabled = cookieJarToggleEl.checked;
+    runAction(
+      enabled ? "Keeping session cookies" : "Dropping session cookies",
+      async () => invoke("upsert_profile", { profile: { ...(await activeProfile()), cookie_jar: enabled } }),
+      false,
+    ).catch(() => {});
+  });
+  clearCookiesBtn?.addEventListener("click", () => {
+    runAction("Clearing cookies", () => invoke("clear_cookie_jar"), false)
+      .then(() => renderCookieJar())
+      .catch(() => {});
+  });
+}
+
+async function activeProfile() {
+  const settings = await invoke("get_settings");
+  const profile = (settings.profiles || []).find((item) => item.id === settings.active_profile);
+  if (!profile) throw new Error("INVALID_INPUT: No active profile");
+  return profile;
+}
+
+function renderPlanComparison(out) {
+  const { summary } = out;
+  const targets = [
+    ...out.targets.added.map((t) => `+${t}`),
+    ...out.targets.removed.map((t) => `−${t}`),
+  ];
+  planComparisonSummaryEl.textContent = out.same_steps
+    ? `Plan ${out.b.id} has the same ${out.b.steps} steps as ${out.a.id}.`
+    : `${out.a.id} → ${out.b.id}: ${summary.added} added, ${summary.removed} removed, ${summary.changed} changed, ${summary.unchanged} unchanged${targets.length ? ` · targets ${targets.join(", ")}` : ""}`;
+  const fields = out.fields.map(
+    (f) => `<div class="plan"><div class="plan-head"><span class="plan-id">${escapeHTML(f.key)}</span><span class="badge neutral">changed</span></div>
+      <p class="event-meta">${escapeHTML(JSON.stringify(f.from ?? null))} → ${escapeHTML(JSON.stringify(f.to ?? null))}</p></div>`,
+  );
+  const tone = { added: "ok", removed: "error", changed: "neutral" };
+  const steps = out.steps
+    .filter((step) => step.change !== "unchanged")
+    .map((step) => {
+      const where = step.step_a && step.step_b ? `${step.step_a} → ${step.step_b}` : `${step.step_a || step.step_b}`;
+      const params = (step.params || [])
+        .map((p) => `<p class="event-meta">${escapeHTML(p.key)}: ${escapeHTML(JSON.stringify(p.from ?? null))} → ${escapeHTML(JSON.stringify(p.to ?? null))}</p>`)
+        .join("");
+      return `<div class="plan"><div class="plan-head"><span class="plan-id">Step ${escapeHTML(where)} · ${escapeHTML(step.kind || "action")}${step.target ? ` on ${escapeHTML(step.target)}` : ""}</span>
+        <span class="badge ${tone[step.change]}">${escapeHTML(step.change)}</span></div>${params}</div>`;
+    });
+  planComparisonEl.innerHTML = [...fields, ...steps].join("");
+}
+
+async function renderRequestHistory() {
+  const { entries } = await invoke("get_request_history", { limit: 50 });
+  requestHistoryEl.innerHTML = entries.length
+    ? entries
+        .map((entry) => {
+          const outcome = entry.error ? `<span class="badge error">failed</span>` : `<span class="badge ${entry.status < 400 ? "ok" : "error"}">${entry.status}</span>`;
+          const redirect = entry.final_url ? `<p class="event-meta">→ ${escapeHTML(entry.final_url)}</p>` : "";
+          const error = entry.error ? `<p class="event-meta">${escapeHTML(entry.error)}</p>` : "";
+          return `<div class="plan"><div class="plan-head"><span class="plan-id">${escapeHTML(entry.method)} ${escapeHTML(entry.url)}</span>${outcome}</div>
+            ${redirect}${error}<p class="event-meta">${escapeHTML(entry.at)} · ${entry.ms} ms</p></div>`;
+        })
+        .join("")
+    : `<p class="event-meta">No requests yet.</p>`;
+}
+
+function promptRevisionFeedback() {
+  const steps = window.prompt("Steps to drop or redo (numbers, comma-separated)", "");
+  if (steps === null) return null;
+  const constraints = window.prompt("Constraints for the new plan (one per ;)", "");
+  if (constraints === null) return null;
+  const comment = window.prompt("Anything else the agent should know", "");
+  if (comment === null) return null;
+  return {
+    rejected_steps: steps
+      .split(",")
+      .map((n) => Number(n.trim()))
+      .filter((n) => Number.isInteger(n) && n > 0),
+    constraints: constraints.split(";").map((c) => c.trim()).filter(Boolean),
+    comment: comment.trim() || null,
+  };
+}
+
+function watchPlanComparison() {
+  if (!hasTauri || !comparePlansBtn) return;
+  comparePlansBtn.addEventListener("click", async () => {
+    const out = await runAction(
+      "Comparing plans",
+      () => invoke("compare_plans", { idA: comparePlanAEl.value, idB: comparePlanBEl.value }),
+      false,
+    ).catch(() => null);
+    if (out) renderPlanComparison(out);
+  });
+  // A revision lands ready to compare with the plan it replaces.
+  listen("plan:revised", ({ payload }) => {
+    comparePlanAEl.value = payload.plan_id;
+    comparePlanBEl.value = payload.revision_plan_id;
+    setActionStatus(`Plan ${payload.plan_id} revised as ${payload.revision_plan_id}`, "ok");
+    scheduleRefresh();
+  });
+}
+
+function watchRequestHistory() {
+  if (!hasTauri || !requestHistoryEl) return;
+  const loadRedirects = () =>
+    activeProfile()
+      .then((profile) => {
+        redirectModeEl.value = profile.redirects?.mode || "follow";
+        redirectMaxEl.value = profile.redirects?.max ?? "";
+      })
+      .catch(() => {});
+  loadRedirects();
+  listen("settings:changed", loadRedirects);
+  saveRedirectsBtn?.addEventListener("click", () => {
+    const redirects = { mode: redirectModeEl.value, max: Number(redirectMaxEl.value || 0) };
+    runAction(
+      "Saving redirect policy",
+      async () => invoke("upsert_profile", { profile: { ...(await activeProfile()), redirects } }),
+      false,
+    ).catch(() => {});
+  });
+  refreshRequestHistoryBtn?.addEventListener("click", () => renderRequestHistory().catch(() => {}));
+  renderRequestHistory().catch(() => {});
+}
+
+// Decisions taken from a notification while the OS session was locked wait
+// in the backend and fire after the unlock.
+function watchNotificationActions() {
+  if (!hasTauri) return;
+  listen("notification_action:queued", ({ payload }) => {
+    setActionStatus(`Plan ${payload.plan_id}: ${payload.decision} queued until the session is unlocked`, "neutral");
+  });
+  listen("notification_action:done", ({ payload }) => {
+    const { intent } = payload;
+    if (payload.sent) {
+      setActionStatus(`Plan ${intent.plan_id}: queued ${intent.decision} sent`, "ok");
+      scheduleRefresh();
+    } else {
+      setActionStatus(`Plan ${intent.plan_id}: queued ${intent.decision} not sent`, "error");
+      summaryEl.textContent = describeError(payload.error);
+    }
+  });
+}
+
+// Plans carrying inline credentials tend to leak them into execution logs,
+// so approving one takes an explicit confirmation. A scan that fails does
+// not block the approval.
+async function confirmNoSecrets(planId) {
+  if (!hasTauri) return true;
+  let scan;
+  try {
+    scan = await invoke("scan_plan_secrets", { planId });
+  } catch {
+    return true;
+  }
+  if (scan.clean) return true;
+  const listed = scan.findings
+    .slice(0, 8)
+    .map((f) => `• ${f.rule} at ${f.path || "(plan)"}: ${f.excerpt}`)
+    .join("\n");
+  const more = scan.findings.length > 8 ? `\n…and ${scan.findings.length - 8} more` : "";
+  return window.confirm(
+    `Plan ${planId} appears to contain secrets:\n${listed}${more}\n\nExecuting it may write them to logs. Approve anyway?`,
+  );
+}
+
+// Plans approved step by step wait before each step until it is approved.
+function watchStepGates() {
+  if (!hasTauri) return;
+  listen("plan:step_awaiting", ({ payload }) => {
+    stepGates.set(payload.plan_id, payload);
+    setActionStatus(`Plan ${payload.plan_id}: step ${payload.position || payload.awaiting_step} awaits approval`, "neutral");
+    scheduleRefresh();
+  });
+  listen("plan:step_gate_done", ({ payload }) => {
+    stepGates.delete(payload.plan_id);
+    setActionStatus(`Plan ${payload.plan_id} finished step-by-step execution (${payload.status || "gone"})`, "ok");
+    scheduleRefresh();
+  });
+  invoke("list_step_gates")
+    .then((out) => (out.gates || []).forEach((entry) => stepGates.set(entry.plan_id, entry)))
+    .catch(() => {});
+}
+
+// Aborting takes two backend calls: the first returns the plan as it is now
+// and a one-time code, the second sends the abort with it.
+async function confirmAbort(planId) {
+  const reason = window.prompt(`Why abort plan ${planId}?`, "Misbehaving execution");
+  if (!reason) return null;
+  const check = await runAction("Checking plan before abort", () => invoke("abort_execution", { planId, reason }), false);
+  const progress = check.progress_total ? ` at ${check.progress_completed || 0}/${check.progress_total} steps` : "";
+  const confirmed = window.confirm(
+    `Abort "${check.objective || planId}" (${check.status || "unknown"}${progress})? Steps already run are not undone.`,
+  );
+  if (!confirmed) return null;
+  return runAction(
+    "Aborting execution",
+    () => invoke("abort_execution", { planId, reason, confirmation: check.confirmation }),
+    false,
+  );
+}
+
+// Executing plans the backend saw make no progress for the configured
+// window get Retry Step / Abort Execution buttons until they move again.
+function watchStalledPlans() {
+  if (!hasTauri) return;
+  listen("plan:stalled", ({ payload }) => {
+    stalledPlans.set(payload.plan_id, payload);
+    setActionStatus(`Plan ${payload.plan_id} may be stalled at step ${payload.step}`, "error");
+    scheduleRefresh();
+  });
+  listen("plan:resumed", ({ payload }) => {
+    stalledPlans.delete(payload.plan_id);
+    setActionStatus(`Plan ${payload.plan_id} is progressing again`, "ok");
+    scheduleRefresh();
+  });
+  invoke("list_stalled_plans")
+    .then((out) => (out.plans || []).forEach((entry) => stalledPlans.set(entry.plan_id, entry)))
+    .catch(() => {});
+}
+
+async function togglePlanWatch(planId) {
+  if (watchedPlans.has(planId)) {
+    watchedPlans.delete(planId);
+    return invoke("unwatch_plan", { planId });
+  }
+  const out = await invoke("watch_plan", { planId });
+  watchedPlans.add(planId);
+  return out;
+}
+
+function describePlanWatch(event) {
+  switch (event.kind) {
+    case "watching":
+      return `watching (${event.status || "unknown"}, ${event.completed}/${event.total} steps)`;
+    case "status":
+      return `${event.from || "unknown"} → ${event.to || "unknown"}`;
+    case "step":
+      return `step ${event.step} ${event.status}`;
+    case "progress":
+      return `${event.completed}/${event.total} steps done`;
+    case "comment":
+      return `new comment${event.comment?.author ? ` from ${event.comment.author}` : ""}: ${event.comment?.body || event.comment?.text || ""}`;
+    case "gone":
+      return "no longer on the core; watch removed";
+    default:
+      return `watch failed: ${describeError(event.error)}`;
+  }
+}
+
+// Watched plans are polled by the backend on their own, so their changes
+// show up here without a dashboard refresh.
+function watchPlanWatches() {
+  if (!hasTauri) return;
+  listen("plan:watch", ({ payload }) => {
+    if (payload.kind === "gone") watchedPlans.delete(payload.plan_id);
+    const tone = payload.kind === "error" ? "error" : payload.kind === "watching" ? "neutral" : "ok";
+    setActionStatus(`Plan ${payload.plan_id}: ${describePlanWatch(payload)}`, tone);
+    if (payload.kind === "status" || payload.kind === "gone") scheduleRefresh();
+  });
+  invoke("list_watched_plans")
+    .then((out) => (out.plans || []).forEach((entry) => watchedPlans.add(entry.plan_id)))
+    .catch(() => {});
+}
+
+let offlineReview = null;
+
+function renderOfflineReview(review) {
+  offlineReview = review;
+  if (!review) {
+    offlinePlansEl.innerHTML = "";
+    return;
+  }
+  airgapStatusEl.textContent = `Bundle ${review.bundle_id} from ${review.core || "unknown core"}: ${review.decided}/${review.plans.length} decided.`;
+  offlinePlansEl.innerHTML = review.plans
+    .map((entry) => {
+      const plan = entry.plan || {};
+      const decided = entry.decision?.decision;
+      const badge = decided
+        ? `<span class="badge ${decided === "approve" ? "ok" : "error"}">${escapeHTML(decided)}</span>`
+        : `<span class="badge neutral">undecided</span>`;
+      return `<div class="plan"><div class="plan-head"><span class="plan-id">${escapeHTML(entry.id)}</span>${badge}</div>
+        <p>${escapeHTML(plan.objective || plan.title || "")}</p>
+        <div class="row">
+          <button class="secondary" data-offline-decision="approve" data-plan-id="${escapeHTML(entry.id)}">Approve</button>
+          <button class="secondary" data-offline-decision="reject" data-plan-id="${escapeHTML(entry.id)}">Reject</button>
+        </div></div>`;
+    })
+    .join("");
+}
+
+function watchAirgap() {
+  if (!hasTauri || !offlinePlansEl) return;
+  invoke("get_offline_review").then(renderOfflineReview).catch(() => {});
+  const path = () => airgapPathEl.value.trim();
+  const show = (out) => {
+    airgapOutEl.textContent = JSON.stringify(out, null, 2);
+    return out;
+  };
+  const bind = (id, label, fn) =>
+    document.querySelector(id)?.addEventListener("click", () => {
+      runAction(label, fn, false).catch(() => {});
+    });
+  bind("#exportBundleBtn", "Exporting plan bundle", () => invoke("export_plan_bundle", { path: path() }).then(show));
+  bind("#importBundleBtn", "Importing plan bundle", () =>
+    invoke("import_plan_bundle", { path: path() }).then(renderOfflineReview),
+  );
+  bind("#exportDecisionsBtn", "Exporting decisions", () => {
+    if (!offlineReview) throw new Error("Import a plan bundle first");
+    return invoke("export_decision_file", { bundleId: offlineReview.bundle_id, path: path() }).then(show);
+  });
+  bind("#importDecisionsBtn", "Applying decision file", () =>
+    invoke("import_decision_file", { path: path() }).then((out) => {
+      scheduleRefresh();
+      return show(out);
+    }),
+  );
+  bind("#airgapKeyBtn", "Reading signing key", () => invoke("get_airgap_key").then(show));
+  offlinePlansEl.addEventListener("click", (event) => {
+    const decision = event.target?.dataset?.offlineDecision;
+    if (!decision || !offlineReview) return;
+    const reason = window.prompt(`Reason for ${decision} (optional)`, "");
+    if (reason === null) return;
+    runAction(
+      `Recording ${decision}`,
+      () =>
+        invoke("decide_offline", {
+          bundleId: offlineReview.bundle_id,
+          planId: event.target.dataset.planId,
+          decision,
+          reason,
+        }).then(() => invoke("get_offline_review", { bundleId: offlineReview.bundle_id }).then(renderOfflineReview)),
+      false,
+    ).catch(() => {});
+  });
+}
+
+function watchMaintenance() {
+  if (!hasTauri) return;
+  listen("core:maintenance", ({ payload }) => {
+    coreMaintenance = true;
+    const left = payload.retry_in_seconds;
+    setConnectionStatus(left > 0 ? `Maintenance, retry in ${left}s` : "Maintenance, checking", "error");
+    if (payload.reason) summaryEl.textContent = payload.reason;
+  });
+  listen("core:maintenance_ended", () => {
+    coreMaintenance = false;
+    setConnectionStatus("Connected", "ok");
+    refresh().catch(() => {});
+  });
+}
+
+// Input in the window counts as activity for idle detection where the OS
+// idle time cannot be read; reported at most every 30 seconds.
+function watchOperatorActivity() {
+  if (!hasTauri) return;
+  let reported = 0;
+  const report = () => {
+    const now = Date.now();
+    if (now - reported < 30000) return;
+    reported = now;
+    invoke("report_operator_activity").catch(() => {});
+  };
+  for (const type of ["keydown", "pointerdown", "pointermove", "wheel"]) {
+    window.addEventListener(type, report, { passive: true });
+  }
+}
+
+function watchSlowRequests() {
+  if (!hasTauri) return;
+  listen("core:slow_request", ({ payload }) => {
+    const seconds = (payload.elapsed_ms / 1000).toFixed(1);
+    const endpoint = `${payload.method} ${payload.endpoint}`;
+    if (payload.kind === "slow") {
+      setActionStatus(`Slow core request: ${endpoint} (${seconds}s so far)`, "neutral");
+    } else if (payload.kind === "aborted") {
+      setActionStatus(`Retrying ${endpoint} after ${seconds}s without an answer`, "neutral");
+    }
+  });
+}
+
+function watchEndpointChanges() {
+  if (!hasTauri) return;
+  listen("core:endpoint_changed", ({ payload }) => {
+    const tone = payload.reason === "faster" ? "neutral" : "error";
+    setActionStatus(`Core endpoint switched to ${payload.to} (${payload.reason})`, tone);
+  });
+}
+
+// Adds styled spans from `parse_ansi` (or the backend streams) to `list`.
+// A span with `line_reset` rewrites the current line, so what came after the
+// last newline is dropped first. Keeps at most `maxChars` of text.
+function appendSpans(list, spans, maxChars) {
+  for (const span of spans) {
+    if (span.line_reset) {
+      while (list.length > 0) {
+        const last = list[list.length - 1];
+        const newline = last.text.lastIndexOf("\n");
+        if (newline >= 0) {
+          last.text = last.text.slice(0, newline + 1);
+          break;
+        }
+        list.pop();
+      }
+    }
+    if (span.text) list.push({ ...span, line_reset: false });
+  }
+  let total = list.reduce((sum, span) => sum + span.text.length, 0);
+  while (total > maxChars && list.length > 1) total -= list.shift().text.length;
+  return list;
+}
+
+function renderSpans(el, spans, emptyText) {
+  if (!el) return;
+  if (!spans.some((span) => span.text)) {
+    el.textContent = emptyText;
+    return;
+  }
+  el.innerHTML = spans
+    .map((span) => {
+      let [fg, bg] = [span.fg, span.bg];
+      if (span.inverse) [fg, bg] = [bg || "default_bg", fg || "default_fg"];
+      const classes = [];
+      const styles = [];
+      for (const [kind, color] of [["fg", fg], ["bg", bg]]) {
+        if (!color) continue;
+        if (color.startsWith("#")) styles.push(`${kind === "fg" ? "color" : "background"}:${color}`);
+        else classes.push(`ansi-${kind}-${color}`);
+      }
+      for (const flag of ["bold", "dim", "italic", "underline", "strike"]) {
+        if (span[flag]) classes.push(`ansi-${flag}`);
+      }
+      const text = escapeHTML(span.text);
+      if (classes.length === 0 && styles.length === 0) return text;
+      return `<span class="${classes.join(" ")}" style="${styles.join(";")}">${text}</span>`;
+    })
+    .join("");
+}
+
+function appendPlanOutput(text, spans = [{ text }]) {
+  appendSpans(planOutputState.spans, spans, PLAN_OUTPUT_MAX_CHARS);
+  renderSpans(planOutputEl, planOutputState.spans, "Waiting for output…");
+}
+
+function renderPlanOutputChunk(chunk) {
+  if (chunk.stream === "status") return appendPlanOutput(`\n[step ${chunk.step}] ${chunk.text}\n`);
+  const spans = chunk.spans || [{ text: chunk.text }];
+  if (chunk.stream !== "stderr") return appendPlanOutput(chunk.text, spans);
+  appendPlanOutput(
+    chunk.text,
+    spans.map((span) => ({ ...span, fg: span.fg || "red" })),
+  );
+}
+
+// Counts a visit toward the frecency-ranked recent items; best effort.
+function recordRecent(kind, itemId, label = "") {
+  if (!hasTauri || !itemId) return;
+  invoke("record_recent_item", { kind, itemId: String(itemId), label: String(label || "") }).catch(() => {});
+}
+
+async function followPlanOutput(planId) {
+  if (!hasTauri || !planId) return;
+  recordRecent("plan", planId);
+  Object.assign(planOutputState, { planId, lastSeq: 0, spans: [] });
+  prefSet(PLAN_OUTPUT_PREF, planId);
+  appendPlanOutput("");
+  let after = 0;
+  for (;;) {
+    const page = await invoke("plan_output_transcript", { planId, afterSeq: after });
+    for (const chunk of page.chunks || []) renderPlanOutputChunk(chunk);
+    after = Number(page.last_seq || after);
+    if (!page.more) break;
+  }
+  planOutputState.lastSeq = after;
+  await invoke("watch_plan_output", { planId });
+}
+
+function watchPlanOutput() {
+  if (!hasTauri) return;
+  listen("plan:output", ({ payload }) => {
+    if (payload.plan_id !== planOutputState.planId) return;
+    if (payload.kind === "output") {
+      if (payload.to_seq <= planOutputState.lastSeq) return;
+      planOutputState.lastSeq = payload.to_seq;
+      renderPlanOutputChunk(payload);
+    } else if (payload.kind === "step") {
+      if (payload.seq <= planOutputState.lastSeq) return;
+      planOutputState.lastSeq = payload.seq;
+      renderPlanOutputChunk({ step: payload.step, stream: "status", text: payload.status });
+    } else if (payload.kind === "dropped") {
+      appendPlanOutput(`\n… ${payload.bytes} bytes skipped here (kept in the transcript)\n`);
+    } else if (payload.kind === "end") {
+      appendPlanOutput(`\n[plan ${payload.status || `stream ended: ${payload.error}`}]\n`);
+    }
+  });
+  const planId = prefGet(PLAN_OUTPUT_PREF);
+  if (planId) followPlanOutput(planId).catch(() => {});
+}
+
+function planHistoryQuery() {
+  const view = savedViews.find((item) => item.id === planHistoryViewEl?.value);
+  if (view) return { profile: view.profile || null, filter: view.filter, sort: view.sort || null };
+  const text = String(planHistoryTextEl?.value || "").trim();
+  const status = String(planHistoryStatusEl?.value || "");
+  return {
+    filter: { text: text || null, status: status ? [status] : [] },
+    sort: String(planHistorySortEl?.value || "-created_at"),
+  };
+}
+
+async function loadPlanHistoryPage(page) {
+  const { generation } = planHistoryState;
+  if (planHistoryState.pages.has(page) || planHistoryState.loading.has(page)) return;
+  planHistoryState.loading.add(page);
+  try {
+    const result = await invoke("get_plans_window", {
+      offset: page * PLAN_HISTORY_PAGE,
+      limit: PLAN_HISTORY_PAGE,
+      ...planHistoryQuery(),
+    });
+    if (generation !== planHistoryState.generation) return;
+    planHistoryState.total = Number(result.total || 0);
+    planHistoryState.pages.set(page, result.items || []);
+  } finally {
+    if (generation === planHistoryState.generation) planHistoryState.loading.delete(page);
+  }
+  renderPlanHistory();
+}
+
+function renderPlanHistory() {
+  if (!planHistoryEl) return;
+  const { total, pages } = planHistoryState;
+  const spacer = planHistoryEl.querySelector(".plan-history-spacer");
+  spacer.style.height = `${total * PLAN_HISTORY_ROW_HEIGHT}px`;
+  if (planHistoryCountEl) planHistoryCountEl.textContent = `${total} cached plans`;
+
+  const first = Math.floor(planHistoryEl.scrollTop / PLAN_HISTORY_ROW_HEIGHT);
+  const visible = Math.ceil(planHistoryEl.clientHeight / PLAN_HISTORY_ROW_HEIGHT) + 1;
+  const last = Math.min(total, first + visible);
+  const wanted = new Set();
+  for (let row = Math.max(0, first - visible); row < Math.min(total, last + visible); row += 1) {
+    wanted.add(Math.floor(row / PLAN_HISTORY_PAGE));
+  }
+  for (const page of wanted) loadPlanHistoryPage(page).catch(() => {});
+  const current = Math.floor(first / PLAN_HISTORY_PAGE);
+  for (const page of [...pages.keys()]) {
+    if (pages.size <= PLAN_HISTORY_KEEP_PAGES) break;
+    if (!wanted.has(page) && Math.abs(page - current) > 1) pages.delete(page);
+  }
+
+  const rows = [];
+  for (let row = first; row < last; row += 1) {
+    const plan = pages.get(Math.floor(row / PLAN_HISTORY_PAGE))?.[row % PLAN_HISTORY_PAGE];
+    const top = row * PLAN_HISTORY_ROW_HEIGHT;
+    rows.push(
+      plan
+        ? `<div class="plan-history-row" style="top:${top}px">
+            <span class="plan-id">${escapeHTML(formatTimestamp(plan.created_at))}</span>
+            <strong>${escapeHTML(plan.objective || "(no objective)")}</strong>
+            <span class="plan-id">${escapeHTML(String(plan.status || "unknown").toLowerCase())}</span>
+          </div>`
+        : `<div class="plan-history-row" style="top:${top}px"><span class="plan-id">Loading…</span></div>`,
+    );
+  }
+  planHistoryEl.innerHTML = `<div class="plan-history-spacer" style="height:${spacer.style.height}"></div>${rows.join("")}`;
+}
+
+function refreshPlanHistory() {
+  if (!hasTauri || !planHistoryEl) return;
+  planHistoryState.generation += 1;
+  planHistoryState.pages.clear();
+  planHistoryState.loading.clear();
+  planHistoryEl.scrollTop = 0;
+  loadPlanHistoryPage(0).catch((err) => {
+    if (planHistoryCountEl) planHistoryCountEl.textContent = describeError(err);
+  });
+}
+
+function renderSavedViews(views) {
+  savedViews = Array.isArray(views) ? views : [];
+  if (!planHistoryViewEl) return;
+  const selected = planHistoryViewEl.value;
+  planHistoryViewEl.innerHTML = [
+    `<option value="">No saved view</option>`,
+    ...savedViews.map((view) => `<option value="${escapeHTML(view.id)}">${escapeHTML(view.name)}</option>`),
+  ].join("");
+  planHistoryViewEl.value = savedViews.some((view) => view.id === selected) ? selected : "";
+}
+
+function applySavedView() {
+  const view = savedViews.find((item) => item.id === planHistoryViewEl?.value);
+  if (view) {
+    if (planHistoryTextEl) planHistoryTextEl.value = view.filter?.text || "";
+    if (planHistoryStatusEl) planHistoryStatusEl.value = view.filter?.status?.[0] || "";
+    if (planHistorySortEl) planHistorySortEl.value = view.sort || "-created_at";
+  }
+  refreshPlanHistory();
+}
+
+async function saveCurrentView() {
+  const name = window.prompt("Name this view", "");
+  if (!name || !name.trim()) return;
+  const { filter, sort } = planHistoryQuery();
+  const view = await invoke("create_view", { view: { name, filter, sort } });
+  renderSavedViews([...savedViews.filter((item) => item.id !== view.id), view]);
+  if (planHistoryViewEl) planHistoryViewEl.value = view.id;
+}
+
+async function moveSelectedViewUp() {
+  const index = savedViews.findIndex((item) => item.id === planHistoryViewEl?.value);
+  if (index <= 0) return;
+  const ids = savedViews.map((item) => item.id);
+  [ids[index - 1], ids[index]] = [ids[index], ids[index - 1]];
+  renderSavedViews((await invoke("reorder_views", { ids })).views);
+}
+
+async function deleteSelectedView() {
+  const view = savedViews.find((item) => item.id === planHistoryViewEl?.value);
+  if (!view || !window.confirm(`Delete the saved view "${view.name}"?`)) return;
+  renderSavedViews((await invoke("delete_view", { id: view.id })).views);
+  refreshPlanHistory();
+}
+
+function watchPlanHistory() {
+  if (!hasTauri || !planHistoryEl) return;
+  let scheduled = false;
+  planHistoryEl.addEventListener("scroll", () => {
+    if (scheduled) return;
+    scheduled = true;
+    window.requestAnimationFrame(() => {
+      scheduled = false;
+      renderPlanHistory();
+    });
+  });
+  // Editing the filter leaves the saved view it started from.
+  const edited = () => {
+    if (planHistoryViewEl) planHistoryViewEl.value = "";
+    refreshPlanHistory();
+  };
+  let debounce = null;
+  // A search counts once typing has settled, not for every prefix.
+  let settled = null;
+  planHistoryTextEl?.addEventListener("input", () => {
+    window.clearTimeout(debounce);
+    window.clearTimeout(settled);
+    debounce = window.setTimeout(edited, 250);
+    settled = window.setTimeout(() => {
+      const text = planHistoryTextEl.value.trim();
+      if (text) recordRecent("search", text, text);
+    }, 2000);
+  });
+  planHistoryStatusEl?.addEventListener("change", edited);
+  planHistorySortEl?.addEventListener("change", edited);
+  planHistoryViewEl?.addEventListener("change", applySavedView);
+  saveViewBtn?.addEventListener("click", () => {
+    runAction("Saving view", () => saveCurrentView(), false).catch(() => {});
+  });
+  moveViewUpBtn?.addEventListener("click", () => {
+    runAction("Reordering views", () => moveSelectedViewUp(), false).catch(() => {});
+  });
+  deleteViewBtn?.addEventListener("click", () => {
+    runAction("Deleting view", () => deleteSelectedView(), false).catch(() => {});
+  });
+  listen("sync:changed", () => refreshPlanHistory());
+  // Views saved in another window arrive with the settings.
+  listen("settings:changed", ({ payload }) => renderSavedViews(payload?.saved_views));
+  invoke("list_views")
+    .then((result) => renderSavedViews(result.views))
+    .catch(() => {});
+  refreshPlanHistory();
+}
+
+function renderQuickActions(entries) {
+  if (!quickActionListEl) return;
+  quickActionListEl.innerHTML = "";
+  if (!entries.length) {
+    quickActionListEl.innerHTML = "<p>No quick actions defined.</p>";
+    return;
+  }
+  for (const entry of entries) {
+    const { action } = entry;
+    const card = document.createElement("div");
+    card.className = "plan";
+    card.innerHTML = `
+      <div class="plan-head">
+        <strong>${escapeHTML(action.name)}</strong>
+        <span class="plan-id">${escapeHTML(`${action.method} ${action.path}`)}</span>
+      </div>`;
+    const row = document.createElement("div");
+    row.className = "row";
+    row.appendChild(
+      actionButton("Run", "primary", () => {
+        runAction(`Running ${action.name}`, () => runQuickAction(entry), false).catch(() => {});
+      }),
+    );
+    row.appendChild(
+      actionButton("Delete", "danger", () => {
+        if (!window.confirm(`Delete the quick action "${action.name}"?`)) return;
+        runAction(`Deleting ${action.name}`, () => invoke("delete_quick_action", { id: action.id }), false).catch(
+          () => {},
+        );
+      }),
+    );
+    card.appendChild(row);
+    quickActionListEl.appendChild(card);
+  }
+}
+
+// Asks for each parameter, shows the rendered request when the action wants
+// confirmation, then sends it.
+async function runQuickAction(entry) {
+  const params = {};
+  for (const name of entry.parameters || []) {
+    const value = window.prompt(`${entry.action.name}: ${name}`, "");
+    if (value === null) return;
+    params[name] = value;
+  }
+  let result = await invoke("run_quick_action", { id: entry.action.id, params });
+  if (result.confirmation_required) {
+    const body = result.payload === null || result.payload === undefined ? "" : `\n\n${JSON.stringify(result.payload, null, 2)}`;
+    if (!window.confirm(`Send to ${result.profile_id}?\n\n${result.method} ${result.path}${body}`)) return;
+    result = await invoke("run_quick_action", { id: entry.action.id, params, confirmed: true });
+  }
+  if (quickActionOutputEl) quickActionOutputEl.textContent = JSON.stringify(result, null, 2);
+}
+
+async function saveQuickAction() {
+  const raw = String(quickActionPayloadEl?.value || "").trim();
+  let payload = null;
+  if (raw) {
+    try {
+      payload = JSON.parse(raw);
+    } catch {
+      throw new Error("Quick action payload must be JSON");
+    }
+  }
+  await invoke("upsert_quick_action", {
+    action: {
+      name: quickActionNameEl?.value || "",
+      method: quickActionMethodEl?.value || "POST",
+      path: quickActionPathEl?.value || "",
+      payload,
+      confirm: Boolean(quickActionConfirmEl?.checked),
+      tray: Boolean(quickActionTrayEl?.checked),
+    },
+  });
+  if (quickActionNameEl) quickActionNameEl.value = "";
+  if (quickActionPathEl) quickActionPathEl.value = "";
+  if (quickActionPayloadEl) quickActionPayloadEl.value = "";
+}
+
+function watchQuickActions() {
+  if (!hasTauri) return;
+  const reload = () =>
+    invoke("list_quick_actions")
+      .then((result) => renderQuickActions(result.actions || []))
+      .catch(() => {});
+  saveQuickActionBtn?.addEventListener("click", () => {
+    runAction("Saving quick action", () => saveQuickAction(), false).catch(() => {});
+  });
+  listen("settings:changed", reload);
+  // Tray entries that need parameters or a confirmation finish here.
+  listen("quick_action:requested", ({ payload }) => {
+    runAction(`Running ${payload.action.name}`, () => runQuickAction(payload), false).catch(() => {});
+  });
+  reload();
+}
+
+function renderPlugins(result) {
+  if (!pluginListEl) return;
+  const plugins = result.plugins || [];
+  if (pluginStatusEl) {
+    pluginStatusEl.textContent = result.supported
+      ? `Plugins are loaded from ${result.dir}.`
+      : "This build has no plugin runtime (build with --features plugins).";
+  }
+  pluginListEl.innerHTML = "";
+  if (!plugins.length) {
+    pluginListEl.innerHTML = "<p>No plugins installed.</p>";
+    return;
+  }
+  for (const plugin of plugins) {
+    const { manifest } = plugin;
+    const card = document.createElement("div");
+    card.className = "plan";
+    card.innerHTML = `
+      <div class="plan-head">
+        <strong>${escapeHTML(manifest.name || manifest.id)}</strong>
+        <span class="plan-id">${escapeHTML(`${manifest.id} ${manifest.version || ""}`)}</span>
+      </div>
+      <p>${escapeHTML(plugin.error || manifest.description || "")}</p>`;
+    const toggles = document.createElement("div");
+    toggles.className = "row";
+    const granted = new Set(plugin.granted || []);
+    const boxes = [];
+    for (const capability of manifest.capabilities || []) {
+      const label = document.createElement("label");
+      label.className = "toggle checkbox-inline";
+      const box = document.createElement("input");
+      box.type = "checkbox";
+      box.checked = granted.has(capability);
+      box.dataset.capability = capability;
+      boxes.push(box);
+      label.append(box, document.createTextNode(` ${capability}`));
+      toggles.appendChild(label);
+    }
+    card.appendChild(toggles);
+    const row = document.createElement("div");
+    row.className = "row";
+    const save = (enabled) =>
+      runAction(
+        `${enabled ? "Enabling" : "Disabling"} ${manifest.id}`,
+        () =>
+          invoke("set_plugin_grant", {
+            id: manifest.id,
+            enabled,
+            capabilities: boxes.filter((b) => b.checked).map((b) => b.dataset.capability),
+          }),
+        false,
+      )
+        .then(() => refreshPlugins())
+        .catch(() => {});
+    row.appendChild(actionButton(plugin.enabled ? "Update Grant" : "Enable", "primary", () => save(true)));
+    if (plugin.enabled) row.appendChild(actionButton("Disable", "danger", () => save(false)));
+    row.appendChild(
+      actionButton("Remove", "danger", () => {
+        if (!window.confirm(`Uninstall the plugin "${manifest.name || manifest.id}"?`)) return;
+        runAction(`Removing ${manifest.id}`, () => invoke("remove_plugin", { id: manifest.id }), false)
+          .then(() => refreshPlugins())
+          .catch(() => {});
+      }),
+    );
+    card.appendChild(row);
+    if ((plugin.logs || []).length) {
+      const logs = document.createElement("pre");
+      logs.className = "summary";
+      logs.textContent = plugin.logs.slice(-10).join("\n");
+      card.appendChild(logs);
+    }
+    pluginListEl.appendChild(card);
+  }
+}
+
+function renderPluginPalette(entries) {
+  if (!pluginPaletteEl) return;
+  pluginPaletteEl.innerHTML = "";
+  if (!entries.length) {
+    pluginPaletteEl.innerHTML = "<p>No palette entries from plugins.</p>";
+    return;
+  }
+  const row = document.createElement("div");
+  row.className = "row";
+  for (const entry of entries) {
+    row.appendChild(
+      actionButton(entry.title, "secondary", () => {
+        runAction(
+          entry.title,
+          () => invoke("invoke_plugin", { pluginId: entry.plugin_id, command: entry.command, input: entry.input }),
+          false,
+        )
+          .then((result) => {
+            if (pluginOutputEl) pluginOutputEl.textContent = JSON.stringify(result, null, 2);
+          })
+          .catch(() => {});
+      }),
+    );
+  }
+  pluginPaletteEl.appendChild(row);
+}
+
+// Shows what the verified package asks for before anything is installed;
+// declining leaves the installed version as it was.
+async function installPlugin(id, version) {
+  const staged = await invoke("install_plugin", { id, version });
+  const lines = [
+    `Install ${staged.name} ${staged.version}${staged.installed ? ` (replacing ${staged.installed})` : ""}?`,
+    "",
+    `Capabilities: ${(staged.capabilities || []).join(", ") || "none"}`,
+  ];
+  if ((staged.new_capabilities || []).length) lines.push(`New: ${staged.new_capabilities.join(", ")}`);
+  if ((staged.core_paths || []).length) lines.push(`Core paths: ${staged.core_paths.join(", ")}`);
+  if ((staged.events || []).length) lines.push(`Events: ${staged.events.join(", ")}`);
+  lines.push("", `Signed by ${staged.signed_by}`);
+  if (!window.confirm(lines.join("\n"))) return null;
+  return invoke("confirm_plugin_install", { token: staged.token, capabilities: staged.capabilities });
+}
+
+function renderPluginRegistry(result) {
+  if (!pluginRegistryListEl) return;
+  pluginRegistryListEl.innerHTML = "";
+  const plugins = result.plugins || [];
+  if (!plugins.length) {
+    pluginRegistryListEl.innerHTML = "<p>The registry lists no plugins.</p>";
+    return;
+  }
+  for (const plugin of plugins) {
+    const card = document.createElement("div");
+    card.className = "plan";
+    const state = plugin.installed
+      ? `installed ${plugin.installed}${plugin.update_available ? ", update available" : ""}`
+      : "not installed";
+    card.innerHTML = `
+      <div class="plan-head">
+        <strong>${escapeHTML(plugin.name || plugin.id)}</strong>
+        <span class="plan-id">${escapeHTML(`${plugin.id} · ${state}${plugin.pin ? ` · pinned ${plugin.pin}` : ""}`)}</span>
+      </div>
+      <p>${escapeHTML(plugin.description || "")}</p>`;
+    const row = document.createElement("div");
+    row.className = "row";
+    const picker = document.createElement("select");
+    for (const release of plugin.versions || []) {
+      const option = document.createElement("option");
+      option.value = release.version;
+      option.textContent = release.version;
+      picker.appendChild(option);
+    }
+    if (plugin.pin) picker.value = plugin.pin;
+    row.appendChild(picker);
+    const label = !plugin.installed ? "Install" : plugin.update_available ? "Update" : "Reinstall";
+    row.appendChild(
+      actionButton(label, "primary", () => {
+        runAction(`${label} ${plugin.id}`, () => installPlugin(plugin.id, picker.value), false)
+          .then(() => refreshPlugins())
+          .catch(() => {});
+      }),
+    );
+    row.appendChild(
+      actionButton(plugin.pin ? "Unpin" : "Pin", "secondary", () => {
+        runAction(
+          `${plugin.pin ? "Unpinning" : "Pinning"} ${plugin.id}`,
+          () => invoke("pin_plugin", { id: plugin.id, version: plugin.pin ? null : picker.value }),
+          false,
+        )
+          .then(() => browseRegistry())
+          .catch(() => {});
+      }),
+    );
+    card.appendChild(row);
+    pluginRegistryListEl.appendChild(card);
+  }
+}
+
+function browseRegistry() {
+  return runAction("Browsing plugin registry", () => invoke("browse_plugin_registry"), false)
+    .then(renderPluginRegistry)
+    .catch(() => {});
+}
+
+function refreshPlugins() {
+  invoke("list_plugins")
+    .then(renderPlugins)
+    .catch(() => {});
+  invoke("plugin_palette_entries")
+    .then((result) => renderPluginPalette(result.entries || []))
+    .catch(() => {});
+}
+
+function watchPlugins() {
+  if (!hasTauri) return;
+  invoke("get_settings")
+    .then((settings) => {
+      const registry = settings.plugin_registry || {};
+      if (pluginRegistryUrlEl) pluginRegistryUrlEl.value = registry.url || "";
+      if (pluginRegistryKeysEl) pluginRegistryKeysEl.value = (registry.trusted_keys || []).join("\n");
+    })
+    .catch(() => {});
+  saveRegistryBtn?.addEventListener("click", () => {
+    const trusted_keys = String(pluginRegistryKeysEl?.value || "")
+      .split("\n")
+      .map((k) => k.trim())
+      .filter(Boolean);
+    const url = String(pluginRegistryUrlEl?.value || "").trim();
+    runAction(
+      "Saving plugin registry",
+      () => invoke("update_settings", { patch: { plugin_registry: { url, trusted_keys } } }),
+      false,
+    ).catch(() => {});
+  });
+  browseRegistryBtn?.addEventListener("click", () => browseRegistry());
+  reloadPluginsBtn?.addEventListener("click", () => {
+    runAction("Reloading plugins", () => invoke("reload_plugins"), false)
+      .then(() => refreshPlugins())
+      .catch(() => {});
+  });
+  refreshPlugins();
+}
+
+function watchShutdown() {
+  if (!hasTauri) return;
+  listen("shutdown:confirm", ({ payload }) => {
+    const pending = [];
+    if (payload.in_flight > 0) pending.push(`${payload.in_flight} change(s) still sending`);
+    if (payload.undo_window > 0) pending.push(`${payload.undo_window} decision(s) in their undo window`);
+    const ok = window.confirm(
+      `${pending.join(" and ")}. Quit anyway? Approvals are kept and sent on next launch; rejections are sent now.`
+    );
+    if (ok) invoke("confirm_close").catch(() => {});
+  });
+  listen("shutdown:draining", () => {
+    setActionStatus("Finishing pending changes before quitting", "neutral");
+  });
+}
+
+async function undoPlan(planId, execute = false, markOnly = true) {
+  const send = () =>
+    coreRequest("POST", `/plans/${encodeURIComponent(planId)}/undo`, {
+      execute,
+      mark_only: markOnly,
+    });
+  try {
+    return await send();
+  } catch (err) {
+    if (!hasTauri || errorCode(err) !== "CONFIRMATION_REQUIRED") throw err;
+    await confirmProductionAction(err);
+    return send();
+  }
+}
+
 async function cancelJob(jobId) {
   return coreRequest("POST", `/jobs/${encodeURIComponent(jobId)}/cancel`, {});
 }
@@ -492,7 +2207,7 @@ function scheduleRefresh(delayMs = 150) {
     scheduledRefreshTimer = null;
     refresh().catch((err) => {
       setActionStatus("Live refresh failed", "error");
-      summaryEl.textContent = String(err?.message || err);
+      summaryEl.textContent = describeError(err);
     });
   }, Math.max(0, Number(delayMs || 0)));
 }
@@ -510,7 +2225,7 @@ async function runAction(label, fn, refreshAfter = true) {
     return out;
   } catch (err) {
     setActionStatus(`${label} failed`, "error");
-    summaryEl.textContent = String(err?.message || err);
+    summaryEl.textContent = describeError(err);
     throw err;
   } finally {
     setBusy(false);
@@ -651,10 +2366,31 @@ function summarizeJobResult(result, fallbackKind = "run") {
   return parts.join(" • ");
 }
 
+// Plan statuses from the previous render; null until the first one, so
+// plans already present at startup don't sound.
+let seenPlanStatuses = null;
+
+function playAlertSound(soundClass) {
+  if (!hasTauri) return;
+  invoke("play_alert_sound", { class: soundClass }).catch(() => {});
+}
+
+function soundPlanChanges(items) {
+  const next = new Map(items.map((plan) => [plan.id, String(plan.status || "").toLowerCase()]));
+  if (seenPlanStatuses) {
+    const statusChanged = (status) =>
+      [...next].some(([id, s]) => s === status && seenPlanStatuses.get(id) !== status);
+    if (statusChanged("failed")) playAlertSound("plan_failed");
+    else if (statusChanged("pending")) playAlertSound("new_plan");
+  }
+  seenPlanStatuses = next;
+}
+
 function renderPlans(plans) {
   const items = sortPlans(Array.isArray(plans) ? plans : []);
   const pending = items.filter((item) => String(item.status || "").toLowerCase() === "pending");
   planCountEl.textContent = String(pending.length);
+  soundPlanChanges(items);
 
   if (!items.length) {
     plansEl.innerHTML = "<p>No plans available.</p>";
@@ -668,6 +2404,12 @@ function renderPlans(plans) {
     const repairSummary = summarizeRepair(plan.repair, plan.execution_results);
     const collaborationSummary = summarizeCollaboration(plan.vote_summary, plan.collaboration, plan.strategy);
     const transcriptLines = transcriptPreviewLines(plan.collaboration);
+    const deadline = status === "pending" ? planDeadlines.get(plan.id) : null;
+    const deadlineSummary = deadline
+      ? deadline.expired
+        ? "expired locally"
+        : `${Math.ceil(Number(deadline.remaining_seconds || 0) / 60)} min left (${deadline.level || "normal"})`
+      : "";
     const card = document.createElement("article");
     card.className = "plan";
     card.innerHTML = `
@@ -682,24 +2424,88 @@ function renderPlans(plans) {
         • Progress: ${Number(plan.progress_completed || 0)}/${Number(plan.progress_total || 0)}
       </p>
       ${plan.execution_error ? `<p class="plan-meta">Error: ${escapeHTML(plan.execution_error)}</p>` : ""}
+      ${deadlineSummary ? `<p class="plan-meta">Deadline: ${escapeHTML(deadlineSummary)}</p>` : ""}
       ${repairSummary ? `<p class="plan-meta">Repair: ${escapeHTML(repairSummary)}</p>` : ""}
       ${collaborationSummary ? `<p class="plan-meta">Collab: ${escapeHTML(collaborationSummary)}</p>` : ""}
       ${transcriptLines.length ? `<div class="plan-meta">${transcriptLines.map((line) => `• ${escapeHTML(line)}`).join("<br />")}</div>` : ""}
+      ${planTimelines.has(plan.id) ? renderTimeline(planTimelines.get(plan.id)) : ""}
       <div class="row"></div>
     `;
 
     const actionRow = card.querySelector(".row");
-    if (status === "pending") {
+    const scheduled = scheduledDecisions.get(plan.id);
+    if (status === "pending" && scheduled) {
+      actionRow.appendChild(
+        actionButton(`Undo ${scheduled.decision} (${scheduled.remaining}s)`, "danger", async () => {
+          await runAction("Cancelling decision", () => cancelPlanDecision(plan.id));
+        }),
+      );
+    } else if (status === "pending") {
       actionRow.appendChild(
         actionButton("Approve + Execute", "secondary", async () => {
-          await runAction("Approving plan", () => approvePlan(plan.id, { execute: true, ...buildRepairOptions() }));
+          if (!(await confirmNoSecrets(plan.id))) return;
+          await runAction("Approving plan", () =>
+            decidePlan(plan.id, "approve", { execute: true, ...buildRepairOptions() }, planVersion(plan)),
+          );
         }),
       );
+      if (hasTauri) {
+        actionRow.appendChild(
+          actionButton("Approve Step-by-Step", "secondary", async () => {
+            if (!(await confirmNoSecrets(plan.id))) return;
+            const out = await runAction("Approving plan step by step", () =>
+              invoke("approve_plan_step_gated", { planId: plan.id, version: planVersion(plan) }),
+            );
+            stepGates.set(plan.id, out.gate);
+          }),
+        );
+      }
       actionRow.appendChild(
         actionButton("Reject", "danger", async () => {
           const reason = window.prompt("Reject reason", "Operator rejected");
           if (!reason) return;
-          await runAction("Rejecting plan", () => rejectPlan(plan.id, reason));
+          await runAction("Rejecting plan", () => decidePlan(plan.id, "reject", { reason }, planVersion(plan)));
+        }),
+      );
+    }
+
+    const gate = stepGates.get(plan.id);
+    if (hasTauri && gate?.awaiting_step) {
+      actionRow.appendChild(
+        actionButton(`Approve Step ${gate.position || gate.awaiting_step}`, "secondary", async () => {
+          await runAction("Approving step", () => invoke("approve_step", { planId: plan.id, stepId: gate.awaiting_step }));
+          gate.awaiting_step = null;
+        }),
+      );
+    }
+
+    const stalled = stalledPlans.get(plan.id);
+    if (hasTauri && stalled) {
+      actionRow.appendChild(
+        actionButton(`Retry Step ${stalled.step}`, "secondary", async () => {
+          await runAction("Retrying stalled step", () => invoke("retry_step", { planId: plan.id, step: stalled.step }));
+          stalledPlans.delete(plan.id);
+        }),
+      );
+    }
+    if (hasTauri && (stalled || ["executing", "running", "in_progress"].includes(status))) {
+      actionRow.appendChild(
+        actionButton("Abort Execution", "danger", async () => {
+          const aborted = await confirmAbort(plan.id);
+          if (!aborted) return;
+          stalledPlans.delete(plan.id);
+          plan.status = aborted.status;
+          renderPlans(plans);
+        }),
+      );
+    }
+
+    if (hasTauri && (status === "pending" || status === "rejected")) {
+      actionRow.appendChild(
+        actionButton("Request Revision", "secondary", async () => {
+          const feedback = promptRevisionFeedback();
+          if (!feedback) return;
+          await runAction("Requesting plan revision", () => invoke("request_revision", { planId: plan.id, feedback }));
         }),
       );
     }
@@ -723,6 +2529,37 @@ function renderPlans(plans) {
       );
     }
 
+    if (hasTauri) {
+      const timelineBtn = actionButton(
+        planTimelines.has(plan.id) ? "Hide Timeline" : "Timeline",
+        "secondary",
+        async () => {
+          await runAction("Loading plan timeline", () => togglePlanTimeline(plan.id));
+        },
+      );
+      delete timelineBtn.dataset.mutate;
+      actionRow.appendChild(timelineBtn);
+      // A plain-sentence narrative, announced through the summary's live region.
+      const narrativeBtn = actionButton("Read Summary", "secondary", async () => {
+        const out = await runAction(
+          "Summarizing plan",
+          () => invoke("summarize_plan_for_a11y", { planId: plan.id }),
+          false,
+        );
+        summaryEl.textContent = out.text;
+        summaryEl.focus?.();
+      });
+      delete narrativeBtn.dataset.mutate;
+      actionRow.appendChild(narrativeBtn);
+      const watching = watchedPlans.has(plan.id);
+      const watchBtn = actionButton(watching ? "Unwatch" : "Watch", "secondary", async () => {
+        await runAction(watching ? "Unwatching plan" : "Watching plan", () => togglePlanWatch(plan.id), false);
+        renderPlans(plans);
+      });
+      delete watchBtn.dataset.mutate;
+      actionRow.appendChild(watchBtn);
+    }
+
     if (actionLogCount > 0) {
       actionRow.appendChild(
         actionButton("Undo (Mark Only)", "secondary", async () => {
@@ -742,6 +2579,57 @@ function renderPlans(plans) {
   }
 }
 
+async function togglePlanTimeline(planId) {
+  if (planTimelines.has(planId)) {
+    planTimelines.delete(planId);
+    return null;
+  }
+  const timeline = await invoke("get_plan_timeline", { planId });
+  planTimelines.set(planId, timeline);
+  return timeline;
+}
+
+function formatDuration(ms) {
+  if (ms === null || ms === undefined) return "";
+  const seconds = Math.round(Number(ms) / 1000);
+  if (seconds < 60) return `${seconds}s`;
+  const minutes = Math.floor(seconds / 60);
+  if (minutes < 60) return `${minutes}m ${seconds % 60}s`;
+  return `${Math.floor(minutes / 60)}h ${minutes % 60}m`;
+}
+
+function renderTimeline(timeline) {
+  const entries = Array.isArray(timeline?.entries) ? timeline.entries : [];
+  if (!entries.length) return `<p class="plan-meta">No timeline events recorded.</p>`;
+  const durations = timeline.durations || {};
+  const totals = [
+    ["Review", durations.review_ms],
+    ["Queued", durations.queued_ms],
+    ["Execution", durations.execution_ms],
+    ["Total", durations.total_ms],
+  ]
+    .filter(([, ms]) => ms !== null && ms !== undefined)
+    .map(([label, ms]) => `${label}: ${formatDuration(ms)}`)
+    .join(" • ");
+  return `
+    <ol class="plan-timeline">
+      ${entries
+        .map(
+          (entry) => `
+        <li class="timeline-${escapeHTML(entry.stage)}">
+          <span class="status">${escapeHTML(entry.stage)}</span>
+          ${escapeHTML(entry.label)}
+          <span class="plan-id">${escapeHTML(formatTimestamp(entry.at))}${
+            entry.duration_ms !== null ? ` • ${escapeHTML(formatDuration(entry.duration_ms))}` : ""
+          }</span>
+        </li>`,
+        )
+        .join("")}
+    </ol>
+    ${totals ? `<p class="plan-meta">${escapeHTML(totals)}</p>` : ""}
+  `;
+}
+
 function renderJobs(jobs) {
   const items = Array.isArray(jobs) ? jobs : [];
   const active = items.filter((item) => {
@@ -899,7 +2787,7 @@ function renderGovernance(governance) {
 
 async function refreshGovernance() {
   saveConfig();
-  const result = await coreRequest("GET", "/runtime/governance");
+  const result = await cachedCoreGet("/runtime/governance", renderGovernance);
   renderGovernance(result);
   return result;
 }
@@ -954,6 +2842,55 @@ async function cancelAllJobs() {
   return result;
 }
 
+// Deliberately not routed through runAction/setBusy: the kill switch must stay
+// clickable while other actions are in flight.
+async function emergencyHalt() {
+  if (!hasTauri) {
+    return cancelAllJobs();
+  }
+  const challenge = await invoke("emergency_halt", { scope: "stop_all" });
+  const typed = window.prompt(
+    `Stop all agents and pause the runtime now?\nType ${challenge.confirmation} to confirm.`
+  );
+  if (!typed) return null;
+  setActionStatus("Emergency halt", "neutral");
+  try {
+    const result = await invoke("emergency_halt", { scope: "stop_all", confirmation: typed });
+    setActionStatus("Emergency halt sent", "ok");
+    if (result?.core?.governance) {
+      renderGovernance(result.core.governance);
+    }
+    return result;
+  } catch (err) {
+    setActionStatus("Emergency halt failed", "error");
+    summaryEl.textContent = describeError(err);
+    throw err;
+  }
+}
+
+// Offboarding a shared workstation: the shell restarts itself once done.
+async function wipeLocalData() {
+  if (!hasTauri) {
+    localStorage.clear();
+    window.location.reload();
+    return null;
+  }
+  const challenge = await invoke("wipe_local_data");
+  const typed = window.prompt(
+    `Delete every token, setting, cached item and audit entry on this device?\nType ${challenge.confirmation} to confirm.`
+  );
+  if (!typed) return null;
+  try {
+    const result = await invoke("wipe_local_data", { confirmation: typed });
+    setActionStatus("Local data wiped; restarting", "ok");
+    return result;
+  } catch (err) {
+    setActionStatus("Wipe failed", "error");
+    summaryEl.textContent = describeError(err);
+    throw err;
+  }
+}
+
 function quickActionsForEntity(entity) {
   const entityId = String(entity?.entity_id || "");
   const domain = entityId.split(".", 1)[0] || "";
@@ -1176,9 +3113,12 @@ function formatMobileRuntimeStatus(payload) {
 
 async function refreshMobileStatus() {
   saveConfig();
-  const result = await coreRequest("GET", "/mobile/status");
-  setControlStatus(result?.ok ? formatMobileRuntimeStatus(result) : "Mobile runtime unavailable", result?.ok ? "ok" : "error");
-  renderControlOutput(result, "Mobile runtime status unavailable.");
+  const render = (status) => {
+    setControlStatus(status?.ok ? formatMobileRuntimeStatus(status) : "Mobile runtime unavailable", status?.ok ? "ok" : "error");
+    renderControlOutput(status, "Mobile runtime status unavailable.");
+  };
+  const result = await cachedCoreGet("/mobile/status", render);
+  render(result);
   return result;
 }
 
@@ -1272,6 +3212,7 @@ function attachTerminalSession(sessionId, options = {}) {
   const normalized = String(sessionId || "").trim();
   terminalActiveSessionId = normalized;
   terminalNextSeq = Number(options.nextSeq || 0);
+  terminalRaw = "";
   stopTerminalPolling();
   if (!normalized) {
     renderTerminalOutput("No terminal session attached.");
@@ -1281,7 +3222,7 @@ function attachTerminalSession(sessionId, options = {}) {
   const sessionLabel = String(options.command || normalized);
   setTerminalStatus(`Attached ${sessionLabel}`, "ok");
   pollTerminalOutput().catch((err) => {
-    setTerminalStatus(String(err?.message || err), "error");
+    setTerminalStatus(describeError(err), "error");
   });
 }
 
@@ -1375,13 +3316,17 @@ async function pollTerminalOutput() {
     );
     const chunks = Array.isArray(result?.chunks) ? result.chunks : [];
     if (chunks.length > 0) {
-      let existing = String(terminalOutputEl?.textContent || "");
-      if (existing === "No terminal session attached.") existing = "";
       for (const chunk of chunks) {
         terminalNextSeq = Math.max(terminalNextSeq, Number(chunk?.seq || terminalNextSeq));
-        existing += String(chunk?.data || "");
+        terminalRaw += String(chunk?.data || "");
+      }
+      terminalRaw = terminalRaw.slice(-TERMINAL_MAX_CHARS);
+      if (hasTauri) {
+        const { spans } = await invoke("parse_ansi", { text: terminalRaw });
+        renderSpans(terminalOutputEl, appendSpans([], spans, TERMINAL_MAX_CHARS), "No terminal output yet.");
+      } else {
+        renderTerminalOutput(terminalRaw || "No terminal output yet.");
       }
-      renderTerminalOutput(existing || "No terminal output yet.");
       if (terminalOutputEl) terminalOutputEl.scrollTop = terminalOutputEl.scrollHeight;
     }
     const open = Boolean(result?.open);
@@ -1390,7 +3335,7 @@ async function pollTerminalOutput() {
       terminalPollTimer = window.setTimeout(() => {
         terminalPollTimer = null;
         pollTerminalOutput().catch((err) => {
-          setTerminalStatus(String(err?.message || err), "error");
+          setTerminalStatus(describeError(err), "error");
         });
       }, 300);
       setTerminalStatus("Streaming", "ok");
@@ -1491,6 +3436,7 @@ function renderTemplateLibrary(payload) {
               context: "desktop-tauri",
             });
             renderTemplateOutput(result, "Template plan launch complete.");
+            recordRecent("agent", template.template_id, template.name);
             await refresh();
           }, true);
         },
@@ -1508,6 +3454,7 @@ function renderTemplateLibrary(payload) {
               context: "desktop-tauri",
             });
             renderTemplateOutput(result, "Template run launch complete.");
+            recordRecent("agent", template.template_id, template.name);
             await refresh();
           }, true);
         },
@@ -1523,9 +3470,9 @@ function renderTemplateLibrary(payload) {
             });
             renderTemplateOutput(result, "Template share metadata ready.");
             const shareUrl = result?.share?.share_url || result?.share?.share_uri || result?.share?.share_path;
-            if (shareUrl && navigator.clipboard?.writeText) {
+            if (shareUrl) {
               try {
-                await navigator.clipboard.writeText(String(shareUrl));
+                await copyText(String(shareUrl));
               } catch {
                 // clipboard failure is non-fatal
               }
@@ -1674,11 +3621,15 @@ async function refresh() {
   saveConfig();
   refreshInFlight = true;
   try {
-    const data = await dashboardData();
-    render(data);
+    if (hasTauri) {
+      // The backend state store renders through `state:dashboard`.
+      await invoke("refresh_state");
+    } else {
+      render(await dashboardData());
+    }
     setConnectionStatus("Connected", "ok");
   } catch (error) {
-    setConnectionStatus("Connection failed", "error");
+    markConnectionFailed();
     throw error;
   } finally {
     refreshInFlight = false;
@@ -1687,13 +3638,35 @@ async function refresh() {
       window.setTimeout(() => {
         refresh().catch((err) => {
           setActionStatus("Refresh failed", "error");
-          summaryEl.textContent = String(err?.message || err);
+          summaryEl.textContent = describeError(err);
         });
       }, 25);
     }
   }
 }
 
+function markConnectionFailed() {
+  if (connectionStatusEl.textContent === "Connected" && !coreMaintenance) {
+    playAlertSound("core_down");
+  }
+  setConnectionStatus("Connection failed", "error");
+}
+
+// With Tauri, the backend polls once for every window and pushes changes;
+// the webview only subscribes.
+function watchState() {
+  if (!hasTauri) return;
+  listen("state:dashboard", ({ payload }) => {
+    render(payload.data || {});
+    setConnectionStatus("Connected", "ok");
+  });
+  listen("state:error", ({ payload }) => {
+    if (coreMaintenance) return;
+    markConnectionFailed();
+    summaryEl.textContent = describeError(payload.error);
+  });
+}
+
 async function testConnection() {
   await coreRequest("GET", "/health");
   setConnectionStatus("Connected", "ok");
@@ -1704,11 +3677,17 @@ function startAutoRefresh() {
     window.clearInterval(refreshTimer);
     refreshTimer = null;
   }
+  if (hasTauri) {
+    const command = autoRefreshInput.checked ? "subscribe_state" : "unsubscribe_state";
+    invoke(command, { intervalMs: DEFAULT_REFRESH_INTERVAL_MS }).catch(() => {});
+    return;
+  }
   if (!autoRefreshInput.checked) return;
   refreshTimer = window.setInterval(() => {
+    if (coreMaintenance) return;
     refresh().catch((err) => {
       setActionStatus("Auto refresh failed", "error");
-      summaryEl.textContent = String(err?.message || err);
+      summaryEl.textContent = describeError(err);
     });
   }, DEFAULT_REFRESH_INTERVAL_MS);
 }
@@ -1763,9 +3742,39 @@ function stopLivePolling() {
   }
 }
 
+function watchLiveStream() {
+  if (!hasTauri) return;
+  listen("live:event", ({ payload }) => {
+    const item = payload.data || {};
+    liveState.lastAuditId = extractMaxAuditId([item]);
+    const category = String(item.category || "audit");
+    const action = String(item.action || "update");
+    setLiveStatus(`Live • ${category}:${action}`, "ok");
+    scheduleRefresh(80);
+  });
+  listen("live:status", ({ payload }) => {
+    liveState.connected = payload.state === "connected";
+    if (payload.state === "connected") {
+      setLiveStatus("Live • watching audit feed", "ok");
+    } else if (payload.state === "reconnecting") {
+      const seconds = Math.ceil((payload.retry_in_ms || 0) / 1000);
+      setLiveStatus(`Live reconnecting in ${seconds}s • ${payload.error}`, "error");
+    } else if (payload.state === "stale") {
+      setLiveStatus("Live • quiet stream, reopening", "neutral");
+    }
+  });
+}
+
 function syncLivePolling() {
   updateLiveButton();
   saveConfig();
+  if (hasTauri) {
+    const command = liveState.enabled ? "start_live_events" : "stop_live_events";
+    const args = liveState.enabled ? { sinceId: liveState.lastAuditId } : {};
+    if (!liveState.enabled) setLiveStatus("Live idle", "neutral");
+    invoke(command, args).catch((err) => setLiveStatus(describeError(err), "error"));
+    return;
+  }
   if (!liveState.enabled) {
     stopLivePolling();
     setLiveStatus("Live idle", "neutral");
@@ -1774,7 +3783,7 @@ function syncLivePolling() {
   stopLivePolling();
   setLiveStatus("Live starting", "neutral");
   pollLiveEvents().catch((err) => {
-    setLiveStatus(String(err?.message || err), "error");
+    setLiveStatus(describeError(err), "error");
   });
 }
 
@@ -1821,11 +3830,12 @@ baseUrlInput.addEventListener("change", () => {
     saveConfig();
   } catch (err) {
     setConnectionStatus("Invalid base URL", "error");
-    summaryEl.textContent = String(err?.message || err);
+    summaryEl.textContent = describeError(err);
   }
 });
 tokenInput.addEventListener("change", saveConfig);
 rememberTokenInput.addEventListener("change", saveConfig);
+undoWindowInput?.addEventListener("change", saveConfig);
 objectiveInput.addEventListener("change", saveConfig);
 strategySelect.addEventListener("change", saveConfig);
 candidatesInput.addEventListener("change", saveConfig);
@@ -1904,6 +3914,12 @@ resetUsageBtn?.addEventListener("click", () => {
 cancelAllJobsBtn?.addEventListener("click", () => {
   runAction("Canceling all jobs", () => cancelAllJobs(), true).catch(() => {});
 });
+emergencyHaltBtn?.addEventListener("click", () => {
+  emergencyHalt().catch(() => {});
+});
+wipeLocalDataBtn?.addEventListener("click", () => {
+  wipeLocalData().catch(() => {});
+});
 refreshMqttStatusBtn?.addEventListener("click", () => {
   runAction("Refreshing MQTT status", () => refreshMQTTStatus(), false).catch(() => {});
 });
@@ -1938,11 +3954,42 @@ importTemplateBtn?.addEventListener("click", () => {
   runAction("Importing template manifest", () => importTemplateManifest(), false).catch(() => {});
 });
 
+await hydratePrefs().catch((err) => console.warn("Loading settings failed", err));
 loadConfig();
 setActionStatus("Idle", "neutral");
 setConnectionStatus("Not connected", "neutral");
 updateLiveButton();
 setLiveStatus(liveState.enabled ? "Live waiting for first event" : "Live idle", "neutral");
+watchScheduledDecisions();
+watchTokenExpiry();
+watchMaintenance();
+watchOperatorActivity();
+watchSafeMode();
+watchManagedPolicy();
+watchEnvironment();
+watchAllowlist();
+watchClipboardGuard();
+watchSecondApprovers();
+watchAirgap();
+watchCookieJar();
+watchNotificationActions();
+watchPlanWatches();
+watchStalledPlans();
+watchStepGates();
+watchPlanComparison();
+watchRequestHistory();
+watchResponseCache();
+watchSetup();
+watchConfigCheck();
+watchSlowRequests();
+watchEndpointChanges();
+watchLiveStream();
+watchPlanOutput();
+watchPlanHistory();
+watchQuickActions();
+watchPlugins();
+watchShutdown();
+watchState();
 setTerminalStatus("Idle", "neutral");
 setGovernanceStatus("Unknown", "neutral");
 setIoTStatus("Idle", "neutral");
@@ -1955,7 +4002,7 @@ renderTerminalOutput(null);
 refresh()
   .catch((err) => {
     setActionStatus("Initial refresh failed", "error");
-    summaryEl.textContent = String(err?.message || err);
+    summaryEl.textContent = describeError(err);
   })
   .finally(() => {
     refreshTerminalSessions().catch(() => {});